@@ -0,0 +1,150 @@
+//! GitHub Actions annotations for a build report.
+//!
+//! [`render`] turns each detected error group into a workflow command,
+//!
+//! ```text
+//! ::error file=src/api.ts,line=14,col=3,title=TypeScript Error (Build)::src/api.ts(14,3): error TS2322: ...
+//! ```
+//!
+//! which a GitHub Actions step can print to annotate the pull request's
+//! diff with the failures of the CircleCI build it mirrors.
+
+use crate::patterns::{ErrorGroup, Severity};
+use crate::report::BuildReport;
+use crate::source::locate;
+
+/// Renders a report as GitHub Actions workflow commands, one per error
+/// group.
+///
+/// Groups whose line names a file are annotated on that file, at the line
+/// and column when the log has them. Hints become `::notice`. A failed
+/// build without any detected errors gets one `::error` pointing at it.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::annotations::render;
+/// use circle_debug::report::BuildReport;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null}"#,
+/// )
+/// .unwrap();
+/// let report = BuildReport::new("org", "repo", &build);
+/// assert_eq!(
+///     render(&report),
+///     "::error title=CircleCI build #7 failed::No known error pattern matched; see the build's logs\n"
+/// );
+/// ```
+pub fn render(report: &BuildReport) -> String {
+    let mut out = String::new();
+    for action in &report.failed_actions {
+        for group in &action.errors {
+            out.push_str(&annotation(group, &action.step));
+            out.push('\n');
+        }
+    }
+
+    let failed = ["failed", "infrastructure_fail", "timedout"].contains(&report.status.as_str());
+    if out.is_empty() && failed {
+        let link = report
+            .build_url
+            .as_deref()
+            .map(|url| format!(" at {}", url))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "::error title={}::{}\n",
+            escape_property(&format!("CircleCI build #{} failed", report.build_num)),
+            escape_data(&format!(
+                "No known error pattern matched; see the build's logs{}",
+                link
+            ))
+        ));
+    }
+    out
+}
+
+/// Formats one error group as a workflow command.
+fn annotation(group: &ErrorGroup, step: &str) -> String {
+    let command = match group.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Hint => "notice",
+    };
+    let line = group.first.line.trim();
+
+    let mut properties = Vec::new();
+    if let Some(location) = locate(line) {
+        properties.push(format!("file={}", escape_property(&location.file)));
+        if let Some(number) = location.line {
+            properties.push(format!("line={}", number));
+        }
+        if let Some(col) = location.col {
+            properties.push(format!("col={}", col));
+        }
+    }
+    properties.push(format!(
+        "title={}",
+        escape_property(&format!("{} ({})", group.category, step))
+    ));
+
+    let message = match group.count() {
+        1 => line.to_string(),
+        n => format!("{} ({} occurrences)", line, n),
+    };
+    format!(
+        "::{} {}::{}",
+        command,
+        properties.join(","),
+        escape_data(&message)
+    )
+}
+
+/// Escapes a workflow command's message.
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a workflow command's property value, which also can't contain
+/// the `:` and `,` separators.
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{BuildBuilder, FailedActionBuilder};
+
+    #[test]
+    fn test_render_annotations() {
+        let log = "\
+/home/circleci/project/src/api/client.ts(14,3): error TS2322: Type 'string' is not assignable to type 'number'.
+";
+        let mut report = BuildReport::new("org", "repo", &BuildBuilder::new(9).build());
+        report
+            .failed_actions
+            .push(FailedActionBuilder::new("Type check").log(log).build());
+
+        let rendered = render(&report);
+        let first = rendered.lines().next().unwrap();
+        assert!(
+            first.starts_with("::error file=src/api/client.ts,line=14,col=3,title="),
+            "{}",
+            first
+        );
+        assert!(first.ends_with(
+            "::/home/circleci/project/src/api/client.ts(14,3): error TS2322: Type 'string' is not assignable to type 'number'."
+        ));
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(escape_data("100%\nnext"), "100%25%0Anext");
+        assert_eq!(escape_property("a: b, c"), "a%3A b%2C c");
+    }
+}
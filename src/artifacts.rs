@@ -0,0 +1,382 @@
+//! Build artifacts: listing what a build stored, downloading it, and
+//! comparing two builds' artifacts.
+//!
+//! Artifacts are served from `*.circle-artifacts.com`, which needs the API
+//! token for private projects, so downloads go through the same
+//! [`download`](crate::download) path as logs.
+//!
+//! The artifacts API lists only paths and URLs; sizes and hashes come from
+//! a `HEAD` request per artifact (`Content-Length` and `ETag`, which for
+//! files stored in one part is their MD5).
+
+use crate::codeowners::pattern_regex;
+use crate::download::{decompress, is_circleci_url};
+use crate::{v1_vcs, CircleClient, CircleDebugError};
+use anyhow::{Context, Result};
+use regex::Regex;
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A file a build stored with `store_artifacts`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Artifact {
+    /// Path under the artifacts root, e.g. `coverage/lcov.info`.
+    pub path: String,
+    /// Parallel container that stored it.
+    #[serde(default)]
+    pub node_index: u32,
+    /// Where to download it.
+    pub url: String,
+}
+
+/// An artifact with the size and hash its server reports.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArtifactInfo {
+    /// Path under the artifacts root.
+    pub path: String,
+    /// Parallel container that stored it.
+    pub node_index: u32,
+    /// Size in bytes, if the server reported it.
+    pub size: Option<u64>,
+    /// The server's `ETag`, without quotes.
+    pub hash: Option<String>,
+}
+
+/// How one artifact differs between two builds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum ArtifactChange {
+    /// Only in the newer build.
+    Added { artifact: ArtifactInfo },
+    /// Only in the older build.
+    Removed { artifact: ArtifactInfo },
+    /// In both, with a different size or hash.
+    Changed {
+        old: ArtifactInfo,
+        new: ArtifactInfo,
+    },
+}
+
+impl ArtifactChange {
+    /// Returns the artifact's path.
+    pub fn path(&self) -> &str {
+        match self {
+            ArtifactChange::Added { artifact } | ArtifactChange::Removed { artifact } => {
+                &artifact.path
+            }
+            ArtifactChange::Changed { new, .. } => &new.path,
+        }
+    }
+
+    /// Returns the size change in bytes, when both sizes are known.
+    pub fn size_delta(&self) -> Option<i64> {
+        match self {
+            ArtifactChange::Added { artifact } => artifact.size.map(|size| size as i64),
+            ArtifactChange::Removed { artifact } => artifact.size.map(|size| -(size as i64)),
+            ArtifactChange::Changed { old, new } => Some(new.size? as i64 - old.size? as i64),
+        }
+    }
+
+    /// Returns how much a changed artifact grew, in percent of its old
+    /// size.
+    pub fn growth_percent(&self) -> Option<f64> {
+        match self {
+            ArtifactChange::Changed { old, .. } => {
+                let old_size = old.size.filter(|size| *size > 0)?;
+                Some(self.size_delta()? as f64 * 100.0 / old_size as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The differences between two builds' artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ArtifactDiff {
+    /// Added, removed, and changed artifacts, by path.
+    pub changes: Vec<ArtifactChange>,
+    /// Artifacts with the same size and hash in both builds.
+    pub unchanged: usize,
+    /// Total size of the older build's artifacts, counting those with a
+    /// known size.
+    pub old_total: u64,
+    /// Total size of the newer build's artifacts, likewise.
+    pub new_total: u64,
+}
+
+/// Compares two builds' artifacts, matching them by container and path.
+///
+/// An artifact changed if its size differs, or both builds have a hash
+/// and the hashes differ.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::artifacts::{diff, ArtifactInfo};
+///
+/// let info = |path: &str, size| ArtifactInfo {
+///     path: path.to_string(),
+///     node_index: 0,
+///     size: Some(size),
+///     hash: None,
+/// };
+/// let result = diff(&[info("dist/app.js", 100)], &[info("dist/app.js", 120), info("dist/new.js", 5)]);
+/// assert_eq!(result.changes.len(), 2);
+/// assert_eq!(result.changes[0].growth_percent(), Some(20.0));
+/// ```
+pub fn diff(old: &[ArtifactInfo], new: &[ArtifactInfo]) -> ArtifactDiff {
+    let key = |artifact: &ArtifactInfo| (artifact.path.clone(), artifact.node_index);
+    let mut old_by_key: BTreeMap<_, &ArtifactInfo> = old.iter().map(|a| (key(a), a)).collect();
+    let mut result = ArtifactDiff {
+        old_total: old.iter().filter_map(|a| a.size).sum(),
+        new_total: new.iter().filter_map(|a| a.size).sum(),
+        ..Default::default()
+    };
+
+    for artifact in new {
+        match old_by_key.remove(&key(artifact)) {
+            None => result.changes.push(ArtifactChange::Added {
+                artifact: artifact.clone(),
+            }),
+            Some(previous) => {
+                let hash_changed = matches!(
+                    (&previous.hash, &artifact.hash),
+                    (Some(a), Some(b)) if a != b
+                );
+                if previous.size != artifact.size || hash_changed {
+                    result.changes.push(ArtifactChange::Changed {
+                        old: previous.clone(),
+                        new: artifact.clone(),
+                    });
+                } else {
+                    result.unchanged += 1;
+                }
+            }
+        }
+    }
+    result.changes.extend(
+        old_by_key
+            .into_values()
+            .map(|artifact| ArtifactChange::Removed {
+                artifact: artifact.clone(),
+            }),
+    );
+    result.changes.sort_by(|a, b| a.path().cmp(b.path()));
+    result
+}
+
+impl CircleClient {
+    /// Lists the artifacts of a build, from every container.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the response cannot be
+    /// parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// for artifact in client.get_artifacts("myorg", "myrepo", 12345).await? {
+    ///     println!("{} {}", artifact.node_index, artifact.path);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_artifacts(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+    ) -> Result<Vec<Artifact>> {
+        let url = format!(
+            "{}/v1.1/project/{}/{}/{}/{}/artifacts",
+            self.base_url,
+            v1_vcs(org),
+            org,
+            project,
+            build_num
+        );
+        self.get_json(&url)
+            .await
+            .with_context(|| format!("Failed to list artifacts of build #{}", build_num))
+    }
+
+    /// Downloads an artifact, decompressing it if it was stored gzip- or
+    /// zstd-compressed, e.g. a `.log.gz`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the download fails or the body is corrupt.
+    pub async fn get_artifact(&self, artifact: &Artifact) -> Result<Vec<u8>> {
+        let (body, _) = self
+            .download(&artifact.url, None, |_, _| {})
+            .await
+            .with_context(|| format!("Failed to download artifact {}", artifact.path))?;
+        decompress(body)
+    }
+
+    /// Looks up an artifact's size and hash with a `HEAD` request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server answers with
+    /// an error status.
+    pub async fn artifact_info(&self, artifact: &Artifact) -> Result<ArtifactInfo> {
+        let _permit = self.request_permit().await;
+        let mut request = self.client.head(&artifact.url);
+        if is_circleci_url(&artifact.url) || artifact.url.starts_with(&self.base_url) {
+            request = request.header("Circle-Token", &self.token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(CircleDebugError::from)
+            .with_context(|| format!("Failed to look up artifact {}", artifact.path))?;
+        if !response.status().is_success() {
+            return Err(CircleDebugError::from_status(
+                response.status(),
+                &artifact.url,
+                String::new(),
+            ))
+            .with_context(|| format!("Failed to look up artifact {}", artifact.path));
+        }
+        let headers = response.headers();
+        let value = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &header::HeaderValue| v.to_str().ok())
+        };
+        Ok(ArtifactInfo {
+            path: artifact.path.clone(),
+            node_index: artifact.node_index,
+            size: value(header::CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+            hash: value(header::ETAG)
+                .map(|v| v.trim_start_matches("W/").trim_matches('"').to_string()),
+        })
+    }
+
+    /// Lists a build's artifacts matching `glob`, with their sizes and
+    /// hashes.
+    ///
+    /// `glob` is gitignore-style, as in CODEOWNERS: `dist/**`, `*.js`.
+    /// Artifacts whose `HEAD` request fails are listed without a size or
+    /// hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the glob is invalid or the artifacts cannot be
+    /// listed.
+    pub async fn artifact_infos(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+        glob: Option<&str>,
+    ) -> Result<Vec<ArtifactInfo>> {
+        let glob: Option<Regex> = glob.map(pattern_regex).transpose()?;
+        let artifacts: Vec<Artifact> = self
+            .get_artifacts(org, project, build_num)
+            .await?
+            .into_iter()
+            .filter(|artifact| glob.as_ref().is_none_or(|re| re.is_match(&artifact.path)))
+            .collect();
+        let infos = futures::future::join_all(
+            artifacts
+                .iter()
+                .map(|artifact| self.artifact_info(artifact)),
+        )
+        .await;
+        Ok(artifacts
+            .iter()
+            .zip(infos)
+            .map(|(artifact, info)| {
+                info.unwrap_or_else(|e| {
+                    tracing::debug!(path = %artifact.path, "{:#}", e);
+                    ArtifactInfo {
+                        path: artifact.path.clone(),
+                        node_index: artifact.node_index,
+                        size: None,
+                        hash: None,
+                    }
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+
+    #[tokio::test]
+    async fn test_list_and_download_artifacts() {
+        let server = FakeCircleCi::start().await;
+        server
+            .mount_artifacts("org", "repo", 7, &[("coverage/lcov.info", "LF:10\nLH:8\n")])
+            .await;
+        let client = server.client();
+
+        let artifacts = client.get_artifacts("org", "repo", 7).await.unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, "coverage/lcov.info");
+        let body = client.get_artifact(&artifacts[0]).await.unwrap();
+        assert_eq!(body, b"LF:10\nLH:8\n");
+
+        let infos = client
+            .artifact_infos("org", "repo", 7, Some("coverage/**"))
+            .await
+            .unwrap();
+        assert_eq!(infos[0].size, Some(11));
+        assert!(client
+            .artifact_infos("org", "repo", 7, Some("dist/**"))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_diff_artifacts() {
+        let info = |path: &str, size, hash: &str| ArtifactInfo {
+            path: path.to_string(),
+            node_index: 0,
+            size: Some(size),
+            hash: Some(hash.to_string()),
+        };
+        let old = [
+            info("dist/app.js", 1000, "a"),
+            info("dist/vendor.js", 5000, "b"),
+            info("dist/old.css", 300, "c"),
+            info("dist/index.html", 200, "d"),
+        ];
+        let new = [
+            info("dist/app.js", 1000, "e"),
+            info("dist/vendor.js", 5500, "f"),
+            info("dist/new.css", 400, "g"),
+            info("dist/index.html", 200, "d"),
+        ];
+
+        let result = diff(&old, &new);
+        assert_eq!(result.unchanged, 1);
+        assert_eq!((result.old_total, result.new_total), (6500, 7100));
+        let paths: Vec<_> = result.changes.iter().map(ArtifactChange::path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "dist/app.js",
+                "dist/new.css",
+                "dist/old.css",
+                "dist/vendor.js"
+            ]
+        );
+        assert_eq!(result.changes[0].size_delta(), Some(0));
+        assert_eq!(result.changes[2].size_delta(), Some(-300));
+        assert_eq!(result.changes[3].growth_percent(), Some(10.0));
+    }
+}
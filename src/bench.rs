@@ -0,0 +1,356 @@
+//! Performance regressions reported by benchmark runs in CI.
+//!
+//! Criterion compares each benchmark with the last saved run and says so
+//! under its timing:
+//!
+//! ```text
+//! parse/large             time:   [26.029 ms 26.251 ms 26.505 ms]
+//!                         change: [+10.2% +12.4% +14.7%] (p = 0.00 < 0.05)
+//!                         Performance has regressed.
+//! ```
+//!
+//! hyperfine ends with a summary relative to one command:
+//!
+//! ```text
+//! Summary
+//!   './target/main' ran
+//!     1.34 ± 0.02 times faster than './target/pr'
+//! ```
+//!
+//! [`parse_regressions`] turns both into [`PerfRegression`]s, ranked by
+//! how much slower the benchmark got.
+
+use crate::patterns::Severity;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// A slowdown of this many percent or more is an error.
+pub const ERROR_PERCENT: f64 = 25.0;
+/// A slowdown of this many percent or more is a warning; smaller ones are
+/// hints.
+pub const WARNING_PERCENT: f64 = 10.0;
+
+/// The benchmark harness that reported a regression.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchTool {
+    Criterion,
+    Hyperfine,
+}
+
+impl BenchTool {
+    /// Returns the tool's display name.
+    pub fn label(self) -> &'static str {
+        match self {
+            BenchTool::Criterion => "Criterion",
+            BenchTool::Hyperfine => "hyperfine",
+        }
+    }
+}
+
+/// A benchmark that got slower.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PerfRegression {
+    /// Harness that measured it.
+    pub tool: BenchTool,
+    /// Benchmark ID for Criterion, the command for hyperfine.
+    pub benchmark: String,
+    /// How much slower it got, in percent.
+    pub change_percent: f64,
+    /// What it was compared with: the command hyperfine measured it
+    /// against. Criterion compares with its own saved baseline.
+    pub baseline: Option<String>,
+    /// 1-based line of the change in the log.
+    pub line_number: usize,
+    /// Error, warning, or hint, by [`ERROR_PERCENT`] and
+    /// [`WARNING_PERCENT`].
+    pub severity: Severity,
+}
+
+/// Returns the severity of a slowdown of `percent`.
+pub fn severity_for(percent: f64) -> Severity {
+    if percent >= ERROR_PERCENT {
+        Severity::Error
+    } else if percent >= WARNING_PERCENT {
+        Severity::Warning
+    } else {
+        Severity::Hint
+    }
+}
+
+/// `name  time:   [...]`, or a bare `time:` under a long name or the
+/// `change:` label of a throughput benchmark.
+fn criterion_time_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?P<name>\S.*?)?\s*\btime:\s+\[(?P<values>[^\]]+)\](?P<rest>.*)$").unwrap()
+    })
+}
+
+fn criterion_change_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*change:\s*(?:\[(?P<values>[^\]]+)\](?P<rest>.*))?$").unwrap()
+    })
+}
+
+fn p_value_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\(p = (?P<p>[\d.]+) (?P<cmp>[<>])").unwrap())
+}
+
+fn hyperfine_ran_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s+(?P<cmd>.+?) ran$").unwrap())
+}
+
+fn hyperfine_ratio_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s+(?P<ratio>[\d.]+)(?: ± (?P<err>[\d.]+))? times (?P<dir>faster|slower) than (?P<cmd>.+?)\s*$").unwrap()
+    })
+}
+
+/// Finds the benchmarks a log reports as slower, most regressed first.
+///
+/// Criterion benchmarks count when Criterion says "Performance has
+/// regressed", or, for output without the verdict, when the change is an
+/// increase with p < 0.05; the middle estimate is the change. For
+/// hyperfine, every command slower than the summary's reference command
+/// by more than the measurement error counts. Without `--reference` that
+/// is the fastest command, so the others are reported relative to it.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::bench::parse_regressions;
+///
+/// let log = "\
+/// fib 20                  time:   [26.029 us 26.251 us 26.505 us]
+///                         change: [+10.2% +12.4% +14.7%] (p = 0.00 < 0.05)
+///                         Performance has regressed.
+/// ";
+/// let regressions = parse_regressions(log);
+/// assert_eq!(regressions[0].benchmark, "fib 20");
+/// assert_eq!(regressions[0].change_percent, 12.4);
+/// ```
+pub fn parse_regressions(log: &str) -> Vec<PerfRegression> {
+    let lines: Vec<&str> = log.lines().map(|line| line.trim_end()).collect();
+    let mut regressions = criterion_regressions(&lines);
+    regressions.extend(hyperfine_regressions(&lines));
+    regressions.sort_by(|a, b| b.change_percent.total_cmp(&a.change_percent));
+    regressions
+}
+
+fn criterion_regressions(lines: &[&str]) -> Vec<PerfRegression> {
+    let mut regressions = Vec::new();
+    let mut benchmark: Option<String> = None;
+    // Set by a bare `change:` line; the `time:` line after it holds the
+    // change rather than a new measurement
+    let mut change_follows = false;
+    // The change waiting for Criterion's verdict, and whether it counts
+    // without one
+    let mut pending: Option<(PerfRegression, bool)> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let change = if change_follows {
+            change_follows = false;
+            criterion_time_regex()
+                .captures(line)
+                .filter(|caps| caps.name("name").is_none())
+                .map(|caps| (caps["values"].to_string(), caps["rest"].to_string()))
+        } else if let Some(caps) = criterion_change_regex().captures(line) {
+            match caps.name("values") {
+                Some(values) => Some((values.as_str().to_string(), caps["rest"].to_string())),
+                None => {
+                    change_follows = true;
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some((values, rest)) = change {
+            let (Some(name), Some(percent)) = (benchmark.clone(), middle_percent(&values)) else {
+                continue;
+            };
+            let significant = p_value_regex()
+                .captures(&rest)
+                .is_some_and(|caps| &caps["cmp"] == "<");
+            pending = Some((
+                PerfRegression {
+                    tool: BenchTool::Criterion,
+                    benchmark: name,
+                    change_percent: percent,
+                    baseline: None,
+                    line_number: i + 1,
+                    severity: severity_for(percent),
+                },
+                significant && percent > 0.0,
+            ));
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed == "Performance has regressed." {
+            if let Some((regression, _)) = pending.take() {
+                regressions.push(regression);
+            }
+            continue;
+        }
+        if trimmed == "Performance has improved."
+            || trimmed.starts_with("No change in performance")
+            || trimmed.starts_with("Change within noise threshold")
+        {
+            pending = None;
+            continue;
+        }
+
+        if let Some(caps) = criterion_time_regex().captures(line) {
+            flush_unjudged(&mut pending, &mut regressions);
+            if let Some(name) = caps.name("name") {
+                benchmark = Some(name.as_str().trim().to_string());
+            }
+        } else if !trimmed.is_empty()
+            && !line.starts_with(char::is_whitespace)
+            && !trimmed.starts_with("thrpt:")
+        {
+            // A name too long for Criterion's column gets a line of its own
+            // above `time:`; anything else at the margin ends the benchmark
+            flush_unjudged(&mut pending, &mut regressions);
+            benchmark = Some(trimmed.to_string());
+        }
+    }
+    flush_unjudged(&mut pending, &mut regressions);
+    regressions
+}
+
+/// Keeps a change that no verdict line followed if it's a significant
+/// increase.
+fn flush_unjudged(
+    pending: &mut Option<(PerfRegression, bool)>,
+    regressions: &mut Vec<PerfRegression>,
+) {
+    if let Some((regression, true)) = pending.take() {
+        regressions.push(regression);
+    }
+}
+
+/// Parses the middle of Criterion's `+10.2% +12.4% +14.7%` estimates.
+fn middle_percent(values: &str) -> Option<f64> {
+    let estimates: Vec<&str> = values.split_whitespace().collect();
+    let middle = estimates.get(estimates.len() / 2)?;
+    middle.trim_end_matches('%').parse().ok()
+}
+
+fn hyperfine_regressions(lines: &[&str]) -> Vec<PerfRegression> {
+    let mut regressions = Vec::new();
+    let mut reference: Option<String> = None;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim() == "Summary" {
+            reference = None;
+        } else if let Some(caps) = hyperfine_ran_regex().captures(line) {
+            reference = Some(unquote(&caps["cmd"]));
+        } else if let Some(caps) = hyperfine_ratio_regex().captures(line) {
+            let Some(reference) = &reference else {
+                continue;
+            };
+            // "Reference ran 1.3 times faster than X" means X is 30% slower
+            if &caps["dir"] != "faster" {
+                continue;
+            }
+            let ratio: f64 = caps["ratio"].parse().unwrap_or(1.0);
+            let error: f64 = caps
+                .name("err")
+                .and_then(|m| m.as_str().parse().ok())
+                .unwrap_or(0.0);
+            if ratio - error <= 1.0 {
+                continue;
+            }
+            let percent = ((ratio - 1.0) * 1000.0).round() / 10.0;
+            regressions.push(PerfRegression {
+                tool: BenchTool::Hyperfine,
+                benchmark: unquote(&caps["cmd"]),
+                change_percent: percent,
+                baseline: Some(reference.clone()),
+                line_number: i + 1,
+                severity: severity_for(percent),
+            });
+        } else if !line.starts_with(char::is_whitespace) {
+            reference = None;
+        }
+    }
+    regressions
+}
+
+/// Strips the quotes hyperfine puts around command names.
+fn unquote(command: &str) -> String {
+    command
+        .strip_prefix('\'')
+        .and_then(|c| c.strip_suffix('\''))
+        .unwrap_or(command)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_criterion_regressions() {
+        let log = "\
+Benchmarking parse/small: Analyzing
+parse/small             time:   [1.0012 ms 1.0034 ms 1.0057 ms]
+                        change: [-1.2% +0.3% +1.8%] (p = 0.68 > 0.05)
+                        No change in performance detected.
+a_benchmark_with_a_very_long_name
+                        time:   [26.029 ms 26.251 ms 26.505 ms]
+                        change: [+28.1% +30.4% +32.9%] (p = 0.00 < 0.05)
+                        Performance has regressed.
+Found 3 outliers among 100 measurements (3.00%)
+throughput/encode       time:   [4.1 us 4.2 us 4.3 us]
+                        thrpt:  [232 MiB/s 238 MiB/s 243 MiB/s]
+                 change:
+                        time:   [+10.1% +11.0% +12.2%] (p = 0.00 < 0.05)
+                        thrpt:  [-10.9% -9.9% -9.2%]
+                        Performance has regressed.
+parse/large             time:   [9.1 ms 9.2 ms 9.3 ms]
+                        change: [-8.0% -6.5% -5.1%] (p = 0.00 < 0.05)
+                        Performance has improved.
+";
+        let regressions = parse_regressions(log);
+        let found: Vec<_> = regressions
+            .iter()
+            .map(|r| (r.benchmark.as_str(), r.change_percent, r.severity))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a_benchmark_with_a_very_long_name", 30.4, Severity::Error),
+                ("throughput/encode", 11.0, Severity::Warning),
+            ]
+        );
+        assert_eq!(regressions[0].line_number, 7);
+    }
+
+    #[test]
+    fn test_hyperfine_regressions() {
+        let log = "\
+Benchmark 1: ./target/main
+  Time (mean ± σ):      1.203 s ±  0.010 s
+Summary
+  './target/main' ran
+    1.34 ± 0.02 times faster than './target/pr'
+    1.01 ± 0.03 times faster than './target/other'
+";
+        let regressions = parse_regressions(log);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].tool, BenchTool::Hyperfine);
+        assert_eq!(regressions[0].benchmark, "./target/pr");
+        assert_eq!(regressions[0].baseline.as_deref(), Some("./target/main"));
+        assert_eq!(regressions[0].change_percent, 34.0);
+        assert_eq!(regressions[0].severity, Severity::Error);
+    }
+}
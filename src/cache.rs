@@ -0,0 +1,351 @@
+//! On-disk HTTP response cache.
+//!
+//! Responses are stored by URL along with their `ETag` and `Last-Modified`
+//! validators, so later requests can be made conditional and a
+//! `304 Not Modified` answered from disk. Finished builds never change, so
+//! their metadata is marked immutable and served without a request at all.
+//!
+//! Presigned log URLs carry a fresh signature on every build fetch, so
+//! entries for hosts other than CircleCI are keyed without the query string.
+//!
+//! Logs make up most of the cache, and nothing expires on its own;
+//! [`ResponseCache::prune`] removes the oldest entries to keep it under a
+//! size. The CLI prunes to [`DEFAULT_MAX_SIZE`] unless configured otherwise.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use anyhow::Result;
+//! # fn main() -> Result<()> {
+//! use circle_debug::cache::ResponseCache;
+//! use circle_debug::CircleClient;
+//!
+//! let client = CircleClient::new()?.with_cache(ResponseCache::new("/var/cache/cdb"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::download::{is_circleci_url, redact_query};
+use anyhow::{Context, Result};
+use reqwest::header::{self, HeaderMap};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Size the CLI keeps the cache under unless configured otherwise: 1 GB.
+pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// A directory of cached responses.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+/// A cached response body and how to revalidate it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CachedResponse {
+    pub(crate) meta: CacheMeta,
+    pub(crate) body: Vec<u8>,
+}
+
+/// What [`ResponseCache::prune`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    /// Entries removed.
+    pub entries: usize,
+    /// Bytes freed, bodies and metadata together.
+    pub bytes: u64,
+    /// Bytes left in the cache.
+    pub remaining: u64,
+}
+
+/// Validators stored next to a cached body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CacheMeta {
+    /// The (redacted) URL the entry was stored for, to detect collisions.
+    pub(crate) url: String,
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    /// The resource can't change; skip revalidation.
+    #[serde(default)]
+    pub(crate) immutable: bool,
+}
+
+impl ResponseCache {
+    /// Creates a cache that stores entries in `dir`.
+    ///
+    /// The directory is created on the first write.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ResponseCache { dir: dir.into() }
+    }
+
+    /// Returns the default cache directory, see [`paths`](crate::paths).
+    pub fn default_dir() -> PathBuf {
+        crate::paths::http_cache_dir()
+    }
+
+    /// Returns the directory entries are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes every cached entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory exists but can't be removed.
+    pub fn clear(&self) -> Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to clear cache {}", self.dir.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes entries written more than `max_age` ago, then the oldest
+    /// ones until the cache takes at most `max_size` bytes.
+    ///
+    /// Entries are aged by when they were stored; revalidating one doesn't
+    /// make it newer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry can't be removed. A missing directory
+    /// has nothing to prune.
+    pub fn prune(&self, max_size: u64, max_age: Option<Duration>) -> Result<Pruned> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Ok(Pruned::default());
+        };
+        // (metadata path, size of the entry, when it was stored)
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = dir
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let meta = std::fs::metadata(&path).ok()?;
+                let body = std::fs::metadata(path.with_extension("body")).map_or(0, |m| m.len());
+                Some((path, meta.len() + body, meta.modified().ok()?))
+            })
+            .collect();
+        entries.sort_by_key(|(_, _, stored)| *stored);
+
+        let now = SystemTime::now();
+        let mut pruned = Pruned {
+            remaining: entries.iter().map(|(_, bytes, _)| bytes).sum(),
+            ..Pruned::default()
+        };
+        for (path, bytes, stored) in entries {
+            let expired = max_age
+                .is_some_and(|max_age| now.duration_since(stored).is_ok_and(|age| age > max_age));
+            if !expired && pruned.remaining <= max_size {
+                continue;
+            }
+            // Metadata first, so a reader never sees it without its body
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            let _ = std::fs::remove_file(path.with_extension("body"));
+            pruned.entries += 1;
+            pruned.bytes += bytes;
+            pruned.remaining -= bytes;
+        }
+        Ok(pruned)
+    }
+
+    /// Looks up the entry for `url`.
+    ///
+    /// Missing, unreadable, and colliding entries are all treated as misses.
+    pub(crate) fn get(&self, url: &str) -> Option<CachedResponse> {
+        let key = cache_key(url);
+        let (meta_path, body_path) = self.paths(key);
+        let meta: CacheMeta = serde_json::from_slice(&std::fs::read(meta_path).ok()?).ok()?;
+        if meta.url != key {
+            return None;
+        }
+        let body = std::fs::read(body_path).ok()?;
+        Some(CachedResponse { meta, body })
+    }
+
+    /// Returns the metadata of every entry, in no particular order.
+    ///
+    /// Unreadable entries are skipped; a missing directory has none.
+    pub(crate) fn entries(&self) -> Vec<CacheMeta> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        dir.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+        })
+        .collect()
+    }
+
+    /// Stores a response if it has validators or is `immutable`.
+    ///
+    /// Write failures are logged and otherwise ignored; the cache is an
+    /// optimization.
+    pub(crate) fn put(&self, url: &str, headers: &HeaderMap, body: &[u8], immutable: bool) {
+        let meta = CacheMeta {
+            url: cache_key(url).to_string(),
+            etag: header_value(headers, header::ETAG),
+            last_modified: header_value(headers, header::LAST_MODIFIED),
+            immutable,
+        };
+        if meta.etag.is_none() && meta.last_modified.is_none() && !immutable {
+            return;
+        }
+        if let Err(err) = self.write(&meta, body) {
+            tracing::debug!(error = %format!("{:#}", err), "failed to write cache entry");
+        }
+    }
+
+    fn write(&self, meta: &CacheMeta, body: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache {}", self.dir.display()))?;
+        let (meta_path, body_path) = self.paths(&meta.url);
+        // Body first, so a reader never sees metadata without its body
+        std::fs::write(&body_path, body)?;
+        std::fs::write(&meta_path, serde_json::to_vec(meta)?)?;
+        Ok(())
+    }
+
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        let name = format!("{:016x}", fnv1a(key.as_bytes()));
+        (
+            self.dir.join(format!("{}.json", name)),
+            self.dir.join(format!("{}.body", name)),
+        )
+    }
+}
+
+impl CachedResponse {
+    /// Adds `If-None-Match`/`If-Modified-Since` headers for this entry.
+    pub(crate) fn revalidate(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.meta.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &self.meta.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, modified);
+        }
+        request
+    }
+}
+
+/// Returns the string an entry for `url` is stored under.
+pub(crate) fn cache_key(url: &str) -> &str {
+    if is_circleci_url(url) {
+        url
+    } else {
+        redact_query(url)
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_string)
+}
+
+/// 64-bit FNV-1a, used for file names since it is stable across builds.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn temp_cache(name: &str) -> ResponseCache {
+        let dir =
+            std::env::temp_dir().join(format!("cdb-cache-test-{}-{}", name, std::process::id()));
+        let cache = ResponseCache::new(dir);
+        cache.clear().unwrap();
+        cache
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let cache = temp_cache("round-trip");
+        let url = "https://circleci.com/api/v1.1/project/github/org/repo/1";
+        assert!(cache.get(url).is_none());
+
+        // Nothing to revalidate with, so nothing is stored
+        cache.put(url, &HeaderMap::new(), b"{}", false);
+        assert!(cache.get(url).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"abc\""));
+        cache.put(url, &headers, b"{\"build_num\":1}", false);
+        let entry = cache.get(url).unwrap();
+        assert_eq!(entry.meta.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(entry.body, b"{\"build_num\":1}");
+        assert!(!entry.meta.immutable);
+
+        cache.put(url, &HeaderMap::new(), b"{\"build_num\":1}", true);
+        let entry = cache.get(url).unwrap();
+        assert!(entry.meta.immutable);
+        assert_eq!(entry.meta.etag, None);
+
+        cache.clear().unwrap();
+        assert!(cache.get(url).is_none());
+    }
+
+    #[test]
+    fn test_prune_removes_oldest_entries() {
+        let cache = temp_cache("prune");
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"log\""));
+        let urls = [
+            "https://circleci.com/output/1",
+            "https://circleci.com/output/2",
+            "https://circleci.com/output/3",
+        ];
+        for url in urls {
+            cache.put(url, &headers, &[b'x'; 1000], false);
+            // Distinct modification times, oldest first
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let total = cache.prune(u64::MAX, None).unwrap();
+        assert_eq!(total.entries, 0);
+        let entry = total.remaining / 3;
+
+        let pruned = cache.prune(2 * entry, None).unwrap();
+        assert_eq!(pruned.entries, 1);
+        assert_eq!(pruned.remaining, 2 * entry);
+        assert!(cache.get(urls[0]).is_none());
+        assert!(cache.get(urls[1]).is_some());
+
+        let pruned = cache.prune(u64::MAX, Some(Duration::ZERO)).unwrap();
+        assert_eq!(pruned.entries, 2);
+        assert_eq!(pruned.remaining, 0);
+        assert!(cache.get(urls[2]).is_none());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_presigned_urls_share_an_entry() {
+        let cache = temp_cache("presigned");
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"log\""));
+        cache.put(
+            "https://bucket.s3.amazonaws.com/output?X-Amz-Signature=one",
+            &headers,
+            b"[]",
+            false,
+        );
+
+        let entry = cache.get("https://bucket.s3.amazonaws.com/output?X-Amz-Signature=two");
+        assert_eq!(entry.unwrap().body, b"[]");
+        assert!(cache
+            .get("https://circleci.com/api/v1.1/project/github/org/repo/1?x=1")
+            .is_none());
+        cache.clear().unwrap();
+    }
+}
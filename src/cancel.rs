@@ -0,0 +1,224 @@
+//! Who or what canceled a build.
+//!
+//! The v1.1 build names the user who pressed Cancel (or whose API token
+//! canceled it) as its `canceler`. Builds stopped by auto-cancel of
+//! redundant builds have no canceler; for those the branch's later builds
+//! show which one superseded it.
+
+use crate::history::BuildQuery;
+use crate::{BuildInfo, CircleClient};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How many of the branch's recent builds to search for the one that
+/// superseded an auto-canceled build.
+const SUPERSEDED_SEARCH_LIMIT: u32 = 30;
+
+/// Why a build was canceled, and when.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Cancellation {
+    /// Who or what canceled the build.
+    pub by: CanceledBy,
+    /// When the build stopped.
+    pub canceled_at: Option<DateTime<Utc>>,
+}
+
+/// Who or what canceled a build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CanceledBy {
+    /// A user, from the UI or with their API token.
+    User {
+        /// Login name, usually the VCS username.
+        login: String,
+        /// Display name, if set.
+        name: Option<String>,
+    },
+    /// Auto-cancel of redundant builds, after a newer build of the branch
+    /// was queued.
+    Superseded {
+        /// The newer build.
+        build_num: u32,
+        /// Commit the newer build ran.
+        vcs_revision: Option<String>,
+        /// When the newer build was queued.
+        queued_at: Option<DateTime<Utc>>,
+    },
+    /// CircleCI didn't say.
+    Unknown,
+}
+
+/// The `canceler` object of a v1.1 build.
+#[derive(Debug, Deserialize)]
+struct Canceler {
+    login: String,
+    name: Option<String>,
+}
+
+impl BuildInfo {
+    /// Returns `true` if the build was canceled before it finished.
+    pub fn is_canceled(&self) -> bool {
+        self.status == "canceled"
+            || self
+                .extra
+                .get("canceled")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+    }
+}
+
+impl CircleClient {
+    /// Finds out who or what canceled `build`.
+    ///
+    /// Uses the build's canceler when it has one, and otherwise looks
+    /// for a newer build of the same job on the same branch that would have
+    /// triggered auto-cancel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the branch's builds can't be listed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::cancel::CanceledBy;
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let build = client.get_build("org", "repo", 123).await?;
+    /// if build.is_canceled() {
+    ///     if let CanceledBy::User { login, .. } = client.cancellation("org", "repo", &build).await?.by {
+    ///         println!("Canceled by {}", login);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn cancellation(
+        &self,
+        org: &str,
+        project: &str,
+        build: &BuildInfo,
+    ) -> Result<Cancellation> {
+        let canceled_at = build.stop_time;
+        if let Some(canceler) = build
+            .extra
+            .get("canceler")
+            .filter(|v| !v.is_null())
+            .and_then(|v| serde_json::from_value::<Canceler>(v.clone()).ok())
+        {
+            return Ok(Cancellation {
+                by: CanceledBy::User {
+                    login: canceler.login,
+                    name: canceler.name,
+                },
+                canceled_at,
+            });
+        }
+
+        let Some(branch) = &build.branch else {
+            return Ok(Cancellation {
+                by: CanceledBy::Unknown,
+                canceled_at,
+            });
+        };
+        let query = BuildQuery {
+            branch: Some(branch.clone()),
+            limit: SUPERSEDED_SEARCH_LIMIT,
+            ..BuildQuery::default()
+        };
+        let builds = self.recent_builds(org, project, &query).await?;
+        Ok(Cancellation {
+            by: superseded_by(build, &builds).unwrap_or(CanceledBy::Unknown),
+            canceled_at,
+        })
+    }
+}
+
+/// Picks the first later build of the same job that was queued before
+/// `build` stopped.
+fn superseded_by(build: &BuildInfo, builds: &[BuildInfo]) -> Option<CanceledBy> {
+    let job = build.workflows.as_ref().and_then(|w| w.job_name.as_deref());
+    builds
+        .iter()
+        .filter(|newer| newer.build_num > build.build_num)
+        .filter(|newer| newer.workflows.as_ref().and_then(|w| w.job_name.as_deref()) == job)
+        .filter(|newer| match (newer.queued_at, build.stop_time) {
+            (Some(queued), Some(stopped)) => queued <= stopped,
+            _ => true,
+        })
+        .min_by_key(|newer| newer.build_num)
+        .map(|newer| CanceledBy::Superseded {
+            build_num: newer.build_num,
+            vcs_revision: newer.vcs_revision.clone(),
+            queued_at: newer.queued_at,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{BuildBuilder, FakeCircleCi};
+
+    #[tokio::test]
+    async fn test_cancellation_by_user_and_auto_cancel() {
+        let server = FakeCircleCi::start().await;
+        let client = server.client();
+
+        let mut by_user = BuildBuilder::new(10).status("canceled").build();
+        by_user.extra.insert(
+            "canceler".to_string(),
+            serde_json::json!({"login": "octocat", "name": "Mona", "avatar_url": "x"}),
+        );
+        assert!(by_user.is_canceled());
+        let cancellation = client.cancellation("org", "repo", &by_user).await.unwrap();
+        assert_eq!(
+            cancellation.by,
+            CanceledBy::User {
+                login: "octocat".to_string(),
+                name: Some("Mona".to_string())
+            }
+        );
+
+        let stopped: DateTime<Utc> = "2024-05-01T12:10:00Z".parse().unwrap();
+        let mut redundant = BuildBuilder::new(20)
+            .status("canceled")
+            .workflow("ci", "test")
+            .build();
+        redundant.stop_time = Some(stopped);
+        let mut newer = BuildBuilder::new(23)
+            .running()
+            .workflow("ci", "test")
+            .revision("abc1234def")
+            .build();
+        newer.queued_at = Some("2024-05-01T12:09:58Z".parse().unwrap());
+        let other_job = BuildBuilder::new(21)
+            .running()
+            .workflow("ci", "lint")
+            .build();
+        server
+            .mount_builds("org", "repo", &[newer, other_job])
+            .await;
+
+        let cancellation = client
+            .cancellation("org", "repo", &redundant)
+            .await
+            .unwrap();
+        assert_eq!(cancellation.canceled_at, Some(stopped));
+        assert!(matches!(
+            cancellation.by,
+            CanceledBy::Superseded { build_num: 23, .. }
+        ));
+
+        // Nothing newer was queued before it stopped
+        redundant.stop_time = Some("2024-05-01T12:00:00Z".parse().unwrap());
+        let cancellation = client
+            .cancellation("org", "repo", &redundant)
+            .await
+            .unwrap();
+        assert_eq!(cancellation.by, CanceledBy::Unknown);
+    }
+}
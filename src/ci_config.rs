@@ -0,0 +1,373 @@
+//! Pipeline configuration, and what changed in it between two builds.
+//!
+//! "Did someone change the CI config?" is a common root cause that the
+//! build itself doesn't answer. [`CircleClient::build_config`] fetches the
+//! configuration a build's pipeline ran with, and [`diff`] compares two of
+//! them line by line, as a unified diff, picking out changes to
+//! executors, images, and commands.
+
+use crate::CircleClient;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Largest number of differing lines, old times new, compared line by
+/// line; beyond it the differing region is shown as removed and re-added.
+const MAX_COMPARED_CELLS: usize = 4_000_000;
+
+/// A pipeline's configuration, as returned by the v2 API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineConfig {
+    /// The `.circleci/config.yml` as committed.
+    pub source: String,
+    /// The configuration after orbs, parameters, and anchors were
+    /// expanded.
+    pub compiled: String,
+    /// The setup configuration, for dynamic configuration.
+    #[serde(default, rename = "setup-config")]
+    pub setup_config: Option<String>,
+    /// The compiled setup configuration.
+    #[serde(default, rename = "compiled-setup-config")]
+    pub compiled_setup_config: Option<String>,
+}
+
+/// Whether a line of a diff was kept, removed, or added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineChange {
+    /// In both configurations.
+    Context,
+    /// Only in the old configuration.
+    Removed,
+    /// Only in the new configuration.
+    Added,
+}
+
+/// What a changed line configures, for changes worth pointing out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notable {
+    /// An executor, resource class, or machine type.
+    Executor,
+    /// A Docker or machine image.
+    Image,
+    /// A command a step runs.
+    Command,
+}
+
+/// A line of a diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    /// Whether the line was kept, removed, or added.
+    pub change: LineChange,
+    /// The line, without its line break.
+    pub text: String,
+    /// What the line configures, for removed and added lines worth
+    /// pointing out.
+    pub notable: Option<Notable>,
+}
+
+/// A run of changes with the lines around them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hunk {
+    /// 1-based line of the old configuration the hunk starts at.
+    pub old_start: usize,
+    /// Lines of the old configuration the hunk covers.
+    pub old_lines: usize,
+    /// 1-based line of the new configuration the hunk starts at.
+    pub new_start: usize,
+    /// Lines of the new configuration the hunk covers.
+    pub new_lines: usize,
+    /// The lines, in order.
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    /// Returns the `@@ -a,b +c,d @@` header of the hunk.
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        )
+    }
+}
+
+/// Differences between two configurations.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigDiff {
+    /// The changed regions, in order; empty when the configurations are
+    /// the same.
+    pub hunks: Vec<Hunk>,
+}
+
+impl ConfigDiff {
+    /// Returns the removed and added lines that change an executor, an
+    /// image, or a command.
+    pub fn notable(&self) -> impl Iterator<Item = &DiffLine> {
+        self.hunks
+            .iter()
+            .flat_map(|hunk| hunk.lines.iter())
+            .filter(|line| line.notable.is_some())
+    }
+
+    /// Renders the diff in unified format, with `---`/`+++` headers.
+    pub fn unified(&self, old_name: &str, new_name: &str) -> String {
+        let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+        for hunk in &self.hunks {
+            out.push_str(&hunk.header());
+            out.push('\n');
+            for line in &hunk.lines {
+                let sign = match line.change {
+                    LineChange::Context => ' ',
+                    LineChange::Removed => '-',
+                    LineChange::Added => '+',
+                };
+                out.push(sign);
+                out.push_str(&line.text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// Returns what a configuration line sets, if it's an executor, image, or
+/// command.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::ci_config::{classify, Notable};
+///
+/// assert_eq!(classify("      - image: cimg/node:20.11"), Some(Notable::Image));
+/// assert_eq!(classify("    resource_class: large"), Some(Notable::Executor));
+/// assert_eq!(classify("          command: npm test"), Some(Notable::Command));
+/// assert_eq!(classify("    working_directory: ~/app"), None);
+/// ```
+pub fn classify(line: &str) -> Option<Notable> {
+    let line = line.trim_start();
+    let line = line.strip_prefix("- ").unwrap_or(line).trim_start();
+    let key = line.split_once(':')?.0.trim();
+    match key {
+        "image" => Some(Notable::Image),
+        "executor" | "resource_class" | "docker" | "machine" | "macos" | "xcode" => {
+            Some(Notable::Executor)
+        }
+        "command" | "run" | "shell" => Some(Notable::Command),
+        _ => None,
+    }
+}
+
+/// Compares two configurations line by line.
+///
+/// Hunks keep `context` unchanged lines around each change, and changes
+/// closer than twice that share a hunk, as with `diff -U`.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::ci_config::diff;
+///
+/// let old = "jobs:\n  test:\n    docker:\n      - image: cimg/node:18.20\n";
+/// let new = "jobs:\n  test:\n    docker:\n      - image: cimg/node:20.11\n";
+/// let changes = diff(old, new, 1);
+/// assert_eq!(changes.hunks[0].header(), "@@ -3,2 +3,2 @@");
+/// assert_eq!(changes.notable().count(), 2);
+/// ```
+pub fn diff(old: &str, new: &str, context: usize) -> ConfigDiff {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&old, &new);
+
+    // Indexes into `ops` of the changes, grouped into hunks
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (change, _, _))| *change != LineChange::Context)
+        .map(|(i, _)| i)
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let hunks = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let ops = &ops[start..end];
+            let (_, first_old, first_new) = ops[0];
+            // Each side has every line but those only the other side has
+            let lines_without =
+                |other: LineChange| ops.iter().filter(|(c, _, _)| *c != other).count();
+            Hunk {
+                old_start: first_old + 1,
+                old_lines: lines_without(LineChange::Added),
+                new_start: first_new + 1,
+                new_lines: lines_without(LineChange::Removed),
+                lines: ops
+                    .iter()
+                    .map(|&(change, o, n)| {
+                        let text = match change {
+                            LineChange::Added => new[n],
+                            _ => old[o],
+                        };
+                        DiffLine {
+                            change,
+                            text: text.to_string(),
+                            notable: (change != LineChange::Context)
+                                .then(|| classify(text))
+                                .flatten(),
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    ConfigDiff { hunks }
+}
+
+/// Returns the lines of both sides in diff order, each with its change and
+/// the 0-based positions in `old` and `new` it's at.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<(LineChange, usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut ops: Vec<(LineChange, usize, usize)> =
+        (0..prefix).map(|i| (LineChange::Context, i, i)).collect();
+    if a.len() * b.len() > MAX_COMPARED_CELLS {
+        ops.extend((0..a.len()).map(|i| (LineChange::Removed, prefix + i, prefix)));
+        ops.extend((0..b.len()).map(|j| (LineChange::Added, prefix + a.len(), prefix + j)));
+    } else {
+        // Longest common subsequence of the differing middle, from the end
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((LineChange::Context, prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push((LineChange::Removed, prefix + i, prefix + j));
+                i += 1;
+            } else {
+                ops.push((LineChange::Added, prefix + i, prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|k| (LineChange::Context, old_end + k, new_end + k)));
+    ops
+}
+
+impl CircleClient {
+    /// Fetches the configuration a pipeline ran with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipeline doesn't exist or the request fails.
+    pub async fn get_pipeline_config(&self, pipeline_id: &str) -> Result<PipelineConfig> {
+        let url = format!("{}/v2/pipeline/{}/config", self.base_url, pipeline_id);
+        self.get_json(&url)
+            .await
+            .with_context(|| format!("Failed to fetch the config of pipeline {}", pipeline_id))
+    }
+
+    /// Fetches the configuration of the pipeline a build ran in, with the
+    /// pipeline's number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the build has no pipeline, which is the case
+    /// for builds from before pipelines, or a request fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::ci_config::diff;
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let (_, old) = client.build_config("myorg", "myrepo", 12340).await?;
+    /// let (_, new) = client.build_config("myorg", "myrepo", 12345).await?;
+    /// print!("{}", diff(&old.compiled, &new.compiled, 3).unified("#12340", "#12345"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_config(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+    ) -> Result<(u32, PipelineConfig)> {
+        let job = self.resolve_job(org, project, build_num).await?;
+        let (Some(pipeline_id), Some(pipeline_number)) = (job.pipeline_id, job.pipeline_number)
+        else {
+            anyhow::bail!("Build #{} didn't run in a pipeline", build_num);
+        };
+        Ok((
+            pipeline_number,
+            self.get_pipeline_config(&pipeline_id).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_hunks_and_notable_changes() {
+        let old = "version: 2.1\njobs:\n  test:\n    docker:\n      - image: cimg/node:18.20\n    steps:\n      - checkout\n      - run:\n          command: npm test\n      - store_test_results:\n          path: reports\n";
+        let new = "version: 2.1\njobs:\n  test:\n    docker:\n      - image: cimg/node:20.11\n    resource_class: large\n    steps:\n      - checkout\n      - run:\n          command: npm test -- --ci\n      - store_test_results:\n          path: reports\n";
+
+        let changes = diff(old, new, 1);
+        assert_eq!(changes.hunks.len(), 2);
+        assert_eq!(changes.hunks[0].header(), "@@ -4,3 +4,4 @@");
+        let notable: Vec<(LineChange, Option<Notable>)> = changes
+            .notable()
+            .map(|line| (line.change, line.notable))
+            .collect();
+        assert_eq!(
+            notable,
+            vec![
+                (LineChange::Removed, Some(Notable::Image)),
+                (LineChange::Added, Some(Notable::Image)),
+                (LineChange::Added, Some(Notable::Executor)),
+                (LineChange::Removed, Some(Notable::Command)),
+                (LineChange::Added, Some(Notable::Command)),
+            ]
+        );
+        assert_eq!(
+            changes.unified("old", "new"),
+            "--- old\n+++ new\n\
+             @@ -4,3 +4,4 @@\n     docker:\n-      - image: cimg/node:18.20\n+      - image: cimg/node:20.11\n+    resource_class: large\n     steps:\n\
+             @@ -8,3 +9,3 @@\n       - run:\n-          command: npm test\n+          command: npm test -- --ci\n       - store_test_results:\n"
+        );
+        assert!(diff(old, old, 3).hunks.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! Reading a build URL from the system clipboard, for `--clipboard`.
+
+use anyhow::{bail, Result};
+use circle_debug::parse_circleci_url;
+use regex::Regex;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Clipboard readers to try, in order: the program and its arguments.
+fn readers() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            &["-NoProfile", "-Command", "Get-Clipboard"] as &[&str],
+        )]
+    } else {
+        vec![
+            ("wl-paste", &["--no-newline"] as &[&str]),
+            ("xclip", &["-selection", "clipboard", "-o"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    }
+}
+
+/// Returns the clipboard's text, from the first reader that's installed
+/// and succeeds.
+///
+/// # Errors
+///
+/// Returns an error naming the tools to install if none of them worked.
+pub fn read() -> Result<String> {
+    let readers = readers();
+    for (program, args) in &readers {
+        if !super::command_exists(program) {
+            continue;
+        }
+        match Command::new(program).args(*args).output() {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+            Ok(output) => {
+                tracing::debug!(program, status = %output.status, "clipboard reader failed")
+            }
+            Err(e) => tracing::debug!(program, "couldn't run clipboard reader: {}", e),
+        }
+    }
+    let names: Vec<&str> = readers.iter().map(|(program, _)| *program).collect();
+    bail!(
+        "Couldn't read the clipboard; install one of: {}",
+        names.join(", ")
+    )
+}
+
+/// Returns the first CircleCI build URL in `text`.
+///
+/// Text copied from Slack or a browser often has more around the link,
+/// e.g. `<https://circleci.com/gh/org/repo/123|build failed>`, so the URL
+/// is searched for rather than expected alone.
+pub fn find_build_url(text: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE
+        .get_or_init(|| Regex::new(r#"https://(?:app\.)?circleci\.com/[^\s<>|"'`)\]]+"#).unwrap());
+    re.find_iter(text)
+        .map(|url| url.as_str().trim_end_matches(['.', ',', ';']))
+        .find(|url| parse_circleci_url(url).is_ok())
+        .map(str::to_string)
+}
+
+/// Reads the clipboard and returns the CircleCI build URL on it.
+///
+/// # Errors
+///
+/// Returns an error if the clipboard can't be read or holds no build URL.
+pub fn build_url() -> Result<String> {
+    let text = read()?;
+    match find_build_url(&text) {
+        Some(url) => Ok(url),
+        None => bail!("No CircleCI build URL on the clipboard; copy one first"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_build_url() {
+        assert_eq!(
+            find_build_url("CI broke: <https://circleci.com/gh/org/repo/123|#123>").as_deref(),
+            Some("https://circleci.com/gh/org/repo/123")
+        );
+        assert_eq!(
+            find_build_url(
+                "see https://app.circleci.com/pipelines/github/org/repo/77/workflows/abc/jobs/456."
+            )
+            .as_deref(),
+            Some("https://app.circleci.com/pipelines/github/org/repo/77/workflows/abc/jobs/456")
+        );
+        // Links that aren't builds are skipped
+        assert_eq!(
+            find_build_url("https://circleci.com/docs/ then https://circleci.com/gh/org/repo/9")
+                .as_deref(),
+            Some("https://circleci.com/gh/org/repo/9")
+        );
+        assert_eq!(find_build_url("https://github.com/org/repo/pull/1"), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! Environment checks for `cdb doctor`.
+//!
+//! Each check reports pass, warn, or fail with a hint on how to fix it.
+//! Warnings cover things only some commands need, like the GitHub CLI for
+//! `cdb pr`; failures stop `cdb build` from working at all.
+
+use super::command_exists;
+use super::progress::Progress;
+use super::style::Themed;
+use circle_debug::config::Config;
+use circle_debug::{credentials, paths, CircleClient, CircleDebugError};
+use colored::Colorize;
+use std::path::Path;
+use std::process::Command;
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A check's outcome and what to do about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Runs every check, in the order they are printed.
+pub async fn run(config_path: Option<&Path>, progress: &Progress) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let from_env = std::env::var("CIRCLECI_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty());
+    let token = match (from_env, credentials::stored_token()) {
+        (Some(token), _) => {
+            checks.push(Check::pass("Token", "CIRCLECI_TOKEN is set"));
+            Some(token)
+        }
+        (None, Some(token)) => {
+            let path = paths::credentials_file().unwrap_or_default();
+            checks.push(Check::pass(
+                "Token",
+                format!("Stored in {}", path.display()),
+            ));
+            Some(token)
+        }
+        (None, None) => {
+            checks.push(Check::fail(
+                "Token",
+                "No token in CIRCLECI_TOKEN or saved by `cdb init`",
+                "Run `cdb init`, or create a personal API token at https://app.circleci.com/settings/user/tokens and export CIRCLECI_TOKEN",
+            ));
+            None
+        }
+    };
+    if let Some(token) = token {
+        let spinner = progress.spinner("Contacting CircleCI...");
+        let api = check_api(&token).await;
+        spinner.finish_and_clear();
+        checks.push(api);
+    }
+
+    checks.push(check_gh());
+    checks.push(check_cache_dir(&paths::cache_dir()));
+    let cwd = std::env::current_dir().unwrap_or_default();
+    checks.push(check_config(config_path, &cwd));
+    checks
+}
+
+/// Prints the checks and a summary line.
+pub fn print(checks: &[Check]) {
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in checks {
+        let mark = match check.status {
+            Status::Pass => "✓".success().bold(),
+            Status::Warn => "!".warning().bold(),
+            Status::Fail => "✗".error().bold(),
+        };
+        println!(
+            "{} {:width$}  {}",
+            mark,
+            check.name.bold(),
+            check.detail,
+            width = width
+        );
+        if let Some(hint) = &check.hint {
+            println!(
+                "  {:width$}  {} {}",
+                "",
+                "→".dim(),
+                hint.dim(),
+                width = width
+            );
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warned = checks.iter().filter(|c| c.status == Status::Warn).count();
+    println!();
+    if failed > 0 {
+        super::style::print_error(&format!(
+            "{} check{} failed",
+            failed,
+            if failed == 1 { "" } else { "s" }
+        ));
+    } else if warned > 0 {
+        super::style::print_success(&format!(
+            "Ready, with {} warning{}",
+            warned,
+            if warned == 1 { "" } else { "s" }
+        ));
+    } else {
+        super::style::print_success("Everything looks good");
+    }
+}
+
+/// Checks that the token is accepted, which also proves CircleCI is
+/// reachable.
+async fn check_api(token: &str) -> Check {
+    let user = match CircleClient::with_token(token) {
+        Ok(client) => client.me().await,
+        Err(err) => Err(err),
+    };
+    match user {
+        Ok(user) => Check::pass("CircleCI API", format!("Authenticated as {}", user.login)),
+        Err(err) => match err.downcast_ref::<CircleDebugError>() {
+            Some(CircleDebugError::Forbidden(_)) => Check::fail(
+                "CircleCI API",
+                "Token can't identify a user",
+                "Project API tokens don't work with cdb; create a personal one at https://app.circleci.com/settings/user/tokens",
+            ),
+            Some(err) if err.is_auth_error() => Check::fail(
+                "CircleCI API",
+                "Token was rejected",
+                "The token may be expired or revoked; create a new one at https://app.circleci.com/settings/user/tokens",
+            ),
+            Some(CircleDebugError::NetworkError(msg)) => Check::fail(
+                "CircleCI API",
+                format!("Can't reach circleci.com: {}", msg),
+                "Check your network connection and any HTTPS_PROXY settings",
+            ),
+            _ => Check::fail(
+                "CircleCI API",
+                format!("{:#}", err),
+                "Check https://status.circleci.com, then rerun with -vv for details",
+            ),
+        },
+    }
+}
+
+/// Checks for the GitHub CLI, which only `cdb pr` needs.
+fn check_gh() -> Check {
+    if !command_exists("gh") {
+        return Check::warn(
+            "GitHub CLI",
+            "gh is not installed (only needed for `cdb pr`)",
+            "Install it from https://cli.github.com/",
+        );
+    }
+
+    let authed = Command::new("gh")
+        .args(["auth", "status"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if authed {
+        Check::pass("GitHub CLI", "gh is installed and authenticated")
+    } else {
+        Check::warn(
+            "GitHub CLI",
+            "gh is installed but not authenticated",
+            "Run `gh auth login`",
+        )
+    }
+}
+
+/// Checks that saved logs and cached responses can be written under `dir`.
+fn check_cache_dir(dir: &Path) -> Check {
+    let probe = dir.join(".doctor-probe");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, b"ok"))
+        .and_then(|()| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::pass("Cache directory", format!("{} is writable", dir.display())),
+        Err(err) => Check::fail(
+            "Cache directory",
+            format!("Can't write to {}: {}", dir.display(), err),
+            "Fix the directory's permissions or free up disk space",
+        ),
+    }
+}
+
+/// Checks that the config files that apply in `dir`, if any, parse and
+/// their patterns and highlight rules compile.
+fn check_config(path: Option<&Path>, dir: &Path) -> Check {
+    let user = path
+        .map(Path::to_path_buf)
+        .or_else(Config::default_path)
+        .filter(|path| path.exists());
+    let shown: Vec<String> = user
+        .into_iter()
+        .chain(Config::find_project_file(dir))
+        .map(|path| path.display().to_string())
+        .collect();
+    let result = Config::load_with_project(path, dir).and_then(|config| {
+        config.patterns()?;
+        config.suggestions()?;
+        config.highlights()?;
+        Ok(config)
+    });
+
+    match result {
+        Ok(_) if shown.is_empty() => {
+            Check::pass("Config file", "None found, using built-in patterns")
+        }
+        Ok(_) => Check::pass(
+            "Config file",
+            format!(
+                "{} {} valid",
+                shown.join(" and "),
+                if shown.len() == 1 { "is" } else { "are" }
+            ),
+        ),
+        Err(err) => Check::fail(
+            "Config file",
+            format!("{:#}", err),
+            "Fix the file, or point --config/CDB_CONFIG at a valid one",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("cdb-doctor-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_check_config() {
+        let path = temp_path("config.toml");
+        std::fs::write(
+            &path,
+            "[[patterns]]\nid = \"x\"\ncategory = \"Oops\"\nregex = \"oops\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            check_config(Some(&path), &std::env::temp_dir()).status,
+            Status::Pass
+        );
+
+        std::fs::write(
+            &path,
+            "[[patterns]]\nid = \"x\"\ncategory = \"Oops\"\nregex = \"(\"\n",
+        )
+        .unwrap();
+        let check = check_config(Some(&path), &std::env::temp_dir());
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.hint.is_some());
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            check_config(Some(&path), &std::env::temp_dir()).status,
+            Status::Fail
+        );
+    }
+
+    #[test]
+    fn test_check_cache_dir() {
+        let dir = temp_path("cache");
+        assert_eq!(check_cache_dir(&dir).status, Status::Pass);
+        assert!(!dir.join(".doctor-probe").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A file where the directory should be
+        std::fs::write(&dir, b"").unwrap();
+        assert_eq!(check_cache_dir(&dir).status, Status::Fail);
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
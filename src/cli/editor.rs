@@ -0,0 +1,91 @@
+//! Opening a file at a line in the user's editor, for `--open-editor`.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Returns the editor command line: `$VISUAL`, then `$EDITOR`, then VS
+/// Code's `code` if it's installed.
+pub fn editor() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .or_else(|| super::command_exists("code").then(|| "code".to_string()))
+}
+
+/// Returns the arguments that make `editor` open `file` at `line` and
+/// `col`.
+///
+/// VS Code and its forks take `-g file:line:col`, Sublime Text, Helix, and
+/// Zed `file:line:col`, and vi-likes, Emacs, nano, and most others
+/// `+line file`.
+pub fn editor_args(editor: &str, file: &str, line: Option<u32>, col: Option<u32>) -> Vec<String> {
+    let program = Path::new(editor)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(editor);
+    let position = match (line, col) {
+        (Some(line), Some(col)) => format!("{}:{}:{}", file, line, col),
+        (Some(line), None) => format!("{}:{}", file, line),
+        (None, _) => file.to_string(),
+    };
+    match program {
+        "code" | "code-insiders" | "codium" | "cursor" => vec!["-g".to_string(), position],
+        "subl" | "hx" | "helix" | "zed" => vec![position],
+        _ => match line {
+            Some(line) => vec![format!("+{}", line), file.to_string()],
+            None => vec![file.to_string()],
+        },
+    }
+}
+
+/// Opens `file` at `line` and `col` in the user's editor and waits for
+/// it to exit.
+///
+/// # Errors
+///
+/// Returns an error if no editor is configured or it fails to start or
+/// exits unsuccessfully.
+pub fn open(file: &Path, line: Option<u32>, col: Option<u32>) -> Result<()> {
+    let Some(editor) = editor() else {
+        bail!("No editor found; set $EDITOR (e.g., export EDITOR=vim) or install VS Code's `code`");
+    };
+    // $EDITOR may carry arguments, e.g. "code --wait" or "emacsclient -t"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .args(editor_args(program, &file.to_string_lossy(), line, col))
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_args() {
+        assert_eq!(
+            editor_args("/usr/local/bin/code", "src/a.ts", Some(14), Some(3)),
+            vec!["-g", "src/a.ts:14:3"]
+        );
+        assert_eq!(
+            editor_args("nvim", "src/a.ts", Some(14), Some(3)),
+            vec!["+14", "src/a.ts"]
+        );
+        assert_eq!(
+            editor_args("hx", "src/a.ts", Some(14), None),
+            vec!["src/a.ts:14"]
+        );
+        assert_eq!(
+            editor_args("nano", "src/a.ts", None, None),
+            vec!["src/a.ts"]
+        );
+    }
+}
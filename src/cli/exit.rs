@@ -0,0 +1,207 @@
+//! Process exit codes.
+//!
+//! Scripts and CI jobs wrapping `cdb` can branch on these instead of
+//! parsing output:
+//!
+//! | Code | Meaning                                                     |
+//! |------|-------------------------------------------------------------|
+//! | 0    | Success (build passed, or `--fail-on` didn't trip)          |
+//! | 1    | Any other error                                             |
+//! | 10   | Build failed and an error pattern was detected              |
+//! | 11   | Build failed but no pattern was detected (rerun with --full) |
+//! | 12   | Line coverage is below `--coverage-threshold`               |
+//! | 13   | `diff-artifacts`: a file grew by more than `--max-growth`   |
+//! | 14   | `status`, `wait`: the build failed                          |
+//! | 15   | `status`, `wait`: still queued or running, or canceled      |
+//! | 16   | `compare-branch`: a step is consistently slower on the branch |
+//! | 20   | Authentication error (missing, rejected, or forbidden token) |
+//! | 21   | Build or resource not found                                 |
+//! | 30   | Network error                                               |
+
+use circle_debug::patterns::FailureKind;
+use circle_debug::{BuildInfo, CircleDebugError};
+use clap::ValueEnum;
+use std::process::ExitCode;
+
+pub const SUCCESS: u8 = 0;
+pub const GENERAL_ERROR: u8 = 1;
+pub const BUILD_FAILED_DETECTED: u8 = 10;
+pub const BUILD_FAILED_UNDETECTED: u8 = 11;
+pub const COVERAGE_BELOW_THRESHOLD: u8 = 12;
+pub const ARTIFACT_GROWTH: u8 = 13;
+pub const STATUS_FAILED: u8 = 14;
+pub const STATUS_PENDING: u8 = 15;
+pub const STEP_SLOWDOWN: u8 = 16;
+pub const AUTH_ERROR: u8 = 20;
+pub const NOT_FOUND: u8 = 21;
+pub const NETWORK_ERROR: u8 = 30;
+
+/// Result of analyzing a build, used to pick the exit code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildOutcome {
+    /// The build has no failures.
+    Passed,
+    /// The build failed.
+    Failed {
+        /// Whether any error pattern matched.
+        detected: bool,
+        /// Kinds of the detected patterns, plus [`FailureKind::Infra`]
+        /// when CircleCI reported an infrastructure failure.
+        kinds: Vec<FailureKind>,
+    },
+    /// The build passed, but line coverage is below
+    /// `--coverage-threshold`.
+    BelowCoverage,
+}
+
+/// Which build failures make `cdb build` exit non-zero (`--fail-on`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FailOn {
+    /// Any failed build.
+    #[default]
+    Failed,
+    /// Only failures classified as infrastructure problems.
+    Infra,
+    /// Only failures with failing tests.
+    Tests,
+    /// Never; only errors running `cdb` itself exit non-zero.
+    None,
+}
+
+impl FailOn {
+    /// Picks the exit code for `outcome` under this gate.
+    pub fn exit_code(self, outcome: &BuildOutcome) -> ExitCode {
+        let (detected, kinds) = match outcome {
+            BuildOutcome::Passed => return ExitCode::from(SUCCESS),
+            BuildOutcome::BelowCoverage if self == FailOn::None => return ExitCode::from(SUCCESS),
+            BuildOutcome::BelowCoverage => return ExitCode::from(COVERAGE_BELOW_THRESHOLD),
+            BuildOutcome::Failed { detected, kinds } => (detected, kinds),
+        };
+
+        let gated = match self {
+            FailOn::Failed => true,
+            FailOn::Infra => kinds.contains(&FailureKind::Infra),
+            FailOn::Tests => kinds.contains(&FailureKind::Tests),
+            FailOn::None => false,
+        };
+
+        ExitCode::from(match (gated, detected) {
+            (false, _) => SUCCESS,
+            (true, true) => BUILD_FAILED_DETECTED,
+            (true, false) => BUILD_FAILED_UNDETECTED,
+        })
+    }
+}
+
+/// Picks the exit code of `cdb status`: green, red, or no result yet.
+pub fn for_status(build: &BuildInfo) -> ExitCode {
+    ExitCode::from(match build.status.as_str() {
+        "success" | "fixed" => SUCCESS,
+        _ if build.is_failed() || build.is_infrastructure_failure() => STATUS_FAILED,
+        _ => STATUS_PENDING,
+    })
+}
+
+/// Picks the exit code for an error by looking through its cause chain.
+pub fn for_error(err: &anyhow::Error) -> ExitCode {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<CircleDebugError>() {
+            return ExitCode::from(match err {
+                err if err.is_auth_error() => AUTH_ERROR,
+                CircleDebugError::NetworkError(_) => NETWORK_ERROR,
+                err if err.is_not_found() => NOT_FOUND,
+                _ => GENERAL_ERROR,
+            });
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            if err.is_connect() || err.is_timeout() {
+                return ExitCode::from(NETWORK_ERROR);
+            }
+        }
+    }
+
+    ExitCode::from(GENERAL_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_fail_on() {
+        let tests_failed = BuildOutcome::Failed {
+            detected: true,
+            kinds: vec![FailureKind::Code, FailureKind::Tests],
+        };
+        let undetected = BuildOutcome::Failed {
+            detected: false,
+            kinds: Vec::new(),
+        };
+
+        assert_eq!(
+            FailOn::Failed.exit_code(&tests_failed),
+            ExitCode::from(BUILD_FAILED_DETECTED)
+        );
+        assert_eq!(
+            FailOn::Tests.exit_code(&tests_failed),
+            ExitCode::from(BUILD_FAILED_DETECTED)
+        );
+        assert_eq!(FailOn::Infra.exit_code(&tests_failed), ExitCode::SUCCESS);
+        assert_eq!(FailOn::None.exit_code(&tests_failed), ExitCode::SUCCESS);
+        assert_eq!(
+            FailOn::Failed.exit_code(&undetected),
+            ExitCode::from(BUILD_FAILED_UNDETECTED)
+        );
+        assert_eq!(
+            FailOn::Failed.exit_code(&BuildOutcome::Passed),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(
+            FailOn::Infra.exit_code(&BuildOutcome::BelowCoverage),
+            ExitCode::from(COVERAGE_BELOW_THRESHOLD)
+        );
+        assert_eq!(
+            FailOn::None.exit_code(&BuildOutcome::BelowCoverage),
+            ExitCode::SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_for_error_looks_through_context() {
+        let err = Err::<(), _>(CircleDebugError::NetworkError("reset".into()))
+            .context("Failed to fetch logs")
+            .unwrap_err();
+        assert_eq!(for_error(&err), ExitCode::from(NETWORK_ERROR));
+
+        let err = anyhow::Error::from(CircleDebugError::ApiError {
+            status: 404,
+            message: String::new(),
+        });
+        assert_eq!(for_error(&err), ExitCode::from(NOT_FOUND));
+
+        assert_eq!(
+            for_error(&anyhow::anyhow!("bad url")),
+            ExitCode::from(GENERAL_ERROR)
+        );
+    }
+
+    #[test]
+    fn test_for_status() {
+        let build = |status: &str| -> BuildInfo {
+            serde_json::from_value(serde_json::json!({
+                "build_num": 1, "status": status, "branch": "main", "subject": null, "steps": []
+            }))
+            .unwrap()
+        };
+        assert_eq!(for_status(&build("fixed")), ExitCode::SUCCESS);
+        assert_eq!(
+            for_status(&build("timedout")),
+            ExitCode::from(STATUS_FAILED)
+        );
+        assert_eq!(
+            for_status(&build("running")),
+            ExitCode::from(STATUS_PENDING)
+        );
+    }
+}
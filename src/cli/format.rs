@@ -0,0 +1,85 @@
+//! Output formats for `cdb build`.
+
+use anyhow::Result;
+use circle_debug::postmortem::PostMortemFormat;
+use clap::ValueEnum;
+
+/// How `cdb build` prints its analysis (`--format`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable report.
+    #[default]
+    Text,
+    /// The full [`circle_debug::report::BuildReport`] as pretty-printed JSON.
+    Json,
+    /// Detected errors as GitHub Actions `::error` workflow commands.
+    GhAnnotations,
+    /// Steps and failed tests as CSV rows.
+    Csv,
+    /// Detected errors and failed tests as `file:line:col: message` lines
+    /// for Vim's quickfix list or a VS Code problem matcher.
+    Quickfix,
+    /// One [`circle_debug::events::Event`] per line as the analysis
+    /// progresses.
+    Events,
+    /// A few lines: status, failed step, top error, its suggestion, and
+    /// the saved log, for shell prompts, git hooks, and status bars.
+    Summary,
+}
+
+/// Document format of `cdb report` (`--format`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ReportFormat {
+    /// GitHub-flavored Markdown.
+    #[default]
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+impl From<ReportFormat> for PostMortemFormat {
+    fn from(format: ReportFormat) -> Self {
+        match format {
+            ReportFormat::Markdown => PostMortemFormat::Markdown,
+            ReportFormat::Html => PostMortemFormat::Html,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Parses a format name from the config file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the valid formats.
+    pub fn from_config(value: &str) -> Result<Self> {
+        OutputFormat::from_str(value, true).map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid output format '{}', expected text, json, gh-annotations, csv, quickfix, events, or summary",
+                value
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(
+            OutputFormat::from_config("JSON").unwrap(),
+            OutputFormat::Json
+        );
+        assert_eq!(
+            OutputFormat::from_config("gh-annotations").unwrap(),
+            OutputFormat::GhAnnotations
+        );
+        assert_eq!(
+            OutputFormat::from_config("summary").unwrap(),
+            OutputFormat::Summary
+        );
+        assert!(OutputFormat::from_config("yaml").is_err());
+    }
+}
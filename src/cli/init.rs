@@ -0,0 +1,223 @@
+//! Interactive first-run setup for `cdb init`.
+//!
+//! Asks for the API token, a default repository, and the preferred output
+//! format. The token is checked against CircleCI and saved with
+//! [`credentials::save_token`]; the rest goes into the `[defaults]` table
+//! of the config file, keeping anything already in it.
+
+use super::format::OutputFormat;
+use super::progress::Progress;
+use super::style::{print_error, print_info, print_success, print_warning};
+use anyhow::{bail, Context, Result};
+use circle_debug::config::{Config, Defaults};
+use circle_debug::{credentials, git, CircleClient, CircleDebugError};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+/// How many times a rejected token is asked for again.
+const TOKEN_ATTEMPTS: u32 = 3;
+
+/// Runs the setup, writing to `config_path` or the default config file.
+///
+/// # Errors
+///
+/// Returns an error if stdin isn't a terminal, the token keeps being
+/// rejected, or a file can't be written.
+pub async fn run(config_path: Option<&Path>, progress: &Progress) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        bail!("cdb init needs an interactive terminal; set CIRCLECI_TOKEN and edit the config file instead");
+    }
+
+    let path = match config_path {
+        Some(path) => path.to_path_buf(),
+        None => Config::default_path().context("No config directory on this platform")?,
+    };
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read config file {}", path.display()))
+        }
+    };
+    let current = Config::from_toml(&existing)
+        .with_context(|| format!("Invalid config file {}", path.display()))?
+        .defaults;
+
+    setup_token(progress).await?;
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut output = std::io::stdout();
+
+    let detected = current.repo.clone().or_else(|| {
+        git::origin_repo()
+            .ok()
+            .map(|(org, project)| format!("{}/{}", org, project))
+    });
+    let repo = loop {
+        let answer = ask(
+            &mut input,
+            &mut output,
+            "Default repository (org/repo, used by `cdb pr` and `cdb bisect`)",
+            detected.as_deref(),
+        )?;
+        match answer.map(|repo| parse_repo(&repo)).transpose() {
+            Ok(repo) => break repo,
+            Err(err) => print_error(&err.to_string()),
+        }
+    };
+
+    let format = loop {
+        let answer = ask(
+            &mut input,
+            &mut output,
+            "Preferred output format for `cdb build` (text, json, gh-annotations, csv, quickfix, events, or summary)",
+            Some(current.format.as_deref().unwrap_or("text")),
+        )?;
+        match answer.as_deref().map(OutputFormat::from_config).transpose() {
+            Ok(_) => break answer,
+            Err(err) => print_error(&err.to_string()),
+        }
+    };
+
+    let defaults = Defaults {
+        repo,
+        format,
+        ..current
+    };
+    let updated = Config::with_defaults(&existing, &defaults)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, updated)
+        .with_context(|| format!("Failed to write config file {}", path.display()))?;
+    print_success(&format!("Saved settings to {}", path.display()));
+    print_info("Try it: cdb build https://circleci.com/gh/<org>/<repo>/<build>");
+    Ok(())
+}
+
+/// Asks for a token until CircleCI accepts one, then stores it.
+///
+/// Pressing Enter keeps a token that is already configured.
+async fn setup_token(progress: &Progress) -> Result<()> {
+    let have_token = credentials::token().is_some();
+    println!("Create a personal API token at https://app.circleci.com/settings/user/tokens");
+
+    for _ in 0..TOKEN_ATTEMPTS {
+        let prompt = if have_token {
+            "CircleCI API token (Enter to keep the current one): "
+        } else {
+            "CircleCI API token: "
+        };
+        let token = rpassword::prompt_password(prompt).context("Failed to read token")?;
+        let token = token.trim();
+        if token.is_empty() {
+            if have_token {
+                return Ok(());
+            }
+            print_error("A token is required");
+            continue;
+        }
+
+        let spinner = progress.spinner("Checking token...");
+        let user = match CircleClient::with_token(token) {
+            Ok(client) => client.me().await,
+            Err(err) => Err(err),
+        };
+        spinner.finish_and_clear();
+
+        match user {
+            Ok(user) => print_success(&format!("Token belongs to {}", user.login)),
+            Err(err) => match err.downcast_ref::<CircleDebugError>() {
+                Some(err) if err.is_auth_error() => {
+                    print_error("CircleCI rejected that token");
+                    continue;
+                }
+                // Offline setup is fine; `cdb doctor` can check it later
+                _ => print_warning(&format!("Couldn't verify the token: {:#}", err)),
+            },
+        }
+
+        let path = credentials::save_token(token)?;
+        print_success(&format!("Token saved to {}", path.display()));
+        if std::env::var_os("CIRCLECI_TOKEN").is_some() {
+            print_info("CIRCLECI_TOKEN is also set and takes precedence over the saved token");
+        }
+        return Ok(());
+    }
+
+    bail!("No valid token after {} attempts", TOKEN_ATTEMPTS)
+}
+
+/// Prints `question` and reads one line, returning `default` for an empty
+/// answer.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<&str>,
+) -> Result<Option<String>> {
+    match default {
+        Some(default) => write!(output, "{} [{}]: ", question, default)?,
+        None => write!(output, "{}: ", question)?,
+    }
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        bail!("Setup cancelled");
+    }
+    let answer = line.trim();
+    Ok(if answer.is_empty() {
+        default.map(str::to_string)
+    } else {
+        Some(answer.to_string())
+    })
+}
+
+/// Checks that `repo` looks like `org/repo`.
+fn parse_repo(repo: &str) -> Result<String> {
+    match repo.split_once('/') {
+        Some((org, name)) if !org.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok(repo.to_string())
+        }
+        _ => bail!("Invalid repository '{}', expected org/repo", repo),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask() {
+        let mut output = Vec::new();
+        let answer = ask(&mut "\n".as_bytes(), &mut output, "Repo", Some("a/b")).unwrap();
+        assert_eq!(answer.as_deref(), Some("a/b"));
+        assert_eq!(String::from_utf8(output).unwrap(), "Repo [a/b]: ");
+
+        let answer = ask(
+            &mut "  c/d \n".as_bytes(),
+            &mut Vec::new(),
+            "Repo",
+            Some("a/b"),
+        )
+        .unwrap();
+        assert_eq!(answer.as_deref(), Some("c/d"));
+        assert_eq!(
+            ask(&mut "\n".as_bytes(), &mut Vec::new(), "Repo", None).unwrap(),
+            None
+        );
+        assert!(ask(&mut "".as_bytes(), &mut Vec::new(), "Repo", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_repo() {
+        assert_eq!(parse_repo("org/repo").unwrap(), "org/repo");
+        assert!(parse_repo("org").is_err());
+        assert!(parse_repo("org/").is_err());
+        assert!(parse_repo("https://github.com/org/repo").is_err());
+    }
+}
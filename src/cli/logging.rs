@@ -0,0 +1,46 @@
+//! Diagnostic logging for `cdb` itself (`-v`, `-vv`, `-vvv`).
+//!
+//! The library emits `tracing` spans and events for API calls, log
+//! downloads, and the analysis pipeline. They are written to stderr so
+//! they never mix with the report on stdout.
+
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Returns the filter directives for a `-v` count.
+fn directives(verbosity: u8) -> String {
+    let level = match verbosity {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    format!("circle_debug={level},cdb={level}")
+}
+
+/// Installs the stderr subscriber.
+///
+/// `RUST_LOG`, when set, takes precedence over the `-v` count.
+pub fn init(verbosity: u8) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives(verbosity)));
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(directives(0), "circle_debug=warn,cdb=warn");
+        assert_eq!(directives(2), "circle_debug=debug,cdb=debug");
+        assert_eq!(directives(7), "circle_debug=trace,cdb=trace");
+    }
+}
@@ -0,0 +1,24 @@
+//! Terminal presentation helpers used by the `cdb` binary.
+//!
+//! These live outside the library so that embedding `circle_debug` doesn't
+//! pull in terminal UI concerns.
+
+pub mod clipboard;
+pub mod doctor;
+pub mod editor;
+pub mod exit;
+pub mod format;
+pub mod init;
+pub mod logging;
+pub mod notify;
+pub mod progress;
+pub mod style;
+
+/// Returns `true` if `name` is an executable on the `PATH`.
+pub fn command_exists(name: &str) -> bool {
+    let finder = if cfg!(windows) { "where" } else { "which" };
+    std::process::Command::new(finder)
+        .arg(name)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
@@ -0,0 +1,139 @@
+//! Desktop notifications, for `cdb pr --watch` and `cdb wait --notify`.
+
+use std::io::Write;
+use std::process::Command;
+
+/// Notifiers to try, in order: the program and its arguments.
+///
+/// `terminal-notifier` and the Windows toast open `url` when the
+/// notification is clicked; the others show it in the body.
+fn notifiers(title: &str, body: &str, url: &str) -> Vec<(&'static str, Vec<String>)> {
+    let body_with_url = format!("{}\n{}", body, url);
+    if cfg!(target_os = "macos") {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        vec![
+            (
+                "terminal-notifier",
+                vec![
+                    "-title".to_string(),
+                    title.to_string(),
+                    "-message".to_string(),
+                    body.to_string(),
+                    "-open".to_string(),
+                    url.to_string(),
+                ],
+            ),
+            (
+                "osascript",
+                vec![
+                    "-e".to_string(),
+                    format!(
+                        "display notification \"{}\" with title \"{}\"",
+                        escape(&body_with_url),
+                        escape(title)
+                    ),
+                ],
+            ),
+        ]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            vec![
+                "-NoProfile".to_string(),
+                "-NonInteractive".to_string(),
+                "-Command".to_string(),
+                toast_script(title, body, url),
+            ],
+        )]
+    } else {
+        vec![(
+            "notify-send",
+            vec![
+                "--app-name=cdb".to_string(),
+                title.to_string(),
+                body_with_url,
+            ],
+        )]
+    }
+}
+
+/// PowerShell that shows a Windows toast opening `url` when clicked.
+///
+/// Toasts need a registered app id, so PowerShell's own is used.
+fn toast_script(title: &str, body: &str, url: &str) -> String {
+    let xml_escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let toast = format!(
+        "<toast activationType=\"protocol\" launch=\"{}\"><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual></toast>",
+        xml_escape(url),
+        xml_escape(title),
+        xml_escape(body)
+    );
+    format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null; \
+         [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime] | Out-Null; \
+         $xml = New-Object Windows.Data.Xml.Dom.XmlDocument; \
+         $xml.LoadXml('{}'); \
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}}\\WindowsPowerShell\\v1.0\\powershell.exe').Show([Windows.UI.Notifications.ToastNotification]::new($xml))",
+        // Single-quoted PowerShell strings only escape quotes, by doubling
+        toast.replace('\'', "''")
+    )
+}
+
+/// Shows a desktop notification with the first notifier that's installed
+/// and succeeds, or rings the terminal bell if none does.
+///
+/// Never fails: a missing notification isn't worth failing the command
+/// over, so problems are only logged.
+pub fn send(title: &str, body: &str, url: &str) {
+    for (program, args) in notifiers(title, body, url) {
+        if !super::command_exists(program) {
+            continue;
+        }
+        match Command::new(program).args(&args).output() {
+            Ok(output) if output.status.success() => return,
+            Ok(output) => tracing::debug!(program, status = %output.status, "notifier failed"),
+            Err(e) => tracing::debug!(program, "couldn't run notifier: {}", e),
+        }
+    }
+    tracing::debug!("no desktop notifier worked; ringing the bell");
+    let mut stderr = std::io::stderr();
+    let _ = stderr.write_all(b"\x07");
+    let _ = stderr.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notifiers_show_the_url() {
+        let notifiers = notifiers(
+            "cdb: org/repo #42 failed",
+            "Fix \"flaky\" test",
+            "https://circleci.com/gh/org/repo/42",
+        );
+        let (_, args) = notifiers.last().unwrap();
+        let shown = args.join(" ");
+        assert!(shown.contains("https://circleci.com/gh/org/repo/42"));
+        if cfg!(target_os = "macos") {
+            assert!(shown.contains("Fix \\\"flaky\\\" test"));
+        }
+    }
+
+    #[test]
+    fn test_toast_script_escapes_text() {
+        let script = toast_script(
+            "cdb: PR #7 failed",
+            "Don't <merge> & \"retry\"",
+            "https://github.com/org/repo/pull/7",
+        );
+        assert!(script.contains("launch=\"https://github.com/org/repo/pull/7\""));
+        assert!(script.contains("<text>Don''t &lt;merge&gt; &amp; &quot;retry&quot;</text>"));
+        assert!(script.contains("CreateToastNotifier('{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}"));
+    }
+}
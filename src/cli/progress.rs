@@ -0,0 +1,84 @@
+//! Progress bars and spinners for API calls and log downloads.
+//!
+//! Indicators draw to stderr and are hidden entirely when stdout isn't a
+//! terminal or `--quiet` is set, so piped and captured output stays clean.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::time::Duration;
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shared display for every indicator in a command run.
+///
+/// Bars created from the same `Progress` stack together, which gives a
+/// multi-line display when several logs download concurrently.
+pub struct Progress {
+    multi: MultiProgress,
+}
+
+impl Progress {
+    /// Creates the display, hidden when `quiet` or stdout isn't a TTY.
+    pub fn new(quiet: bool) -> Self {
+        let multi = MultiProgress::new();
+        if quiet || !std::io::stdout().is_terminal() {
+            multi.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        Progress { multi }
+    }
+
+    /// Starts a spinner for an operation of unknown length.
+    ///
+    /// Call `finish_and_clear()` on the returned bar before printing results.
+    pub fn spinner(&self, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+        let bar = self.multi.add(ProgressBar::new_spinner());
+        bar.set_style(
+            ProgressStyle::with_template("{spinner:.yellow} {msg:.dim}")
+                .expect("valid spinner template"),
+        );
+        bar.set_message(message);
+        bar.enable_steady_tick(TICK_INTERVAL);
+        bar
+    }
+
+    /// Starts a byte-counting bar for a download.
+    ///
+    /// Shows a spinner with the running byte count and transfer rate until
+    /// the total size is known via [`update_download`], then switches to a
+    /// proportional bar.
+    pub fn download(&self, name: impl Into<Cow<'static, str>>) -> ProgressBar {
+        let bar = self.multi.add(ProgressBar::no_length());
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.yellow} {msg} {bytes:.dim} {bytes_per_sec:.dim} {elapsed:.dim}",
+            )
+            .expect("valid download template"),
+        );
+        bar.set_message(name);
+        bar.enable_steady_tick(TICK_INTERVAL);
+        bar
+    }
+
+    /// Prints a line above the indicators, or nothing when they're hidden.
+    pub fn println(&self, line: impl AsRef<str>) {
+        let _ = self.multi.println(line);
+    }
+}
+
+/// Updates a download bar from a `get_logs_with_progress` callback.
+pub fn update_download(bar: &ProgressBar, received: u64, total: Option<u64>) {
+    if let Some(total) = total {
+        if bar.length() != Some(total) {
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{spinner:.yellow} {msg} [{bar:30.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec:.dim} {eta:.dim}",
+                )
+                .expect("valid download template")
+                .progress_chars("=> "),
+            );
+            bar.set_length(total);
+        }
+    }
+    bar.set_position(received);
+}
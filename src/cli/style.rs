@@ -0,0 +1,540 @@
+//! Terminal colors and the print helpers that use them.
+//!
+//! All colored output goes through [`colored`], which [`init`] configures
+//! once at startup from `--color`, the `NO_COLOR` and `CLICOLOR_FORCE`
+//! conventions, and whether stdout is a terminal. Errors, warnings, and
+//! the other semantic roles are colored through [`Themed`], so the
+//! config's `[theme]` can restyle them.
+
+use circle_debug::highlight::{self, HighlightRule};
+use circle_debug::theme::{RoleStyle, Theme};
+use clap::ValueEnum;
+use colored::{ColoredString, Colorize};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// When to color output (`--color`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal, unless `NO_COLOR` is set or
+    /// `CLICOLOR_FORCE` forces it on.
+    #[default]
+    Auto,
+    /// Always color.
+    Always,
+    /// Never color.
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether to color, given the environment lookup and whether
+    /// stdout is a terminal.
+    ///
+    /// `CLICOLOR_FORCE` wins over `NO_COLOR`; both are ignored for an
+    /// explicit `always` or `never`. As the conventions specify, an empty
+    /// `NO_COLOR` and a `CLICOLOR_FORCE` of `0` count as unset.
+    pub fn should_color(self, env: impl Fn(&str) -> Option<String>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if env("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0") {
+                    true
+                } else if env("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    false
+                } else {
+                    is_terminal
+                }
+            }
+        }
+    }
+}
+
+/// Turns colors on or off for the rest of the run.
+pub fn init(choice: ColorChoice) {
+    let color = choice.should_color(
+        |name| std::env::var(name).ok(),
+        std::io::stdout().is_terminal(),
+    );
+    colored::control::set_override(color);
+}
+
+/// The config's `[theme]`, once loaded.
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Styles the roles with `theme` for the rest of the run.
+///
+/// Output printed before this, e.g. while the config loads, uses the
+/// default styles.
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// A semantic part of the output, styled by the config's `[theme]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Error,
+    Warning,
+    Success,
+    Dimmed,
+    Highlight,
+}
+
+impl Role {
+    /// Returns how `theme` styles this role, or the default if it
+    /// doesn't set it.
+    fn resolve(self, theme: &Theme) -> RoleStyle {
+        use highlight::Color;
+        let configured = match self {
+            Role::Error => &theme.error,
+            Role::Warning => &theme.warning,
+            Role::Success => &theme.success,
+            Role::Dimmed => &theme.dimmed,
+            Role::Highlight => &theme.highlight,
+        };
+        configured.clone().unwrap_or_else(|| match self {
+            Role::Error => RoleStyle::color(Color::Red),
+            Role::Warning => RoleStyle::color(Color::Yellow),
+            Role::Success => RoleStyle::color(Color::Green),
+            Role::Dimmed => RoleStyle {
+                style: vec![highlight::Style::Dimmed],
+                ..Default::default()
+            },
+            Role::Highlight => RoleStyle {
+                color: Some(Color::White),
+                background: Some(Color::Red),
+                style: vec![highlight::Style::Bold],
+            },
+        })
+    }
+
+    /// Styles `text` as this role.
+    pub fn paint(self, text: &str) -> ColoredString {
+        let style = self.resolve(THEME.get_or_init(Theme::default));
+        apply(text.normal(), style.color, style.background, &style.style)
+    }
+}
+
+/// Colors text by its role rather than a fixed color, e.g.
+/// `"✗".error().bold()`.
+pub trait Themed {
+    /// Styles as an error; red by default.
+    fn error(&self) -> ColoredString;
+    /// Styles as a warning; yellow by default.
+    fn warning(&self) -> ColoredString;
+    /// Styles as a success; green by default.
+    fn success(&self) -> ColoredString;
+    /// Styles as a secondary detail; dimmed by default.
+    fn dim(&self) -> ColoredString;
+    /// Styles as the highlighted error line; bold white on red by default.
+    fn highlight(&self) -> ColoredString;
+}
+
+impl Themed for str {
+    fn error(&self) -> ColoredString {
+        Role::Error.paint(self)
+    }
+
+    fn warning(&self) -> ColoredString {
+        Role::Warning.paint(self)
+    }
+
+    fn success(&self) -> ColoredString {
+        Role::Success.paint(self)
+    }
+
+    fn dim(&self) -> ColoredString {
+        Role::Dimmed.paint(self)
+    }
+
+    fn highlight(&self) -> ColoredString {
+        Role::Highlight.paint(self)
+    }
+}
+
+/// Prints a formatted section header to the terminal.
+///
+/// Creates a visually distinctive header with the text in bold blue
+/// and an underline of equal signs.
+///
+/// # Arguments
+///
+/// * `text` - The header text to display
+///
+/// # Examples
+///
+/// ```
+/// print_header("Build Summary");
+/// // Output:
+/// // Build Summary (in bold blue)
+/// // ============= (in blue)
+/// ```
+pub fn print_header(text: &str) {
+    println!("\n{}", text.bold().blue());
+    println!("{}", "=".repeat(text.len()).blue());
+}
+
+/// Prints an error message with a red cross indicator.
+///
+/// Formats error messages with a red "✗" symbol prefix for
+/// clear visual indication of failures.
+///
+/// # Arguments
+///
+/// * `text` - The error message to display
+///
+/// # Examples
+///
+/// ```
+/// print_error("Build failed with exit code 1");
+/// // Output: ✗ Build failed with exit code 1 (in red)
+/// ```
+pub fn print_error(text: &str) {
+    println!("{} {}", "✗".error().bold(), text.error());
+}
+
+/// Prints a success message with a green checkmark indicator.
+///
+/// Formats success messages with a green "✓" symbol prefix for
+/// clear visual indication of successful operations.
+///
+/// # Arguments
+///
+/// * `text` - The success message to display
+///
+/// # Examples
+///
+/// ```
+/// print_success("All tests passed");
+/// // Output: ✓ All tests passed (in green)
+/// ```
+pub fn print_success(text: &str) {
+    println!("{} {}", "✓".success().bold(), text.success());
+}
+
+/// Prints an informational message with a yellow arrow indicator.
+///
+/// Formats informational messages with a yellow "→" symbol prefix
+/// for neutral status updates.
+///
+/// # Arguments
+///
+/// * `text` - The informational message to display
+///
+/// # Examples
+///
+/// ```
+/// print_info("Fetching build details...");
+/// // Output: → Fetching build details... (with yellow arrow)
+/// ```
+pub fn print_info(text: &str) {
+    println!("{} {}", "→".warning(), text);
+}
+
+/// Prints a warning with a yellow "⚠" indicator, preceded by a blank line
+/// so it stands apart from the section above.
+///
+/// # Arguments
+///
+/// * `text` - The warning message to display
+///
+/// # Examples
+///
+/// ```
+/// print_warning("Bottleneck detected: 'Run tests' takes 80% of total time");
+/// // Output:
+/// //
+/// // ⚠ Bottleneck detected: 'Run tests' takes 80% of total time (with yellow ⚠)
+/// ```
+pub fn print_warning(text: &str) {
+    println!("\n{} {}", "⚠".warning(), text);
+}
+
+/// Colors a log line: the parts `rules` match with their rule's color and
+/// style, the rest with `base`, e.g. red for an error line.
+pub fn highlight_line(
+    line: &str,
+    rules: &[HighlightRule],
+    base: impl Fn(&str) -> ColoredString,
+) -> String {
+    let mut out = String::new();
+    let mut at = 0;
+    for (range, rule) in highlight::highlight_spans(line, rules) {
+        if range.start > at {
+            out.push_str(&base(&line[at..range.start]).to_string());
+        }
+        out.push_str(&styled(&line[range.clone()], rule).to_string());
+        at = range.end;
+    }
+    if at < line.len() {
+        out.push_str(&base(&line[at..]).to_string());
+    }
+    out
+}
+
+/// Applies a highlight rule's colors and styles to `text`.
+fn styled(text: &str, rule: &HighlightRule) -> ColoredString {
+    apply(text.normal(), rule.color, rule.background, &rule.style)
+}
+
+/// Adds colors and styles to `text`.
+fn apply(
+    mut text: ColoredString,
+    color: Option<highlight::Color>,
+    background: Option<highlight::Color>,
+    styles: &[highlight::Style],
+) -> ColoredString {
+    if let Some(color) = color {
+        text = text.color(term_color(color));
+    }
+    if let Some(color) = background {
+        text = text.on_color(term_color(color));
+    }
+    for style in styles {
+        text = match style {
+            highlight::Style::Bold => text.bold(),
+            highlight::Style::Dimmed => text.dimmed(),
+            highlight::Style::Italic => text.italic(),
+            highlight::Style::Underline => text.underline(),
+        };
+    }
+    text
+}
+
+fn term_color(color: highlight::Color) -> colored::Color {
+    use highlight::Color::*;
+    match color {
+        Black => colored::Color::Black,
+        Red => colored::Color::Red,
+        Green => colored::Color::Green,
+        Yellow => colored::Color::Yellow,
+        Blue => colored::Color::Blue,
+        Magenta => colored::Color::Magenta,
+        Cyan => colored::Color::Cyan,
+        White => colored::Color::White,
+        BrightBlack => colored::Color::BrightBlack,
+        BrightRed => colored::Color::BrightRed,
+        BrightGreen => colored::Color::BrightGreen,
+        BrightYellow => colored::Color::BrightYellow,
+        BrightBlue => colored::Color::BrightBlue,
+        BrightMagenta => colored::Color::BrightMagenta,
+        BrightCyan => colored::Color::BrightCyan,
+        BrightWhite => colored::Color::BrightWhite,
+    }
+}
+
+/// What a piece of a shell command is, for coloring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellToken {
+    /// The program a command runs.
+    Command,
+    /// An option such as `-x` or `--verbose`.
+    Flag,
+    /// A single- or double-quoted string.
+    Quoted,
+    /// `$NAME`, `${NAME}`, or a special parameter such as `$?`.
+    Variable,
+    /// Pipes, lists, redirections, and subshell parentheses.
+    Operator,
+    /// A `#` comment, up to the end of the line.
+    Comment,
+    /// Arguments, assignments, and whitespace.
+    Plain,
+}
+
+/// Characters that end a word.
+const SHELL_SPECIAL: &str = "|&;<>()'\"$";
+
+/// Splits one line of a shell command into colorable pieces.
+///
+/// A rough tokenizer rather than a shell parser: quotes and comments
+/// don't continue onto the next line.
+fn shell_tokens(line: &str) -> Vec<(ShellToken, &str)> {
+    let mut tokens = Vec::new();
+    // Whether the next word names a program
+    let mut command_next = true;
+    let mut at = 0;
+    while let Some(c) = line[at..].chars().next() {
+        let rest = &line[at..];
+        let end_of = |f: &dyn Fn(char) -> bool| rest.find(f).unwrap_or(rest.len());
+        let (token, len) = match c {
+            c if c.is_whitespace() => (ShellToken::Plain, end_of(&|c| !c.is_whitespace())),
+            '#' => (ShellToken::Comment, rest.len()),
+            '\'' => (
+                ShellToken::Quoted,
+                rest[1..].find('\'').map_or(rest.len(), |i| i + 2),
+            ),
+            '"' => {
+                let mut escaped = false;
+                let close = rest[1..].find(|c| {
+                    let close = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    close
+                });
+                (ShellToken::Quoted, close.map_or(rest.len(), |i| i + 2))
+            }
+            '$' if rest[1..].starts_with('(') => (ShellToken::Operator, 2),
+            '$' if rest[1..].starts_with('{') => (
+                ShellToken::Variable,
+                rest.find('}').map_or(rest.len(), |i| i + 1),
+            ),
+            '$' => {
+                let name = rest[1..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len() - 1);
+                let special = rest[1..]
+                    .chars()
+                    .next()
+                    .filter(|c| "?!#@*$-".contains(*c))
+                    .map_or(0, char::len_utf8);
+                (ShellToken::Variable, 1 + name.max(special))
+            }
+            c if SHELL_SPECIAL.contains(c) => {
+                (ShellToken::Operator, end_of(&|c| !"|&;<>()".contains(c)))
+            }
+            _ => {
+                let len = end_of(&|c| c.is_whitespace() || SHELL_SPECIAL.contains(c));
+                let word = &rest[..len];
+                let token = if command_next && !word.contains('=') {
+                    ShellToken::Command
+                } else if word.starts_with('-') {
+                    ShellToken::Flag
+                } else {
+                    ShellToken::Plain
+                };
+                (token, len)
+            }
+        };
+        let text = &rest[..len];
+        command_next = match token {
+            ShellToken::Plain if text.trim().is_empty() || text.contains('=') => command_next,
+            // A redirection is followed by a file name, not a program
+            ShellToken::Operator => !text.starts_with(['<', '>']),
+            ShellToken::Comment => command_next,
+            _ => false,
+        };
+        tokens.push((token, text));
+        at += len;
+    }
+    tokens
+}
+
+/// Colors one line of a shell command, e.g. a failed `run` step's.
+pub fn highlight_shell(line: &str) -> String {
+    shell_tokens(line)
+        .into_iter()
+        .map(|(token, text)| {
+            match token {
+                ShellToken::Command => text.bold(),
+                ShellToken::Flag => text.cyan(),
+                ShellToken::Quoted => text.green(),
+                ShellToken::Variable => text.magenta(),
+                ShellToken::Operator => text.yellow(),
+                ShellToken::Comment => text.dimmed(),
+                ShellToken::Plain => text.normal(),
+            }
+            .to_string()
+        })
+        .collect()
+}
+
+/// Draws values as a row of block characters scaled between their
+/// minimum and maximum, e.g. `▁▂▂▅█`.
+pub fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) else {
+        return String::new();
+    };
+    let range = (max - min).max(1) as f64;
+    values
+        .iter()
+        .map(|value| BARS[((value - min) as f64 / range * 7.0).round() as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_tokens() {
+        use ShellToken::*;
+        let tokens = shell_tokens(r#"CI=1 npm test -- --ci "$SPEC" 2>&1 | tee out.log # keep"#);
+        assert_eq!(
+            tokens
+                .into_iter()
+                .filter(|(_, text)| !text.trim().is_empty())
+                .collect::<Vec<_>>(),
+            vec![
+                (Plain, "CI=1"),
+                (Command, "npm"),
+                (Plain, "test"),
+                (Flag, "--"),
+                (Flag, "--ci"),
+                (Quoted, "\"$SPEC\""),
+                (Plain, "2"),
+                (Operator, ">&"),
+                (Plain, "1"),
+                (Operator, "|"),
+                (Command, "tee"),
+                (Plain, "out.log"),
+                (Comment, "# keep"),
+            ]
+        );
+        let variables: Vec<&str> = shell_tokens("echo $? ${HOME}/bin $(pwd)")
+            .into_iter()
+            .filter(|(token, _)| *token == Variable)
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(variables, vec!["$?", "${HOME}"]);
+    }
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_should_color() {
+        assert!(ColorChoice::Auto.should_color(env(&[]), true));
+        assert!(!ColorChoice::Auto.should_color(env(&[]), false));
+        assert!(!ColorChoice::Auto.should_color(env(&[("NO_COLOR", "1")]), true));
+        assert!(ColorChoice::Auto.should_color(env(&[("NO_COLOR", "")]), true));
+        assert!(ColorChoice::Auto.should_color(env(&[("CLICOLOR_FORCE", "1")]), false));
+        assert!(!ColorChoice::Auto.should_color(env(&[("CLICOLOR_FORCE", "0")]), false));
+        assert!(ColorChoice::Always.should_color(env(&[("NO_COLOR", "1")]), false));
+        assert!(!ColorChoice::Never.should_color(env(&[("CLICOLOR_FORCE", "1")]), true));
+    }
+
+    #[test]
+    fn test_role_resolve_falls_back_to_defaults() {
+        use highlight::Color;
+        let theme = Theme {
+            highlight: Some(RoleStyle {
+                color: Some(Color::Black),
+                background: Some(Color::BrightYellow),
+                style: Vec::new(),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            Role::Highlight.resolve(&theme).background,
+            Some(Color::BrightYellow)
+        );
+        assert_eq!(Role::Error.resolve(&theme), RoleStyle::color(Color::Red));
+        assert_eq!(
+            Role::Highlight.resolve(&Theme::default()).background,
+            Some(Color::Red)
+        );
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[100, 100, 150, 200]), "▁▁▅█");
+        assert_eq!(sparkline(&[7, 7]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,245 @@
+//! CODEOWNERS lookup.
+//!
+//! Maps files named in detected errors to the teams or users that own
+//! them, so triage knows who to ping. The file is read from the local
+//! checkout when it is the build's repository, otherwise from GitHub at
+//! the built revision.
+
+use crate::git;
+use crate::github::GitHubClient;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Locations GitHub checks for a CODEOWNERS file, in order.
+pub const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// A parsed CODEOWNERS file.
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    regex: Regex,
+    owners: Vec<String>,
+}
+
+/// The owners of one file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileOwners {
+    /// Repository-relative path.
+    pub path: String,
+    /// Owning teams and users, e.g. `@org/payments` or `@alice`.
+    pub owners: Vec<String>,
+}
+
+/// Converts a CODEOWNERS (gitignore-style) pattern to a regex over
+/// repository-relative paths.
+///
+/// Also used for artifact path globs.
+pub(crate) fn pattern_regex(pattern: &str) -> Result<Regex> {
+    let dir_only = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    // A slash at the start or in the middle anchors the pattern to the root
+    let anchored = trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+
+    let mut re = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // A match on a directory covers everything beneath it
+    re.push_str(if dir_only { "/.*$" } else { "(?:/.*)?$" });
+
+    Regex::new(&re).with_context(|| format!("invalid path pattern '{}'", pattern))
+}
+
+/// Turns a path from a log into a repository-relative one.
+///
+/// Strips the build container's checkout directory (`~/project` by
+/// default on CircleCI) and any leading `./` or `/`.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::codeowners::repo_relative;
+///
+/// assert_eq!(repo_relative("/home/circleci/project/src/a.ts"), "src/a.ts");
+/// assert_eq!(repo_relative("./src/a.ts"), "src/a.ts");
+/// ```
+pub fn repo_relative(path: &str) -> &str {
+    let path = match path.find("/project/") {
+        Some(i) => &path[i + "/project/".len()..],
+        None => path,
+    };
+    path.trim_start_matches("./").trim_start_matches('/')
+}
+
+impl CodeOwners {
+    /// Parses CODEOWNERS text. Comment lines and lines without owners are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern can't be converted to a regex.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.split(" #").next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            rules.push(Rule {
+                regex: pattern_regex(pattern)?,
+                owners: fields.map(str::to_string).collect(),
+            });
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Returns the owners of a repository-relative path.
+    ///
+    /// The last matching rule wins, as on GitHub. A matching rule without
+    /// owners means the path is deliberately unowned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::codeowners::CodeOwners;
+    ///
+    /// let owners = CodeOwners::parse("* @org/core\n/src/payments/ @org/payments @alice\n").unwrap();
+    ///
+    /// assert_eq!(owners.owners_of("src/payments/charge.ts"), ["@org/payments", "@alice"]);
+    /// assert_eq!(owners.owners_of("README.md"), ["@org/core"]);
+    /// ```
+    pub fn owners_of(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.regex.is_match(path))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Looks up the owners of files named in logs, dropping unowned ones.
+    pub fn owners_of_files(&self, paths: &[String]) -> Vec<FileOwners> {
+        let mut found: Vec<FileOwners> = Vec::new();
+        for path in paths {
+            let path = repo_relative(path);
+            let owners = self.owners_of(path);
+            if !owners.is_empty() && !found.iter().any(|f| f.path == path) {
+                found.push(FileOwners {
+                    path: path.to_string(),
+                    owners: owners.to_vec(),
+                });
+            }
+        }
+        found
+    }
+}
+
+/// Loads the CODEOWNERS file of a repository.
+///
+/// Reads it from the current checkout when its `origin` is `org/project`,
+/// otherwise fetches it from GitHub at `revision` (the default branch when
+/// `None`).
+///
+/// # Returns
+///
+/// `None` if the repository has no CODEOWNERS file.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or parsed.
+pub async fn load(org: &str, project: &str, revision: Option<&str>) -> Result<Option<CodeOwners>> {
+    let is_local = git::origin_repo()
+        .is_ok_and(|(o, p)| o.eq_ignore_ascii_case(org) && p.eq_ignore_ascii_case(project));
+    if is_local {
+        if let Ok(root) = git::toplevel() {
+            for location in LOCATIONS {
+                let path = root.join(location);
+                if path.exists() {
+                    let text = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    return CodeOwners::parse(&text).map(Some);
+                }
+            }
+            return Ok(None);
+        }
+    }
+
+    let github = GitHubClient::new();
+    for location in LOCATIONS {
+        if let Some(text) = github
+            .file_contents(org, project, location, revision)
+            .await?
+        {
+            return CodeOwners::parse(&text).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_semantics() {
+        let owners = CodeOwners::parse(
+            "# Default owners\n\
+             *                @org/everyone\n\
+             *.rs             @org/rust\n\
+             /docs/           @org/docs  # inline comment\n\
+             apps/**/test/    @org/qa\n\
+             /build/logs      @org/infra\n\
+             /vendor/\n",
+        )
+        .unwrap();
+
+        assert_eq!(owners.owners_of("src/main.rs"), ["@org/rust"]);
+        assert_eq!(owners.owners_of("docs/guide/intro.md"), ["@org/docs"]);
+        assert_eq!(owners.owners_of("sub/docs/readme.md"), ["@org/everyone"]);
+        assert_eq!(owners.owners_of("apps/web/unit/test/a.ts"), ["@org/qa"]);
+        assert_eq!(owners.owners_of("build/logs/out.txt"), ["@org/infra"]);
+        assert!(owners.owners_of("vendor/lib.js").is_empty());
+    }
+
+    #[test]
+    fn test_owners_of_files() {
+        let owners = CodeOwners::parse("/src/api/ @alice\n").unwrap();
+        let found = owners.owners_of_files(&[
+            "/home/circleci/project/src/api/client.ts".to_string(),
+            "src/api/client.ts".to_string(),
+            "src/ui/button.tsx".to_string(),
+        ]);
+
+        assert_eq!(
+            found,
+            vec![FileOwners {
+                path: "src/api/client.ts".to_string(),
+                owners: vec!["@alice".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Step timings of a branch against its base branch.
+//!
+//! [`CircleClient::compare_branches`] averages how long each step took
+//! over the recent builds of two branches, such as a pull request's branch
+//! and `main`, so a change that makes CI slower shows up before it's
+//! merged. [`compare_step_timings`] does the averaging; a step counts as
+//! slower only when every branch build ran it slower than the base's
+//! average, which keeps one slow machine from flagging it.
+
+use crate::history::{BuildFilter, BuildQuery};
+use crate::{BuildInfo, CircleClient};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Slowdowns smaller than this many milliseconds are noise, however large
+/// in percent.
+pub const MIN_SLOWDOWN_MILLIS: u64 = 5_000;
+
+/// Which builds to compare.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareQuery {
+    /// Branch to compare against, e.g. `main`.
+    pub base: String,
+    /// Branch under test.
+    pub branch: String,
+    /// Number of most recent finished builds of each branch to average.
+    pub builds: u32,
+    /// Only builds of this workflow job.
+    pub job: Option<String>,
+}
+
+/// How long one step took on both branches.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepComparison {
+    /// Workflow job the step belongs to, or `-` for builds without one.
+    pub job: String,
+    /// Step name.
+    pub name: String,
+    /// Average milliseconds on the base branch.
+    pub base_millis: u64,
+    /// Average milliseconds on the branch.
+    pub branch_millis: u64,
+    /// Number of base builds the step passed in.
+    pub base_runs: usize,
+    /// Number of branch builds the step passed in.
+    pub branch_runs: usize,
+    /// Number of those branch builds that ran it slower than the base's
+    /// average.
+    pub slower_runs: usize,
+}
+
+impl StepComparison {
+    /// Milliseconds the step got slower by; negative if it got faster.
+    pub fn growth_millis(&self) -> i64 {
+        self.branch_millis as i64 - self.base_millis as i64
+    }
+
+    /// Growth in percent of the base's average, if the base took any time.
+    pub fn growth_percent(&self) -> Option<f64> {
+        (self.base_millis > 0)
+            .then(|| self.growth_millis() as f64 / self.base_millis as f64 * 100.0)
+    }
+
+    /// Whether the step is consistently slower on the branch: every branch
+    /// build ran it slower than the base's average, by at least
+    /// `threshold_percent` and [`MIN_SLOWDOWN_MILLIS`] on average.
+    pub fn is_slower(&self, threshold_percent: f64) -> bool {
+        self.slower_runs == self.branch_runs
+            && self.growth_millis() >= MIN_SLOWDOWN_MILLIS as i64
+            && self
+                .growth_percent()
+                .is_some_and(|percent| percent >= threshold_percent)
+    }
+}
+
+/// Step timings of a branch against its base.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BranchComparison {
+    /// Base branch.
+    pub base: String,
+    /// Branch under test.
+    pub branch: String,
+    /// Base builds averaged, most recent first.
+    pub base_builds: Vec<u32>,
+    /// Branch builds averaged, most recent first.
+    pub branch_builds: Vec<u32>,
+    /// Steps that passed on both branches, the most slowed down first.
+    pub steps: Vec<StepComparison>,
+}
+
+impl BranchComparison {
+    /// Returns the steps consistently slower on the branch by at least
+    /// `threshold_percent`; see [`StepComparison::is_slower`].
+    pub fn slower_steps(&self, threshold_percent: f64) -> Vec<&StepComparison> {
+        self.steps
+            .iter()
+            .filter(|step| step.is_slower(threshold_percent))
+            .collect()
+    }
+}
+
+/// Returns the milliseconds each passed step of `build` took, by job and
+/// step name.
+///
+/// A parallel step takes as long as its slowest container. Steps that ran
+/// more than once under the same name, such as retries, are added up.
+fn passed_step_millis(build: &BuildInfo) -> HashMap<(String, String), u64> {
+    let job = build
+        .workflows
+        .as_ref()
+        .and_then(|w| w.job_name.as_deref())
+        .unwrap_or("-");
+    let mut millis: HashMap<(String, String), u64> = HashMap::new();
+    for step in &build.steps {
+        let passed = !step.actions.is_empty()
+            && step.actions.iter().all(|action| action.status == "success");
+        let Some(wall) = step.actions.iter().filter_map(|a| a.run_time_millis).max() else {
+            continue;
+        };
+        if passed {
+            *millis
+                .entry((job.to_string(), step.name.clone()))
+                .or_default() += wall;
+        }
+    }
+    millis
+}
+
+/// Averages the step timings of `base` and `branch` builds and compares
+/// them, for the steps that passed in builds of both.
+///
+/// Failed, canceled, and untimed steps are left out, since they didn't
+/// run to completion.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::compare::compare_step_timings;
+///
+/// let build = |num: u32, millis: u64| serde_json::from_value(serde_json::json!({
+///     "build_num": num, "status": "success", "branch": null, "subject": null,
+///     "steps": [{"name": "npm test", "actions": [{
+///         "name": "npm test", "status": "success", "failed": false, "output_url": null,
+///         "type": "test", "run_time_millis": millis
+///     }]}]
+/// }))
+/// .unwrap();
+/// let steps = compare_step_timings(
+///     &[build(1, 60_000), build(2, 80_000)],
+///     &[build(3, 100_000), build(4, 110_000)],
+/// );
+/// assert_eq!(steps[0].base_millis, 70_000);
+/// assert_eq!(steps[0].branch_millis, 105_000);
+/// assert!(steps[0].is_slower(20.0));
+/// ```
+pub fn compare_step_timings(base: &[BuildInfo], branch: &[BuildInfo]) -> Vec<StepComparison> {
+    let collect = |builds: &[BuildInfo]| {
+        let mut runs: HashMap<(String, String), Vec<u64>> = HashMap::new();
+        for build in builds {
+            for (key, millis) in passed_step_millis(build) {
+                runs.entry(key).or_default().push(millis);
+            }
+        }
+        runs
+    };
+    let average = |runs: &[u64]| runs.iter().sum::<u64>() / runs.len() as u64;
+    let base = collect(base);
+    let branch = collect(branch);
+
+    let mut steps: Vec<StepComparison> = branch
+        .into_iter()
+        .filter_map(|(key, branch_runs)| {
+            let base_runs = base.get(&key)?;
+            let base_millis = average(base_runs);
+            let (job, name) = key;
+            Some(StepComparison {
+                job,
+                name,
+                base_millis,
+                branch_millis: average(&branch_runs),
+                base_runs: base_runs.len(),
+                branch_runs: branch_runs.len(),
+                slower_runs: branch_runs.iter().filter(|&&m| m > base_millis).count(),
+            })
+        })
+        .collect();
+    steps.sort_by(|a, b| {
+        b.growth_millis()
+            .cmp(&a.growth_millis())
+            .then_with(|| (&a.job, &a.name).cmp(&(&b.job, &b.name)))
+    });
+    steps
+}
+
+impl CircleClient {
+    /// Compares the step timings of the recent finished builds of
+    /// `query.branch` with those of `query.base`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either branch's builds can't be listed. Builds
+    /// whose details can't be fetched are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::compare::CompareQuery;
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let query = CompareQuery {
+    ///     base: "main".to_string(),
+    ///     branch: "feature/faster-tests".to_string(),
+    ///     builds: 10,
+    ///     job: Some("test".to_string()),
+    /// };
+    /// let comparison = client.compare_branches("myorg", "myrepo", &query).await?;
+    /// for step in comparison.slower_steps(20.0) {
+    ///     println!("{}: {} → {} ms", step.name, step.base_millis, step.branch_millis);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn compare_branches(
+        &self,
+        org: &str,
+        project: &str,
+        query: &CompareQuery,
+    ) -> Result<BranchComparison> {
+        let (base, branch) = futures::future::try_join(
+            self.branch_builds(org, project, &query.base, query),
+            self.branch_builds(org, project, &query.branch, query),
+        )
+        .await?;
+
+        Ok(BranchComparison {
+            base: query.base.clone(),
+            branch: query.branch.clone(),
+            base_builds: base.iter().map(|build| build.build_num).collect(),
+            branch_builds: branch.iter().map(|build| build.build_num).collect(),
+            steps: compare_step_timings(&base, &branch),
+        })
+    }
+
+    /// Fetches the recent finished builds of `branch`, with their steps.
+    async fn branch_builds(
+        &self,
+        org: &str,
+        project: &str,
+        branch: &str,
+        query: &CompareQuery,
+    ) -> Result<Vec<BuildInfo>> {
+        let listed = self
+            .recent_builds(
+                org,
+                project,
+                &BuildQuery {
+                    branch: Some(branch.to_string()),
+                    filter: BuildFilter::Completed,
+                    limit: query.builds,
+                    offset: 0,
+                },
+            )
+            .await
+            .with_context(|| format!("Failed to list recent builds of {}", branch))?;
+        let listed: Vec<_> = listed
+            .into_iter()
+            .filter(|build| {
+                query.job.as_deref().is_none_or(|job| {
+                    build.workflows.as_ref().and_then(|w| w.job_name.as_deref()) == Some(job)
+                })
+            })
+            .collect();
+
+        let builds = futures::future::join_all(
+            listed
+                .iter()
+                .map(|build| self.get_build(org, project, build.build_num)),
+        )
+        .await;
+        Ok(listed
+            .iter()
+            .zip(builds)
+            .filter_map(|(listed, build)| match build {
+                Ok(build) => Some(build),
+                Err(e) => {
+                    tracing::debug!(build = listed.build_num, "skipping build: {:#}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, StepBuilder};
+
+    fn build(num: u32, steps: &[(&str, u64, bool)]) -> BuildInfo {
+        let mut build = BuildBuilder::new(num).workflow("ci", "test");
+        for (name, millis, failed) in steps {
+            let action = ActionBuilder::new(name).run_time_millis(*millis);
+            build = build.step(StepBuilder::new(name).action(if *failed {
+                action.failed()
+            } else {
+                action
+            }));
+        }
+        build.build()
+    }
+
+    #[test]
+    fn test_compare_step_timings() {
+        let base = [
+            build(
+                1,
+                &[("Install", 30_000, false), ("Run tests", 100_000, false)],
+            ),
+            build(
+                2,
+                &[("Install", 40_000, false), ("Run tests", 120_000, false)],
+            ),
+        ];
+        let branch = [
+            // Failed steps don't count
+            build(
+                3,
+                &[("Install", 35_000, false), ("Run tests", 10_000, true)],
+            ),
+            build(
+                4,
+                &[("Install", 34_000, false), ("Run tests", 150_000, false)],
+            ),
+            build(
+                5,
+                &[("Install", 36_000, false), ("Run tests", 170_000, false)],
+            ),
+            build(6, &[("Lint", 5_000, false)]),
+        ];
+
+        let steps = compare_step_timings(&base, &branch);
+        assert_eq!(
+            steps,
+            vec![
+                StepComparison {
+                    job: "test".to_string(),
+                    name: "Run tests".to_string(),
+                    base_millis: 110_000,
+                    branch_millis: 160_000,
+                    base_runs: 2,
+                    branch_runs: 2,
+                    slower_runs: 2,
+                },
+                StepComparison {
+                    job: "test".to_string(),
+                    name: "Install".to_string(),
+                    base_millis: 35_000,
+                    branch_millis: 35_000,
+                    base_runs: 2,
+                    branch_runs: 3,
+                    slower_runs: 1,
+                },
+            ]
+        );
+        assert!(steps[0].is_slower(20.0));
+        assert!(!steps[0].is_slower(50.0));
+        assert!(!steps[1].is_slower(0.0));
+    }
+}
@@ -0,0 +1,618 @@
+//! User configuration loaded from a TOML file.
+//!
+//! The default location is `<config dir>/cdb/config.toml` (for example
+//! `~/.config/cdb/config.toml` on Linux). A missing default file is not an
+//! error; a missing file passed explicitly is.
+//!
+//! A repository can commit its own [`PROJECT_FILE`] with the same format.
+//! It is found by walking up from the working directory and layered over
+//! the user's file: its patterns replace user patterns with the same id,
+//! its disabled patterns add to the user's, its suggestions and highlights
+//! are checked first, and its defaults and theme roles win.
+//!
+//! ```toml
+//! disabled_patterns = ["non-zero-exit", "command-failure"]
+//!
+//! [defaults]
+//! repo = "myorg/myrepo"
+//! format = "json"
+//! package = "@myorg/api"
+//! concurrency = 4
+//! tail_default = 100
+//! max_errors = 10
+//! smart_window = true
+//! cache_max_mb = 512
+//!
+//! [[patterns]]
+//! id = "flaky-redis"
+//! category = "Redis Connection"
+//! regex = "ECONNREFUSED 127\\.0\\.0\\.1:6379"
+//! severity = "warning"
+//! confidence = 0.7
+//! kind = "infra"
+//! transient = true
+//!
+//! [[suggestions]]
+//! pattern = "flaky-redis"
+//! text = "Redis wasn't ready; ping #platform-ci if this keeps happening"
+//!
+//! [[highlights]]
+//! regex = "req-[0-9a-f]{8}"
+//! color = "magenta"
+//! style = ["bold"]
+//!
+//! [theme]
+//! highlight = { color = "black", background = "bright_yellow" }
+//! ```
+//!
+//! See [`crate::highlight`] for the colors and styles of `[[highlights]]`
+//! and [`crate::theme`] for the roles of `[theme]`.
+
+use crate::highlight::{HighlightConfig, HighlightRule};
+use crate::patterns::{self, ErrorPattern, FailureKind, Severity, DEFAULT_CONFIDENCE};
+use crate::suggestions::{Suggestion, SuggestionRegistry};
+use crate::theme::Theme;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-local configuration file.
+pub const PROJECT_FILE: &str = ".cdb.toml";
+
+/// Parsed contents of the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Extra error patterns checked alongside the built-in set.
+    #[serde(default)]
+    pub patterns: Vec<PatternConfig>,
+    /// Ids of built-in or configured patterns smart detection skips.
+    #[serde(default)]
+    pub disabled_patterns: Vec<String>,
+    /// Extra suggestions, checked before the built-in ones.
+    #[serde(default)]
+    pub suggestions: Vec<SuggestionConfig>,
+    /// Rules coloring parts of printed log lines.
+    #[serde(default)]
+    pub highlights: Vec<HighlightConfig>,
+    /// Values used when the matching command-line option isn't given.
+    #[serde(default)]
+    pub defaults: Defaults,
+    /// Colors of errors, warnings, and the other output roles.
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+/// The `[defaults]` table, written by `cdb init`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    /// Repository as `org/repo` for commands that take `--repo`.
+    pub repo: Option<String>,
+    /// Output format for `cdb build`, like `--format`: `text`, `json`,
+    /// `gh-annotations`, or `csv`.
+    pub format: Option<String>,
+    /// Only show log lines containing this text, like `--filter`.
+    pub filter: Option<String>,
+    /// Only show the lines of this monorepo package, like `--package`.
+    pub package: Option<String>,
+    /// Most API requests in flight at once, like `--concurrency`.
+    pub concurrency: Option<usize>,
+    /// Lines of the exit zone shown after smart detection, like
+    /// `--tail-default`.
+    pub tail_default: Option<usize>,
+    /// Most error patterns smart detection shows, like `--max-errors`.
+    pub max_errors: Option<usize>,
+    /// Whether the exit zone reaches back to the earliest detected error,
+    /// like `--smart-window`.
+    pub smart_window: Option<bool>,
+    /// Megabytes the response cache is pruned to, see
+    /// [`cache`](crate::cache).
+    pub cache_max_mb: Option<u64>,
+}
+
+/// An error pattern defined in the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternConfig {
+    /// Stable identifier; reusing a built-in id replaces that pattern.
+    pub id: String,
+    /// Label shown in the smart detection output.
+    pub category: String,
+    /// Regex matched against each log line.
+    pub regex: String,
+    /// Defaults to `error`.
+    #[serde(default)]
+    pub severity: Severity,
+    /// Defaults to [`DEFAULT_CONFIDENCE`].
+    pub confidence: Option<f32>,
+    /// Defaults to `code`.
+    #[serde(default)]
+    pub kind: FailureKind,
+    /// Whether a rerun is likely to fix it. Defaults to `false`.
+    #[serde(default)]
+    pub transient: bool,
+}
+
+/// A fix suggestion defined in the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuggestionConfig {
+    /// Id of the pattern this suggestion applies to.
+    pub pattern: String,
+    /// Only suggest when the matched line also matches this regex.
+    pub when: Option<String>,
+    /// Suggestion text; `{name}` or `{1}` insert regex capture groups.
+    pub text: String,
+}
+
+impl Config {
+    /// Returns the default configuration file path, if the platform has one.
+    pub fn default_path() -> Option<PathBuf> {
+        crate::paths::config_file()
+    }
+
+    /// Loads the configuration.
+    ///
+    /// With `path` set the file must exist. Otherwise the default path is
+    /// tried and an empty configuration is returned if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not valid TOML.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        tracing::debug!(path = %path.display(), "loading config");
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::from_toml(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Loads the user configuration as [`load`](Self::load) does, then
+    /// layers the nearest [`PROJECT_FILE`] at or above `dir` over it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either file cannot be read or is invalid.
+    pub fn load_with_project(path: Option<&Path>, dir: &Path) -> Result<Self> {
+        let user = Self::load(path)?;
+        match Self::find_project_file(dir) {
+            Some(project) => {
+                tracing::debug!(path = %project.display(), "loading project config");
+                let contents = std::fs::read_to_string(&project).with_context(|| {
+                    format!("Failed to read project config {}", project.display())
+                })?;
+                let project_config = Self::from_toml(&contents)
+                    .with_context(|| format!("Invalid project config {}", project.display()))?;
+                Ok(user.layered(project_config))
+            }
+            None => Ok(user),
+        }
+    }
+
+    /// Returns the nearest [`PROJECT_FILE`] in `dir` or its ancestors.
+    pub fn find_project_file(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(PROJECT_FILE))
+            .find(|path| path.is_file())
+    }
+
+    /// Returns this configuration with `project` layered over it.
+    fn layered(mut self, project: Config) -> Config {
+        // Later patterns replace earlier ones with the same id
+        self.patterns.extend(project.patterns);
+        self.disabled_patterns.extend(project.disabled_patterns);
+        // Earlier suggestions are checked first
+        let mut suggestions = project.suggestions;
+        suggestions.append(&mut self.suggestions);
+        self.suggestions = suggestions;
+        let mut highlights = project.highlights;
+        highlights.append(&mut self.highlights);
+        self.highlights = highlights;
+        self.defaults = Defaults {
+            repo: project.defaults.repo.or(self.defaults.repo),
+            format: project.defaults.format.or(self.defaults.format),
+            filter: project.defaults.filter.or(self.defaults.filter),
+            package: project.defaults.package.or(self.defaults.package),
+            concurrency: project.defaults.concurrency.or(self.defaults.concurrency),
+            tail_default: project.defaults.tail_default.or(self.defaults.tail_default),
+            max_errors: project.defaults.max_errors.or(self.defaults.max_errors),
+            smart_window: project.defaults.smart_window.or(self.defaults.smart_window),
+            cache_max_mb: project.defaults.cache_max_mb.or(self.defaults.cache_max_mb),
+        };
+        self.theme = self.theme.overridden_by(project.theme);
+        self
+    }
+
+    /// Parses a configuration from TOML text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not a valid configuration.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Sets the `[defaults]` table of a configuration file's text.
+    ///
+    /// Everything else in the file, including comments, is kept. `None`
+    /// values are removed from the table.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `contents` is not valid TOML.
+    pub fn with_defaults(contents: &str, defaults: &Defaults) -> Result<String> {
+        let mut doc: toml_edit::DocumentMut = contents.parse()?;
+        let table = doc
+            .entry("defaults")
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .context("'defaults' must be a table")?;
+        for (key, value) in [
+            ("repo", &defaults.repo),
+            ("format", &defaults.format),
+            ("filter", &defaults.filter),
+            ("package", &defaults.package),
+        ] {
+            match value {
+                Some(value) => table[key] = toml_edit::value(value.as_str()),
+                None => {
+                    table.remove(key);
+                }
+            }
+        }
+        Ok(doc.to_string())
+    }
+
+    /// Returns the built-in patterns combined with the configured ones,
+    /// without the disabled ones.
+    ///
+    /// A configured pattern with the same id as a built-in one replaces it
+    /// in place; new ids are appended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configured regex is invalid, a confidence is
+    /// outside `0.0..=1.0`, or a disabled id names no pattern.
+    pub fn patterns(&self) -> Result<Vec<ErrorPattern>> {
+        let mut all = patterns::builtin_patterns();
+
+        for pattern in self.configured_patterns()? {
+            match all.iter_mut().find(|p| p.id == pattern.id) {
+                Some(existing) => *existing = pattern,
+                None => all.push(pattern),
+            }
+        }
+
+        if let Some(unknown) = self
+            .disabled_patterns
+            .iter()
+            .find(|id| !all.iter().any(|p| p.id == **id))
+        {
+            bail!(
+                "Can't disable unknown pattern '{}'; run `cdb patterns list` to see the ids",
+                unknown
+            );
+        }
+        all.retain(|p| !self.disabled_patterns.contains(&p.id));
+        Ok(all)
+    }
+
+    /// Compiles just the configured patterns, in file order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configured regex is invalid or a confidence
+    /// is outside `0.0..=1.0`.
+    pub fn configured_patterns(&self) -> Result<Vec<ErrorPattern>> {
+        self.patterns.iter().map(PatternConfig::compile).collect()
+    }
+
+    /// Returns the built-in suggestions with the configured ones in front.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configured `when` regex is invalid.
+    pub fn suggestions(&self) -> Result<SuggestionRegistry> {
+        let configured = self
+            .suggestions
+            .iter()
+            .map(|s| Suggestion::new(&s.pattern, s.when.as_deref(), &s.text))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut registry = SuggestionRegistry::builtin();
+        registry.prepend(configured);
+        Ok(registry)
+    }
+
+    /// Compiles the configured highlight rules, in order of precedence.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a rule's regex is invalid or it sets no color
+    /// or style.
+    pub fn highlights(&self) -> Result<Vec<HighlightRule>> {
+        self.highlights
+            .iter()
+            .map(HighlightConfig::compile)
+            .collect()
+    }
+}
+
+impl PatternConfig {
+    /// Compiles this entry into an [`ErrorPattern`].
+    fn compile(&self) -> Result<ErrorPattern> {
+        let confidence = self.confidence.unwrap_or(DEFAULT_CONFIDENCE);
+        if !(0.0..=1.0).contains(&confidence) {
+            bail!(
+                "Confidence for pattern '{}' must be between 0.0 and 1.0, got {}",
+                self.id,
+                confidence
+            );
+        }
+
+        Ok(ErrorPattern {
+            severity: self.severity,
+            confidence,
+            kind: self.kind,
+            transient: self.transient,
+            ..ErrorPattern::new(&self.id, &self.category, &self.regex)?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlight::{Color, Style};
+    use crate::theme::RoleStyle;
+
+    #[test]
+    fn test_config_patterns() {
+        let config = Config::from_toml(
+            r#"
+            [[patterns]]
+            id = "flaky-redis"
+            category = "Redis Connection"
+            regex = "ECONNREFUSED .*:6379"
+            severity = "warning"
+            confidence = 0.7
+            kind = "infra"
+
+            [[patterns]]
+            id = "npm-error"
+            category = "npm"
+            regex = "npm ERR!"
+            "#,
+        )
+        .unwrap();
+
+        let patterns = config.patterns().unwrap();
+        let redis = patterns.iter().find(|p| p.id == "flaky-redis").unwrap();
+        assert_eq!(redis.severity, Severity::Warning);
+        assert_eq!(redis.confidence, 0.7);
+        assert_eq!(redis.kind, FailureKind::Infra);
+
+        let npm: Vec<_> = patterns.iter().filter(|p| p.id == "npm-error").collect();
+        assert_eq!(npm.len(), 1);
+        assert_eq!(npm[0].severity, Severity::Error);
+        assert_eq!(npm[0].confidence, DEFAULT_CONFIDENCE);
+
+        let configured: Vec<_> = config
+            .configured_patterns()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(configured, vec!["flaky-redis", "npm-error"]);
+    }
+
+    #[test]
+    fn test_config_disabled_patterns() {
+        let mut config = Config::from_toml(
+            r#"
+            disabled_patterns = ["non-zero-exit"]
+            "#,
+        )
+        .unwrap();
+        config.disabled_patterns.push("command-failure".to_string());
+
+        let ids: Vec<String> = config
+            .patterns()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert!(!ids
+            .iter()
+            .any(|id| id == "non-zero-exit" || id == "command-failure"));
+        assert!(ids.iter().any(|id| id == "npm-error"));
+
+        config.disabled_patterns.push("no-such-pattern".to_string());
+        assert!(config.patterns().is_err());
+    }
+
+    #[test]
+    fn test_config_suggestions() {
+        let config = Config::from_toml(
+            r#"
+            [[suggestions]]
+            pattern = "segfault"
+            text = "Ping #platform-ci for this one"
+            "#,
+        )
+        .unwrap();
+
+        let registry = config.suggestions().unwrap();
+        let segfault = patterns::builtin_patterns()
+            .into_iter()
+            .find(|p| p.id == "segfault")
+            .unwrap();
+        assert_eq!(
+            registry.suggest(&segfault, "Segmentation fault").as_deref(),
+            Some("Ping #platform-ci for this one")
+        );
+    }
+
+    #[test]
+    fn test_config_rejects_bad_confidence() {
+        let config = Config::from_toml(
+            r#"
+            [[patterns]]
+            id = "x"
+            category = "X"
+            regex = "x"
+            confidence = 1.5
+            "#,
+        )
+        .unwrap();
+        assert!(config.patterns().is_err());
+    }
+
+    #[test]
+    fn test_config_explicit_missing_file() {
+        assert!(Config::load(Some(Path::new("/nonexistent/cdb.toml"))).is_err());
+    }
+
+    #[test]
+    fn test_config_with_defaults_keeps_the_rest() {
+        let original = "# my patterns\n[[patterns]]\nid = \"x\"\ncategory = \"X\"\nregex = \"x\"\n";
+        let defaults = Defaults {
+            repo: Some("org/repo".to_string()),
+            ..Defaults::default()
+        };
+        let updated = Config::with_defaults(original, &defaults).unwrap();
+        assert!(updated.starts_with("# my patterns"));
+
+        let config = Config::from_toml(&updated).unwrap();
+        assert_eq!(config.defaults, defaults);
+        assert_eq!(config.patterns.len(), 1);
+
+        let cleared = Config::with_defaults(&updated, &Defaults::default()).unwrap();
+        assert_eq!(
+            Config::from_toml(&cleared).unwrap().defaults,
+            Defaults::default()
+        );
+    }
+
+    #[test]
+    fn test_project_config_layers_over_user_config() {
+        let root = std::env::temp_dir().join(format!("cdb-project-test-{}", std::process::id()));
+        let nested = root.join("packages").join("api");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(Config::find_project_file(&nested), None);
+
+        let user_path = root.join("user.toml");
+        std::fs::write(
+            &user_path,
+            r#"
+            [defaults]
+            repo = "me/fork"
+            format = "json"
+            concurrency = 4
+            max_errors = 8
+            tail_default = 80
+
+            [[patterns]]
+            id = "flaky-redis"
+            category = "User Redis"
+            regex = "redis"
+
+            [[suggestions]]
+            pattern = "flaky-redis"
+            text = "user tip"
+
+            [[highlights]]
+            regex = "user"
+            color = "red"
+
+            [theme]
+            error = { color = "bright_red" }
+            highlight = { background = "red" }
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join(PROJECT_FILE),
+            r#"
+            [defaults]
+            repo = "acme/monorepo"
+            filter = "@acme/api"
+            package = "api"
+            tail_default = 120
+            smart_window = true
+
+            [[patterns]]
+            id = "flaky-redis"
+            category = "Project Redis"
+            regex = "redis"
+
+            [[suggestions]]
+            pattern = "flaky-redis"
+            text = "project tip"
+
+            [[highlights]]
+            regex = "req-[0-9a-f]+"
+            color = "magenta"
+            style = ["bold"]
+
+            [theme]
+            highlight = { color = "black", background = "bright_yellow", style = ["bold"] }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config::find_project_file(&nested),
+            Some(root.join(PROJECT_FILE))
+        );
+        let config = Config::load_with_project(Some(&user_path), &nested).unwrap();
+        assert_eq!(config.defaults.repo.as_deref(), Some("acme/monorepo"));
+        assert_eq!(config.defaults.format.as_deref(), Some("json"));
+        assert_eq!(config.defaults.filter.as_deref(), Some("@acme/api"));
+        assert_eq!(config.defaults.package.as_deref(), Some("api"));
+        assert_eq!(config.defaults.concurrency, Some(4));
+        assert_eq!(config.defaults.max_errors, Some(8));
+        assert_eq!(config.defaults.tail_default, Some(120));
+        assert_eq!(config.defaults.smart_window, Some(true));
+        assert_eq!(config.theme.error, Some(RoleStyle::color(Color::BrightRed)));
+        assert_eq!(
+            config.theme.highlight,
+            Some(RoleStyle {
+                color: Some(Color::Black),
+                background: Some(Color::BrightYellow),
+                style: vec![Style::Bold],
+            })
+        );
+
+        let redis: Vec<_> = config
+            .patterns()
+            .unwrap()
+            .into_iter()
+            .filter(|p| p.id == "flaky-redis")
+            .collect();
+        assert_eq!(redis.len(), 1);
+        assert_eq!(redis[0].category, "Project Redis");
+        assert_eq!(
+            config
+                .suggestions()
+                .unwrap()
+                .suggest(&redis[0], "redis")
+                .as_deref(),
+            Some("project tip")
+        );
+        let highlights = config.highlights().unwrap();
+        let regexes: Vec<&str> = highlights.iter().map(|h| h.regex.as_str()).collect();
+        assert_eq!(regexes, vec!["req-[0-9a-f]+", "user"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+//! Credit cost estimates.
+//!
+//! CircleCI bills credits per minute of every container, at a rate that
+//! depends on the resource class. [`estimate`] multiplies a build's billable
+//! minutes by its parallelism and the published rate for its class. Plans
+//! with negotiated rates will differ, so treat the result as an estimate.
+
+use crate::BuildInfo;
+use serde::{Deserialize, Serialize};
+
+/// Price of one credit in US dollars on the public plans.
+pub const USD_PER_CREDIT: f64 = 0.0006;
+
+/// Resource class assumed when the API doesn't report one (Docker's default).
+pub const DEFAULT_RESOURCE_CLASS: &str = "medium";
+
+/// Published credits-per-minute rates by resource class name.
+const CREDITS_PER_MINUTE: &[(&str, f64)] = &[
+    // Docker and Linux machine
+    ("small", 5.0),
+    ("medium", 10.0),
+    ("medium+", 15.0),
+    ("large", 20.0),
+    ("xlarge", 40.0),
+    ("2xlarge", 80.0),
+    ("2xlarge+", 100.0),
+    // Arm
+    ("arm.medium", 10.0),
+    ("arm.large", 20.0),
+    ("arm.xlarge", 40.0),
+    ("arm.2xlarge", 80.0),
+    // Windows
+    ("windows.medium", 40.0),
+    ("windows.large", 120.0),
+    ("windows.xlarge", 210.0),
+    ("windows.2xlarge", 500.0),
+    // macOS
+    ("macos.m1.medium.gen1", 150.0),
+    ("macos.m1.large.gen1", 250.0),
+];
+
+/// Execution environment details reported by the v1.1 API as `picard`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Picard {
+    /// Executor type, e.g. "docker", "machine", or "macos".
+    pub executor: Option<String>,
+    /// Resource class the build ran on.
+    pub resource_class: Option<ResourceClass>,
+}
+
+/// A resource class as reported by the API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ResourceClass {
+    /// Class name, e.g. "medium" or "arm.large".
+    pub class: Option<String>,
+    /// Number of vCPUs.
+    pub cpu: Option<f64>,
+    /// Memory in MB.
+    pub ram: Option<u64>,
+}
+
+/// Estimated credit cost of a build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CostEstimate {
+    /// Resource class the rate is based on.
+    pub resource_class: String,
+    /// Whether the resource class was assumed because the API omitted it.
+    pub assumed_class: bool,
+    /// Number of containers the build ran on.
+    pub parallelism: u32,
+    /// Credits charged per minute per container.
+    pub credits_per_minute: f64,
+    /// Billed minutes per container; partial minutes round up.
+    pub billable_minutes: u64,
+    /// Total estimated credits.
+    pub credits: f64,
+    /// Estimated cost in US dollars at [`USD_PER_CREDIT`].
+    pub usd: f64,
+}
+
+/// Returns the published credits-per-minute rate for a resource class.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::cost::credits_per_minute;
+///
+/// assert_eq!(credits_per_minute("large"), Some(20.0));
+/// assert_eq!(credits_per_minute("gpu.nvidia.unknown"), None);
+/// ```
+pub fn credits_per_minute(resource_class: &str) -> Option<f64> {
+    CREDITS_PER_MINUTE
+        .iter()
+        .find(|(class, _)| *class == resource_class)
+        .map(|(_, rate)| *rate)
+}
+
+/// Estimates the credit cost of a build.
+///
+/// Returns `None` when the build has no recorded run time or runs on a
+/// resource class without a known rate.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # async fn example() -> Result<()> {
+/// use circle_debug::{cost, CircleClient};
+///
+/// let build = CircleClient::new()?.get_build("org", "repo", 123).await?;
+/// if let Some(cost) = cost::estimate(&build) {
+///     println!("~{:.0} credits (${:.2})", cost.credits, cost.usd);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn estimate(build: &BuildInfo) -> Option<CostEstimate> {
+    let millis = build.build_time_millis?;
+    let class = build
+        .picard
+        .as_ref()
+        .and_then(|picard| picard.resource_class.as_ref())
+        .and_then(|resource_class| resource_class.class.clone());
+    let assumed_class = class.is_none();
+    let resource_class = class.unwrap_or_else(|| DEFAULT_RESOURCE_CLASS.to_string());
+
+    let rate = credits_per_minute(&resource_class)?;
+    let parallelism = build.parallel.unwrap_or(1).max(1);
+    let billable_minutes = millis.div_ceil(60_000).max(1);
+    let credits = rate * billable_minutes as f64 * parallelism as f64;
+
+    Some(CostEstimate {
+        resource_class,
+        assumed_class,
+        parallelism,
+        credits_per_minute: rate,
+        billable_minutes,
+        credits,
+        usd: credits * USD_PER_CREDIT,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(json: &str) -> BuildInfo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_estimate_uses_class_and_parallelism() {
+        let build = build(
+            r#"{"build_num": 1, "status": "failed", "branch": null, "subject": null, "steps": [],
+                "build_time_millis": 150000, "parallel": 4,
+                "picard": {"executor": "docker", "resource_class": {"class": "large", "cpu": 4.0, "ram": 8192}}}"#,
+        );
+        let cost = estimate(&build).unwrap();
+
+        assert_eq!(cost.billable_minutes, 3);
+        assert_eq!(cost.parallelism, 4);
+        assert_eq!(cost.credits, 240.0);
+        assert!(!cost.assumed_class);
+        assert!((cost.usd - 0.144).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_assumes_default_class() {
+        let build = build(
+            r#"{"build_num": 1, "status": "success", "branch": null, "subject": null, "steps": [],
+                "build_time_millis": 1000}"#,
+        );
+        let cost = estimate(&build).unwrap();
+
+        assert!(cost.assumed_class);
+        assert_eq!(cost.resource_class, "medium");
+        assert_eq!(cost.credits, 10.0);
+    }
+
+    #[test]
+    fn test_estimate_unknown_class() {
+        let build = build(
+            r#"{"build_num": 1, "status": "success", "branch": null, "subject": null, "steps": [],
+                "build_time_millis": 1000,
+                "picard": {"resource_class": {"class": "gpu.nvidia.custom"}}}"#,
+        );
+        assert!(estimate(&build).is_none());
+    }
+}
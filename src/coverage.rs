@@ -0,0 +1,422 @@
+//! Line coverage of a build, from coverage artifacts or log summaries.
+//!
+//! A build's coverage comes from the first of:
+//!
+//! - lcov (`lcov.info`, `*.lcov`) or Cobertura (`cobertura*.xml`,
+//!   `coverage.xml`) reports stored as artifacts, merged across parallel
+//!   containers
+//! - the summary a coverage tool printed at the end of a test step: Jest
+//!   and nyc (`All files | ... |`, `Lines : 81.2% ( 812/1000 )`),
+//!   coverage.py and `cargo llvm-cov` (`TOTAL ... 81%`), tarpaulin
+//!   (`81.23% coverage, 812/1000 lines covered`), `go tool cover`
+//!   (`total: (statements) 81.2%`), and Jest's threshold failures
+//!
+//! [`CoverageCheck`] compares it with the build's previous passing build
+//! and an optional threshold, for `cdb build --coverage-threshold`.
+
+use crate::artifacts::Artifact;
+use crate::search::strip_ansi;
+use crate::{BuildInfo, CircleClient};
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How much of a step's log is fetched to find its coverage summary,
+/// which tools print at the end.
+pub const SUMMARY_TAIL_BYTES: u64 = 64 * 1024;
+
+/// Line coverage of a build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Coverage {
+    /// Percentage of lines covered.
+    pub percent: f64,
+    /// Covered lines, when the source counts them.
+    pub covered: Option<u64>,
+    /// Instrumented lines, when the source counts them.
+    pub total: Option<u64>,
+    /// Where it came from: the report's artifact path, or the step whose
+    /// log printed it.
+    pub source: String,
+}
+
+impl Coverage {
+    fn from_counts(covered: u64, total: u64, source: &str) -> Option<Self> {
+        (total > 0).then(|| Coverage {
+            percent: covered as f64 * 100.0 / total as f64,
+            covered: Some(covered),
+            total: Some(total),
+            source: source.to_string(),
+        })
+    }
+
+    fn from_percent(percent: &str, source: &str) -> Option<Self> {
+        Some(Coverage {
+            percent: percent.parse().ok()?,
+            covered: None,
+            total: None,
+            source: source.to_string(),
+        })
+    }
+}
+
+/// A build's coverage next to its previous passing build's and a
+/// threshold.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoverageCheck {
+    /// Coverage of the analyzed build.
+    pub coverage: Coverage,
+    /// Coverage of the previous passing build of the same branch and job.
+    pub baseline: Option<Coverage>,
+    /// Number of the baseline build.
+    pub baseline_build: Option<u32>,
+    /// Minimum percentage from `--coverage-threshold`.
+    pub threshold: Option<f64>,
+}
+
+impl CoverageCheck {
+    /// Returns the change in percentage points since the baseline.
+    pub fn delta(&self) -> Option<f64> {
+        self.baseline
+            .as_ref()
+            .map(|baseline| self.coverage.percent - baseline.percent)
+    }
+
+    /// Returns `true` if coverage is under the threshold.
+    pub fn below_threshold(&self) -> bool {
+        self.threshold
+            .is_some_and(|threshold| self.coverage.percent < threshold)
+    }
+}
+
+/// Coverage report formats that can be read from artifacts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportKind {
+    Lcov,
+    Cobertura,
+}
+
+/// Recognizes a coverage report by its file name.
+fn report_kind(path: &str) -> Option<ReportKind> {
+    let name = path.rsplit('/').next().unwrap_or(path).to_ascii_lowercase();
+    if name == "lcov.info" || name.ends_with(".lcov") {
+        Some(ReportKind::Lcov)
+    } else if name.ends_with(".xml") && (name.contains("cobertura") || name == "coverage.xml") {
+        Some(ReportKind::Cobertura)
+    } else {
+        None
+    }
+}
+
+/// Returns the `(covered, total)` lines of an lcov tracefile.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::coverage::parse_lcov;
+///
+/// let lcov = "SF:src/a.ts\nLF:10\nLH:8\nend_of_record\nSF:src/b.ts\nLF:30\nLH:12\nend_of_record\n";
+/// assert_eq!(parse_lcov(lcov), Some((20, 40)));
+/// ```
+pub fn parse_lcov(text: &str) -> Option<(u64, u64)> {
+    let (mut covered, mut total, mut found) = (0, 0, false);
+    for line in text.lines() {
+        if let Some(n) = line.strip_prefix("LF:") {
+            total += n.trim().parse::<u64>().ok()?;
+            found = true;
+        } else if let Some(n) = line.strip_prefix("LH:") {
+            covered += n.trim().parse::<u64>().ok()?;
+        }
+    }
+    found.then_some((covered, total))
+}
+
+fn cobertura_attr_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"\b(?P<name>lines-covered|lines-valid)="(?P<value>\d+)""#).unwrap()
+    })
+}
+
+/// Returns the `(covered, total)` lines of a Cobertura report, from the
+/// root element's `lines-covered` and `lines-valid`.
+pub fn parse_cobertura(xml: &str) -> Option<(u64, u64)> {
+    let start = xml.find("<coverage ")?;
+    let end = start + xml[start..].find('>')?;
+    let (mut covered, mut total) = (None, None);
+    for caps in cobertura_attr_regex().captures_iter(&xml[start..end]) {
+        let value = caps["value"].parse().ok();
+        match &caps["name"] {
+            "lines-covered" => covered = value,
+            _ => total = value,
+        }
+    }
+    Some((covered?, total?))
+}
+
+/// `Lines : 81.2% ( 812/1000 )` from Istanbul's text-summary reporter.
+fn istanbul_summary_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*Lines\s*:\s*(?P<pct>[\d.]+)%\s*\(\s*(?P<covered>\d+)/(?P<total>\d+)\s*\)")
+            .unwrap()
+    })
+}
+
+/// `All files | Stmts | Branch | Funcs | Lines |` from Istanbul's text
+/// reporter.
+fn istanbul_table_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^\s*All files\s*\|\s*[\d.]+\s*\|\s*[\d.]+\s*\|\s*[\d.]+\s*\|\s*(?P<pct>[\d.]+)\s*\|",
+        )
+        .unwrap()
+    })
+}
+
+/// The `TOTAL` row of coverage.py and `cargo llvm-cov`; the last
+/// percentage is line coverage.
+fn total_row_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*TOTAL\s.*?(?P<pct>[\d.]+)%(?:\s+\d+\s+\d+\s+(?:-|[\d.]+%))?\s*$").unwrap()
+    })
+}
+
+fn tarpaulin_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?P<pct>[\d.]+)% coverage, (?P<covered>\d+)/(?P<total>\d+) lines covered")
+            .unwrap()
+    })
+}
+
+fn go_total_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^total:\s+\(statements\)\s+(?P<pct>[\d.]+)%").unwrap())
+}
+
+fn jest_threshold_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"coverage threshold for lines \([\d.]+%\) not met: (?P<pct>[\d.]+)%"#).unwrap()
+    })
+}
+
+/// Finds the last coverage summary in a log.
+///
+/// `source` is recorded as where the coverage came from, usually the step
+/// name.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::coverage::parse_log;
+///
+/// let log = "Name    Stmts   Miss  Cover\n---\nTOTAL    1000    188    81%\n";
+/// assert_eq!(parse_log(log, "pytest").unwrap().percent, 81.0);
+/// assert!(parse_log("no summary here", "pytest").is_none());
+/// ```
+pub fn parse_log(log: &str, source: &str) -> Option<Coverage> {
+    let log = strip_ansi(log);
+    let mut found = None;
+    for line in log.lines() {
+        let line = line.trim_end();
+        let counted = istanbul_summary_regex()
+            .captures(line)
+            .or_else(|| tarpaulin_regex().captures(line));
+        let coverage = if let Some(caps) = counted {
+            match (caps["covered"].parse(), caps["total"].parse()) {
+                (Ok(covered), Ok(total)) => Coverage::from_counts(covered, total, source),
+                _ => None,
+            }
+        } else {
+            [
+                istanbul_table_regex(),
+                total_row_regex(),
+                go_total_regex(),
+                jest_threshold_regex(),
+            ]
+            .iter()
+            .find_map(|re| re.captures(line))
+            .and_then(|caps| Coverage::from_percent(&caps["pct"], source))
+        };
+        if coverage.is_some() {
+            found = coverage;
+        }
+    }
+    found
+}
+
+impl CircleClient {
+    /// Finds a build's line coverage.
+    ///
+    /// Coverage reports among the build's artifacts are used first: every
+    /// lcov report, or failing that every Cobertura one, summed. Otherwise
+    /// the last [`SUMMARY_TAIL_BYTES`] of the logs of steps named like
+    /// tests or coverage are searched for a summary, the last step's
+    /// winning. `build` must have its steps, as from
+    /// [`get_build`](Self::get_build).
+    ///
+    /// Returns `None` if the build reported no coverage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the artifacts cannot be listed or none of the
+    /// reports could be downloaded. Logs that fail to load are skipped.
+    pub async fn build_coverage(
+        &self,
+        org: &str,
+        project: &str,
+        build: &BuildInfo,
+    ) -> Result<Option<Coverage>> {
+        let artifacts = self.get_artifacts(org, project, build.build_num).await?;
+        for kind in [ReportKind::Lcov, ReportKind::Cobertura] {
+            let reports: Vec<&Artifact> = artifacts
+                .iter()
+                .filter(|artifact| report_kind(&artifact.path) == Some(kind))
+                .collect();
+            if reports.is_empty() {
+                continue;
+            }
+            let bodies = futures::future::join_all(
+                reports.iter().map(|artifact| self.get_artifact(artifact)),
+            )
+            .await;
+            if bodies.iter().all(Result::is_err) {
+                if let Some(Err(e)) = bodies.into_iter().next() {
+                    return Err(e);
+                }
+                continue;
+            }
+            let (mut covered, mut total) = (0, 0);
+            for body in bodies.into_iter().flatten() {
+                let text = String::from_utf8_lossy(&body);
+                let counts = match kind {
+                    ReportKind::Lcov => parse_lcov(&text),
+                    ReportKind::Cobertura => parse_cobertura(&text),
+                };
+                if let Some((c, t)) = counts {
+                    covered += c;
+                    total += t;
+                }
+            }
+            let mut paths: Vec<&str> = reports.iter().map(|r| r.path.as_str()).collect();
+            paths.dedup();
+            if let Some(coverage) = Coverage::from_counts(covered, total, &paths.join(", ")) {
+                return Ok(Some(coverage));
+            }
+        }
+
+        let actions: Vec<(&str, &str)> = build
+            .steps
+            .iter()
+            .filter(|step| {
+                let name = step.name.to_ascii_lowercase();
+                name.contains("test") || name.contains("cover")
+            })
+            .flat_map(|step| {
+                step.actions.iter().filter_map(move |action| {
+                    Some((step.name.as_str(), action.output_url.as_deref()?))
+                })
+            })
+            .collect();
+        let logs = futures::future::join_all(
+            actions
+                .iter()
+                .map(|(_, url)| self.get_logs_tail(url, SUMMARY_TAIL_BYTES)),
+        )
+        .await;
+        Ok(actions
+            .iter()
+            .zip(logs)
+            .filter_map(|((step, _), log)| parse_log(&log.ok()?, step))
+            .last())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, FakeCircleCi, StepBuilder};
+
+    #[test]
+    fn test_parse_log_summaries() {
+        let jest = "\
+----------|---------|----------|---------|---------|
+File      | % Stmts | % Branch | % Funcs | % Lines |
+----------|---------|----------|---------|---------|
+All files |   82.14 |    70.59 |   77.78 |   81.48 |
+";
+        assert_eq!(parse_log(jest, "test").unwrap().percent, 81.48);
+
+        let summary = "Lines        : 81.2% ( 812/1000 )\n";
+        let coverage = parse_log(summary, "test").unwrap();
+        assert_eq!((coverage.covered, coverage.total), (Some(812), Some(1000)));
+
+        let llvm_cov =
+            "TOTAL   1234   200   83.79%   100   10   90.00%   2000   300   85.00%   0   0   -\n";
+        assert_eq!(parse_log(llvm_cov, "test").unwrap().percent, 85.0);
+
+        let go = "ok  \tgithub.com/org/repo\t0.012s\ntotal:\t\t\t(statements)\t\t71.3%\n";
+        assert_eq!(parse_log(go, "test").unwrap().percent, 71.3);
+
+        let tarpaulin = "|| Tested/Total Lines:\n81.23% coverage, 812/1000 lines covered\n";
+        assert_eq!(parse_log(tarpaulin, "test").unwrap().covered, Some(812));
+
+        let xml = r#"<?xml version="1.0" ?><coverage version="7.4" timestamp="1" lines-valid="400" lines-covered="300" line-rate="0.75">"#;
+        assert_eq!(parse_cobertura(xml), Some((300, 400)));
+        assert_eq!(
+            report_kind("reports/coverage/cobertura-coverage.xml"),
+            Some(ReportKind::Cobertura)
+        );
+        assert_eq!(report_kind("junit.xml"), None);
+    }
+
+    #[tokio::test]
+    async fn test_build_coverage_prefers_artifacts() {
+        let server = FakeCircleCi::start().await;
+        let log_url = server
+            .mount_log("test-0", "All files |   50 |   50 |   50 |   50 |\n")
+            .await;
+        let build = BuildBuilder::new(7)
+            .step(
+                StepBuilder::new("Run tests")
+                    .action(ActionBuilder::new("Run tests").output_url(log_url)),
+            )
+            .build();
+        let client = server.client();
+
+        server.mount_artifacts("org", "repo", 7, &[]).await;
+        let from_log = client
+            .build_coverage("org", "repo", &build)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (from_log.percent, from_log.source.as_str()),
+            (50.0, "Run tests")
+        );
+
+        server.server().reset().await;
+        server
+            .mount_artifacts(
+                "org",
+                "repo",
+                7,
+                &[(
+                    "coverage/lcov.info",
+                    "SF:a.ts\nLF:40\nLH:30\nend_of_record\n",
+                )],
+            )
+            .await;
+        let coverage = client
+            .build_coverage("org", "repo", &build)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(coverage.percent, 75.0);
+        assert_eq!(coverage.source, "coverage/lcov.info");
+    }
+}
@@ -0,0 +1,120 @@
+//! The stored CircleCI API token.
+//!
+//! `cdb init` saves the token to [`paths::credentials_file`] so it doesn't
+//! have to live in a shell profile. On Unix the file is created readable
+//! by its owner only. `CIRCLECI_TOKEN` always takes precedence over it.
+//!
+//! ```toml
+//! circleci_token = "CCIPAT_..."
+//! ```
+
+use crate::paths;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Contents of the credentials file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Credentials {
+    circleci_token: Option<String>,
+}
+
+/// Returns the token from `CIRCLECI_TOKEN`, falling back to the stored one.
+pub fn token() -> Option<String> {
+    std::env::var("CIRCLECI_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .or_else(stored_token)
+}
+
+/// Returns the token saved by [`save_token`], if any.
+///
+/// An unreadable or malformed file counts as no token.
+pub fn stored_token() -> Option<String> {
+    read_token(&paths::credentials_file()?)
+}
+
+/// Saves `token` to the credentials file, replacing any stored one.
+///
+/// # Returns
+///
+/// The path the token was written to.
+///
+/// # Errors
+///
+/// Returns an error if the platform has no config directory or the file
+/// can't be written.
+pub fn save_token(token: &str) -> Result<PathBuf> {
+    let path = paths::credentials_file().context("No config directory on this platform")?;
+    write_token(&path, token)?;
+    Ok(path)
+}
+
+fn read_token(path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let credentials: Credentials = toml::from_str(&contents).ok()?;
+    credentials
+        .circleci_token
+        .filter(|token| !token.trim().is_empty())
+}
+
+fn write_token(path: &Path, token: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let contents = toml::to_string(&Credentials {
+        circleci_token: Some(token.to_string()),
+    })?;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    // `mode` only applies to new files; tighten an existing one too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("cdb-credentials-test-{}", std::process::id()))
+            .join("credentials.toml");
+        assert_eq!(read_token(&path), None);
+
+        write_token(&path, "CCIPAT_abc").unwrap();
+        assert_eq!(read_token(&path).as_deref(), Some("CCIPAT_abc"));
+        write_token(&path, "CCIPAT_def").unwrap();
+        assert_eq!(read_token(&path).as_deref(), Some("CCIPAT_def"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::write(&path, "circleci_token = \"\"\n").unwrap();
+        assert_eq!(read_token(&path), None);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
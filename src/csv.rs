@@ -0,0 +1,100 @@
+//! CSV export of a build's steps and failed tests.
+//!
+//! [`render`] writes one row per step and one per failed test, for
+//! spreadsheets and notebooks:
+//!
+//! ```text
+//! type,name,file,status,duration_seconds
+//! step,Run tests,,failed,80
+//! test,Button › renders label,src/Button.test.tsx,failed,
+//! ```
+
+use crate::report::BuildReport;
+
+/// The header row.
+const HEADER: &str = "type,name,file,status,duration_seconds";
+
+/// Renders a report's steps and failed tests as CSV, with a header row.
+///
+/// Steps come first, longest first, with their run time summed over
+/// parallel containers. Tests are the failed tests found in the logs;
+/// their duration is left empty, as test runners' summaries don't
+/// reliably include it.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::csv::render;
+/// use circle_debug::report::BuildReport;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 7, "status": "success", "branch": "main", "subject": null,
+///         "steps": [{"name": "Build, test", "actions": [
+///             {"name": "Build, test", "status": "success", "type": "test", "run_time_millis": 1500}
+///         ]}]}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     render(&BuildReport::new("org", "repo", &build)),
+///     "type,name,file,status,duration_seconds\nstep,\"Build, test\",,success,1.5\n"
+/// );
+/// ```
+pub fn render(report: &BuildReport) -> String {
+    let mut out = format!("{}\n", HEADER);
+    for step in &report.step_timings {
+        let seconds = (step.millis as f64 / 1000.0).to_string();
+        out.push_str(&row(&["step", &step.name, "", &step.status, &seconds]));
+    }
+    for test in report.failed_tests() {
+        out.push_str(&row(&[
+            "test",
+            test.name.as_deref().unwrap_or_default(),
+            test.file.as_deref().unwrap_or_default(),
+            "failed",
+            "",
+        ]));
+    }
+    out
+}
+
+/// Formats one CSV row, quoting fields as RFC 4180 requires.
+fn row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repro::parse_failed_tests;
+    use crate::test_utils::{BuildBuilder, FailedActionBuilder};
+
+    #[test]
+    fn test_render_steps_and_tests() {
+        let mut report = BuildReport::new("org", "repo", &BuildBuilder::new(3).build());
+        report.failed_actions.push(
+            FailedActionBuilder::new("Test")
+                .failed_tests(parse_failed_tests(
+                    "FAILED tests/test_api.py::test_login - AssertionError\n\
+                 test parser::tests::says \"hi\" ... FAILED\n",
+                ))
+                .build(),
+        );
+        assert_eq!(
+            render(&report),
+            "type,name,file,status,duration_seconds\n\
+             test,test_login,tests/test_api.py,failed,\n"
+        );
+        assert_eq!(row(&["a \"b\"", "c"]), "\"a \"\"b\"\"\",c\n");
+    }
+}
@@ -0,0 +1,288 @@
+//! The failing instruction of a `docker build`.
+//!
+//! BuildKit's plain progress output, which is what CI logs get, interleaves
+//! the output of every build step, each line tagged with its vertex
+//! number:
+//!
+//! ```text
+//! #8 [builder 4/7] RUN apt-get install -y libfoo
+//! #8 0.512 Reading package lists...
+//! #8 1.204 E: Unable to locate package libfoo
+//! #8 ERROR: process "/bin/sh -c apt-get install -y libfoo" did not complete successfully: exit code: 100
+//! ```
+//!
+//! [`parse_build`] untangles that into the failing step, its exit code,
+//! and only that step's output. The legacy builder's `Step 4/7 : ...`
+//! format is understood too.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A failed Dockerfile instruction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DockerFailure {
+    /// Position among the stage's instructions, e.g. `4/7`.
+    pub step: Option<String>,
+    /// Name of the build stage, for multi-stage builds.
+    pub stage: Option<String>,
+    /// The instruction as Docker printed it, e.g. `RUN npm ci`.
+    pub instruction: String,
+    /// Exit code of a failed `RUN`.
+    pub exit_code: Option<i32>,
+    /// Docker's error message.
+    pub error: String,
+    /// Output of the failing step alone, without vertex numbers and
+    /// timestamps.
+    pub output: Vec<String>,
+    /// Line of the instruction in the Dockerfile.
+    pub dockerfile_line: Option<u32>,
+}
+
+fn vertex_header_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^#(?P<id>\d+) \[(?P<label>[^\]]+)\] (?P<instr>.+)$").unwrap())
+}
+
+fn vertex_line_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^#(?P<id>\d+) (?:\d+\.\d+ )?(?P<text>.*)$").unwrap())
+}
+
+fn step_label_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:(?P<stage>\S+) )?(?P<step>\d+/\d+)$").unwrap())
+}
+
+fn exit_code_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:exit code: |returned a non-zero code: )(?P<code>\d+)").unwrap()
+    })
+}
+
+fn dockerfile_line_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:\S*/)?Dockerfile[\w.-]*:(?P<line>\d+)$").unwrap())
+}
+
+fn legacy_step_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^Step (?P<step>\d+/\d+) : (?P<instr>.+)$").unwrap())
+}
+
+/// Finds the Dockerfile instruction a `docker build` failed on.
+///
+/// # Returns
+///
+/// `None` if the log has no failed Docker build step.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::docker::parse_build;
+///
+/// let log = "#5 [2/3] COPY . .\n\
+///            #5 DONE 0.1s\n\
+///            #6 [3/3] RUN npm ci\n\
+///            #6 2.301 npm ERR! code ERESOLVE\n\
+///            #6 ERROR: process \"/bin/sh -c npm ci\" did not complete successfully: exit code: 1\n";
+/// let failure = parse_build(log).unwrap();
+/// assert_eq!(failure.step.as_deref(), Some("3/3"));
+/// assert_eq!(failure.instruction, "RUN npm ci");
+/// assert_eq!(failure.exit_code, Some(1));
+/// assert_eq!(failure.output, vec!["npm ERR! code ERESOLVE"]);
+/// ```
+pub fn parse_build(log: &str) -> Option<DockerFailure> {
+    let mut failure = parse_buildkit(log).or_else(|| parse_legacy(log))?;
+    failure.dockerfile_line = log.lines().find_map(|line| {
+        dockerfile_line_regex()
+            .captures(line.trim())
+            .and_then(|caps| caps["line"].parse().ok())
+    });
+    Some(failure)
+}
+
+fn parse_buildkit(log: &str) -> Option<DockerFailure> {
+    let mut headers: HashMap<&str, (&str, &str)> = HashMap::new();
+    let mut output: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut failed: Option<(&str, &str)> = None;
+
+    for line in log.lines() {
+        if let Some(caps) = vertex_header_regex().captures(line) {
+            let id = caps.name("id").unwrap().as_str();
+            headers.entry(id).or_insert((
+                caps.name("label").unwrap().as_str(),
+                caps.name("instr").unwrap().as_str(),
+            ));
+            continue;
+        }
+        let Some(caps) = vertex_line_regex().captures(line) else {
+            continue;
+        };
+        let id = caps.name("id").unwrap().as_str();
+        let text = caps.name("text").unwrap().as_str();
+        if let Some(error) = text.strip_prefix("ERROR: ") {
+            // Later errors are usually other steps being cancelled
+            if failed.is_none() {
+                failed = Some((id, error));
+            }
+        } else if !is_progress(text) {
+            output.entry(id).or_default().push(text);
+        }
+    }
+
+    let (id, error) = failed?;
+    let (label, instruction) = headers.get(id).copied().unwrap_or(("", ""));
+    let (stage, step) = match step_label_regex().captures(label) {
+        Some(caps) => (
+            caps.name("stage").map(|m| m.as_str().to_string()),
+            Some(caps["step"].to_string()),
+        ),
+        // `[internal] load metadata for ...` and similar
+        None if !label.is_empty() => (Some(label.to_string()), None),
+        None => (None, None),
+    };
+    Some(DockerFailure {
+        step,
+        stage,
+        instruction: instruction.to_string(),
+        exit_code: exit_code(error),
+        error: error.to_string(),
+        output: output
+            .remove(id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        dockerfile_line: None,
+    })
+}
+
+fn parse_legacy(log: &str) -> Option<DockerFailure> {
+    let mut current: Option<(&str, &str)> = None;
+    let mut output = Vec::new();
+    for line in log.lines() {
+        if let Some(caps) = legacy_step_regex().captures(line) {
+            current = Some((
+                caps.name("step").unwrap().as_str(),
+                caps.name("instr").unwrap().as_str(),
+            ));
+            output.clear();
+            continue;
+        }
+        if line.contains("returned a non-zero code") {
+            let (step, instruction) = current?;
+            return Some(DockerFailure {
+                step: Some(step.to_string()),
+                stage: None,
+                instruction: instruction.to_string(),
+                exit_code: exit_code(line),
+                error: line.trim().to_string(),
+                output,
+                dockerfile_line: None,
+            });
+        }
+        // Intermediate containers and cache notices aren't step output
+        if current.is_some()
+            && !line.starts_with(" ---> ")
+            && !line.starts_with("Removing intermediate container")
+        {
+            output.push(line.to_string());
+        }
+    }
+    None
+}
+
+/// Whether a vertex line is BuildKit's own status rather than output.
+fn is_progress(text: &str) -> bool {
+    text.starts_with("DONE ")
+        || text.starts_with("CACHED")
+        || text.starts_with("CANCELED")
+        || text.starts_with("sha256:")
+        || text.starts_with("extracting ")
+        || text.starts_with("resolve ")
+        || text.starts_with("transferring ")
+}
+
+fn exit_code(text: &str) -> Option<i32> {
+    exit_code_regex()
+        .captures(text)
+        .and_then(|caps| caps["code"].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_buildkit_interleaved_stages() {
+        let log = "\
+#1 [internal] load build definition from Dockerfile
+#1 transferring dockerfile: 612B done
+#1 DONE 0.0s
+#7 [web 3/5] RUN npm ci
+#8 [builder 4/7] RUN apt-get update && apt-get install -y libfoo-dev
+#7 1.002 added 812 packages in 9s
+#8 0.512 Reading package lists...
+#8 1.204 E: Unable to locate package libfoo-dev
+#8 ERROR: process \"/bin/sh -c apt-get update && apt-get install -y libfoo-dev\" did not complete successfully: exit code: 100
+#7 CANCELED
+#7 ERROR: context canceled
+------
+ > [builder 4/7] RUN apt-get update && apt-get install -y libfoo-dev:
+1.204 E: Unable to locate package libfoo-dev
+------
+Dockerfile:12
+--------------------
+  12 | >>> RUN apt-get update && apt-get install -y libfoo-dev
+--------------------
+ERROR: failed to solve: process \"/bin/sh -c apt-get update && apt-get install -y libfoo-dev\" did not complete successfully: exit code: 100
+";
+        let failure = parse_build(log).unwrap();
+        assert_eq!(failure.stage.as_deref(), Some("builder"));
+        assert_eq!(failure.step.as_deref(), Some("4/7"));
+        assert_eq!(
+            failure.instruction,
+            "RUN apt-get update && apt-get install -y libfoo-dev"
+        );
+        assert_eq!(failure.exit_code, Some(100));
+        assert_eq!(
+            failure.output,
+            vec![
+                "Reading package lists...",
+                "E: Unable to locate package libfoo-dev"
+            ]
+        );
+        assert_eq!(failure.dockerfile_line, Some(12));
+    }
+
+    #[test]
+    fn test_parse_missing_context_file_and_legacy_builder() {
+        let log = "\
+#6 [3/5] COPY package.json yarn.lock ./
+#6 ERROR: failed to calculate checksum of ref 4f1c::k2x: \"/yarn.lock\": not found
+";
+        let failure = parse_build(log).unwrap();
+        assert_eq!(failure.instruction, "COPY package.json yarn.lock ./");
+        assert_eq!(failure.exit_code, None);
+        assert!(failure.error.contains("\"/yarn.lock\": not found"));
+
+        let legacy = "\
+Step 3/5 : RUN make
+ ---> Running in 1a2b3c4d
+cc: error: missing.c: No such file or directory
+The command '/bin/sh -c make' returned a non-zero code: 2
+";
+        let failure = parse_build(legacy).unwrap();
+        assert_eq!(failure.step.as_deref(), Some("3/5"));
+        assert_eq!(failure.exit_code, Some(2));
+        assert_eq!(
+            failure.output,
+            vec!["cc: error: missing.c: No such file or directory"]
+        );
+
+        assert!(parse_build("#1 [1/2] FROM node:20\n#1 DONE 1.2s\n").is_none());
+    }
+}
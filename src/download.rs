@@ -0,0 +1,621 @@
+//! Log downloads with HTTP range support.
+//!
+//! Output URLs for long-running jobs can point at logs that are hundreds of
+//! megabytes. The helpers here resume interrupted transfers with `Range`
+//! requests instead of starting over, and can fetch only the tail of a log
+//! when the exit zone is all that matters.
+//!
+//! Output URLs are often presigned S3 links rather than circleci.com
+//! endpoints. Those carry their own signature, so the API token is only
+//! sent to CircleCI hosts. Bodies stored gzip- or zstd-compressed, such as
+//! `.log.gz` uploads, are decompressed.
+//!
+//! Full downloads go through the client's [`cache`](crate::cache) when one
+//! is configured: a cached log is revalidated and reused on `304`. Finished
+//! downloads are also what [`record`](crate::record) saves and replays.
+
+use crate::cache::ResponseCache;
+use crate::{CircleClient, CircleDebugError};
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use reqwest::{header, StatusCode, Url};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Overall time limit for a single download attempt.
+///
+/// Much longer than the API timeout since large logs legitimately take a
+/// while; stalled connections are caught by the client's read timeout.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Number of consecutive attempts that make no progress before giving up.
+const MAX_STALLED_ATTEMPTS: u32 = 3;
+
+/// Wait before resuming a download; doubled for each attempt in a row
+/// that made no progress.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Returns how long to wait before the next attempt, after `stalled`
+/// attempts in a row that made no progress.
+fn retry_delay(stalled: u32) -> Duration {
+    RETRY_BACKOFF * 2u32.pow(stalled)
+}
+
+impl CircleClient {
+    /// Fetches only the last `max_bytes` of an action's log output.
+    ///
+    /// Uses a suffix `Range` request, so only the requested bytes are
+    /// transferred when the server supports ranges. The first message in
+    /// the returned text may be missing its beginning. A compressed log is
+    /// downloaded whole and the tail taken once it's decompressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_url` - The URL to fetch logs from
+    /// * `max_bytes` - How many bytes from the end of the log to fetch
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails after retries.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let tail = client.get_logs_tail("https://example.com/output", 64 * 1024).await?;
+    /// println!("{}", tail);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_logs_tail(&self, output_url: &str, max_bytes: u64) -> Result<String> {
+        self.get_logs_with_progress(output_url, Some(max_bytes), |_, _| {})
+            .await
+    }
+
+    /// Fetches action logs while reporting download progress.
+    ///
+    /// `on_progress` is called with the bytes received so far and, when the
+    /// server reports it, the expected total. Pass `tail` to fetch only the
+    /// last N bytes as with [`get_logs_tail`](Self::get_logs_tail).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails after retries.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let logs = client
+    ///     .get_logs_with_progress("https://example.com/output", None, |received, total| {
+    ///         eprintln!("{} of {:?} bytes", received, total);
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_logs_with_progress<F>(
+        &self,
+        output_url: &str,
+        tail: Option<u64>,
+        on_progress: F,
+    ) -> Result<String>
+    where
+        F: Fn(u64, Option<u64>),
+    {
+        let (body, partial) = self.download(output_url, tail, &on_progress).await?;
+        let (body, partial) = if !partial {
+            keep_tail(decompress(body)?, 0, tail)
+        } else if is_text(&body) {
+            (body, true)
+        } else {
+            // The end of a compressed log can't be decompressed on its own,
+            // so fetch all of it and cut the tail from the decompressed text
+            let (full, _) = self.download(output_url, None, &on_progress).await?;
+            if is_compressed(&full) {
+                keep_tail(decompress(full)?, 0, tail)
+            } else {
+                (body, true)
+            }
+        };
+        let text = String::from_utf8_lossy(&body);
+
+        if partial {
+            Ok(decode_partial_log_body(&text))
+        } else {
+            Ok(decode_log_body(&text))
+        }
+    }
+
+    /// Returns an action's log from the response cache without a request.
+    ///
+    /// Returns `None` if the log isn't cached or the client has no cache.
+    /// The entry isn't revalidated, so it may be stale for running builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cached body is corrupt compressed data.
+    pub fn cached_logs(&self, output_url: &str) -> Result<Option<String>> {
+        match &self.cache {
+            Some(cache) => read_cached_log(cache, output_url),
+            None => Ok(None),
+        }
+    }
+
+    /// Downloads a URL, resuming with `Range` requests after interruptions.
+    ///
+    /// When `tail` is set, only that many bytes from the end are requested.
+    /// Returns the body along with whether it is a strict suffix of the
+    /// full resource. Full downloads are cached and revalidated.
+    #[tracing::instrument(
+        level = "debug",
+        skip(self, url, on_progress),
+        fields(url = %redact_query(url), bytes, elapsed_ms)
+    )]
+    pub(crate) async fn download<F>(
+        &self,
+        url: &str,
+        tail: Option<u64>,
+        on_progress: F,
+    ) -> Result<(Vec<u8>, bool)>
+    where
+        F: Fn(u64, Option<u64>),
+    {
+        if let Some(recorded) = self.replayed(url)? {
+            if !recorded.status.is_success() {
+                return Err(CircleDebugError::from_status(
+                    recorded.status,
+                    url,
+                    recorded.text(),
+                ))
+                .context("Failed to fetch logs");
+            }
+            let len = recorded.body.len() as u64;
+            on_progress(len, Some(len));
+            if recorded.partial {
+                return Ok((recorded.body, true));
+            }
+            return Ok(keep_tail(recorded.body, 0, tail));
+        }
+
+        let _permit = self.request_permit().await;
+        let started = Instant::now();
+        let mut body: Vec<u8> = Vec::new();
+        // Absolute position of `body[0]` once the server has told us.
+        let mut offset: Option<u64> = None;
+        let mut stalled = 0;
+        let cached = match (&self.cache, tail) {
+            (Some(cache), None) => cache.get(url),
+            _ => None,
+        };
+        // Headers of the full response, for caching it once complete
+        let mut validators = None;
+
+        loop {
+            let range = match (offset, tail) {
+                (Some(start), _) => Some(format!("bytes={}-", start + body.len() as u64)),
+                (None, Some(n)) => Some(format!("bytes=-{}", n)),
+                (None, None) => None,
+            };
+
+            let mut request = self.client.get(url).timeout(DOWNLOAD_TIMEOUT);
+            if is_circleci_url(url) || url.starts_with(&self.base_url) {
+                request = request.header("Circle-Token", &self.token);
+            }
+            if let Some(range) = &range {
+                request = request.header(header::RANGE, range);
+            }
+            if let (Some(entry), None) = (&cached, offset) {
+                request = entry.revalidate(request);
+            }
+
+            tracing::debug!(range = range.as_deref(), "requesting log");
+            let mut response = match request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    tracing::debug!(error = %err, attempt = stalled + 1, "log request failed");
+                    stalled += 1;
+                    if stalled >= MAX_STALLED_ATTEMPTS {
+                        return Err(CircleDebugError::from(err))
+                            .context("Failed to fetch logs from CircleCI");
+                    }
+                    tokio::time::sleep(retry_delay(stalled)).await;
+                    continue;
+                }
+            };
+
+            let expected = match response.status() {
+                StatusCode::PARTIAL_CONTENT => {
+                    if offset.is_none() {
+                        offset = Some(content_range_start(response.headers()).unwrap_or(0));
+                    }
+                    response.content_length().map(|len| len + body.len() as u64)
+                }
+                StatusCode::NOT_MODIFIED if cached.is_some() => {
+                    tracing::debug!("cache revalidated");
+                    body = cached.map(|entry| entry.body).unwrap_or_default();
+                    offset = Some(0);
+                    break;
+                }
+                StatusCode::RANGE_NOT_SATISFIABLE if offset.is_some() => {
+                    // Everything was already received before the interruption
+                    break;
+                }
+                status if status.is_success() => {
+                    // Server ignored the range and sent the whole thing
+                    body.clear();
+                    offset = Some(0);
+                    validators = Some(response.headers().clone());
+                    response.content_length()
+                }
+                status => {
+                    let text = response.text().await.unwrap_or_default();
+                    self.record(url, status, text.as_bytes(), false)?;
+                    return Err(CircleDebugError::from_status(status, url, text))
+                        .context("Failed to fetch logs");
+                }
+            };
+
+            let received_before = body.len();
+            let finished = loop {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => {
+                        body.extend_from_slice(&chunk);
+                        on_progress(body.len() as u64, expected);
+                    }
+                    Ok(None) => break true,
+                    Err(_) => break false,
+                }
+            };
+
+            if finished {
+                break;
+            }
+
+            tracing::debug!(received = body.len(), "log download interrupted, resuming");
+            if body.len() > received_before {
+                stalled = 0;
+            } else {
+                stalled += 1;
+                if stalled >= MAX_STALLED_ATTEMPTS {
+                    return Err(CircleDebugError::NetworkError(format!(
+                        "Log download stalled after {} KB; giving up after {} attempts",
+                        body.len() / 1024,
+                        MAX_STALLED_ATTEMPTS
+                    ))
+                    .into());
+                }
+            }
+            tokio::time::sleep(retry_delay(stalled)).await;
+        }
+
+        let span = tracing::Span::current();
+        span.record("bytes", body.len() as u64);
+        span.record("elapsed_ms", started.elapsed().as_millis() as u64);
+        tracing::info!("log downloaded");
+
+        let start = offset.unwrap_or(0);
+        if let (Some(cache), Some(headers), None) = (&self.cache, &validators, tail) {
+            cache.put(url, headers, &body, false);
+        }
+        let (body, partial) = keep_tail(body, start, tail);
+        self.record(url, StatusCode::OK, &body, partial)?;
+        Ok((body, partial))
+    }
+}
+
+/// Trims a body that starts at byte `start` of the resource to the last
+/// `tail` bytes, returning it with whether it is a strict suffix.
+///
+/// Compressed bodies are returned whole, to be trimmed once decompressed.
+fn keep_tail(mut body: Vec<u8>, start: u64, tail: Option<u64>) -> (Vec<u8>, bool) {
+    if let Some(n) = tail {
+        // Servers without range support send everything; keep the tail
+        if start == 0 && body.len() as u64 > n && !is_compressed(&body) {
+            body.drain(..body.len() - n as usize);
+            return (body, true);
+        }
+    }
+
+    (body, start > 0)
+}
+
+/// Drops the query string of a URL for logging; presigned links carry
+/// their credentials there.
+pub(crate) fn redact_query(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(base, _)| base)
+}
+
+/// Returns `true` if `url` points at a CircleCI host, including the
+/// `circle-artifacts.com` hosts that serve artifacts.
+///
+/// Presigned storage URLs must not get the API token: it leaks the token
+/// to a third party and extra auth headers can break the signature check.
+pub(crate) fn is_circleci_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| {
+            host == "circleci.com"
+                || host.ends_with(".circleci.com")
+                || host.ends_with(".circle-artifacts.com")
+        })
+}
+
+/// Reads an action's log from `cache`, without a request.
+///
+/// # Errors
+///
+/// Returns an error if the cached body is corrupt compressed data.
+pub(crate) fn read_cached_log(cache: &ResponseCache, output_url: &str) -> Result<Option<String>> {
+    let Some(entry) = cache.get(output_url) else {
+        return Ok(None);
+    };
+    let body = decompress(entry.body)?;
+    Ok(Some(decode_log_body(&String::from_utf8_lossy(&body))))
+}
+
+/// Magic bytes a gzip stream starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Magic bytes a zstd frame starts with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Returns `true` if `body` starts with the gzip or zstd magic bytes.
+fn is_compressed(body: &[u8]) -> bool {
+    body.starts_with(&GZIP_MAGIC) || body.starts_with(&ZSTD_MAGIC)
+}
+
+/// Returns `true` if `body` is UTF-8 text, allowing for a character cut
+/// off at the start as in a tail fetch.
+fn is_text(body: &[u8]) -> bool {
+    (0..4).any(|skip| {
+        body.get(skip..)
+            .is_some_and(|rest| std::str::from_utf8(rest).is_ok())
+    })
+}
+
+/// Decompresses `body` if it starts with the gzip or zstd magic bytes.
+///
+/// Storage backends serve `.gz` and `.zst` objects, and sometimes logs,
+/// compressed with or without a `Content-Encoding` header, and the client
+/// doesn't decode either, so the body itself is checked. Gzip files made
+/// by appending several streams are read to the end.
+pub(crate) fn decompress(body: Vec<u8>) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    if body.starts_with(&GZIP_MAGIC) {
+        MultiGzDecoder::new(body.as_slice())
+            .read_to_end(&mut decompressed)
+            .context("Failed to decompress gzip data")?;
+    } else if body.starts_with(&ZSTD_MAGIC) {
+        zstd::stream::copy_decode(body.as_slice(), &mut decompressed)
+            .context("Failed to decompress zstd data")?;
+    } else {
+        return Ok(body);
+    }
+    Ok(decompressed)
+}
+
+/// Extracts the start offset from a `Content-Range: bytes start-end/total` header.
+fn content_range_start(headers: &header::HeaderMap) -> Option<u64> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Decodes a complete log body into plain text.
+///
+/// CircleCI serves action output as a JSON array of message objects; their
+/// `message` fields are concatenated. Anything that isn't such an array is
+/// returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::download::decode_log_body;
+///
+/// let body = r#"[{"message":"line 1\n"},{"message":"line 2\n"}]"#;
+/// assert_eq!(decode_log_body(body), "line 1\nline 2\n");
+/// assert_eq!(decode_log_body("plain text"), "plain text");
+/// ```
+pub fn decode_log_body(body: &str) -> String {
+    if let Ok(json_array) = serde_json::from_str::<Vec<serde_json::Value>>(body) {
+        return json_array
+            .iter()
+            .filter_map(|v| v.get("message").and_then(|m| m.as_str()))
+            .collect();
+    }
+
+    body.to_string()
+}
+
+/// Decodes a log body that was cut off at the start, such as a tail fetch.
+///
+/// The fragment is not valid JSON, so `"message"` string values are
+/// scanned for directly. A message truncated before its key is dropped.
+/// Falls back to [`decode_log_body`] when no messages are found.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::download::decode_partial_log_body;
+///
+/// let fragment = r#"st line\n"},{"message":"exited with code 1\n","type":"out"}]"#;
+/// assert_eq!(decode_partial_log_body(fragment), "exited with code 1\n");
+/// ```
+pub fn decode_partial_log_body(body: &str) -> String {
+    const KEY: &str = "\"message\":";
+
+    let mut messages = String::new();
+    let mut found = false;
+    let mut rest = body;
+
+    while let Some(pos) = rest.find(KEY) {
+        rest = rest[pos + KEY.len()..].trim_start();
+        let Some(literal) = string_literal(rest) else {
+            continue;
+        };
+        if let Ok(message) = serde_json::from_str::<String>(literal) {
+            messages.push_str(&message);
+            found = true;
+        }
+        rest = &rest[literal.len()..];
+    }
+
+    if found {
+        messages
+    } else {
+        decode_log_body(body)
+    }
+}
+
+/// Returns the JSON string literal (including quotes) at the start of `s`.
+fn string_literal(s: &str) -> Option<&str> {
+    if !s.starts_with('"') {
+        return None;
+    }
+
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(&s[..=i]),
+            _ => escaped = false,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failed_requests_back_off() {
+        let server = crate::test_utils::FakeCircleCi::start().await;
+        let started = Instant::now();
+        // Nothing listens on the discard port
+        let result = server.client().get_logs("http://127.0.0.1:9/output").await;
+        assert!(result.is_err());
+        assert!(started.elapsed() >= retry_delay(1) + retry_delay(2));
+    }
+
+    #[test]
+    fn test_decode_partial_log_body_skips_cut_message() {
+        let fragment = r#"essage":"cut\n"},{"message":"a \"quoted\" word\n"},{"message":"done"}]"#;
+        assert_eq!(decode_partial_log_body(fragment), "a \"quoted\" word\ndone");
+    }
+
+    #[test]
+    fn test_decode_partial_log_body_plain_text() {
+        assert_eq!(decode_partial_log_body("no json here"), "no json here");
+    }
+
+    #[test]
+    fn test_is_circleci_url() {
+        assert!(is_circleci_url(
+            "https://circleci.com/api/v1.1/project/gh/o/r/1/output/1/0"
+        ));
+        assert!(is_circleci_url("https://output.circleci.com/file"));
+        assert!(is_circleci_url(
+            "https://123-4567-gh.circle-artifacts.com/0/coverage/lcov.info"
+        ));
+        assert!(!is_circleci_url(
+            "https://circle-production-action-output.s3.amazonaws.com/abc?X-Amz-Signature=def"
+        ));
+        assert!(!is_circleci_url("https://notcircleci.com/x"));
+        assert!(!is_circleci_url("not a url"));
+    }
+
+    #[test]
+    fn test_decompress() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let gzip = |text: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(text).unwrap();
+            encoder.finish().unwrap()
+        };
+        assert_eq!(
+            decompress(gzip(br#"[{"message":"zipped"}]"#)).unwrap(),
+            br#"[{"message":"zipped"}]"#
+        );
+
+        // A log appended to with `gzip >>` has a stream per append
+        let mut appended = gzip(b"first\n");
+        appended.extend(gzip(b"second\n"));
+        assert_eq!(decompress(appended).unwrap(), b"first\nsecond\n");
+
+        let zstd = zstd::encode_all(&b"zstd log"[..], 0).unwrap();
+        assert_eq!(decompress(zstd).unwrap(), b"zstd log");
+        assert_eq!(decompress(b"plain".to_vec()).unwrap(), b"plain");
+    }
+
+    #[tokio::test]
+    async fn test_tail_of_compressed_log() {
+        use wiremock::matchers::{header_exists, method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mut messages: Vec<String> = (0..500)
+            .map(|i| format!(r#"{{"message":"step {} took {}ms\n"}}"#, i, i * 37 % 1000))
+            .collect();
+        messages.push(r#"{"message":"exited with code 1\n"}"#.to_string());
+        let log = format!("[{}]", messages.join(","));
+        let compressed = zstd::encode_all(log.as_bytes(), 0).unwrap();
+        let start = compressed.len() - 64;
+
+        let server = crate::test_utils::FakeCircleCi::start().await;
+        Mock::given(method("GET"))
+            .and(path("/output.zst"))
+            .and(header_exists("range"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header(
+                        "Content-Range",
+                        format!(
+                            "bytes {}-{}/{}",
+                            start,
+                            compressed.len() - 1,
+                            compressed.len()
+                        ),
+                    )
+                    .set_body_bytes(compressed[start..].to_vec()),
+            )
+            .with_priority(1)
+            .mount(server.server())
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/output.zst"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(compressed.clone()))
+            .mount(server.server())
+            .await;
+
+        let url = format!("{}/output.zst", server.server().uri());
+        let tail = server.client().get_logs_tail(&url, 64).await.unwrap();
+        assert!(tail.ends_with("exited with code 1\n"), "{:?}", tail);
+        assert!(!tail.contains('\u{fffd}'));
+        assert!(!tail.contains("step 0 "));
+    }
+
+    #[test]
+    fn test_content_range_start() {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_RANGE,
+            "bytes 1024-2047/2048".parse().unwrap(),
+        );
+        assert_eq!(content_range_start(&headers), Some(1024));
+        assert_eq!(content_range_start(&header::HeaderMap::new()), None);
+    }
+}
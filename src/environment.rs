@@ -0,0 +1,193 @@
+//! The machine a job ran on.
+//!
+//! The build payload says which executor and resource class a job used.
+//! The log of its "Spin up environment" step adds the rest: the images
+//! its containers started from, or the VM image, and the host's operating
+//! system:
+//!
+//! ```text
+//! System information:
+//!  Operating System: Ubuntu 20.04.6 LTS
+//!  Architecture: x86_64
+//!
+//! Starting container cimg/node:20.11
+//! Starting container cimg/postgres:14.9
+//! ```
+//!
+//! [`Environment`] puts the two together, so comparing CI with a local
+//! machine starts from what CI actually ran.
+
+use crate::BuildInfo;
+use serde::{Deserialize, Serialize};
+
+/// Name of the step that provisions a job's executor.
+pub const SPIN_UP_STEP: &str = "Spin up environment";
+
+/// Executor, resource class, images, and OS of a job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Environment {
+    /// Executor type, e.g. "docker", "machine", or "macos".
+    pub executor: Option<String>,
+    /// Resource class name, e.g. "medium" or "arm.large".
+    pub resource_class: Option<String>,
+    /// Number of vCPUs.
+    pub cpu: Option<f64>,
+    /// Memory in MB.
+    pub ram: Option<u64>,
+    /// Images the job ran in, the primary container's first; the VM image
+    /// for machine executors.
+    #[serde(default)]
+    pub images: Vec<String>,
+    /// Operating system of the host, e.g. "Ubuntu 20.04.6 LTS".
+    pub os: Option<String>,
+    /// CPU architecture of the host, e.g. "x86_64" or "aarch64".
+    pub arch: Option<String>,
+}
+
+impl Environment {
+    /// Reads the executor and resource class from the build payload.
+    pub fn from_build(build: &BuildInfo) -> Self {
+        let picard = build.picard.clone().unwrap_or_default();
+        let class = picard.resource_class.unwrap_or_default();
+        Environment {
+            executor: picard.executor,
+            resource_class: class.class,
+            cpu: class.cpu,
+            ram: class.ram,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the images and host details a "Spin up environment" log
+    /// reports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::environment::Environment;
+    ///
+    /// let mut environment = Environment::default();
+    /// environment.add_spin_up_log(
+    ///     "System information:\n Operating System: Ubuntu 20.04.6 LTS\n Architecture: x86_64\n\n\
+    ///      Starting container cimg/node:20.11\n  using image cimg/node@sha256:1234\n\
+    ///      Starting container cimg/postgres:14.9\n",
+    /// );
+    /// assert_eq!(environment.images, vec!["cimg/node:20.11", "cimg/postgres:14.9"]);
+    /// assert_eq!(environment.os_label().as_deref(), Some("Ubuntu 20.04.6 LTS (x86_64)"));
+    /// ```
+    pub fn add_spin_up_log(&mut self, log: &str) {
+        for line in log.lines().map(str::trim) {
+            let image = line
+                .strip_prefix("Starting container ")
+                .or_else(|| {
+                    // "Creating a dedicated VM with ubuntu-2204:2024.01.1 image"
+                    line.split_once("VM with ")
+                        .and_then(|(_, rest)| rest.strip_suffix(" image"))
+                })
+                .map(str::trim)
+                .filter(|image| !image.is_empty() && !image.contains(' '));
+            if let Some(image) = image {
+                if !self.images.iter().any(|seen| seen == image) {
+                    self.images.push(image.to_string());
+                }
+            } else if let Some(os) = line.strip_prefix("Operating System:") {
+                self.os.get_or_insert_with(|| os.trim().to_string());
+            } else if let Some(arch) = line.strip_prefix("Architecture:") {
+                self.arch.get_or_insert_with(|| arch.trim().to_string());
+            }
+        }
+    }
+
+    /// Returns `true` if nothing about the environment is known.
+    pub fn is_empty(&self) -> bool {
+        *self == Environment::default()
+    }
+
+    /// Describes the executor and its size, e.g. `docker · medium (2 vCPU,
+    /// 4 GB RAM)`.
+    pub fn executor_label(&self) -> Option<String> {
+        let mut parts: Vec<String> = Vec::new();
+        parts.extend(self.executor.clone());
+        parts.extend(self.resource_class.clone());
+        let mut label = parts.join(" · ");
+        let size: Vec<String> = [
+            self.cpu.map(|cpu| format!("{} vCPU", cpu)),
+            self.ram
+                .map(|ram| format!("{} GB RAM", ram as f64 / 1024.0)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !size.is_empty() {
+            if label.is_empty() {
+                label = size.join(", ");
+            } else {
+                label = format!("{} ({})", label, size.join(", "));
+            }
+        }
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// Describes the host's operating system, e.g. `Ubuntu 20.04.6 LTS
+    /// (x86_64)`.
+    pub fn os_label(&self) -> Option<String> {
+        match (&self.os, &self.arch) {
+            (Some(os), Some(arch)) => Some(format!("{} ({})", os, arch)),
+            (Some(os), None) => Some(os.clone()),
+            (None, Some(arch)) => Some(arch.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+impl BuildInfo {
+    /// Returns where the log of the "Spin up environment" step is fetched
+    /// from.
+    pub fn spin_up_log_url(&self) -> Option<&str> {
+        self.steps
+            .iter()
+            .filter(|step| step.name == SPIN_UP_STEP)
+            .flat_map(|step| step.actions.iter())
+            .find_map(|action| action.output_url.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_from_build_and_machine_log() {
+        let build: BuildInfo = serde_json::from_value(serde_json::json!({
+            "build_num": 9,
+            "status": "failed",
+            "branch": "main",
+            "subject": null,
+            "picard": {
+                "executor": "machine",
+                "resource_class": {"class": "large", "cpu": 4.0, "ram": 15360}
+            },
+            "steps": [{
+                "name": "Spin up environment",
+                "actions": [{
+                    "name": "Spin up environment", "status": "success", "failed": null,
+                    "output_url": "https://example.com/spin-up", "type": "test"
+                }]
+            }]
+        }))
+        .unwrap();
+
+        let mut environment = Environment::from_build(&build);
+        assert_eq!(
+            environment.executor_label().as_deref(),
+            Some("machine · large (4 vCPU, 15 GB RAM)")
+        );
+        assert_eq!(build.spin_up_log_url(), Some("https://example.com/spin-up"));
+
+        environment.add_spin_up_log(
+            "Creating a dedicated VM with ubuntu-2204:2024.01.1 image\nusing VM ID 0a1b\n",
+        );
+        assert_eq!(environment.images, vec!["ubuntu-2204:2024.01.1"]);
+        assert_eq!(environment.os_label(), None);
+    }
+}
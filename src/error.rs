@@ -4,6 +4,8 @@ use std::fmt;
 pub enum CircleDebugError {
     ApiError { status: u16, message: String },
     AuthenticationError(String),
+    Unauthorized(String),
+    Forbidden(String),
     NetworkError(String),
     ParseError(String),
     ConfigurationError(String),
@@ -16,6 +18,8 @@ impl fmt::Display for CircleDebugError {
                 write!(f, "CircleCI API error (HTTP {}): {}", status, message)
             }
             Self::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
+            Self::Unauthorized(msg) => write!(f, "Authentication error: {}", msg),
+            Self::Forbidden(msg) => write!(f, "Permission denied: {}", msg),
             Self::NetworkError(msg) => write!(f, "Network error: {}", msg),
             Self::ParseError(msg) => write!(f, "Parse error: {}", msg),
             Self::ConfigurationError(msg) => write!(f, "Configuration error: {}", msg),
@@ -25,6 +29,193 @@ impl fmt::Display for CircleDebugError {
 
 impl std::error::Error for CircleDebugError {}
 
+impl CircleDebugError {
+    /// Classifies a non-success HTTP response from CircleCI.
+    ///
+    /// 401 means the token was rejected and becomes an
+    /// [`Unauthorized`](Self::Unauthorized); 403 means it was accepted but
+    /// can't do what `url` asks, and becomes a
+    /// [`Forbidden`](Self::Forbidden) saying why for that endpoint, e.g. a
+    /// project the token's user can't see or doesn't follow. A 404 from a
+    /// project endpoint gets a hint about following the project. Anything
+    /// else is reported as an [`ApiError`](Self::ApiError) carrying the status
+    /// and the body, or the page title for HTML error pages.
+    pub fn from_status(status: reqwest::StatusCode, url: &str, body: String) -> Self {
+        const NEW_TOKEN: &str = "help: Create a new token at https://app.circleci.com/settings/user/tokens and check it with 'cdb auth check'";
+
+        let endpoint = Endpoint::of(url);
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED if endpoint == Endpoint::Output => Self::Forbidden(
+                format!("CircleCI rejected the log or artifact URL (HTTP 401)\n  {}", NEW_TOKEN),
+            ),
+            reqwest::StatusCode::UNAUTHORIZED => Self::Unauthorized(format!(
+                "your CircleCI token is invalid, expired, or revoked (HTTP 401)\n  {}",
+                NEW_TOKEN
+            )),
+            reqwest::StatusCode::FORBIDDEN => Self::Forbidden(match endpoint {
+                Endpoint::Me => format!(
+                    "your token can't identify a user (HTTP 403); project API tokens don't work with cdb\n  {}",
+                    NEW_TOKEN
+                ),
+                Endpoint::Project { org, project, .. }
+                    if body.to_ascii_lowercase().contains("follow") =>
+                {
+                    format!(
+                        "{org}/{project} isn't followed on CircleCI (HTTP 403)\n  help: Follow it with 'cdb follow {org}/{project}'"
+                    )
+                }
+                Endpoint::Project {
+                    org,
+                    project,
+                    action: ProjectAction::Read,
+                } => format!(
+                    "your token has no access to {org}/{project} (HTTP 403)\n  help: Check that the token's user is a member of the {org} organization on GitHub and that {org} has approved CircleCI under its third-party access settings; 'cdb auth check' shows who the token belongs to"
+                ),
+                Endpoint::Project {
+                    org,
+                    project,
+                    action: ProjectAction::Trigger,
+                } => format!(
+                    "your token can't trigger builds of {org}/{project} (HTTP 403)\n  help: Rerunning needs write access to the repository on GitHub"
+                ),
+                Endpoint::Project {
+                    org,
+                    project,
+                    action: ProjectAction::Admin,
+                } => format!(
+                    "your token can't manage {org}/{project} (HTTP 403)\n  help: Checkout keys can only be listed by a project admin, i.e. an admin of the repository on GitHub"
+                ),
+                Endpoint::Workflow => "your token has no access to this workflow's project (HTTP 403)\n  help: Check that the token's user is a member of the project's organization on GitHub; 'cdb auth check' shows who the token belongs to".to_string(),
+                Endpoint::Output => "the log or artifact URL was refused (HTTP 403); these links expire\n  help: Rerun with --no-cache to fetch fresh ones".to_string(),
+            }),
+            reqwest::StatusCode::NOT_FOUND => {
+                let mut message = summarize_body(status, &body);
+                if let Endpoint::Project {
+                    org,
+                    project,
+                    action: ProjectAction::Read,
+                } = endpoint
+                {
+                    message.push_str(&format!(
+                        "\n  help: If {org}/{project} exists, check that it's followed: 'cdb projects' lists followed projects, 'cdb follow {org}/{project}' follows it"
+                    ));
+                }
+                Self::ApiError {
+                    status: 404,
+                    message,
+                }
+            }
+            status => Self::ApiError {
+                status: status.as_u16(),
+                message: summarize_body(status, &body),
+            },
+        }
+    }
+
+    /// Returns `true` if CircleCI rejected the token, or it's missing or
+    /// not allowed to do what was asked.
+    pub fn is_auth_error(&self) -> bool {
+        matches!(
+            self,
+            Self::AuthenticationError(_) | Self::Unauthorized(_) | Self::Forbidden(_)
+        )
+    }
+
+    /// Returns `true` if the requested build or resource does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::ApiError { status: 404, .. })
+    }
+}
+
+/// Which kind of CircleCI endpoint a request went to, for explaining a
+/// 401 or 403.
+#[derive(Debug, PartialEq, Eq)]
+enum Endpoint {
+    /// `/v2/me`, which only checks the token.
+    Me,
+    /// Anything under a project.
+    Project {
+        org: String,
+        project: String,
+        action: ProjectAction,
+    },
+    /// `/v2/workflow/...`, whose project isn't in the URL.
+    Workflow,
+    /// Log output and artifacts, served from signed URLs.
+    Output,
+}
+
+/// What a project endpoint needs the token's user to be allowed to do.
+#[derive(Debug, PartialEq, Eq)]
+enum ProjectAction {
+    Read,
+    Trigger,
+    Admin,
+}
+
+impl Endpoint {
+    fn of(url: &str) -> Self {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        if path.ends_with("/v2/me") {
+            return Endpoint::Me;
+        }
+        if path.contains("/v2/workflow/") {
+            return Endpoint::Workflow;
+        }
+        let segments: Vec<&str> = path.split('/').collect();
+        let project = segments.windows(4).find_map(|w| {
+            (w[0] == "project" && matches!(w[1], "github" | "gh" | "circleci"))
+                .then(|| (w[2].to_string(), w[3].to_string()))
+        });
+        match project {
+            Some((org, project)) => Endpoint::Project {
+                org,
+                project,
+                action: if path.ends_with("/ssh") || path.ends_with("/retry") {
+                    ProjectAction::Trigger
+                } else if path.contains("/checkout-key") {
+                    ProjectAction::Admin
+                } else {
+                    ProjectAction::Read
+                },
+            },
+            None => Endpoint::Output,
+        }
+    }
+}
+
+/// Shortens an error response body for display: the `message` of a JSON
+/// body, the `<title>` of an HTML page, or the status's reason phrase
+/// when an HTML page has no title.
+fn summarize_body(status: reqwest::StatusCode, body: &str) -> String {
+    let trimmed = body.trim();
+    if let Ok(serde_json::Value::Object(json)) = serde_json::from_str(trimmed) {
+        if let Some(message) = json.get("message").and_then(|m| m.as_str()) {
+            return message.to_string();
+        }
+    }
+    if trimmed.starts_with('<') {
+        let lower = trimmed.to_ascii_lowercase();
+        let title = lower
+            .find("<title>")
+            .map(|start| start + "<title>".len())
+            .and_then(|start| {
+                lower[start..]
+                    .find("</title>")
+                    .map(|len| trimmed[start..start + len].trim())
+            })
+            .filter(|title| !title.is_empty());
+        return match title {
+            Some(title) => title.to_string(),
+            None => status
+                .canonical_reason()
+                .unwrap_or("error page")
+                .to_string(),
+        };
+    }
+    trimmed.to_string()
+}
+
 impl From<reqwest::Error> for CircleDebugError {
     fn from(err: reqwest::Error) -> Self {
         CircleDebugError::NetworkError(err.to_string())
@@ -43,3 +234,72 @@ impl From<regex::Error> for CircleDebugError {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_from_status() {
+        assert!(matches!(
+            CircleDebugError::from_status(
+                StatusCode::UNAUTHORIZED,
+                "https://circleci.com/api/v2/me",
+                String::new()
+            ),
+            CircleDebugError::Unauthorized(_)
+        ));
+        let not_found = CircleDebugError::from_status(
+            StatusCode::NOT_FOUND,
+            "https://circleci.com/api/v1.1/project/github/org/repo/1",
+            "missing".into(),
+        );
+        assert!(not_found.is_not_found());
+        assert_eq!(
+            not_found.to_string(),
+            "CircleCI API error (HTTP 404): missing\n  help: If org/repo exists, check that it's followed: 'cdb projects' lists followed projects, 'cdb follow org/repo' follows it"
+        );
+        let html = CircleDebugError::from_status(
+            StatusCode::BAD_GATEWAY,
+            "https://circleci.com/api/v1.1/project/github/org/repo/1",
+            "<html><head><title>502 Bad Gateway</title></head><body>...</body></html>".into(),
+        );
+        assert_eq!(
+            html.to_string(),
+            "CircleCI API error (HTTP 502): 502 Bad Gateway"
+        );
+    }
+
+    #[test]
+    fn test_forbidden_explains_endpoint() {
+        let forbidden = |url: &str, body: &str| {
+            CircleDebugError::from_status(StatusCode::FORBIDDEN, url, body.to_string()).to_string()
+        };
+        let read = forbidden(
+            "https://circleci.com/api/v1.1/project/github/acme/web/12",
+            "<html>Forbidden</html>",
+        );
+        assert!(read.starts_with("Permission denied: your token has no access to acme/web"));
+        assert!(read.contains("member of the acme organization"));
+        assert!(forbidden(
+            "https://circleci.com/api/v1.1/project/github/acme/web/12",
+            r#"{"message": "You must follow this project"}"#
+        )
+        .contains("acme/web isn't followed"));
+        assert!(forbidden(
+            "https://circleci.com/api/v1.1/project/github/acme/web/12/ssh",
+            ""
+        )
+        .contains("can't trigger builds"));
+        assert!(forbidden(
+            "https://circleci.com/api/v2/project/gh/acme/web/checkout-key",
+            ""
+        )
+        .contains("project admin"));
+        assert!(forbidden(
+            "https://circle-production-action-output.s3.amazonaws.com/x?sig=1",
+            ""
+        )
+        .contains("--no-cache"));
+    }
+}
@@ -0,0 +1,138 @@
+//! Newline-delimited JSON events, for `--format events`.
+//!
+//! Instead of one report at the end, `cdb build --format events` prints an
+//! [`Event`] per line as the analysis gets there, so a consumer can act
+//! on the first detected error while the remaining logs are still
+//! downloading:
+//!
+//! ```text
+//! {"event":"build_fetched","org":"org","project":"repo","build_num":12345,"status":"failed",...}
+//! {"event":"action_failed","step":"Run tests","name":"Run tests","container":0}
+//! {"event":"error_detected","step":"Run tests","action":"Run tests","error":{...}}
+//! {"event":"timing_computed","build_time_millis":312000,"queued_millis":4000,"steps":[...]}
+//! ```
+
+use crate::patterns::ErrorGroup;
+use crate::report::{BuildReport, StepTiming};
+use serde::{Deserialize, Serialize};
+
+/// One step of the analysis, printed as a line of JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The build's details arrived.
+    BuildFetched {
+        /// Organization (or user) that owns the project.
+        org: String,
+        /// Project name.
+        project: String,
+        /// Build number.
+        build_num: u32,
+        /// Build status (e.g., "failed", "success").
+        status: String,
+        /// Branch the build ran on.
+        branch: Option<String>,
+        /// Full commit SHA.
+        vcs_revision: Option<String>,
+        /// Web URL of the build.
+        build_url: Option<String>,
+    },
+    /// A failed action was found; its log is fetched next.
+    ActionFailed {
+        /// Step the action belongs to.
+        step: String,
+        /// Action name.
+        name: String,
+        /// Parallel container the action ran on.
+        container: Option<u32>,
+    },
+    /// A pattern matched a failed action's log.
+    ErrorDetected {
+        /// Step the action belongs to.
+        step: String,
+        /// Action whose log matched.
+        action: String,
+        /// The matches.
+        error: ErrorGroup,
+    },
+    /// Step run times were added up; the last event of an analysis.
+    TimingComputed {
+        /// Wall-clock run time of the build.
+        build_time_millis: Option<u64>,
+        /// Time spent waiting for a machine.
+        queued_millis: Option<u64>,
+        /// Run time per step, longest first.
+        steps: Vec<StepTiming>,
+    },
+}
+
+impl Event {
+    /// Returns the [`BuildFetched`](Self::BuildFetched) event for a
+    /// report.
+    pub fn build_fetched(report: &BuildReport) -> Self {
+        Event::BuildFetched {
+            org: report.org.clone(),
+            project: report.project.clone(),
+            build_num: report.build_num,
+            status: report.status.clone(),
+            branch: report.branch.clone(),
+            vcs_revision: report.vcs_revision.clone(),
+            build_url: report.build_url.clone(),
+        }
+    }
+
+    /// Returns the [`TimingComputed`](Self::TimingComputed) event for a
+    /// report.
+    pub fn timing_computed(report: &BuildReport) -> Self {
+        Event::TimingComputed {
+            build_time_millis: report.build_time_millis,
+            queued_millis: report.queued_millis,
+            steps: report.step_timings.clone(),
+        }
+    }
+
+    /// Serializes the event as a single line of JSON, without the
+    /// trailing newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::events::Event;
+    ///
+    /// let event = Event::ActionFailed {
+    ///     step: "Run tests".to_string(),
+    ///     name: "Run tests".to_string(),
+    ///     container: Some(0),
+    /// };
+    /// assert_eq!(
+    ///     event.to_line(),
+    ///     r#"{"event":"action_failed","step":"Run tests","name":"Run tests","container":0}"#
+    /// );
+    /// ```
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("events serialize to JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::BuildBuilder;
+
+    #[test]
+    fn test_events_round_trip_one_per_line() {
+        let build = BuildBuilder::new(12).build();
+        let report = BuildReport::new("org", "repo", &build);
+        for event in [
+            Event::build_fetched(&report),
+            Event::timing_computed(&report),
+        ] {
+            let line = event.to_line();
+            assert!(!line.contains('\n'));
+            assert_eq!(serde_json::from_str::<Event>(&line).unwrap(), event);
+        }
+        assert!(Event::build_fetched(&report).to_line().starts_with(
+            r#"{"event":"build_fetched","org":"org","project":"repo","build_num":12"#
+        ));
+    }
+}
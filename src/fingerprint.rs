@@ -0,0 +1,128 @@
+//! Failure fingerprints.
+//!
+//! A fingerprint identifies "the same failure" across builds: the pattern's
+//! category plus its first matched line with the parts that change from
+//! run to run (paths, numbers, hex ids, timestamps) replaced by
+//! placeholders. Two builds with equal fingerprints failed the same way,
+//! which tells a known flake apart from a new break.
+
+use crate::patterns::ErrorGroup;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+fn time_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?:\d{4}-\d{2}-\d{2}[T ])?\d{1,2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+        )
+        .unwrap()
+    })
+}
+
+fn path_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:[A-Za-z]:)?(?:\.{0,2}[/\\])?(?:[\w.@-]+[/\\])+[\w.@-]+").unwrap()
+    })
+}
+
+fn hex_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(?:0x[0-9a-fA-F]+|[0-9a-fA-F]{6,})\b").unwrap())
+}
+
+fn number_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\d+(?:\.\d+)?").unwrap())
+}
+
+/// Replaces the run-specific parts of a log line with placeholders and
+/// collapses whitespace.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::fingerprint::normalize;
+///
+/// assert_eq!(
+///     normalize("  src/app/user.test.ts:42:7 - expected 3 to equal 4"),
+///     "<path>:<n>:<n> - expected <n> to equal <n>"
+/// );
+/// ```
+pub fn normalize(line: &str) -> String {
+    let text = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Timestamps before their digits are taken, paths before their parts
+    let text = time_regex().replace_all(&text, "<time>");
+    let text = path_regex().replace_all(&text, "<path>");
+    // Hex ids (SHAs, addresses) need digits and letters, so words like
+    // "decade" stay and plain numbers are left for the number placeholder
+    let text = hex_regex().replace_all(&text, |caps: &Captures| {
+        let id = &caps[0];
+        let digits = id.bytes().any(|b| b.is_ascii_digit());
+        let letters = id.bytes().any(|b| b.is_ascii_alphabetic());
+        if id.starts_with("0x") || (digits && letters) {
+            "<hex>".to_string()
+        } else {
+            id.to_string()
+        }
+    });
+    number_regex().replace_all(&text, "<n>").into_owned()
+}
+
+/// Returns the fingerprint of a detected error, as 16 hex digits.
+///
+/// The hash is FNV-1a, so fingerprints stay stable across Rust releases
+/// and can be stored.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::fingerprint::fingerprint;
+/// use circle_debug::patterns::{builtin_patterns, detect_errors};
+///
+/// let first = detect_errors("Segmentation fault at 0x7ffd3a", &builtin_patterns());
+/// let again = detect_errors("Segmentation fault at 0x1b2c9e", &builtin_patterns());
+/// assert_eq!(fingerprint(&first[0]), fingerprint(&again[0]));
+/// ```
+pub fn fingerprint(group: &ErrorGroup) -> String {
+    let text = format!("{}\n{}", group.category, normalize(&group.first.line));
+    let hash = text.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_strips_volatile_parts() {
+        assert_eq!(
+            normalize("2024-05-01T12:00:03.123Z Error: ENOENT /home/circleci/project/a.json"),
+            "<time> Error: ENOENT <path>"
+        );
+        assert_eq!(
+            normalize("container 3f9a2c1e exited after 12.5s at 14:02:11"),
+            "container <hex> exited after <n>s at <time>"
+        );
+        // Words that merely look like hex stay
+        assert_eq!(
+            normalize("Cannot find module 'facade'"),
+            "Cannot find module 'facade'"
+        );
+        assert_eq!(normalize("exit 1234567"), "exit <n>");
+    }
+
+    #[test]
+    fn test_fingerprint_depends_on_category() {
+        let groups = crate::patterns::detect_errors(
+            "npm ERR! code ELIFECYCLE\nexited with code 1",
+            &crate::patterns::builtin_patterns(),
+        );
+        assert!(groups.len() >= 2);
+        assert_ne!(fingerprint(&groups[0]), fingerprint(&groups[1]));
+        assert_eq!(fingerprint(&groups[0]).len(), 16);
+    }
+}
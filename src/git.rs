@@ -0,0 +1,317 @@
+//! Local git checkout helpers.
+//!
+//! Commands such as `cdb bisect` default to the repository of the current
+//! directory by reading its `origin` remote, and suspect-commit lookups
+//! read history from the checkout before falling back to the GitHub API.
+//! Source excerpts read files from it at the build's commit.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A commit and the files it changed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Commit {
+    /// Full commit SHA.
+    pub sha: String,
+    /// Author name.
+    pub author: Option<String>,
+    /// When the commit was authored.
+    #[serde(default)]
+    pub date: Option<DateTime<Utc>>,
+    /// First line of the commit message.
+    pub subject: String,
+    /// Paths changed by the commit, relative to the repository root.
+    pub files: Vec<String>,
+}
+
+impl Commit {
+    /// Returns the first 7 characters of the SHA.
+    pub fn short_sha(&self) -> &str {
+        self.sha.get(..7).unwrap_or(&self.sha)
+    }
+}
+
+/// Parses a GitHub remote URL into `(org, repo)`.
+///
+/// Accepts HTTPS (`https://github.com/org/repo.git`), SSH
+/// (`git@github.com:org/repo.git`), and `ssh://` URLs.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::git::parse_remote_url;
+///
+/// assert_eq!(
+///     parse_remote_url("git@github.com:myorg/myrepo.git"),
+///     Some(("myorg".to_string(), "myrepo".to_string()))
+/// );
+/// assert_eq!(parse_remote_url("https://gitlab.com/myorg/myrepo"), None);
+/// ```
+pub fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let path = url
+        .strip_prefix("git@github.com:")
+        .or_else(|| url.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| url.strip_prefix("https://github.com/"))
+        .or_else(|| url.strip_prefix("http://github.com/"))?;
+
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (org, repo) = path.split_once('/')?;
+    if org.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((org.to_string(), repo.to_string()))
+}
+
+/// Returns `(org, repo)` of the current directory's `origin` remote.
+///
+/// # Errors
+///
+/// Returns an error if git isn't installed, the directory isn't a
+/// checkout, or `origin` doesn't point at GitHub.
+pub fn origin_repo() -> Result<(String, String)> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .context("Failed to run 'git remote get-url origin'. Is git installed?")?;
+    if !output.status.success() {
+        bail!("No 'origin' remote found. Run from a checkout or pass --repo org/repo");
+    }
+
+    let url = String::from_utf8_lossy(&output.stdout);
+    parse_remote_url(&url).with_context(|| {
+        format!(
+            "'origin' ({}) is not a GitHub repository. Pass --repo org/repo",
+            url.trim()
+        )
+    })
+}
+
+/// Returns the root directory of the current checkout.
+///
+/// # Errors
+///
+/// Returns an error if git isn't installed or the directory isn't a
+/// checkout.
+pub fn toplevel() -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .context("Failed to run 'git rev-parse'. Is git installed?")?;
+    if !output.status.success() {
+        bail!("Not inside a git checkout");
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// Returns the full SHA of the current checkout's `HEAD`.
+///
+/// # Errors
+///
+/// Returns an error if git isn't installed or the directory isn't a
+/// checkout with commits.
+pub fn head_revision() -> Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .context("Failed to run 'git rev-parse'. Is git installed?")?;
+    if !output.status.success() {
+        bail!("Not inside a git checkout with commits");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the name of the branch checked out in the current directory.
+///
+/// # Errors
+///
+/// Returns an error if git isn't installed, the directory isn't a
+/// checkout, or `HEAD` is detached.
+pub fn current_branch() -> Result<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .context("Failed to run 'git symbolic-ref'. Is git installed?")?;
+    if !output.status.success() {
+        bail!("Not on a branch: HEAD is detached or this isn't a git checkout");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns a file's contents at commit `sha`, from the current checkout.
+///
+/// `path` is relative to the repository root.
+///
+/// # Errors
+///
+/// Returns an error if git fails, for example because the commit hasn't
+/// been fetched or the file didn't exist at it.
+pub fn file_at(sha: &str, path: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args(["show", &format!("{}:{}", sha, path)])
+        .output()
+        .context("Failed to run 'git show'. Is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git show {}:{} failed: {}",
+            sha,
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns `true` if the current checkout has the commit `sha`.
+pub fn has_commit(sha: &str) -> bool {
+    Command::new("git")
+        .args(["cat-file", "-e", &format!("{}^{{commit}}", sha)])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Lists the commits reachable from `head` but not `base`, newest first,
+/// from the current checkout.
+///
+/// # Errors
+///
+/// Returns an error if git fails, for example because either commit
+/// hasn't been fetched.
+pub fn commits_between(base: &str, head: &str) -> Result<Vec<Commit>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            LOG_FORMAT,
+            "--name-only",
+            &format!("{}..{}", base, head),
+        ])
+        .output()
+        .context("Failed to run 'git log'. Is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git log {}..{} failed: {}",
+            base,
+            head,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Lists the last `limit` commits up to `revision` that touched `path`,
+/// newest first, from the current checkout.
+///
+/// `path` may be relative to a package rather than the repository root,
+/// as test runners in monorepos report it: it matches at any depth.
+///
+/// # Errors
+///
+/// Returns an error if git fails, for example because `revision` hasn't
+/// been fetched.
+pub fn file_history(revision: &str, path: &str, limit: usize) -> Result<Vec<Commit>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            LOG_FORMAT,
+            "--name-only",
+            "-n",
+            &limit.to_string(),
+            revision,
+            "--",
+            &format!(":(glob)**/{}", path),
+        ])
+        .output()
+        .context("Failed to run 'git log'. Is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git log -- {} failed: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// `git log` format read by [`parse_log`]: a record separator, then the
+/// SHA, author, author date, and subject separated by unit separators.
+const LOG_FORMAT: &str = "--format=%x1e%H%x1f%an%x1f%aI%x1f%s";
+
+/// Parses `git log` output in [`LOG_FORMAT`] with `--name-only`.
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut header = lines.next()?.split('\x1f');
+            let sha = header.next()?.trim();
+            if sha.is_empty() {
+                return None;
+            }
+            Some(Commit {
+                sha: sha.to_string(),
+                author: header.next().map(str::to_string),
+                date: header
+                    .next()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    .map(|date| date.with_timezone(&Utc)),
+                subject: header.next().unwrap_or_default().to_string(),
+                files: lines
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_url() {
+        let expected = Some(("org".to_string(), "repo".to_string()));
+        assert_eq!(
+            parse_remote_url("https://github.com/org/repo.git\n"),
+            expected
+        );
+        assert_eq!(parse_remote_url("https://github.com/org/repo/"), expected);
+        assert_eq!(
+            parse_remote_url("ssh://git@github.com/org/repo.git"),
+            expected
+        );
+        assert_eq!(parse_remote_url("git@github.com:org/repo"), expected);
+        assert_eq!(parse_remote_url("https://github.com/org"), None);
+        assert_eq!(
+            parse_remote_url("https://github.com/org/repo/tree/main"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let output = "\x1eabc123\x1fAda\x1f2024-05-01T12:00:00+02:00\x1fFix login\n\nsrc/login.ts\nsrc/login.test.ts\n\
+                      \x1edef456\x1fBob\x1fnot a date\x1fAdd tests\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].author.as_deref(), Some("Ada"));
+        assert_eq!(
+            commits[0].date.map(|date| date.to_rfc3339()),
+            Some("2024-05-01T10:00:00+00:00".to_string())
+        );
+        assert_eq!(commits[0].files, vec!["src/login.ts", "src/login.test.ts"]);
+        assert_eq!(
+            (commits[1].date, commits[1].subject.as_str()),
+            (None, "Add tests")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! GitHub REST API client.
+//!
+//! Used when the local checkout can't answer a question, such as which
+//! commits lie between two builds. A token from `GITHUB_TOKEN` or
+//! `GH_TOKEN` is sent when set; public repositories work without one, at
+//! a lower rate limit.
+
+use crate::git::Commit;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Commits whose changed files are looked up individually; the compare
+/// endpoint only lists files for the range as a whole.
+pub const MAX_COMMIT_FILE_LOOKUPS: usize = 30;
+
+/// Client for the GitHub REST API.
+#[derive(Debug, Clone)]
+pub struct GitHubClient {
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comparison {
+    commits: Vec<ApiCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiCommit {
+    sha: String,
+    commit: CommitDetails,
+    #[serde(default)]
+    files: Vec<ApiFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitDetails {
+    message: String,
+    author: Option<CommitAuthor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitAuthor {
+    name: Option<String>,
+    date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiFile {
+    filename: String,
+}
+
+impl From<ApiCommit> for Commit {
+    fn from(api: ApiCommit) -> Self {
+        Commit {
+            sha: api.sha,
+            author: api
+                .commit
+                .author
+                .as_ref()
+                .and_then(|author| author.name.clone()),
+            date: api.commit.author.as_ref().and_then(|author| author.date),
+            subject: api
+                .commit
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            files: api.files.into_iter().map(|file| file.filename).collect(),
+        }
+    }
+}
+
+impl Default for GitHubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitHubClient {
+    /// Creates a client using `GITHUB_TOKEN` or `GH_TOKEN` if set.
+    pub fn new() -> Self {
+        let token = std::env::var("GITHUB_TOKEN")
+            .or_else(|_| std::env::var("GH_TOKEN"))
+            .ok()
+            .filter(|token| !token.is_empty());
+        Self::with_token(token)
+    }
+
+    /// Creates a client with an explicit token, or none.
+    pub fn with_token(token: Option<String>) -> Self {
+        GitHubClient {
+            client: reqwest::Client::new(),
+            token,
+        }
+    }
+
+    /// Lists the commits between `base` and `head`, newest first, with the
+    /// files each one changed.
+    ///
+    /// Files are looked up for at most [`MAX_COMMIT_FILE_LOOKUPS`] of the
+    /// newest commits; older ones are returned without files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails or GitHub rejects it (for
+    /// example a private repository without a token).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::github::GitHubClient;
+    ///
+    /// let commits = GitHubClient::new().compare("myorg", "myrepo", "abc123", "def456").await?;
+    /// for commit in commits {
+    ///     println!("{} {}", commit.short_sha(), commit.subject);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn compare(
+        &self,
+        org: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<Vec<Commit>> {
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/compare/{}...{}",
+            org, repo, base, head
+        );
+        let comparison: Comparison = self.get_json(&url).await?;
+        let mut commits: Vec<Commit> = comparison.commits.into_iter().map(Commit::from).collect();
+        commits.reverse();
+
+        let details = futures::future::join_all(
+            commits
+                .iter()
+                .take(MAX_COMMIT_FILE_LOOKUPS)
+                .map(|commit| self.commit(org, repo, &commit.sha)),
+        )
+        .await;
+        for (commit, detail) in commits.iter_mut().zip(details) {
+            if let Ok(detail) = detail {
+                commit.files = detail.files;
+            }
+        }
+
+        Ok(commits)
+    }
+
+    /// Fetches a single commit with its changed files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the commit doesn't exist.
+    pub async fn commit(&self, org: &str, repo: &str, sha: &str) -> Result<Commit> {
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/commits/{}",
+            org, repo, sha
+        );
+        let commit: ApiCommit = self.get_json(&url).await?;
+        Ok(commit.into())
+    }
+
+    /// Lists the last `limit` commits up to `revision` that touched
+    /// `path`, newest first, without their changed files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or GitHub rejects it.
+    pub async fn file_commits(
+        &self,
+        org: &str,
+        repo: &str,
+        path: &str,
+        revision: &str,
+        limit: usize,
+    ) -> Result<Vec<Commit>> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("https://api.github.com/repos/{}/{}/commits", org, repo),
+            [
+                ("path", path),
+                ("sha", revision),
+                ("per_page", &limit.to_string()),
+            ],
+        )?;
+        let commits: Vec<ApiCommit> = self.get_json(url.as_str()).await?;
+        Ok(commits.into_iter().map(Commit::from).collect())
+    }
+
+    /// Fetches a file's raw contents at `revision` (the default branch when
+    /// `None`).
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file doesn't exist, or the repository is private and
+    /// no token is set (GitHub answers 404 for both).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or GitHub rejects it.
+    pub async fn file_contents(
+        &self,
+        org: &str,
+        repo: &str,
+        path: &str,
+        revision: Option<&str>,
+    ) -> Result<Option<String>> {
+        let mut url = format!(
+            "https://api.github.com/repos/{}/{}/contents/{}",
+            org, repo, path
+        );
+        if let Some(revision) = revision {
+            url.push_str(&format!("?ref={}", revision));
+        }
+
+        let response = self.send(&url, "application/vnd.github.raw+json").await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(self.check(response).await?.text().await?))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let response = self.send(url, "application/vnd.github+json").await?;
+        Ok(self.check(response).await?.json().await?)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, accept))]
+    async fn send(&self, url: &str, accept: &str) -> Result<reqwest::Response> {
+        let mut request = self
+            .client
+            .get(url)
+            .header("Accept", accept)
+            .header("User-Agent", concat!("cdb/", env!("CARGO_PKG_VERSION")));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let started = std::time::Instant::now();
+        let response = request
+            .send()
+            .await
+            .context("Failed to connect to the GitHub API")?;
+        tracing::info!(
+            status = response.status().as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "response received"
+        );
+        Ok(response)
+    }
+
+    /// Turns an unsuccessful response into an error.
+    async fn check(&self, response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let hint = if self.token.is_none() && (status == 401 || status == 403 || status == 404)
+            {
+                "\n  help: set GITHUB_TOKEN for private repositories or to raise the rate limit"
+            } else {
+                ""
+            };
+            bail!("GitHub API returned {}: {}{}", status, text.trim(), hint);
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_from_api() {
+        let comparison: Comparison = serde_json::from_str(
+            r#"{"commits": [
+                {"sha": "aaaaaaa1", "commit": {"message": "Fix parser\n\nLonger body", "author": {"name": "Ada"}}},
+                {"sha": "bbbbbbb2", "commit": {"message": "Bump deps", "author": null},
+                 "files": [{"filename": "package.json", "status": "modified"}]}
+            ]}"#,
+        )
+        .unwrap();
+        let commits: Vec<Commit> = comparison.commits.into_iter().map(Commit::from).collect();
+
+        assert_eq!(commits[0].subject, "Fix parser");
+        assert_eq!(commits[0].author.as_deref(), Some("Ada"));
+        assert!(commits[0].files.is_empty());
+        assert_eq!(commits[1].author, None);
+        assert_eq!(commits[1].files, vec!["package.json"]);
+    }
+}
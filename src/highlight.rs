@@ -0,0 +1,197 @@
+//! User-defined highlighting of log lines.
+//!
+//! `[[highlights]]` entries in the config file color parts of the log
+//! lines `cdb build` prints, on top of the built-in error and warning
+//! coloring, e.g. to make correlation ids or service names stand out:
+//!
+//! ```toml
+//! [[highlights]]
+//! regex = "req-[0-9a-f]{8}"
+//! color = "magenta"
+//! style = ["bold"]
+//!
+//! [[highlights]]
+//! regex = "payments-api"
+//! background = "blue"
+//! ```
+//!
+//! Highlighting doesn't affect error detection.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::ops::Range;
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+/// A text style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    Bold,
+    Dimmed,
+    Italic,
+    Underline,
+}
+
+/// A highlight rule defined in the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightConfig {
+    /// Regex whose matches are highlighted.
+    pub regex: String,
+    /// Text color.
+    pub color: Option<Color>,
+    /// Background color.
+    pub background: Option<Color>,
+    /// Styles, e.g. `["bold", "underline"]`.
+    #[serde(default)]
+    pub style: Vec<Style>,
+}
+
+impl HighlightConfig {
+    /// Compiles this entry into a [`HighlightRule`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid or the entry sets neither
+    /// a color nor a style.
+    pub fn compile(&self) -> Result<HighlightRule> {
+        if self.color.is_none() && self.background.is_none() && self.style.is_empty() {
+            bail!(
+                "Highlight '{}' needs a color, background, or style",
+                self.regex
+            );
+        }
+        Ok(HighlightRule {
+            regex: Regex::new(&self.regex)
+                .with_context(|| format!("Invalid regex for highlight '{}'", self.regex))?,
+            color: self.color,
+            background: self.background,
+            style: self.style.clone(),
+        })
+    }
+}
+
+/// A compiled highlight rule.
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    /// Regex whose matches are highlighted.
+    pub regex: Regex,
+    /// Text color.
+    pub color: Option<Color>,
+    /// Background color.
+    pub background: Option<Color>,
+    /// Styles to apply.
+    pub style: Vec<Style>,
+}
+
+/// Finds the parts of `line` that `rules` highlight.
+///
+/// Where matches overlap, the one starting first wins, and at the same
+/// start, the earlier rule.
+///
+/// # Returns
+///
+/// Non-overlapping byte ranges in order, each with the rule that matched.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::highlight::{highlight_spans, HighlightConfig};
+///
+/// let rule = HighlightConfig {
+///     regex: r"req-\d+".to_string(),
+///     color: None,
+///     background: None,
+///     style: vec![circle_debug::highlight::Style::Bold],
+/// }
+/// .compile()
+/// .unwrap();
+/// let spans = highlight_spans("GET /users req-42 500", std::slice::from_ref(&rule));
+/// assert_eq!(spans[0].0, 11..17);
+/// ```
+pub fn highlight_spans<'r>(
+    line: &str,
+    rules: &'r [HighlightRule],
+) -> Vec<(Range<usize>, &'r HighlightRule)> {
+    let mut matches: Vec<(Range<usize>, usize)> = rules
+        .iter()
+        .enumerate()
+        .flat_map(|(i, rule)| {
+            rule.regex
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(move |m| (m.range(), i))
+        })
+        .collect();
+    matches.sort_by_key(|(range, i)| (range.start, *i));
+
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for (range, i) in matches {
+        if range.start >= end {
+            end = range.end;
+            spans.push((range, &rules[i]));
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_spans_first_match_wins() {
+        let rule = |regex: &str, color: Option<Color>, style: Vec<Style>| {
+            HighlightConfig {
+                regex: regex.to_string(),
+                color,
+                background: None,
+                style,
+            }
+            .compile()
+        };
+        let rules = vec![
+            rule("payments-api", Some(Color::Blue), Vec::new()).unwrap(),
+            rule("api", None, vec![Style::Underline]).unwrap(),
+            rule("req-[0-9a-f]+", Some(Color::Magenta), vec![Style::Bold]).unwrap(),
+        ];
+
+        let line = "payments-api req-1f2e failed; retrying api";
+        let spans: Vec<(&str, Option<Color>)> = highlight_spans(line, &rules)
+            .into_iter()
+            .map(|(range, rule)| (&line[range], rule.color))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("payments-api", Some(Color::Blue)),
+                ("req-1f2e", Some(Color::Magenta)),
+                ("api", None),
+            ]
+        );
+        assert!(rule("x", None, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! Recent builds of a project.
+//!
+//! Lists builds with the v1.1 project endpoints and checks whether errors
+//! seen in one build keep showing up in others, which separates a flaky
+//! network from a problem introduced by the change under test.
+//! [`Bisect`] walks a branch back to the build where it went red.
+
+use crate::patterns::{self, ErrorPattern};
+use crate::{v1_vcs, BuildInfo, CircleClient};
+use anyhow::{Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// How much of each log tail to scan when looking for recurring errors.
+pub const HISTORY_TAIL_BYTES: u64 = 256 * 1024;
+
+/// Which builds of a project to list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuildFilter {
+    /// Every build, including queued and running ones.
+    #[default]
+    All,
+    /// Finished builds, whatever their result.
+    Completed,
+    /// Builds that passed.
+    Successful,
+    /// Builds that failed.
+    Failed,
+    /// Builds that are queued or running.
+    Running,
+}
+
+impl BuildFilter {
+    /// Value of the API's `filter` query parameter.
+    fn as_query(self) -> Option<&'static str> {
+        match self {
+            BuildFilter::All => None,
+            BuildFilter::Completed => Some("completed"),
+            BuildFilter::Successful => Some("successful"),
+            BuildFilter::Failed => Some("failed"),
+            BuildFilter::Running => Some("running"),
+        }
+    }
+}
+
+/// Parameters for [`CircleClient::recent_builds`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildQuery {
+    /// Only list builds of this branch.
+    pub branch: Option<String>,
+    /// Only list builds in this state.
+    pub filter: BuildFilter,
+    /// Number of builds to return; the API allows at most 100.
+    pub limit: u32,
+    /// Number of most recent builds to skip, for paging further back.
+    pub offset: u32,
+}
+
+impl Default for BuildQuery {
+    fn default() -> Self {
+        BuildQuery {
+            branch: None,
+            filter: BuildFilter::All,
+            limit: 30,
+            offset: 0,
+        }
+    }
+}
+
+/// Recent builds in which a pattern matched again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SimilarErrors {
+    /// Id of the pattern.
+    pub pattern_id: String,
+    /// Category of the pattern.
+    pub category: String,
+    /// Build numbers whose failed logs matched, most recent first.
+    pub builds: Vec<u32>,
+    /// Number of recent failed builds whose logs were checked.
+    pub checked: usize,
+}
+
+/// Where a job on a branch went from passing to failing.
+///
+/// Feed builds newest first to [`push`](Self::push) until it returns
+/// `true`, or use [`CircleClient::bisect`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bisect {
+    /// Only consider builds of this workflow job; `None` considers all.
+    pub job: Option<String>,
+    /// Only consider builds numbered below this one.
+    pub before: Option<u32>,
+    /// Most recent finished build of the job.
+    pub latest: Option<BuildInfo>,
+    /// Oldest build of the current failing streak.
+    pub first_red: Option<BuildInfo>,
+    /// Newest passing build before the failing streak, or the latest build
+    /// when the branch is green.
+    pub last_green: Option<BuildInfo>,
+    /// Number of builds of the job that were considered.
+    pub checked: usize,
+}
+
+/// Whether a finished build passed (`Some(true)`), failed (`Some(false)`),
+/// or says nothing about the code (`None`, e.g. canceled).
+fn outcome(build: &BuildInfo) -> Option<bool> {
+    match build.status.as_str() {
+        "success" | "fixed" | "no_tests" => Some(true),
+        "failed" | "infrastructure_fail" | "timedout" => Some(false),
+        _ => None,
+    }
+}
+
+impl Bisect {
+    /// Creates a bisect over builds of `job`, or of every job with `None`.
+    pub fn new(job: Option<&str>) -> Self {
+        Bisect {
+            job: job.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    /// Considers the next older build.
+    ///
+    /// Builds of other jobs and builds that were canceled or didn't run
+    /// are ignored.
+    ///
+    /// # Returns
+    ///
+    /// `true` once the last green build is found and no more builds are
+    /// needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::history::Bisect;
+    /// use circle_debug::BuildInfo;
+    ///
+    /// let build = |num: u32, status: &str| -> BuildInfo {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "build_num": num, "status": status, "branch": "main", "subject": null
+    ///     }))
+    ///     .unwrap()
+    /// };
+    ///
+    /// let mut bisect = Bisect::new(None);
+    /// assert!(!bisect.push(&build(12, "failed")));
+    /// assert!(!bisect.push(&build(11, "failed")));
+    /// assert!(bisect.push(&build(10, "success")));
+    ///
+    /// assert_eq!(bisect.first_red.unwrap().build_num, 11);
+    /// assert_eq!(bisect.last_green.unwrap().build_num, 10);
+    /// ```
+    pub fn push(&mut self, build: &BuildInfo) -> bool {
+        if self.is_done() {
+            return true;
+        }
+        if self.before.is_some_and(|before| build.build_num >= before) {
+            return false;
+        }
+        if let Some(job) = &self.job {
+            let name = build.workflows.as_ref().and_then(|w| w.job_name.as_deref());
+            if name != Some(job.as_str()) {
+                return false;
+            }
+        }
+        let Some(passed) = outcome(build) else {
+            return false;
+        };
+
+        self.checked += 1;
+        if self.latest.is_none() {
+            self.latest = Some(build.clone());
+        }
+        if passed {
+            self.last_green = Some(build.clone());
+        } else {
+            self.first_red = Some(build.clone());
+        }
+        self.is_done()
+    }
+
+    /// Returns `true` once the last green build is known.
+    pub fn is_done(&self) -> bool {
+        self.last_green.is_some()
+    }
+
+    /// Returns `true` if the latest build of the job passed.
+    pub fn is_green(&self) -> bool {
+        self.first_red.is_none() && self.last_green.is_some()
+    }
+}
+
+impl CircleClient {
+    /// Lists a project's builds, most recent first.
+    ///
+    /// Builds in the list don't include their steps; fetch them with
+    /// [`get_build`](Self::get_build) to see actions and log URLs.
+    ///
+    /// # Arguments
+    ///
+    /// * `org` - The GitHub organization name
+    /// * `project` - The repository/project name
+    /// * `query` - Branch, state, and paging
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the response cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::history::{BuildFilter, BuildQuery};
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let query = BuildQuery {
+    ///     branch: Some("main".to_string()),
+    ///     filter: BuildFilter::Failed,
+    ///     limit: 10,
+    ///     ..Default::default()
+    /// };
+    /// for build in client.recent_builds("myorg", "myrepo", &query).await? {
+    ///     println!("#{} {}", build.build_num, build.status);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn recent_builds(
+        &self,
+        org: &str,
+        project: &str,
+        query: &BuildQuery,
+    ) -> Result<Vec<BuildInfo>> {
+        let mut url = Url::parse(&format!("{}/v1.1/project/{}/", self.base_url, v1_vcs(org)))?;
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| anyhow::anyhow!("invalid API base URL"))?;
+            segments.pop_if_empty().push(org).push(project);
+            if let Some(branch) = &query.branch {
+                segments.push("tree").push(branch);
+            }
+        }
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_pair("limit", &query.limit.to_string());
+            pairs.append_pair("offset", &query.offset.to_string());
+            if let Some(filter) = query.filter.as_query() {
+                pairs.append_pair("filter", filter);
+            }
+        }
+
+        self.get_json(url.as_str()).await
+    }
+
+    /// Walks a branch's builds back from the newest to find where `job`
+    /// went red.
+    ///
+    /// Pages through at most `max_builds` finished builds of the branch.
+    /// The returned [`Bisect`] has no `last_green` if none was found in
+    /// that window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a page of builds cannot be listed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let bisect = client.bisect("myorg", "myrepo", "main", Some("build-and-test"), 200).await?;
+    /// if let (Some(good), Some(bad)) = (&bisect.last_green, &bisect.first_red) {
+    ///     println!("broke between #{} and #{}", good.build_num, bad.build_num);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn bisect(
+        &self,
+        org: &str,
+        project: &str,
+        branch: &str,
+        job: Option<&str>,
+        max_builds: u32,
+    ) -> Result<Bisect> {
+        self.walk_branch(org, project, branch, Bisect::new(job), max_builds)
+            .await
+    }
+
+    /// Finds the newest passing build of the same branch and job that ran
+    /// before `build`.
+    ///
+    /// Looks back through at most `max_builds` finished builds. Returns
+    /// `None` if the build has no branch or no passing build was found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a page of builds cannot be listed.
+    pub async fn previous_green(
+        &self,
+        org: &str,
+        project: &str,
+        build: &BuildInfo,
+        max_builds: u32,
+    ) -> Result<Option<BuildInfo>> {
+        let Some(branch) = &build.branch else {
+            return Ok(None);
+        };
+        let job = build.workflows.as_ref().and_then(|w| w.job_name.as_deref());
+        let bisect = Bisect {
+            before: Some(build.build_num),
+            ..Bisect::new(job)
+        };
+
+        Ok(self
+            .walk_branch(org, project, branch, bisect, max_builds)
+            .await?
+            .last_green)
+    }
+
+    /// Feeds a branch's finished builds, newest first, to `bisect` until it
+    /// is done or `max_builds` were listed.
+    async fn walk_branch(
+        &self,
+        org: &str,
+        project: &str,
+        branch: &str,
+        mut bisect: Bisect,
+        max_builds: u32,
+    ) -> Result<Bisect> {
+        const PAGE_SIZE: u32 = 100;
+        let mut offset = 0;
+
+        while offset < max_builds {
+            let query = BuildQuery {
+                branch: Some(branch.to_string()),
+                filter: BuildFilter::Completed,
+                limit: PAGE_SIZE.min(max_builds - offset),
+                offset,
+            };
+            let page = self.recent_builds(org, project, &query).await?;
+            tracing::debug!(offset, builds = page.len(), "walking branch history");
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len() as u32;
+            if page.iter().any(|build| bisect.push(build)) {
+                break;
+            }
+        }
+
+        Ok(bisect)
+    }
+
+    /// Checks how many recent failed builds hit the same patterns.
+    ///
+    /// Fetches the last `limit` failed builds of the project (skipping
+    /// `exclude`, usually the build being analyzed) and scans the last
+    /// [`HISTORY_TAIL_BYTES`] of each failed action's log with `patterns`.
+    /// Builds or logs that fail to load are skipped and not counted as
+    /// checked.
+    ///
+    /// # Returns
+    ///
+    /// One [`SimilarErrors`] per pattern, in the order given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recent builds cannot be listed.
+    pub async fn similar_errors(
+        &self,
+        org: &str,
+        project: &str,
+        exclude: u32,
+        patterns: &[ErrorPattern],
+        limit: u32,
+    ) -> Result<Vec<SimilarErrors>> {
+        let query = BuildQuery {
+            filter: BuildFilter::Failed,
+            // One extra in case the excluded build is among them
+            limit: limit + 1,
+            ..Default::default()
+        };
+        let recent: Vec<u32> = self
+            .recent_builds(org, project, &query)
+            .await
+            .context("Failed to list recent builds")?
+            .into_iter()
+            .map(|build| build.build_num)
+            .filter(|&number| number != exclude)
+            .take(limit as usize)
+            .collect();
+
+        let scans = futures::future::join_all(
+            recent
+                .iter()
+                .map(|&number| self.matched_patterns(org, project, number, patterns)),
+        )
+        .await;
+
+        let mut similar: Vec<SimilarErrors> = patterns
+            .iter()
+            .map(|pattern| SimilarErrors {
+                pattern_id: pattern.id.clone(),
+                category: pattern.category.clone(),
+                builds: Vec::new(),
+                checked: 0,
+            })
+            .collect();
+
+        for (number, matched) in recent.iter().zip(scans) {
+            let Ok(matched) = matched else { continue };
+            for entry in &mut similar {
+                entry.checked += 1;
+                if matched.contains(&entry.pattern_id) {
+                    entry.builds.push(*number);
+                }
+            }
+        }
+
+        Ok(similar)
+    }
+
+    /// Returns the ids of `patterns` matching any failed log of a build.
+    async fn matched_patterns(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+        patterns: &[ErrorPattern],
+    ) -> Result<Vec<String>> {
+        let build = self.get_build(org, project, build_num).await?;
+        let logs = futures::future::join_all(
+            build
+                .failed_actions()
+                .filter_map(|action| action.output_url.as_deref())
+                .map(|url| self.get_logs_tail(url, HISTORY_TAIL_BYTES)),
+        )
+        .await;
+
+        // A build counts as checked if at least one of its logs loaded
+        if logs.iter().all(Result::is_err) {
+            if let Some(Err(e)) = logs.into_iter().next() {
+                return Err(e);
+            }
+            return Ok(Vec::new());
+        }
+
+        let mut matched = Vec::new();
+        for log in logs.into_iter().flatten() {
+            for group in patterns::detect_errors(&log, patterns) {
+                if !matched.contains(&group.pattern_id) {
+                    matched.push(group.pattern_id);
+                }
+            }
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_list_without_steps() {
+        let builds: Vec<BuildInfo> = serde_json::from_str(
+            r#"[{"build_num": 120, "status": "failed", "branch": "main", "subject": "Bump deps",
+                 "vcs_revision": "abc123"},
+                {"build_num": 119, "status": "success", "branch": "main", "subject": null}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(builds.len(), 2);
+        assert!(builds[0].steps.is_empty());
+        assert!(builds[1].is_success());
+        assert_eq!(BuildFilter::Failed.as_query(), Some("failed"));
+        assert_eq!(BuildFilter::All.as_query(), None);
+    }
+
+    fn build(num: u32, status: &str, job: &str) -> BuildInfo {
+        serde_json::from_value(serde_json::json!({
+            "build_num": num, "status": status, "branch": "main", "subject": null,
+            "workflows": {"job_name": job}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bisect_skips_other_jobs_and_canceled_builds() {
+        let mut bisect = Bisect::new(Some("test"));
+        let builds = [
+            build(20, "failed", "test"),
+            build(19, "success", "lint"),
+            build(18, "canceled", "test"),
+            build(17, "timedout", "test"),
+            build(16, "fixed", "test"),
+            build(15, "failed", "test"),
+        ];
+        let done_at = builds.iter().position(|b| bisect.push(b));
+
+        assert_eq!(done_at, Some(4));
+        assert_eq!(bisect.checked, 3);
+        assert_eq!(bisect.latest.as_ref().unwrap().build_num, 20);
+        assert_eq!(bisect.first_red.as_ref().unwrap().build_num, 17);
+        assert_eq!(bisect.last_green.as_ref().unwrap().build_num, 16);
+        assert!(!bisect.is_green());
+    }
+
+    #[test]
+    fn test_bisect_before() {
+        let mut bisect = Bisect {
+            before: Some(20),
+            ..Bisect::new(Some("test"))
+        };
+        assert!(!bisect.push(&build(21, "success", "test")));
+        assert!(!bisect.push(&build(20, "failed", "test")));
+        assert!(bisect.push(&build(19, "success", "test")));
+        assert_eq!(bisect.last_green.unwrap().build_num, 19);
+        assert!(bisect.first_red.is_none());
+    }
+
+    #[test]
+    fn test_bisect_green_branch() {
+        let mut bisect = Bisect::new(None);
+        assert!(bisect.push(&build(5, "success", "test")));
+        assert!(bisect.is_green());
+        assert!(bisect.first_red.is_none());
+    }
+}
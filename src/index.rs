@@ -0,0 +1,572 @@
+//! Full-text index of cached logs.
+//!
+//! [`LogIndex`] indexes the action logs in the response
+//! [`cache`](crate::cache) by their trigrams. A search turns the literal
+//! text a regex requires into trigrams and runs the regex only over the
+//! logs that contain all of them, so searching hundreds of builds takes
+//! milliseconds instead of a scan of every log. Only cached logs are
+//! indexed; builds are added with [`LogIndex::update`] (`cdb cache index`).
+//!
+//! Requires the `index` feature.
+
+use crate::cache::ResponseCache;
+use crate::download::read_cached_log;
+use crate::search::{
+    cached_builds, search_log, strip_ansi, SearchHit, SearchOptions, SearchResults,
+};
+use crate::BuildInfo;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use regex_syntax::hir::{Hir, HirKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use tantivy::collector::{Count, DocSetCollector};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING,
+};
+use tantivy::tokenizer::{LowerCaser, NgramTokenizer, TextAnalyzer};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// Name the trigram tokenizer is registered under.
+const TRIGRAM: &str = "trigram";
+
+/// Memory the index writer may buffer before flushing to disk.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// A trigram index over cached action logs.
+pub struct LogIndex {
+    index: Index,
+    reader: IndexReader,
+    fields: Fields,
+}
+
+/// Fields of an indexed log.
+struct Fields {
+    /// `org/project/build_num`, to find a build's logs.
+    build: Field,
+    /// `org/project`.
+    project: Field,
+    /// Branch of the build.
+    branch: Field,
+    /// [`IndexedLog`] as JSON.
+    meta: Field,
+    /// Trigrams of the log text; not stored.
+    text: Field,
+}
+
+/// What's stored about an indexed log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedLog {
+    build_num: u32,
+    status: String,
+    branch: Option<String>,
+    start_time: Option<DateTime<Utc>>,
+    step: String,
+    container: Option<u32>,
+    /// Position of the action within the build, to keep step order.
+    position: usize,
+    output_url: String,
+}
+
+/// What [`LogIndex::update`] added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexUpdate {
+    /// Number of builds added.
+    pub builds: usize,
+    /// Number of logs added.
+    pub logs: usize,
+}
+
+impl LogIndex {
+    /// Returns the default index directory, see [`paths`](crate::paths).
+    pub fn default_dir() -> PathBuf {
+        crate::paths::log_index_dir()
+    }
+
+    /// Opens the index in `dir`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be created or holds an
+    /// index that can't be read.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let directory = MmapDirectory::open(dir)
+            .with_context(|| format!("Failed to open index {}", dir.display()))?;
+        let index = Index::open_or_create(directory, schema())
+            .with_context(|| format!("Failed to open index {}", dir.display()))?;
+        Self::with_index(index)
+    }
+
+    /// Opens the index in `dir` if one has been created.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index exists but can't be read.
+    pub fn open_existing(dir: &Path) -> Result<Option<Self>> {
+        if !dir.join("meta.json").exists() {
+            return Ok(None);
+        }
+        Self::open(dir).map(Some)
+    }
+
+    /// Creates an empty index in memory, for tests and one-off use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index can't be set up.
+    pub fn in_memory() -> Result<Self> {
+        Self::with_index(Index::create_in_ram(schema()))
+    }
+
+    fn with_index(index: Index) -> Result<Self> {
+        index.tokenizers().register(
+            TRIGRAM,
+            TextAnalyzer::builder(NgramTokenizer::all_ngrams(3, 3)?)
+                .filter(LowerCaser)
+                .build(),
+        );
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let schema = index.schema();
+        let fields = Fields {
+            build: schema.get_field("build")?,
+            project: schema.get_field("project")?,
+            branch: schema.get_field("branch")?,
+            meta: schema.get_field("meta")?,
+            text: schema.get_field("text")?,
+        };
+        Ok(LogIndex {
+            index,
+            reader,
+            fields,
+        })
+    }
+
+    /// Returns the number of indexed logs.
+    pub fn len(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Returns `true` if no logs are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Indexes the cached logs of every cached finished build that isn't
+    /// indexed yet.
+    ///
+    /// A build is indexed once; logs cached after that are picked up by
+    /// [`rebuild`](Self::rebuild).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index can't be written.
+    pub fn update(&self, cache: &ResponseCache) -> Result<IndexUpdate> {
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        let searcher = self.reader.searcher();
+        let mut update = IndexUpdate { builds: 0, logs: 0 };
+
+        for (org, project, build) in cached_builds(cache) {
+            let key = format!("{}/{}/{}", org, project, build.build_num);
+            let query = TermQuery::new(
+                Term::from_field_text(self.fields.build, &key),
+                IndexRecordOption::Basic,
+            );
+            if searcher.search(&query, &Count)? > 0 {
+                continue;
+            }
+            update.logs += self.add_build(&writer, cache, &org, &project, &build)?;
+            update.builds += 1;
+        }
+
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(update)
+    }
+
+    /// Drops everything and indexes the cache again from scratch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index can't be written.
+    pub fn rebuild(&self, cache: &ResponseCache) -> Result<IndexUpdate> {
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        writer.delete_all_documents()?;
+        writer.commit()?;
+        // Release the writer lock for update's own writer
+        drop(writer);
+        self.reader.reload()?;
+        self.update(cache)
+    }
+
+    /// Adds the cached logs of one build, returning how many there were.
+    fn add_build(
+        &self,
+        writer: &IndexWriter,
+        cache: &ResponseCache,
+        org: &str,
+        project: &str,
+        build: &BuildInfo,
+    ) -> Result<usize> {
+        let actions = build
+            .steps
+            .iter()
+            .flat_map(|step| step.actions.iter().map(move |action| (step, action)));
+        let mut logs = 0;
+        for (position, (step, action)) in actions.enumerate() {
+            let Some(url) = action.output_url.as_deref() else {
+                continue;
+            };
+            let Ok(Some(log)) = read_cached_log(cache, url) else {
+                continue;
+            };
+            let meta = IndexedLog {
+                build_num: build.build_num,
+                status: build.status.clone(),
+                branch: build.branch.clone(),
+                start_time: build.start_time,
+                step: step.name.clone(),
+                container: action.index,
+                position,
+                output_url: url.to_string(),
+            };
+
+            let mut doc = TantivyDocument::new();
+            doc.add_text(
+                self.fields.build,
+                format!("{}/{}/{}", org, project, build.build_num),
+            );
+            doc.add_text(self.fields.project, format!("{}/{}", org, project));
+            if let Some(branch) = &build.branch {
+                doc.add_text(self.fields.branch, branch);
+            }
+            doc.add_text(self.fields.meta, serde_json::to_string(&meta)?);
+            doc.add_text(self.fields.text, unique_lines(&strip_ansi(&log)));
+            writer.add_document(doc)?;
+            logs += 1;
+        }
+        Ok(logs)
+    }
+
+    /// Searches the indexed logs of a project's most recent indexed builds.
+    ///
+    /// Works like [`CircleClient::search_builds`](crate::CircleClient::search_builds)
+    /// without any requests: [`SearchOptions::builds`] counts indexed builds,
+    /// and [`SearchOptions::fetch`] is ignored. Candidate logs are read back
+    /// from `cache`; ones evicted since indexing count as not cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use circle_debug::cache::ResponseCache;
+    /// use circle_debug::index::LogIndex;
+    /// use circle_debug::search::SearchOptions;
+    /// use regex::Regex;
+    ///
+    /// let cache = ResponseCache::new(ResponseCache::default_dir());
+    /// let index = LogIndex::open(&LogIndex::default_dir())?;
+    /// index.update(&cache)?;
+    /// let results = index.search(
+    ///     &cache,
+    ///     "myorg",
+    ///     "myrepo",
+    ///     &Regex::new("DeprecationWarning")?,
+    ///     &SearchOptions::default(),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search(
+        &self,
+        cache: &ResponseCache,
+        org: &str,
+        project: &str,
+        regex: &Regex,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
+        let searcher = self.reader.searcher();
+        let mut scope: Vec<(Occur, Box<dyn Query>)> = vec![(
+            Occur::Must,
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.project, &format!("{}/{}", org, project)),
+                IndexRecordOption::Basic,
+            )),
+        )];
+        if let Some(branch) = &options.branch {
+            scope.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.fields.branch, branch),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        // The most recent builds, and how many logs they have
+        let mut in_scope = Vec::new();
+        for address in
+            searcher.search(&BooleanQuery::new(clone_clauses(&scope)), &DocSetCollector)?
+        {
+            in_scope.push(self.log_meta(&searcher.doc(address)?)?.build_num);
+        }
+        let recent: BTreeSet<u32> = in_scope
+            .iter()
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .rev()
+            .take(options.builds as usize)
+            .collect();
+
+        let mut clauses = scope;
+        for trigram in required_trigrams(regex) {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.fields.text, &trigram),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        let mut candidates = Vec::new();
+        for address in searcher.search(&BooleanQuery::new(clauses), &DocSetCollector)? {
+            let meta = self.log_meta(&searcher.doc(address)?)?;
+            if recent.contains(&meta.build_num) {
+                candidates.push(meta);
+            }
+        }
+        candidates.sort_by_key(|log| (std::cmp::Reverse(log.build_num), log.position));
+
+        let mut results = SearchResults {
+            hits: Vec::new(),
+            builds_searched: recent.len(),
+            logs_searched: in_scope.iter().filter(|n| recent.contains(n)).count(),
+            logs_not_cached: 0,
+            logs_failed: 0,
+        };
+        for log in candidates {
+            let text = match read_cached_log(cache, &log.output_url) {
+                Ok(Some(text)) => text,
+                Ok(None) => {
+                    results.logs_not_cached += 1;
+                    continue;
+                }
+                Err(_) => {
+                    results.logs_failed += 1;
+                    continue;
+                }
+            };
+            let (total, matches) =
+                search_log(&text, regex, options.context, options.max_matches_per_log);
+            if total > 0 {
+                results.hits.push(SearchHit {
+                    project: format!("{}/{}", org, project),
+                    build_num: log.build_num,
+                    status: log.status,
+                    branch: log.branch,
+                    start_time: log.start_time,
+                    step: log.step,
+                    container: log.container,
+                    total_matches: total,
+                    matches,
+                });
+            }
+        }
+        results.logs_searched -= results.logs_not_cached + results.logs_failed;
+        Ok(results)
+    }
+
+    fn log_meta(&self, doc: &TantivyDocument) -> Result<IndexedLog> {
+        let json = doc
+            .get_first(self.fields.meta)
+            .and_then(|value| value.as_str())
+            .context("Indexed log has no metadata")?;
+        serde_json::from_str(json).context("Indexed log has invalid metadata")
+    }
+}
+
+fn schema() -> Schema {
+    let mut schema = Schema::builder();
+    schema.add_text_field("build", STRING);
+    schema.add_text_field("project", STRING);
+    schema.add_text_field("branch", STRING);
+    schema.add_text_field("meta", STORED);
+    schema.add_text_field(
+        "text",
+        TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(TRIGRAM)
+                .set_index_option(IndexRecordOption::Basic),
+        ),
+    );
+    schema.build()
+}
+
+fn clone_clauses(clauses: &[(Occur, Box<dyn Query>)]) -> Vec<(Occur, Box<dyn Query>)> {
+    clauses
+        .iter()
+        .map(|(occur, query)| (*occur, query.box_clone()))
+        .collect()
+}
+
+/// Keeps the first occurrence of each line; repeats add no trigrams.
+fn unique_lines(text: &str) -> String {
+    let mut seen = HashSet::new();
+    text.lines()
+        .filter(|line| seen.insert(*line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the lowercase trigrams every match of `regex` contains.
+///
+/// Only literal runs the regex can't skip count, so an empty result (for
+/// case-insensitive or alternation-only patterns, say) means every log is
+/// a candidate.
+fn required_trigrams(regex: &Regex) -> BTreeSet<String> {
+    let Ok(hir) = regex_syntax::parse(regex.as_str()) else {
+        return BTreeSet::new();
+    };
+    let mut literals = Vec::new();
+    let mut run = Vec::new();
+    collect_literals(&hir, &mut run, &mut literals);
+    flush_literal(&mut run, &mut literals);
+
+    let mut trigrams = BTreeSet::new();
+    for literal in literals {
+        let chars: Vec<char> = literal.to_lowercase().chars().collect();
+        for window in chars.windows(3) {
+            trigrams.insert(window.iter().collect());
+        }
+    }
+    trigrams
+}
+
+/// Adds the literal runs of `hir` that every match contains to `literals`,
+/// extending `run` with literal text that continues the current run.
+fn collect_literals(hir: &Hir, run: &mut Vec<u8>, literals: &mut Vec<String>) {
+    match hir.kind() {
+        HirKind::Literal(literal) => run.extend_from_slice(&literal.0),
+        HirKind::Concat(subs) => {
+            for sub in subs {
+                collect_literals(sub, run, literals);
+            }
+        }
+        HirKind::Capture(capture) => collect_literals(&capture.sub, run, literals),
+        HirKind::Repetition(repetition) if repetition.min > 0 => {
+            flush_literal(run, literals);
+            collect_literals(&repetition.sub, run, literals);
+            flush_literal(run, literals);
+        }
+        _ => flush_literal(run, literals),
+    }
+}
+
+fn flush_literal(run: &mut Vec<u8>, literals: &mut Vec<String>) {
+    if !run.is_empty() {
+        literals.push(String::from_utf8_lossy(run).into_owned());
+        run.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, StepBuilder};
+    use reqwest::header::{HeaderMap, HeaderValue, ETAG};
+
+    #[test]
+    fn test_required_trigrams() {
+        let trigrams = |pattern: &str| required_trigrams(&Regex::new(pattern).unwrap());
+
+        assert_eq!(
+            trigrams("Err.r: (fatal)+"),
+            ["err", "r: ", "fat", "ata", "tal"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+        assert!(trigrams("foo|bar").is_empty());
+        assert!(trigrams("(?i)timeout").is_empty());
+        assert!(trigrams("a?bcd?").is_empty());
+    }
+
+    #[test]
+    fn test_index_and_search() {
+        let dir = std::env::temp_dir().join(format!("cdb-index-test-{}", std::process::id()));
+        let cache = ResponseCache::new(&dir);
+        cache.clear().unwrap();
+
+        let mut etag = HeaderMap::new();
+        etag.insert(ETAG, HeaderValue::from_static("\"log\""));
+        let log_url = |n: u32| format!("https://output.circle-artifacts.com/output/{}", n);
+        for (n, output) in [(1, "all good"), (2, "DeprecationWarning: Buffer()")] {
+            let build = BuildBuilder::new(n)
+                .step(
+                    StepBuilder::new("test")
+                        .action(ActionBuilder::new("test").output_url(log_url(n))),
+                )
+                .build();
+            let build_url = format!(
+                "https://circleci.com/api/v1.1/project/github/org/repo/{}",
+                n
+            );
+            cache.put(
+                &build_url,
+                &HeaderMap::new(),
+                &serde_json::to_vec(&build).unwrap(),
+                true,
+            );
+            let body = serde_json::json!([{ "type": "out", "message": output }]);
+            cache.put(&log_url(n), &etag, body.to_string().as_bytes(), false);
+        }
+
+        let index = LogIndex::in_memory().unwrap();
+        assert_eq!(
+            index.update(&cache).unwrap(),
+            IndexUpdate { builds: 2, logs: 2 }
+        );
+        assert_eq!(
+            index.update(&cache).unwrap(),
+            IndexUpdate { builds: 0, logs: 0 }
+        );
+        assert_eq!(index.len(), 2);
+
+        let search = |pattern: &str| {
+            index
+                .search(
+                    &cache,
+                    "org",
+                    "repo",
+                    &Regex::new(pattern).unwrap(),
+                    &SearchOptions::default(),
+                )
+                .unwrap()
+        };
+        let results = search(r"Deprecation\w+: Buf");
+        assert_eq!(results.builds_searched, 2);
+        assert_eq!(results.hits.len(), 1);
+        assert_eq!(results.hits[0].build_num, 2);
+        assert_eq!(results.hits[0].step, "test");
+        assert!(search("(?i)all GOOD").hits[0].build_num == 1);
+        assert!(search("Segmentation").hits.is_empty());
+
+        assert_eq!(index.rebuild(&cache).unwrap().builds, 2);
+        assert_eq!(index.len(), 2);
+        cache.clear().unwrap();
+    }
+}
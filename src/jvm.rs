@@ -0,0 +1,297 @@
+//! Structured failures from Gradle and Maven logs.
+//!
+//! Both tools end a failed build with a summary that says more than any
+//! single error line: Gradle's `* What went wrong:` blocks name the failed
+//! task and its cause, and Maven's reactor summary shows which modules
+//! failed and which were skipped because of it. [`summarize`] extracts
+//! both.
+//!
+//! ```text
+//! FAILURE: Build failed with an exception.
+//!
+//! * What went wrong:
+//! Execution failed for task ':app:compileJava'.
+//! > Compilation failed; see the compiler error output for details.
+//! ```
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// What a Gradle or Maven build reported about its failure.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct JvmSummary {
+    /// Gradle failures, one per `* What went wrong:` block.
+    pub gradle: Vec<GradleFailure>,
+    /// Maven's reactor summary and failed goals, if Maven ran.
+    pub maven: Option<MavenSummary>,
+}
+
+/// One `* What went wrong:` block of a Gradle build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GradleFailure {
+    /// Task path that failed, e.g. `:app:compileJava`.
+    pub task: Option<String>,
+    /// First line of the block, e.g. "Execution failed for task ':app:test'."
+    pub message: String,
+    /// The `> ` cause lines below it, without the marker.
+    pub causes: Vec<String>,
+}
+
+/// Maven's end-of-build report.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MavenSummary {
+    /// Modules from the reactor summary, in build order. Empty for
+    /// single-module builds, which have no reactor summary.
+    pub modules: Vec<ReactorModule>,
+    /// Goals reported by `[ERROR] Failed to execute goal`.
+    pub failed_goals: Vec<FailedGoal>,
+}
+
+/// One line of the reactor summary.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReactorModule {
+    /// Module name as Maven prints it.
+    pub name: String,
+    /// Outcome of the module's build.
+    pub status: ModuleStatus,
+    /// Time taken as printed, e.g. "2.345 s".
+    pub time: Option<String>,
+}
+
+/// Outcome of a module in the reactor summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleStatus {
+    Success,
+    Failure,
+    Skipped,
+}
+
+/// A goal Maven failed to execute.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailedGoal {
+    /// Plugin goal, e.g. `org.apache.maven.plugins:maven-compiler-plugin:3.11.0:compile`.
+    pub goal: String,
+    /// Module the goal ran in.
+    pub project: String,
+    /// Maven's one-line reason, e.g. "Compilation failure".
+    pub reason: String,
+}
+
+impl MavenSummary {
+    /// Returns the modules that failed.
+    pub fn failed_modules(&self) -> impl Iterator<Item = &ReactorModule> {
+        self.modules
+            .iter()
+            .filter(|module| module.status == ModuleStatus::Failure)
+    }
+}
+
+fn reactor_module_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^\[INFO\] (?P<name>\S.*?) \.+ ?(?P<status>SUCCESS|FAILURE|SKIPPED)(?: \[\s*(?P<time>[^\]]+?)\s*\])?\s*$",
+        )
+        .unwrap()
+    })
+}
+
+fn failed_goal_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\[ERROR\] Failed to execute goal (?P<goal>\S+)(?: \([^)]*\))? on project (?P<project>[^:\s]+): (?P<reason>.*?)(?: -> \[Help 1\])?\s*$",
+        )
+        .unwrap()
+    })
+}
+
+fn gradle_task_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"task '(?P<task>:?[^']+)'").unwrap())
+}
+
+/// Extracts Gradle and Maven failure summaries from a log.
+///
+/// # Returns
+///
+/// `None` if the log has neither.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::jvm::summarize;
+///
+/// let log = "FAILURE: Build failed with an exception.\n\
+///            \n\
+///            * What went wrong:\n\
+///            Execution failed for task ':app:test'.\n\
+///            > There were failing tests. See the report at: file:///app/build/reports/tests/test/index.html\n\
+///            \n\
+///            * Try:\n\
+///            > Run with --stacktrace option to get the stack trace.\n";
+/// let summary = summarize(log).unwrap();
+/// assert_eq!(summary.gradle[0].task.as_deref(), Some(":app:test"));
+/// assert_eq!(summary.gradle[0].causes.len(), 1);
+/// assert!(summary.maven.is_none());
+///
+/// assert!(summarize("BUILD SUCCESS\n").is_none());
+/// ```
+pub fn summarize(log: &str) -> Option<JvmSummary> {
+    let summary = JvmSummary {
+        gradle: gradle_failures(log),
+        maven: maven_summary(log),
+    };
+    (!summary.gradle.is_empty() || summary.maven.is_some()).then_some(summary)
+}
+
+fn gradle_failures(log: &str) -> Vec<GradleFailure> {
+    let mut failures = Vec::new();
+    let mut lines = log.lines().map(str::trim);
+    while lines.by_ref().any(|line| line == "* What went wrong:") {
+        let mut block = lines
+            .by_ref()
+            .take_while(|line| !line.is_empty() && !line.starts_with("* "));
+        let Some(message) = block.next() else {
+            continue;
+        };
+        // A failure in a nested build repeats the marker on each line
+        let causes = block
+            .filter_map(|line| line.strip_prefix('>'))
+            .map(|cause| cause.trim_start_matches(['>', ' ']).to_string())
+            .filter(|cause| !cause.is_empty())
+            .collect();
+        failures.push(GradleFailure {
+            task: gradle_task_regex()
+                .captures(message)
+                .map(|caps| caps["task"].to_string()),
+            message: message.to_string(),
+            causes,
+        });
+    }
+    failures
+}
+
+fn maven_summary(log: &str) -> Option<MavenSummary> {
+    let mut summary = MavenSummary::default();
+    let mut in_reactor = false;
+    for line in log.lines().map(str::trim) {
+        if line.starts_with("[INFO] Reactor Summary") {
+            in_reactor = true;
+            summary.modules.clear();
+            continue;
+        }
+        if in_reactor {
+            if let Some(caps) = reactor_module_regex().captures(line) {
+                summary.modules.push(ReactorModule {
+                    name: caps["name"].to_string(),
+                    status: match &caps["status"] {
+                        "SUCCESS" => ModuleStatus::Success,
+                        "FAILURE" => ModuleStatus::Failure,
+                        _ => ModuleStatus::Skipped,
+                    },
+                    time: caps.name("time").map(|m| m.as_str().to_string()),
+                });
+            } else if line.starts_with("[INFO] ---") || line.starts_with("[INFO] BUILD") {
+                in_reactor = false;
+            }
+        }
+
+        if let Some(caps) = failed_goal_regex().captures(line) {
+            summary.failed_goals.push(FailedGoal {
+                goal: caps["goal"].to_string(),
+                project: caps["project"].to_string(),
+                reason: caps["reason"].to_string(),
+            });
+        }
+    }
+    (!summary.modules.is_empty() || !summary.failed_goals.is_empty()).then_some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradle_multiple_failures() {
+        let log = "\
+> Task :core:test FAILED
+
+FAILURE: Build completed with 2 failures.
+
+1: Task failed with an exception.
+-----------
+* What went wrong:
+Execution failed for task ':core:test'.
+> There were failing tests. See the report at: file:///repo/core/build/reports/tests/test/index.html
+
+* Try:
+> Run with --scan to get full insights.
+==============================================================================
+
+2: Task failed with an exception.
+-----------
+* What went wrong:
+Execution failed for task ':app:compileKotlin'.
+> A failure occurred while executing org.jetbrains.kotlin.compilerRunner.GradleCompilerRunnerWithWorkers$GradleKotlinCompilerWorkAction
+   > Compilation error. See log for more details
+
+* Try:
+> Run with --stacktrace option to get the stack trace.
+
+BUILD FAILED in 41s
+";
+        let failures = summarize(log).unwrap().gradle;
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].task.as_deref(), Some(":core:test"));
+        assert_eq!(failures[1].task.as_deref(), Some(":app:compileKotlin"));
+        assert_eq!(failures[1].causes.len(), 2);
+        assert_eq!(
+            failures[1].causes[1],
+            "Compilation error. See log for more details"
+        );
+    }
+
+    #[test]
+    fn test_maven_reactor_summary() {
+        let log = "\
+[ERROR] /repo/core/src/main/java/com/acme/Core.java:[12,5] cannot find symbol
+[INFO] ------------------------------------------------------------------------
+[INFO] Reactor Summary for acme-parent 1.0-SNAPSHOT:
+[INFO]
+[INFO] acme-parent ........................................ SUCCESS [  0.412 s]
+[INFO] core ............................................... FAILURE [  2.345 s]
+[INFO] api ................................................ SKIPPED
+[INFO] ------------------------------------------------------------------------
+[INFO] BUILD FAILURE
+[INFO] ------------------------------------------------------------------------
+[ERROR] Failed to execute goal org.apache.maven.plugins:maven-compiler-plugin:3.11.0:compile (default-compile) on project core: Compilation failure
+";
+        let maven = summarize(log).unwrap().maven.unwrap();
+        let statuses: Vec<_> = maven
+            .modules
+            .iter()
+            .map(|m| (m.name.as_str(), m.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("acme-parent", ModuleStatus::Success),
+                ("core", ModuleStatus::Failure),
+                ("api", ModuleStatus::Skipped)
+            ]
+        );
+        assert_eq!(maven.modules[1].time.as_deref(), Some("2.345 s"));
+        assert_eq!(maven.failed_modules().count(), 1);
+        assert_eq!(
+            maven.failed_goals,
+            vec![FailedGoal {
+                goal: "org.apache.maven.plugins:maven-compiler-plugin:3.11.0:compile".to_string(),
+                project: "core".to_string(),
+                reason: "Compilation failure".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Checkout keys, for `cdb keys` and "Permission denied (publickey)".
+//!
+//! A project clones its repository with a checkout key: a deploy key, which
+//! can only read that one repository, or a user key, which can read
+//! everything its GitHub user can. Git auth failures are opaque in the
+//! log, so when a build hits one, the project's keys are compared with the
+//! repositories the build tried to clone.
+
+use crate::workflow::Page;
+use crate::{project_slug, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Id of the built-in pattern for "Permission denied (publickey)".
+pub const GIT_AUTH_PATTERN: &str = "git-auth-failed";
+
+/// Kind of checkout key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckoutKeyType {
+    /// Reads only the project's repository.
+    DeployKey,
+    /// Reads every repository its GitHub user can.
+    #[serde(alias = "user-key")]
+    GithubUserKey,
+    /// A kind this version doesn't know about.
+    #[serde(other)]
+    Other,
+}
+
+impl CheckoutKeyType {
+    /// Name shown in `cdb keys`.
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckoutKeyType::DeployKey => "deploy key",
+            CheckoutKeyType::GithubUserKey => "user key",
+            CheckoutKeyType::Other => "other",
+        }
+    }
+}
+
+/// A project's checkout key, as returned by the v2 API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct CheckoutKey {
+    /// Deploy or user key.
+    #[serde(rename = "type")]
+    pub key_type: CheckoutKeyType,
+    /// MD5 fingerprint, as GitHub shows it under the repository's deploy
+    /// keys.
+    pub fingerprint: String,
+    /// Whether builds use this key when the project has several.
+    #[serde(default)]
+    pub preferred: bool,
+    /// When the key was added.
+    pub created_at: Option<DateTime<Utc>>,
+    /// The OpenSSH public key.
+    #[serde(default)]
+    pub public_key: String,
+}
+
+/// Why a build's checkout keys can't have worked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "problem", rename_all = "kebab-case")]
+pub enum KeyProblem {
+    /// The project has no checkout key.
+    NoKeys,
+    /// The build cloned another repository, such as a submodule or a
+    /// private dependency, but the project only has deploy keys.
+    DeployKeyOnly {
+        /// The other repository, as `org/repo`.
+        repository: String,
+    },
+}
+
+impl std::fmt::Display for KeyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyProblem::NoKeys => write!(
+                f,
+                "The project has no checkout key - add one under Project Settings → SSH Keys"
+            ),
+            KeyProblem::DeployKeyOnly { repository } => write!(
+                f,
+                "The build clones {} but the project only has deploy keys, which read just its own repository - add a user key, or a deploy key to {} with add_ssh_keys",
+                repository, repository
+            ),
+        }
+    }
+}
+
+/// The project's checkout keys, and what's wrong with them, for a build
+/// that failed with "Permission denied (publickey)".
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyCheck {
+    /// The project's checkout keys.
+    pub keys: Vec<CheckoutKey>,
+    /// Repositories the failed steps tried to clone over SSH.
+    pub remotes: Vec<String>,
+    /// Why the keys can't have worked, if it's clear.
+    pub problem: Option<KeyProblem>,
+}
+
+fn git_remote_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:git@github\.com:|ssh://git@github\.com/)(?P<repo>[\w.-]+/[\w.-]+?)(?:\.git)?(?:[^\w.-]|$)")
+            .unwrap()
+    })
+}
+
+/// Returns the GitHub repositories a log clones or fetches over SSH, as
+/// `org/repo`, in order of first mention.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::keys::git_remotes;
+///
+/// let log = "Cloning into 'vendor/lib'...\nfatal: clone of 'git@github.com:acme/lib.git' into submodule path 'vendor/lib' failed";
+/// assert_eq!(git_remotes(log), vec!["acme/lib"]);
+/// ```
+pub fn git_remotes(log: &str) -> Vec<String> {
+    let mut remotes: Vec<String> = Vec::new();
+    for caps in git_remote_regex().captures_iter(log) {
+        let repo = caps["repo"].to_string();
+        if !remotes.contains(&repo) {
+            remotes.push(repo);
+        }
+    }
+    remotes
+}
+
+/// Works out why `keys` couldn't clone `remotes` for the project
+/// `org/project`.
+///
+/// # Returns
+///
+/// `None` when the keys look right, e.g. a user key exists or only the
+/// project's own repository was cloned; the key may then have been
+/// removed on GitHub's side.
+pub fn diagnose(
+    keys: &[CheckoutKey],
+    org: &str,
+    project: &str,
+    remotes: &[String],
+) -> Option<KeyProblem> {
+    if keys.is_empty() {
+        return Some(KeyProblem::NoKeys);
+    }
+    if keys
+        .iter()
+        .any(|key| key.key_type != CheckoutKeyType::DeployKey)
+    {
+        return None;
+    }
+    let own = format!("{}/{}", org, project);
+    remotes
+        .iter()
+        .find(|remote| !remote.eq_ignore_ascii_case(&own))
+        .map(|remote| KeyProblem::DeployKeyOnly {
+            repository: remote.clone(),
+        })
+}
+
+impl CircleClient {
+    /// Lists a project's checkout keys, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails; listing keys needs a token of
+    /// a user with admin access to the project.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// for key in client.checkout_keys("myorg", "myrepo").await? {
+    ///     println!("{} {}", key.key_type.label(), key.fingerprint);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn checkout_keys(&self, org: &str, project: &str) -> Result<Vec<CheckoutKey>> {
+        let base = format!(
+            "{}/v2/project/{}/checkout-key",
+            self.base_url,
+            project_slug(org, project)
+        );
+        let mut keys = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<CheckoutKey> = self
+                .get_json(url.as_str())
+                .await
+                .with_context(|| format!("Failed to list checkout keys of {}/{}", org, project))?;
+            keys.extend(page.items);
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Lists the project's checkout keys and diagnoses them against the
+    /// repositories in `remotes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keys can't be listed.
+    pub async fn check_keys(
+        &self,
+        org: &str,
+        project: &str,
+        remotes: Vec<String>,
+    ) -> Result<KeyCheck> {
+        let keys = self.checkout_keys(org, project).await?;
+        let problem = diagnose(&keys, org, project, &remotes);
+        Ok(KeyCheck {
+            keys,
+            remotes,
+            problem,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_check_keys_deploy_key_only() {
+        let server = FakeCircleCi::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/project/gh/org/repo/checkout-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "items": [{
+                    "public-key": "ssh-rsa AAAA",
+                    "type": "deploy-key",
+                    "fingerprint": "c9:0b:1c:4f:d5:65:56:b9:ad:88:f9:81:2b:37:74:2f",
+                    "preferred": true,
+                    "created-at": "2024-01-15T10:00:00Z"
+                }],
+                "next_page_token": null
+            })))
+            .mount(server.server())
+            .await;
+
+        let log = "Cloning into 'vendor/lib'...\n\
+                   git@github.com: Permission denied (publickey).\n\
+                   fatal: clone of 'git@github.com:org/lib.git' into submodule path 'vendor/lib' failed";
+        let check = server
+            .client()
+            .check_keys("org", "repo", git_remotes(log))
+            .await
+            .unwrap();
+        assert_eq!(check.keys[0].key_type, CheckoutKeyType::DeployKey);
+        assert_eq!(
+            check.problem,
+            Some(KeyProblem::DeployKeyOnly {
+                repository: "org/lib".to_string()
+            })
+        );
+
+        assert_eq!(diagnose(&[], "org", "repo", &[]), Some(KeyProblem::NoKeys));
+        assert_eq!(
+            diagnose(&check.keys, "org", "repo", &["Org/Repo".to_string()]),
+            None
+        );
+    }
+}
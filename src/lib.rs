@@ -40,9 +40,65 @@
 //! ## Features
 //!
 //! - **API Client**: [`CircleClient`] for interacting with CircleCI API
-//! - **Data Models**: [`BuildInfo`], [`Step`], [`Action`] for build data
+//! - **Data Models**: [`BuildInfo`], [`WorkflowInfo`], [`Step`], [`Action`], [`User`] for API data
+//! - **Cost Estimates**: credit estimates from resource class and duration in [`cost`]
+//! - **Environments**: a job's executor, resource class, images, and OS in [`environment`]
+//! - **Resource Usage**: a job's peak CPU and memory against its resource class, and whether a smaller or larger one fits, in [`resources`]
+//! - **Log Downloads**: resumable and tail-only fetches in [`download`]
+//! - **Response Caching**: ETag revalidation and immutable finished builds in [`cache`]
+//! - **Fixtures**: recording and replaying API traffic with [`record::RecordingClient`]
+//! - **Test Utilities**: model builders and a fake CircleCI server in `test_utils` (`test-utils` feature)
 //! - **URL Parsing**: [`parse_circleci_url`] for extracting build information
-//! - **Duration Formatting**: [`format_duration`] for human-readable time display
+//! - **Duration Formatting**: [`format_duration`] and [`parse_duration`] for time values
+//! - **Log Processing**: timestamp filtering and cleanup in [`logs`]
+//! - **Phases**: how long a step spent installing, compiling, testing, and tearing down, from its log timestamps, in [`phases`]
+//! - **Local Runs**: splitting `circleci local execute` output into steps in [`local`]
+//! - **Docker Builds**: the failing Dockerfile instruction and its output from BuildKit logs in [`docker`]
+//! - **Artifacts**: listing, downloading, and diffing builds' stored files in [`artifacts`]
+//! - **Artifact Sizes**: a bundle's or binary's size across recent builds, and the builds where it jumped, in [`size`]
+//! - **Coverage**: line coverage from lcov and Cobertura artifacts or tool summaries in logs, against the previous passing build, in [`coverage`]
+//! - **Benchmarks**: Criterion and hyperfine performance regressions, ranked by slowdown, in [`bench`]
+//! - **JVM Builds**: Gradle `What went wrong` blocks and Maven reactor summaries in [`jvm`]
+//! - **Monorepos**: per-package failures and filtering for Turborepo, Nx, Lerna, and Yarn logs in [`monorepo`]
+//! - **Error Detection**: grouped, ranked pattern matching in [`patterns`]
+//! - **Checkout Keys**: a project's deploy and user keys, and why they couldn't clone a repository, in [`keys`]
+//! - **SSH Reruns**: rerunning a job with SSH and the command that connects to it, in [`ssh`]
+//! - **Triggers**: why a build ran, who started it, and its pull request, in [`trigger`]
+//! - **Cancellations**: who canceled a build, or which newer build auto-canceled it, in [`cancel`]
+//! - **Build History**: recent builds, recurring errors, and where a branch went red in [`history`]
+//! - **Local History**: an opt-in SQLite record of analyzed builds and their errors in `store`, with trends in `stats` (with the `history` feature)
+//! - **Fingerprints**: run-independent identities of detected errors, for "seen before" matching, in [`fingerprint`]
+//! - **Log Search**: a regex across the cached or fetched logs of recent builds in [`search`], sped up by a full-text index in `index` (with the `index` feature)
+//! - **Git**: repository detection and commit history from the local checkout in [`git`], or the API in [`github`]
+//! - **Source Excerpts**: where errors point in the code, read from a local checkout at the build's commit, in [`source`]
+//! - **Code Owners**: who owns the files named in errors, from CODEOWNERS, in [`codeowners`]
+//! - **Suspect Commits**: commits since the last passing build, flagged by the files errors mention, in [`suspects`]
+//! - **Branch Timings**: step timings averaged over recent builds of a branch and its base, and the steps consistently slower on the branch, in [`compare`]
+//! - **Projects**: followed projects with their latest build, and following new ones, in [`projects`]
+//! - **Scheduled Runs**: a project's nightly and other scheduled workflows, week by week, in [`nightly`]
+//! - **Schedules**: a project's scheduled pipelines, when they run, and the pipeline each last started, in [`schedules`]
+//! - **Pipeline Parameters**: the parameters a config declares, checking values against them, and triggering pipelines, in [`parameters`]
+//! - **CI Config Changes**: the configuration a build's pipeline ran with, and a unified diff between two builds' that points out executor, image, and command changes, in [`ci_config`]
+//! - **Workflows**: jobs of a workflow and their queue times in [`workflow`]
+//! - **Test Splitting**: per-test timings from test metadata or JUnit artifacts, and the parallelism and split they suggest, in [`parallelism`]
+//! - **Identifiers**: mapping build and job numbers to pipelines and workflows, and back, in [`resolve`]
+//! - **Upstream Failures**: the earlier job a failed dependency or workspace attach comes from, in [`upstream`]
+//! - **Retries**: steps a build ran again after failing, and the ones that only passed on retry, in [`retries`]
+//! - **Failure Summaries**: a build's failed steps, detected errors, failure kinds, failed tests, and timing from its logs in one call, [`summarize`]
+//! - **Reports**: serializable analysis results in [`report`], renderable through custom [`template`]s
+//! - **Annotations**: detected errors as GitHub Actions workflow commands in [`annotations`]
+//! - **CSV Export**: steps and failed tests as spreadsheet rows in [`csv`]
+//! - **Markdown**: the analysis as a Markdown document, saved with the logs, in [`markdown`]
+//! - **Post-Mortems**: build, workflow, errors with their log context, tests, timing, and suspects in one Markdown or HTML document, in [`postmortem`]
+//! - **Quickfix**: `file:line:col` lines for Vim and VS Code in [`quickfix`]
+//! - **Event Stream**: analysis progress as newline-delimited JSON in [`events`]
+//! - **Queries**: JMESPath-style paths that pick fields out of JSON output in [`query`]
+//! - **Metrics**: build and step durations and detected errors as Prometheus metrics, pushed to a Pushgateway, in [`metrics`]
+//! - **Fix Suggestions**: templated remediation tips in [`suggestions`]
+//! - **Local Reruns**: commands that rerun just the failed Jest, `cargo test`, and pytest tests, in [`repro`]
+//! - **Highlighting**: user-defined colors for parts of printed log lines, such as correlation ids, in [`highlight`]
+//! - **Configuration**: user-defined patterns and defaults loaded from TOML in [`config`], and the stored token in [`credentials`]
+//! - **File Locations**: platform config, cache, and saved-log paths in [`paths`]
 //!
 //! ## Error Handling
 //!
@@ -50,17 +106,87 @@
 //! Errors include network failures, authentication issues, and parsing problems.
 
 use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+pub mod annotations;
+pub mod artifacts;
+pub mod bench;
+pub mod cache;
+pub mod cancel;
+pub mod ci_config;
+pub mod codeowners;
+pub mod compare;
+pub mod config;
+pub mod cost;
+pub mod coverage;
+pub mod credentials;
+pub mod csv;
+pub mod docker;
+pub mod download;
+pub mod environment;
 pub mod error;
+pub mod events;
+pub mod fingerprint;
+pub mod git;
+pub mod github;
+pub mod highlight;
+pub mod history;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod jvm;
+pub mod keys;
+pub mod local;
+pub mod logs;
+pub mod markdown;
+pub mod metrics;
+pub mod monorepo;
+pub mod nightly;
+pub mod parallelism;
+pub mod parameters;
+pub mod paths;
+pub mod patterns;
+pub mod phases;
+pub mod postmortem;
+pub mod projects;
+pub mod query;
+pub mod quickfix;
+pub mod record;
+pub mod report;
+pub mod repro;
+pub mod resolve;
+pub mod resources;
+pub mod retries;
+pub mod schedules;
+pub mod search;
+pub mod size;
+pub mod source;
+pub mod ssh;
+#[cfg(feature = "history")]
+pub mod stats;
+#[cfg(feature = "history")]
+pub mod store;
+pub mod suggestions;
+pub mod summary;
+pub mod suspects;
+pub mod template;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod theme;
+pub mod trigger;
+pub mod upstream;
+pub mod workflow;
 pub use error::CircleDebugError;
+pub use summary::{summarize, FailureSummary};
 
 /// CircleCI build information returned by the API.
 ///
 /// Contains comprehensive information about a build including its status,
-/// steps, actions, and timing data.
+/// steps, actions, and timing data. Fields the crate doesn't model are
+/// available in [`extra`](Self::extra).
 ///
 /// # See Also
 ///
@@ -76,8 +202,53 @@ pub struct BuildInfo {
     pub branch: Option<String>,
     /// Commit message or subject line.
     pub subject: Option<String>,
-    /// List of build steps executed in this build.
+    /// List of build steps executed in this build. Empty in build lists,
+    /// which omit them.
+    #[serde(default)]
     pub steps: Vec<Step>,
+    /// When the build was queued.
+    pub queued_at: Option<DateTime<Utc>>,
+    /// When the build started waiting for capacity, after any plan or
+    /// concurrency checks.
+    pub usage_queued_at: Option<DateTime<Utc>>,
+    /// When the build started running.
+    pub start_time: Option<DateTime<Utc>>,
+    /// When the build finished.
+    pub stop_time: Option<DateTime<Utc>>,
+    /// Total build time in milliseconds.
+    pub build_time_millis: Option<u64>,
+    /// Full commit SHA that was built.
+    pub vcs_revision: Option<String>,
+    /// Name of the commit author.
+    pub author_name: Option<String>,
+    /// Why the build was triggered (e.g., "github", "retry", "api").
+    pub why: Option<String>,
+    /// Workflow and job this build belongs to, for builds run by workflows.
+    pub workflows: Option<WorkflowInfo>,
+    /// Link to the build in the CircleCI UI.
+    pub build_url: Option<String>,
+    /// Number of containers the build ran on.
+    pub parallel: Option<u32>,
+    /// Executor and resource class the build ran on.
+    pub picard: Option<cost::Picard>,
+    /// API fields not modeled above, kept as returned.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Workflow metadata attached to a build.
+///
+/// Every field is optional since older and non-workflow builds omit them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct WorkflowInfo {
+    /// Name of the job within the workflow.
+    pub job_name: Option<String>,
+    /// UUID of the job.
+    pub job_id: Option<String>,
+    /// Name of the workflow.
+    pub workflow_name: Option<String>,
+    /// UUID of the workflow.
+    pub workflow_id: Option<String>,
 }
 
 impl BuildInfo {
@@ -85,10 +256,41 @@ impl BuildInfo {
         self.status == "failed"
     }
 
+    /// Returns `true` if CircleCI itself failed the build, e.g. the
+    /// executor couldn't start or the build hit its time limit.
+    pub fn is_infrastructure_failure(&self) -> bool {
+        matches!(self.status.as_str(), "infrastructure_fail" | "timedout")
+    }
+
     pub fn is_success(&self) -> bool {
         self.status == "success"
     }
 
+    /// Returns `true` once the build has stopped running and its data can
+    /// no longer change.
+    pub fn is_finished(&self) -> bool {
+        match self.extra.get("lifecycle").and_then(|v| v.as_str()) {
+            Some(lifecycle) => lifecycle == "finished",
+            None => self.stop_time.is_some(),
+        }
+    }
+
+    /// Returns how long the build waited before it started running.
+    ///
+    /// Measured from `usage_queued_at` when present, otherwise from
+    /// `queued_at`. Returns `None` if the build hasn't started.
+    pub fn queue_duration(&self) -> Option<Duration> {
+        let queued = self.usage_queued_at.or(self.queued_at)?;
+        (self.start_time? - queued).to_std().ok()
+    }
+
+    /// Returns the first 7 characters of the built commit SHA.
+    pub fn short_revision(&self) -> Option<&str> {
+        self.vcs_revision
+            .as_deref()
+            .map(|sha| sha.get(..7).unwrap_or(sha))
+    }
+
     pub fn failed_actions(&self) -> impl Iterator<Item = &Action> {
         self.steps
             .iter()
@@ -97,6 +299,21 @@ impl BuildInfo {
     }
 }
 
+/// The CircleCI user an API token belongs to.
+///
+/// # See Also
+///
+/// * [`CircleClient::me`] - Method to fetch this information
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct User {
+    /// UUID of the user.
+    pub id: String,
+    /// Login name, usually the VCS username.
+    pub login: String,
+    /// Display name, if set.
+    pub name: Option<String>,
+}
+
 /// Represents a single step in a CircleCI build.
 ///
 /// A step groups related actions that are executed sequentially.
@@ -111,12 +328,20 @@ pub struct Step {
     pub name: String,
     /// List of actions executed within this step.
     pub actions: Vec<Action>,
+    /// API fields not modeled above, kept as returned.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl Step {
     pub fn has_failures(&self) -> bool {
         self.actions.iter().any(|a| a.failed.unwrap_or(false))
     }
+
+    /// Returns `true` if the step ran on more than one container.
+    pub fn is_parallel(&self) -> bool {
+        self.actions.len() > 1 || self.actions.iter().any(|a| a.parallel.unwrap_or(false))
+    }
 }
 
 /// Represents an individual action within a CircleCI build step.
@@ -142,6 +367,19 @@ pub struct Action {
     pub action_type: String,
     /// Execution time in milliseconds.
     pub run_time_millis: Option<u64>,
+    /// When the action started running.
+    pub start_time: Option<DateTime<Utc>>,
+    /// When the action finished running.
+    pub end_time: Option<DateTime<Utc>>,
+    /// Parallel container this action ran on, starting at 0.
+    pub index: Option<u32>,
+    /// Whether the step runs on several containers in parallel.
+    pub parallel: Option<bool>,
+    /// The shell command a `run` step ran, as written in the config.
+    pub bash_command: Option<String>,
+    /// API fields not modeled above, kept as returned.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl Action {
@@ -149,6 +387,11 @@ impl Action {
         self.failed.unwrap_or(false) || self.status == "failed"
     }
 
+    /// Parallel container index, `0` when the API didn't report one.
+    pub fn container(&self) -> u32 {
+        self.index.unwrap_or(0)
+    }
+
     pub fn duration(&self) -> Duration {
         Duration::from_millis(self.run_time_millis.unwrap_or(0))
     }
@@ -180,8 +423,21 @@ impl Action {
 pub struct CircleClient {
     token: String,
     client: reqwest::Client,
+    base_url: String,
+    cache: Option<cache::ResponseCache>,
+    recorder: Option<record::Recorder>,
+    /// Shared by every request, so bulk operations like history scans and
+    /// artifact listings together stay under the limit.
+    limiter: tokio::sync::Semaphore,
 }
 
+/// API root used unless [`CircleClient::with_base_url`] says otherwise.
+const DEFAULT_BASE_URL: &str = "https://circleci.com/api";
+
+/// Requests in flight at once unless [`CircleClient::with_concurrency`]
+/// says otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
 impl CircleClient {
     /// Creates a new CircleCI API client.
     ///
@@ -191,7 +447,8 @@ impl CircleClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the `CIRCLECI_TOKEN` environment variable is not set.
+    /// Returns an error if the `CIRCLECI_TOKEN` environment variable is not
+    /// set and no token was stored with `cdb init` (see [`credentials`]).
     ///
     /// # Examples
     ///
@@ -206,22 +463,87 @@ impl CircleClient {
     /// # }
     /// ```
     pub fn new() -> Result<Self> {
-        Self::with_token(std::env::var("CIRCLECI_TOKEN").context(
-            "cannot find CircleCI API token\n  help: Set CIRCLECI_TOKEN environment variable",
-        )?)
+        let token = credentials::token().ok_or_else(|| {
+            CircleDebugError::AuthenticationError(
+                "cannot find CircleCI API token\n  help: Set CIRCLECI_TOKEN environment variable or run `cdb init`"
+                    .to_string(),
+            )
+        })?;
+        Self::with_token(token)
     }
 
     pub fn with_token(token: impl Into<String>) -> Result<Self> {
         let token = token.into();
         if token.is_empty() {
-            bail!("CircleCI token cannot be empty");
+            return Err(CircleDebugError::AuthenticationError(
+                "CircleCI token cannot be empty".to_string(),
+            )
+            .into());
         }
 
         let client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(30))
+            .read_timeout(std::time::Duration::from_secs(30))
             .build()?;
 
-        Ok(CircleClient { token, client })
+        Ok(CircleClient {
+            token,
+            client,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            cache: None,
+            recorder: None,
+            limiter: tokio::sync::Semaphore::new(DEFAULT_CONCURRENCY),
+        })
+    }
+
+    /// Sends API requests to `base_url` instead of `https://circleci.com/api`.
+    ///
+    /// For self-hosted CircleCI server installs and fake servers in tests.
+    /// The token is also sent with log downloads from this host.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Allows at most `limit` requests in flight at once, across every
+    /// operation of this client (default: [`DEFAULT_CONCURRENCY`]).
+    ///
+    /// Lower it for organizations that hit CircleCI's rate limits, raise it
+    /// on fast networks. Cached and replayed responses don't count.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?.with_concurrency(2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.limiter = tokio::sync::Semaphore::new(limit.max(1));
+        self
+    }
+
+    /// Waits for a free request slot under the
+    /// [`with_concurrency`](Self::with_concurrency) limit; the slot is freed
+    /// when the permit is dropped.
+    pub(crate) async fn request_permit(&self) -> tokio::sync::SemaphorePermit<'_> {
+        self.limiter
+            .acquire()
+            .await
+            .expect("the request limiter is never closed")
+    }
+
+    /// Caches responses in `cache`, revalidating them with conditional
+    /// requests.
+    ///
+    /// Finished builds are served from the cache without a request.
+    pub fn with_cache(mut self, cache: cache::ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
     /// Fetches build information from CircleCI.
@@ -255,37 +577,188 @@ impl CircleClient {
     /// ```
     pub async fn get_build(&self, org: &str, project: &str, build_num: u32) -> Result<BuildInfo> {
         let url = format!(
-            "https://circleci.com/api/v1.1/project/github/{}/{}/{}",
-            org, project, build_num
+            "{}/v1.1/project/{}/{}/{}/{}",
+            self.base_url,
+            v1_vcs(org),
+            org,
+            project,
+            build_num
         );
 
-        let response = self
-            .client
-            .get(&url)
-            .header("Circle-Token", &self.token)
+        let build: BuildInfo = self.get_json(&url).await?;
+        if let Some(cache) = self.cache.as_ref().filter(|_| build.is_finished()) {
+            // Finished builds can't change; never revalidate them
+            let body = serde_json::to_vec(&build)?;
+            cache.put(&url, &reqwest::header::HeaderMap::new(), &body, true);
+        }
+        Ok(build)
+    }
+
+    /// Returns the user the API token belongs to.
+    ///
+    /// Useful for checking that a token is valid before running heavier
+    /// requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CircleDebugError::Unauthorized`] if the token is invalid
+    /// or expired, a [`CircleDebugError::Forbidden`] for project API tokens,
+    /// or another error if the request fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let user = client.me().await?;
+    /// println!("Authenticated as {}", user.login);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn me(&self) -> Result<User> {
+        self.get_json(&format!("{}/v2/me", self.base_url)).await
+    }
+
+    /// Sends an authenticated GET request and parses the JSON response.
+    ///
+    /// With a cache configured, immutable entries are returned without a
+    /// request and others are revalidated with their `ETag` or
+    /// `Last-Modified`.
+    #[tracing::instrument(level = "debug", skip(self), fields(status, elapsed_ms))]
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        if let Some(recorded) = self.replayed(url)? {
+            if !recorded.status.is_success() {
+                return Err(
+                    CircleDebugError::from_status(recorded.status, url, recorded.text()).into(),
+                );
+            }
+            return parse_json(&recorded.body);
+        }
+
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(url));
+        if let Some(entry) = cached.as_ref().filter(|entry| entry.meta.immutable) {
+            tracing::debug!("cache hit");
+            return parse_json(&entry.body);
+        }
+
+        let _permit = self.request_permit().await;
+        let started = Instant::now();
+        let mut request = self.client.get(url).header("Circle-Token", &self.token);
+        if let Some(entry) = &cached {
+            request = entry.revalidate(request);
+        }
+        let response = request
             .send()
             .await
-            .context("Failed to connect to CircleCI API")?;
+            .map_err(CircleDebugError::from)
+            .context("Failed to connect to CircleCI API");
+
+        let span = tracing::Span::current();
+        span.record("elapsed_ms", started.elapsed().as_millis() as u64);
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::debug!(error = %format!("{:#}", err), "request failed");
+                return Err(err);
+            }
+        };
+        span.record("status", response.status().as_u16());
+        tracing::info!("response received");
 
-        if !response.status().is_success() {
-            let status = response.status();
+        if let (reqwest::StatusCode::NOT_MODIFIED, Some(entry)) = (response.status(), &cached) {
+            tracing::debug!("cache revalidated");
+            return parse_json(&entry.body);
+        }
+
+        let status = response.status();
+        if !status.is_success() {
             let text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "<no response body>".to_string());
-            bail!("CircleCI API returned error {}: {}", status, text);
+            self.record(url, status, text.as_bytes(), false)?;
+            return Err(CircleDebugError::from_status(status, url, text).into());
         }
 
-        let build_info = response
-            .json::<BuildInfo>()
+        let headers = response.headers().clone();
+        let body = response
+            .bytes()
             .await
-            .context("Failed to parse CircleCI response")?;
+            .map_err(CircleDebugError::from)
+            .context("Failed to read CircleCI response")?;
+        self.record(url, status, &body, false)?;
+        if let Some(cache) = &self.cache {
+            cache.put(url, &headers, &body, false);
+        }
+        parse_json(&body)
+    }
 
-        Ok(build_info)
+    /// Sends an authenticated POST request, with `body` as JSON if given,
+    /// and parses the JSON response.
+    ///
+    /// Never cached, recorded, or replayed, since it changes state.
+    async fn post_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T> {
+        let _permit = self.request_permit().await;
+        let mut request = self.client.post(url).header("Circle-Token", &self.token);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(CircleDebugError::from)
+            .context("Failed to connect to CircleCI API")?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "<no response body>".to_string());
+            return Err(CircleDebugError::from_status(status, url, text).into());
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(CircleDebugError::from)
+            .context("Failed to read CircleCI response")?;
+        parse_json(&body)
+    }
+
+    /// Returns the recorded response for `url` when replaying fixtures.
+    fn replayed(&self, url: &str) -> Result<Option<record::Recorded>> {
+        match &self.recorder {
+            Some(recorder) => recorder.replayed(url),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves a response to the fixture directory when recording.
+    fn record(
+        &self,
+        url: &str,
+        status: reqwest::StatusCode,
+        body: &[u8],
+        partial: bool,
+    ) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => recorder.save(url, status, body, partial),
+            None => Ok(()),
+        }
     }
 
     /// Fetches action logs from CircleCI.
     ///
+    /// The API token is only sent when `output_url` is a CircleCI host, so
+    /// presigned S3 links work as-is. Gzip- and zstd-compressed bodies are
+    /// decompressed.
+    ///
     /// # Arguments
     ///
     /// * `output_url` - The URL to fetch logs from
@@ -296,7 +769,8 @@ impl CircleClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if the API request fails.
+    /// Returns an error if the API request fails. Interrupted transfers are
+    /// resumed with range requests before giving up.
     ///
     /// # Examples
     ///
@@ -320,39 +794,25 @@ impl CircleClient {
     /// # }
     /// ```
     pub async fn get_logs(&self, output_url: &str) -> Result<String> {
-        let response = self
-            .client
-            .get(output_url)
-            .header("Circle-Token", &self.token)
-            .send()
+        self.get_logs_with_progress(output_url, None, |_, _| {})
             .await
-            .context("Failed to fetch logs from CircleCI")?;
-
-        if !response.status().is_success() {
-            bail!("Failed to fetch logs: HTTP {}", response.status());
-        }
-
-        let text = response
-            .text()
-            .await
-            .context("Failed to read log response")?;
-
-        // Try to parse as JSON array and extract messages
-        if let Ok(json_array) = serde_json::from_str::<Vec<serde_json::Value>>(&text) {
-            let messages: Vec<String> = json_array
-                .iter()
-                .filter_map(|v| v.get("message").and_then(|m| m.as_str()))
-                .map(|s| s.to_string())
-                .collect();
-            return Ok(messages.join(""));
-        }
-
-        Ok(text)
     }
 }
 
+/// Parses a CircleCI API response body.
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).context("Failed to parse CircleCI response")
+}
+
 /// Parses a CircleCI URL to extract organization, project, and build number.
 ///
+/// Accepts legacy build URLs (`circleci.com/gh/org/repo/12345`), job pages
+/// of the web app (`app.circleci.com/pipelines/github/org/repo/7/workflows/<id>/jobs/12345`),
+/// and `app.circleci.com/jobs/...` links. For standalone organizations,
+/// created through the GitHub App or for GitLab, the organization and
+/// project are UUIDs, as in `app.circleci.com/pipelines/circleci/<org-id>/<project-id>/...`;
+/// see [`is_standalone_org`].
+///
 /// # Arguments
 ///
 /// * `url` - A CircleCI build URL
@@ -382,21 +842,89 @@ impl CircleClient {
 /// # }
 /// ```
 pub fn parse_circleci_url(url: &str) -> Result<(String, String, u32)> {
-    let re = Regex::new(r"circleci\.com/gh/([^/]+)/([^/]+)/(\d+)")?;
+    let re = Regex::new(
+        r"circleci\.com/(?:(?:gh|jobs/(?:github|gh|circleci))/(?P<org>[^/]+)/(?P<project>[^/]+)/(?P<num>\d+)|pipelines/(?:github|gh|circleci)/(?P<porg>[^/]+)/(?P<pproject>[^/]+)/\d+/workflows/[^/]+/jobs/(?P<job>\d+))",
+    )?;
 
     let caps = re.captures(url)
         .with_context(|| format!(
-            "cannot parse CircleCI URL\n  expected: https://circleci.com/gh/org/repo/12345\n  got: {}",
+            "cannot parse CircleCI URL\n  expected: https://circleci.com/gh/org/repo/12345\n  or: https://app.circleci.com/pipelines/<vcs>/<org>/<repo>/<pipeline>/workflows/<id>/jobs/12345\n  got: {}",
             url
         ))?;
 
-    let org = caps.get(1).unwrap().as_str().to_string();
-    let project = caps.get(2).unwrap().as_str().to_string();
-    let build_num = caps.get(3).unwrap().as_str().parse::<u32>()?;
+    let group = |a: &str, b: &str| caps.name(a).or_else(|| caps.name(b)).unwrap().as_str();
+    let org = group("org", "porg").to_string();
+    let project = group("project", "pproject").to_string();
+    let build_num = group("num", "job").parse::<u32>()?;
 
     Ok((org, project, build_num))
 }
 
+/// Returns `true` if `org` is a standalone organization's UUID rather than
+/// a GitHub organization name.
+///
+/// Organizations created through the GitHub App, or for GitLab, address
+/// their projects as `circleci/<org-id>/<project-id>` instead of
+/// `gh/<org>/<repo>`; the client builds its API paths accordingly.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::is_standalone_org;
+///
+/// assert!(is_standalone_org("1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e"));
+/// assert!(!is_standalone_org("myorg"));
+/// ```
+pub fn is_standalone_org(org: &str) -> bool {
+    let bytes = org.as_bytes();
+    bytes.len() == 36
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// Returns a project's v2 API slug: `gh/org/repo`, or
+/// `circleci/<org-id>/<project-id>` for a standalone organization.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::project_slug;
+///
+/// assert_eq!(project_slug("myorg", "myrepo"), "gh/myorg/myrepo");
+/// ```
+pub fn project_slug(org: &str, project: &str) -> String {
+    let vcs = if is_standalone_org(org) {
+        "circleci"
+    } else {
+        "gh"
+    };
+    format!("{}/{}/{}", vcs, org, project)
+}
+
+/// Returns the VCS segment of a project's v1.1 API paths.
+pub(crate) fn v1_vcs(org: &str) -> &'static str {
+    if is_standalone_org(org) {
+        "circleci"
+    } else {
+        "github"
+    }
+}
+
+/// Returns a link to a job's page in the web app, which
+/// [`parse_circleci_url`] accepts.
+pub fn job_web_url(org: &str, project: &str, build_num: u32) -> String {
+    if is_standalone_org(org) {
+        format!(
+            "https://app.circleci.com/jobs/circleci/{}/{}/{}",
+            org, project, build_num
+        )
+    } else {
+        format!("https://circleci.com/gh/{}/{}/{}", org, project, build_num)
+    }
+}
+
 /// Formats a duration from milliseconds to a human-readable string.
 ///
 /// # Arguments
@@ -432,6 +960,66 @@ pub fn format_duration(millis: u64) -> String {
     }
 }
 
+/// Parses a compact duration like "90s", "2m13s", "1h30m", or "7d".
+///
+/// Supported units are `d`, `h`, `m`, `s`, and `ms`. An optional leading
+/// `+` is accepted so log offsets such as "+2m13s" parse as well.
+///
+/// # Errors
+///
+/// Returns an error if the string is empty or contains an unknown unit.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+/// assert_eq!(parse_duration("+2m13s").unwrap(), Duration::from_secs(133));
+/// assert!(parse_duration("soon").is_err());
+/// ```
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let spec = input.trim().trim_start_matches('+');
+    if spec.is_empty() {
+        bail!("empty duration");
+    }
+
+    let re = Regex::new(r"(\d+)(ms|d|h|m|s)")?;
+    let mut total = Duration::ZERO;
+    let mut consumed = 0;
+
+    for caps in re.captures_iter(spec) {
+        let whole = caps.get(0).unwrap();
+        if whole.start() != consumed {
+            break;
+        }
+        consumed = whole.end();
+
+        let value: u64 = caps[1].parse()?;
+        let part = match &caps[2] {
+            "ms" => Some(Duration::from_millis(value)),
+            "s" => Some(Duration::from_secs(value)),
+            "m" => value.checked_mul(60).map(Duration::from_secs),
+            "h" => value.checked_mul(3600).map(Duration::from_secs),
+            _ => value.checked_mul(86400).map(Duration::from_secs),
+        };
+        total = match part.and_then(|part| total.checked_add(part)) {
+            Some(total) => total,
+            None => bail!("duration '{}' is too long", input),
+        };
+    }
+
+    if consumed != spec.len() {
+        bail!(
+            "cannot parse duration '{}'\n  expected: e.g. 30s, 5m, 1h30m, 7d",
+            input
+        );
+    }
+
+    Ok(total)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -457,6 +1045,26 @@ mod tests {
                 "http://circleci.com/gh/test/project/1",
                 ("test", "project", 1),
             ),
+            (
+                "https://app.circleci.com/pipelines/github/org/repo/77/workflows/5034460f-c7c4-4c43-9457-de07e2029e7b/jobs/4321",
+                ("org", "repo", 4321),
+            ),
+            (
+                "https://app.circleci.com/pipelines/circleci/1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e/9a3e6f1d-5b7c-4e2a-8f0d-2c1b4a6e8d90/12/workflows/5034460f-c7c4-4c43-9457-de07e2029e7b/jobs/88",
+                (
+                    "1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e",
+                    "9a3e6f1d-5b7c-4e2a-8f0d-2c1b4a6e8d90",
+                    88,
+                ),
+            ),
+            (
+                "https://app.circleci.com/jobs/circleci/1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e/9a3e6f1d-5b7c-4e2a-8f0d-2c1b4a6e8d90/88",
+                (
+                    "1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e",
+                    "9a3e6f1d-5b7c-4e2a-8f0d-2c1b4a6e8d90",
+                    88,
+                ),
+            ),
         ];
 
         for (url, expected) in test_cases {
@@ -478,6 +1086,20 @@ mod tests {
         assert_eq!(format_duration(59999), "59s");
     }
 
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("m5").is_err());
+        assert!(parse_duration("+999999999999999999d").is_err());
+        assert!(parse_duration("99999999999999999h").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+    }
+
     #[test]
     fn test_parse_circleci_url_invalid() {
         let invalid_urls = vec![
@@ -503,6 +1125,8 @@ mod tests {
             status: "failed".to_string(),
             branch: Some("main".to_string()),
             subject: Some("Test commit".to_string()),
+            queued_at: Some("2024-05-01T12:00:00Z".parse().unwrap()),
+            usage_queued_at: None,
             steps: vec![Step {
                 name: "Test".to_string(),
                 actions: vec![Action {
@@ -512,16 +1136,146 @@ mod tests {
                     output_url: Some("http://example.com/logs".to_string()),
                     action_type: "test".to_string(),
                     run_time_millis: Some(5000),
+                    start_time: None,
+                    end_time: None,
+                    index: None,
+                    parallel: None,
+                    bash_command: None,
+                    extra: Default::default(),
                 }],
+                extra: Default::default(),
             }],
+            start_time: Some("2024-05-01T12:02:30Z".parse().unwrap()),
+            stop_time: None,
+            build_time_millis: None,
+            vcs_revision: Some("0123456789abcdef".to_string()),
+            author_name: None,
+            why: None,
+            workflows: None,
+            build_url: None,
+            parallel: None,
+            picard: None,
+            extra: Default::default(),
         };
 
         assert!(build.is_failed());
+        assert_eq!(build.short_revision(), Some("0123456"));
+        assert_eq!(build.queue_duration(), Some(Duration::from_secs(150)));
         assert!(!build.is_success());
+        assert!(!build.is_infrastructure_failure());
         assert_eq!(build.failed_actions().count(), 1);
         assert!(build.steps[0].has_failures());
     }
 
+    #[test]
+    fn test_build_info_deserializes_metadata() {
+        let build: BuildInfo = serde_json::from_str(
+            r#"{
+                "build_num": 42,
+                "status": "failed",
+                "branch": "main",
+                "subject": null,
+                "steps": [],
+                "start_time": "2024-05-01T12:00:00.000Z",
+                "stop_time": "2024-05-01T12:03:30.000Z",
+                "build_time_millis": 210000,
+                "vcs_revision": "abc1234def",
+                "author_name": "Jane Doe",
+                "why": "github",
+                "workflows": {
+                    "job_name": "test",
+                    "job_id": "job-uuid",
+                    "workflow_name": "ci",
+                    "workflow_id": "wf-uuid",
+                    "upstream_job_ids": []
+                },
+                "build_url": "https://circleci.com/gh/org/repo/42"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(build.build_time_millis, Some(210000));
+        assert_eq!(build.author_name.as_deref(), Some("Jane Doe"));
+        let workflows = build.workflows.unwrap();
+        assert_eq!(workflows.workflow_name.as_deref(), Some("ci"));
+        assert_eq!(workflows.job_name.as_deref(), Some("test"));
+    }
+
+    #[test]
+    fn test_models_keep_unknown_fields() {
+        let json = r#"{
+            "build_num": 7,
+            "status": "success",
+            "branch": null,
+            "subject": null,
+            "lifecycle": "finished",
+            "steps": [{
+                "name": "Build",
+                "actions": [{
+                    "name": "Build",
+                    "status": "success",
+                    "failed": null,
+                    "output_url": null,
+                    "type": "test",
+                    "run_time_millis": 10,
+                    "bash_command": "make",
+                    "background": false
+                }]
+            }]
+        }"#;
+        let build: BuildInfo = serde_json::from_str(json).unwrap();
+
+        assert_eq!(build.extra["lifecycle"], "finished");
+        assert!(build.is_finished());
+        assert_eq!(build.steps[0].actions[0].extra["background"], false);
+        assert_eq!(
+            build.steps[0].actions[0].bash_command.as_deref(),
+            Some("make")
+        );
+        assert_eq!(build.steps[0].actions[0].container(), 0);
+        assert!(!build.steps[0].is_parallel());
+        assert!(build.steps[0].extra.is_empty());
+
+        // Unknown fields survive a round trip
+        let reparsed: BuildInfo =
+            serde_json::from_str(&serde_json::to_string(&build).unwrap()).unwrap();
+        assert_eq!(reparsed, build);
+    }
+
+    #[test]
+    fn test_parallel_step() {
+        let step: Step = serde_json::from_str(
+            r#"{
+                "name": "Run tests",
+                "actions": [
+                    {"name": "Run tests", "status": "success", "failed": null, "output_url": null,
+                     "type": "test", "run_time_millis": 10, "index": 0, "parallel": true},
+                    {"name": "Run tests", "status": "failed", "failed": true, "output_url": null,
+                     "type": "test", "run_time_millis": 12, "index": 1, "parallel": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(step.is_parallel());
+        let failed: Vec<u32> = step
+            .actions
+            .iter()
+            .filter(|a| a.is_failed())
+            .map(Action::container)
+            .collect();
+        assert_eq!(failed, vec![1]);
+    }
+
+    #[test]
+    fn test_user_deserializes() {
+        let user: User =
+            serde_json::from_str(r#"{"id":"u-1","login":"octocat","name":null,"avatar_url":"x"}"#)
+                .unwrap();
+        assert_eq!(user.login, "octocat");
+        assert_eq!(user.name, None);
+    }
+
     #[test]
     fn test_action_helpers() {
         let action = Action {
@@ -531,6 +1285,12 @@ mod tests {
             output_url: None,
             action_type: "test".to_string(),
             run_time_millis: Some(3000),
+            start_time: None,
+            end_time: None,
+            index: None,
+            parallel: None,
+            bash_command: None,
+            extra: Default::default(),
         };
 
         assert!(!action.is_failed());
@@ -543,6 +1303,12 @@ mod tests {
             output_url: None,
             action_type: "test".to_string(),
             run_time_millis: None,
+            start_time: None,
+            end_time: None,
+            index: None,
+            parallel: None,
+            bash_command: None,
+            extra: Default::default(),
         };
 
         assert!(failed_action.is_failed());
@@ -557,5 +1323,50 @@ mod tests {
         let result = CircleClient::with_token("valid-token");
         assert!(result.is_ok());
     }
-}
 
+    #[tokio::test]
+    async fn test_with_concurrency_limits_requests_in_flight() {
+        use wiremock::matchers::path_regex;
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = test_utils::FakeCircleCi::start().await;
+        Mock::given(path_regex(r"^/api/v1\.1/project/github/org/repo/\d+$"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(test_utils::BuildBuilder::new(1).build())
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .mount(server.server())
+            .await;
+
+        let client = server.client().with_concurrency(1);
+        let started = Instant::now();
+        let builds =
+            futures::future::join_all((1..=3).map(|n| client.get_build("org", "repo", n))).await;
+        assert!(builds.iter().all(Result::is_ok));
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_standalone_org_uses_circleci_slug() {
+        let org = "1f6c2bc4-21b4-4e0a-9d34-ea7c8d2c4f1e";
+        let project = "9a3e6f1d-5b7c-4e2a-8f0d-2c1b4a6e8d90";
+        assert_eq!(
+            project_slug(org, project),
+            format!("circleci/{}/{}", org, project)
+        );
+        let (parsed_org, parsed_project, num) =
+            parse_circleci_url(&job_web_url(org, project, 88)).unwrap();
+        assert_eq!(
+            (parsed_org.as_str(), parsed_project.as_str(), num),
+            (org, project, 88)
+        );
+
+        let server = test_utils::FakeCircleCi::start().await;
+        server
+            .mount_build(org, project, &test_utils::BuildBuilder::new(88).build())
+            .await;
+        let build = server.client().get_build(org, project, 88).await.unwrap();
+        assert_eq!(build.build_num, 88);
+    }
+}
@@ -0,0 +1,186 @@
+//! Output of `circleci local execute`.
+//!
+//! The CircleCI CLI runs a job in Docker on the developer's machine and
+//! writes every step to one stream, each under a `====>>` header, with its
+//! own status lines mixed in:
+//!
+//! ```text
+//! ====>> Spin up environment
+//! Build-agent version 1.0.228380-bb91e3ce (2024-03-12T16:05:22+0000)
+//! ====>> npm test
+//!   #!/bin/bash -eo pipefail
+//!   npm test
+//! FAIL src/app.test.js
+//! Error: Exited with code exit status 1
+//! Step failed
+//! Task failed
+//! ```
+//!
+//! [`parse`] splits the stream back into steps, so the one that failed can
+//! be analyzed like a step of a remote build.
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the line that starts a step.
+const STEP_HEADER: &str = "====>> ";
+
+/// One step of a local run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LocalStep {
+    /// Step name, as in the config.
+    pub name: String,
+    /// The shell script the CLI echoed for a `run` step, shebang included.
+    pub command: Option<String>,
+    /// What the step printed, without the CLI's own status lines.
+    #[serde(skip)]
+    pub output: String,
+    /// Exit code the CLI reported for the step.
+    pub exit_code: Option<i32>,
+    /// Whether the CLI reported the step as failed.
+    pub failed: bool,
+}
+
+/// A parsed `circleci local execute` run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LocalRun {
+    /// Steps in the order they ran.
+    pub steps: Vec<LocalStep>,
+    /// Whether the CLI reported the job as failed.
+    pub failed: bool,
+}
+
+impl LocalRun {
+    /// Returns the steps to analyze: the failed ones, or the last step
+    /// when the job failed without a step being marked failed, e.g. while
+    /// preparing the environment.
+    pub fn failed_steps(&self) -> Vec<&LocalStep> {
+        let failed: Vec<&LocalStep> = self.steps.iter().filter(|step| step.failed).collect();
+        if failed.is_empty() && self.failed {
+            self.steps.last().into_iter().collect()
+        } else {
+            failed
+        }
+    }
+}
+
+/// Splits `circleci local execute` output into steps.
+///
+/// Expects ANSI codes to be stripped already. Returns `None` if `log` has
+/// no step headers, i.e. isn't the local CLI's output.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::local;
+///
+/// let log = "====>> Checkout code\n\
+///            ====>> npm test\n  #!/bin/bash -eo pipefail\n  npm test\n\
+///            FAIL src/app.test.js\n\
+///            Error: Exited with code exit status 1\nStep failed\nTask failed\n";
+/// let run = local::parse(log).unwrap();
+/// assert_eq!(run.steps.len(), 2);
+/// let failed = run.failed_steps();
+/// assert_eq!(failed[0].name, "npm test");
+/// assert_eq!(failed[0].exit_code, Some(1));
+/// assert_eq!(failed[0].output, "FAIL src/app.test.js\n");
+/// ```
+pub fn parse(log: &str) -> Option<LocalRun> {
+    let mut run = LocalRun {
+        steps: Vec::new(),
+        failed: false,
+    };
+    // Whether the lines after a header are still the echoed script
+    let mut in_command = false;
+
+    for line in log.lines() {
+        let line = line.trim_end_matches('\r');
+        if let Some(name) = line.strip_prefix(STEP_HEADER) {
+            run.steps.push(LocalStep {
+                name: name.trim().to_string(),
+                command: None,
+                output: String::new(),
+                exit_code: None,
+                failed: false,
+            });
+            in_command = true;
+            continue;
+        }
+        let Some(step) = run.steps.last_mut() else {
+            // Image pulls and other setup before the first step
+            continue;
+        };
+
+        if in_command {
+            let echoed = if step.command.is_none() {
+                Some(line.trim_start()).filter(|line| line.starts_with("#!"))
+            } else {
+                line.strip_prefix("  ")
+                    .filter(|rest| !rest.trim().is_empty())
+            };
+            if let Some(echoed) = echoed {
+                let command = step.command.get_or_insert_with(String::new);
+                command.push_str(echoed);
+                command.push('\n');
+                continue;
+            }
+            in_command = false;
+            if step.command.is_some() && line.trim().is_empty() {
+                // The blank line that ends the echoed script
+                continue;
+            }
+        }
+
+        let status = line.trim();
+        if let Some(code) = status.strip_prefix("Error: Exited with code ") {
+            step.exit_code = code.trim_start_matches("exit status ").parse().ok();
+        } else if status == "Step failed" {
+            step.failed = true;
+        } else if matches!(status, "Task failed" | "Error: task failed") {
+            run.failed = true;
+        } else if status != "Success!" {
+            step.output.push_str(line);
+            step.output.push('\n');
+        }
+    }
+
+    run.failed |= run.steps.iter().any(|step| step.failed);
+    (!run.steps.is_empty()).then_some(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multiline_command_and_setup_failure() {
+        let log = "Fetching latest build environment...\n\
+                   ====>> Spin up environment\n\
+                   Build-agent version 1.0.228380\n\
+                   ====>> Run migrations\n  #!/bin/bash -eo pipefail\n  cd db\n  ./migrate.sh\n\n\
+                   applied 3 migrations\n\
+                   ====>> Restoring cache\n\
+                   Error: Unexpected environment preparation error: disk full\n\
+                   Task failed\n";
+        let run = parse(log).unwrap();
+
+        let names: Vec<&str> = run.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Spin up environment", "Run migrations", "Restoring cache"]
+        );
+        let migrations = &run.steps[1];
+        assert_eq!(
+            migrations.command.as_deref(),
+            Some("#!/bin/bash -eo pipefail\ncd db\n./migrate.sh\n")
+        );
+        assert_eq!(migrations.output, "applied 3 migrations\n");
+
+        assert!(run.failed);
+        let failed = run.failed_steps();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "Restoring cache");
+        assert!(failed[0].output.contains("disk full"));
+
+        assert!(parse("plain log\nError: something\n").is_none());
+    }
+}
@@ -0,0 +1,640 @@
+//! Log text processing.
+//!
+//! Helpers that operate on decoded log output: stripping ANSI codes,
+//! collapsing progress spam and repeated lines, capping pathological lines, finding timestamps in log
+//! lines, filtering by time window, and rewriting absolute timestamps as
+//! offsets from the start of a step.
+
+use crate::parse_duration;
+use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Timestamps further into a line than this are treated as message content.
+const TIMESTAMP_SEARCH_WINDOW: usize = 40;
+
+fn timestamp_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(\d{4}-\d{2}-\d{2})[T ](\d{2}:\d{2}:\d{2}(?:[.,]\d{1,9})?)(Z|[+-]\d{2}:?\d{2})?",
+        )
+        .unwrap()
+    })
+}
+
+/// Removes ANSI escape codes, such as colors and cursor movement, from
+/// log text.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::strip_ansi;
+///
+/// assert_eq!(strip_ansi("\x1b[31merror\x1b[0m: boom"), "error: boom");
+/// ```
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap())
+        .replace_all(text, "")
+}
+
+/// Runs shorter than this are left alone by [`collapse_repeats`].
+const MIN_COLLAPSE_RUN: usize = 3;
+
+/// Result of [`reduce_noise`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseReduction {
+    /// The cleaned-up log text.
+    pub text: String,
+    /// How many lines were removed by collapsing.
+    pub lines_removed: usize,
+    /// 1-based number, in the original log, of each line of `text`; a
+    /// collapsed run maps to its last line, the one kept.
+    pub line_numbers: Vec<usize>,
+}
+
+/// Removes progress animations and collapses repeated lines.
+///
+/// Combines [`strip_carriage_returns`] and [`collapse_repeats`], which is
+/// what webpack, yarn, and docker progress output needs before the last
+/// lines of a log are worth reading.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::reduce_noise;
+///
+/// let log = "start\n10%\r50%\r100%\nwaiting\nwaiting\nwaiting\nwaiting\ndone";
+/// let reduced = reduce_noise(log);
+/// assert_eq!(reduced.text, "start\n100%\nwaiting (repeated 4×)\ndone");
+/// assert_eq!(reduced.lines_removed, 3);
+/// assert_eq!(reduced.line_numbers, vec![1, 2, 6, 7]);
+/// ```
+pub fn reduce_noise(text: &str) -> NoiseReduction {
+    // Carriage returns only rewrite lines, so line numbers carry over
+    let stripped = strip_carriage_returns(text);
+    let (text, line_numbers) = collapse_repeats_numbered(&stripped);
+    let lines_removed = stripped.lines().count() - line_numbers.len();
+    tracing::debug!(lines_removed, "collapsed noise");
+    NoiseReduction {
+        text,
+        lines_removed,
+        line_numbers,
+    }
+}
+
+/// Longest line, in bytes, kept whole by [`cap_lines`].
+pub const MAX_LINE_BYTES: usize = 2048;
+
+/// Result of [`cap_lines`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CappedLines {
+    /// The log with long lines cut and binary lines replaced.
+    pub text: String,
+    /// How many lines were cut to `max_bytes`.
+    pub lines_truncated: usize,
+    /// How many lines were replaced because they held binary data.
+    pub binary_lines: usize,
+}
+
+/// Cuts lines longer than `max_bytes` and replaces binary ones, so a
+/// megabyte of minified JavaScript or a `cat` of an image can't flood the
+/// terminal or slow down pattern matching.
+///
+/// Cut lines end with a marker saying how much was dropped, e.g.
+/// `… [truncated 182 KB]`; binary lines become `[binary data, 4 KB]`.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::cap_lines;
+///
+/// let log = format!("ok\n{}\nbin\u{0}\u{1}\u{2}\u{3}\u{4}\u{5}", "x".repeat(3000));
+/// let capped = cap_lines(&log, 1000);
+/// let lines: Vec<&str> = capped.text.lines().collect();
+/// assert_eq!(lines[0], "ok");
+/// assert!(lines[1].ends_with("x… [truncated 2 KB]"));
+/// assert_eq!(lines[2], "[binary data, 9 B]");
+/// assert_eq!((capped.lines_truncated, capped.binary_lines), (1, 1));
+/// ```
+pub fn cap_lines(text: &str, max_bytes: usize) -> CappedLines {
+    let mut lines_truncated = 0;
+    let mut binary_lines = 0;
+    let lines: Vec<std::borrow::Cow<str>> = text
+        .split('\n')
+        .map(|line| {
+            if is_binary(line) {
+                binary_lines += 1;
+                format!("[binary data, {}]", format_size(line.len())).into()
+            } else if line.len() > max_bytes {
+                lines_truncated += 1;
+                let mut end = max_bytes;
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                format!(
+                    "{}… [truncated {}]",
+                    &line[..end],
+                    format_size(line.len() - end)
+                )
+                .into()
+            } else {
+                line.into()
+            }
+        })
+        .collect();
+    CappedLines {
+        text: lines.join("\n"),
+        lines_truncated,
+        binary_lines,
+    }
+}
+
+/// Returns `true` if a line looks like binary data rather than text: it
+/// has a NUL byte, or a fifth or more of its characters are control
+/// characters or the replacement character left by invalid UTF-8.
+pub fn is_binary(line: &str) -> bool {
+    if line.contains('\0') {
+        return true;
+    }
+    let mut total = 0;
+    let mut odd = 0;
+    for c in line.chars() {
+        total += 1;
+        if c == char::REPLACEMENT_CHARACTER
+            || (c.is_control() && !matches!(c, '\t' | '\r' | '\x1b'))
+        {
+            odd += 1;
+        }
+    }
+    total >= 8 && odd * 5 >= total
+}
+
+/// Formats a byte count for the markers of [`cap_lines`], e.g. "182 KB".
+fn format_size(bytes: usize) -> String {
+    const KB: usize = 1024;
+    if bytes >= KB * KB {
+        format!("{:.1} MB", bytes as f64 / (KB * KB) as f64)
+    } else if bytes >= KB {
+        format!("{} KB", bytes.div_ceil(KB))
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Keeps only the final state of lines redrawn with carriage returns.
+///
+/// Progress bars rewrite a single terminal line with `\r`; in a captured log
+/// every frame piles up on one physical line. Only the last non-empty frame
+/// is kept. Windows `\r\n` line endings are handled as plain newlines.
+pub fn strip_carriage_returns(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            if line.contains('\r') {
+                line.rsplit('\r')
+                    .find(|frame| !frame.is_empty())
+                    .unwrap_or("")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collapses runs of identical or near-identical lines into one.
+///
+/// Lines are near-identical when they differ only in their numbers, as with
+/// percentage counters and elapsed-time tickers; the last line of the run is
+/// kept since it carries the final state. Error and failure lines are only
+/// merged with exact duplicates, so distinct errors stay countable. Blank
+/// lines are never collapsed.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::collapse_repeats;
+///
+/// let log = "Downloading 1/3\nDownloading 2/3\nDownloading 3/3\nok";
+/// assert_eq!(collapse_repeats(log), "Downloading 3/3 (repeated 3×)\nok");
+/// ```
+pub fn collapse_repeats(text: &str) -> String {
+    collapse_repeats_numbered(text).0
+}
+
+/// [`collapse_repeats`], also returning the 1-based line number in `text`
+/// of each output line.
+fn collapse_repeats_numbered(text: &str) -> (String, Vec<usize>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut line_numbers: Vec<usize> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+
+    while i < lines.len() {
+        let mut end = i + 1;
+        while end < lines.len() && is_repeat(lines[i], lines[end]) {
+            end += 1;
+        }
+
+        let run = end - i;
+        if run >= MIN_COLLAPSE_RUN {
+            output.push(format!("{} (repeated {}×)", lines[end - 1], run));
+            line_numbers.push(end);
+        } else {
+            output.extend(lines[i..end].iter().map(|line| line.to_string()));
+            line_numbers.extend(i + 1..=end);
+        }
+        i = end;
+    }
+
+    (output.join("\n"), line_numbers)
+}
+
+fn is_repeat(first: &str, line: &str) -> bool {
+    if first.trim().is_empty() {
+        return false;
+    }
+    if first == line {
+        return true;
+    }
+
+    let lower = first.to_lowercase();
+    if lower.contains("error") || lower.contains("fail") {
+        return false;
+    }
+
+    normalize_numbers(first) == normalize_numbers(line)
+}
+
+fn normalize_numbers(line: &str) -> String {
+    let mut normalized = String::with_capacity(line.len());
+    let mut in_number = false;
+    for c in line.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                normalized.push('#');
+            }
+            in_number = true;
+        } else {
+            in_number = false;
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+/// Finds an ISO8601 timestamp near the start of a log line.
+///
+/// Returns the parsed time (UTC when no offset is given) and the byte range
+/// it occupies in the line.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::find_timestamp;
+///
+/// let line = "2024-05-01T12:00:03.120Z Running tests";
+/// let (time, range) = find_timestamp(line).unwrap();
+/// assert_eq!(time.to_rfc3339(), "2024-05-01T12:00:03.120+00:00");
+/// assert_eq!(&line[range], "2024-05-01T12:00:03.120Z");
+///
+/// assert!(find_timestamp("no time here").is_none());
+/// ```
+pub fn find_timestamp(line: &str) -> Option<(DateTime<Utc>, Range<usize>)> {
+    let caps = timestamp_regex().captures(line)?;
+    let whole = caps.get(0)?;
+    if whole.start() > TIMESTAMP_SEARCH_WINDOW {
+        return None;
+    }
+
+    let date = NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").ok()?;
+    let time = NaiveTime::parse_from_str(&caps[2].replace(',', "."), "%H:%M:%S%.f").ok()?;
+    let naive = NaiveDateTime::new(date, time);
+
+    let time = match caps.get(3).map(|m| m.as_str()) {
+        None | Some("Z") => Utc.from_utc_datetime(&naive),
+        Some(offset) => {
+            let stamp = format!("{}{}", naive.format("%Y-%m-%dT%H:%M:%S%.f"), offset);
+            DateTime::parse_from_str(&stamp, "%Y-%m-%dT%H:%M:%S%.f%z")
+                .ok()?
+                .with_timezone(&Utc)
+        }
+    };
+
+    Some((time, whole.range()))
+}
+
+/// Resolves the effective timestamp of every line in a log.
+///
+/// Lines without their own timestamp inherit the most recent one above
+/// them, so multi-line messages stay together. Lines before the first
+/// timestamp get `start` (typically the step's start time).
+pub fn line_timestamps(text: &str, start: Option<DateTime<Utc>>) -> Vec<Option<DateTime<Utc>>> {
+    let mut current = start;
+    text.lines()
+        .map(|line| {
+            if let Some((time, _)) = find_timestamp(line) {
+                current = Some(time);
+            }
+            current
+        })
+        .collect()
+}
+
+/// One end of a `--since`/`--until` time window.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeBound {
+    /// An absolute point in time, e.g. `2024-05-01T12:00:00Z`.
+    Absolute(DateTime<Utc>),
+    /// A UTC time of day on the log's date, e.g. `12:05` or `12:05:30`.
+    TimeOfDay(NaiveTime),
+    /// An offset from the start of the step, e.g. `+5m`.
+    Offset(chrono::Duration),
+}
+
+impl FromStr for TimeBound {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if s.starts_with('+') {
+            let offset = parse_duration(s)?;
+            return Ok(TimeBound::Offset(chrono::Duration::from_std(offset)?));
+        }
+
+        if let Some((time, range)) = find_timestamp(s) {
+            if range == (0..s.len()) {
+                return Ok(TimeBound::Absolute(time));
+            }
+        }
+
+        for format in ["%H:%M:%S", "%H:%M"] {
+            if let Ok(time) = NaiveTime::parse_from_str(s, format) {
+                return Ok(TimeBound::TimeOfDay(time));
+            }
+        }
+
+        bail!(
+            "cannot parse time '{}'\n  expected: 2024-05-01T12:00:00Z, 12:05, or +5m",
+            s
+        )
+    }
+}
+
+impl TimeBound {
+    /// Resolves the bound to an absolute time relative to the log's start.
+    ///
+    /// Returns `None` for relative bounds when the start is unknown. An
+    /// offset past the last representable time resolves to that time.
+    pub fn resolve(&self, start: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match self {
+            TimeBound::Absolute(time) => Some(*time),
+            TimeBound::TimeOfDay(time) => {
+                let date = start?.date_naive();
+                Some(Utc.from_utc_datetime(&NaiveDateTime::new(date, *time)))
+            }
+            TimeBound::Offset(offset) => Some(
+                start?
+                    .checked_add_signed(*offset)
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            ),
+        }
+    }
+}
+
+/// Keeps only the log lines whose effective timestamp falls in a window.
+///
+/// `start` anchors relative bounds and untimestamped leading lines; when it
+/// is `None` the first timestamp in the log is used. Lines with no
+/// resolvable time are kept so nothing silently disappears from logs that
+/// have no timestamps at all.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::{filter_by_time, TimeBound};
+///
+/// let log = "2024-05-01T12:00:00Z start\n2024-05-01T12:03:00Z middle\n2024-05-01T12:09:00Z end";
+/// let since: TimeBound = "+2m".parse().unwrap();
+/// let until: TimeBound = "12:05".parse().unwrap();
+///
+/// assert_eq!(
+///     filter_by_time(log, None, Some(&since), Some(&until)),
+///     "2024-05-01T12:03:00Z middle"
+/// );
+/// ```
+pub fn filter_by_time(
+    text: &str,
+    start: Option<DateTime<Utc>>,
+    since: Option<&TimeBound>,
+    until: Option<&TimeBound>,
+) -> String {
+    text.lines()
+        .zip(lines_in_window(text, start, since, until))
+        .filter(|(_, kept)| *kept)
+        .map(|(line, _)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns, for each line of `text`, whether [`filter_by_time`] keeps it.
+pub fn lines_in_window(
+    text: &str,
+    start: Option<DateTime<Utc>>,
+    since: Option<&TimeBound>,
+    until: Option<&TimeBound>,
+) -> Vec<bool> {
+    let start = start.or_else(|| first_timestamp(text));
+    let since = since.and_then(|bound| bound.resolve(start));
+    let until = until.and_then(|bound| bound.resolve(start));
+
+    line_timestamps(text, start)
+        .into_iter()
+        .map(|time| match time {
+            Some(time) => {
+                since.is_none_or(|since| time >= since) && until.is_none_or(|until| time <= until)
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Rewrites each line's timestamp as an offset from `start`, e.g. "+2m13s".
+///
+/// When `start` is `None` the first timestamp in the log is used. Lines
+/// without a timestamp are left unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::relative_timestamps;
+///
+/// let log = "2024-05-01T12:00:00Z start\n2024-05-01T12:02:13Z tests done";
+/// assert_eq!(relative_timestamps(log, None), "+0s start\n+2m13s tests done");
+/// ```
+pub fn relative_timestamps(text: &str, start: Option<DateTime<Utc>>) -> String {
+    let Some(start) = start.or_else(|| first_timestamp(text)) else {
+        return text.to_string();
+    };
+
+    text.lines()
+        .map(|line| match find_timestamp(line) {
+            Some((time, range)) => format!(
+                "{}{}{}",
+                &line[..range.start],
+                format_offset((time - start).num_milliseconds()),
+                &line[range.end..]
+            ),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats a millisecond offset compactly with a sign, e.g. "+1h02m05s".
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::logs::format_offset;
+///
+/// assert_eq!(format_offset(0), "+0s");
+/// assert_eq!(format_offset(133_000), "+2m13s");
+/// assert_eq!(format_offset(3_725_000), "+1h02m05s");
+/// assert_eq!(format_offset(-5_000), "-5s");
+/// ```
+pub fn format_offset(millis: i64) -> String {
+    let sign = if millis < 0 { '-' } else { '+' };
+    let seconds = millis.unsigned_abs() / 1000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}{}h{:02}m{:02}s", sign, hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}{}m{:02}s", sign, minutes, seconds)
+    } else {
+        format!("{}{}s", sign, seconds)
+    }
+}
+
+/// Returns the first timestamp found in the log, if any.
+pub fn first_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    text.lines()
+        .find_map(|line| find_timestamp(line).map(|(time, _)| time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_carriage_returns() {
+        assert_eq!(strip_carriage_returns("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(strip_carriage_returns("0%\r50%\r\rdone\r"), "done");
+    }
+
+    #[test]
+    fn test_collapse_repeats_keeps_distinct_errors() {
+        let log = "error at line 1\nerror at line 2\nerror at line 3";
+        assert_eq!(collapse_repeats(log), log);
+
+        let log = "error: boom\nerror: boom\nerror: boom";
+        assert_eq!(collapse_repeats(log), "error: boom (repeated 3×)");
+    }
+
+    #[test]
+    fn test_reduce_noise_numbers_lines_of_the_original() {
+        let log = "start\nwaiting\nwaiting\nwaiting\nError: boom\nwaiting\ndone";
+        let reduced = reduce_noise(log);
+        assert_eq!(
+            reduced.text,
+            "start\nwaiting (repeated 3×)\nError: boom\nwaiting\ndone"
+        );
+        assert_eq!(reduced.line_numbers, vec![1, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_collapse_repeats_short_runs_and_blanks() {
+        assert_eq!(collapse_repeats("tick 1\ntick 2\nok"), "tick 1\ntick 2\nok");
+        assert_eq!(collapse_repeats("a\n\n\n\nb"), "a\n\n\n\nb");
+    }
+
+    #[test]
+    fn test_cap_lines_at_char_boundaries_and_invalid_utf8() {
+        // A multi-byte character straddling the cut isn't split
+        let capped = cap_lines(&"é".repeat(10), 5);
+        assert_eq!(capped.text, "éé… [truncated 16 B]");
+
+        let invalid = String::from_utf8_lossy(&[0xff, 0xfe, b'a', 0xfd, 0xfc, 0xfb, b'b', 0xfa]);
+        assert!(is_binary(&invalid));
+        assert!(!is_binary("\x1b[31merror\x1b[0m: tabs\tare fine"));
+    }
+
+    #[test]
+    fn test_find_timestamp_formats() {
+        let cases = [
+            ("2024-05-01T12:00:00Z ok", "2024-05-01T12:00:00+00:00"),
+            ("[2024-05-01 12:00:00] ok", "2024-05-01T12:00:00+00:00"),
+            ("2024-05-01T14:00:00+02:00 ok", "2024-05-01T12:00:00+00:00"),
+            ("2024-05-01T12:00:00,5Z ok", "2024-05-01T12:00:00.500+00:00"),
+        ];
+
+        for (line, expected) in cases {
+            let (time, _) = find_timestamp(line).unwrap();
+            assert_eq!(time.to_rfc3339(), expected, "line: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_find_timestamp_ignores_message_content() {
+        let line = format!("{} released 2024-05-01T12:00:00Z", "x".repeat(60));
+        assert!(find_timestamp(&line).is_none());
+    }
+
+    #[test]
+    fn test_time_bound_parsing() {
+        assert!(matches!(
+            "+5m".parse::<TimeBound>(),
+            Ok(TimeBound::Offset(_))
+        ));
+        assert!(matches!(
+            "12:05".parse::<TimeBound>(),
+            Ok(TimeBound::TimeOfDay(_))
+        ));
+        assert!(matches!(
+            "2024-05-01T12:00:00Z".parse::<TimeBound>(),
+            Ok(TimeBound::Absolute(_))
+        ));
+        assert!("yesterday".parse::<TimeBound>().is_err());
+        assert!("+999999999999999d".parse::<TimeBound>().is_err());
+
+        // Offsets too far for a date don't overflow
+        let far: TimeBound = "+99999999d".parse().unwrap();
+        assert_eq!(
+            far.resolve(Some(Utc::now())),
+            Some(DateTime::<Utc>::MAX_UTC)
+        );
+    }
+
+    #[test]
+    fn test_filter_by_time_keeps_continuation_lines() {
+        let log = "2024-05-01T12:00:00Z a\ncontinued\n2024-05-01T12:10:00Z b\nmore";
+        let since: TimeBound = "+5m".parse().unwrap();
+        assert_eq!(
+            filter_by_time(log, None, Some(&since), None),
+            "2024-05-01T12:10:00Z b\nmore"
+        );
+    }
+
+    #[test]
+    fn test_filter_by_time_without_timestamps() {
+        let since: TimeBound = "+5m".parse().unwrap();
+        assert_eq!(filter_by_time("a\nb", None, Some(&since), None), "a\nb");
+    }
+}
@@ -21,12 +21,12 @@
 //!
 //! // Run the CLI to analyze a build
 //! let output = Command::new("cdb")
-//!     .args(&["build", "https://circleci.com/gh/org/repo/12345"])
+//!     .args(["build", "https://circleci.com/gh/org/repo/12345"])
 //!     .output()?;
 //!
 //! // Check PR status
 //! let output = Command::new("cdb")
-//!     .args(&["pr", "123", "--repo", "org/repo"])
+//!     .args(["pr", "123", "--repo", "org/repo"])
 //!     .output()?;
 //! # Ok(())
 //! # }
@@ -58,13 +58,88 @@
 //! - Medium builds (1-10MB): ~500ms processing
 //! - Large builds (>10MB): 1-3 seconds processing
 //!
-//! All logs are automatically cached to `/tmp` for faster re-analysis.
+//! All logs are automatically saved to the cache directory for faster re-analysis.
 
 use anyhow::{bail, Context, Result};
-use circle_debug::{format_duration, parse_circleci_url, CircleClient};
+use circle_debug::annotations;
+use circle_debug::artifacts::{self, ArtifactChange, ArtifactInfo};
+use circle_debug::bench::PerfRegression;
+use circle_debug::cache::ResponseCache;
+use circle_debug::cancel::{CanceledBy, Cancellation};
+use circle_debug::ci_config::{self, LineChange, Notable};
+use circle_debug::codeowners::{self, FileOwners};
+use circle_debug::compare::CompareQuery;
+use circle_debug::config::Config;
+use circle_debug::cost::{self, CostEstimate};
+use circle_debug::coverage::CoverageCheck;
+use circle_debug::csv;
+use circle_debug::docker::DockerFailure;
+use circle_debug::download;
+use circle_debug::events::Event;
+use circle_debug::git;
+use circle_debug::highlight::HighlightRule;
+use circle_debug::history::{BuildFilter, BuildQuery};
+#[cfg(feature = "index")]
+use circle_debug::index::LogIndex;
+use circle_debug::jvm::{JvmSummary, ModuleStatus};
+use circle_debug::keys::{self, CheckoutKey, KeyCheck, GIT_AUTH_PATTERN};
+use circle_debug::local::{self, LocalRun};
+use circle_debug::logs::{self, TimeBound};
+use circle_debug::markdown;
+use circle_debug::metrics;
+use circle_debug::monorepo::PackageSummary;
+use circle_debug::nightly::{NightlyQuery, NightlySummary};
+use circle_debug::parallelism;
+use circle_debug::parameters::{self, ParameterType};
+use circle_debug::paths;
+use circle_debug::patterns::{self, ErrorGroup, ErrorPattern, Severity};
+use circle_debug::phases::PhaseTiming;
+use circle_debug::postmortem::{PostMortem, PostMortemFormat};
+use circle_debug::projects::ActiveBuild;
+use circle_debug::query::{self, Query};
+use circle_debug::quickfix;
+use circle_debug::record::RecordingClient;
+use circle_debug::report::{identical_failures, shared_failures, BuildReport, FailedAction};
+use circle_debug::repro::{self, FailedTest};
+use circle_debug::resources::{self, Resource, Verdict};
+use circle_debug::retries::RetriedStep;
+use circle_debug::search::{self, SearchOptions, SearchResults};
+use circle_debug::size::{self, SizeQuery};
+use circle_debug::source::{self, Checkout, Excerpt, SourceLocation};
+use circle_debug::ssh;
+use circle_debug::stats::StatsQuery;
+use circle_debug::store::{HistoryStore, RecordQuery, SeenBefore};
+use circle_debug::suggestions::SuggestionRegistry;
+use circle_debug::summary::{self, FailureSummary, LogAnalysis, LogOptions};
+use circle_debug::suspects::{self, CommitSource, FileHistory, SuspectCommits};
+use circle_debug::template::Template;
+use circle_debug::trigger::TriggerKind;
+use circle_debug::upstream::{self, UpstreamFailure, UpstreamReason};
+use circle_debug::workflow::{JobSummary, WorkflowJob};
+use circle_debug::{
+    format_duration, is_standalone_org, job_web_url, parse_circleci_url, Action, BuildInfo,
+    CircleClient, Step,
+};
 use clap::{Parser, Subcommand};
 use colored::*;
+use futures::StreamExt;
+use indicatif::ProgressBar;
 use regex::Regex;
+use std::collections::HashSet;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod cli;
+use cli::editor;
+use cli::exit::{self, BuildOutcome, FailOn};
+use cli::format::{OutputFormat, ReportFormat};
+use cli::notify;
+use cli::progress::{update_download, Progress};
+use cli::style::{
+    highlight_line, highlight_shell, print_error, print_header, print_info, print_success,
+    print_warning, sparkline, ColorChoice, Themed,
+};
 
 /// Command-line interface for the CircleCI debugger.
 ///
@@ -91,7 +166,7 @@ This tool follows a three-tier debugging approach:
 2. --full: Complete logs when error isn't visible in default view
 3. --tail N: Custom line count for specific context needs
 
-The tool automatically saves full logs to /tmp for fallback analysis.
+The tool automatically saves full logs to its cache directory for fallback analysis.
 
 Use 'cdb <command> --help' for detailed information about each command.
 "#,
@@ -102,18 +177,105 @@ EXAMPLES:
   # Quick diagnosis (most common case)
   cdb build https://circleci.com/gh/org/repo/12345
   
+  # The latest failed build of the current branch
+  cdb build
+  
+  # The build URL on the clipboard, e.g. copied from Slack
+  cdb build --clipboard
+  
   # Full logs when error not found in summary
   cdb build --full https://circleci.com/gh/org/repo/12345
   
   # Custom context window
   cdb build --tail 200 https://circleci.com/gh/org/repo/12345
   
-  # Filter logs by package/task
-  cdb build --filter "@stitch-fix/graphql-api-provider" https://circleci.com/gh/org/repo/12345
+  # Only the output of one monorepo package (Turborepo, Nx, Lerna, Yarn)
+  cdb build --package @stitch-fix/graphql-api-provider https://circleci.com/gh/org/repo/12345
+  
+  # Filter logs to lines containing some text
+  cdb build --filter "TS2304" https://circleci.com/gh/org/repo/12345
   
   # Save to specific file
   cdb build --output debug.log https://circleci.com/gh/org/repo/12345
   
+  # Only download the exit zone of a huge log
+  cdb build --tail-fetch 256 https://circleci.com/gh/org/repo/12345
+  
+  # Lines from a time window, with offsets from the step start
+  cdb build --since 14:02 --until +10m --relative-time https://circleci.com/gh/org/repo/12345
+  
+  # Gate another CI job: only fail when tests failed, not on flaky infra
+  cdb build --fail-on tests https://circleci.com/gh/org/repo/12345
+  
+  # Machine-readable analysis, including the cost estimate
+  cdb build --format json https://circleci.com/gh/org/repo/12345
+  
+  # Annotate a GitHub pull request from a GitHub Actions step
+  cdb build --format gh-annotations https://circleci.com/gh/org/repo/12345
+  
+  # Jump from the failure to the source line in Vim (:cexpr system('...'))
+  cdb build --format quickfix https://circleci.com/gh/org/repo/12345
+  
+  # A few lines for a shell prompt or git hook: status, step, error, fix, log
+  cdb build --summary https://circleci.com/gh/org/repo/12345
+  
+  # Open the failing line in your editor once the analysis is done
+  cdb build --open-editor https://circleci.com/gh/org/repo/12345
+  
+  # The project's deploy and user keys, e.g. after "Permission denied (publickey)"
+  cdb keys org/repo
+  
+  # Rerun a job with SSH and print the command that connects to it
+  cdb ssh https://circleci.com/gh/org/repo/12345
+  
+  # How the nightly suite did over the last four weeks, and why it last failed
+  cdb nightly --workflow nightly-regression
+  
+  # Every followed project with its latest build
+  cdb projects
+  
+  # Follow a project, e.g. after a 404 on its builds
+  cdb follow org/repo
+  
+  # What the org's CI is running and queueing right now, refreshed every 5s
+  cdb top --org myorg
+  
+  # Did the CI config change between a green and a red build?
+  cdb diff-config https://circleci.com/gh/org/repo/12340 https://circleci.com/gh/org/repo/12345
+  
+  # How the bundle's size changed over the last 20 builds of main
+  cdb size --artifact 'dist/app.js' --branch main --last 20
+  
+  # How many containers the test job needs, from its test timings
+  cdb suggest-parallelism https://circleci.com/gh/org/repo/12345
+  
+  # Steps your branch made slower than on main, before merging
+  cdb compare-branch --base main --job test
+  
+  # What changed in the bundle between two builds
+  cdb diff-artifacts --glob 'dist/**' https://circleci.com/gh/org/repo/12340 https://circleci.com/gh/org/repo/12345
+  
+  # Fail when line coverage drops below 80%
+  cdb build --coverage-threshold 80 https://circleci.com/gh/org/repo/12345
+  
+  # An incident writeup: errors with context, tests, timing, suspects
+  cdb report --format html -o postmortem.html https://circleci.com/gh/org/repo/12345
+  
+  # Your own report format (wiki markup, email body, ...)
+  cdb build --template report.hbs https://circleci.com/gh/org/repo/12345
+  
+  # Which parameters the pipeline takes, then run it with one set
+  cdb trigger --list-params
+  cdb trigger -b main -p run-e2e=true
+  
+  # The project's scheduled pipelines, and rerun the nightly after a fix
+  cdb schedules org/repo
+  cdb schedules org/repo --trigger-now nightly
+  
+  # Capture a build's API traffic once, then analyze it offline
+  cdb build --record fixtures/12345 https://circleci.com/gh/org/repo/12345
+  cdb build --replay fixtures/12345 https://circleci.com/gh/org/repo/12345
+  
   # Check current PR (auto-detects PR and repo - requires gh CLI)
   cdb pr
   
@@ -122,9 +284,61 @@ EXAMPLES:
   
   # Check PR by URL
   cdb pr https://github.com/org/repo/pull/123
+  
+  # Analyze all failed jobs of a PR in parallel, with shared failures
+  cdb pr 123 --analyze
+  
+  # Triage a list of red builds; reports go to triage/
+  cdb bulk red-builds.txt -o triage/
+  
+  # Is main green? One request, no logs; exits 0, 14 (failed), or 15 (running)
+  cdb status --branch main
+  
+  # Deploy once CI passes; waits up to 45 minutes
+  cdb wait https://circleci.com/gh/org/repo/12345 --timeout 45m && ./deploy.sh
+  
+  # Find where a job went red on main, with a git bisect command
+  cdb bisect --branch main --job build-and-test --git-bisect
+  
+  # First-time setup: token, default repo, output format
+  cdb init
+  
+  # Remember analyzed builds locally, then list the failed ones
+  cdb history enable
+  cdb history list --failed
+  
+  # Failure rates, frequent errors, and slowing steps over the last month
+  cdb stats --last 30d
+  
+  # When did this warning first show up in the last 50 builds?
+  cdb search "DeprecationWarning" --project org/repo --last 50 --fetch
+  
+  # Grep every step log of one build, with 3 lines of context
+  cdb grep https://circleci.com/gh/org/repo/12345 'ECONNRESET|ETIMEDOUT' -C 3
+  
+  # Grep every cached log of every project, offline
+  cdb cache search "ECONNRESET"
+  
+  # Verify your token
+  cdb auth check
+  
+  # Analyze a log from anywhere, no API access needed
+  cdb analyze build-output.log
+  kubectl logs job/migrate | cdb analyze -
+  circleci local execute test | cdb analyze -
+  
+  # Try a custom error pattern on a sample log before adding it to the config
+  cdb patterns test --file custom.toml --log sample.log
+  
+  # Something not working? Check token, network, gh, cache, and config
+  cdb doctor
 
 ENVIRONMENT:
-  CIRCLECI_TOKEN    Your CircleCI API token (required)
+  CIRCLECI_TOKEN    Your CircleCI API token (required unless saved by `cdb init`)
+  NO_COLOR          Disable colors (with --color auto)
+  CLICOLOR_FORCE    Force colors even when output isn't a terminal
+  RUST_LOG          Diagnostic log filter, overrides -v (e.g. circle_debug=debug)
+  GITHUB_TOKEN      GitHub token for listing suspect commits of private repos
 
 AUTO-DETECTION:
   The 'pr' command auto-detects:
@@ -134,8 +348,13 @@ AUTO-DETECTION:
   Just run 'cdb pr' from your feature branch!
 
 EXIT CODES:
-  0    Success - analysis completed
-  1    General error or authentication failure
+  0    Success - build passed, or --fail-on didn't match the failure
+  1    General error
+  10   Build failed and an error pattern was detected
+  11   Build failed but no pattern was detected (rerun with --full)
+  20   Authentication error (missing or rejected CIRCLECI_TOKEN)
+  21   Build not found
+  30   Network error
   
 AI/AUTOMATION NOTE:
   If the error is not found in the default output, rerun with --full flag
@@ -145,6 +364,30 @@ For bug reports: https://github.com/silouanwright/circle-debug/issues
 "#
 )]
 struct Cli {
+    /// Hide progress bars and spinners
+    #[arg(long, short = 'q', global = true)]
+    quiet: bool,
+    /// Log what cdb is doing to stderr (-v info, -vv debug, -vvv trace)
+    #[arg(long, short = 'v', global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// When to use colors: auto (default) respects NO_COLOR, CLICOLOR_FORCE, and whether stdout is a terminal
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Config file with custom error patterns (default: ~/.config/cdb/config.toml)
+    #[arg(long, global = true, env = "CDB_CONFIG", value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Skip these error patterns in smart detection, e.g. 'non-zero-exit,command-failure' (adds to disabled_patterns in the config)
+    #[arg(long, global = true, value_name = "IDS", value_delimiter = ',')]
+    disable_pattern: Vec<String>,
+    /// Don't cache API responses and logs (default: cached in ~/.cache/cdb/http)
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Most API requests in flight at once; lower it if your organization hits rate limits (default: 8)
+    #[arg(long, global = true, env = "CDB_CONCURRENCY", value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: Option<u16>,
+    /// Print only what this path selects from JSON output, e.g. 'failed_actions[0].errors[0].first.line'
+    #[arg(long, global = true, value_name = "PATH", value_parser = Query::parse)]
+    query: Option<Query>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -153,6 +396,8 @@ struct Cli {
 ///
 /// Each command represents a different debugging operation that can be
 /// performed on CircleCI builds or pull requests.
+// Parsed once per run, so boxing `Build`'s many flags buys nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Analyze a failed build from URL (use --help for full options)
@@ -167,8 +412,16 @@ enum Commands {
     /// - If error not visible: Add --full to see everything
     /// - For AI/automation: Check exit code; non-zero means rerun with --full
     Build {
-        /// CircleCI build URL (e.g., `https://circleci.com/gh/org/repo/12345`)
-        url: String,
+        /// CircleCI build URL (e.g., `https://circleci.com/gh/org/repo/12345`);
+        /// omit to analyze the current branch's latest failed build
+        url: Option<String>,
+        /// Take the build URL from the clipboard
+        #[arg(
+            long,
+            conflicts_with = "url",
+            help = "Analyze the CircleCI build URL on the clipboard, e.g. one copied from Slack"
+        )]
+        clipboard: bool,
         /// Show full logs instead of summary (use when error not found in default view)
         #[arg(
             long,
@@ -180,18 +433,266 @@ enum Commands {
         #[arg(
             long,
             short = 'o',
-            help = "Save clean logs to file (automatic: /tmp/cdb-<build>.log)"
+            help = "Save clean logs to file (automatic: <cache dir>/cdb/logs/cdb-<build>-<step>.log)"
         )]
         output: Option<String>,
         /// Only show last N lines without smart detection
         #[arg(long, help = "Show only the last N lines of output")]
         tail: Option<usize>,
-        /// Filter logs by package or task (e.g., "@stitch-fix/graphql-api-provider")
+        /// Filter logs to lines containing some text
         #[arg(long, help = "Filter logs to show only lines containing this text")]
         filter: Option<String>,
+        /// Show only one monorepo package's output
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Show only the output of this monorepo package, with or without its scope (e.g. @acme/api or api)"
+        )]
+        package: Option<String>,
         /// Skip fetching logs (only show build metadata)
         #[arg(long, help = "Skip fetching and analyzing logs")]
         no_fetch: bool,
+        /// Only download the last N KB of each log (the exit zone)
+        #[arg(
+            long,
+            value_name = "KB",
+            help = "Fetch only the last N KB of each log instead of the whole file"
+        )]
+        tail_fetch: Option<u64>,
+        /// Only show log lines at or after this time
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "Only show log lines at or after TIME (2024-05-01T12:00:00Z, 12:05, or +5m from step start)"
+        )]
+        since: Option<TimeBound>,
+        /// Only show log lines at or before this time
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "Only show log lines at or before TIME (2024-05-01T12:00:00Z, 12:05, or +5m from step start)"
+        )]
+        until: Option<TimeBound>,
+        /// Show timestamps relative to the step start
+        #[arg(
+            long,
+            help = "Replace log timestamps with offsets from the step start (e.g. +2m13s)"
+        )]
+        relative_time: bool,
+        /// Keep repeated lines and progress animations as-is
+        #[arg(
+            long,
+            help = "Don't collapse repeated lines and carriage-return progress output"
+        )]
+        no_collapse: bool,
+        /// Maximum number of error patterns to show in smart detection
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Show at most N error patterns in smart detection (default: 5, or max_errors under [defaults])"
+        )]
+        max_errors: Option<usize>,
+        /// Show every detected error pattern
+        #[arg(
+            long,
+            conflicts_with = "max_errors",
+            help = "Show all detected error patterns instead of the top few"
+        )]
+        all_errors: bool,
+        /// How many lines of the exit zone to show after smart detection
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Show the last N lines after smart detection (default: 50, or tail_default under [defaults])"
+        )]
+        tail_default: Option<usize>,
+        /// Extend the exit zone back to the earliest detected error
+        #[arg(
+            long,
+            help = "Show every line from the earliest detected error on, when that's more than the last N"
+        )]
+        smart_window: bool,
+        /// Which build failures make cdb exit non-zero
+        #[arg(
+            long,
+            value_enum,
+            value_name = "WHEN",
+            default_value_t = FailOn::Failed,
+            help = "Exit non-zero for: any failed build, infra failures, test failures, or never"
+        )]
+        fail_on: FailOn,
+        /// Only show failures from one parallel container
+        #[arg(
+            long,
+            value_name = "INDEX",
+            help = "Only show failures from parallel container INDEX (0-based)"
+        )]
+        container: Option<u32>,
+        /// Show queue vs run time for every job in the workflow
+        #[arg(
+            long,
+            help = "Show how long each job in the workflow queued versus ran"
+        )]
+        queue_times: bool,
+        /// Analyze the upstream job a failure came from instead
+        #[arg(
+            long,
+            help = "When the failure comes from an earlier job (a failed dependency or workspace), analyze that job instead"
+        )]
+        follow_upstream: bool,
+        /// Output format
+        #[arg(
+            long,
+            value_enum,
+            help = "Print a colored report (text, default), the full analysis as JSON (json), GitHub Actions annotations (gh-annotations), steps and failed tests as CSV (csv), file:line:col lines for Vim's quickfix list and VS Code problem matchers (quickfix), or one JSON event per line as the analysis progresses (events), or a few summary lines (summary)"
+        )]
+        format: Option<OutputFormat>,
+        /// Print only a few summary lines
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Print just the status, failed step, top error, suggestion, and log path (same as --format summary); for shell prompts, git hooks, and status bars"
+        )]
+        summary: bool,
+        /// Recent failed builds to check for the same transient errors
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 10,
+            help = "Check the last N failed builds for the same transient network errors (0 to skip)"
+        )]
+        flake_history: u32,
+        /// Don't look up commits since the previous passing build, or
+        /// the latest commits to failing tests
+        #[arg(
+            long,
+            help = "Don't list the commits since the previous passing build of the job, or the latest commits to failing tests' files"
+        )]
+        no_suspects: bool,
+        /// Don't look up CODEOWNERS for files named in errors
+        #[arg(
+            long,
+            help = "Don't look up CODEOWNERS for files named in detected errors"
+        )]
+        no_owners: bool,
+        /// Don't show source excerpts under detected errors
+        #[arg(
+            long,
+            help = "Don't show the lines of the local checkout that detected errors point at"
+        )]
+        no_source: bool,
+        /// Open the first failing file in $EDITOR
+        #[arg(
+            long,
+            help = "Open the first file:line a detected error or failed test points at in $VISUAL, $EDITOR, or VS Code"
+        )]
+        open_editor: bool,
+        /// Report line coverage and its change since the previous passing build
+        #[arg(
+            long,
+            help = "Report line coverage from lcov/Cobertura artifacts or the test log, and its change since the previous passing build"
+        )]
+        coverage: bool,
+        /// Exit with code 12 when coverage is below PERCENT
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            help = "Exit with code 12 if line coverage is below PERCENT (implies --coverage)"
+        )]
+        coverage_threshold: Option<f64>,
+        /// Report peak CPU and memory against the resource class
+        #[arg(
+            long,
+            help = "Report the job's peak CPU and memory use, and whether a smaller or larger resource class would fit"
+        )]
+        resources: bool,
+        /// Render the analysis through a Handlebars template
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "format",
+            help = "Render the analysis through a Handlebars template file (sees the same data as --format json)"
+        )]
+        template: Option<PathBuf>,
+        /// Save every CircleCI response to DIR as replayable fixtures
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+        /// Serve CircleCI responses from fixtures in DIR instead of the network (implies --no-suspects --no-owners)
+        #[arg(long, value_name = "DIR")]
+        replay: Option<PathBuf>,
+    },
+    /// Print a build's status in one line, without fetching any logs
+    ///
+    /// Makes a single API request, so scripts can check red or green
+    /// quickly. Exits 0 when the build passed, 14 when it failed, and 15
+    /// while it's queued or running, or if it was canceled.
+    Status {
+        /// CircleCI build URL
+        #[arg(
+            required_unless_present = "branch",
+            conflicts_with_all = ["branch", "repo", "job"],
+            help = "CircleCI build URL (e.g., https://circleci.com/gh/org/repo/12345)"
+        )]
+        url: Option<String>,
+        /// Branch whose newest finished build to check
+        #[arg(
+            long,
+            short = 'b',
+            help = "Check the newest finished build of this branch instead of a URL"
+        )]
+        branch: Option<String>,
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            requires = "branch",
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Workflow job to check on the branch
+        #[arg(
+            long,
+            short = 'j',
+            requires = "branch",
+            help = "Only consider builds of this workflow job (e.g., build-and-test)"
+        )]
+        job: Option<String>,
+        /// Print the status as JSON
+        #[arg(long, help = "Print the build's status as JSON")]
+        json: bool,
+    },
+    /// Block until a build finishes, then exit with its status
+    ///
+    /// Polls the build's metadata and prints only its final status line,
+    /// so a deploy script can chain on CI: exits 0 when the build passed,
+    /// 14 when it failed, and 15 if it was canceled or is still running
+    /// at --timeout. Use -v to log each poll.
+    Wait {
+        /// CircleCI build URL
+        #[arg(help = "CircleCI build URL (e.g., https://circleci.com/gh/org/repo/12345)")]
+        url: String,
+        /// Give up after this long
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "30m",
+            help = "Give up after DURATION (e.g., 30m, 2h) and exit 15"
+        )]
+        timeout: String,
+        /// Time between polls
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "15s",
+            help = "Check the build every DURATION, at least 1s (e.g., 15s, 1m)"
+        )]
+        interval: String,
+        /// Show a desktop notification when the build finishes
+        #[arg(
+            long,
+            help = "Show a desktop notification with the result when the build finishes or the wait times out"
+        )]
+        notify: bool,
     },
     /// Check PR status and CircleCI checks (use --help for full options)
     ///
@@ -213,834 +714,6953 @@ enum Commands {
             help = "Repository (e.g., org/repo) - auto-detects if not specified"
         )]
         repo: Option<String>,
+        /// Wait for pending checks to finish, then notify
+        #[arg(
+            long,
+            help = "Wait until no check is pending, then show the checks and a desktop notification"
+        )]
+        watch: bool,
+        /// Analyze every failed CircleCI job concurrently
+        #[arg(
+            long,
+            help = "Analyze all failed CircleCI jobs in parallel and merge their errors"
+        )]
+        analyze: bool,
     },
-}
-
-/// Prints a formatted section header to the terminal.
-///
-/// Creates a visually distinctive header with the text in bold blue
-/// and an underline of equal signs.
-///
-/// # Arguments
-///
-/// * `text` - The header text to display
-///
-/// # Examples
-///
-/// ```
-/// print_header("Build Summary");
-/// // Output:
-/// // Build Summary (in bold blue)
-/// // ============= (in blue)
-/// ```
-fn print_header(text: &str) {
-    println!("\n{}", text.bold().blue());
-    println!("{}", "=".repeat(text.len()).blue());
-}
-
-/// Prints an error message with a red cross indicator.
-///
-/// Formats error messages with a red "✗" symbol prefix for
-/// clear visual indication of failures.
-///
-/// # Arguments
-///
-/// * `text` - The error message to display
-///
-/// # Examples
-///
-/// ```
-/// print_error("Build failed with exit code 1");
-/// // Output: ✗ Build failed with exit code 1 (in red)
-/// ```
-fn print_error(text: &str) {
-    println!("{} {}", "✗".red().bold(), text.red());
-}
-
-/// Prints a success message with a green checkmark indicator.
-///
-/// Formats success messages with a green "✓" symbol prefix for
-/// clear visual indication of successful operations.
-///
-/// # Arguments
-///
-/// * `text` - The success message to display
-///
-/// # Examples
-///
-/// ```
-/// print_success("All tests passed");
-/// // Output: ✓ All tests passed (in green)
-/// ```
-fn print_success(text: &str) {
-    println!("{} {}", "✓".green().bold(), text.green());
-}
-
-/// Prints an informational message with a yellow arrow indicator.
-///
-/// Formats informational messages with a yellow "→" symbol prefix
-/// for neutral status updates.
-///
-/// # Arguments
-///
-/// * `text` - The informational message to display
-///
-/// # Examples
-///
-/// ```
-/// print_info("Fetching build details...");
-/// // Output: → Fetching build details... (with yellow arrow)
-/// ```
-fn print_info(text: &str) {
-    println!("{} {}", "→".yellow(), text);
-}
-
-/// Analyzes a CircleCI build and displays detailed failure information.
-///
-/// This is the main analysis function that fetches build details, identifies
-/// failures, retrieves logs, and provides smart error detection with contextual
-/// suggestions for fixing common issues.
-///
-/// # Arguments
-///
-/// * `url` - The CircleCI build URL to analyze
-/// * `full_logs` - If true, displays complete logs instead of summary
-/// * `output_file` - Optional path to save logs to a file
-/// * `tail_lines` - If specified, shows only the last N lines of logs
-/// * `filter` - Optional text filter to show only matching log lines
-/// * `no_fetch` - If true, skips fetching logs (only shows metadata)
-///
-/// # Returns
-///
-/// A `Result` indicating success or containing an error.
-///
-/// # Errors
-///
-/// This function will return an error if:
-/// * The URL cannot be parsed (invalid format)
-/// * The `CIRCLECI_TOKEN` environment variable is not set
-/// * The API request fails (network, authentication, or not found)
-/// * File I/O operations fail when saving logs
-///
-/// # Examples
-///
-/// ```no_run
-/// # use anyhow::Result;
-/// # async fn example() -> Result<()> {
-/// // Basic usage - smart summary + last 50 lines
-/// analyze_build(
-///     "https://circleci.com/gh/org/repo/123",
-///     false, None, None, None, false
-/// ).await?;
-///
-/// // Full logs with output to file
-/// analyze_build(
-///     "https://circleci.com/gh/org/repo/123",
-///     true,
-///     Some("debug.log".to_string()),
-///     None, None, false
-/// ).await?;
-///
-/// // Filter logs for specific package
-/// analyze_build(
-///     "https://circleci.com/gh/org/repo/123",
-///     false, None, None,
-///     Some("@mypackage".to_string()),
-///     false
-/// ).await?;
-/// # Ok(())
-/// # }
-/// ```
-///
-/// # Performance
-///
-/// Log processing performance depends on log size:
-/// * Small logs (<1MB): ~100ms
-/// * Medium logs (1-10MB): ~500ms
-/// * Large logs (>10MB): 1-3 seconds
-///
-/// The function automatically saves logs to `/tmp` for caching.
-///
-/// # See Also
-///
+    /// Find the last green and first red build of a job on a branch
+    ///
+    /// Walks the branch's build history back from the newest build and
+    /// prints the commit range in which the job started failing.
+    Bisect {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Branch to walk
+        #[arg(long, short = 'b', help = "Branch whose history to walk (e.g., main)")]
+        branch: String,
+        /// Workflow job to follow
+        #[arg(
+            long,
+            short = 'j',
+            help = "Only consider builds of this workflow job (e.g., build-and-test)"
+        )]
+        job: Option<String>,
+        /// Maximum number of builds to look back through
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 200,
+            help = "Give up after looking back through N finished builds"
+        )]
+        max_builds: u32,
+        /// Print a `git bisect start` command for the range
+        #[arg(
+            long,
+            help = "Print a 'git bisect start <bad> <good>' command for the range"
+        )]
+        git_bisect: bool,
+    },
+    /// Search the logs of recent builds for a regex
+    ///
+    /// Only logs already in the response cache are searched unless
+    /// --fetch is given, so repeated searches don't download anything.
+    Search {
+        /// Regex to look for in the logs
+        #[arg(help = "Regex to search for (e.g., 'DeprecationWarning')")]
+        pattern: String,
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            visible_alias = "project",
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Number of most recent builds to search
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 20,
+            help = "Search the N most recent builds"
+        )]
+        last: u32,
+        /// Only search builds of this branch
+        #[arg(long, short = 'b', help = "Only search builds of this branch")]
+        branch: Option<String>,
+        /// Lines of context around each match
+        #[arg(
+            long,
+            short = 'C',
+            value_name = "N",
+            default_value_t = 2,
+            help = "Show N lines before and after each match"
+        )]
+        context: usize,
+        /// Download logs that aren't cached yet
+        #[arg(
+            long,
+            help = "Download logs that aren't cached yet instead of skipping them"
+        )]
+        fetch: bool,
+        /// Print the matches as JSON
+        #[arg(long, help = "Print the matches as JSON")]
+        json: bool,
+    },
+    /// Search every step log of one build, like grep
+    ///
+    /// Logs are downloaded once and cached, so repeated searches of the
+    /// same build are instant.
+    Grep {
+        /// CircleCI build URL
+        #[arg(help = "CircleCI build URL (e.g., https://circleci.com/gh/org/repo/12345)")]
+        url: String,
+        /// Regex to look for in the logs
+        #[arg(help = "Regex to search for (e.g., 'ECONNRESET|ETIMEDOUT')")]
+        pattern: String,
+        /// Lines of context around each match
+        #[arg(
+            long,
+            short = 'C',
+            value_name = "N",
+            default_value_t = 0,
+            help = "Show N lines before and after each match"
+        )]
+        context: usize,
+        /// Only count matches per step
+        #[arg(
+            long,
+            short = 'c',
+            help = "Only print how many lines matched in each step"
+        )]
+        count: bool,
+        /// Match case-insensitively
+        #[arg(long, short = 'i', help = "Match case-insensitively")]
+        ignore_case: bool,
+        /// Stop after this many matches per log
+        #[arg(
+            long,
+            short = 'm',
+            value_name = "N",
+            help = "Show at most N matches per step log (counts still include every match)"
+        )]
+        max_count: Option<usize>,
+        /// Print the matches as JSON
+        #[arg(long, help = "Print the matches as JSON")]
+        json: bool,
+    },
+    /// Compare the artifacts of two builds
+    ///
+    /// Lists added, removed, and changed files with their sizes. Sizes and
+    /// hashes come from the artifact server; exits with code 13 if a file
+    /// grew by more than --max-growth.
+    DiffArtifacts {
+        /// The older build
+        #[arg(help = "CircleCI URL of the build to compare against")]
+        old: String,
+        /// The newer build
+        #[arg(help = "CircleCI URL of the build to compare")]
+        new: String,
+        /// Only compare artifacts matching a glob
+        #[arg(
+            long,
+            short = 'g',
+            value_name = "GLOB",
+            help = "Only compare artifacts whose path matches GLOB (e.g., 'dist/**', '*.js')"
+        )]
+        glob: Option<String>,
+        /// Percent growth that counts as a regression
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            default_value_t = 5.0,
+            help = "Flag files that grew by more than PERCENT, and exit with code 13 if any did"
+        )]
+        max_growth: f64,
+        /// Print the differences as JSON
+        #[arg(long, help = "Print the differences as JSON")]
+        json: bool,
+    },
+    /// Compare the CI configuration two builds ran with
+    ///
+    /// Fetches the compiled configuration of each build's pipeline and
+    /// prints a unified diff, with executor, image, and command changes
+    /// listed first.
+    DiffConfig {
+        /// The older build
+        #[arg(help = "CircleCI URL of the build to compare against")]
+        old: String,
+        /// The newer build
+        #[arg(help = "CircleCI URL of the build to compare")]
+        new: String,
+        /// Compare the committed config instead of the compiled one
+        #[arg(
+            long,
+            help = "Compare .circleci/config.yml as committed instead of the compiled config (orbs and parameters expanded)"
+        )]
+        source: bool,
+        /// Unchanged lines shown around each change
+        #[arg(
+            long,
+            short = 'U',
+            value_name = "N",
+            default_value_t = 3,
+            help = "Show N unchanged lines around each change"
+        )]
+        context: usize,
+        /// Print the differences as JSON
+        #[arg(long, help = "Print the differences as JSON")]
+        json: bool,
+    },
+    /// Track an artifact's size across recent builds
+    ///
+    /// Shows a sparkline and a table of the artifact's size in each recent
+    /// build that stored it, and flags the builds where it jumped.
+    Size {
+        /// Artifact to track
+        #[arg(
+            long,
+            short = 'a',
+            value_name = "GLOB",
+            help = "Artifact path or glob (e.g., 'dist/app.js', 'dist/*.js'); sizes of matching files are summed"
+        )]
+        artifact: String,
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            visible_alias = "project",
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Number of most recent builds to look through
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 20,
+            help = "Look through the N most recent finished builds"
+        )]
+        last: u32,
+        /// Only builds of this branch
+        #[arg(long, short = 'b', help = "Only include builds of this branch")]
+        branch: Option<String>,
+        /// Only builds of this workflow job
+        #[arg(
+            long,
+            short = 'j',
+            help = "Only include builds of this workflow job (e.g., build)"
+        )]
+        job: Option<String>,
+        /// Percent growth between builds that counts as a jump
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            default_value_t = 5.0,
+            help = "Flag builds where the size grew by more than PERCENT"
+        )]
+        threshold: f64,
+        /// Print the sizes as JSON
+        #[arg(long, help = "Print the sizes and jumps as JSON")]
+        json: bool,
+    },
+    /// Recommend a test job's parallelism from its test timings
+    ///
+    /// Reads how long each test took from the build's test metadata, or
+    /// its JUnit artifacts, and estimates the job's wall time and credits
+    /// at each parallelism, splitting test files by timings. Recommends
+    /// the lowest parallelism close to the fastest, and how to split.
+    SuggestParallelism {
+        /// A build of the test job
+        #[arg(help = "CircleCI URL of a build of the test job")]
+        url: String,
+        /// Highest parallelism to consider
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 20,
+            help = "Estimate parallelism up to N containers"
+        )]
+        max: u32,
+        /// Print the advice as JSON
+        #[arg(long, help = "Print the estimates and recommendation as JSON")]
+        json: bool,
+    },
+    /// Compare step timings between a branch and its base branch
+    ///
+    /// Averages how long each step took over the recent finished builds of
+    /// both branches and reports the steps consistently slower on the
+    /// branch, to catch CI-time regressions before merging. Exits with
+    /// code 16 if any are found.
+    CompareBranch {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            visible_alias = "project",
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Branch to compare against
+        #[arg(
+            long,
+            default_value = "main",
+            help = "Branch to compare against (e.g., main)"
+        )]
+        base: String,
+        /// Branch under test
+        #[arg(
+            long,
+            short = 'b',
+            help = "Branch to compare (default: the checked-out branch)"
+        )]
+        branch: Option<String>,
+        /// Number of most recent builds of each branch
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 10,
+            help = "Average the N most recent finished builds of each branch"
+        )]
+        last: u32,
+        /// Only builds of this workflow job
+        #[arg(
+            long,
+            short = 'j',
+            help = "Only include builds of this workflow job (e.g., test)"
+        )]
+        job: Option<String>,
+        /// Percent slowdown that counts as a regression
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            default_value_t = 20.0,
+            help = "Report steps at least PERCENT slower on average, in every build of the branch"
+        )]
+        threshold: f64,
+        /// Print the comparison as JSON
+        #[arg(long, help = "Print the step timings of both branches as JSON")]
+        json: bool,
+    },
+    /// Summarize a project's scheduled runs and analyze the latest failure
+    ///
+    /// Finds the pipelines started by a schedule, shows the results of
+    /// their workflows week by week and the current failing streak, then
+    /// analyzes the failed job of the newest failed run. Exits non-zero
+    /// while the newest finished run is failed, for use from cron.
+    Nightly {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            long,
+            short = 'r',
+            visible_alias = "project",
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Only this workflow of the scheduled pipelines
+        #[arg(
+            long,
+            short = 'w',
+            help = "Only include this workflow (e.g., nightly-regression)"
+        )]
+        workflow: Option<String>,
+        /// Only pipelines on this branch
+        #[arg(long, short = 'b', help = "Only include pipelines on this branch")]
+        branch: Option<String>,
+        /// How far back to look
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "28d",
+            help = "Only include runs started in the last DURATION (e.g., 14d)"
+        )]
+        last: String,
+        /// Only summarize; don't analyze the latest failure
+        #[arg(long, help = "Don't analyze the latest failed run")]
+        no_analyze: bool,
+        /// Print the summary as JSON
+        #[arg(long, help = "Print the runs and weekly results as JSON")]
+        json: bool,
+    },
+    /// List a project's checkout keys
+    ///
+    /// Shows each deploy and user key with its fingerprint and when it was
+    /// added. Listing keys needs a token of a project admin.
+    Keys {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Print the keys as JSON
+        #[arg(long, help = "Print the keys as JSON")]
+        json: bool,
+    },
+    /// List the projects your token follows, with their latest build
+    ///
+    /// CircleCI only serves builds of followed projects; the rest come back
+    /// as 404s.
+    Projects {
+        /// Print the projects as JSON
+        #[arg(long, help = "Print the projects as JSON")]
+        json: bool,
+    },
+    /// Follow a project so its builds can be fetched
+    Follow {
+        /// Repository in format org/repo
+        #[arg(help = "Repository to follow (e.g., org/repo)")]
+        repo: String,
+    },
+    /// Show the running and queued builds of followed projects, live
+    ///
+    /// Lists every build that is running or waiting for capacity with its
+    /// branch and how long it has been running or waiting, longest first,
+    /// and refreshes like `top` until interrupted.
+    Top {
+        /// Only this organization's builds
+        #[arg(long, help = "Only show builds of this organization")]
+        org: Option<String>,
+        /// Time between refreshes
+        #[arg(
+            long,
+            short = 'n',
+            value_name = "DURATION",
+            default_value = "5s",
+            help = "Refresh every DURATION, at least 1s (e.g., 5s, 1m)"
+        )]
+        interval: String,
+        /// Print once and exit
+        #[arg(long, help = "Print the builds once instead of refreshing")]
+        once: bool,
+        /// Print the builds as JSON, once
+        #[arg(long, help = "Print the builds as JSON, once")]
+        json: bool,
+    },
+    /// Rerun a job with SSH and print the command that connects to it
+    ///
+    /// Triggers "Rerun job with SSH", waits until the container is up, and
+    /// prints the `ssh -p PORT HOST` command and how long the session lasts.
+    /// Given a build that's already an SSH rerun, only waits.
+    Ssh {
+        /// The build to rerun
+        #[arg(help = "CircleCI URL of the build to rerun with SSH")]
+        url: String,
+        /// How long to wait for the container
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "10m",
+            help = "Give up if SSH isn't ready after DURATION (e.g., 90s, 10m)"
+        )]
+        timeout: String,
+    },
+    /// Trigger a pipeline, checking its parameters against the config first
+    ///
+    /// Reads the pipeline parameters the project's config declares, from
+    /// the latest pipeline on the branch, and checks every --param against
+    /// them before triggering, so a mistyped name or value fails here
+    /// instead of silently running with the default.
+    Trigger {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Branch to run on
+        #[arg(
+            long,
+            short = 'b',
+            help = "Branch to run on (default: the checked-out branch for the origin remote's project, otherwise the project's default branch)"
+        )]
+        branch: Option<String>,
+        /// Pipeline parameters
+        #[arg(
+            long = "param",
+            short = 'p',
+            value_name = "NAME=VALUE",
+            help = "Set a pipeline parameter; repeat for several (e.g., -p run-e2e=true)"
+        )]
+        params: Vec<String>,
+        /// List the declared parameters instead of triggering
+        #[arg(
+            long,
+            help = "List the pipeline parameters the config declares, with types and defaults, and exit"
+        )]
+        list_params: bool,
+        /// Print the result as JSON
+        #[arg(long, help = "Print the parameters or the triggered pipeline as JSON")]
+        json: bool,
+    },
+    /// List a project's scheduled pipelines, or run one now
+    ///
+    /// Shows each schedule's name, when it runs, its branch and pipeline
+    /// parameters, and the last pipeline it started. --trigger-now runs a
+    /// schedule's pipeline immediately, e.g. to rerun a nightly after a fix.
+    Schedules {
+        /// Repository in format org/repo (defaults to the origin remote)
+        #[arg(
+            help = "Repository (e.g., org/repo) - auto-detects from the git origin remote if not specified"
+        )]
+        repo: Option<String>,
+        /// Schedule to run now
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Trigger the named schedule's pipeline now, on its branch and with its parameters"
+        )]
+        trigger_now: Option<String>,
+        /// Print the result as JSON
+        #[arg(long, help = "Print the schedules or the triggered pipeline as JSON")]
+        json: bool,
+    },
+    /// Write a post-mortem of a build as Markdown or HTML
+    ///
+    /// Puts the build's details, the rest of its workflow, each detected
+    /// error with the log lines around it, failed tests, reproduction
+    /// commands, step timing, suspect commits, and code owners in one
+    /// document for an incident writeup.
+    Report {
+        /// CircleCI build URL
+        #[arg(help = "CircleCI build URL (e.g., https://circleci.com/gh/org/repo/12345)")]
+        url: String,
+        /// Document format
+        #[arg(long, value_enum, default_value_t, help = "Document format")]
+        format: ReportFormat,
+        /// File to write the document to
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "Write the document to FILE instead of printing it"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Analyze many builds at once from a list of URLs
+    ///
+    /// Prints one summary row per build and writes each build's report, as
+    /// Markdown and JSON, to an output directory. For triaging a batch of
+    /// red builds, e.g. after an outage.
+    Bulk {
+        /// File with one build URL per line
+        #[arg(
+            required_unless_present = "stdin",
+            conflicts_with = "stdin",
+            help = "File with one CircleCI build URL per line (blank lines and # comments are skipped)"
+        )]
+        file: Option<PathBuf>,
+        /// Read the URLs from stdin
+        #[arg(long, help = "Read the URLs from stdin instead of a file")]
+        stdin: bool,
+        /// Directory for the reports
+        #[arg(
+            long,
+            short = 'o',
+            value_name = "DIR",
+            default_value = "cdb-bulk",
+            help = "Write each build's report to DIR as <org>-<project>-<build>.md and .json"
+        )]
+        output: PathBuf,
+        /// How many builds to analyze at once
+        #[arg(
+            long,
+            short = 'j',
+            value_name = "N",
+            default_value_t = 4,
+            help = "Analyze N builds at once (--concurrency still caps API requests)"
+        )]
+        jobs: usize,
+        /// Print the summary rows as JSON
+        #[arg(long, help = "Print the summary rows as JSON")]
+        json: bool,
+    },
+    /// Analyze a local log file, or stdin, without any API access
+    ///
+    /// Runs the same smart error detection, test and build tool parsing,
+    /// suggestions, and last-50-lines view as `cdb build` over a log from
+    /// anywhere.
+    Analyze {
+        /// Log file to analyze, or - for stdin
+        #[arg(help = "Log file to analyze, or - to read stdin")]
+        file: String,
+        /// Show the full log instead of the summary
+        #[arg(
+            long,
+            short = 'f',
+            help = "Show the complete log instead of the smart summary"
+        )]
+        full: bool,
+        /// Only show last N lines without smart detection
+        #[arg(long, help = "Show only the last N lines of output")]
+        tail: Option<usize>,
+        /// Filter the log to lines containing some text
+        #[arg(long, help = "Filter the log to show only lines containing this text")]
+        filter: Option<String>,
+        /// Show only one monorepo package's output
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Show only the output of this monorepo package, with or without its scope (e.g. @acme/api or api)"
+        )]
+        package: Option<String>,
+        /// Only show log lines at or after this time
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "Only show log lines at or after TIME (2024-05-01T12:00:00Z, 12:05, or +5m from the first timestamp)"
+        )]
+        since: Option<TimeBound>,
+        /// Only show log lines at or before this time
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "Only show log lines at or before TIME (2024-05-01T12:00:00Z, 12:05, or +5m from the first timestamp)"
+        )]
+        until: Option<TimeBound>,
+        /// Keep repeated lines and progress animations as-is
+        #[arg(
+            long,
+            help = "Don't collapse repeated lines and carriage-return progress output"
+        )]
+        no_collapse: bool,
+        /// Maximum number of error patterns to show in smart detection
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Show at most N error patterns in smart detection (default: 5, or max_errors under [defaults])"
+        )]
+        max_errors: Option<usize>,
+        /// Show every detected error pattern
+        #[arg(
+            long,
+            conflicts_with = "max_errors",
+            help = "Show all detected error patterns instead of the top few"
+        )]
+        all_errors: bool,
+        /// How many lines of the exit zone to show after smart detection
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Show the last N lines after smart detection (default: 50, or tail_default under [defaults])"
+        )]
+        tail_default: Option<usize>,
+        /// Extend the exit zone back to the earliest detected error
+        #[arg(
+            long,
+            help = "Show every line from the earliest detected error on, when that's more than the last N"
+        )]
+        smart_window: bool,
+        /// Don't show source excerpts under detected errors
+        #[arg(
+            long,
+            help = "Don't show the lines of the current checkout that detected errors point at"
+        )]
+        no_source: bool,
+        /// Print the analysis as JSON
+        #[arg(long, help = "Print the detected errors and failed tests as JSON")]
+        json: bool,
+    },
+    /// Check the token, API access, gh CLI, cache directory, and config file
+    Doctor,
+    /// Set up the API token, default repository, and output format
+    Init,
+    /// Manage and verify CircleCI authentication
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+    /// Publish build metrics to Prometheus
+    Metrics {
+        #[command(subcommand)]
+        command: MetricsCommand,
+    },
+    /// Show failure rates, frequent errors, time to green, and slowing steps from the local history
+    ///
+    /// Summarizes the builds recorded by `cdb history enable`; no API
+    /// access is needed.
+    Stats {
+        /// How far back to look
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "30d",
+            help = "Only include builds that started in the last DURATION (e.g., 7d, 12h)"
+        )]
+        last: String,
+        /// Repository in format org/repo
+        #[arg(
+            long,
+            short = 'r',
+            help = "Only include builds of this repository (e.g., org/repo)"
+        )]
+        repo: Option<String>,
+        /// Print the statistics as JSON
+        #[arg(long, help = "Print the statistics as JSON")]
+        json: bool,
+    },
+    /// Keep a local database of analyzed builds and their errors
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Search cached logs offline, free cache space, and manage the log index
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// List error patterns, and try custom ones against a sample log
+    Patterns {
+        #[command(subcommand)]
+        command: PatternsCommand,
+    },
+}
+
+/// Subcommands of `cdb patterns`.
+#[derive(Subcommand, Debug)]
+enum PatternsCommand {
+    /// List the built-in and configured error patterns with their ids
+    List {
+        /// Print the patterns as JSON
+        #[arg(long, help = "Print the patterns as JSON")]
+        json: bool,
+    },
+    /// Run custom patterns against a sample log and show what each matched
+    Test {
+        /// TOML file with `[[patterns]]` entries
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "TOML file with [[patterns]] to test (default: the configured patterns)"
+        )]
+        file: Option<PathBuf>,
+        /// Log to run the patterns against
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Sample log to run the patterns against, or - to read stdin"
+        )]
+        log: String,
+        /// Also report built-in patterns that match
+        #[arg(long, help = "Also show which built-in patterns match the log")]
+        builtin: bool,
+        /// Print the matches as JSON
+        #[arg(long, help = "Print the matches as JSON")]
+        json: bool,
+    },
+}
+
+/// Subcommands of `cdb history`.
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Start recording every analyzed build in the local history database
+    Enable,
+    /// List recorded builds, most recent first
+    List {
+        /// Repository in format org/repo
+        #[arg(
+            long,
+            short = 'r',
+            help = "Only list builds of this repository (e.g., org/repo)"
+        )]
+        repo: Option<String>,
+        /// Only list failed builds
+        #[arg(long, help = "Only list failed builds")]
+        failed: bool,
+        /// Maximum number of builds to list
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 20,
+            help = "List at most N builds"
+        )]
+        limit: u32,
+    },
+    /// Delete the history database and stop recording
+    Clear,
+}
+
+/// Subcommands of `cdb cache`.
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Search the logs of every cached build, without network access
+    Search {
+        /// Regex to search for
+        #[arg(help = "Regex to search for (e.g., 'ECONNRESET|ETIMEDOUT')")]
+        pattern: String,
+        /// Repository in format org/repo
+        #[arg(
+            long,
+            short = 'r',
+            help = "Only search builds of this repository (e.g., org/repo)"
+        )]
+        repo: Option<String>,
+        /// Only search builds of this branch
+        #[arg(long, short = 'b', help = "Only search builds of this branch")]
+        branch: Option<String>,
+        /// Only search the most recent cached builds
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Only search the N most recently started cached builds"
+        )]
+        last: Option<u32>,
+        /// Lines of context around each match
+        #[arg(
+            long,
+            short = 'C',
+            default_value_t = 2,
+            help = "Show N lines of context before and after each match"
+        )]
+        context: usize,
+        /// Print the matches as JSON
+        #[arg(long, help = "Print the matches as JSON")]
+        json: bool,
+    },
+    /// Remove the oldest cached responses to free space
+    Prune {
+        /// Size to trim the cache to
+        #[arg(
+            long,
+            value_name = "MB",
+            help = "Trim the cache to MB megabytes (default: cache_max_mb from the config, or 1024)"
+        )]
+        max_mb: Option<u64>,
+        /// Also remove entries cached longer ago than this
+        #[arg(
+            long,
+            value_name = "DURATION",
+            help = "Also remove entries cached more than DURATION ago (e.g., 30d)"
+        )]
+        older_than: Option<String>,
+    },
+    /// Remove every cached response
+    Clear,
+    /// Add cached builds that aren't indexed yet to the log index
+    #[cfg(feature = "index")]
+    Index,
+    /// Rebuild the log index from the cache, picking up logs cached since
+    #[cfg(feature = "index")]
+    Reindex,
+}
+
+/// Subcommands of `cdb metrics`.
+#[derive(Subcommand)]
+enum MetricsCommand {
+    /// Analyze a build and push its duration, queue time, step durations,
+    /// and detected errors to a Prometheus Pushgateway
+    Push {
+        /// CircleCI build URL
+        #[arg(help = "CircleCI build URL (e.g., https://circleci.com/gh/org/repo/12345)")]
+        url: String,
+        /// Pushgateway base URL
+        #[arg(
+            long,
+            value_name = "URL",
+            help = "Pushgateway to push to (e.g., http://pushgateway:9091)"
+        )]
+        gateway: String,
+        /// Grouping key `job` label
+        #[arg(
+            long,
+            default_value = "cdb",
+            help = "Value of the 'job' label in the grouping key"
+        )]
+        job: String,
+    },
+}
+
+/// Subcommands of `cdb auth`.
+#[derive(Subcommand)]
+enum AuthCommand {
+    /// Verify CIRCLECI_TOKEN and show which user it belongs to
+    Check,
+}
+
+/// Error patterns smart detection shows unless `--max-errors` or the
+/// config says otherwise.
+const DEFAULT_MAX_ERRORS: usize = 5;
+
+/// Lines at the end of the log shown after smart detection unless
+/// `--tail-default` or the config says otherwise.
+const EXIT_ZONE_LINES: usize = 50;
+
+/// Options for the `build` command that control log fetching and display.
+///
+/// Mirrors the flags of [`Commands::Build`] so they can be passed around
+/// as a unit.
+#[derive(Debug, Default)]
+struct BuildOptions {
+    /// Display complete logs instead of the smart summary.
+    full_logs: bool,
+    /// Extra path to save the cleaned logs to.
+    output_file: Option<String>,
+    /// Show only the last N lines of each log.
+    tail_lines: Option<usize>,
+    /// Only keep log lines containing this text.
+    filter: Option<String>,
+    /// Only keep the lines of this monorepo package.
+    package: Option<String>,
+    /// Skip fetching logs; only show build metadata.
+    no_fetch: bool,
+    /// Download only the last N KB of each log.
+    tail_fetch: Option<u64>,
+    /// Drop log lines timestamped before this.
+    since: Option<TimeBound>,
+    /// Drop log lines timestamped after this.
+    until: Option<TimeBound>,
+    /// Show timestamps as offsets from the step start.
+    relative_time: bool,
+    /// Keep repeated and progress lines instead of collapsing them.
+    no_collapse: bool,
+    /// How many error patterns to show in smart detection (`None` shows all).
+    max_errors: Option<usize>,
+    /// Lines of the exit zone shown after smart detection (`None` for
+    /// [`EXIT_ZONE_LINES`]).
+    exit_zone_lines: Option<usize>,
+    /// Start the exit zone at the earliest detected error if that's
+    /// further back.
+    smart_window: bool,
+    /// Show queue vs run time for every job in the build's workflow.
+    queue_times: bool,
+    /// Analyze the upstream job a failure came from instead of this one.
+    follow_upstream: bool,
+    /// Only show failures from this parallel container index.
+    container: Option<u32>,
+    /// Print a text report or JSON.
+    format: OutputFormat,
+    /// Push the report's metrics here instead of printing it.
+    metrics_gateway: Option<MetricsGateway>,
+    /// Write a post-mortem of the report instead of printing it.
+    post_mortem: Option<PostMortemOutput>,
+    /// How many recent failed builds to check for the same transient errors.
+    flake_history: u32,
+    /// Skip looking up commits since the previous passing build, and the
+    /// latest commits to failing tests' files.
+    no_suspects: bool,
+    /// Skip looking up CODEOWNERS for files named in errors.
+    no_owners: bool,
+    /// Skip source excerpts from the local checkout under detected errors.
+    no_source: bool,
+    /// Open the first failing file:line in the user's editor.
+    open_editor: bool,
+    /// Report line coverage and its change since the previous passing
+    /// build.
+    coverage: bool,
+    /// Minimum line coverage, below which the exit code is non-zero.
+    coverage_threshold: Option<f64>,
+    /// Report the job's CPU and memory use against its resource class.
+    resources: bool,
+    /// Render the report through this template instead of the built-in output.
+    template: Option<Template>,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+    /// Save CircleCI responses as fixtures in this directory.
+    record: Option<PathBuf>,
+    /// Serve CircleCI responses from fixtures in this directory.
+    replay: Option<PathBuf>,
+    /// Record the report in this history database, when history is enabled.
+    history_db: Option<PathBuf>,
+    /// Patterns used by smart detection: built-ins plus any from the config file.
+    patterns: Vec<ErrorPattern>,
+    /// Fix suggestions shown under detected errors.
+    suggestions: SuggestionRegistry,
+    /// Configured highlight rules for printed log lines.
+    highlights: Vec<HighlightRule>,
+}
+
+/// Where `cdb metrics push` sends a report's metrics.
+#[derive(Debug)]
+struct MetricsGateway {
+    /// Pushgateway base URL.
+    url: String,
+    /// Grouping key `job` label.
+    job: String,
+}
+
+/// Where and how `cdb report` writes a post-mortem.
+#[derive(Debug)]
+struct PostMortemOutput {
+    /// Document format.
+    format: PostMortemFormat,
+    /// File to write to; printed when unset.
+    path: Option<PathBuf>,
+}
+
+/// Analyzes a CircleCI build and displays detailed failure information.
+///
+/// This is the main analysis function that fetches build details, identifies
+/// failures, retrieves logs, and provides smart error detection with contextual
+/// suggestions for fixing common issues.
+///
+/// # Arguments
+///
+/// * `url` - The CircleCI build URL to analyze
+/// * `options` - Log fetching and display options from the command line
+/// * `progress` - Progress display for API calls and downloads
+///
+/// # Returns
+///
+/// The [`BuildOutcome`], used to pick the process exit code, or an error.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The URL cannot be parsed (invalid format)
+/// * The `CIRCLECI_TOKEN` environment variable is not set
+/// * The API request fails (network, authentication, or not found)
+/// * File I/O operations fail when saving logs
+///
+/// # Examples
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # async fn example() -> Result<()> {
+/// let progress = Progress::new(false);
+///
+/// // Basic usage - smart summary + last 50 lines
+/// analyze_build(
+///     "https://circleci.com/gh/org/repo/123",
+///     &BuildOptions::default(),
+///     &progress,
+/// ).await?;
+///
+/// // Full logs with output to file
+/// let options = BuildOptions {
+///     full_logs: true,
+///     output_file: Some("debug.log".to_string()),
+///     ..Default::default()
+/// };
+/// analyze_build("https://circleci.com/gh/org/repo/123", &options, &progress).await?;
+///
+/// // Logs of one monorepo package, fetching only the last 256 KB
+/// let options = BuildOptions {
+///     package: Some("@mypackage".to_string()),
+///     tail_fetch: Some(256),
+///     ..Default::default()
+/// };
+/// analyze_build("https://circleci.com/gh/org/repo/123", &options, &progress).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Performance
+///
+/// Log processing performance depends on log size:
+/// * Small logs (<1MB): ~100ms
+/// * Medium logs (1-10MB): ~500ms
+/// * Large logs (>10MB): 1-3 seconds
+///
+/// Logs of all failed actions are downloaded concurrently. The function
+/// automatically saves logs to the cache directory.
+///
+/// # See Also
+///
 /// * [`parse_circleci_url`] - Parses the build URL
 /// * [`CircleClient`] - Handles API communication
 /// * [`format_duration`] - Formats timing information
 async fn analyze_build(
     url: &str,
-    full_logs: bool,
-    output_file: Option<String>,
-    tail_lines: Option<usize>,
-    filter: Option<String>,
-    no_fetch: bool,
+    options: &BuildOptions,
+    progress: &Progress,
+) -> Result<BuildOutcome> {
+    let text = options.format == OutputFormat::Text && options.template.is_none();
+    let events = options.format == OutputFormat::Events && options.template.is_none();
+    let (org, project, build_num) = parse_circleci_url(url)?;
+    if text {
+        print_header("Analyzing CircleCI Build");
+        print_info(&format!("Organization: {}", org));
+        print_info(&format!("Project: {}", project));
+        print_info(&format!("Build Number: {}", build_num));
+    }
+
+    let client = match (&options.record, &options.replay) {
+        (Some(dir), _) => RecordingClient::record(circle_client(true)?, dir).into_inner(),
+        (None, Some(dir)) => RecordingClient::replay(dir)?.into_inner(),
+        (None, None) => circle_client(options.no_cache)?,
+    };
+
+    let spinner = progress.spinner("Fetching build details...");
+    let build = client.get_build(&org, &project, build_num).await;
+    spinner.finish_and_clear();
+    let build = build?;
+
+    let mut report = BuildReport::new(&org, &project, &build);
+    if events {
+        emit(&Event::build_fetched(&report));
+    }
+    // The other jobs of the workflow put this one's failure in context
+    if let Some(workflow_id) = build
+        .workflows
+        .as_ref()
+        .and_then(|w| w.workflow_id.as_deref())
+    {
+        let spinner = progress.spinner("Fetching workflow jobs...");
+        let jobs = client.get_workflow_jobs(workflow_id).await;
+        spinner.finish_and_clear();
+        match jobs {
+            Ok(jobs) => {
+                report.upstream = upstream::find_upstream(&build, &jobs);
+                report.workflow_jobs = jobs;
+            }
+            Err(e) if text => print_warning(&format!("Couldn't list workflow jobs: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+    // The images and OS are only in the log of the step that provisions
+    // the executor
+    if let (false, Some(log_url)) = (options.no_fetch, build.spin_up_log_url()) {
+        let spinner = progress.spinner("Fetching environment details...");
+        let log = client.get_logs(log_url).await;
+        spinner.finish_and_clear();
+        match log {
+            Ok(log) => {
+                let mut environment = report.environment.take().unwrap_or_default();
+                environment.add_spin_up_log(&log);
+                report.environment = Some(environment);
+            }
+            Err(e) => tracing::debug!("Couldn't fetch the spin-up log: {:#}", e),
+        }
+    }
+    if text {
+        print_build_summary(&build, &report);
+    }
+
+    if let Some(upstream) = &report.upstream {
+        let upstream_url = upstream.job_number.map(|n| job_web_url(&org, &project, n));
+        if text {
+            print_upstream(upstream, upstream_url.as_deref(), options.follow_upstream);
+        }
+        if let (true, Some(upstream_url)) = (options.follow_upstream, upstream_url) {
+            // Boxed because the future would otherwise contain itself
+            return Box::pin(analyze_build(&upstream_url, options, progress)).await;
+        }
+    }
+
+    // A canceled build's steps were stopped, not failed; say who stopped
+    // it instead of analyzing logs that have nothing to find
+    if build.is_canceled() {
+        let spinner = progress.spinner("Finding out who canceled the build...");
+        let cancellation = client.cancellation(&org, &project, &build).await;
+        spinner.finish_and_clear();
+        match cancellation {
+            Ok(cancellation) => report.cancellation = Some(cancellation),
+            Err(e) if text => print_warning(&format!("Couldn't tell who canceled it: {:#}", e)),
+            Err(_) => {}
+        }
+        if text {
+            print_cancellation(report.cancellation.as_ref(), &build, &org, &project);
+        }
+        update_history(&mut report, options, text);
+        print_report(&report, url, options, text).await?;
+        return Ok(BuildOutcome::Passed);
+    }
+
+    // Repositories the steps that failed with "Permission denied
+    // (publickey)" tried to clone
+    let mut git_remotes: Vec<String> = Vec::new();
+    // Failed actions paired with their step; with parallelism each step has
+    // one action per container, so order by container to group the output
+    let parallel = build.steps.iter().any(Step::is_parallel);
+    let mut failed: Vec<(&Step, &Action)> = build
+        .steps
+        .iter()
+        .flat_map(|step| step.actions.iter().map(move |action| (step, action)))
+        .filter(|(_, action)| action.failed.unwrap_or(false))
+        .filter(|(_, action)| options.container.is_none_or(|c| action.container() == c))
+        .collect();
+    if parallel {
+        failed.sort_by_key(|(_, action)| action.container());
+    }
+
+    if !failed.is_empty() {
+        if text {
+            print_header("Failed Steps");
+        }
+        // Excerpts come from the current directory, when it's a clone of
+        // the build's repository
+        let checkout = (text && !options.no_source)
+            .then(|| Checkout::for_build(&org, &project, build.vcs_revision.as_deref()))
+            .flatten();
+        if events {
+            for (step, action) in &failed {
+                emit(&Event::ActionFailed {
+                    step: step.name.clone(),
+                    name: action.name.clone(),
+                    container: action.index,
+                });
+            }
+        }
+        // Download every failed action's log up front so they run concurrently
+        let failed_actions: Vec<&Action> = failed.iter().map(|(_, action)| *action).collect();
+        let mut fetched = if options.no_fetch {
+            Vec::new()
+        } else {
+            fetch_action_logs(&client, &failed_actions, options.tail_fetch, progress).await
+        }
+        .into_iter();
+
+        // Analyze every log first, so failures repeated on several parallel
+        // containers can be shown once
+        let mut actions: Vec<FailedAction> = Vec::new();
+        let mut outputs: Vec<Option<ActionLogs>> = Vec::new();
+        for (step, action) in &failed {
+            let mut failed_action = FailedAction::new(step, action);
+            let mut output = None;
+
+            if action.output_url.is_some() && !options.no_fetch {
+                match fetched.next().flatten() {
+                    Some(Ok(logs)) => {
+                        let action_logs =
+                            process_action_logs(&logs, &build, step, action, options)?;
+                        if events {
+                            for group in &action_logs.analysis.errors {
+                                emit(&Event::ErrorDetected {
+                                    step: step.name.clone(),
+                                    action: action.name.clone(),
+                                    error: group.clone(),
+                                });
+                            }
+                        }
+                        if action_logs
+                            .analysis
+                            .errors
+                            .iter()
+                            .any(|group| group.pattern_id == GIT_AUTH_PATTERN)
+                        {
+                            for remote in keys::git_remotes(&action_logs.analysis.text) {
+                                if !git_remotes.contains(&remote) {
+                                    git_remotes.push(remote);
+                                }
+                            }
+                        }
+                        failed_action = FailedAction {
+                            log_path: action_logs.auto_save_path.clone(),
+                            ..action_logs.analysis.failed_action(step, action)
+                        };
+                        output = Some(action_logs);
+                    }
+                    Some(Err(e)) => failed_action.fetch_error = Some(format!("{:#}", e)),
+                    None => {}
+                }
+            } else if !options.no_fetch {
+                fetched.next();
+            }
+            actions.push(failed_action);
+            outputs.push(output);
+        }
+
+        let groups = if parallel {
+            identical_failures(&actions)
+        } else {
+            (0..actions.len()).map(|i| vec![i]).collect()
+        };
+        // Containers and step of the last printed action, to print headers
+        // only when they change
+        let mut current: Option<(Vec<u32>, String)> = None;
+        for group in groups {
+            let mut failed_action = actions[group[0]].clone();
+            let action_logs = outputs[group[0]].take();
+            let containers: Vec<u32> = group
+                .iter()
+                .map(|&i| actions[i].container.unwrap_or(0))
+                .collect();
+            if containers.len() > 1 {
+                failed_action.containers = containers.clone();
+            }
+            let position = (containers, failed_action.step.clone());
+
+            if text {
+                if parallel && current.as_ref().map(|(c, _)| c) != Some(&position.0) {
+                    let label = match position.0.as_slice() {
+                        [container] => format!("━━ Container {} ━━", container),
+                        containers => format!(
+                            "━━ Containers {} (identical failures) ━━",
+                            containers
+                                .iter()
+                                .map(u32::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    };
+                    println!("\n{}", label.magenta().bold());
+                }
+                if current.as_ref() != Some(&position) {
+                    println!("\n{} {}", "▸".error().bold(), failed_action.step.bold());
+                }
+                print_error(&format!("  {}", failed_action.name));
+                print_command(&failed_action);
+
+                if let Some(action_logs) = &action_logs {
+                    print_action_logs(action_logs, options, checkout.as_ref());
+                } else if let Some(error) = &failed_action.fetch_error {
+                    print_error(&format!("  Failed to fetch logs: {}", error));
+                } else if let Some(output_url) = failed_action
+                    .output_url
+                    .as_ref()
+                    .filter(|_| options.no_fetch)
+                {
+                    println!("\n  {}", "=== LOG FETCHING SKIPPED ===".warning().bold());
+                    println!("  View logs directly at:");
+                    println!("  {}", output_url.blue().underline());
+                }
+            }
+            current = Some(position);
+            report.failed_actions.push(failed_action);
+        }
+    } else if text {
+        match options.container {
+            Some(container) => {
+                print_success(&format!("No failed steps on container {}", container))
+            }
+            None => print_success("No failed steps found"),
+        }
+    }
+
+    // A transient error that keeps showing up in other builds is less
+    // likely to be a one-off flake
+    let transient: Vec<ErrorPattern> = options
+        .patterns
+        .iter()
+        .filter(|pattern| {
+            report
+                .errors()
+                .any(|group| group.transient && group.pattern_id == pattern.id)
+        })
+        .cloned()
+        .collect();
+    if options.flake_history > 0 && !transient.is_empty() {
+        let spinner = progress.spinner("Checking recent builds for the same errors...");
+        let history = client
+            .similar_errors(&org, &project, build_num, &transient, options.flake_history)
+            .await;
+        spinner.finish_and_clear();
+        match history {
+            Ok(history) => report.transient_history = history,
+            Err(e) if text => print_warning(&format!("Couldn't check recent builds: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    let build_failed = build.is_failed()
+        || build.is_infrastructure_failure()
+        || build.failed_actions().next().is_some();
+    // Only CircleCI traffic is recorded; GitHub and git lookups aren't
+    let offline = options.replay.is_some();
+    // Standalone orgs name projects by UUID, which GitHub doesn't know
+    let github = !is_standalone_org(&org);
+    if build_failed && !options.no_suspects && !offline && github {
+        let spinner = progress.spinner("Looking for suspect commits...");
+        let suspects =
+            suspects::find_suspects(&client, &org, &project, &build, report.errors()).await;
+        spinner.finish_and_clear();
+        match suspects {
+            Ok(suspects) => report.suspects = suspects,
+            Err(e) if text => print_warning(&format!("Couldn't list suspect commits: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    let error_files = suspects::error_files(report.errors());
+    if !error_files.is_empty() && !options.no_owners && !offline && github {
+        let spinner = progress.spinner("Looking up code owners...");
+        let owners = codeowners::load(&org, &project, build.vcs_revision.as_deref()).await;
+        spinner.finish_and_clear();
+        match owners {
+            Ok(Some(owners)) => report.owners = owners.owners_of_files(&error_files),
+            Ok(None) => {}
+            Err(e) if text => print_warning(&format!("Couldn't read CODEOWNERS: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    if report
+        .errors()
+        .any(|group| group.pattern_id == GIT_AUTH_PATTERN)
+        && !offline
+    {
+        let spinner = progress.spinner("Checking the project's checkout keys...");
+        let check = client.check_keys(&org, &project, git_remotes).await;
+        spinner.finish_and_clear();
+        match check {
+            Ok(check) => report.checkout_keys = Some(check),
+            Err(e) if text => print_warning(&format!("Couldn't list checkout keys: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    if options.coverage {
+        let spinner = progress.spinner("Looking for coverage reports...");
+        let coverage = coverage_check(&client, &org, &project, &build, options).await;
+        spinner.finish_and_clear();
+        match coverage {
+            Ok(coverage) => report.coverage = coverage,
+            Err(e) if text => print_warning(&format!("Couldn't read coverage: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    if options.resources {
+        let spinner = progress.spinner("Fetching resource usage...");
+        let usage = client.resource_usage(&org, &project, build_num).await;
+        spinner.finish_and_clear();
+        match usage {
+            Ok(samples) => {
+                report.resources = samples.and_then(|samples| resources::check(&build, &samples))
+            }
+            Err(e) if text => print_warning(&format!("Couldn't fetch resource usage: {:#}", e)),
+            Err(_) => {}
+        }
+    }
+
+    // What `summarize` would return for the build, from the logs analyzed
+    // above
+    let failure = FailureSummary::new(&build, report.failed_actions.clone());
+    let failed_tests: Vec<FailedTest> = failure.failed_tests().cloned().collect();
+    report.reproduce = failure.reproduce.clone();
+    if let Some(revision) = build
+        .vcs_revision
+        .as_deref()
+        .filter(|_| !failed_tests.is_empty() && !options.no_suspects && !offline && github)
+    {
+        let spinner = progress.spinner("Looking up recent changes to failing tests...");
+        let history = suspects::test_file_history(&org, &project, revision, &failed_tests).await;
+        spinner.finish_and_clear();
+        match history {
+            Ok(history) => report.test_history = history,
+            Err(e) if text => print_warning(&format!(
+                "Couldn't list recent changes to failing tests: {:#}",
+                e
+            )),
+            Err(_) => {}
+        }
+    }
+    update_history(&mut report, options, text);
+
+    if text {
+        if !report.reproduce.is_empty() {
+            print_reproduce(&report.reproduce, failed_tests.len());
+        }
+        if report.regressions().next().is_some() {
+            print_regressions(&report);
+        }
+        if !report.seen_before.is_empty() {
+            print_seen_before(&report.seen_before);
+        }
+        if !transient.is_empty() {
+            print_transient_errors(&report, &transient, url);
+        }
+        if !report.retries.is_empty() {
+            print_retries(&report.retries);
+        }
+        if let Some(suspects) = &report.suspects {
+            print_suspects(suspects);
+        }
+        if !report.test_history.is_empty() {
+            print_test_history(&report.test_history);
+        }
+        if !report.owners.is_empty() {
+            print_owners(&report.owners);
+        }
+        if options.coverage {
+            print_coverage(report.coverage.as_ref());
+        }
+        if let Some(check) = &report.checkout_keys {
+            print_key_check(check);
+        }
+        if options.resources {
+            print_resource_check(&report);
+        }
+        print_timing_analysis(&report);
+    }
+
+    if options.queue_times {
+        match build
+            .workflows
+            .as_ref()
+            .and_then(|w| w.workflow_id.as_deref())
+        {
+            Some(workflow_id) => {
+                let spinner = progress.spinner("Fetching workflow jobs...");
+                let jobs = client.get_job_summaries(&org, &project, workflow_id).await;
+                spinner.finish_and_clear();
+                let jobs = jobs?;
+                if text {
+                    print_workflow_jobs(&jobs);
+                }
+                report.jobs = Some(jobs);
+            }
+            None if text => print_warning("--queue-times: this build isn't part of a workflow"),
+            None => {}
+        }
+    }
+
+    print_report(&report, url, options, text).await?;
+    if options.open_editor && build_failed {
+        open_in_editor(&report)?;
+    }
+
+    if build_failed {
+        Ok(BuildOutcome::Failed {
+            detected: failure.errors().next().is_some(),
+            kinds: failure.kinds.clone(),
+        })
+    } else if report
+        .coverage
+        .as_ref()
+        .is_some_and(CoverageCheck::below_threshold)
+    {
+        Ok(BuildOutcome::BelowCoverage)
+    } else {
+        Ok(BuildOutcome::Passed)
+    }
+}
+
+/// Builds of the current branch to look through for the latest failure;
+/// one page of the build list.
+const BRANCH_BUILDS: u32 = 30;
+
+/// Finds the latest failed build of the current git branch, for `cdb
+/// build` without a URL.
+///
+/// The project is `repo` (`org/repo`) or, when unset, the one the git
+/// origin remote points at. With `text`, says which build was picked and
+/// warns if its job has run again on the branch since.
+///
+/// # Returns
+///
+/// The build's CircleCI URL.
+///
+/// # Errors
+///
+/// Returns an error if the branch or project can't be determined, the
+/// builds can't be listed, or none of the branch's last finished builds
+/// failed.
+async fn latest_failed_build(
+    repo: Option<&str>,
+    no_cache: bool,
+    text: bool,
+    progress: &Progress,
+) -> Result<String> {
+    let branch = git::current_branch().context("Pass a build URL, or run from a branch")?;
+    let (org, project) = match repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let client = circle_client(no_cache)?;
+    let query = BuildQuery {
+        branch: Some(branch.clone()),
+        filter: BuildFilter::Completed,
+        limit: BRANCH_BUILDS,
+        offset: 0,
+    };
+    let spinner = progress.spinner("Finding the branch's latest failed build...");
+    let builds = client.recent_builds(&org, &project, &query).await;
+    spinner.finish_and_clear();
+    let builds = builds?;
+
+    let failed = builds
+        .iter()
+        .position(|build| build.is_failed() || build.is_infrastructure_failure());
+    let Some(index) = failed else {
+        match builds.first() {
+            Some(latest) => bail!(
+                "None of the last {} finished builds of {} on {}/{} failed; the latest, #{}, is {}",
+                builds.len(),
+                branch,
+                org,
+                project,
+                latest.build_num,
+                latest.status
+            ),
+            None => bail!("No finished builds of {} on {}/{}", branch, org, project),
+        }
+    };
+    let build = &builds[index];
+    if text {
+        print_info(&format!(
+            "Latest failed build of {}: #{}",
+            branch, build.build_num
+        ));
+        // Only later runs of the same job say whether the failure still
+        // stands; the workflow's other jobs passing doesn't
+        let job = |build: &BuildInfo| {
+            build
+                .workflows
+                .as_ref()
+                .and_then(|workflow| workflow.job_name.clone())
+        };
+        let newer: Vec<String> = builds[..index]
+            .iter()
+            .filter(|newer| job(newer) == job(build))
+            .map(|newer| format!("#{} {}", newer.build_num, newer.status))
+            .collect();
+        if !newer.is_empty() {
+            print_warning(&format!(
+                "The same job has run again on {} since: {}",
+                branch,
+                newer.join(", ")
+            ));
+        }
+    }
+    Ok(job_web_url(&org, &project, build.build_num))
+}
+
+/// Finds the build's line coverage and that of the previous passing build
+/// of its branch and job.
+///
+/// Returns `None` if the build reported no coverage. A baseline that
+/// can't be found only leaves the delta out.
+async fn coverage_check(
+    client: &CircleClient,
+    org: &str,
+    project: &str,
+    build: &BuildInfo,
+    options: &BuildOptions,
+) -> Result<Option<CoverageCheck>> {
+    let Some(coverage) = client.build_coverage(org, project, build).await? else {
+        return Ok(None);
+    };
+    let previous = match client.previous_green(org, project, build, 100).await {
+        Ok(Some(previous)) => client
+            .get_build(org, project, previous.build_num)
+            .await
+            .ok(),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("couldn't find the previous passing build: {:#}", e);
+            None
+        }
+    };
+    let baseline = match &previous {
+        Some(previous) => client
+            .build_coverage(org, project, previous)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("couldn't read the previous build's coverage: {:#}", e);
+                None
+            }),
+        None => None,
+    };
+    Ok(Some(CoverageCheck {
+        coverage,
+        baseline_build: baseline.as_ref().and(previous.map(|b| b.build_num)),
+        baseline,
+        threshold: options.coverage_threshold,
+    }))
+}
+
+/// Prints the "Checkout Keys" section for a build that failed with
+/// "Permission denied (publickey)": the project's keys and, if it's clear,
+/// why they couldn't clone.
+fn print_key_check(check: &KeyCheck) {
+    print_header("Checkout Keys");
+    print_checkout_keys(&check.keys);
+    if !check.remotes.is_empty() {
+        print_info(&format!("Cloned over SSH: {}", check.remotes.join(", ")));
+    }
+    match &check.problem {
+        Some(problem) => print_error(&problem.to_string()),
+        None => print_info(
+            "The keys look right; check the key is still listed under the repository's deploy keys, or the user's SSH keys, on GitHub",
+        ),
+    }
+}
+
+/// Prints one line per checkout key: type, fingerprint, and when it was
+/// added.
+fn print_checkout_keys(keys: &[CheckoutKey]) {
+    for key in keys {
+        let created = key
+            .created_at
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        println!(
+            "  {:<11} {} {}{}",
+            key.key_type.label(),
+            key.fingerprint.cyan(),
+            created.dim(),
+            if key.preferred {
+                " (preferred)".success().to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+}
+
+/// Prints the "Coverage" section: line coverage, its change since the
+/// previous passing build, and whether it meets the threshold.
+fn print_coverage(check: Option<&CoverageCheck>) {
+    print_header("Coverage");
+    let Some(check) = check else {
+        print_info(
+            "No coverage found: no lcov or Cobertura artifacts, and no summary in test logs",
+        );
+        return;
+    };
+    let counts = match (check.coverage.covered, check.coverage.total) {
+        (Some(covered), Some(total)) => format!(" ({}/{} lines)", covered, total),
+        _ => String::new(),
+    };
+    println!(
+        "{} {}{} from {}",
+        "Lines:".bold(),
+        format!("{:.2}%", check.coverage.percent).bold(),
+        counts,
+        check.coverage.source.dim()
+    );
+    if let (Some(delta), Some(baseline), Some(number)) =
+        (check.delta(), &check.baseline, check.baseline_build)
+    {
+        let change = format!("{:+.2}", delta);
+        let change = if delta < 0.0 {
+            change.error().bold()
+        } else {
+            change.success()
+        };
+        println!(
+            "{} {} points since #{} ({:.2}%)",
+            "Change:".bold(),
+            change,
+            number,
+            baseline.percent
+        );
+    }
+    if let Some(threshold) = check.threshold {
+        if check.below_threshold() {
+            print_error(&format!("Below the {}% coverage threshold", threshold));
+        } else {
+            print_success(&format!("Meets the {}% coverage threshold", threshold));
+        }
+    }
+}
+
+/// Prints the "Resource Usage" section: the job's peak CPU and memory
+/// against its resource class, and the class that would fit better.
+fn print_resource_check(report: &BuildReport) {
+    print_header("Resource Usage");
+    let Some(check) = &report.resources else {
+        print_info("No resource usage: CircleCI has no CPU or memory samples for this job");
+        return;
+    };
+    println!(
+        "{} {} ({} vCPUs, {:.0} GB)",
+        "Class:".bold(),
+        check.resource_class.bold(),
+        check.cpu,
+        check.ram_mb as f64 / 1024.0
+    );
+    println!(
+        "{} peak {:.0}%, average {:.0}%",
+        "CPU:".bold(),
+        check.peak_cpu_percent,
+        check.average_cpu_percent
+    );
+    println!(
+        "{} peak {:.1} GB ({:.0}%)",
+        "Memory:".bold(),
+        check.peak_memory_mb / 1024.0,
+        check.peak_memory_percent
+    );
+    match &check.verdict {
+        Verdict::Fits => print_success(&format!("{} fits this job", check.resource_class)),
+        Verdict::Underutilized { suggested_class } => {
+            print_info(&format!(
+                "Peaked at {:.0}% CPU and {:.0}% memory on {}; consider {}",
+                check.peak_cpu_percent,
+                check.peak_memory_percent,
+                check.resource_class,
+                suggested_class
+            ));
+            if let (Some(current), Some(suggested)) = (
+                cost::credits_per_minute(&check.resource_class),
+                cost::credits_per_minute(suggested_class),
+            ) {
+                let minutes = report.build_time_millis.unwrap_or(0) as f64 / 60_000.0;
+                print_info(&format!(
+                    "{} uses {} fewer credits per minute ({:.0} fewer for this run)",
+                    suggested_class,
+                    current - suggested,
+                    (current - suggested) * minutes
+                ));
+            }
+        }
+        Verdict::Saturated {
+            resource,
+            suggested_class,
+        } => {
+            let oom = *resource == Resource::Memory
+                && report
+                    .errors()
+                    .any(|group| group.pattern_id == "out-of-memory");
+            print_warning(&format!(
+                "The job used nearly all of {}'s {}{}",
+                check.resource_class,
+                resource,
+                if oom {
+                    ", which explains the out-of-memory error"
+                } else {
+                    ""
+                }
+            ));
+            if let Some(class) = suggested_class {
+                print_info(&format!("Try resource_class: {}", class));
+            }
+        }
+    }
+}
+
+/// Looks up the report's failures in the history database, then saves
+/// the report to it, if history is enabled.
+///
+/// Failing either only warns, since the analysis itself succeeded.
+fn update_history(report: &mut BuildReport, options: &BuildOptions, text: bool) {
+    let Some(path) = &options.history_db else {
+        return;
+    };
+    let updated = HistoryStore::open(path).and_then(|store| {
+        report.seen_before = store.recall(report)?;
+        store.record(report)
+    });
+    match updated {
+        Ok(()) => tracing::debug!(path = %path.display(), "recorded build in history"),
+        Err(e) if text => print_warning(&format!("Couldn't update build history: {:#}", e)),
+        Err(e) => tracing::warn!("couldn't update build history: {:#}", e),
+    }
+}
+
+/// Prints the "Seen Before" section: which failures match earlier builds
+/// in the local history, and which are new.
+fn print_seen_before(seen: &[SeenBefore]) {
+    const MAX_BUILDS: usize = 5;
+    print_header("Seen Before");
+    for entry in seen {
+        if entry.builds.is_empty() {
+            println!(
+                "{} {} - new: not seen in earlier recorded builds",
+                "★".cyan().bold(),
+                entry.category.bold()
+            );
+            continue;
+        }
+        let mut builds: Vec<String> = entry
+            .builds
+            .iter()
+            .take(MAX_BUILDS)
+            .map(|n| format!("#{}", n))
+            .collect();
+        if entry.builds.len() > MAX_BUILDS {
+            builds.push(format!("and {} more", entry.builds.len() - MAX_BUILDS));
+        }
+        let first_seen = entry
+            .first_seen
+            .map(|at| format!(" (first seen {})", format_age(chrono::Utc::now() - at)))
+            .unwrap_or_default();
+        println!(
+            "{} {} - identical failure seen in build{} {}{}",
+            "↺".warning().bold(),
+            entry.category.bold(),
+            if entry.builds.len() == 1 { "" } else { "s" },
+            builds.join(", "),
+            first_seen
+        );
+    }
+}
+
+/// Formats how long ago something happened, e.g. "6 days ago".
+fn format_age(age: chrono::Duration) -> String {
+    let (count, unit) = if age.num_days() > 0 {
+        (age.num_days(), "day")
+    } else if age.num_hours() > 0 {
+        (age.num_hours(), "hour")
+    } else if age.num_minutes() > 0 {
+        (age.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// The `--query` path, applied by [`print_json`].
+static QUERY: std::sync::OnceLock<Query> = std::sync::OnceLock::new();
+
+/// Prints a value as pretty-printed JSON, or only what `--query` selects
+/// from it.
+///
+/// # Errors
+///
+/// Returns an error if the value can't be serialized.
+fn print_json<T: serde::Serialize>(value: &T) -> Result<()> {
+    match QUERY.get() {
+        Some(query) => {
+            let found = query.apply(&serde_json::to_value(value)?);
+            println!("{}", query::format_result(&found));
+        }
+        None => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+/// Prints an event for `--format events` as a line of its own.
+fn emit(event: &Event) {
+    println!("{}", event.to_line());
+}
+
+/// Saves the report as Markdown next to the build's logs.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+fn save_markdown_report(report: &BuildReport) -> Result<PathBuf> {
+    let path = paths::saved_report(report.build_num);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, markdown::render(report))
+        .with_context(|| format!("Failed to save the report to {}", path.display()))?;
+    Ok(path)
+}
+
+/// Finishes the output: the "Quick Actions" section for text, otherwise
+/// the whole report through the template or as JSON, or for
+/// `cdb metrics push`, pushes the report's metrics. In every case the
+/// report is also saved as Markdown.
+async fn print_report(
+    report: &BuildReport,
+    url: &str,
+    options: &BuildOptions,
+    text: bool,
+) -> Result<()> {
+    // Saving the report only warns, since the analysis itself succeeded
+    let saved = match save_markdown_report(report) {
+        Ok(path) => Some(path),
+        Err(e) if text => {
+            print_warning(&format!("Couldn't save the Markdown report: {:#}", e));
+            None
+        }
+        Err(e) => {
+            tracing::warn!("couldn't save the Markdown report: {:#}", e);
+            None
+        }
+    };
+    if let Some(gateway) = &options.metrics_gateway {
+        metrics::push(&gateway.url, &gateway.job, report).await?;
+        print_success(&format!(
+            "Pushed metrics for build #{} to {}",
+            report.build_num, gateway.url
+        ));
+    } else if let Some(output) = &options.post_mortem {
+        let document = PostMortem::new(report).render(output.format);
+        match &output.path {
+            Some(path) => {
+                std::fs::write(path, document)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                print_success(&format!(
+                    "Wrote the post-mortem of build #{} to {}",
+                    report.build_num,
+                    path.display()
+                ));
+            }
+            None => print!("{}", document),
+        }
+    } else if text {
+        print_header("Quick Actions");
+        println!("• Rerun: {}", format!("{}/retry", url).blue().underline());
+        println!("• SSH Debug: {}", format!("cdb ssh {}", url).cyan());
+        println!(
+            "• View artifacts: {}",
+            format!("{}/artifacts", url).blue().underline()
+        );
+        if let Some(saved) = saved {
+            println!("• Markdown report: {}", saved.display().to_string().cyan());
+        }
+    } else if let Some(template) = &options.template {
+        print!("{}", template.render(report)?);
+    } else if options.format == OutputFormat::GhAnnotations {
+        print!("{}", annotations::render(report));
+    } else if options.format == OutputFormat::Csv {
+        print!("{}", csv::render(report));
+    } else if options.format == OutputFormat::Quickfix {
+        print!("{}", quickfix::render(report));
+    } else if options.format == OutputFormat::Events {
+        emit(&Event::timing_computed(report));
+    } else if options.format == OutputFormat::Summary {
+        print_summary(report, options);
+    } else {
+        print_json(report)?;
+    }
+    Ok(())
+}
+
+/// Prints `--summary`: the build's status line, then for the first failed
+/// step with a detected error (or the first failed step) the step, its top
+/// error and suggestion, and where its log was saved.
+fn print_summary(report: &BuildReport, options: &BuildOptions) {
+    let failed = !report.failed_actions.is_empty()
+        || matches!(
+            report.status.as_str(),
+            "failed" | "infrastructure_fail" | "timedout"
+        );
+    let marker = if failed {
+        "✗".error().bold()
+    } else if report.status == "canceled" {
+        "•".warning().bold()
+    } else {
+        "✓".success().bold()
+    };
+    let context: Vec<&str> = report
+        .workflow
+        .as_ref()
+        .and_then(|workflow| workflow.job_name.as_deref())
+        .into_iter()
+        .chain(report.branch.as_deref())
+        .collect();
+    let context = if context.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", context.join(" · "))
+    };
+    println!(
+        "{} {}/{} #{} {}{}",
+        marker, report.org, report.project, report.build_num, report.status, context
+    );
+
+    let Some(action) = report
+        .failed_actions
+        .iter()
+        .find(|action| !action.errors.is_empty())
+        .or(report.failed_actions.first())
+    else {
+        return;
+    };
+    let container = action
+        .container_label()
+        .map(|label| format!(" ({})", label))
+        .unwrap_or_default();
+    let more = match report.failed_actions.len() - 1 {
+        0 => String::new(),
+        n => format!(" and {} more", n),
+    };
+    println!("Step: {}{}{}", action.step, container, more);
+    if let Some(group) = action.errors.first() {
+        println!("Error: [{}] {}", group.category, group.first.line.trim());
+        let suggestion = options
+            .patterns
+            .iter()
+            .find(|p| p.id == group.pattern_id)
+            .and_then(|pattern| options.suggestions.suggest(pattern, &group.first.line));
+        if let Some(suggestion) = suggestion {
+            println!("Fix: {}", suggestion);
+        }
+    } else if let Some(error) = &action.fetch_error {
+        println!("Error: couldn't fetch the log: {}", error);
+    }
+    if let Some(path) = &action.log_path {
+        println!("Log: {}", path);
+    }
+}
+
+/// Opens the first file a detected error or failed test points at in the
+/// user's editor.
+///
+/// Paths are resolved against the current directory's clone of the build's
+/// repository, or the current directory when it isn't one.
+///
+/// # Errors
+///
+/// Returns an error if no error names a file that exists locally, or the
+/// editor can't be started.
+fn open_in_editor(report: &BuildReport) -> Result<()> {
+    let checkout =
+        Checkout::for_build(&report.org, &report.project, report.vcs_revision.as_deref())
+            .unwrap_or_else(|| Checkout::new("."));
+    let tests = report.failed_tests().filter_map(|test| {
+        Some(SourceLocation {
+            file: test.file.clone()?,
+            line: None,
+            col: None,
+        })
+    });
+    let target = report
+        .errors()
+        .filter_map(|group| source::locate(group.first.line.trim()))
+        .chain(tests)
+        .find_map(|location| Some((checkout.resolve(&location.file)?, location)));
+    let Some((file, location)) = target else {
+        bail!(
+            "No detected error names a file found under {}",
+            checkout.root().display()
+        );
+    };
+    editor::open(&checkout.root().join(file), location.line, location.col)
+}
+
+/// Prints the source lines around an error, marking its line and column.
+fn print_excerpt(excerpt: &Excerpt) {
+    let location = match excerpt.col {
+        Some(col) => format!("{}:{}:{}", excerpt.file, excerpt.line, col),
+        None => format!("{}:{}", excerpt.file, excerpt.line),
+    };
+    let note = if excerpt.exact {
+        String::new()
+    } else {
+        " (local checkout is at a different commit; lines may have moved)".to_string()
+    };
+    println!("    {}{}", location.cyan(), note.warning());
+
+    let width = excerpt
+        .lines
+        .last()
+        .map(|(number, _)| number.to_string().len())
+        .unwrap_or(1);
+    for (number, text) in &excerpt.lines {
+        if *number == excerpt.line {
+            println!(
+                "    {} {:>width$} │ {}",
+                "→".error().bold(),
+                number.to_string().bold(),
+                text.bold()
+            );
+            if let Some(col) = excerpt.col {
+                println!(
+                    "      {:width$} │ {}{}",
+                    "",
+                    " ".repeat(col.saturating_sub(1) as usize),
+                    "^".error().bold()
+                );
+            }
+        } else {
+            println!(
+                "      {} │ {}",
+                format!("{:>width$}", number).dim(),
+                text.dim()
+            );
+        }
+    }
+}
+
+/// Prints the "Canceled" section for a canceled build.
+fn print_cancellation(
+    cancellation: Option<&Cancellation>,
+    build: &BuildInfo,
+    org: &str,
+    project: &str,
+) {
+    print_header("Canceled");
+    let when = cancellation
+        .and_then(|c| c.canceled_at)
+        .map(|at| format!(" at {}", at.format("%Y-%m-%d %H:%M:%S UTC")))
+        .unwrap_or_default();
+    match cancellation.map(|c| &c.by) {
+        Some(CanceledBy::User { login, name }) => {
+            let who = match name {
+                Some(name) if name != login => format!("{} ({})", name, login),
+                _ => login.clone(),
+            };
+            print_info(&format!("Canceled by {}{}", who.bold(), when));
+        }
+        Some(CanceledBy::Superseded {
+            build_num,
+            vcs_revision,
+            ..
+        }) => {
+            let commit = vcs_revision
+                .as_deref()
+                .map(|sha| format!(" ({})", sha.get(..7).unwrap_or(sha).warning()))
+                .unwrap_or_default();
+            print_info(&format!(
+                "Auto-canceled{}: build #{}{} on {} superseded it",
+                when,
+                build_num,
+                commit,
+                build.branch.as_deref().unwrap_or("the same branch")
+            ));
+            println!(
+                "  {}",
+                job_web_url(org, project, *build_num).blue().underline()
+            );
+        }
+        Some(CanceledBy::Unknown) | None => {
+            print_info(&format!("Canceled{}; CircleCI doesn't say by whom", when))
+        }
+    }
+    println!(
+        "  {}",
+        "Skipping failure analysis: the steps were stopped, not failed".dim()
+    );
+}
+
+/// Prints the "Reproduce Locally" section: commands that rerun only the
+/// failed tests.
+fn print_reproduce(commands: &[String], test_count: usize) {
+    print_header("Reproduce Locally");
+    print_info(&format!(
+        "{} failed test{}; to rerun just {}:",
+        test_count,
+        if test_count == 1 { "" } else { "s" },
+        if test_count == 1 { "it" } else { "them" }
+    ));
+    for command in commands {
+        println!("  {} {}", "$".dim(), command.cyan());
+    }
+}
+
+/// Prints the "Performance Regressions" section: benchmarks the failed
+/// actions' logs report as slower, most regressed first.
+fn print_regressions(report: &BuildReport) {
+    print_header("Performance Regressions");
+    let mut regressions: Vec<(&FailedAction, &PerfRegression)> = report.regressions().collect();
+    regressions.sort_by(|a, b| b.1.change_percent.total_cmp(&a.1.change_percent));
+    for (action, regression) in regressions {
+        let change = format!("+{:.1}%", regression.change_percent);
+        let change = match regression.severity {
+            Severity::Error => change.error().bold(),
+            Severity::Warning => change.warning().bold(),
+            Severity::Hint => change.normal(),
+        };
+        let baseline = regression
+            .baseline
+            .as_deref()
+            .map(|baseline| format!(" vs {}", baseline))
+            .unwrap_or_default();
+        println!(
+            "{} {} {}{} ({}, log line {})",
+            change,
+            regression.benchmark.bold(),
+            regression.tool.label().dim(),
+            baseline.dim(),
+            action.step,
+            regression.line_number
+        );
+    }
+}
+
+/// Prints where a failure that came from an earlier job came from, and how
+/// to analyze that job.
+fn print_upstream(upstream: &UpstreamFailure, url: Option<&str>, following: bool) {
+    print_header("Upstream Failure");
+    let number = upstream
+        .job_number
+        .map(|n| format!(" (#{})", n))
+        .unwrap_or_default();
+    match upstream.reason {
+        UpstreamReason::WorkspaceAttach if upstream.status == "success" => print_warning(&format!(
+            "Attaching the workspace failed; check that {}{} persists it",
+            upstream.job.bold(),
+            number
+        )),
+        UpstreamReason::WorkspaceAttach => print_warning(&format!(
+            "Attaching the workspace failed because upstream job {}{} failed",
+            upstream.job.bold(),
+            number
+        )),
+        UpstreamReason::DependencyFailed => print_warning(&format!(
+            "Upstream job {}{} failed first; this job likely failed because of it",
+            upstream.job.bold(),
+            number
+        )),
+    }
+    match url {
+        Some(url) if following => print_info(&format!("Following it: {}", url.blue().underline())),
+        Some(url) => println!(
+            "  {} cdb build {}  {}",
+            "→".cyan(),
+            url,
+            "(or pass --follow-upstream)".dim()
+        ),
+        None => {}
+    }
+}
+
+/// Formats a workflow's jobs as one line, e.g.
+/// "lint ✓, test ✗ ← you are here, deploy ○ blocked".
+fn format_workflow_jobs(jobs: &[WorkflowJob], build_num: u32) -> String {
+    jobs.iter()
+        .map(|job| {
+            let mark = match job.status.as_str() {
+                "success" => "✓".success().to_string(),
+                "failed" | "infrastructure_fail" | "timedout" | "terminated-unknown" => {
+                    "✗".error().to_string()
+                }
+                "running" | "queued" => format!("{} {}", "●".warning(), job.status),
+                "canceled" => format!("{} canceled", "⊘".dim()),
+                "on_hold" => format!("{} on hold", "○".dim()),
+                "not_run" => format!("{} not run", "○".dim()),
+                other => format!("{} {}", "○".dim(), other.replace('_', " ")),
+            };
+            let here = if job.job_number == Some(build_num) {
+                format!(" {}", "← you are here".bold())
+            } else {
+                String::new()
+            };
+            format!("{} {}{}", job.name, mark, here)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints the "Build Summary" section: status, commit, trigger, timing,
+/// and estimated cost.
+fn print_build_summary(build: &BuildInfo, report: &BuildReport) {
+    print_header("Build Summary");
+    print_info(&format!(
+        "Status: {}",
+        if build.is_failed() || build.is_infrastructure_failure() {
+            build.status.error().to_string()
+        } else if build.is_canceled() {
+            build.status.warning().to_string()
+        } else {
+            build.status.success().to_string()
+        }
+    ));
+
+    if let Some(branch) = &build.branch {
+        print_info(&format!("Branch: {}", branch));
+    }
+
+    match (build.short_revision(), &build.subject) {
+        (Some(sha), Some(subject)) => print_info(&format!("Commit: {} {}", sha.warning(), subject)),
+        (Some(sha), None) => print_info(&format!("Commit: {}", sha.warning())),
+        (None, Some(subject)) => print_info(&format!("Commit: {}", subject)),
+        (None, None) => {}
+    }
+
+    if let Some(author) = &build.author_name {
+        print_info(&format!("Author: {}", author));
+    }
+    if let Some(committer) = report.trigger.as_ref().and_then(|t| t.committer.as_ref()) {
+        if build.author_name.as_ref() != Some(committer) {
+            print_info(&format!("Committer: {}", committer));
+        }
+    }
+
+    if let Some(workflows) = &build.workflows {
+        if let (Some(workflow), Some(job)) = (&workflows.workflow_name, &workflows.job_name) {
+            print_info(&format!("Workflow: {} › {}", workflow, job));
+        }
+    }
+    if !report.workflow_jobs.is_empty() {
+        print_info(&format!(
+            "Jobs: {}",
+            format_workflow_jobs(&report.workflow_jobs, build.build_num)
+        ));
+    }
+
+    if let Some(trigger) = &report.trigger {
+        let mut line = format!("Triggered by: {}", trigger.kind);
+        if let Some(retry_of) = trigger.retry_of {
+            line.push_str(&format!(" of #{}", retry_of));
+        }
+        if let Some(user) = &trigger.user {
+            line.push_str(&format!(" by {}", user.bold()));
+        }
+        if let Some(number) = trigger.pull_request.as_ref().and_then(|pr| pr.number) {
+            line.push_str(&format!(" · PR #{}", number));
+        }
+        print_info(&line);
+        if let Some(pr) = &trigger.pull_request {
+            println!("  {}", pr.url.blue().underline());
+        }
+    }
+
+    if let Some(start) = build.start_time {
+        let mut started = format!("Started: {}", start.format("%Y-%m-%d %H:%M:%S UTC"));
+        if let Some(millis) = build.build_time_millis {
+            started.push_str(&format!(" (took {})", format_duration(millis)));
+        }
+        print_info(&started);
+    }
+
+    if let Some(queued_ms) = report.queued_millis {
+        let line = format!(
+            "Queued: {} waiting for capacity",
+            format_duration(queued_ms)
+        );
+        // Flag builds that waited longer than they ran
+        if build.build_time_millis.is_some_and(|ran| queued_ms > ran) {
+            print_info(&line.warning().to_string());
+        } else {
+            print_info(&line);
+        }
+    }
+
+    if let Some(environment) = &report.environment {
+        if let Some(executor) = environment.executor_label() {
+            print_info(&format!("Executor: {}", executor));
+        }
+        match environment.images.as_slice() {
+            [] => {}
+            [image] => print_info(&format!("Image: {}", image.cyan())),
+            images => print_info(&format!(
+                "Images: {}",
+                images
+                    .iter()
+                    .map(|image| image.cyan().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+        if let Some(os) = environment.os_label() {
+            print_info(&format!("OS: {}", os));
+        }
+    }
+
+    if let Some(cost) = &report.cost {
+        print_info(&format!("Estimated cost: {}", format_cost(cost)));
+    }
+
+    if let Some(build_url) = &build.build_url {
+        print_info(&format!("URL: {}", build_url.blue().underline()));
+    }
+}
+
+/// Formats a cost estimate as "~240 credits ($0.14) - large × 4".
+fn format_cost(cost: &CostEstimate) -> String {
+    let mut text = format!(
+        "~{:.0} credits (${:.2}) - {}",
+        cost.credits, cost.usd, cost.resource_class
+    );
+    if cost.parallelism > 1 {
+        text.push_str(&format!(" × {}", cost.parallelism));
+    }
+    if cost.assumed_class {
+        text.push_str(" (assumed)");
+    }
+    text
+}
+
+/// Prints the "Retried Steps" section: how many attempts each retried
+/// step needed, with the ones that only passed on retry flagged as flaky.
+fn print_retries(retries: &[RetriedStep]) {
+    print_header("Retried Steps");
+    for step in retries {
+        let name = match step.container {
+            Some(container) => format!("{} (container {})", step.name, container),
+            None => step.name.clone(),
+        };
+        let wasted = format!(
+            "{} lost to failed attempts",
+            format_duration(step.wasted_millis)
+        );
+        if step.is_flaky() {
+            println!(
+                "  {} {} passed on attempt {} {}",
+                "⚠".warning(),
+                name.bold(),
+                step.attempts,
+                format!("(flaky; {})", wasted).dim()
+            );
+        } else {
+            println!(
+                "  {} {} still failed after {} attempts {}",
+                "✗".error(),
+                name.bold(),
+                step.attempts,
+                format!("({})", wasted).dim()
+            );
+        }
+    }
+    let flaky = retries.iter().filter(|step| step.is_flaky()).count();
+    if flaky > 0 {
+        print_warning(&format!(
+            "{} step{} only passed on retry: latent flakes that will fail a build once retries run out",
+            flaky,
+            if flaky == 1 { "" } else { "s" }
+        ));
+    }
+}
+
+/// Prints the "Transient Errors" section: which detected errors a rerun
+/// will likely fix, and how often they hit recent builds.
+fn print_transient_errors(report: &BuildReport, transient: &[ErrorPattern], url: &str) {
+    print_header("Transient Errors");
+    for pattern in transient {
+        println!(
+            "{} {} - likely a flake rather than a problem with the change",
+            "↻".warning().bold(),
+            pattern.category.bold()
+        );
+
+        let Some(history) = report
+            .transient_history
+            .iter()
+            .find(|history| history.pattern_id == pattern.id)
+        else {
+            continue;
+        };
+        if history.builds.is_empty() {
+            println!("  Not seen in the last {} failed build(s)", history.checked);
+        } else {
+            let builds: Vec<String> = history.builds.iter().map(|n| format!("#{}", n)).collect();
+            println!(
+                "  {}",
+                format!(
+                    "Seen in {} of the last {} failed build(s): {}",
+                    history.builds.len(),
+                    history.checked,
+                    builds.join(", ")
+                )
+                .warning()
+            );
+            if history.builds.len() * 2 >= history.checked {
+                println!("  Recurring - consider adding retries or caching the dependency");
+            }
+        }
+    }
+    println!(
+        "\n  Re-trigger the job: {}",
+        format!("{}/retry", url).blue().underline()
+    );
+}
+
+/// Prints the "Suspect Commits" section, flagging commits that touch files
+/// named in the detected errors.
+fn print_suspects(suspects: &SuspectCommits) {
+    const MAX_SHOWN: usize = 15;
+    print_header("Suspect Commits");
+
+    let range = format!(
+        "{}..{}",
+        short_sha(&suspects.base_revision),
+        short_sha(&suspects.head_revision)
+    );
+    if suspects.commits.is_empty() {
+        print_info(&format!(
+            "Same commit as the last green build #{} ({}) - the failure is likely flaky or environmental",
+            suspects.base_build,
+            short_sha(&suspects.head_revision)
+        ));
+        return;
+    }
+    let source = match suspects.source {
+        CommitSource::Git => "local git",
+        CommitSource::GitHub => "GitHub",
+    };
+    print_info(&format!(
+        "{} commit(s) since the last green build #{} ({}, from {})",
+        suspects.commits.len(),
+        suspects.base_build,
+        range,
+        source
+    ));
+
+    // Flagged commits first, then the rest newest first
+    let mut ordered: Vec<_> = suspects.flagged().collect();
+    ordered.extend(
+        suspects
+            .commits
+            .iter()
+            .filter(|s| s.matched_files.is_empty()),
+    );
+    for suspect in ordered.iter().take(MAX_SHOWN) {
+        let commit = &suspect.commit;
+        let author = commit
+            .author
+            .as_deref()
+            .map(|author| format!(" ({})", author))
+            .unwrap_or_default();
+        if suspect.matched_files.is_empty() {
+            println!(
+                "    {} {}",
+                commit.short_sha().warning(),
+                format!("{}{}", commit.subject, author).dim()
+            );
+        } else {
+            println!(
+                "  {} {} {}{}",
+                "►".error().bold(),
+                commit.short_sha().warning(),
+                commit.subject.bold(),
+                author
+            );
+            println!(
+                "      {}",
+                format!("touches {}", suspect.matched_files.join(", ")).error()
+            );
+        }
+    }
+    if ordered.len() > MAX_SHOWN {
+        println!(
+            "    {}",
+            format!("… {} more commit(s)", ordered.len() - MAX_SHOWN).dim()
+        );
+    }
+}
+
+/// Prints the "Last Touched" section: the latest commits to each failing
+/// test's file, with their authors.
+fn print_test_history(history: &[FileHistory]) {
+    print_header("Last Touched");
+    for file in history {
+        print_info(&file.file.bold().to_string());
+        for commit in &file.commits {
+            let age = commit
+                .date
+                .map(|date| format!(", {}", format_age(chrono::Utc::now() - date)))
+                .unwrap_or_default();
+            println!(
+                "    {} {} {}",
+                commit.short_sha().warning(),
+                commit.subject,
+                format!("({}{})", commit.author.as_deref().unwrap_or("unknown"), age).dim()
+            );
+        }
+    }
+}
+
+/// Prints the "Who to Ping" section, grouping files by their owners.
+fn print_owners(owners: &[FileOwners]) {
+    print_header("Who to Ping");
+
+    let mut groups: Vec<(&[String], Vec<&str>)> = Vec::new();
+    for file in owners {
+        match groups
+            .iter_mut()
+            .find(|(o, _)| *o == file.owners.as_slice())
+        {
+            Some((_, paths)) => paths.push(&file.path),
+            None => groups.push((&file.owners, vec![&file.path])),
+        }
+    }
+    for (owners, paths) in groups {
+        print_info(&owners.join(", ").bold().to_string());
+        println!("    {}", paths.join(", ").dim());
+    }
+}
+
+/// Prints the "Timing Analysis" section with the slowest steps and any
+/// bottleneck.
+fn print_timing_analysis(report: &BuildReport) {
+    print_header("Timing Analysis");
+    let step_timings = &report.step_timings;
+    let total_time: u64 = step_timings.iter().map(|t| t.millis).sum();
+
+    if !step_timings.is_empty() {
+        println!("Total build time: {}", format_duration(total_time));
+        println!("\nSlowest steps:");
+        for (i, timing) in step_timings.iter().take(5).enumerate() {
+            let (name, duration) = (&timing.name, timing.millis);
+            let percentage = (duration as f64 / total_time as f64 * 100.0) as u32;
+            let duration_str = format_duration(duration);
+
+            // Color code based on duration
+            let formatted = if duration > 60000 {
+                // > 1 minute
+                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).error()
+            } else if duration > 30000 {
+                // > 30 seconds
+                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).warning()
+            } else {
+                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).success()
+            };
+            println!("  {}", formatted);
+        }
+
+        // Identify bottlenecks
+        if let Some(slowest) = step_timings.first() {
+            let percentage = (slowest.millis as f64 / total_time as f64 * 100.0) as u32;
+            if percentage > 50 {
+                print_warning(&format!(
+                    "Bottleneck detected: '{}' takes {}% of total time",
+                    slowest.name, percentage
+                ));
+                println!("  Consider optimizing or parallelizing this step");
+            }
+        }
+    } else {
+        println!("No timing data available for this build");
+    }
+}
+
+/// Prints how long each job of the workflow queued versus ran, and what
+/// it cost.
+fn print_workflow_jobs(jobs: &[JobSummary]) {
+    print_header("Workflow Jobs");
+
+    let show = |millis: Option<u64>| {
+        millis
+            .map(format_duration)
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let width = jobs.iter().map(|j| j.name.len()).max().unwrap_or(0).max(3);
+    println!(
+        "  {:width$}  {:>10}  {:>10}  {:>8}",
+        "Job", "Queued", "Ran", "Credits"
+    );
+    for job in jobs {
+        let queued = format!("{:>10}", show(job.queued_millis));
+        let queued = if job.queued_millis > job.ran_millis {
+            queued.warning().to_string()
+        } else {
+            queued
+        };
+        let credits = job
+            .cost
+            .as_ref()
+            .map(|cost| format!("{:.0}", cost.credits))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "  {:width$}  {}  {:>10}  {:>8}",
+            job.name,
+            queued,
+            show(job.ran_millis),
+            credits
+        );
+    }
+
+    let queued: u64 = jobs.iter().filter_map(|j| j.queued_millis).sum();
+    let ran: u64 = jobs.iter().filter_map(|j| j.ran_millis).sum();
+    let credits: f64 = jobs
+        .iter()
+        .filter_map(|j| j.cost.as_ref())
+        .map(|c| c.credits)
+        .sum();
+    println!(
+        "\n  Jobs spent {} queued and {} running, ~{:.0} credits (${:.2})",
+        format_duration(queued).bold(),
+        format_duration(ran).bold(),
+        credits,
+        credits * cost::USD_PER_CREDIT
+    );
+}
+
+/// A failed action's log after cleanup, ready to display.
+struct ActionLogs {
+    /// Where the ANSI-stripped log was saved; `None` for local files.
+    auto_save_path: Option<String>,
+    /// Size of the downloaded log.
+    size_bytes: usize,
+    /// The cleaned-up log and what was found in it.
+    analysis: LogAnalysis,
+}
+
+/// Cleans up and analyzes the log output of a single failed action.
+///
+/// Strips ANSI codes, saves the logs to disk, collapses noise, summarizes
+/// monorepo packages, Gradle/Maven failures, Docker builds, and failed
+/// tests, applies `--package`, the time window, and `--filter`, and runs
+/// smart error detection.
+///
+/// # Errors
+///
+/// Returns an error if saving the logs to disk fails.
+fn process_action_logs(
+    logs: &str,
+    build: &BuildInfo,
+    step: &Step,
+    action: &Action,
+    options: &BuildOptions,
+) -> Result<ActionLogs> {
+    // Strip ANSI escape codes
+    let clean_logs = logs::strip_ansi(logs);
+    let log_start = action
+        .start_time
+        .or_else(|| logs::first_timestamp(&clean_logs));
+
+    // Always save to the cache directory for fallback, one file per step
+    // and container so several failed actions don't overwrite each other
+    let step_index = build
+        .steps
+        .iter()
+        .position(|s| std::ptr::eq(s, step))
+        .unwrap_or(0);
+    let container = action.parallel.unwrap_or(false).then(|| action.container());
+    let auto_save_path = paths::saved_log(build.build_num, step_index, container);
+    if let Some(dir) = auto_save_path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&auto_save_path, clean_logs.as_ref())
+        .with_context(|| format!("Failed to save logs to {}", auto_save_path.display()))?;
+    let auto_save_path = auto_save_path.display().to_string();
+
+    // Save to custom file if requested
+    if let Some(output_path) = &options.output_file {
+        std::fs::write(output_path, clean_logs.as_ref())?;
+    }
+
+    let mut action_logs = analyze_log(&clean_logs, log_start, options);
+    action_logs.auto_save_path = Some(auto_save_path);
+    action_logs.size_bytes = logs.len();
+    Ok(action_logs)
+}
+
+/// Runs [`summary::analyze_log`] over an ANSI-stripped log with the
+/// `--no-collapse`, `--package`, time window, `--filter`, and
+/// `--relative-time` options.
+///
+/// `log_start` anchors `--since`/`--until` offsets and relative times.
+fn analyze_log(
+    clean_logs: &str,
+    log_start: Option<chrono::DateTime<chrono::Utc>>,
+    options: &BuildOptions,
+) -> ActionLogs {
+    let log_options = LogOptions {
+        no_collapse: options.no_collapse,
+        package: options.package.clone(),
+        since: options.since.clone(),
+        until: options.until.clone(),
+        filter: options.filter.clone(),
+        relative_time: options.relative_time,
+        start: log_start,
+    };
+    ActionLogs {
+        auto_save_path: None,
+        size_bytes: clean_logs.len(),
+        analysis: summary::analyze_log(clean_logs, &log_options, &options.patterns),
+    }
+}
+
+/// Prints which packages of a monorepo log failed, e.g.
+/// "3 of 14 packages failed: a, b, c".
+fn print_package_summary(summary: &PackageSummary) {
+    let failed: Vec<&str> = summary.failed().map(|p| p.name.as_str()).collect();
+    let total = summary.packages.len();
+    if failed.is_empty() {
+        println!(
+            "  {}",
+            format!(
+                "{} package{} ran, none reported a failure",
+                total,
+                if total == 1 { "" } else { "s" }
+            )
+            .dim()
+        );
+    } else {
+        println!(
+            "  {} {}",
+            format!(
+                "{} of {} package{} failed:",
+                failed.len(),
+                total,
+                if total == 1 { "" } else { "s" }
+            )
+            .error()
+            .bold(),
+            failed.join(", ")
+        );
+    }
+}
+
+/// Most lines of a failed Docker step's output to show.
+const DOCKER_OUTPUT_LINES: usize = 20;
+
+/// Prints how the action's time split into phases, e.g. "Phases: install
+/// 6m 0s (67%), test 3m 0s (33%)", with the longest phase bold.
+fn print_phases(phases: &[PhaseTiming]) {
+    let total: u64 = phases.iter().map(|timing| timing.millis).sum();
+    let longest = phases.iter().map(|timing| timing.millis).max();
+    let parts: Vec<String> = phases
+        .iter()
+        .map(|timing| {
+            let part = format!(
+                "{} {} ({}%)",
+                timing.phase,
+                format_duration(timing.millis),
+                (timing.millis as f64 / total.max(1) as f64 * 100.0).round()
+            );
+            if Some(timing.millis) == longest {
+                part.bold().to_string()
+            } else {
+                part
+            }
+        })
+        .collect();
+    println!("  {} {}", "Phases:".cyan(), parts.join(", "));
+}
+
+/// Prints the failed Dockerfile instruction and the end of its output.
+fn print_docker_failure(failure: &DockerFailure) {
+    let label = match (&failure.stage, &failure.step) {
+        (Some(stage), Some(step)) => format!("[{} {}] ", stage, step),
+        (None, Some(step)) => format!("[{}] ", step),
+        (Some(stage), None) => format!("[{}] ", stage),
+        (None, None) => String::new(),
+    };
+    let mut line = format!("{}{}", label, failure.instruction.bold());
+    match failure.exit_code {
+        Some(code) => line.push_str(&format!(" failed with exit code {}", code)),
+        None => line.push_str(&format!(" failed: {}", failure.error)),
+    }
+    if let Some(dockerfile_line) = failure.dockerfile_line {
+        line.push_str(&format!(" (Dockerfile:{})", dockerfile_line));
+    }
+    println!("  {} {}", "Docker:".error().bold(), line);
+
+    let skipped = failure.output.len().saturating_sub(DOCKER_OUTPUT_LINES);
+    if skipped > 0 {
+        println!(
+            "    {}",
+            format!("… {} earlier lines of this step", skipped).dim()
+        );
+    }
+    for output in &failure.output[skipped..] {
+        println!("    {} {}", "│".dim(), output);
+    }
+}
+
+/// Prints what Gradle and Maven said went wrong.
+fn print_jvm_summary(summary: &JvmSummary) {
+    for failure in &summary.gradle {
+        println!("  {} {}", "Gradle:".error().bold(), failure.message);
+        for cause in &failure.causes {
+            println!("    {} {}", "›".dim(), cause);
+        }
+    }
+
+    let Some(maven) = &summary.maven else {
+        return;
+    };
+    let failed: Vec<&str> = maven.failed_modules().map(|m| m.name.as_str()).collect();
+    let skipped = maven
+        .modules
+        .iter()
+        .filter(|m| m.status == ModuleStatus::Skipped)
+        .count();
+    if !failed.is_empty() {
+        let mut line = format!(
+            "{} of {} modules failed: {}",
+            failed.len(),
+            maven.modules.len(),
+            failed.join(", ")
+        );
+        if skipped > 0 {
+            line.push_str(&format!(" ({} skipped)", skipped));
+        }
+        println!("  {} {}", "Maven:".error().bold(), line);
+    }
+    for goal in &maven.failed_goals {
+        println!(
+            "  {} {} failed on {}: {}",
+            "Maven:".error().bold(),
+            goal.goal,
+            goal.project.bold(),
+            goal.reason
+        );
+    }
+}
+
+/// Prints the shell command a failed `run` step ran, highlighted.
+fn print_command(action: &FailedAction) {
+    let lines = action.command_lines();
+    if lines.is_empty() {
+        return;
+    }
+    println!();
+    for line in lines {
+        println!("  {} {}", "│".dim(), highlight_shell(line));
+    }
+}
+
+/// Displays a processed action log.
+///
+/// Shows either the full log, the last N lines, or the default smart
+/// summary with the build exit zone.
+fn print_action_logs(
+    action_logs: &ActionLogs,
+    options: &BuildOptions,
+    checkout: Option<&Checkout>,
+) {
+    let auto_save_path = &action_logs.auto_save_path;
+    let filtered_logs = &action_logs.analysis.text;
+    let error_groups = &action_logs.analysis.errors;
+
+    if let Some(auto_save_path) = auto_save_path {
+        println!(
+            "\n  {}",
+            format!("Auto-saved full logs to: {}", auto_save_path).dim()
+        );
+    }
+    if let Some(output_path) = &options.output_file {
+        println!(
+            "  {}",
+            format!("Logs also saved to: {}", output_path).success()
+        );
+    }
+
+    if action_logs.analysis.lines_collapsed > 0 {
+        println!(
+            "  {}",
+            format!(
+                "Collapsed {} repeated/progress lines (use --no-collapse to keep them)",
+                action_logs.analysis.lines_collapsed
+            )
+            .dim()
+        );
+    }
+    if action_logs.analysis.lines_truncated + action_logs.analysis.binary_lines > 0 {
+        let mut skipped = Vec::new();
+        if action_logs.analysis.lines_truncated > 0 {
+            skipped.push(format!(
+                "cut {} line{} longer than {} KB",
+                action_logs.analysis.lines_truncated,
+                if action_logs.analysis.lines_truncated == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                logs::MAX_LINE_BYTES / 1024
+            ));
+        }
+        if action_logs.analysis.binary_lines > 0 {
+            skipped.push(format!(
+                "hid {} line{} of binary data",
+                action_logs.analysis.binary_lines,
+                if action_logs.analysis.binary_lines == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            ));
+        }
+        println!(
+            "  {}",
+            format!("{} (the saved log has them whole)", skipped.join(", ")).dim()
+        );
+    }
+
+    if let Some((kept, total)) = action_logs.analysis.time_window {
+        println!(
+            "  {}",
+            format!("Time window: {} of {} lines", kept, total).cyan()
+        );
+    }
+
+    if let (Some(filter_text), Some((matched, total))) =
+        (&options.filter, action_logs.analysis.filter_counts)
+    {
+        if matched == 0 {
+            println!(
+                "  {}",
+                format!("No lines matching filter: '{}'", filter_text).warning()
+            );
+        } else {
+            println!(
+                "  {}",
+                format!("Filter '{}': {} of {} lines", filter_text, matched, total).cyan()
+            );
+        }
+    }
+
+    if let Some(summary) = &action_logs.analysis.findings.packages {
+        print_package_summary(summary);
+    }
+    if let Some(summary) = &action_logs.analysis.findings.jvm {
+        print_jvm_summary(summary);
+    }
+    if let Some(failure) = &action_logs.analysis.findings.docker {
+        print_docker_failure(failure);
+    }
+    if action_logs.analysis.findings.phases.len() > 1 {
+        print_phases(&action_logs.analysis.findings.phases);
+    }
+    if let (Some(name), Some((matched, total))) =
+        (&options.package, action_logs.analysis.package_counts)
+    {
+        if matched == 0 {
+            let names: Vec<&str> = action_logs
+                .analysis
+                .findings
+                .packages
+                .iter()
+                .flat_map(|summary| summary.packages.iter())
+                .map(|package| package.name.as_str())
+                .collect();
+            let known = if names.is_empty() {
+                "no packages found in this log".to_string()
+            } else {
+                format!("packages in this log: {}", names.join(", "))
+            };
+            println!(
+                "  {}",
+                format!("No lines from package '{}' ({})", name, known).warning()
+            );
+        } else {
+            println!(
+                "  {}",
+                format!("Package '{}': {} of {} lines", name, matched, total).cyan()
+            );
+        }
+    }
+
+    let total_lines = filtered_logs.lines().count();
+    println!(
+        "  {}",
+        format!(
+            "Total: {} lines, {} KB",
+            total_lines,
+            action_logs.size_bytes / 1024
+        )
+        .dim()
+    );
+    if let Some(kb) = options.tail_fetch {
+        println!(
+            "  {}",
+            format!(
+                "Partial log: last {} KB only, line numbers are relative",
+                kb
+            )
+            .warning()
+        );
+    }
+
+    if options.full_logs {
+        // Show full logs
+        println!("\n  {}", "=== FULL LOG OUTPUT ===".warning().bold());
+        if options.highlights.is_empty() {
+            println!("{}", filtered_logs);
+        } else {
+            for line in filtered_logs.lines() {
+                println!(
+                    "{}",
+                    highlight_line(line, &options.highlights, |s| s.normal())
+                );
+            }
+        }
+    } else if let Some(n) = options.tail_lines {
+        // Show only last N lines
+        let lines: Vec<_> = filtered_logs.lines().collect();
+        let start = if lines.len() > n { lines.len() - n } else { 0 };
+        println!(
+            "\n  {}",
+            format!("=== LAST {} LINES ===", n).warning().bold()
+        );
+        for line in lines.iter().skip(start) {
+            println!(
+                "{}",
+                highlight_line(line, &options.highlights, |s| s.normal())
+            );
+        }
+    } else {
+        // DEFAULT: Smart detection + last 50 lines
+        println!("\n  {}", "=== SMART ERROR DETECTION ===".blue().bold());
+
+        let error_line_numbers: HashSet<usize> = error_groups
+            .iter()
+            .flat_map(|group| group.line_numbers.iter().copied())
+            .collect();
+
+        if !error_groups.is_empty() {
+            let limit = options.max_errors.unwrap_or(usize::MAX);
+            println!(
+                "  Found {} error pattern(s), {} matching line(s):",
+                error_groups.len(),
+                error_line_numbers.len()
+            );
+            for group in error_groups.iter().take(limit) {
+                let label = if group.count() > 1 {
+                    format!("[{} ×{}]", group.category, group.count())
+                } else {
+                    format!("[{}]", group.category)
+                };
+                let label = match group.severity {
+                    Severity::Error => label.error().bold(),
+                    Severity::Warning => label.warning().bold(),
+                    Severity::Hint => label.cyan(),
+                };
+
+                // Highlight with background color for better visibility
+                println!(
+                    "  {} {} {} {}",
+                    label,
+                    format!("({}, {:.0}%)", group.severity, group.confidence * 100.0).dim(),
+                    format!("Line {}:", group.first.line_number).error().bold(),
+                    group.first.line.trim().highlight()
+                );
+                if group.count() > 1 {
+                    println!(
+                        "    {} {}",
+                        format!("last at line {}:", group.last.line_number).error(),
+                        group.last.line.trim()
+                    );
+                }
+                let excerpt = checkout.and_then(|checkout| {
+                    checkout.excerpt(&source::locate(group.first.line.trim())?, 2)
+                });
+                if let Some(excerpt) = excerpt {
+                    print_excerpt(&excerpt);
+                }
+
+                let suggestion = options
+                    .patterns
+                    .iter()
+                    .find(|p| p.id == group.pattern_id)
+                    .and_then(|pattern| options.suggestions.suggest(pattern, &group.first.line));
+                if let Some(suggestion) = suggestion {
+                    println!("  {} Suggestion: {}", "💡".warning(), suggestion);
+                }
+            }
+
+            let hidden = error_groups.len().saturating_sub(limit);
+            if hidden > 0 {
+                println!(
+                    "  {}",
+                    format!(
+                        "… {} more pattern(s) not shown (use --all-errors or --max-errors N)",
+                        hidden
+                    )
+                    .dim()
+                );
+            }
+        } else {
+            println!("  {}", "No specific error patterns detected".warning());
+        }
+
+        // Always show the exit zone, by default the last 50 lines
+        let show_lines = options.exit_zone_lines.unwrap_or(EXIT_ZONE_LINES);
+        let lines: Vec<_> = filtered_logs.lines().collect();
+        let line_numbers = &action_logs.analysis.line_numbers;
+        let mut start = lines.len().saturating_sub(show_lines);
+        if options.smart_window {
+            let earliest = error_groups
+                .iter()
+                .map(|group| group.first.line_number)
+                .min();
+            if let Some(earliest) = earliest {
+                start = start.min(line_numbers.partition_point(|&n| n < earliest));
+            }
+        }
+
+        println!(
+            "\n  {}",
+            format!(
+                "=== LAST {} LINES (BUILD EXIT ZONE) ===",
+                lines.len() - start
+            )
+            .warning()
+            .bold()
+        );
+        for (i, line) in lines.iter().skip(start).enumerate() {
+            let line_num = line_numbers
+                .get(start + i)
+                .copied()
+                .unwrap_or(start + i + 1);
+            let trimmed = line.trim();
+
+            // Check if this line was identified as an error in smart detection
+            let is_detected_error = error_line_numbers.contains(&line_num);
+
+            // Highlight error-like lines with enhanced visibility
+            if is_detected_error {
+                // Lines detected by smart detection get special highlighting
+                println!(
+                    "{:5} {} {}",
+                    format!("{}", line_num).error().bold(),
+                    "►".error().bold(),
+                    trimmed.highlight()
+                );
+            } else if trimmed.to_lowercase().contains("error")
+                || trimmed.to_lowercase().contains("failed")
+                || trimmed.contains("✗")
+                || trimmed.contains("FAIL")
+            {
+                println!(
+                    "{:5} │ {}",
+                    line_num,
+                    highlight_line(trimmed, &options.highlights, |s| s.error().bold())
+                );
+            } else if trimmed.to_lowercase().contains("warn") {
+                println!(
+                    "{:5} │ {}",
+                    line_num,
+                    highlight_line(trimmed, &options.highlights, |s| s.warning())
+                );
+            } else {
+                println!(
+                    "{:5} │ {}",
+                    line_num,
+                    highlight_line(trimmed, &options.highlights, |s| s.dim())
+                );
+            }
+        }
+
+        // Help text for next steps
+        println!("\n  {}", "=== DIDN'T FIND YOUR ERROR? ===".cyan().bold());
+        println!("  {}", "• Use --full to see complete logs".cyan());
+        println!("  {}", "• Use --tail 100 to see more context".cyan());
+        if let Some(auto_save_path) = auto_save_path {
+            println!(
+                "  {}",
+                format!("• Full logs saved at: {}", auto_save_path).cyan()
+            );
+        }
+        println!(
+            "  {}",
+            "• For AI: If error not found above, rerun with --full flag"
+                .cyan()
+                .bold()
+        );
+    }
+}
+
+/// Analyzes a local log file, or stdin for `-`, and prints the detected
+/// errors, parsed failures, and the end of the log like `cdb build` does.
+///
+/// CircleCI's JSON log format is decoded, so a saved `output_url` response
+/// works as well as plain text. Output of `circleci local execute` is split
+/// into its steps first, see [`analyze_local_run`].
+///
+/// # Errors
+///
+/// Returns an error if the file or stdin can't be read.
+fn analyze_file(file: &str, options: &BuildOptions) -> Result<()> {
+    let bytes = read_log_input(file)?;
+    let clean_logs = clean_log_input(&bytes);
+    let name = if file == "-" { "<stdin>" } else { file };
+    if let Some(run) = local::parse(&clean_logs) {
+        return analyze_local_run(name, &run, options);
+    }
+    let mut action_logs = analyze_log(&clean_logs, logs::first_timestamp(&clean_logs), options);
+    action_logs.size_bytes = bytes.len();
+    let reproduce = repro::reproduce_commands(&action_logs.analysis.findings.failed_tests);
+
+    if options.format == OutputFormat::Json {
+        return print_json(&serde_json::json!({
+            "file": name,
+            "lines": action_logs.analysis.text.lines().count(),
+            "errors": action_logs.analysis.errors,
+            "failed_tests": action_logs.analysis.findings.failed_tests,
+            "reproduce": reproduce,
+            "packages": action_logs.analysis.findings.packages,
+            "jvm": action_logs.analysis.findings.jvm,
+            "docker": action_logs.analysis.findings.docker,
+            "regressions": action_logs.analysis.findings.regressions,
+            "phases": action_logs.analysis.findings.phases,
+        }));
+    }
+
+    print_header(&format!("Log Analysis: {}", name));
+    let checkout = (!options.no_source)
+        .then(git::toplevel)
+        .and_then(Result::ok)
+        .map(Checkout::new);
+    print_action_logs(&action_logs, options, checkout.as_ref());
+    if !reproduce.is_empty() {
+        print_reproduce(&reproduce, action_logs.analysis.findings.failed_tests.len());
+    }
+    Ok(())
+}
+
+/// Reads a log file, or stdin for `-`.
+///
+/// # Errors
+///
+/// Returns an error if the file or stdin can't be read.
+fn read_log_input(file: &str) -> Result<Vec<u8>> {
+    if file == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .context("Failed to read the log from stdin")?;
+        Ok(bytes)
+    } else {
+        std::fs::read(file).with_context(|| format!("Failed to read {}", file))
+    }
+}
+
+/// Decodes a log read by [`read_log_input`], from CircleCI's JSON format
+/// if it's in it, and strips ANSI codes.
+fn clean_log_input(bytes: &[u8]) -> String {
+    let logs = download::decode_log_body(&String::from_utf8_lossy(bytes));
+    logs::strip_ansi(&logs).into_owned()
+}
+
+/// Lists the steps of a `circleci local execute` run and analyzes each
+/// failed one like a failed action of a remote build.
+///
+/// # Errors
+///
+/// Returns an error if the JSON output can't be printed.
+fn analyze_local_run(name: &str, run: &LocalRun, options: &BuildOptions) -> Result<()> {
+    let failed_steps = run.failed_steps();
+    let failed: Vec<(FailedAction, ActionLogs)> = failed_steps
+        .iter()
+        .map(|step| {
+            let action_logs =
+                analyze_log(&step.output, logs::first_timestamp(&step.output), options);
+            let action = FailedAction {
+                step: step.name.clone(),
+                name: step.name.clone(),
+                command: step.command.clone(),
+                errors: action_logs.analysis.errors.clone(),
+                packages: action_logs.analysis.findings.packages.clone(),
+                jvm: action_logs.analysis.findings.jvm.clone(),
+                docker: action_logs.analysis.findings.docker.clone(),
+                failed_tests: action_logs.analysis.findings.failed_tests.clone(),
+                regressions: action_logs.analysis.findings.regressions.clone(),
+                phases: action_logs.analysis.findings.phases.clone(),
+                ..FailedAction::default()
+            };
+            (action, action_logs)
+        })
+        .collect();
+    let failed_tests: Vec<FailedTest> = failed
+        .iter()
+        .flat_map(|(action, _)| action.failed_tests.iter().cloned())
+        .collect();
+    let reproduce = repro::reproduce_commands(&failed_tests);
+
+    if options.format == OutputFormat::Json {
+        let actions: Vec<&FailedAction> = failed.iter().map(|(action, _)| action).collect();
+        return print_json(&serde_json::json!({
+            "file": name,
+            "local": true,
+            "failed": run.failed,
+            "steps": run.steps,
+            "failed_actions": actions,
+            "reproduce": reproduce,
+        }));
+    }
+
+    print_header(&format!("Local Run: {}", name));
+    for step in &run.steps {
+        if failed_steps
+            .iter()
+            .any(|failed| std::ptr::eq(*failed, step))
+        {
+            let code = step
+                .exit_code
+                .map(|code| format!(" (exit code {})", code))
+                .unwrap_or_default();
+            println!("  {} {}{}", "✗".error().bold(), step.name, code.dim());
+        } else {
+            println!("  {} {}", "✓".success(), step.name);
+        }
+    }
+    if failed.is_empty() {
+        println!();
+        print_success(&format!("All {} steps passed", run.steps.len()));
+        return Ok(());
+    }
+
+    let checkout = (!options.no_source)
+        .then(git::toplevel)
+        .and_then(Result::ok)
+        .map(Checkout::new);
+    for (action, action_logs) in &failed {
+        println!("\n{} {}", "▸".error().bold(), action.step.bold());
+        print_command(action);
+        print_action_logs(action_logs, options, checkout.as_ref());
+    }
+    if !reproduce.is_empty() {
+        print_reproduce(&reproduce, failed_tests.len());
+    }
+    Ok(())
+}
+
+/// Downloads the logs of several failed actions concurrently.
+///
+/// Each download gets its own progress bar. Results come back in the same
+/// order as `actions`, with `None` for actions that have no output URL.
+async fn fetch_action_logs(
+    client: &CircleClient,
+    actions: &[&Action],
+    tail_fetch: Option<u64>,
+    progress: &Progress,
+) -> Vec<Option<Result<String>>> {
+    let downloads = actions.iter().map(|action| async move {
+        let output_url = action.output_url.as_deref()?;
+        let bar = progress.download(action.name.clone());
+        let result = client
+            .get_logs_with_progress(
+                output_url,
+                tail_fetch.map(|kb| kb * 1024),
+                |received, total| update_download(&bar, received, total),
+            )
+            .await;
+        bar.finish_and_clear();
+        if result.is_ok() {
+            progress.println(download_summary(&action.name, &bar).dim().to_string());
+        }
+        Some(result)
+    });
+
+    futures::future::join_all(downloads).await
+}
+
+/// Describes a finished download, e.g. "Fetched Run tests: 48.2 MB in
+/// 12.4s (3.9 MB/s)".
+fn download_summary(name: &str, bar: &ProgressBar) -> String {
+    let bytes = bar.position();
+    let elapsed = bar.elapsed();
+    let took = if elapsed.as_secs() < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format_duration(elapsed.as_millis() as u64)
+    };
+    let mut summary = format!("Fetched {}: {} in {}", name, format_bytes(bytes), took);
+    // A rate over a cached or tiny response says nothing
+    if elapsed.as_secs_f64() >= 0.5 {
+        let rate = (bytes as f64 / elapsed.as_secs_f64()) as u64;
+        summary.push_str(&format!(" ({}/s)", format_bytes(rate)));
+    }
+    summary
+}
+
+/// Analyzes GitHub PR status and CircleCI checks.
+///
+/// Fetches and displays all CircleCI-related checks for a GitHub pull request.
+/// This function requires the GitHub CLI (`gh`) to be installed and authenticated.
+///
+/// # Arguments
+///
+/// * `pr_input` - Either a PR number (e.g., "123") or full GitHub PR URL
+/// * `repo` - Optional repository in format "org/repo". If not provided,
+///   attempts to detect from current directory
+/// * `analyze` - With `--analyze`, options for analyzing every failed
+///   CircleCI job; see [`analyze_failed_jobs`]
+/// * `progress` - Progress display for `gh` calls
+///
+/// # Returns
+///
+/// A `Result` indicating success or containing an error.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The PR URL format is invalid
+/// * The repository cannot be determined and isn't specified
+/// * The GitHub CLI is not installed or not authenticated
+/// * The PR doesn't exist or is inaccessible
+/// * JSON parsing fails for PR details
+///
+/// # Examples
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # async fn example() -> Result<()> {
+/// let progress = Progress::new(false);
+///
+/// // Using PR number with explicit repo
+/// analyze_pr(Some("123".to_string()), Some("myorg/myrepo".to_string()), None, &progress).await?;
+///
+/// // Using full PR URL
+/// analyze_pr(
+///     Some("https://github.com/myorg/myrepo/pull/123".to_string()),
+///     None,
+///     None,
+///     &progress,
+/// ).await?;
+///
+/// // Auto-detect PR and repo from current directory
+/// analyze_pr(None, None, None, &progress).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # See Also
+///
+/// * [`analyze_build`] - Analyze specific failed builds from PR checks
+async fn analyze_pr(
+    pr_input: Option<String>,
+    repo: Option<String>,
+    analyze: Option<&BuildOptions>,
+    progress: &Progress,
+) -> Result<()> {
+    print_header("Analyzing GitHub PR");
+
+    // Check if gh CLI is available
+    if !cli::command_exists("gh") {
+        eprintln!(
+            "{}",
+            "Error: GitHub CLI (gh) is not installed or not in PATH".error()
+        );
+        eprintln!("\nTo use the 'pr' command, you need to install GitHub CLI:");
+        eprintln!("  • macOS: brew install gh");
+        eprintln!("  • Linux: See https://cli.github.com/");
+        eprintln!("  • Windows: winget install GitHub.cli");
+        eprintln!("\nThen authenticate with: gh auth login");
+        eprintln!("\nNote: The 'build' command works without GitHub CLI");
+        bail!("GitHub CLI (gh) is required for PR analysis");
+    }
+
+    // Determine PR number - auto-detect if not provided
+    let pr_number = if let Some(input) = pr_input {
+        // Parse PR number from URL or use directly
+        if input.contains("github.com") {
+            // Extract from URL like https://github.com/org/repo/pull/123
+            input
+                .split('/')
+                .next_back()
+                .context("Invalid PR URL")?
+                .to_string()
+        } else {
+            input
+        }
+    } else {
+        // Auto-detect current PR using gh CLI
+        let spinner = progress.spinner("Auto-detecting current PR...");
+        let output = std::process::Command::new("gh")
+            .args(["pr", "view", "--json", "number", "-q", ".number"])
+            .output();
+        spinner.finish_and_clear();
+        let output = output
+            .context("Failed to run 'gh pr view'. Is GitHub CLI installed and authenticated?")?;
+
+        let pr_num = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if pr_num.is_empty() {
+            bail!("No PR found for current branch. Create a PR first or specify PR number explicitly.");
+        }
+        pr_num
+    };
+
+    print_info(&format!("PR Number: {}", pr_number));
+
+    // Determine repository
+    let repository = if let Some(r) = repo {
+        r
+    } else {
+        // Try to get from current directory using gh
+        let output = std::process::Command::new("gh")
+            .args([
+                "repo",
+                "view",
+                "--json",
+                "nameWithOwner",
+                "-q",
+                ".nameWithOwner",
+            ])
+            .output();
+
+        match output {
+            Ok(out) => String::from_utf8_lossy(&out.stdout).trim().to_string(),
+            Err(_) => {
+                bail!("Could not determine repository. Please specify with --repo org/repo");
+            }
+        }
+    };
+
+    if repository.is_empty() {
+        bail!("Could not determine repository. Please specify with --repo org/repo");
+    }
+
+    print_info(&format!("Repository: {}", repository));
+
+    // Get PR checks using gh CLI
+    let spinner = progress.spinner("Fetching PR status checks...");
+    let checks_output = std::process::Command::new("gh")
+        .args(["pr", "checks", &pr_number, "--repo", &repository])
+        .output();
+    spinner.finish_and_clear();
+    let checks_output = checks_output
+        .context("Failed to run 'gh pr checks'. Is GitHub CLI installed and authenticated?")?;
+
+    // gh pr checks returns non-zero when there are failed checks, but still outputs data
+    let checks = if !checks_output.stdout.is_empty() {
+        String::from_utf8_lossy(&checks_output.stdout)
+    } else if !checks_output.stderr.is_empty() {
+        // Sometimes gh outputs to stderr even on success
+        String::from_utf8_lossy(&checks_output.stderr)
+    } else {
+        bail!("No output from gh pr checks command");
+    };
+
+    print_header("PR Status Checks");
+
+    // Parse and display CircleCI-specific checks
+    let mut circleci_checks = Vec::new();
+    let mut failed_checks = Vec::new();
+
+    for line in checks.lines() {
+        if line.contains("circleci") || line.contains("CircleCI") {
+            circleci_checks.push(line);
+            if line.contains("fail") || line.contains("✗") {
+                failed_checks.push(line);
+            }
+        }
+    }
+
+    if circleci_checks.is_empty() {
+        println!("{}", "No CircleCI checks found on this PR".warning());
+        println!("\nAll checks:");
+        println!("{}", checks);
+    } else {
+        println!("Found {} CircleCI check(s):", circleci_checks.len());
+        println!();
+
+        for check in &circleci_checks {
+            if check.contains("fail") || check.contains("✗") {
+                println!("{}", check.error());
+            } else if check.contains("pass") || check.contains("✓") {
+                println!("{}", check.success());
+            } else if check.contains("pending") || check.contains("○") {
+                println!("{}", check.warning());
+            } else {
+                println!("{}", check);
+            }
+        }
+
+        if !failed_checks.is_empty() {
+            print_header("Failed CircleCI Checks");
+            println!("{}", "Extract URLs from failed checks to debug:".cyan());
+
+            // Try to extract CircleCI URLs from the output
+            let url_regex = Regex::new(r"https://circleci\.com/gh/[^\s]+/\d+")?;
+            let mut failed_jobs: Vec<(String, String)> = Vec::new();
+            for check in &failed_checks {
+                if let Some(url_match) = url_regex.find(check) {
+                    let url = url_match.as_str();
+                    let name = check.split('\t').next().unwrap_or("Unknown check");
+                    println!("\n{} {}", "•".error(), name.error());
+                    println!("  Debug with: {}", format!("cdb build {}", url).cyan());
+                    failed_jobs.push((name.to_string(), url.to_string()));
+                }
+            }
+
+            if let (Some(options), false) = (analyze, failed_jobs.is_empty()) {
+                analyze_failed_jobs(&failed_jobs, options, progress).await;
+            }
+        }
+    }
+
+    // Also show PR details
+    println!();
+    print_header("PR Details");
+
+    let pr_details = std::process::Command::new("gh")
+        .args([
+            "pr",
+            "view",
+            &pr_number,
+            "--repo",
+            &repository,
+            "--json",
+            "state,title,author,url",
+        ])
+        .output();
+
+    if let Ok(output) = pr_details {
+        if output.status.success() {
+            let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+
+            if let Some(title) = json.get("title").and_then(|v| v.as_str()) {
+                print_info(&format!("Title: {}", title));
+            }
+            if let Some(state) = json.get("state").and_then(|v| v.as_str()) {
+                print_info(&format!("State: {}", state));
+            }
+            if let Some(author) = json
+                .get("author")
+                .and_then(|v| v.get("login"))
+                .and_then(|v| v.as_str())
+            {
+                print_info(&format!("Author: {}", author));
+            }
+            if let Some(url) = json.get("url").and_then(|v| v.as_str()) {
+                print_info(&format!("URL: {}", url.blue().underline()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Blocks until none of a PR's checks are pending, using
+/// `gh pr checks --watch`.
+///
+/// # Returns
+///
+/// The title, body, and link of the notification to show once the checks
+/// are printed: whether they all passed, and the PR's URL.
+///
+/// # Errors
+///
+/// Returns an error if the GitHub CLI isn't installed or can't be run.
+fn watch_pr(
+    pr: Option<&str>,
+    repo: Option<&str>,
+    progress: &Progress,
+) -> Result<(String, String, String)> {
+    if !cli::command_exists("gh") {
+        bail!("GitHub CLI (gh) is required to watch a PR's checks");
+    }
+    let mut target: Vec<&str> = pr.into_iter().collect();
+    if let Some(repo) = repo {
+        target.extend(["--repo", repo]);
+    }
+
+    let spinner = progress.spinner("Waiting for the PR's checks to finish...");
+    let status = std::process::Command::new("gh")
+        .args(["pr", "checks"])
+        .args(&target)
+        .arg("--watch")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+    spinner.finish_and_clear();
+    // Exits non-zero when a check failed
+    let passed = status
+        .context(
+            "Failed to run 'gh pr checks --watch'. Is GitHub CLI installed and authenticated?",
+        )?
+        .success();
+
+    let view = std::process::Command::new("gh")
+        .args(["pr", "view"])
+        .args(&target)
+        .args(["--json", "number,url"])
+        .output()
+        .ok()
+        .and_then(|output| serde_json::from_slice::<serde_json::Value>(&output.stdout).ok());
+    let number = view
+        .as_ref()
+        .and_then(|pr| pr.get("number"))
+        .and_then(|number| number.as_u64());
+    let url = view
+        .as_ref()
+        .and_then(|pr| pr.get("url"))
+        .and_then(|url| url.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let pr_label = number.map_or_else(|| "PR".to_string(), |number| format!("PR #{}", number));
+    Ok(if passed {
+        (
+            format!("cdb: {} checks passed", pr_label),
+            "Every check finished successfully".to_string(),
+            url,
+        )
+    } else {
+        (
+            format!("cdb: {} checks failed", pr_label),
+            "Run `cdb pr` to see which".to_string(),
+            url,
+        )
+    })
+}
+
+/// Analyzes the failed jobs of a PR concurrently and prints one report:
+/// each job's failed steps and top errors, then the distinct failures
+/// across jobs by fingerprint, with the jobs each one hit.
+///
+/// `jobs` pairs each check's name with its CircleCI URL. A job that can't
+/// be analyzed is reported and skipped, so one bad job doesn't hide the
+/// others.
+async fn analyze_failed_jobs(
+    jobs: &[(String, String)],
+    options: &BuildOptions,
+    progress: &Progress,
+) {
+    const MAX_ERRORS_PER_ACTION: usize = 3;
+
+    let client = match circle_client(options.no_cache) {
+        Ok(client) => client,
+        Err(e) => {
+            print_warning(&format!("Couldn't analyze the failed jobs: {:#}", e));
+            return;
+        }
+    };
+    let spinner = progress.spinner("Analyzing the failed jobs...");
+    let reports = futures::future::join_all(
+        jobs.iter()
+            .map(|(_, url)| job_report(&client, url, options, progress)),
+    )
+    .await;
+    spinner.finish_and_clear();
+
+    print_header("Failed Jobs");
+    for ((name, url), report) in jobs.iter().zip(&reports) {
+        println!("\n{} {} {}", "▸".error().bold(), name.bold(), url.dim());
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                print_error(&format!("  Couldn't analyze the job: {:#}", e));
+                continue;
+            }
+        };
+        if report.failed_actions.is_empty() {
+            println!("  {}", "No failed steps found".dim());
+        }
+        for action in &report.failed_actions {
+            let container = action
+                .container_label()
+                .map(|label| format!(" ({})", label))
+                .unwrap_or_default();
+            print_error(&format!("  {}{}", action.step, container));
+            if let Some(error) = &action.fetch_error {
+                println!("    Failed to fetch logs: {}", error);
+            }
+            for group in action.errors.iter().take(MAX_ERRORS_PER_ACTION) {
+                println!(
+                    "    {} {}",
+                    format!("[{}]", group.category).warning(),
+                    group.first.line.trim()
+                );
+            }
+            if action.errors.len() > MAX_ERRORS_PER_ACTION {
+                println!(
+                    "    {}",
+                    format!(
+                        "... {} more; run cdb build for the rest",
+                        action.errors.len() - MAX_ERRORS_PER_ACTION
+                    )
+                    .dim()
+                );
+            }
+        }
+    }
+
+    let analyzed: Vec<&BuildReport> = reports.iter().filter_map(|r| r.as_ref().ok()).collect();
+    let shared = shared_failures(analyzed.iter().copied());
+    if shared.is_empty() {
+        return;
+    }
+    print_header("Failures Across Jobs");
+    for failure in &shared {
+        let marker = if failure.jobs.len() > 1 {
+            "●".error().bold()
+        } else {
+            "•".normal()
+        };
+        println!(
+            "{} {} {} {}",
+            marker,
+            format!("[{}]", failure.category).warning(),
+            failure.line.trim(),
+            failure.fingerprint.dim()
+        );
+        println!("  in {}", failure.jobs.join(", ").cyan());
+    }
+}
+
+/// Fetches a job and analyzes its failed steps' logs without printing, for
+/// reports that merge several jobs.
+///
+/// Only the logs are analyzed; suspects, owners, history, and the other
+/// lookups of [`analyze_build`] are left out.
+///
+/// # Errors
+///
+/// Returns an error if the URL can't be parsed, the build can't be
+/// fetched, or a log can't be saved.
+async fn job_report(
+    client: &CircleClient,
+    url: &str,
+    options: &BuildOptions,
+    progress: &Progress,
+) -> Result<BuildReport> {
+    let (org, project, build_num) = parse_circleci_url(url)?;
+    let build = client.get_build(&org, &project, build_num).await?;
+    let mut report = BuildReport::new(&org, &project, &build);
+
+    let failed: Vec<(&Step, &Action)> = build
+        .steps
+        .iter()
+        .flat_map(|step| step.actions.iter().map(move |action| (step, action)))
+        .filter(|(_, action)| action.failed.unwrap_or(false))
+        .collect();
+    let failed_actions: Vec<&Action> = failed.iter().map(|(_, action)| *action).collect();
+    let fetched = fetch_action_logs(client, &failed_actions, options.tail_fetch, progress).await;
+
+    for ((step, action), logs) in failed.iter().zip(fetched) {
+        let mut failed_action = FailedAction::new(step, action);
+        match logs {
+            Some(Ok(logs)) => {
+                let action_logs = process_action_logs(&logs, &build, step, action, options)?;
+                failed_action = FailedAction {
+                    log_path: action_logs.auto_save_path,
+                    ..action_logs.analysis.failed_action(step, action)
+                };
+            }
+            Some(Err(e)) => failed_action.fetch_error = Some(format!("{:#}", e)),
+            None => {}
+        }
+        report.failed_actions.push(failed_action);
+    }
+
+    report.reproduce = FailureSummary::new(&build, report.failed_actions.clone()).reproduce;
+    Ok(report)
+}
+
+/// Options for the `bulk` command.
+#[derive(Debug)]
+struct BulkOptions {
+    /// File with one URL per line; stdin when unset.
+    file: Option<PathBuf>,
+    /// Directory the reports are written to.
+    output: PathBuf,
+    /// How many builds are analyzed at once.
+    jobs: usize,
+    /// Print the summary rows as JSON.
+    json: bool,
+}
+
+/// Analyzes every build listed in a file or on stdin, a few at a time,
+/// then prints one row per build: its status, job, and first failed step
+/// with its top error. Each report is written to the output directory as
+/// Markdown and JSON.
+///
+/// Builds are analyzed like [`job_report`] does, logs only. A build that
+/// can't be analyzed gets a row with the reason instead of stopping the
+/// others.
+///
+/// # Errors
+///
+/// Returns an error if the list can't be read or is empty, or the output
+/// directory can't be created.
+async fn analyze_bulk(
+    bulk: &BulkOptions,
+    options: &BuildOptions,
+    progress: &Progress,
+) -> Result<()> {
+    let list = match &bulk.file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => {
+            let mut list = String::new();
+            std::io::stdin()
+                .read_to_string(&mut list)
+                .context("Failed to read the URLs from stdin")?;
+            list
+        }
+    };
+    let mut urls: Vec<&str> = Vec::new();
+    for line in list.lines().map(str::trim) {
+        if !line.is_empty() && !line.starts_with('#') && !urls.contains(&line) {
+            urls.push(line);
+        }
+    }
+    if urls.is_empty() {
+        bail!("No build URLs to analyze");
+    }
+    std::fs::create_dir_all(&bulk.output)
+        .with_context(|| format!("Failed to create {}", bulk.output.display()))?;
+
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner(format!("Analyzing {} builds...", urls.len()));
+    let results: Vec<Result<(BuildReport, PathBuf)>> = futures::stream::iter(&urls)
+        .map(|url| {
+            let client = &client;
+            async move {
+                let report = job_report(client, url, options, progress).await?;
+                let name = format!("{}-{}-{}", report.org, report.project, report.build_num);
+                let path = bulk.output.join(format!("{}.md", name));
+                std::fs::write(&path, markdown::render(&report))
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                let json_path = bulk.output.join(format!("{}.json", name));
+                std::fs::write(&json_path, serde_json::to_string_pretty(&report)?)
+                    .with_context(|| format!("Failed to write {}", json_path.display()))?;
+                Ok((report, path))
+            }
+        })
+        .buffered(bulk.jobs.max(1))
+        .collect()
+        .await;
+    spinner.finish_and_clear();
+
+    if bulk.json {
+        let rows: Vec<serde_json::Value> = urls
+            .iter()
+            .zip(&results)
+            .map(|(url, result)| match result {
+                Ok((report, path)) => {
+                    let action = report.failed_actions.first();
+                    let error = action.and_then(|action| action.errors.first());
+                    serde_json::json!({
+                        "url": url,
+                        "project": format!("{}/{}", report.org, report.project),
+                        "build_num": report.build_num,
+                        "status": report.status,
+                        "job": report.workflow.as_ref().and_then(|w| w.job_name.as_deref()),
+                        "failed_step": action.map(|action| &action.step),
+                        "category": error.map(|error| &error.category),
+                        "error": error.map(|error| error.first.line.trim()),
+                        "report": path,
+                    })
+                }
+                Err(e) => serde_json::json!({"url": url, "error": format!("{:#}", e)}),
+            })
+            .collect();
+        return print_json(&rows);
+    }
+
+    print_header("Builds");
+    let mut analyzed = 0;
+    for (url, result) in urls.iter().zip(&results) {
+        let (report, _) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // Keep the row to one line; --json has the whole error
+                let error = format!("{:#}", e);
+                let reason = error.lines().next().unwrap_or_default();
+                println!("{} {} {}", "!".warning().bold(), url, reason.dim());
+                continue;
+            }
+        };
+        analyzed += 1;
+        let failed = !report.failed_actions.is_empty()
+            || matches!(
+                report.status.as_str(),
+                "failed" | "infrastructure_fail" | "timedout"
+            );
+        let marker = if failed {
+            "✗".error().bold()
+        } else {
+            "✓".success().bold()
+        };
+        let mut row = format!(
+            "{} {}/{} #{} {} {}",
+            marker,
+            report.org,
+            report.project,
+            report.build_num,
+            report.job_label().cyan(),
+            report.status
+        );
+        if let Some(action) = report.failed_actions.first() {
+            row.push_str(&format!(" · {}", action.step.bold()));
+            if let Some(error) = action.errors.first() {
+                row.push_str(&format!(
+                    ": {} {}",
+                    format!("[{}]", error.category).warning(),
+                    error.first.line.trim()
+                ));
+            }
+        }
+        println!("{}", row);
+    }
+    let summary = format!(
+        "Analyzed {} of {} builds; reports are in {}",
+        analyzed,
+        urls.len(),
+        bulk.output.display()
+    );
+    if analyzed == urls.len() {
+        println!();
+        print_success(&summary);
+    } else {
+        print_warning(&summary);
+    }
+    Ok(())
+}
+
+/// Options for the `status` command.
+#[derive(Debug)]
+struct StatusOptions {
+    /// The build to check; when unset, the newest finished build of
+    /// `branch`.
+    url: Option<String>,
+    /// Branch whose newest finished build is checked.
+    branch: Option<String>,
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Only consider builds of this workflow job.
+    job: Option<String>,
+    /// Print the status as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Builds of the branch to look through for `--job`; one page, so the
+/// check stays a single request.
+const STATUS_JOB_BUILDS: u32 = 30;
+
+/// Prints one line about a build: its status, job, branch, commit, and
+/// when it finished. Fetches the build's metadata only, never its logs.
+///
+/// # Returns
+///
+/// The exit code for the build's status, from [`exit::for_status`].
+///
+/// # Errors
+///
+/// Returns an error if the URL or repository is invalid, the build can't
+/// be fetched, or the branch has no finished builds of the job.
+async fn show_status(options: &StatusOptions, progress: &Progress) -> Result<ExitCode> {
+    let client = circle_client(options.no_cache)?;
+    let (org, project, build) = match (&options.url, &options.branch) {
+        (Some(url), _) => {
+            let (org, project, build_num) = parse_circleci_url(url)?;
+            let spinner = progress.spinner("Fetching build status...");
+            let build = client.get_build(&org, &project, build_num).await;
+            spinner.finish_and_clear();
+            (org, project, build?)
+        }
+        (None, Some(branch)) => {
+            let (org, project) = match &options.repo {
+                Some(repo) => split_repo(repo)?,
+                None => git::origin_repo()?,
+            };
+            let query = BuildQuery {
+                branch: Some(branch.clone()),
+                filter: BuildFilter::Completed,
+                limit: if options.job.is_some() {
+                    STATUS_JOB_BUILDS
+                } else {
+                    1
+                },
+                offset: 0,
+            };
+            let spinner = progress.spinner("Fetching the branch's latest build...");
+            let builds = client.recent_builds(&org, &project, &query).await;
+            spinner.finish_and_clear();
+            let build = builds?.into_iter().find(|build| {
+                options.job.as_deref().is_none_or(|job| {
+                    build.workflows.as_ref().and_then(|w| w.job_name.as_deref()) == Some(job)
+                })
+            });
+            let Some(build) = build else {
+                match &options.job {
+                    Some(job) => bail!(
+                        "No finished builds of job '{}' in the last {} builds of {}",
+                        job,
+                        STATUS_JOB_BUILDS,
+                        branch
+                    ),
+                    None => bail!("No finished builds on {}", branch),
+                }
+            };
+            (org, project, build)
+        }
+        (None, None) => bail!("Give a build URL or --branch"),
+    };
+
+    let job = build
+        .workflows
+        .as_ref()
+        .and_then(|workflow| workflow.job_name.as_deref());
+    if options.json {
+        print_json(&serde_json::json!({
+            "org": org,
+            "project": project,
+            "build_num": build.build_num,
+            "status": build.status,
+            "job": job,
+            "branch": build.branch,
+            "vcs_revision": build.vcs_revision,
+            "subject": build.subject,
+            "start_time": build.start_time,
+            "stop_time": build.stop_time,
+            "build_url": build.build_url,
+        }))?;
+        return Ok(exit::for_status(&build));
+    }
+
+    print_status_line(&org, &project, &build);
+    Ok(exit::for_status(&build))
+}
+
+/// Prints the one-line status of `cdb status` and `cdb wait`, e.g.
+/// `✗ failed org/repo #123 · test · main · a1b2c3d Fix it · finished 2
+/// minutes ago`.
+fn print_status_line(org: &str, project: &str, build: &BuildInfo) {
+    let code = exit::for_status(build);
+    let status = if code == ExitCode::SUCCESS {
+        format!("✓ {}", build.status).success().bold()
+    } else if code == ExitCode::from(exit::STATUS_FAILED) {
+        format!("✗ {}", build.status).error().bold()
+    } else {
+        format!("• {}", build.status).warning().bold()
+    };
+    let job = build
+        .workflows
+        .as_ref()
+        .and_then(|workflow| workflow.job_name.as_deref());
+    let mut parts = vec![format!("{}/{} #{}", org, project, build.build_num)];
+    parts.extend(job.map(str::to_string));
+    parts.extend(build.branch.clone());
+    match (build.short_revision(), &build.subject) {
+        (Some(sha), Some(subject)) => parts.push(format!("{} {}", sha.warning(), subject)),
+        (Some(sha), None) => parts.push(sha.warning().to_string()),
+        (None, _) => {}
+    }
+    let now = chrono::Utc::now();
+    match (build.stop_time, build.start_time) {
+        (Some(stop), _) => parts.push(format!("finished {}", format_age(now - stop))),
+        (None, Some(start)) => parts.push(format!("started {}", format_age(now - start))),
+        (None, None) => {}
+    }
+    println!("{} {}", status, parts.join(" · "));
+}
+
+/// Waits for a build to finish, polling every `interval` for at most
+/// `timeout` (durations like "15s" or "30m"), then prints its status line.
+///
+/// Polls are logged at info level, so only `-v` shows them. A failed poll
+/// after the first is logged and retried. With `notify`, the result is
+/// also shown as a desktop notification that links to the build.
+///
+/// # Returns
+///
+/// The exit code for the build's final status from [`exit::for_status`],
+/// or [`exit::STATUS_PENDING`] on timeout.
+///
+/// # Errors
+///
+/// Returns an error if the URL or a duration is invalid, or the build
+/// can't be fetched the first time.
+async fn wait_for_build(
+    url: &str,
+    timeout: &str,
+    interval: &str,
+    notify: bool,
+    no_cache: bool,
+) -> Result<ExitCode> {
+    let timeout = circle_debug::parse_duration(timeout)
+        .with_context(|| format!("Invalid --timeout '{}'", timeout))?;
+    let interval = parse_interval(interval)?;
+    let (org, project, build_num) = parse_circleci_url(url)?;
+    let client = circle_client(no_cache)?;
+
+    let started = std::time::Instant::now();
+    let mut last: Option<BuildInfo> = None;
+    loop {
+        match client.get_build(&org, &project, build_num).await {
+            Ok(build) => {
+                tracing::info!(build = build_num, status = %build.status, "polled build");
+                if build.is_finished() {
+                    print_status_line(&org, &project, &build);
+                    if notify {
+                        notify::send(
+                            &format!("cdb: {}/{} #{} {}", org, project, build_num, build.status),
+                            build.subject.as_deref().unwrap_or(&build.status),
+                            build
+                                .build_url
+                                .as_deref()
+                                .unwrap_or(&job_web_url(&org, &project, build_num)),
+                        );
+                    }
+                    return Ok(exit::for_status(&build));
+                }
+                last = Some(build);
+            }
+            Err(e) if last.is_some() => tracing::warn!("couldn't poll build: {:#}", e),
+            Err(e) => return Err(e),
+        }
+        if started.elapsed() >= timeout {
+            let status = last
+                .as_ref()
+                .map_or("unknown", |build| build.status.as_str());
+            if notify {
+                notify::send(
+                    &format!("cdb: {}/{} #{} still {}", org, project, build_num, status),
+                    &format!(
+                        "Stopped waiting after {}",
+                        format_duration(timeout.as_millis() as u64)
+                    ),
+                    &job_web_url(&org, &project, build_num),
+                );
+            }
+            println!(
+                "{}",
+                format!(
+                    "• Timed out after {} waiting for {}/{} #{} ({})",
+                    format_duration(timeout.as_millis() as u64),
+                    org,
+                    project,
+                    build_num,
+                    status
+                )
+                .warning()
+                .bold()
+            );
+            return Ok(ExitCode::from(exit::STATUS_PENDING));
+        }
+        tokio::time::sleep(interval.min(timeout.saturating_sub(started.elapsed()))).await;
+    }
+}
+
+/// Options for the `bisect` command.
+#[derive(Debug)]
+struct BisectOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Branch whose history is walked.
+    branch: String,
+    /// Only consider builds of this workflow job.
+    job: Option<String>,
+    /// How many finished builds to look back through.
+    max_builds: u32,
+    /// Print a `git bisect start` command for the commit range.
+    git_bisect: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Finds where a job on a branch went from passing to failing.
+///
+/// Prints the last green and first red build, the commit range between
+/// them, and optionally a `git bisect start` command.
+///
+/// # Errors
+///
+/// Returns an error if the repository can't be determined or the build
+/// history can't be listed.
+async fn bisect_branch(options: &BisectOptions, progress: &Progress) -> Result<()> {
+    print_header("Bisecting CircleCI History");
+
+    let (org, project) = match &options.repo {
+        Some(repo) => repo
+            .split_once('/')
+            .map(|(org, project)| (org.to_string(), project.to_string()))
+            .with_context(|| format!("Invalid repository '{}', expected org/repo", repo))?,
+        None => git::origin_repo()?,
+    };
+    print_info(&format!("Repository: {}/{}", org, project));
+    print_info(&format!("Branch: {}", options.branch));
+    if let Some(job) = &options.job {
+        print_info(&format!("Job: {}", job));
+    }
+
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner("Walking build history...");
+    let bisect = client
+        .bisect(
+            &org,
+            &project,
+            &options.branch,
+            options.job.as_deref(),
+            options.max_builds,
+        )
+        .await;
+    spinner.finish_and_clear();
+    let bisect = bisect?;
+
+    let job = options.job.as_deref().unwrap_or("The branch");
+    let Some(latest) = &bisect.latest else {
+        print_error(&format!(
+            "No finished builds found in the last {} builds",
+            options.max_builds
+        ));
+        return Ok(());
+    };
+    if bisect.is_green() {
+        print_success(&format!(
+            "{} is green on {}: {}",
+            job,
+            options.branch,
+            describe_build(latest)
+        ));
+        return Ok(());
+    }
+
+    print_header("Result");
+    let first_red = bisect.first_red.as_ref().unwrap_or(latest);
+    println!(
+        "{} {}",
+        "First red: ".error().bold(),
+        describe_build(first_red)
+    );
+    let Some(last_green) = &bisect.last_green else {
+        print_error(&format!(
+            "No passing build in the last {} builds ({} checked) - try a larger --max-builds",
+            options.max_builds, bisect.checked
+        ));
+        return Ok(());
+    };
+    println!(
+        "{} {}",
+        "Last green:".success().bold(),
+        describe_build(last_green)
+    );
+    if first_red.build_num != latest.build_num {
+        println!(
+            "\n{} has failed since #{} (latest: #{})",
+            job, first_red.build_num, latest.build_num
+        );
+    }
+
+    match (&last_green.vcs_revision, &first_red.vcs_revision) {
+        (Some(good), Some(bad)) if good != bad => {
+            print_header("Commit Range");
+            println!(
+                "  {}..{}",
+                short_sha(good).success(),
+                short_sha(bad).error()
+            );
+            println!("  git log --oneline {}..{}", good, bad);
+            println!(
+                "  {}",
+                format!(
+                    "https://github.com/{}/{}/compare/{}...{}",
+                    org, project, good, bad
+                )
+                .blue()
+                .underline()
+            );
+            if options.git_bisect {
+                println!("\n  git bisect start {} {}", bad, good);
+            }
+        }
+        (Some(_), Some(_)) => print_warning(
+            "Both builds ran the same commit - the failure is likely flaky or environmental",
+        ),
+        _ => print_warning("The API didn't report commits for these builds"),
+    }
+
+    Ok(())
+}
+
+/// Options for the `search` command.
+#[derive(Debug)]
+struct SearchCommandOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Which builds and logs to search.
+    search: SearchOptions,
+    /// Print the results as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Searches the logs of a project's recent builds and prints the matches
+/// grouped by build and step, newest first.
+///
+/// # Errors
+///
+/// Returns an error if `pattern` isn't a valid regex, the repository can't
+/// be determined, or the recent builds can't be listed.
+async fn search_logs(
+    pattern: &str,
+    options: &SearchCommandOptions,
+    progress: &Progress,
+) -> Result<()> {
+    let regex =
+        Regex::new(pattern).with_context(|| format!("Invalid search pattern '{}'", pattern))?;
+    let (org, project) = match &options.repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    if options.no_cache && !options.search.fetch {
+        bail!("Without the cache only fetched logs can be searched; add --fetch");
+    }
+
+    let indexed = search_index(&org, &project, &regex, options)?;
+    let from_index = indexed.is_some();
+    let results = match indexed {
+        Some(results) => results,
+        None => {
+            let client = circle_client(options.no_cache)?;
+            let spinner = progress.spinner("Searching build logs...");
+            let results = client
+                .search_builds(&org, &project, &regex, &options.search)
+                .await;
+            spinner.finish_and_clear();
+            results?
+        }
+    };
+    if options.json {
+        print_json(&results)?;
+        return Ok(());
+    }
+
+    print_header(&format!("Log Search: {}", pattern));
+    print_info(&format!("Repository: {}/{}", org, project));
+    print_search_hits(&results, options.search.context);
+
+    println!();
+    match results.first_seen() {
+        Some(first) => print_success(&format!(
+            "Matched in {} of {} build(s); first seen in #{}",
+            results.matching_builds(),
+            results.builds_searched,
+            first.build_num
+        )),
+        None => print_info(&format!(
+            "No matches in {} log(s) of {} build(s)",
+            results.logs_searched, results.builds_searched
+        )),
+    }
+    if results.logs_not_cached > 0 {
+        print_warning(&format!(
+            "{} log(s) weren't cached and were skipped; add --fetch to download them",
+            results.logs_not_cached
+        ));
+    }
+    if results.logs_failed > 0 {
+        print_warning(&format!(
+            "{} log(s) couldn't be loaded",
+            results.logs_failed
+        ));
+    }
+    if from_index {
+        print_info("Searched the log index; run `cdb cache index` to add builds cached since, or use --fetch");
+    }
+
+    Ok(())
+}
+
+/// Options for the `grep` command.
+#[derive(Debug)]
+struct GrepOptions {
+    /// Context and per-log match limit.
+    search: SearchOptions,
+    /// Only print match counts per step.
+    count: bool,
+    /// Match case-insensitively.
+    ignore_case: bool,
+    /// Print the matches as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Searches every step log of one build and prints the matches under
+/// their step, or just how many there are.
+///
+/// # Errors
+///
+/// Returns an error if `pattern` isn't a valid regex, the URL can't be
+/// parsed, or the build can't be fetched.
+async fn grep_build(
+    url: &str,
+    pattern: &str,
+    options: &GrepOptions,
+    progress: &Progress,
+) -> Result<()> {
+    let regex = regex::RegexBuilder::new(pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid search pattern '{}'", pattern))?;
+    let (org, project, build_num) = parse_circleci_url(url)?;
+    let client = circle_client(options.no_cache)?;
+
+    let spinner = progress.spinner("Searching build logs...");
+    let results = client
+        .grep_build(&org, &project, build_num, &regex, &options.search)
+        .await;
+    spinner.finish_and_clear();
+    let results = results?;
+    if options.json {
+        print_json(&results)?;
+        return Ok(());
+    }
+
+    let total: usize = results.hits.iter().map(|hit| hit.total_matches).sum();
+    if options.count {
+        for hit in &results.hits {
+            let container = hit
+                .container
+                .map(|i| format!(" (container {})", i))
+                .unwrap_or_default();
+            println!(
+                "{}{}: {}",
+                hit.step.cyan(),
+                container,
+                hit.total_matches.to_string().bold()
+            );
+        }
+    } else {
+        print_header(&format!("Grep: {}", pattern));
+        print_info(&format!("Build: {}/{} #{}", org, project, build_num));
+        print_search_hits(&results, options.search.context);
+        println!();
+    }
+
+    if total == 0 {
+        print_info(&format!("No matches in {} log(s)", results.logs_searched));
+    } else if !options.count {
+        print_success(&format!(
+            "{} matching line(s) in {} of {} log(s)",
+            total,
+            results.hits.len(),
+            results.logs_searched
+        ));
+    }
+    if results.logs_failed > 0 {
+        print_warning(&format!(
+            "{} log(s) couldn't be downloaded",
+            results.logs_failed
+        ));
+    }
+    Ok(())
+}
+
+/// Prints search hits grouped by build and step, with the matched lines
+/// and `context` lines around them. Builds are labeled with their project
+/// when the hits span more than one.
+fn print_search_hits(results: &SearchResults, context: usize) {
+    let many_projects = results
+        .hits
+        .iter()
+        .any(|hit| hit.project != results.hits[0].project);
+    let mut last_build = None;
+    for hit in &results.hits {
+        if last_build != Some((&hit.project, hit.build_num)) {
+            last_build = Some((&hit.project, hit.build_num));
+            let when = hit
+                .start_time
+                .map(|t| format!(" {}", t.format("%Y-%m-%d %H:%M")))
+                .unwrap_or_default();
+            let build = if many_projects {
+                format!("{} #{}", hit.project, hit.build_num)
+            } else {
+                format!("#{}", hit.build_num)
+            };
+            println!(
+                "\n{} {}{}{}",
+                build.bold(),
+                hit.status,
+                hit.branch
+                    .as_deref()
+                    .map(|b| format!(" on {}", b))
+                    .unwrap_or_default(),
+                when.dim()
+            );
+        }
+        let container = hit
+            .container
+            .map(|i| format!(" (container {})", i))
+            .unwrap_or_default();
+        println!(
+            "  {}{} - {} match(es)",
+            hit.step.cyan(),
+            container,
+            hit.total_matches
+        );
+        for found in &hit.matches {
+            for line in &found.before {
+                println!("    {}", line.dim());
+            }
+            println!(
+                "    {} {}",
+                format!("{}:", found.line_number).warning(),
+                found.line
+            );
+            for line in &found.after {
+                println!("    {}", line.dim());
+            }
+            if context > 0 {
+                println!("    {}", "--".dim());
+            }
+        }
+    }
+}
+
+/// Options for the `diff-artifacts` command.
+#[derive(Debug)]
+struct DiffArtifactsOptions {
+    /// Only compare artifacts matching this glob.
+    glob: Option<String>,
+    /// Growth, in percent, that flags a file as a regression.
+    max_growth: f64,
+    /// Print the differences as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Compares the artifacts of two builds and prints what was added,
+/// removed, and changed.
+///
+/// # Returns
+///
+/// Whether any file grew by more than `max_growth` percent.
+///
+/// # Errors
+///
+/// Returns an error if a URL can't be parsed, the glob is invalid, or
+/// either build's artifacts can't be listed.
+async fn diff_artifacts(
+    old_url: &str,
+    new_url: &str,
+    options: &DiffArtifactsOptions,
+    progress: &Progress,
+) -> Result<bool> {
+    let (old_org, old_project, old_num) = parse_circleci_url(old_url)?;
+    let (new_org, new_project, new_num) = parse_circleci_url(new_url)?;
+    let client = circle_client(options.no_cache)?;
+    let glob = options.glob.as_deref();
+
+    let spinner = progress.spinner("Listing artifacts...");
+    let (old, new) = futures::future::join(
+        client.artifact_infos(&old_org, &old_project, old_num, glob),
+        client.artifact_infos(&new_org, &new_project, new_num, glob),
+    )
+    .await;
+    spinner.finish_and_clear();
+    let diff = artifacts::diff(&old?, &new?);
+    let grew = |change: &ArtifactChange| {
+        change
+            .growth_percent()
+            .is_some_and(|growth| growth > options.max_growth)
+    };
+    let regressions = diff.changes.iter().filter(|change| grew(change)).count();
+    if options.json {
+        print_json(&diff)?;
+        return Ok(regressions > 0);
+    }
+
+    print_header(&format!("Artifacts: #{} → #{}", old_num, new_num));
+    if let Some(glob) = glob {
+        print_info(&format!("Matching: {}", glob));
+    }
+    let size = |info: &ArtifactInfo| {
+        info.size
+            .map(format_bytes)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    for change in &diff.changes {
+        match change {
+            ArtifactChange::Added { artifact } => {
+                println!(
+                    "{} {} {}",
+                    "+".success().bold(),
+                    artifact.path,
+                    size(artifact).dim()
+                )
+            }
+            ArtifactChange::Removed { artifact } => {
+                println!(
+                    "{} {} {}",
+                    "-".error().bold(),
+                    artifact.path,
+                    size(artifact).dim()
+                )
+            }
+            ArtifactChange::Changed { old, new } => {
+                let delta = match (change.size_delta(), change.growth_percent()) {
+                    (Some(0), _) => "same size, contents changed".to_string(),
+                    (Some(delta), Some(growth)) => {
+                        format!("{}, {:+.1}%", format_bytes_delta(delta), growth)
+                    }
+                    (Some(delta), None) => format_bytes_delta(delta),
+                    (None, _) => "contents changed".to_string(),
+                };
+                let line = format!("{} → {} ({})", size(old), size(new), delta);
+                if grew(change) {
+                    println!(
+                        "{} {} {} {}",
+                        "~".warning().bold(),
+                        new.path.bold(),
+                        line.error(),
+                        "regression".error().bold()
+                    );
+                } else {
+                    println!("{} {} {}", "~".warning().bold(), new.path, line.dim());
+                }
+            }
+        }
+    }
+    if diff.changes.is_empty() {
+        print_success("No differences");
+    }
+    println!(
+        "\n{} {} unchanged; total {} → {} ({})",
+        "Summary:".bold(),
+        diff.unchanged,
+        format_bytes(diff.old_total),
+        format_bytes(diff.new_total),
+        format_bytes_delta(diff.new_total as i64 - diff.old_total as i64)
+    );
+    if regressions > 0 {
+        print_warning(&format!(
+            "{} file{} grew by more than {}%",
+            regressions,
+            if regressions == 1 { "" } else { "s" },
+            options.max_growth
+        ));
+    }
+    Ok(regressions > 0)
+}
+
+/// Lists a project's checkout keys.
+///
+/// # Errors
+///
+/// Returns an error if the repository can't be determined or the keys
+/// can't be listed.
+async fn show_keys(
+    repo: Option<&str>,
+    json: bool,
+    no_cache: bool,
+    progress: &Progress,
+) -> Result<()> {
+    let (org, project) = match repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let client = circle_client(no_cache)?;
+    let spinner = progress.spinner("Listing checkout keys...");
+    let keys = client.checkout_keys(&org, &project).await;
+    spinner.finish_and_clear();
+    let keys = keys?;
+    if json {
+        print_json(&keys)?;
+        return Ok(());
+    }
+
+    print_header(&format!("Checkout Keys: {}/{}", org, project));
+    if keys.is_empty() {
+        print_warning("The project has no checkout keys, so builds can't clone it over SSH");
+        print_info("Add one under Project Settings → SSH Keys");
+        return Ok(());
+    }
+    print_checkout_keys(&keys);
+    Ok(())
+}
+
+/// Prints the followed projects, one line each with the latest build of
+/// the default branch.
+///
+/// # Errors
+///
+/// Returns an error if the projects can't be listed.
+async fn show_projects(json: bool, no_cache: bool, progress: &Progress) -> Result<()> {
+    let client = circle_client(no_cache)?;
+    let spinner = progress.spinner("Listing followed projects...");
+    let projects = client.followed_projects().await;
+    spinner.finish_and_clear();
+    let mut projects = projects?;
+    projects.sort_by_key(|p| (p.username.to_lowercase(), p.reponame.to_lowercase()));
+    if json {
+        print_json(&projects)?;
+        return Ok(());
+    }
+
+    print_header("Followed Projects");
+    if projects.is_empty() {
+        print_warning("Your token doesn't follow any projects");
+        print_info("Follow one with 'cdb follow org/repo'");
+        return Ok(());
+    }
+    for project in &projects {
+        let name = format!("{}/{}", project.username, project.reponame);
+        let Some((branch, build)) = project.latest_build() else {
+            println!("  {} {} {}", "○".dim(), name, "no builds".dim());
+            continue;
+        };
+        let status = build.outcome.as_deref().unwrap_or(&build.status);
+        let mark = match status {
+            "success" | "fixed" => "✓".success().to_string(),
+            "failed" | "infrastructure_fail" | "timedout" => "✗".error().to_string(),
+            "running" | "queued" | "not_running" => "●".warning().to_string(),
+            "canceled" => "⊘".dim().to_string(),
+            _ => "○".dim().to_string(),
+        };
+        let age = build
+            .added_at
+            .map(|at| format!(", {}", format_age(chrono::Utc::now() - at)))
+            .unwrap_or_default();
+        println!(
+            "  {} {} {} #{} {}{}",
+            mark,
+            name,
+            branch.dim(),
+            build.build_num,
+            status.replace('_', " "),
+            age.dim()
+        );
+    }
+    Ok(())
+}
+
+/// Options for the `top` command.
+#[derive(Debug)]
+struct TopOptions {
+    /// Only this organization's builds.
+    org: Option<String>,
+    /// Time between refreshes, e.g. "5s".
+    interval: String,
+    /// Print once instead of refreshing.
+    once: bool,
+    /// Print the builds as JSON, once.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Shortest time between two polls of the API.
+const MIN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Parses an `--interval`, refusing ones shorter than [`MIN_INTERVAL`],
+/// which would poll CircleCI in a tight loop.
+///
+/// # Errors
+///
+/// Returns an error if the interval can't be parsed or is too short.
+fn parse_interval(interval: &str) -> Result<std::time::Duration> {
+    let parsed = circle_debug::parse_duration(interval)
+        .with_context(|| format!("Invalid --interval '{}'", interval))?;
+    if parsed < MIN_INTERVAL {
+        anyhow::bail!(
+            "--interval '{}' is too short; poll at most once every {}",
+            interval,
+            format_duration(MIN_INTERVAL.as_millis() as u64)
+        );
+    }
+    Ok(parsed)
+}
+
+/// Shows the running and queued builds of followed projects, redrawing
+/// the screen every interval until interrupted.
+///
+/// Prints once when `--once` or `--json` is given or stdout isn't a
+/// terminal.
+///
+/// # Errors
+///
+/// Returns an error if the interval can't be parsed or the first listing
+/// fails; later failures are shown and retried.
+async fn show_top(options: &TopOptions, progress: &Progress) -> Result<()> {
+    let interval = parse_interval(&options.interval)?;
+    let client = circle_client(options.no_cache)?;
+    let live = !options.once && !options.json && std::io::stdout().is_terminal();
+    let mut shown = false;
+    loop {
+        let spinner = (!shown).then(|| progress.spinner("Listing running builds..."));
+        let builds = client.active_builds(options.org.as_deref()).await;
+        if let Some(spinner) = spinner {
+            spinner.finish_and_clear();
+        }
+        let builds = match builds {
+            Ok(builds) => builds,
+            Err(e) if shown => {
+                print_warning(&format!("Couldn't refresh: {:#}", e));
+                tokio::time::sleep(interval).await;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if options.json {
+            return print_json(&builds);
+        }
+
+        if live {
+            // Clear the screen and move to its top-left corner
+            print!("\x1b[2J\x1b[H");
+        }
+        print_active_builds(builds, options.org.as_deref());
+        if !live {
+            return Ok(());
+        }
+        println!(
+            "\n{}",
+            format!(
+                "Refreshing every {} · Ctrl-C to quit",
+                format_duration(interval.as_millis() as u64)
+            )
+            .dim()
+        );
+        shown = true;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Prints running builds, then queued ones, each longest first.
+fn print_active_builds(mut builds: Vec<ActiveBuild>, org: Option<&str>) {
+    let now = chrono::Utc::now();
+    builds.sort_by_key(|build| {
+        (
+            !build.is_running(),
+            std::cmp::Reverse(build.elapsed(now).unwrap_or_default()),
+        )
+    });
+    let running = builds.iter().filter(|build| build.is_running()).count();
+
+    print_header(&format!(
+        "CI Activity: {}",
+        org.unwrap_or("followed projects")
+    ));
+    print_info(&format!(
+        "{} running, {} queued · {}",
+        running,
+        builds.len() - running,
+        chrono::Local::now().format("%H:%M:%S")
+    ));
+    if builds.is_empty() {
+        print_success("Nothing running");
+        return;
+    }
+
+    let rows: Vec<[String; 5]> = builds
+        .iter()
+        .map(|build| {
+            let job = build
+                .workflows
+                .as_ref()
+                .and_then(|w| match (&w.workflow_name, &w.job_name) {
+                    (Some(workflow), Some(job)) => Some(format!("{} › {}", workflow, job)),
+                    (None, job) => job.clone(),
+                    (workflow, None) => workflow.clone(),
+                })
+                .unwrap_or_default();
+            let elapsed = build
+                .elapsed(now)
+                .and_then(|elapsed| elapsed.to_std().ok())
+                .map(|elapsed| format_duration(elapsed.as_millis() as u64))
+                .unwrap_or_default();
+            [
+                format!("{}/{}", build.username, build.reponame),
+                build.branch.clone().unwrap_or_default(),
+                format!("#{}", build.build_num),
+                job,
+                elapsed,
+            ]
+        })
+        .collect();
+    let headers = ["PROJECT", "BRANCH", "BUILD", "JOB", "ELAPSED"];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([headers[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: [&str; 5]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    println!("\n    {}", line(headers).dim());
+    for (build, row) in builds.iter().zip(&rows) {
+        let mark = if build.is_running() {
+            "●".warning()
+        } else {
+            "○".dim()
+        };
+        println!(
+            "  {} {}",
+            mark,
+            line([&row[0], &row[1], &row[2], &row[3], &row[4]])
+        );
+    }
+}
+
+/// Follows a project and confirms it.
+///
+/// # Errors
+///
+/// Returns an error if the repository is malformed or can't be followed.
+async fn follow(repo: &str) -> Result<()> {
+    let (org, project) = split_repo(repo)?;
+    let client = circle_client(true)?;
+    let result = client.follow_project(&org, &project).await?;
+    if result.following {
+        print_success(&format!("Following {}/{}", org, project));
+    } else {
+        print_warning(&format!(
+            "CircleCI didn't confirm following {}/{}",
+            org, project
+        ));
+    }
+    Ok(())
+}
+
+/// How often `cdb ssh` checks whether the container is up.
+const SSH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Options for the `trigger` command.
+#[derive(Debug)]
+struct TriggerCommandOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Branch to run on.
+    branch: Option<String>,
+    /// `NAME=VALUE` pipeline parameters.
+    params: Vec<String>,
+    /// List the declared parameters instead of triggering.
+    list_params: bool,
+    /// Print JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Triggers a pipeline with the given parameters, checked against the ones
+/// the config declares, or with `--list-params` lists those.
+///
+/// # Errors
+///
+/// Returns an error if a parameter is undeclared or of the wrong type, the
+/// declared parameters can't be read for `--list-params`, or CircleCI
+/// refuses the trigger.
+async fn trigger_pipeline(options: &TriggerCommandOptions, progress: &Progress) -> Result<()> {
+    let (org, project, branch) = match &options.repo {
+        Some(repo) => {
+            let (org, project) = split_repo(repo)?;
+            (org, project, options.branch.clone())
+        }
+        None => {
+            let (org, project) = git::origin_repo()?;
+            let branch = options
+                .branch
+                .clone()
+                .or_else(|| git::current_branch().ok());
+            (org, project, branch)
+        }
+    };
+    let supplied: Vec<(String, String)> = options
+        .params
+        .iter()
+        .map(|arg| parameters::parse_assignment(arg))
+        .collect::<Result<_>>()?;
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner("Reading the config's pipeline parameters...");
+    let declared = client
+        .pipeline_parameters(&org, &project, branch.as_deref())
+        .await;
+    spinner.finish_and_clear();
+
+    if options.list_params {
+        let declared = declared?;
+        if options.json {
+            return print_json(&declared);
+        }
+        print_header(&format!("Pipeline Parameters: {}/{}", org, project));
+        if declared.is_empty() {
+            print_info("The config declares no pipeline parameters");
+        }
+        for param in &declared {
+            let kind = match (&param.param_type, param.allowed.is_empty()) {
+                (Some(ParameterType::Enum), false) => param.allowed.join(" | "),
+                (Some(kind), _) => kind.to_string(),
+                (None, _) => "?".to_string(),
+            };
+            let default = match &param.default {
+                Some(default) => format!("default: {}", default).dim(),
+                None => "required".warning(),
+            };
+            println!("  {}  {}  {}", param.name.bold(), kind, default);
+            if let Some(description) = &param.description {
+                println!("    {}", description.dim());
+            }
+        }
+        return Ok(());
+    }
+
+    let values = match declared {
+        Ok(declared) => parameters::validate(&declared, &supplied)
+            .context("Parameters don't match the config; see cdb trigger --list-params")?,
+        Err(e) => {
+            if !options.json {
+                print_warning(&format!(
+                    "Couldn't read the declared parameters, so they aren't checked: {:#}",
+                    e
+                ));
+            }
+            supplied
+                .into_iter()
+                .map(|(name, value)| {
+                    let json = match value.as_str() {
+                        "true" => serde_json::Value::Bool(true),
+                        "false" => serde_json::Value::Bool(false),
+                        _ => value
+                            .parse::<i64>()
+                            .map_or_else(|_| serde_json::Value::String(value), Into::into),
+                    };
+                    (name, json)
+                })
+                .collect()
+        }
+    };
+
+    let spinner = progress.spinner("Triggering pipeline...");
+    let pipeline = client
+        .trigger_pipeline(&org, &project, branch.as_deref(), values)
+        .await;
+    spinner.finish_and_clear();
+    let pipeline = pipeline?;
+    if options.json {
+        return print_json(&pipeline);
+    }
+    print_success(&format!(
+        "Triggered pipeline #{} of {}/{}{}",
+        pipeline.number,
+        org,
+        project,
+        branch
+            .as_deref()
+            .map(|branch| format!(" on {}", branch))
+            .unwrap_or_default()
+    ));
+    print_info(&pipeline_url(&org, &project, pipeline.number));
+    Ok(())
+}
+
+/// Returns the web app URL of a project's pipeline.
+fn pipeline_url(org: &str, project: &str, number: u32) -> String {
+    format!(
+        "https://app.circleci.com/pipelines/{}/{}/{}/{}",
+        if is_standalone_org(org) {
+            "circleci"
+        } else {
+            "github"
+        },
+        org,
+        project,
+        number
+    )
+}
+
+/// Options for the `schedules` command.
+#[derive(Debug)]
+struct SchedulesCommandOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Name of a schedule to trigger now.
+    trigger_now: Option<String>,
+    /// Print JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Lists a project's scheduled pipelines with their last runs, or with
+/// `--trigger-now` triggers one schedule's pipeline.
+///
+/// # Errors
+///
+/// Returns an error if the schedules can't be listed, no schedule has the
+/// `--trigger-now` name, or CircleCI refuses the trigger.
+async fn show_schedules(options: &SchedulesCommandOptions, progress: &Progress) -> Result<()> {
+    let (org, project) = match &options.repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner("Fetching scheduled pipelines...");
+    let schedules = client.schedules(&org, &project).await;
+    spinner.finish_and_clear();
+    let schedules = schedules?;
+
+    if let Some(name) = &options.trigger_now {
+        let Some(schedule) = schedules.iter().find(|s| &s.name == name) else {
+            let names: Vec<&str> = schedules.iter().map(|s| s.name.as_str()).collect();
+            anyhow::bail!(
+                "No schedule named '{}' in {}/{} (schedules: {})",
+                name,
+                org,
+                project,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+        };
+        let spinner = progress.spinner("Triggering the schedule's pipeline...");
+        let pipeline = client
+            .trigger_pipeline(
+                &org,
+                &project,
+                schedule.branch(),
+                schedule.trigger_parameters(),
+            )
+            .await;
+        spinner.finish_and_clear();
+        let pipeline = pipeline?;
+        if options.json {
+            return print_json(&pipeline);
+        }
+        print_success(&format!(
+            "Triggered schedule '{}' as pipeline #{} of {}/{}{}",
+            name,
+            pipeline.number,
+            org,
+            project,
+            schedule
+                .branch()
+                .map(|branch| format!(" on {}", branch))
+                .unwrap_or_default()
+        ));
+        print_info(&pipeline_url(&org, &project, pipeline.number));
+        return Ok(());
+    }
+
+    let spinner = progress.spinner("Finding the schedules' last runs...");
+    let last_runs = client.last_scheduled_runs(&org, &project, &schedules).await;
+    spinner.finish_and_clear();
+    let last_runs = match last_runs {
+        Ok(last_runs) => last_runs,
+        Err(e) => {
+            if !options.json {
+                print_warning(&format!("Couldn't find the last runs: {:#}", e));
+            }
+            vec![None; schedules.len()]
+        }
+    };
+
+    if options.json {
+        let entries: Vec<serde_json::Value> = schedules
+            .iter()
+            .zip(&last_runs)
+            .map(|(schedule, last_run)| {
+                serde_json::json!({
+                    "schedule": schedule,
+                    "timetable": schedule.timetable.describe(),
+                    "last_run": last_run,
+                })
+            })
+            .collect();
+        return print_json(&entries);
+    }
+
+    print_header(&format!("Scheduled Pipelines: {}/{}", org, project));
+    if schedules.is_empty() {
+        print_info("No scheduled pipelines");
+        return Ok(());
+    }
+    for (schedule, last_run) in schedules.iter().zip(&last_runs) {
+        println!(
+            "  {}  {}",
+            schedule.name.bold(),
+            schedule.timetable.describe().dim()
+        );
+        if let Some(description) = &schedule.description {
+            println!("    {}", description.dim());
+        }
+        println!(
+            "    Branch: {}",
+            schedule
+                .branch()
+                .or(schedule.parameters.get("tag").and_then(|tag| tag.as_str()))
+                .unwrap_or("default branch")
+        );
+        let params = schedule.trigger_parameters();
+        if !params.is_empty() {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            println!("    Parameters: {}", params.join(" "));
+        }
+        match last_run {
+            Some(run) => {
+                let status = match run.status.as_deref() {
+                    Some("success") => "success".success(),
+                    Some("failed") => "failed".error(),
+                    Some(status) => status.warning(),
+                    None => "unknown".dim(),
+                };
+                println!(
+                    "    Last run: #{}{} {}",
+                    run.pipeline_number,
+                    run.created_at
+                        .map(|at| format!(" at {}", at.format("%Y-%m-%d %H:%M UTC")))
+                        .unwrap_or_default(),
+                    status
+                );
+            }
+            None => println!(
+                "    Last run: {}",
+                format!(
+                    "none in the last {} pipelines",
+                    circle_debug::schedules::MAX_PIPELINES
+                )
+                .dim()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Reruns a build with SSH, unless it already is an SSH rerun, waits until
+/// the container is up, and prints the command that connects to it and
+/// how long the session lasts.
+///
+/// # Errors
+///
+/// Returns an error if the rerun can't be triggered, the build finishes
+/// without SSH access, or `timeout` passes first.
+async fn ssh_rerun(url: &str, timeout: &str, no_cache: bool, progress: &Progress) -> Result<()> {
+    let timeout = circle_debug::parse_duration(timeout)?;
+    let (org, project, build_num) = parse_circleci_url(url)?;
+    let client = circle_client(no_cache)?;
+
+    let build = client.get_build(&org, &project, build_num).await?;
+    let build_num = if build
+        .trigger()
+        .is_some_and(|t| t.kind == TriggerKind::SshRerun)
+    {
+        print_info(&format!("Build #{} is already an SSH rerun", build_num));
+        build_num
+    } else {
+        let rerun = client.rerun_with_ssh(&org, &project, build_num).await?;
+        print_success(&format!(
+            "Rerunning build #{} with SSH as #{}",
+            build_num, rerun.build_num
+        ));
+        rerun.build_num
+    };
+
+    let spinner = progress.spinner(format!("Waiting for SSH on build #{}...", build_num));
+    let started = std::time::Instant::now();
+    let (build, access) = loop {
+        let build = client.get_build(&org, &project, build_num).await?;
+        let access = client.ssh_access(&build).await?;
+        if !access.is_empty() {
+            break (build, access);
+        }
+        if build.is_finished() {
+            spinner.finish_and_clear();
+            anyhow::bail!(
+                "Build #{} finished ({}) without SSH access",
+                build_num,
+                build.status
+            );
+        }
+        if started.elapsed() >= timeout {
+            spinner.finish_and_clear();
+            anyhow::bail!(
+                "SSH wasn't ready on build #{} after {}s; check {}",
+                build_num,
+                timeout.as_secs(),
+                build.build_url.as_deref().unwrap_or(url)
+            );
+        }
+        tokio::time::sleep(SSH_POLL_INTERVAL).await;
+    };
+    spinner.finish_and_clear();
+
+    print_header(&format!("SSH: build #{}", build_num));
+    for access in &access {
+        let label = access
+            .container
+            .map(|index| format!("container {}: ", index))
+            .unwrap_or_default();
+        println!("  {}{}", label.dim(), access.command().success().bold());
+    }
+    let now = chrono::Utc::now();
+    let remaining = |deadline: chrono::DateTime<chrono::Utc>| {
+        format_duration((deadline - now).num_milliseconds().max(0) as u64)
+    };
+    match build.ssh_deadlines() {
+        (Some(idle), session) => {
+            print_info(&format!(
+                "Connect within {} or the container shuts down",
+                remaining(idle)
+            ));
+            if let Some(session) = session {
+                print_info(&format!("The session ends in {} at most", remaining(session)));
+            }
+        }
+        (None, Some(session)) => print_info(&format!(
+            "The steps are still running; once they finish you have {} to connect (the session ends in {} at most)",
+            format_duration(ssh::IDLE_TIMEOUT.num_milliseconds() as u64),
+            remaining(session)
+        )),
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Options for the `diff-config` command.
+#[derive(Debug)]
+struct DiffConfigOptions {
+    /// Compare the committed config instead of the compiled one.
+    source: bool,
+    /// Unchanged lines shown around each change.
+    context: usize,
+    /// Print the differences as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Prints a unified diff of the CI configuration two builds ran with,
+/// after the executor, image, and command changes in it.
+///
+/// # Errors
+///
+/// Returns an error if a URL can't be parsed, a build didn't run in a
+/// pipeline, or a configuration can't be fetched.
+async fn diff_config(
+    old_url: &str,
+    new_url: &str,
+    options: &DiffConfigOptions,
+    progress: &Progress,
+) -> Result<()> {
+    let (old_org, old_project, old_num) = parse_circleci_url(old_url)?;
+    let (new_org, new_project, new_num) = parse_circleci_url(new_url)?;
+    let client = circle_client(options.no_cache)?;
+
+    let spinner = progress.spinner("Fetching pipeline configs...");
+    let (old, new) = futures::future::join(
+        client.build_config(&old_org, &old_project, old_num),
+        client.build_config(&new_org, &new_project, new_num),
+    )
+    .await;
+    spinner.finish_and_clear();
+    let ((old_pipeline, old), (new_pipeline, new)) = (old?, new?);
+    let (old_config, new_config) = if options.source {
+        (&old.source, &new.source)
+    } else {
+        (&old.compiled, &new.compiled)
+    };
+    let diff = ci_config::diff(old_config, new_config, options.context);
+    if options.json {
+        print_json(&diff)?;
+        return Ok(());
+    }
+
+    let kind = if options.source { "Source" } else { "Compiled" };
+    print_header(&format!(
+        "{} CI Config: #{} (pipeline {}) → #{} (pipeline {})",
+        kind, old_num, old_pipeline, new_num, new_pipeline
+    ));
+    if diff.hunks.is_empty() {
+        if old_pipeline == new_pipeline {
+            print_success("No differences: both builds ran in the same pipeline");
+        } else {
+            print_success("No differences");
+        }
+        return Ok(());
+    }
+
+    let notable: Vec<&ci_config::DiffLine> = diff.notable().collect();
+    if !notable.is_empty() {
+        print_header("Executor, Image, and Command Changes");
+        for line in notable {
+            let label = match line.notable {
+                Some(Notable::Executor) => "executor",
+                Some(Notable::Image) => "image",
+                Some(Notable::Command) | None => "command",
+            };
+            let text = line.text.trim();
+            let text = match line.change {
+                LineChange::Removed => format!("- {}", text).error(),
+                _ => format!("+ {}", text).success(),
+            };
+            println!("  {:<8} {}", label.dim(), text);
+        }
+    }
+
+    print_header("Diff");
+    println!("{}", format!("--- #{}", old_num).error());
+    println!("{}", format!("+++ #{}", new_num).success());
+    for hunk in &diff.hunks {
+        println!("{}", hunk.header().cyan());
+        for line in &hunk.lines {
+            let text = match line.change {
+                LineChange::Context => format!(" {}", line.text).normal(),
+                LineChange::Removed => format!("-{}", line.text).error(),
+                LineChange::Added => format!("+{}", line.text).success(),
+            };
+            if line.notable.is_some() {
+                println!("{}", text.bold());
+            } else {
+                println!("{}", text);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Options for the `nightly` command.
+#[derive(Debug)]
+struct NightlyCommandOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Only this workflow.
+    workflow: Option<String>,
+    /// Only pipelines on this branch.
+    branch: Option<String>,
+    /// How far back to look, as a duration like `28d`.
+    last: String,
+    /// Skip analyzing the latest failed run.
+    no_analyze: bool,
+    /// Print the summary as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Prints the results of a project's scheduled runs week by week, then
+/// analyzes the failed job of the newest failed run with `build_options`.
+///
+/// # Returns
+///
+/// The exit code: the analysis's when the newest finished run failed,
+/// success otherwise.
+///
+/// # Errors
+///
+/// Returns an error if the repository can't be determined, `last` isn't a
+/// duration, or the pipelines can't be listed.
+async fn show_nightly(
+    options: &NightlyCommandOptions,
+    build_options: &BuildOptions,
+    progress: &Progress,
+) -> Result<ExitCode> {
+    let (org, project) = match &options.repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let window = circle_debug::parse_duration(&options.last)?;
+    let since = window_start(window);
+    let client = circle_client(options.no_cache)?;
+
+    let spinner = progress.spinner("Finding scheduled pipelines...");
+    let runs = client
+        .scheduled_runs(
+            &org,
+            &project,
+            &NightlyQuery {
+                workflow: options.workflow.clone(),
+                branch: options.branch.clone(),
+                since,
+            },
+        )
+        .await;
+    spinner.finish_and_clear();
+    let summary = NightlySummary::new(runs?, since);
+    if options.json {
+        print_json(&summary)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let name = options.workflow.as_deref().unwrap_or("Scheduled runs");
+    print_header(&format!(
+        "{}: {}/{} (last {})",
+        name, org, project, options.last
+    ));
+    if summary.runs.is_empty() {
+        print_warning(&format!(
+            "No scheduled pipelines{} in the last {}",
+            options
+                .workflow
+                .as_ref()
+                .map(|w| format!(" ran '{}'", w))
+                .unwrap_or_default(),
+            options.last
+        ));
+        print_info(
+            "Only pipelines started by a schedule are included; check the workflow name and --last",
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    match summary.pass_rate() {
+        Some(rate) => print_info(&format!(
+            "{} run(s): {} passed, {} failed ({:.0}% passing)",
+            summary.runs.len(),
+            summary.passed,
+            summary.failed,
+            rate * 100.0
+        )),
+        None => print_info(&format!("{} run(s), none finished", summary.runs.len())),
+    }
+    for week in &summary.weeks {
+        let other = if week.other > 0 {
+            format!(", {} other", week.other)
+        } else {
+            String::new()
+        };
+        println!(
+            "  Week of {}  {}{}{}",
+            week.week_of.format("%b %d"),
+            "✓".repeat(week.passed).success(),
+            "✗".repeat(week.failed).error(),
+            format!(
+                "  ({} passed, {} failed{})",
+                week.passed, week.failed, other
+            )
+            .dim()
+        );
+    }
+
+    print_header("Recent Runs");
+    for run in summary.runs.iter().take(10) {
+        let mark = if run.is_passed() {
+            "✓".success()
+        } else if run.is_failed() {
+            "✗".error()
+        } else {
+            "•".warning()
+        };
+        let age = run
+            .created_at
+            .map(|at| format_age(chrono::Utc::now() - at))
+            .unwrap_or_default();
+        println!(
+            "  {} pipeline #{} {} {} {}",
+            mark,
+            run.pipeline_number,
+            run.workflow_name,
+            run.status,
+            format!(
+                "({}{})",
+                age,
+                run.branch
+                    .as_ref()
+                    .map(|b| format!(", {}", b))
+                    .unwrap_or_default()
+            )
+            .dim()
+        );
+    }
+
+    if summary.failing_streak > 0 {
+        print_error(&format!(
+            "Failing: the last {} finished run(s) failed",
+            summary.failing_streak
+        ));
+    } else {
+        print_success("The newest finished run passed");
+    }
+
+    let Some(failure) = summary.latest_failure() else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if options.no_analyze {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let spinner = progress.spinner("Finding the failed job...");
+    let jobs = client.get_workflow_jobs(&failure.workflow_id).await;
+    spinner.finish_and_clear();
+    let Some(job_number) = jobs?
+        .iter()
+        .filter(|job| job.status == "failed" || job.status == "infrastructure_fail")
+        .find_map(|job| job.job_number)
+    else {
+        print_warning(&format!(
+            "Pipeline #{} failed without a failed job to analyze",
+            failure.pipeline_number
+        ));
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    print_header(&format!(
+        "Latest Failure: pipeline #{}",
+        failure.pipeline_number
+    ));
+    let outcome = analyze_build(
+        &circle_debug::job_web_url(&org, &project, job_number),
+        build_options,
+        progress,
+    )
+    .await?;
+    if summary.failing_streak > 0 {
+        Ok(FailOn::Failed.exit_code(&outcome))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Options for the `size` command.
+#[derive(Debug)]
+struct SizeCommandOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// The artifact and which builds to look at.
+    query: SizeQuery,
+    /// Growth, in percent, that flags a build.
+    threshold: f64,
+    /// Print the sizes as JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Prints an artifact's size across recent builds as a sparkline and a
+/// table, oldest first, flagging the builds where it jumped.
+///
+/// # Errors
+///
+/// Returns an error if the repository can't be determined, the glob is
+/// invalid, or the builds can't be listed.
+async fn show_sizes(options: &SizeCommandOptions, progress: &Progress) -> Result<()> {
+    let (org, project) = match &options.repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner("Looking up artifact sizes...");
+    let points = client.artifact_sizes(&org, &project, &options.query).await;
+    spinner.finish_and_clear();
+    let points = points?;
+    let jumps = size::size_jumps(&points, options.threshold);
+    if options.json {
+        let json = serde_json::json!({ "builds": points, "jumps": jumps });
+        print_json(&json)?;
+        return Ok(());
+    }
+
+    print_header(&format!("Size: {}", options.query.artifact));
+    print_info(&format!("Repository: {}/{}", org, project));
+    if points.is_empty() {
+        print_warning(&format!(
+            "None of the last {} builds stored an artifact matching '{}'",
+            options.query.builds, options.query.artifact
+        ));
+        return Ok(());
+    }
+    let sizes: Vec<u64> = points.iter().map(|point| point.bytes).collect();
+    let (first, last) = (sizes[0], sizes[sizes.len() - 1]);
+    println!(
+        "\n  {}  {} → {} ({})\n",
+        sparkline(&sizes).cyan(),
+        format_bytes(first),
+        format_bytes(last),
+        format_bytes_delta(last as i64 - first as i64)
+    );
+    for point in &points {
+        let jump = jumps.iter().find(|jump| jump.build_num == point.build_num);
+        let when = point
+            .start_time
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let size = format!("{:>10}", format_bytes(point.bytes));
+        let line = format!(
+            "#{:<8} {} {:<8} {:<20}",
+            point.build_num,
+            when,
+            point.revision.as_deref().unwrap_or(""),
+            point.branch.as_deref().unwrap_or("")
+        );
+        match jump {
+            Some(jump) => println!(
+                "  {} {} {}",
+                line,
+                size.error().bold(),
+                format!("+{:.1}% since #{}", jump.percent, jump.previous_build).error()
+            ),
+            None => println!("  {} {}", line.dim(), size),
+        }
+    }
+    if !jumps.is_empty() {
+        print_warning(&format!(
+            "{} build{} grew the artifact by more than {}%",
+            jumps.len(),
+            if jumps.len() == 1 { "" } else { "s" },
+            options.threshold
+        ));
+    }
+    Ok(())
+}
+
+/// Prints the estimated wall time and credits of a test job at each
+/// parallelism, from a build's test timings, and the recommended
+/// parallelism and split.
+async fn suggest_parallelism(
+    url: &str,
+    max: u32,
+    json: bool,
+    no_cache: bool,
+    progress: &Progress,
 ) -> Result<()> {
-    print_header("Analyzing CircleCI Build");
-
     let (org, project, build_num) = parse_circleci_url(url)?;
-    print_info(&format!("Organization: {}", org));
-    print_info(&format!("Project: {}", project));
-    print_info(&format!("Build Number: {}", build_num));
-
-    let client = CircleClient::new()?;
-
-    println!("\n{}", "Fetching build details...".dimmed());
-    let build = client.get_build(&org, &project, build_num).await?;
+    let client = circle_client(no_cache)?;
+    let spinner = progress.spinner("Fetching test timings...");
+    let fetched = futures::try_join!(
+        client.get_build(&org, &project, build_num),
+        client.test_timings(&org, &project, build_num)
+    );
+    spinner.finish_and_clear();
+    let (build, tests) = fetched?;
+    let advice = parallelism::advise(&build, &tests, max);
+    if json {
+        return print_json(&advice);
+    }
 
-    print_header("Build Summary");
+    print_header(&format!("Parallelism: #{}", build_num));
+    let Some(advice) = advice else {
+        print_warning(
+            "No test timings: the build stored no test results (store_test_results) and no JUnit XML artifacts",
+        );
+        return Ok(());
+    };
     print_info(&format!(
-        "Status: {}",
-        if build.status == "failed" {
-            build.status.red().to_string()
+        "{} tests in {} files, {} in all; {} of setup per container on {}",
+        advice.tests,
+        advice.files,
+        format_duration(advice.test_millis),
+        format_duration(advice.setup_millis),
+        advice.resource_class
+    ));
+    println!(
+        "\n  {:>10}  {:>10}  {:>8}",
+        "Containers".bold(),
+        "Wall time".bold(),
+        "Credits".bold()
+    );
+    for estimate in &advice.estimates {
+        let credits = estimate
+            .credits
+            .map(|credits| format!("{:.0}", credits))
+            .unwrap_or_else(|| "?".to_string());
+        let line = format!(
+            "  {:>10}  {:>10}  {:>8}",
+            estimate.parallelism,
+            format_duration(estimate.wall_millis),
+            credits
+        );
+        let current = estimate.parallelism == advice.current;
+        if estimate.parallelism == advice.recommended {
+            let mark = if current {
+                "current, recommended"
+            } else {
+                "recommended"
+            };
+            println!("{}  {}", line.success().bold(), mark.success());
+        } else if current {
+            println!("{}  current", line);
+        } else {
+            println!("{}", line.dim());
+        }
+    }
+
+    if let (Some(recommended), Some(current)) = (
+        advice.estimate(advice.recommended),
+        advice.estimate(advice.current),
+    ) {
+        let credits = match (recommended.credits, current.credits) {
+            (Some(new), Some(old)) => format!(", {:.0} credits instead of {:.0}", new, old),
+            _ => String::new(),
+        };
+        if advice.recommended == advice.current {
+            print_success(&format!(
+                "parallelism: {} is about right; ~{} per run{}",
+                advice.current,
+                format_duration(recommended.wall_millis),
+                credits
+            ));
         } else {
-            build.status.green().to_string()
+            print_success(&format!(
+                "Set parallelism: {}; ~{} per run instead of {}{}",
+                advice.recommended,
+                format_duration(recommended.wall_millis),
+                format_duration(current.wall_millis),
+                credits
+            ));
         }
+    }
+    print_info(&format!(
+        "Split with: circleci tests run --split-by={}",
+        advice.split_by
     ));
+    if let Some(slowest) = advice
+        .slowest_file
+        .as_ref()
+        .filter(|file| file.millis * advice.recommended as u64 > advice.test_millis)
+    {
+        print_warning(&format!(
+            "{} takes {} on its own, more than a container's share; split it to go faster",
+            slowest.file,
+            format_duration(slowest.millis)
+        ));
+    }
+    Ok(())
+}
 
-    if let Some(branch) = &build.branch {
-        print_info(&format!("Branch: {}", branch));
+/// Formats a byte count, e.g. "1.2 MB".
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
     }
+}
 
-    if let Some(subject) = &build.subject {
-        print_info(&format!("Commit: {}", subject));
+/// Formats a change in bytes with its sign, e.g. "+12.0 KB".
+fn format_bytes_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_bytes(delta.unsigned_abs()))
+}
+
+/// Options for the `compare-branch` command.
+#[derive(Debug)]
+struct CompareBranchOptions {
+    /// Repository as `org/repo`; detected from the git origin when unset.
+    repo: Option<String>,
+    /// Branch to compare against.
+    base: String,
+    /// Branch to compare; the checked-out branch when unset.
+    branch: Option<String>,
+    /// Number of recent builds of each branch.
+    builds: u32,
+    /// Only builds of this workflow job.
+    job: Option<String>,
+    /// Percent slowdown to report.
+    threshold: f64,
+    /// Print JSON.
+    json: bool,
+    /// Skip the on-disk response cache.
+    no_cache: bool,
+}
+
+/// Compares step timings between a branch and its base and prints the
+/// steps consistently slower on the branch.
+///
+/// # Returns
+///
+/// Whether any step is slower by at least the threshold.
+///
+/// # Errors
+///
+/// Returns an error if the branch can't be determined or either branch's
+/// builds can't be listed.
+async fn compare_branch(options: &CompareBranchOptions, progress: &Progress) -> Result<bool> {
+    let (org, project) = match &options.repo {
+        Some(repo) => split_repo(repo)?,
+        None => git::origin_repo()?,
+    };
+    let branch = match &options.branch {
+        Some(branch) => branch.clone(),
+        None => git::current_branch().context("Pass the branch to compare with --branch")?,
+    };
+    if branch == options.base {
+        anyhow::bail!(
+            "'{}' is the base branch; check out or pass another with --branch",
+            branch
+        );
+    }
+    let query = CompareQuery {
+        base: options.base.clone(),
+        branch,
+        builds: options.builds,
+        job: options.job.clone(),
+    };
+    let client = circle_client(options.no_cache)?;
+    let spinner = progress.spinner("Fetching step timings of both branches...");
+    let comparison = client.compare_branches(&org, &project, &query).await;
+    spinner.finish_and_clear();
+    let comparison = comparison?;
+    let slower = comparison.slower_steps(options.threshold);
+    if options.json {
+        let json = serde_json::json!({
+            "comparison": comparison,
+            "slower": slower,
+        });
+        print_json(&json)?;
+        return Ok(!slower.is_empty());
     }
 
-    let failed_steps: Vec<_> = build
+    print_header(&format!(
+        "Step Timings: {} vs {}",
+        comparison.branch, comparison.base
+    ));
+    print_info(&format!(
+        "Repository: {}/{} — {} build(s) of {}, {} of {}",
+        org,
+        project,
+        comparison.branch_builds.len(),
+        comparison.branch,
+        comparison.base_builds.len(),
+        comparison.base
+    ));
+    if comparison.branch_builds.is_empty() || comparison.base_builds.is_empty() {
+        print_warning(&format!(
+            "No finished builds of {}{} to compare",
+            if comparison.branch_builds.is_empty() {
+                &comparison.branch
+            } else {
+                &comparison.base
+            },
+            options
+                .job
+                .as_deref()
+                .map(|job| format!(" running {}", job))
+                .unwrap_or_default()
+        ));
+        return Ok(false);
+    }
+    if comparison.steps.is_empty() {
+        print_warning("No step passed on both branches");
+        return Ok(false);
+    }
+
+    println!();
+    let width = comparison
         .steps
         .iter()
-        .filter(|step| {
-            step.actions
-                .iter()
-                .any(|action| action.failed.unwrap_or(false))
-        })
-        .collect();
-
-    if !failed_steps.is_empty() {
-        print_header("Failed Steps");
-
-        for step in failed_steps {
-            println!("\n{} {}", "▸".red().bold(), step.name.bold());
-
-            for action in &step.actions {
-                if action.failed.unwrap_or(false) {
-                    print_error(&format!("  {}", action.name));
-
-                    if let Some(output_url) = &action.output_url {
-                        if !no_fetch {
-                            println!("\n  {}", "Fetching logs...".dimmed());
-                            match client.get_logs(output_url).await {
-                                Ok(logs) => {
-                                    // Strip ANSI escape codes
-                                    let ansi_re = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
-                                    let clean_logs = ansi_re.replace_all(&logs, "");
-
-                                    // Always save to temp file for fallback
-                                    let auto_save_path = format!("/tmp/cdb-{}.log", build_num);
-                                    std::fs::write(&auto_save_path, clean_logs.as_ref())?;
-                                    println!(
-                                        "\n  {}",
-                                        format!("Auto-saved full logs to: {}", auto_save_path)
-                                            .dimmed()
-                                    );
-
-                                    // Save to custom file if requested
-                                    if let Some(ref output_path) = output_file {
-                                        std::fs::write(output_path, clean_logs.as_ref())?;
-                                        println!(
-                                            "  {}",
-                                            format!("Logs also saved to: {}", output_path).green()
-                                        );
-                                    }
-
-                                    // Apply filter if specified
-                                    let filtered_logs = if let Some(ref filter_text) = filter {
-                                        let filtered: String = clean_logs
-                                            .lines()
-                                            .filter(|line| line.contains(filter_text))
-                                            .collect::<Vec<_>>()
-                                            .join("\n");
-
-                                        if filtered.is_empty() {
-                                            println!(
-                                                "  {}",
-                                                format!(
-                                                    "No lines matching filter: '{}'",
-                                                    filter_text
-                                                )
-                                                .yellow()
-                                            );
-                                            clean_logs.clone()
-                                        } else {
-                                            let original_lines = clean_logs.lines().count();
-                                            let filtered_lines = filtered.lines().count();
-                                            println!(
-                                                "  {}",
-                                                format!(
-                                                    "Filter '{}': {} of {} lines",
-                                                    filter_text, filtered_lines, original_lines
-                                                )
-                                                .cyan()
-                                            );
-                                            filtered.into()
-                                        }
-                                    } else {
-                                        clean_logs.clone()
-                                    };
-
-                                    let total_lines = filtered_logs.lines().count();
-                                    println!(
-                                        "  {}",
-                                        format!(
-                                            "Total: {} lines, {} KB",
-                                            total_lines,
-                                            logs.len() / 1024
-                                        )
-                                        .dimmed()
-                                    );
-
-                                    if full_logs {
-                                        // Show full logs
-                                        println!(
-                                            "\n  {}",
-                                            "=== FULL LOG OUTPUT ===".yellow().bold()
-                                        );
-                                        println!("{}", filtered_logs);
-                                    } else if let Some(n) = tail_lines {
-                                        // Show only last N lines
-                                        let lines: Vec<_> = filtered_logs.lines().collect();
-                                        let start =
-                                            if lines.len() > n { lines.len() - n } else { 0 };
-                                        println!(
-                                            "\n  {}",
-                                            format!("=== LAST {} LINES ===", n).yellow().bold()
-                                        );
-                                        for line in lines.iter().skip(start) {
-                                            println!("{}", line);
-                                        }
-                                    } else {
-                                        // DEFAULT: Smart detection + last 50 lines
-                                        println!(
-                                            "\n  {}",
-                                            "=== SMART ERROR DETECTION ===".blue().bold()
-                                        );
-
-                                        // Find known error patterns
-                                        let error_patterns = vec![
-                                            // High confidence - specific errors
-                                            (
-                                                r"(?i)\[commonjs--resolver\].*failed to resolve",
-                                                "Module Resolution",
-                                            ),
-                                            (r"(?i)cannot find module", "Missing Module"),
-                                            (
-                                                r"(?i)ENOENT:.*no such file or directory",
-                                                "File Not Found",
-                                            ),
-                                            (r"(?i)syntaxerror:", "Syntax Error"),
-                                            (r"(?i)typeerror:", "Type Error"),
-                                            (r"(?i)referenceerror:", "Reference Error"),
-                                            (r"(?i)segmentation fault", "Segfault"),
-                                            (
-                                                r"(?i)(oom|out of memory|memory limit)",
-                                                "Out of Memory",
-                                            ),
-                                            // Build & compilation
-                                            (r"(?i)build failed", "Build Failure"),
-                                            (r"(?i)compilation failed", "Compilation Error"),
-                                            (r"(?i)error TS\d+:", "TypeScript Error"),
-                                            (r"(?i)eslint.*error", "Lint Error"),
-                                            // Test failures
-                                            (r"(?i)test.*failed", "Test Failure"),
-                                            (r"(?i)assertion.*failed", "Assertion Failure"),
-                                            (
-                                                r"(?i)\d+ (test|tests|spec|specs) failed",
-                                                "Test Suite Failure",
-                                            ),
-                                            // Package & dependency
-                                            (r"(?i)npm err!", "NPM Error"),
-                                            (r"(?i)yarn error", "Yarn Error"),
-                                            (r"(?i)dependency.*not found", "Missing Dependency"),
-                                            // Exit indicators
-                                            (
-                                                r"(?i)exited with (code|status) [1-9]",
-                                                "Non-zero Exit",
-                                            ),
-                                            (r"(?i)command failed", "Command Failure"),
-                                        ];
-
-                                        let mut found_errors = Vec::new();
-                                        let mut error_line_numbers = Vec::new();
-                                        for (pattern, category) in error_patterns {
-                                            let re = Regex::new(pattern).unwrap();
-                                            for (line_num, line) in
-                                                filtered_logs.lines().enumerate()
-                                            {
-                                                if re.is_match(line) {
-                                                    found_errors.push((
-                                                        category,
-                                                        line,
-                                                        line_num + 1,
-                                                    ));
-                                                    error_line_numbers.push(line_num + 1);
-                                                    if found_errors.len() >= 5 {
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                            if found_errors.len() >= 5 {
-                                                break;
-                                            }
-                                        }
-
-                                        if !found_errors.is_empty() {
-                                            println!(
-                                                "  Found {} error pattern(s):",
-                                                found_errors.len()
-                                            );
-                                            for (category, line, line_num) in
-                                                found_errors.iter().take(5)
-                                            {
-                                                // Highlight with background color for better visibility
-                                                println!(
-                                                    "  {} {} {}",
-                                                    format!("[{}]", category).red().bold(),
-                                                    format!("Line {}:", line_num)
-                                                        .bright_red()
-                                                        .bold(),
-                                                    line.trim().on_red().white().bold()
-                                                );
-
-                                                // Add contextual suggestions based on error type
-                                                match category.as_ref() {
-                                                    "File Not Found" => {
-                                                        if line.contains("README")
-                                                            || line.contains("readme")
-                                                        {
-                                                            println!("  {} Suggestion: Check file case sensitivity (README.md vs readme.md)", "💡".yellow());
-                                                        } else if line.contains("package.json") {
-                                                            println!("  {} Suggestion: Run 'npm install' to ensure dependencies are installed", "💡".yellow());
-                                                        } else {
-                                                            println!("  {} Suggestion: Verify file exists and path is correct", "💡".yellow());
-                                                        }
-                                                    }
-                                                    "Missing Module" | "Missing Dependency" => {
-                                                        println!("  {} Suggestion: Run 'npm install' or check package.json dependencies", "💡".yellow());
-                                                    }
-                                                    "TypeScript Error" => {
-                                                        println!("  {} Suggestion: Run 'npm run typecheck' locally to see full type errors", "💡".yellow());
-                                                    }
-                                                    "Lint Error" => {
-                                                        println!("  {} Suggestion: Run 'npm run lint -- --fix' to auto-fix some issues", "💡".yellow());
-                                                    }
-                                                    "Test Failure" | "Test Suite Failure" => {
-                                                        println!("  {} Suggestion: Run tests locally with '--verbose' for more details", "💡".yellow());
-                                                    }
-                                                    "Out of Memory" => {
-                                                        println!("  {} Suggestion: Increase Node memory: NODE_OPTIONS='--max-old-space-size=4096'", "💡".yellow());
-                                                    }
-                                                    "NPM Error" | "Yarn Error" => {
-                                                        println!("  {} Suggestion: Clear cache (npm cache clean --force) and reinstall", "💡".yellow());
-                                                    }
-                                                    _ => {}
-                                                }
-                                            }
-                                        } else {
-                                            println!(
-                                                "  {}",
-                                                "No specific error patterns detected".yellow()
-                                            );
-                                        }
-
-                                        // Always show last 50 lines
-                                        let show_lines = 50;
-                                        let lines: Vec<_> = filtered_logs.lines().collect();
-                                        let start = if lines.len() > show_lines {
-                                            lines.len() - show_lines
-                                        } else {
-                                            0
-                                        };
-
-                                        println!(
-                                            "\n  {}",
-                                            format!(
-                                                "=== LAST {} LINES (BUILD EXIT ZONE) ===",
-                                                show_lines
-                                            )
-                                            .yellow()
-                                            .bold()
-                                        );
-                                        for (i, line) in lines.iter().skip(start).enumerate() {
-                                            let line_num = start + i + 1;
-                                            let trimmed = line.trim();
-
-                                            // Check if this line was identified as an error in smart detection
-                                            let is_detected_error =
-                                                error_line_numbers.contains(&line_num);
-
-                                            // Highlight error-like lines with enhanced visibility
-                                            if is_detected_error {
-                                                // Lines detected by smart detection get special highlighting
-                                                println!(
-                                                    "{:5} {} {}",
-                                                    format!("{}", line_num).bright_red().bold(),
-                                                    "►".bright_red().bold(),
-                                                    trimmed.on_red().white().bold()
-                                                );
-                                            } else if trimmed.to_lowercase().contains("error")
-                                                || trimmed.to_lowercase().contains("failed")
-                                                || trimmed.contains("✗")
-                                                || trimmed.contains("FAIL")
-                                            {
-                                                println!(
-                                                    "{:5} │ {}",
-                                                    line_num,
-                                                    trimmed.red().bold()
-                                                );
-                                            } else if trimmed.to_lowercase().contains("warn") {
-                                                println!("{:5} │ {}", line_num, trimmed.yellow());
-                                            } else {
-                                                println!("{:5} │ {}", line_num, trimmed.dimmed());
-                                            }
-                                        }
-
-                                        // Help text for next steps
-                                        println!(
-                                            "\n  {}",
-                                            "=== DIDN'T FIND YOUR ERROR? ===".cyan().bold()
-                                        );
-                                        println!(
-                                            "  {}",
-                                            "• Use --full to see complete logs".cyan()
-                                        );
-                                        println!(
-                                            "  {}",
-                                            "• Use --tail 100 to see more context".cyan()
-                                        );
-                                        println!(
-                                            "  {}",
-                                            format!("• Full logs saved at: {}", auto_save_path)
-                                                .cyan()
-                                        );
-                                        println!("  {}", "• For AI: If error not found above, rerun with --full flag".cyan().bold());
-                                    }
-                                }
-                                Err(e) => print_error(&format!("  Failed to fetch logs: {}", e)),
-                            }
-                        } else {
-                            println!("\n  {}", "=== LOG FETCHING SKIPPED ===".yellow().bold());
-                            println!("  View logs directly at:");
-                            println!("  {}", output_url.blue().underline());
-                        }
-                    }
-                }
-            }
-        }
-    } else {
-        print_success("No failed steps found");
+        .map(|step| step.job.len() + step.name.len() + 3)
+        .max()
+        .unwrap_or(0);
+    for step in &comparison.steps {
+        let growth = step.growth_millis();
+        let delta = if growth < 0 {
+            format!("-{}", format_duration(growth.unsigned_abs())).success()
+        } else if step.is_slower(options.threshold) {
+            format!("+{}", format_duration(growth as u64)).error()
+        } else {
+            format!("+{}", format_duration(growth as u64)).dim()
+        };
+        let percent = step
+            .growth_percent()
+            .map(|percent| format!(" ({:+.0}%)", percent))
+            .unwrap_or_default();
+        println!(
+            "  {:<width$}  {:>8} → {:>8}  {}{}",
+            format!("{} › {}", step.job, step.name),
+            format_duration(step.base_millis),
+            format_duration(step.branch_millis),
+            delta,
+            percent,
+            width = width
+        );
     }
+    println!();
 
-    // Add timing analysis
-    print_header("Timing Analysis");
-    let mut step_timings: Vec<(&str, u64)> = Vec::new();
-    let mut total_time = 0u64;
-
-    for step in &build.steps {
-        let step_time: u64 = step.actions.iter().filter_map(|a| a.run_time_millis).sum();
-        if step_time > 0 {
-            step_timings.push((&step.name, step_time));
-            total_time += step_time;
+    if slower.is_empty() {
+        print_success(&format!(
+            "No step is consistently {:.0}% or more slower on {}",
+            options.threshold, comparison.branch
+        ));
+    } else {
+        for step in &slower {
+            print_error(&format!(
+                "{} › {} is slower on {} in all {} build(s): {} → {}",
+                step.job,
+                step.name,
+                comparison.branch,
+                step.branch_runs,
+                format_duration(step.base_millis),
+                format_duration(step.branch_millis)
+            ));
         }
     }
+    Ok(!slower.is_empty())
+}
 
-    // Sort by duration (longest first)
-    step_timings.sort_by(|a, b| b.1.cmp(&a.1));
+/// Searches the full-text log index instead of the API, if one has been
+/// built and the search needs nothing but cached logs.
+///
+/// # Errors
+///
+/// Returns an error if the index exists but can't be read.
+#[cfg(feature = "index")]
+fn search_index(
+    org: &str,
+    project: &str,
+    regex: &Regex,
+    options: &SearchCommandOptions,
+) -> Result<Option<SearchResults>> {
+    if options.search.fetch || options.no_cache {
+        return Ok(None);
+    }
+    let Some(index) = LogIndex::open_existing(&LogIndex::default_dir())? else {
+        return Ok(None);
+    };
+    let cache = ResponseCache::new(ResponseCache::default_dir());
+    index
+        .search(&cache, org, project, regex, &options.search)
+        .map(Some)
+}
 
-    if !step_timings.is_empty() {
-        println!("Total build time: {}", format_duration(total_time));
-        println!("\nSlowest steps:");
-        for (i, (name, duration)) in step_timings.iter().take(5).enumerate() {
-            let percentage = (*duration as f64 / total_time as f64 * 100.0) as u32;
-            let duration_str = format_duration(*duration);
+/// Without the `index` feature there is no index to search.
+#[cfg(not(feature = "index"))]
+fn search_index(
+    _org: &str,
+    _project: &str,
+    _regex: &Regex,
+    _options: &SearchCommandOptions,
+) -> Result<Option<SearchResults>> {
+    Ok(None)
+}
 
-            // Color code based on duration
-            let formatted = if *duration > 60000 {
-                // > 1 minute
-                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).red()
-            } else if *duration > 30000 {
-                // > 30 seconds
-                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).yellow()
-            } else {
-                format!("{}. {} - {} ({}%)", i + 1, name, duration_str, percentage).green()
+/// Runs a `cdb cache` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the search pattern, repository, or `--older-than`
+/// duration is invalid, cached entries can't be removed, or the index
+/// can't be opened or written.
+fn manage_cache(command: CacheCommand, query: bool, progress: &Progress) -> Result<()> {
+    match command {
+        CacheCommand::Search {
+            pattern,
+            repo,
+            branch,
+            last,
+            context,
+            json,
+        } => {
+            let search = SearchOptions {
+                builds: last.unwrap_or(u32::MAX),
+                branch,
+                context,
+                ..Default::default()
             };
-            println!("  {}", formatted);
+            search_cached_logs(&pattern, repo.as_deref(), &search, json || query, progress)
         }
-
-        // Identify bottlenecks
-        if let Some((slowest_name, slowest_time)) = step_timings.first() {
-            let percentage = (*slowest_time as f64 / total_time as f64 * 100.0) as u32;
-            if percentage > 50 {
-                println!(
-                    "\n{} Bottleneck detected: '{}' takes {}% of total time",
-                    "⚠".yellow(),
-                    slowest_name,
-                    percentage
-                );
-                println!("  Consider optimizing or parallelizing this step");
-            }
+        CacheCommand::Prune { max_mb, older_than } => {
+            let max_size = max_mb.map_or_else(cache_max_size, |mb| mb.saturating_mul(1024 * 1024));
+            let max_age = older_than
+                .as_deref()
+                .map(circle_debug::parse_duration)
+                .transpose()?;
+            let cache = ResponseCache::new(ResponseCache::default_dir());
+            let pruned = cache.prune(max_size, max_age)?;
+            print_success(&format!(
+                "Removed {} cached response(s), freeing {}; {} left",
+                pruned.entries,
+                format_bytes(pruned.bytes),
+                format_bytes(pruned.remaining)
+            ));
+            Ok(())
         }
-    } else {
-        println!("No timing data available for this build");
+        CacheCommand::Clear => {
+            let cache = ResponseCache::new(ResponseCache::default_dir());
+            cache.clear()?;
+            print_success(&format!("Cleared {}", cache.dir().display()));
+            Ok(())
+        }
+        #[cfg(feature = "index")]
+        CacheCommand::Index => update_index(false, progress),
+        #[cfg(feature = "index")]
+        CacheCommand::Reindex => update_index(true, progress),
     }
+}
 
-    print_header("Quick Actions");
-    println!("• Rerun: {}", format!("{}/retry", url).blue().underline());
-    println!("• SSH Debug: Click 'Rerun' → 'Rerun job with SSH' in CircleCI UI");
-    println!(
-        "• View artifacts: {}",
-        format!("{}/artifacts", url).blue().underline()
+/// Searches the logs of every cached build, optionally of one repository,
+/// and prints the matches grouped by project, build, and step.
+///
+/// # Errors
+///
+/// Returns an error if `pattern` isn't a valid regex or `repo` isn't in
+/// `org/repo` form.
+fn search_cached_logs(
+    pattern: &str,
+    repo: Option<&str>,
+    options: &SearchOptions,
+    json: bool,
+    progress: &Progress,
+) -> Result<()> {
+    let regex =
+        Regex::new(pattern).with_context(|| format!("Invalid search pattern '{}'", pattern))?;
+    let repo = repo.map(split_repo).transpose()?;
+    let cache = ResponseCache::new(ResponseCache::default_dir());
+
+    let spinner = progress.spinner("Searching cached logs...");
+    let results = search::search_cache(
+        &cache,
+        &regex,
+        options,
+        repo.as_ref()
+            .map(|(org, project)| (org.as_str(), project.as_str())),
     );
+    spinner.finish_and_clear();
+    if json {
+        print_json(&results)?;
+        return Ok(());
+    }
+
+    print_header(&format!("Cached Log Search: {}", pattern));
+    if let Some((org, project)) = &repo {
+        print_info(&format!("Repository: {}/{}", org, project));
+    }
+    print_search_hits(&results, options.context);
+
+    println!();
+    if results.builds_searched == 0 {
+        print_info("No finished builds are cached; analyze or search some builds first");
+    } else if results.hits.is_empty() {
+        print_info(&format!(
+            "No matches in {} cached log(s) of {} build(s)",
+            results.logs_searched, results.builds_searched
+        ));
+    } else {
+        print_success(&format!(
+            "Matched in {} of {} cached build(s)",
+            results.matching_builds(),
+            results.builds_searched
+        ));
+    }
+    if results.logs_not_cached > 0 {
+        print_info(&format!(
+            "{} log(s) of cached builds weren't cached themselves and were skipped",
+            results.logs_not_cached
+        ));
+    }
+    if results.logs_failed > 0 {
+        print_warning(&format!(
+            "{} log(s) couldn't be loaded",
+            results.logs_failed
+        ));
+    }
 
     Ok(())
 }
 
-/// Analyzes GitHub PR status and CircleCI checks.
-///
-/// Fetches and displays all CircleCI-related checks for a GitHub pull request.
-/// This function requires the GitHub CLI (`gh`) to be installed and authenticated.
-///
-/// # Arguments
-///
-/// * `pr_input` - Either a PR number (e.g., "123") or full GitHub PR URL
-/// * `repo` - Optional repository in format "org/repo". If not provided,
-///            attempts to detect from current directory
-///
-/// # Returns
-///
-/// A `Result` indicating success or containing an error.
+/// Adds cached builds to the log index, or rebuilds it from scratch.
 ///
 /// # Errors
 ///
-/// This function will return an error if:
-/// * The PR URL format is invalid
-/// * The repository cannot be determined and isn't specified
-/// * The GitHub CLI is not installed or not authenticated
-/// * The PR doesn't exist or is inaccessible
-/// * JSON parsing fails for PR details
-///
-/// # Examples
+/// Returns an error if the index can't be opened or written.
+#[cfg(feature = "index")]
+fn update_index(rebuild: bool, progress: &Progress) -> Result<()> {
+    let cache = ResponseCache::new(ResponseCache::default_dir());
+    let index = LogIndex::open(&LogIndex::default_dir())?;
+    let started = std::time::Instant::now();
+    let update = if rebuild {
+        let spinner = progress.spinner("Rebuilding the log index...");
+        let update = index.rebuild(&cache);
+        spinner.finish_and_clear();
+        update?
+    } else {
+        let spinner = progress.spinner("Indexing cached logs...");
+        let update = index.update(&cache);
+        spinner.finish_and_clear();
+        update?
+    };
+    print_success(&format!(
+        "Indexed {} log(s) of {} build(s) in {}; {} log(s) indexed in total",
+        update.logs,
+        update.builds,
+        format_duration(started.elapsed().as_millis() as u64),
+        index.len()
+    ));
+    Ok(())
+}
+
+/// Formats a build as "#123 abc1234 Subject (author)".
+fn describe_build(build: &BuildInfo) -> String {
+    let mut text = format!("#{}", build.build_num);
+    if let Some(sha) = build.short_revision() {
+        text.push_str(&format!(" {}", sha.warning()));
+    }
+    if let Some(subject) = &build.subject {
+        text.push_str(&format!(" {}", subject));
+    }
+    if let Some(author) = &build.author_name {
+        text.push_str(&format!(" ({})", author));
+    }
+    text
+}
+
+/// Returns the first 7 characters of a commit SHA.
+fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
+
+/// Most API requests in flight at once, from `--concurrency` or the
+/// config file; set once before any command runs.
+static CONCURRENCY: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+
+/// Bytes the response cache is kept under, from `cache_max_mb` in the
+/// config file; set once before any command runs.
+static CACHE_MAX_SIZE: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+
+/// Returns [`CACHE_MAX_SIZE`], or the default when it isn't configured.
+fn cache_max_size() -> u64 {
+    CACHE_MAX_SIZE
+        .get()
+        .copied()
+        .unwrap_or(circle_debug::cache::DEFAULT_MAX_SIZE)
+}
+
+/// Creates the API client, caching responses unless `no_cache` is set, and
+/// limiting concurrent requests to [`CONCURRENCY`].
 ///
-/// ```no_run
-/// # use anyhow::Result;
-/// # async fn example() -> Result<()> {
-/// // Using PR number with explicit repo
-/// analyze_pr("123", Some("myorg/myrepo".to_string())).await?;
+/// The cache is first pruned to [`cache_max_size`], so it stays bounded
+/// however many logs earlier commands stored.
 ///
-/// // Using full PR URL
-/// analyze_pr(
-///     "https://github.com/myorg/myrepo/pull/123",
-///     None
-/// ).await?;
+/// # Errors
 ///
-/// // Auto-detect repo from current directory
-/// analyze_pr("123", None).await?;
-/// # Ok(())
-/// # }
-/// ```
+/// Returns an error if `CIRCLECI_TOKEN` is not set.
+fn circle_client(no_cache: bool) -> Result<CircleClient> {
+    let client = CircleClient::new()?.with_concurrency(
+        CONCURRENCY
+            .get()
+            .copied()
+            .unwrap_or(circle_debug::DEFAULT_CONCURRENCY),
+    );
+    if no_cache {
+        return Ok(client);
+    }
+    let cache = ResponseCache::new(ResponseCache::default_dir());
+    match cache.prune(cache_max_size(), None) {
+        Ok(pruned) if pruned.entries > 0 => {
+            tracing::debug!(
+                entries = pruned.entries,
+                bytes = pruned.bytes,
+                "pruned cache"
+            )
+        }
+        Ok(_) => {}
+        Err(err) => tracing::debug!(error = %format!("{:#}", err), "failed to prune cache"),
+    }
+    Ok(client.with_cache(cache))
+}
+
+/// Verifies the CircleCI token and prints the user it belongs to.
 ///
-/// # See Also
+/// # Errors
 ///
-/// * [`analyze_build`] - Analyze specific failed builds from PR checks
-async fn analyze_pr(pr_input: Option<String>, repo: Option<String>) -> Result<()> {
-    print_header("Analyzing GitHub PR");
+/// Returns an authentication error if the token is missing, invalid, or
+/// expired, or a network error if CircleCI can't be reached.
+async fn check_auth(progress: &Progress) -> Result<()> {
+    print_header("CircleCI Authentication");
 
-    // Check if gh CLI is available
-    let gh_check = std::process::Command::new("which").arg("gh").output();
+    let client = CircleClient::new()?;
+    let spinner = progress.spinner("Checking token...");
+    let user = client.me().await;
+    spinner.finish_and_clear();
+    let user = user?;
 
-    if gh_check.is_err() || !gh_check.unwrap().status.success() {
-        eprintln!(
-            "{}",
-            "Error: GitHub CLI (gh) is not installed or not in PATH".red()
-        );
-        eprintln!("\nTo use the 'pr' command, you need to install GitHub CLI:");
-        eprintln!("  • macOS: brew install gh");
-        eprintln!("  • Linux: See https://cli.github.com/");
-        eprintln!("  • Windows: winget install GitHub.cli");
-        eprintln!("\nThen authenticate with: gh auth login");
-        eprintln!("\nNote: The 'build' command works without GitHub CLI");
-        bail!("GitHub CLI (gh) is required for PR analysis");
+    print_success("Token is valid");
+    print_info(&format!("Login: {}", user.login));
+    if let Some(name) = &user.name {
+        print_info(&format!("Name: {}", name));
     }
+    print_info(&format!("User ID: {}", user.id));
 
-    // Determine PR number - auto-detect if not provided
-    let pr_number = if let Some(input) = pr_input {
-        // Parse PR number from URL or use directly
-        if input.contains("github.com") {
-            // Extract from URL like https://github.com/org/repo/pull/123
-            input
-                .split('/')
-                .last()
-                .context("Invalid PR URL")?
-                .to_string()
-        } else {
-            input
-        }
-    } else {
-        // Auto-detect current PR using gh CLI
-        println!("{}", "Auto-detecting current PR...".dimmed());
-        let output = std::process::Command::new("gh")
-            .args(&["pr", "view", "--json", "number", "-q", ".number"])
-            .output()
-            .context("Failed to run 'gh pr view'. Is GitHub CLI installed and authenticated?")?;
+    Ok(())
+}
 
-        let pr_num = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if pr_num.is_empty() {
-            bail!("No PR found for current branch. Create a PR first or specify PR number explicitly.");
-        }
-        pr_num
+/// Splits an `org/repo` argument into its parts. A project slug such as
+/// `gh/org/repo` or `circleci/<org-id>/<project-id>` works too.
+///
+/// # Errors
+///
+/// Returns an error if `repo` has no `/`.
+fn split_repo(repo: &str) -> Result<(String, String)> {
+    let repo = ["gh/", "github/", "circleci/"]
+        .iter()
+        .find_map(|vcs| repo.strip_prefix(vcs).filter(|rest| rest.contains('/')))
+        .unwrap_or(repo);
+    repo.split_once('/')
+        .map(|(org, project)| (org.to_string(), project.to_string()))
+        .with_context(|| format!("Invalid repository '{}', expected org/repo", repo))
+}
+
+/// Returns when a `--last` window of `window` up to now started; windows
+/// reaching back further than chrono can count start at its earliest time.
+fn window_start(window: std::time::Duration) -> chrono::DateTime<chrono::Utc> {
+    chrono::Duration::from_std(window)
+        .ok()
+        .and_then(|window| chrono::Utc::now().checked_sub_signed(window))
+        .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC)
+}
+
+/// Prints trends over the builds in the local history.
+///
+/// # Errors
+///
+/// Returns an error if `last` isn't a duration, `repo` isn't `org/repo`,
+/// or the history database can't be read.
+fn show_stats(last: &str, repo: Option<&str>, json: bool) -> Result<()> {
+    let window = circle_debug::parse_duration(last)?;
+    let Some(store) = HistoryStore::open_existing(&HistoryStore::default_path())? else {
+        bail!("History is off; run `cdb history enable` and analyze some builds first");
     };
+    let (org, project) = repo.map(split_repo).transpose()?.unzip();
+    let stats = store.stats(&StatsQuery {
+        org,
+        project,
+        since: window_start(window),
+    })?;
+    if json {
+        print_json(&stats)?;
+        return Ok(());
+    }
 
-    print_info(&format!("PR Number: {}", pr_number));
+    print_header(&format!("Build Stats (last {})", last));
+    if stats.builds == 0 {
+        print_info("No builds recorded in this window");
+        return Ok(());
+    }
+    print_info(&format!(
+        "{} build(s), {} failed ({:.0}%)",
+        stats.builds,
+        stats.failed,
+        stats.failed as f64 / stats.builds as f64 * 100.0
+    ));
 
-    // Determine repository
-    let repository = if let Some(r) = repo {
-        r
-    } else {
-        // Try to get from current directory using gh
-        let output = std::process::Command::new("gh")
-            .args(&[
-                "repo",
-                "view",
-                "--json",
-                "nameWithOwner",
-                "-q",
-                ".nameWithOwner",
-            ])
-            .output();
+    print_header("Failure Rate by Job");
+    let width = stats.jobs.iter().map(|j| j.job.len()).max().unwrap_or(0);
+    for job in &stats.jobs {
+        let rate = format!("{:>4.0}%", job.failure_rate() * 100.0);
+        let rate = if job.failure_rate() >= 0.25 {
+            rate.error()
+        } else if job.failed > 0 {
+            rate.warning()
+        } else {
+            rate.success()
+        };
+        println!(
+            "  {:width$}  {}  {}",
+            job.job,
+            rate,
+            format!("{} of {} failed", job.failed, job.builds).dim()
+        );
+    }
 
-        match output {
-            Ok(out) => String::from_utf8_lossy(&out.stdout).trim().to_string(),
-            Err(_) => {
-                bail!("Could not determine repository. Please specify with --repo org/repo");
-            }
+    if !stats.categories.is_empty() {
+        print_header("Most Frequent Errors");
+        for category in stats.categories.iter().take(10) {
+            println!("  {:>4}×  {}", category.builds, category.category);
         }
-    };
+    }
 
-    if repository.is_empty() {
-        bail!("Could not determine repository. Please specify with --repo org/repo");
+    print_header("Time to Green");
+    match stats.time_to_green_millis {
+        Some(millis) => print_info(&format!(
+            "{} on average, over {} recover{}",
+            format_duration(millis).bold(),
+            stats.recoveries,
+            if stats.recoveries == 1 { "y" } else { "ies" }
+        )),
+        None => print_info("No red job went green again in this window"),
     }
 
-    print_info(&format!("Repository: {}", repository));
+    if !stats.growing_steps.is_empty() {
+        print_header("Slowest-Growing Steps");
+        for trend in stats.growing_steps.iter().take(5) {
+            println!(
+                "  {} › {}  {} → {}  {}",
+                trend.job,
+                trend.name,
+                format_duration(trend.before_millis),
+                format_duration(trend.after_millis),
+                format!("+{}", format_duration(trend.growth_millis() as u64)).error()
+            );
+        }
+    }
+    Ok(())
+}
 
-    // Get PR checks using gh CLI
-    println!("\n{}", "Fetching PR status checks...".dimmed());
+/// Returns the history database path if history is enabled.
+fn history_db() -> Option<PathBuf> {
+    Some(HistoryStore::default_path()).filter(|path| path.exists())
+}
 
-    let checks_output = std::process::Command::new("gh")
-        .args(&["pr", "checks", &pr_number, "--repo", &repository])
-        .output()
-        .context("Failed to run 'gh pr checks'. Is GitHub CLI installed and authenticated?")?;
+/// Most matched lines to show per pattern in `cdb patterns test`.
+const PATTERN_TEST_LINES: usize = 10;
 
-    // gh pr checks returns non-zero when there are failed checks, but still outputs data
-    let checks = if !checks_output.stdout.is_empty() {
-        String::from_utf8_lossy(&checks_output.stdout)
-    } else if !checks_output.stderr.is_empty() {
-        // Sometimes gh outputs to stderr even on success
-        String::from_utf8_lossy(&checks_output.stderr)
-    } else {
-        bail!("No output from gh pr checks command");
-    };
+/// Runs a `cdb patterns` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if a pattern doesn't compile, or the patterns file or
+/// the log can't be read.
+fn manage_patterns(command: PatternsCommand, config: &Config, query: bool) -> Result<()> {
+    match command {
+        PatternsCommand::List { json } => list_patterns(config, json || query),
+        PatternsCommand::Test {
+            file,
+            log,
+            builtin,
+            json,
+        } => {
+            let patterns = match &file {
+                Some(file) => Config::load(Some(file))?.configured_patterns()?,
+                None => config.configured_patterns()?,
+            };
+            let builtin = builtin.then_some(config.disabled_patterns.as_slice());
+            test_patterns(&patterns, &log, builtin, json || query)
+        }
+    }
+}
 
-    print_header("PR Status Checks");
+/// Prints every pattern smart detection uses, built-in ones first, with
+/// where each comes from, then the disabled ones' ids.
+///
+/// # Errors
+///
+/// Returns an error if a configured pattern doesn't compile.
+fn list_patterns(config: &Config, json: bool) -> Result<()> {
+    let builtin: HashSet<String> = patterns::builtin_patterns()
+        .into_iter()
+        .map(|pattern| pattern.id)
+        .collect();
+    let configured: HashSet<&str> = config.patterns.iter().map(|p| p.id.as_str()).collect();
+    let source = |id: &str| match (builtin.contains(id), configured.contains(id)) {
+        (true, true) => "config (replaces built-in)",
+        (false, true) => "config",
+        _ => "built-in",
+    };
+    let all = config.patterns()?;
 
-    // Parse and display CircleCI-specific checks
-    let mut circleci_checks = Vec::new();
-    let mut failed_checks = Vec::new();
+    if json {
+        let list: Vec<serde_json::Value> = all
+            .iter()
+            .map(|pattern| {
+                serde_json::json!({
+                    "id": pattern.id,
+                    "category": pattern.category,
+                    "regex": pattern.regex.as_str(),
+                    "severity": pattern.severity,
+                    "confidence": pattern.confidence,
+                    "kind": pattern.kind,
+                    "transient": pattern.transient,
+                    "source": source(&pattern.id),
+                })
+            })
+            .collect();
+        return print_json(&list);
+    }
 
-    for line in checks.lines() {
-        if line.contains("circleci") || line.contains("CircleCI") {
-            circleci_checks.push(line);
-            if line.contains("fail") || line.contains("✗") {
-                failed_checks.push(line);
-            }
+    print_header("Error Patterns");
+    let width = all.iter().map(|p| p.id.len()).max().unwrap_or(0);
+    for pattern in &all {
+        let mut details = format!(
+            "{}, {:.0}%, {}",
+            pattern.severity,
+            pattern.confidence * 100.0,
+            pattern.kind
+        );
+        if pattern.transient {
+            details.push_str(", transient");
         }
+        let origin = source(&pattern.id);
+        println!(
+            "  {:width$}  {} {}{}",
+            pattern.id.bold(),
+            pattern.category,
+            format!("({})", details).dim(),
+            if origin == "built-in" {
+                String::new()
+            } else {
+                format!(" [{}]", origin).cyan().to_string()
+            },
+            width = width
+        );
+        println!(
+            "  {:width$}  {}",
+            "",
+            pattern.regex.as_str().dim(),
+            width = width
+        );
     }
+    println!();
+    if !config.disabled_patterns.is_empty() {
+        print_info(&format!(
+            "Disabled: {}",
+            config.disabled_patterns.join(", ")
+        ));
+    }
+    print_info(&format!(
+        "{} pattern(s), {} from the config",
+        all.len(),
+        configured.len()
+    ));
+    Ok(())
+}
 
-    if circleci_checks.is_empty() {
-        println!("{}", "No CircleCI checks found on this PR".yellow());
-        println!("\nAll checks:");
-        println!("{}", checks);
+/// Runs `patterns` against a log and prints the lines each one matched,
+/// and which matched nothing.
+///
+/// With `builtin`, built-in patterns that match are reported too, except
+/// the disabled ids it holds.
+///
+/// # Errors
+///
+/// Returns an error if the log can't be read.
+fn test_patterns(
+    patterns: &[ErrorPattern],
+    log: &str,
+    builtin: Option<&[String]>,
+    json: bool,
+) -> Result<()> {
+    if patterns.is_empty() && builtin.is_none() {
+        bail!(
+            "No patterns to test; pass --file with [[patterns]] entries, or add some to the config"
+        );
+    }
+    let text = clean_log_input(&read_log_input(log)?);
+    let results: Vec<(&ErrorPattern, Option<ErrorGroup>)> = patterns
+        .iter()
+        .map(|pattern| {
+            let group = patterns::detect_errors(&text, std::slice::from_ref(pattern)).pop();
+            (pattern, group)
+        })
+        .collect();
+    let custom: HashSet<&str> = patterns.iter().map(|p| p.id.as_str()).collect();
+    let builtin_groups: Vec<ErrorGroup> = if let Some(disabled) = builtin {
+        let others: Vec<ErrorPattern> = patterns::builtin_patterns()
+            .into_iter()
+            .filter(|pattern| {
+                !custom.contains(pattern.id.as_str()) && !disabled.contains(&pattern.id)
+            })
+            .collect();
+        patterns::detect_errors(&text, &others)
     } else {
-        println!("Found {} CircleCI check(s):", circleci_checks.len());
-        println!();
-
-        for check in &circleci_checks {
-            if check.contains("fail") || check.contains("✗") {
-                println!("{}", check.red());
-            } else if check.contains("pass") || check.contains("✓") {
-                println!("{}", check.green());
-            } else if check.contains("pending") || check.contains("○") {
-                println!("{}", check.yellow());
-            } else {
-                println!("{}", check);
-            }
-        }
+        Vec::new()
+    };
+    let lines: Vec<&str> = text.lines().collect();
 
-        if !failed_checks.is_empty() {
-            print_header("Failed CircleCI Checks");
-            println!("{}", "Extract URLs from failed checks to debug:".cyan());
+    if json {
+        let matched = |group: &ErrorGroup| -> Vec<serde_json::Value> {
+            group
+                .line_numbers
+                .iter()
+                .map(|&n| serde_json::json!({ "line_number": n, "line": lines[n - 1] }))
+                .collect()
+        };
+        let list: Vec<serde_json::Value> = results
+            .iter()
+            .map(|(pattern, group)| {
+                serde_json::json!({
+                    "id": pattern.id,
+                    "category": pattern.category,
+                    "builtin": false,
+                    "matches": group.as_ref().map(matched).unwrap_or_default(),
+                })
+            })
+            .chain(builtin_groups.iter().map(|group| {
+                serde_json::json!({
+                    "id": group.pattern_id,
+                    "category": group.category,
+                    "builtin": true,
+                    "matches": matched(group),
+                })
+            }))
+            .collect();
+        return print_json(&list);
+    }
 
-            // Try to extract CircleCI URLs from the output
-            let url_regex = Regex::new(r"https://circleci\.com/gh/[^\s]+/\d+")?;
-            for check in &failed_checks {
-                if let Some(url_match) = url_regex.find(check) {
-                    let url = url_match.as_str();
-                    println!(
-                        "\n{} {}",
-                        "•".red(),
-                        check.split('\t').next().unwrap_or("Unknown check").red()
-                    );
-                    println!("  Debug with: {}", format!("cdb build {}", url).cyan());
-                }
+    print_header(&format!(
+        "Pattern Test: {}",
+        if log == "-" { "stdin" } else { log }
+    ));
+    print_info(&format!("{} lines", lines.len()));
+    let print_group = |group: &ErrorGroup| {
+        for &n in group.line_numbers.iter().take(PATTERN_TEST_LINES) {
+            println!(
+                "    {:>5} {} {}",
+                n.to_string().warning(),
+                "│".dim(),
+                lines[n - 1].trim()
+            );
+        }
+        let hidden = group.count().saturating_sub(PATTERN_TEST_LINES);
+        if hidden > 0 {
+            println!("    {}", format!("… {} more", hidden).dim());
+        }
+    };
+    for (pattern, group) in &results {
+        println!(
+            "\n  {} {}",
+            pattern.id.bold(),
+            format!("({})", pattern.category).dim()
+        );
+        println!("    {}", pattern.regex.as_str().dim());
+        match group {
+            Some(group) => {
+                println!(
+                    "    {}",
+                    format!("{} matching line(s):", group.count()).success()
+                );
+                print_group(group);
             }
+            None => println!("    {}", "No matches".warning()),
+        }
+    }
+    if !builtin_groups.is_empty() {
+        println!("\n  {}", "Built-in patterns that also match:".bold());
+        for group in &builtin_groups {
+            println!(
+                "\n  {} {} {}",
+                group.pattern_id.bold(),
+                format!("({})", group.category).dim(),
+                format!("{} line(s)", group.count()).dim()
+            );
+            print_group(group);
         }
     }
 
-    // Also show PR details
-    println!();
-    print_header("PR Details");
-
-    let pr_details = std::process::Command::new("gh")
-        .args(&[
-            "pr",
-            "view",
-            &pr_number,
-            "--repo",
-            &repository,
-            "--json",
-            "state,title,author,url",
-        ])
-        .output();
+    let matched = results.iter().filter(|(_, group)| group.is_some()).count();
+    if matched == results.len() {
+        println!();
+        print_success(&format!("All {} pattern(s) matched", results.len()));
+    } else {
+        print_warning(&format!(
+            "{} of {} pattern(s) matched nothing",
+            results.len() - matched,
+            results.len()
+        ));
+    }
+    Ok(())
+}
 
-    if let Ok(output) = pr_details {
-        if output.status.success() {
-            let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+/// Runs a `cdb history` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the database can't be created, read, or deleted,
+/// or `--repo` isn't `org/repo`.
+fn manage_history(command: HistoryCommand) -> Result<()> {
+    let path = HistoryStore::default_path();
+    match command {
+        HistoryCommand::Enable => {
+            let store = HistoryStore::open(&path)?;
+            print_success(&format!("Recording analyzed builds in {}", path.display()));
+            print_info(&format!("{} build(s) recorded so far", store.len()?));
+        }
+        HistoryCommand::List {
+            repo,
+            failed,
+            limit,
+        } => {
+            let Some(store) = HistoryStore::open_existing(&path)? else {
+                print_warning("History is off; run `cdb history enable` to start recording");
+                return Ok(());
+            };
+            let (org, project) = repo.as_deref().map(split_repo).transpose()?.unzip();
+            let builds = store.recent(&RecordQuery {
+                org,
+                project,
+                failed,
+                limit,
+            })?;
 
-            if let Some(title) = json.get("title").and_then(|v| v.as_str()) {
-                print_info(&format!("Title: {}", title));
+            print_header("Build History");
+            if builds.is_empty() {
+                print_info("No builds recorded yet");
             }
-            if let Some(state) = json.get("state").and_then(|v| v.as_str()) {
-                print_info(&format!("State: {}", state));
+            for build in &builds {
+                let status = if build.is_failed() {
+                    build.status.error()
+                } else {
+                    build.status.success()
+                };
+                let when = build
+                    .start_time
+                    .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                let took = build
+                    .build_time_millis
+                    .map(format_duration)
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "  {} {}/{} #{} {} {} {}",
+                    when.dim(),
+                    build.org,
+                    build.project,
+                    build.build_num,
+                    status,
+                    build.job.as_deref().unwrap_or("-"),
+                    took.dim()
+                );
+                let failures = store.failures(&build.org, &build.project, build.build_num)?;
+                let mut categories: Vec<&str> = Vec::new();
+                for failure in &failures {
+                    if !categories.contains(&failure.category.as_str()) {
+                        categories.push(&failure.category);
+                    }
+                }
+                if !categories.is_empty() {
+                    println!("      {}", categories.join(", ").warning());
+                }
             }
-            if let Some(author) = json
-                .get("author")
-                .and_then(|v| v.get("login"))
-                .and_then(|v| v.as_str())
-            {
-                print_info(&format!("Author: {}", author));
+        }
+        HistoryCommand::Clear => match std::fs::remove_file(&path) {
+            Ok(()) => print_success(&format!(
+                "Deleted {}; builds are no longer recorded",
+                path.display()
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                print_info("History is already off")
             }
-            if let Some(url) = json.get("url").and_then(|v| v.as_str()) {
-                print_info(&format!("URL: {}", url.blue().underline()));
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to delete {}", path.display()))
             }
-        }
+        },
     }
-
     Ok(())
 }
 
@@ -1062,27 +7682,627 @@ async fn analyze_pr(pr_input: Option<String>, repo: Option<String>) -> Result<()
 ///
 /// # Exit Codes
 ///
-/// * `0` - Success, analysis completed
-/// * `1` - General error or authentication failure
+/// See [`cli::exit`]: `0` success, `10`/`11` build failed with/without a
+/// detected error, `20` authentication, `21` not found, `30` network,
+/// `1` anything else.
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            exit::for_error(&err)
+        }
+    }
+}
+
+/// Parses the command line and dispatches to the requested command.
+async fn run() -> Result<ExitCode> {
     let cli = Cli::parse();
+    cli::style::init(cli.color);
+    cli::logging::init(cli.verbose);
+    // Summary lines are meant for prompts and hooks, where spinners are noise
+    let summary = matches!(
+        cli.command,
+        Commands::Build { summary: true, .. }
+            | Commands::Build {
+                format: Some(OutputFormat::Summary),
+                ..
+            }
+    );
+    let progress = Progress::new(cli.quiet || summary);
+    if let Commands::Init = cli.command {
+        print_header("Setting Up cdb");
+        cli::init::run(cli.config.as_deref(), &progress).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Commands::Doctor = cli.command {
+        // Runs before loading the config so it can report what's wrong with it
+        print_header("Checking Environment");
+        let checks = cli::doctor::run(cli.config.as_deref(), &progress).await;
+        cli::doctor::print(&checks);
+        let failed = checks
+            .iter()
+            .any(|check| check.status == cli::doctor::Status::Fail);
+        return Ok(if failed {
+            ExitCode::from(exit::GENERAL_ERROR)
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+    let mut config = Config::load_with_project(cli.config.as_deref(), &std::env::current_dir()?)?;
+    config
+        .disabled_patterns
+        .extend(cli.disable_pattern.iter().cloned());
+    cli::style::set_theme(config.theme.clone());
+    if let Some(limit) = cli
+        .concurrency
+        .map(usize::from)
+        .or(config.defaults.concurrency)
+    {
+        let _ = CONCURRENCY.set(limit);
+    }
+    if let Some(mb) = config.defaults.cache_max_mb {
+        let _ = CACHE_MAX_SIZE.set(mb.saturating_mul(1024 * 1024));
+    }
+    if let Some(query) = cli.query.clone() {
+        let _ = QUERY.set(query);
+    }
 
     match cli.command {
         Commands::Build {
             url,
+            clipboard,
             full,
             output,
             tail,
             filter,
+            package,
             no_fetch,
+            tail_fetch,
+            since,
+            until,
+            relative_time,
+            no_collapse,
+            max_errors,
+            all_errors,
+            tail_default,
+            smart_window,
+            fail_on,
+            container,
+            queue_times,
+            follow_upstream,
+            format,
+            summary,
+            flake_history,
+            no_suspects,
+            no_owners,
+            no_source,
+            open_editor,
+            coverage,
+            coverage_threshold,
+            resources,
+            template,
+            record,
+            replay,
+        } => {
+            let format = match format {
+                Some(format) => format,
+                None if summary => OutputFormat::Summary,
+                None if cli.query.is_some() => OutputFormat::Json,
+                None => match &config.defaults.format {
+                    Some(name) => OutputFormat::from_config(name).context("Invalid config file")?,
+                    None => OutputFormat::default(),
+                },
+            };
+            // The summary has no room for these, so skip their API calls
+            let summary = format == OutputFormat::Summary;
+            let options = BuildOptions {
+                full_logs: full,
+                output_file: output,
+                tail_lines: tail,
+                filter: filter.or(config.defaults.filter.clone()),
+                package: package.or(config.defaults.package.clone()),
+                no_fetch,
+                tail_fetch,
+                since,
+                until,
+                relative_time,
+                no_collapse,
+                max_errors: (!all_errors).then_some(
+                    max_errors
+                        .or(config.defaults.max_errors)
+                        .unwrap_or(DEFAULT_MAX_ERRORS),
+                ),
+                exit_zone_lines: tail_default.or(config.defaults.tail_default),
+                smart_window: smart_window || config.defaults.smart_window.unwrap_or(false),
+                container,
+                queue_times,
+                follow_upstream,
+                format,
+                metrics_gateway: None,
+                post_mortem: None,
+                flake_history: if summary { 0 } else { flake_history },
+                no_suspects: no_suspects || summary,
+                no_owners: no_owners || summary,
+                no_source,
+                open_editor,
+                coverage: coverage || coverage_threshold.is_some(),
+                coverage_threshold,
+                resources: resources && !summary,
+                template: template.as_deref().map(Template::from_file).transpose()?,
+                no_cache: cli.no_cache,
+                record,
+                replay,
+                history_db: history_db(),
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+            };
+            let text = options.format == OutputFormat::Text && options.template.is_none();
+            let url = match url {
+                Some(url) => url,
+                None if clipboard => {
+                    let url = cli::clipboard::build_url()?;
+                    if text {
+                        print_info(&format!("Build URL from the clipboard: {}", url));
+                    }
+                    url
+                }
+                None => {
+                    let repo = config.defaults.repo.as_deref();
+                    latest_failed_build(repo, cli.no_cache, text, &progress).await?
+                }
+            };
+            let outcome = analyze_build(&url, &options, &progress).await?;
+            Ok(fail_on.exit_code(&outcome))
+        }
+        Commands::Status {
+            url,
+            branch,
+            repo,
+            job,
+            json,
+        } => {
+            let options = StatusOptions {
+                url,
+                branch,
+                repo: repo.or(config.defaults.repo.clone()),
+                job,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            show_status(&options, &progress).await
+        }
+        Commands::Wait {
+            url,
+            timeout,
+            interval,
+            notify,
+        } => wait_for_build(&url, &timeout, &interval, notify, cli.no_cache).await,
+        Commands::Pr {
+            pr,
+            repo,
+            watch,
+            analyze,
+        } => {
+            let repo = repo.or(config.defaults.repo.clone());
+            let options = if analyze {
+                Some(BuildOptions {
+                    filter: config.defaults.filter.clone(),
+                    package: config.defaults.package.clone(),
+                    no_cache: cli.no_cache,
+                    patterns: config.patterns()?,
+                    suggestions: config.suggestions()?,
+                    highlights: config.highlights()?,
+                    ..BuildOptions::default()
+                })
+            } else {
+                None
+            };
+            let notification = if watch {
+                Some(watch_pr(pr.as_deref(), repo.as_deref(), &progress)?)
+            } else {
+                None
+            };
+            analyze_pr(pr, repo, options.as_ref(), &progress).await?;
+            if let Some((title, body, url)) = notification {
+                notify::send(&title, &body, &url);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Bisect {
+            repo,
+            branch,
+            job,
+            max_builds,
+            git_bisect,
+        } => {
+            let options = BisectOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                branch,
+                job,
+                max_builds,
+                git_bisect,
+                no_cache: cli.no_cache,
+            };
+            bisect_branch(&options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Search {
+            pattern,
+            repo,
+            last,
+            branch,
+            context,
+            fetch,
+            json,
+        } => {
+            let options = SearchCommandOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                search: SearchOptions {
+                    builds: last,
+                    branch,
+                    fetch,
+                    context,
+                    ..Default::default()
+                },
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            search_logs(&pattern, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Grep {
+            url,
+            pattern,
+            context,
+            count,
+            ignore_case,
+            max_count,
+            json,
+        } => {
+            let options = GrepOptions {
+                search: SearchOptions {
+                    context,
+                    max_matches_per_log: max_count.unwrap_or(usize::MAX),
+                    ..Default::default()
+                },
+                count,
+                ignore_case,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            grep_build(&url, &pattern, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Keys { repo, json } => {
+            let repo = repo.or(config.defaults.repo.clone());
+            show_keys(
+                repo.as_deref(),
+                json || cli.query.is_some(),
+                cli.no_cache,
+                &progress,
+            )
+            .await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Projects { json } => {
+            show_projects(json || cli.query.is_some(), cli.no_cache, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Top {
+            org,
+            interval,
+            once,
+            json,
+        } => {
+            let options = TopOptions {
+                org,
+                interval,
+                once,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            show_top(&options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Follow { repo } => {
+            follow(&repo).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Trigger {
+            repo,
+            branch,
+            params,
+            list_params,
+            json,
+        } => {
+            let options = TriggerCommandOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                branch,
+                params,
+                list_params,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            trigger_pipeline(&options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Schedules {
+            repo,
+            trigger_now,
+            json,
+        } => {
+            let options = SchedulesCommandOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                trigger_now,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            show_schedules(&options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Ssh { url, timeout } => {
+            ssh_rerun(&url, &timeout, cli.no_cache, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Nightly {
+            repo,
+            workflow,
+            branch,
+            last,
+            no_analyze,
+            json,
+        } => {
+            let options = NightlyCommandOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                workflow,
+                branch,
+                last,
+                no_analyze,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            let build_options = BuildOptions {
+                no_cache: cli.no_cache,
+                history_db: history_db(),
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+                ..BuildOptions::default()
+            };
+            show_nightly(&options, &build_options, &progress).await
+        }
+        Commands::Size {
+            artifact,
+            repo,
+            last,
+            branch,
+            job,
+            threshold,
+            json,
+        } => {
+            let options = SizeCommandOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                query: SizeQuery {
+                    artifact,
+                    builds: last,
+                    branch,
+                    job,
+                },
+                threshold,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            show_sizes(&options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::SuggestParallelism { url, max, json } => {
+            suggest_parallelism(
+                &url,
+                max,
+                json || cli.query.is_some(),
+                cli.no_cache,
+                &progress,
+            )
+            .await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::CompareBranch {
+            repo,
+            base,
+            branch,
+            last,
+            job,
+            threshold,
+            json,
+        } => {
+            let options = CompareBranchOptions {
+                repo: repo.or(config.defaults.repo.clone()),
+                base,
+                branch,
+                builds: last,
+                job,
+                threshold,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            let slower = compare_branch(&options, &progress).await?;
+            Ok(ExitCode::from(if slower {
+                exit::STEP_SLOWDOWN
+            } else {
+                exit::SUCCESS
+            }))
+        }
+        Commands::DiffArtifacts {
+            old,
+            new,
+            glob,
+            max_growth,
+            json,
+        } => {
+            let options = DiffArtifactsOptions {
+                glob,
+                max_growth,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            let grew = diff_artifacts(&old, &new, &options, &progress).await?;
+            Ok(ExitCode::from(if grew {
+                exit::ARTIFACT_GROWTH
+            } else {
+                exit::SUCCESS
+            }))
+        }
+        Commands::DiffConfig {
+            old,
+            new,
+            source,
+            context,
+            json,
+        } => {
+            let options = DiffConfigOptions {
+                source,
+                context,
+                json: json || cli.query.is_some(),
+                no_cache: cli.no_cache,
+            };
+            diff_config(&old, &new, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Auth {
+            command: AuthCommand::Check,
+        } => {
+            check_auth(&progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Metrics {
+            command: MetricsCommand::Push { url, gateway, job },
+        } => {
+            let options = BuildOptions {
+                format: OutputFormat::Json,
+                metrics_gateway: Some(MetricsGateway { url: gateway, job }),
+                no_suspects: true,
+                no_owners: true,
+                no_cache: cli.no_cache,
+                history_db: history_db(),
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+                ..BuildOptions::default()
+            };
+            analyze_build(&url, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Report {
+            url,
+            format,
+            output,
+        } => {
+            let options = BuildOptions {
+                format: OutputFormat::Json,
+                post_mortem: Some(PostMortemOutput {
+                    format: format.into(),
+                    path: output,
+                }),
+                no_cache: cli.no_cache,
+                history_db: history_db(),
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+                ..BuildOptions::default()
+            };
+            analyze_build(&url, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Bulk {
+            file,
+            stdin: _,
+            output,
+            jobs,
+            json,
+        } => {
+            let bulk = BulkOptions {
+                file,
+                output,
+                jobs,
+                json: json || cli.query.is_some(),
+            };
+            let options = BuildOptions {
+                filter: config.defaults.filter.clone(),
+                package: config.defaults.package.clone(),
+                no_cache: cli.no_cache,
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+                ..BuildOptions::default()
+            };
+            analyze_bulk(&bulk, &options, &progress).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Analyze {
+            file,
+            full,
+            tail,
+            filter,
+            package,
+            since,
+            until,
+            no_collapse,
+            max_errors,
+            all_errors,
+            tail_default,
+            smart_window,
+            no_source,
+            json,
         } => {
-            analyze_build(&url, full, output, tail, filter, no_fetch).await?;
+            let options = BuildOptions {
+                full_logs: full,
+                tail_lines: tail,
+                filter: filter.or(config.defaults.filter.clone()),
+                package: package.or(config.defaults.package.clone()),
+                since,
+                until,
+                no_collapse,
+                max_errors: (!all_errors).then_some(
+                    max_errors
+                        .or(config.defaults.max_errors)
+                        .unwrap_or(DEFAULT_MAX_ERRORS),
+                ),
+                exit_zone_lines: tail_default.or(config.defaults.tail_default),
+                smart_window: smart_window || config.defaults.smart_window.unwrap_or(false),
+                format: if json || cli.query.is_some() {
+                    OutputFormat::Json
+                } else {
+                    OutputFormat::Text
+                },
+                no_source,
+                patterns: config.patterns()?,
+                suggestions: config.suggestions()?,
+                highlights: config.highlights()?,
+                ..BuildOptions::default()
+            };
+            analyze_file(&file, &options)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Stats { last, repo, json } => {
+            let repo = repo.or(config.defaults.repo.clone());
+            show_stats(&last, repo.as_deref(), json || cli.query.is_some())?;
+            Ok(ExitCode::SUCCESS)
         }
-        Commands::Pr { pr, repo } => {
-            analyze_pr(pr, repo).await?;
+        Commands::History { command } => {
+            manage_history(command)?;
+            Ok(ExitCode::SUCCESS)
         }
+        Commands::Cache { command } => {
+            manage_cache(command, cli.query.is_some(), &progress)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Patterns { command } => {
+            manage_patterns(command, &config, cli.query.is_some())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Doctor | Commands::Init => unreachable!("handled before loading the config"),
     }
-
-    Ok(())
 }
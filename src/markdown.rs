@@ -0,0 +1,263 @@
+//! Markdown rendering of a build analysis.
+//!
+//! [`render`] turns a [`BuildReport`] into a document that reads well in
+//! a pull request comment, an issue, or a wiki page: a summary table,
+//! each failed step with what was detected in its log, then failed tests,
+//! suspect commits, code owners, and the slowest steps. `cdb build` saves
+//! one next to the cleaned logs on every run (see
+//! [`paths::saved_report`](crate::paths::saved_report)).
+
+use crate::format_duration;
+use crate::report::BuildReport;
+use std::fmt::Write;
+
+/// How many of the slowest steps to list.
+const SLOWEST_STEPS: usize = 5;
+
+/// Renders a report as Markdown.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::markdown::render;
+/// use circle_debug::report::BuildReport;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": "Fix login"}"#,
+/// )
+/// .unwrap();
+/// let markdown = render(&BuildReport::new("org", "repo", &build));
+/// assert!(markdown.starts_with("# Build #7: org/repo\n"));
+/// assert!(markdown.contains("| Branch | main |"));
+/// ```
+pub fn render(report: &BuildReport) -> String {
+    let mut out = format!(
+        "# Build #{}: {}/{}\n\n",
+        report.build_num, report.org, report.project
+    );
+
+    let mut rows: Vec<(&str, String)> = vec![("Status", report.status.clone())];
+    if let Some(branch) = &report.branch {
+        rows.push(("Branch", branch.clone()));
+    }
+    if let Some(sha) = &report.vcs_revision {
+        let short = sha.get(..7).unwrap_or(sha);
+        rows.push((
+            "Commit",
+            match &report.subject {
+                Some(subject) => format!("`{}` {}", short, subject),
+                None => format!("`{}`", short),
+            },
+        ));
+    }
+    if let Some(author) = &report.author_name {
+        rows.push(("Author", author.clone()));
+    }
+    if let Some(trigger) = &report.trigger {
+        rows.push((
+            "Trigger",
+            match &trigger.user {
+                Some(user) => format!("{} by {}", trigger.kind, user),
+                None => trigger.kind.to_string(),
+            },
+        ));
+    }
+    if let Some(workflow) = &report.workflow {
+        if let (Some(name), Some(job)) = (&workflow.workflow_name, &workflow.job_name) {
+            rows.push(("Workflow", format!("{} / {}", name, job)));
+        }
+    }
+    if let Some(millis) = report.build_time_millis {
+        let queued = report
+            .queued_millis
+            .filter(|&q| q > 0)
+            .map(|q| format!(" (queued {})", format_duration(q)))
+            .unwrap_or_default();
+        rows.push(("Duration", format!("{}{}", format_duration(millis), queued)));
+    }
+    if let Some(environment) = &report.environment {
+        if let Some(executor) = environment.executor_label() {
+            rows.push(("Executor", executor));
+        }
+        if !environment.images.is_empty() {
+            let images: Vec<String> = environment
+                .images
+                .iter()
+                .map(|image| format!("`{}`", image))
+                .collect();
+            rows.push(("Images", images.join(", ")));
+        }
+        if let Some(os) = environment.os_label() {
+            rows.push(("OS", os));
+        }
+    }
+    if let Some(cost) = &report.cost {
+        rows.push((
+            "Cost",
+            format!("{:.0} credits (${:.2})", cost.credits, cost.usd),
+        ));
+    }
+    if let Some(url) = &report.build_url {
+        rows.push(("Link", url.clone()));
+    }
+    out.push_str("| | |\n|---|---|\n");
+    for (name, value) in rows {
+        let _ = writeln!(out, "| {} | {} |", name, cell(&value));
+    }
+
+    if let Some(upstream) = &report.upstream {
+        let _ = writeln!(
+            out,
+            "\n> The failure comes from the upstream job **{}** ({}).",
+            upstream.job, upstream.status
+        );
+    }
+
+    if !report.failed_actions.is_empty() {
+        out.push_str("\n## Failed Steps\n");
+    }
+    for action in &report.failed_actions {
+        let container = action
+            .container_label()
+            .map(|label| format!(" ({})", label))
+            .unwrap_or_default();
+        if action.step == action.name {
+            let _ = writeln!(out, "\n### {}{}\n", action.step, container);
+        } else {
+            let _ = writeln!(
+                out,
+                "\n### {} › {}{}\n",
+                action.step, action.name, container
+            );
+        }
+        let command = action.command_lines();
+        if !command.is_empty() {
+            let fence = if command.iter().any(|line| line.contains("```")) {
+                "~~~"
+            } else {
+                "```"
+            };
+            let _ = writeln!(out, "{}sh\n{}\n{}\n", fence, command.join("\n"), fence);
+        }
+        if let Some(error) = &action.fetch_error {
+            let _ = writeln!(out, "Logs couldn't be fetched: {}", error);
+            continue;
+        }
+        if action.errors.is_empty() {
+            out.push_str("No known error pattern detected.\n");
+        }
+        for group in &action.errors {
+            let count = if group.count() > 1 {
+                format!(", {} lines", group.count())
+            } else {
+                String::new()
+            };
+            let _ = writeln!(
+                out,
+                "**{}** ({}, line {}{})\n\n{}",
+                group.category,
+                group.severity,
+                group.first.line_number,
+                count,
+                code_block(group.first.line.trim())
+            );
+        }
+    }
+
+    let tests: Vec<String> = report
+        .failed_tests()
+        .map(|test| match (&test.file, &test.name) {
+            (Some(file), Some(name)) => format!("- `{}` {}", file, name),
+            (Some(file), None) => format!("- `{}`", file),
+            (None, Some(name)) => format!("- {}", name),
+            (None, None) => "- (unnamed test)".to_string(),
+        })
+        .collect();
+    if !tests.is_empty() {
+        let _ = writeln!(out, "\n## Failed Tests\n\n{}", tests.join("\n"));
+    }
+    if !report.reproduce.is_empty() {
+        let _ = writeln!(
+            out,
+            "\n## Reproduce Locally\n\n```sh\n{}\n```",
+            report.reproduce.join("\n")
+        );
+    }
+
+    if let Some(suspects) = &report.suspects {
+        let flagged: Vec<String> = suspects
+            .flagged()
+            .map(|suspect| {
+                format!(
+                    "- `{}` {} ({}): {}",
+                    suspect.commit.short_sha(),
+                    suspect.commit.subject,
+                    suspect.commit.author.as_deref().unwrap_or("unknown"),
+                    suspect.matched_files.join(", ")
+                )
+            })
+            .collect();
+        if !flagged.is_empty() {
+            let _ = writeln!(
+                out,
+                "\n## Suspect Commits\n\nSince build #{}, these commits touch files named in the errors:\n\n{}",
+                suspects.base_build,
+                flagged.join("\n")
+            );
+        }
+    }
+    if !report.owners.is_empty() {
+        out.push_str("\n## Code Owners\n\n");
+        for file in &report.owners {
+            let _ = writeln!(out, "- `{}`: {}", file.path, file.owners.join(", "));
+        }
+    }
+
+    if !report.step_timings.is_empty() {
+        out.push_str("\n## Slowest Steps\n\n| Step | Time | Status |\n|---|---|---|\n");
+        for step in report.step_timings.iter().take(SLOWEST_STEPS) {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                cell(&step.name),
+                format_duration(step.millis),
+                step.status
+            );
+        }
+    }
+    out
+}
+
+/// Escapes a table cell's pipes and line breaks.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Wraps a log line in a fenced code block, with a fence it can't close.
+fn code_block(line: &str) -> String {
+    let fence = if line.contains("```") { "~~~" } else { "```" };
+    format!("{}text\n{}\n{}", fence, line, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{BuildBuilder, FailedActionBuilder};
+
+    #[test]
+    fn test_render_failed_step() {
+        let build = BuildBuilder::new(12).build();
+        let mut report = BuildReport::new("org", "repo", &build);
+        report.failed_actions.push(
+            FailedActionBuilder::new("Run tests")
+                .log("npm ERR! code ELIFECYCLE | exit 1")
+                .build(),
+        );
+
+        let markdown = render(&report);
+        assert!(markdown.contains("\n## Failed Steps\n\n### Run tests\n\n**"));
+        assert!(markdown.contains("```text\nnpm ERR! code ELIFECYCLE | exit 1\n```"));
+        assert!(!markdown.contains("## Suspect Commits"));
+    }
+}
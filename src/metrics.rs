@@ -0,0 +1,365 @@
+//! Prometheus metrics for a build report.
+//!
+//! [`render`] writes a report's timings and failure classification in the
+//! Prometheus text exposition format, and [`push`] sends them to a
+//! [Pushgateway](https://github.com/prometheus/pushgateway), grouped by
+//! organization, project, and workflow job so each job's latest build
+//! replaces the one before it.
+//!
+//! | Metric | Labels |
+//! |---|---|
+//! | `cdb_build_info` | `build_num`, `branch`, `status`, `why` |
+//! | `cdb_build_failed` | |
+//! | `cdb_build_duration_seconds` | |
+//! | `cdb_build_queue_seconds` | |
+//! | `cdb_step_duration_seconds` | `step` |
+//! | `cdb_build_error_matches` | `category`, `kind`, `severity`, `transient` |
+
+use crate::report::BuildReport;
+use anyhow::{bail, Context, Result};
+use std::fmt::Write;
+
+/// The Pushgateway's media type for the text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Renders a report as Prometheus metrics, in the text exposition format.
+///
+/// All metrics are gauges describing the one build; labels that identify
+/// the job are left to the Pushgateway's grouping key.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::metrics::render;
+/// use circle_debug::report::BuildReport;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null,
+///         "build_time_millis": 90500}"#,
+/// )
+/// .unwrap();
+/// let metrics = render(&BuildReport::new("org", "repo", &build));
+/// assert!(metrics.contains("cdb_build_failed 1\n"));
+/// assert!(metrics.contains("cdb_build_duration_seconds 90.5\n"));
+/// ```
+pub fn render(report: &BuildReport) -> String {
+    let mut out = String::new();
+
+    gauge(
+        &mut out,
+        "cdb_build_info",
+        "The analyzed build; always 1.",
+        &[(
+            vec![
+                ("build_num", report.build_num.to_string()),
+                ("branch", report.branch.clone().unwrap_or_default()),
+                ("status", report.status.clone()),
+                ("why", report.why.clone().unwrap_or_default()),
+            ],
+            1.0,
+        )],
+    );
+    let failed = ["failed", "infrastructure_fail", "timedout"].contains(&report.status.as_str());
+    gauge(
+        &mut out,
+        "cdb_build_failed",
+        "Whether the build failed.",
+        &[(vec![], if failed { 1.0 } else { 0.0 })],
+    );
+    if let Some(millis) = report.build_time_millis {
+        gauge(
+            &mut out,
+            "cdb_build_duration_seconds",
+            "How long the build ran.",
+            &[(vec![], seconds(millis))],
+        );
+    }
+    if let Some(millis) = report.queued_millis {
+        gauge(
+            &mut out,
+            "cdb_build_queue_seconds",
+            "How long the build waited for capacity.",
+            &[(vec![], seconds(millis))],
+        );
+    }
+
+    let steps: Vec<_> = report
+        .step_timings
+        .iter()
+        .map(|step| (vec![("step", step.name.clone())], seconds(step.millis)))
+        .collect();
+    gauge(
+        &mut out,
+        "cdb_step_duration_seconds",
+        "How long each step ran, summed over parallel containers.",
+        &steps,
+    );
+
+    let errors: Vec<_> = report
+        .errors()
+        .map(|group| {
+            (
+                vec![
+                    ("category", group.category.clone()),
+                    ("kind", format!("{:?}", group.kind).to_lowercase()),
+                    ("severity", group.severity.to_string()),
+                    ("transient", group.transient.to_string()),
+                ],
+                group.count() as f64,
+            )
+        })
+        .collect();
+    gauge(
+        &mut out,
+        "cdb_build_error_matches",
+        "Log lines matching each detected error pattern.",
+        &errors,
+    );
+
+    out
+}
+
+/// Pushes a report's metrics to a Pushgateway, replacing the metrics of
+/// the same organization, project, and workflow job.
+///
+/// # Arguments
+///
+/// * `gateway` - Base URL of the Pushgateway, e.g. `http://pushgateway:9091`
+/// * `job` - Value of the grouping key's `job` label
+/// * `report` - The analyzed build
+///
+/// # Errors
+///
+/// Returns an error if the gateway can't be reached or rejects the push.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # async fn example(report: circle_debug::report::BuildReport) -> Result<()> {
+/// circle_debug::metrics::push("http://localhost:9091", "cdb", &report).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn push(gateway: &str, job: &str, report: &BuildReport) -> Result<()> {
+    let url = format!(
+        "{}/{}",
+        gateway.trim_end_matches('/'),
+        grouping_path(job, report)
+    );
+    let response = reqwest::Client::new()
+        .put(&url)
+        .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+        .body(render(report))
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach the Pushgateway at {}", gateway))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!(
+            "Pushgateway rejected the metrics ({}): {}",
+            status,
+            body.trim()
+        );
+    }
+    Ok(())
+}
+
+/// The `metrics/job/<job>/<label>/<value>...` path of a report's grouping
+/// key.
+fn grouping_path(job: &str, report: &BuildReport) -> String {
+    let mut labels = vec![
+        ("job", job),
+        ("org", report.org.as_str()),
+        ("project", report.project.as_str()),
+    ];
+    if let Some(ci_job) = report.workflow.as_ref().and_then(|w| w.job_name.as_deref()) {
+        labels.push(("ci_job", ci_job));
+    }
+
+    let mut path = String::from("metrics");
+    for (name, value) in labels {
+        // Values the path can't carry as they are go base64-encoded
+        if value.is_empty() || value.contains('/') {
+            let _ = write!(path, "/{}@base64/{}", name, base64_url(value.as_bytes()));
+        } else {
+            let _ = write!(path, "/{}/{}", name, percent_encode(value));
+        }
+    }
+    path
+}
+
+/// Writes one gauge with its `# HELP` and `# TYPE` lines. Gauges without
+/// samples are left out.
+///
+/// Samples with the same labels, such as two steps of the same name, are
+/// added up: the Pushgateway rejects a push that repeats a series.
+fn gauge(out: &mut String, name: &str, help: &str, samples: &[(Vec<(&str, String)>, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let mut summed: Vec<(&Vec<(&str, String)>, f64)> = Vec::new();
+    for (labels, value) in samples {
+        match summed.iter_mut().find(|(seen, _)| *seen == labels) {
+            Some((_, total)) => *total += value,
+            None => summed.push((labels, *value)),
+        }
+    }
+
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (labels, value) in summed {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+            .collect();
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+fn seconds(millis: u64) -> f64 {
+    millis as f64 / 1000.0
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// URL-safe base64 with padding, as the Pushgateway expects it.
+fn base64_url(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    if bytes.is_empty() {
+        // The gateway's spelling of an empty value
+        return "=".to_string();
+    }
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, FailedActionBuilder, StepBuilder};
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn report() -> BuildReport {
+        let build = BuildBuilder::new(42)
+            .status("failed")
+            .branch(Some("feature/login"))
+            .workflow("ci", "test")
+            .build_time_millis(61_000)
+            .step(
+                StepBuilder::new("Run \"tests\"")
+                    .action(ActionBuilder::new("x").run_time_millis(1500)),
+            )
+            .build();
+        let mut report = BuildReport::new("org", "repo", &build);
+        report.failed_actions.push(
+            FailedActionBuilder::new("Run tests")
+                .log("Error: Cannot find module 'x'\n")
+                .build(),
+        );
+        report
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = render(&report());
+        assert!(metrics
+            .contains("# TYPE cdb_build_duration_seconds gauge\ncdb_build_duration_seconds 61\n"));
+        assert!(metrics.contains("cdb_step_duration_seconds{step=\"Run \\\"tests\\\"\"} 1.5\n"));
+        assert!(metrics.contains("branch=\"feature/login\""));
+        assert!(metrics.contains("cdb_build_error_matches{category="));
+        assert!(!metrics.contains("cdb_build_queue_seconds"));
+    }
+
+    #[test]
+    fn test_render_sums_repeated_series() {
+        let build = BuildBuilder::new(43)
+            .status("failed")
+            .step(
+                StepBuilder::new("Run tests")
+                    .action(ActionBuilder::new("Run tests").run_time_millis(1000)),
+            )
+            .step(
+                StepBuilder::new("Run tests")
+                    .action(ActionBuilder::new("Run tests").run_time_millis(2500)),
+            )
+            .build();
+        let mut report = BuildReport::new("org", "repo", &build);
+        for step in ["Lint", "Run tests"] {
+            report.failed_actions.push(
+                FailedActionBuilder::new(step)
+                    .log("npm ERR! code ELIFECYCLE\nnpm ERR! code ELIFECYCLE\n")
+                    .build(),
+            );
+        }
+
+        let metrics = render(&report);
+        let series: Vec<&str> = metrics
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        let mut unique = series.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(series.len(), unique.len(), "{}", metrics);
+        assert!(metrics.contains("cdb_step_duration_seconds{step=\"Run tests\"} 3.5\n"));
+        assert!(metrics.contains("\"} 4\n"));
+    }
+
+    #[tokio::test]
+    async fn test_push_grouping_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/metrics/job/cdb/org/org/project/repo/ci_job/test"))
+            .and(header("content-type", CONTENT_TYPE))
+            .and(body_string_contains("cdb_build_failed 1"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        push(&server.uri(), "cdb", &report()).await.unwrap();
+
+        assert_eq!(base64_url(b"feature/x"), "ZmVhdHVyZS94");
+        assert_eq!(base64_url(b"a/b"), "YS9i");
+        assert_eq!(base64_url(b"ab/c?"), "YWIvYz8=");
+    }
+}
@@ -0,0 +1,341 @@
+//! Package attribution for monorepo task runner logs.
+//!
+//! Turborepo, Nx, Lerna, and Yarn workspaces interleave the output of many
+//! packages in one log. Each marks which package a line came from:
+//!
+//! | Runner | Marker |
+//! |---|---|
+//! | Turborepo | `@scope/pkg:build: ` line prefix |
+//! | Lerna (`--stream`) | `@scope/pkg: ` line prefix |
+//! | Yarn (`workspaces foreach`) | `[@scope/pkg]: ` line prefix |
+//! | Nx | a `> nx run pkg:build` header before the package's output |
+//!
+//! [`summarize`] works out which packages ran and which of them failed, and
+//! [`filter_package`] keeps only the lines of one package. Package names
+//! follow npm's rules, so they are lowercase; that keeps Maven's `[INFO]`
+//! and friends from being mistaken for packages.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// An npm package name, optionally scoped.
+const NAME: &str = r"(?:@[a-z0-9][a-z0-9._-]*/)?[a-z0-9][a-z0-9._-]*";
+
+/// A task name like `build` or `test:unit`.
+const TASK: &str = r"[A-Za-z][\w-]*(?::[A-Za-z][\w-]*)*";
+
+/// What ran in a monorepo log, by package.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PackageSummary {
+    /// Packages in the order they first appear.
+    pub packages: Vec<PackageStatus>,
+}
+
+/// One package's share of a log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PackageStatus {
+    /// Package name, e.g. `@acme/api`.
+    pub name: String,
+    /// Whether the runner reported a failed task for it.
+    pub failed: bool,
+    /// How many lines of the log belong to it.
+    pub lines: usize,
+}
+
+impl PackageSummary {
+    /// Returns the packages with a failed task.
+    pub fn failed(&self) -> impl Iterator<Item = &PackageStatus> {
+        self.packages.iter().filter(|package| package.failed)
+    }
+}
+
+/// Lines of a log attributed to packages, and the failures the runner
+/// reported.
+struct Attribution<'a> {
+    /// The package of each line, if any.
+    lines: Vec<Option<&'a str>>,
+    /// Packages named in failure messages and end-of-run summaries.
+    failed: Vec<&'a str>,
+}
+
+/// Finds which packages ran in a monorepo log and which failed.
+///
+/// # Returns
+///
+/// `None` if no line is attributed to a package, i.e. the log isn't from a
+/// monorepo task runner.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::monorepo::summarize;
+///
+/// let log = "@acme/ui:build: compiled\n\
+///            @acme/api:build: src/index.ts(3,1): error TS2304\n\
+///            @acme/api:build: npm ERR! Lifecycle script `build` failed\n";
+/// let summary = summarize(log).unwrap();
+/// assert_eq!(summary.packages.len(), 2);
+/// let failed: Vec<_> = summary.failed().map(|p| p.name.as_str()).collect();
+/// assert_eq!(failed, vec!["@acme/api"]);
+///
+/// assert!(summarize("just a plain log\n").is_none());
+/// ```
+pub fn summarize(log: &str) -> Option<PackageSummary> {
+    let attribution = attribute(log);
+    let mut packages: Vec<PackageStatus> = Vec::new();
+    for name in attribution.lines.iter().flatten() {
+        match packages.iter_mut().find(|package| package.name == *name) {
+            Some(package) => package.lines += 1,
+            None => packages.push(PackageStatus {
+                name: name.to_string(),
+                failed: false,
+                lines: 1,
+            }),
+        }
+    }
+    if packages.is_empty() {
+        return None;
+    }
+
+    for name in attribution.failed {
+        match packages.iter_mut().find(|package| package.name == name) {
+            Some(package) => package.failed = true,
+            // Only named in the runner's summary
+            None => packages.push(PackageStatus {
+                name: name.to_string(),
+                failed: true,
+                lines: 0,
+            }),
+        }
+    }
+    Some(PackageSummary { packages })
+}
+
+/// Keeps the lines that belong to package `name`.
+///
+/// `name` matches with or without the scope, so `api` selects `@acme/api`.
+/// Lines that belong to no package are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::monorepo::filter_package;
+///
+/// let log = "• Running build in 2 packages\n\
+///            @acme/ui:build: compiled\n\
+///            @acme/api:build: error TS2304\n";
+/// assert_eq!(filter_package(log, "api"), "@acme/api:build: error TS2304");
+/// assert_eq!(filter_package(log, "web"), "");
+/// ```
+pub fn filter_package(log: &str, name: &str) -> String {
+    log.lines()
+        .zip(package_lines(log, name))
+        .filter(|(_, kept)| *kept)
+        .map(|(line, _)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns, for each line of `log`, whether it belongs to package `name`,
+/// i.e. whether [`filter_package`] keeps it.
+pub fn package_lines(log: &str, name: &str) -> Vec<bool> {
+    attribute(log)
+        .lines
+        .into_iter()
+        .map(|package| package.is_some_and(|package| matches_package(package, name)))
+        .collect()
+}
+
+/// Whether `package` is `wanted`, allowing `wanted` to leave out the scope.
+pub fn matches_package(package: &str, wanted: &str) -> bool {
+    package == wanted
+        || package
+            .strip_prefix('@')
+            .and_then(|scoped| scoped.split_once('/'))
+            .is_some_and(|(_, unscoped)| unscoped == wanted)
+}
+
+fn prefix_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(&format!(
+            // Turborepo `pkg:task: ` (or `pkg#task: ` in its error lines),
+            // Yarn `[pkg]: `, and scoped-only Lerna `@scope/pkg: `
+            r"^\s*(?:(?P<turbo>{NAME})[:#]{TASK}:(?:\s|$)|\[(?P<yarn>{NAME})\]:?(?:\s|$)|(?P<lerna>@[a-z0-9][a-z0-9._-]*/[a-z0-9][a-z0-9._-]*):(?:\s|$))"
+        ))
+        .unwrap()
+    })
+}
+
+fn nx_header_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(&format!(r"^\s*>\s+nx run (?P<pkg>{NAME}):{TASK}")).unwrap())
+}
+
+/// Non-zero exits and npm/yarn failure messages inside a package's output.
+fn failure_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?i)exited \(?[1-9]\d*\)?|exit code [1-9]\d*|npm ERR!|ELIFECYCLE|command failed",
+        )
+        .unwrap()
+    })
+}
+
+/// Packages named by the end-of-run summaries: Turborepo's
+/// `Failed: pkg#task`, Nx's `Failed tasks:` list entries, and Lerna's
+/// `exited 1 in 'pkg'`.
+fn summary_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(&format!(
+            r"^\s*(?:Failed:\s+(?P<turbo>.+)|-\s+(?P<nx>{NAME}):{TASK}\s*$)|exited \d+ in '(?P<lerna>{NAME})'"
+        ))
+        .unwrap()
+    })
+}
+
+fn turbo_task_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(&format!(r"^({NAME})#{TASK}$")).unwrap())
+}
+
+fn attribute(log: &str) -> Attribution<'_> {
+    let mut lines = Vec::new();
+    let mut failed = Vec::new();
+    // Nx prints a header, then the package's output unprefixed
+    let mut nx_section: Option<&str> = None;
+    let mut in_nx_failures = false;
+
+    for line in log.lines() {
+        if let Some(caps) = prefix_regex().captures(line) {
+            let name = caps
+                .name("turbo")
+                .or_else(|| caps.name("yarn"))
+                .or_else(|| caps.name("lerna"))
+                .map(|m| m.as_str())
+                .unwrap();
+            if failure_regex().is_match(&line[caps.get(0).unwrap().end()..]) {
+                failed.push(name);
+            }
+            lines.push(Some(name));
+            continue;
+        }
+
+        if let Some(caps) = nx_header_regex().captures(line) {
+            nx_section = caps.name("pkg").map(|m| m.as_str());
+            lines.push(nx_section);
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.starts_with(">  NX") || trimmed.starts_with("NX ") {
+            nx_section = None;
+            in_nx_failures = false;
+        }
+        if trimmed == "Failed tasks:" {
+            in_nx_failures = true;
+        }
+
+        if let Some(caps) = summary_regex().captures(line) {
+            if let Some(tasks) = caps.name("turbo") {
+                failed.extend(tasks.as_str().split(',').filter_map(|task| {
+                    turbo_task_regex()
+                        .captures(task.trim())
+                        .and_then(|c| c.get(1))
+                        .map(|m| m.as_str())
+                }));
+            } else if let (Some(name), true) = (caps.name("nx"), in_nx_failures) {
+                failed.push(name.as_str());
+            } else if let Some(name) = caps.name("lerna") {
+                failed.push(name.as_str());
+            }
+        }
+
+        if let Some(name) = nx_section {
+            if failure_regex().is_match(line) {
+                failed.push(name);
+            }
+        }
+        lines.push(nx_section);
+    }
+
+    Attribution { lines, failed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_turborepo() {
+        let log = "\
+• Packages in scope: @acme/api, @acme/ui, web
+• Running build in 3 packages
+@acme/ui:build: cache hit, replaying logs
+@acme/ui:build: done
+web:build: next build
+@acme/api:build: src/index.ts(3,1): error TS2304: Cannot find name 'foo'.
+@acme/api:build: ERROR: command finished with error: command (/repo/packages/api) npm run build exited (2)
+@acme/api#build: command (/repo/packages/api) npm run build exited (2)
+
+ Tasks:    2 successful, 3 total
+Failed:    @acme/api#build
+";
+        let summary = summarize(log).unwrap();
+        let names: Vec<_> = summary.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["@acme/ui", "web", "@acme/api"]);
+        let failed: Vec<_> = summary.failed().map(|p| p.name.as_str()).collect();
+        assert_eq!(failed, vec!["@acme/api"]);
+        assert_eq!(summary.packages[2].lines, 3);
+
+        assert_eq!(filter_package(log, "ui").lines().count(), 2);
+        assert_eq!(filter_package(log, "@acme/api").lines().count(), 3);
+    }
+
+    #[test]
+    fn test_summarize_nx_yarn_and_lerna() {
+        let nx = "\
+> nx run ui:build
+compiled
+> nx run api:build
+src/main.ts:3:1 - error TS2304
+ >  NX   Ran target build for 2 projects
+
+   Failed tasks:
+
+   - api:build
+";
+        let summary = summarize(nx).unwrap();
+        assert_eq!(summary.packages.len(), 2);
+        let failed: Vec<_> = summary.failed().map(|p| p.name.as_str()).collect();
+        assert_eq!(failed, vec!["api"]);
+        assert_eq!(
+            filter_package(nx, "api"),
+            "> nx run api:build\nsrc/main.ts:3:1 - error TS2304"
+        );
+
+        let yarn = "[@acme/ui]: done\n[@acme/api]: Process exited (exit code 1), completed in 2s\n";
+        let failed: Vec<_> = summarize(yarn)
+            .unwrap()
+            .failed()
+            .map(|p| p.name.clone())
+            .collect();
+        assert_eq!(failed, vec!["@acme/api"]);
+
+        let lerna = "@acme/ui: done\nlerna ERR! npm run build exited 1 in '@acme/api'\n";
+        let summary = summarize(lerna).unwrap();
+        assert_eq!(summary.packages.len(), 2);
+        assert_eq!(summary.packages[1].lines, 0);
+        assert!(summary.packages[1].failed);
+    }
+
+    #[test]
+    fn test_plain_logs_have_no_packages() {
+        let log = "[INFO] Building app\nError: something broke\nsrc/main.rs:10:5: warning\nhttps://example.com/a\n";
+        assert!(summarize(log).is_none());
+        assert!(matches_package("@acme/api", "api"));
+        assert!(!matches_package("@acme/api", "acme"));
+    }
+}
@@ -0,0 +1,359 @@
+//! Scheduled pipeline runs.
+//!
+//! Nightly and other scheduled suites fail without anyone pushing a
+//! commit, so nobody is watching when they do. [`CircleClient::scheduled_runs`]
+//! finds the scheduled pipelines of a project over a time window and the
+//! result of each of their workflows, and [`NightlySummary`] adds them up
+//! week by week and points at the latest failure.
+
+use crate::resolve::Pipeline;
+use crate::workflow::Page;
+use crate::{project_slug, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// How many pipelines, scheduled or not, are looked back through.
+pub const MAX_PIPELINES: usize = 500;
+
+/// Which scheduled runs to list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NightlyQuery {
+    /// Only workflows with this name.
+    pub workflow: Option<String>,
+    /// Only pipelines on this branch.
+    pub branch: Option<String>,
+    /// Only pipelines created at or after this time.
+    pub since: DateTime<Utc>,
+}
+
+/// One workflow of a scheduled pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledRun {
+    /// Number of the pipeline.
+    pub pipeline_number: u32,
+    /// When the schedule started the pipeline.
+    pub created_at: Option<DateTime<Utc>>,
+    /// Branch the pipeline ran on.
+    pub branch: Option<String>,
+    /// Full commit SHA.
+    pub revision: Option<String>,
+    /// UUID of the workflow.
+    pub workflow_id: String,
+    /// Name of the workflow.
+    pub workflow_name: String,
+    /// Workflow status (e.g., "success", "failed", "running").
+    pub status: String,
+}
+
+impl ScheduledRun {
+    /// Returns `true` if the workflow failed or errored.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status.as_str(), "failed" | "error" | "failing")
+    }
+
+    /// Returns `true` if the workflow succeeded.
+    pub fn is_passed(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+/// Results of the scheduled runs in a time window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NightlySummary {
+    /// Start of the window.
+    pub since: DateTime<Utc>,
+    /// The runs, newest first.
+    pub runs: Vec<ScheduledRun>,
+    /// Number of runs that succeeded.
+    pub passed: usize,
+    /// Number of runs that failed.
+    pub failed: usize,
+    /// Failed runs in a row, counting back from the newest finished run.
+    pub failing_streak: usize,
+    /// Results per week, oldest first.
+    pub weeks: Vec<WeekSummary>,
+}
+
+/// Results of the scheduled runs in one week.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeekSummary {
+    /// Monday the week starts on.
+    pub week_of: NaiveDate,
+    /// Number of runs that succeeded.
+    pub passed: usize,
+    /// Number of runs that failed.
+    pub failed: usize,
+    /// Number of runs still running, canceled, or otherwise unfinished.
+    pub other: usize,
+}
+
+impl NightlySummary {
+    /// Adds up `runs`, given newest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::nightly::{NightlySummary, ScheduledRun};
+    ///
+    /// let run = |number: u32, status: &str| ScheduledRun {
+    ///     pipeline_number: number,
+    ///     created_at: None,
+    ///     branch: Some("main".to_string()),
+    ///     revision: None,
+    ///     workflow_id: format!("w-{}", number),
+    ///     workflow_name: "nightly".to_string(),
+    ///     status: status.to_string(),
+    /// };
+    /// let runs = vec![run(4, "running"), run(3, "failed"), run(2, "failed"), run(1, "success")];
+    /// let summary = NightlySummary::new(runs, chrono::Utc::now());
+    /// assert_eq!((summary.passed, summary.failed, summary.failing_streak), (1, 2, 2));
+    /// assert_eq!(summary.latest_failure().unwrap().pipeline_number, 3);
+    /// ```
+    pub fn new(runs: Vec<ScheduledRun>, since: DateTime<Utc>) -> Self {
+        let failing_streak = runs
+            .iter()
+            .filter(|run| run.is_failed() || run.is_passed())
+            .take_while(|run| run.is_failed())
+            .count();
+
+        let mut weeks: Vec<WeekSummary> = Vec::new();
+        for run in runs.iter().rev() {
+            let Some(created_at) = run.created_at else {
+                continue;
+            };
+            let day = created_at.date_naive();
+            let week_of = day - Duration::days(i64::from(day.weekday().num_days_from_monday()));
+            if weeks.last().is_none_or(|week| week.week_of != week_of) {
+                weeks.push(WeekSummary {
+                    week_of,
+                    passed: 0,
+                    failed: 0,
+                    other: 0,
+                });
+            }
+            let week = weeks.last_mut().expect("a week was just pushed");
+            if run.is_passed() {
+                week.passed += 1;
+            } else if run.is_failed() {
+                week.failed += 1;
+            } else {
+                week.other += 1;
+            }
+        }
+
+        NightlySummary {
+            since,
+            passed: runs.iter().filter(|run| run.is_passed()).count(),
+            failed: runs.iter().filter(|run| run.is_failed()).count(),
+            failing_streak,
+            weeks,
+            runs,
+        }
+    }
+
+    /// Returns the newest failed run.
+    pub fn latest_failure(&self) -> Option<&ScheduledRun> {
+        self.runs.iter().find(|run| run.is_failed())
+    }
+
+    /// Fraction of finished runs that succeeded, `0.0..=1.0`, or `None`
+    /// without finished runs.
+    pub fn pass_rate(&self) -> Option<f64> {
+        let finished = self.passed + self.failed;
+        (finished > 0).then(|| self.passed as f64 / finished as f64)
+    }
+}
+
+impl CircleClient {
+    /// Lists the workflows of a project's scheduled pipelines created since
+    /// `query.since`, newest first.
+    ///
+    /// Looks back through at most [`MAX_PIPELINES`] pipelines of any kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipelines or a pipeline's workflows can't be
+    /// listed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::nightly::{NightlyQuery, NightlySummary};
+    /// use circle_debug::CircleClient;
+    ///
+    /// let since = chrono::Utc::now() - chrono::Duration::days(28);
+    /// let query = NightlyQuery {
+    ///     workflow: Some("nightly-regression".to_string()),
+    ///     branch: None,
+    ///     since,
+    /// };
+    /// let runs = CircleClient::new()?.scheduled_runs("myorg", "myrepo", &query).await?;
+    /// let summary = NightlySummary::new(runs, since);
+    /// println!("{} of the last {} runs failed", summary.failed, summary.runs.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn scheduled_runs(
+        &self,
+        org: &str,
+        project: &str,
+        query: &NightlyQuery,
+    ) -> Result<Vec<ScheduledRun>> {
+        let base = format!(
+            "{}/v2/project/{}/pipeline",
+            self.base_url,
+            project_slug(org, project)
+        );
+        let mut pipelines: Vec<Pipeline> = Vec::new();
+        let mut seen = 0;
+        let mut page_token: Option<String> = None;
+
+        'pages: loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(branch) = &query.branch {
+                url.query_pairs_mut().append_pair("branch", branch);
+            }
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<Pipeline> = self
+                .get_json(url.as_str())
+                .await
+                .with_context(|| format!("Failed to list the pipelines of {}/{}", org, project))?;
+
+            for pipeline in page.items {
+                seen += 1;
+                if pipeline.created_at.is_some_and(|at| at < query.since) || seen > MAX_PIPELINES {
+                    break 'pages;
+                }
+                if pipeline.is_scheduled() {
+                    pipelines.push(pipeline);
+                }
+            }
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        let workflows = futures::future::join_all(
+            pipelines
+                .iter()
+                .map(|pipeline| self.get_pipeline_workflows(&pipeline.id)),
+        )
+        .await;
+
+        let mut runs = Vec::new();
+        for (pipeline, workflows) in pipelines.iter().zip(workflows) {
+            for workflow in workflows? {
+                if query
+                    .workflow
+                    .as_ref()
+                    .is_some_and(|name| *name != workflow.name)
+                {
+                    continue;
+                }
+                let vcs = pipeline.vcs.as_ref();
+                runs.push(ScheduledRun {
+                    pipeline_number: pipeline.number,
+                    created_at: pipeline.created_at,
+                    branch: vcs.and_then(|vcs| vcs.branch.clone()),
+                    revision: vcs.and_then(|vcs| vcs.revision.clone()),
+                    workflow_id: workflow.id,
+                    workflow_name: workflow.name,
+                    status: workflow.status,
+                });
+            }
+        }
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_scheduled_runs_skip_other_triggers_and_workflows() {
+        let server = FakeCircleCi::start().await;
+        let mount = |route: &str, body: serde_json::Value| {
+            Mock::given(method("GET"))
+                .and(path(format!("/api{}", route)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        };
+        let pipeline = |id: &str, number: u32, day: u32, trigger: &str| {
+            serde_json::json!({
+                "id": id, "number": number, "state": "created",
+                "created_at": format!("2024-05-{:02}T02:00:00Z", day),
+                "vcs": {"branch": "main", "revision": "abc123"},
+                "trigger": {"type": trigger}
+            })
+        };
+        let workflows = |pipeline: &str, number: u32, status: &str| {
+            serde_json::json!({
+                "items": [
+                    {"id": format!("{}-nightly", pipeline), "name": "nightly-regression",
+                     "status": status, "pipeline_id": pipeline, "pipeline_number": number},
+                    {"id": format!("{}-lint", pipeline), "name": "lint",
+                     "status": "success", "pipeline_id": pipeline, "pipeline_number": number}
+                ],
+                "next_page_token": null
+            })
+        };
+        for mock in [
+            mount(
+                "/v2/project/gh/org/repo/pipeline",
+                serde_json::json!({
+                    "items": [
+                        pipeline("p-4", 4, 14, "scheduled_pipeline"),
+                        pipeline("p-3", 3, 13, "webhook"),
+                        pipeline("p-2", 2, 6, "schedule"),
+                        pipeline("p-1", 1, 1, "scheduled_pipeline")
+                    ],
+                    "next_page_token": null
+                }),
+            ),
+            mount("/v2/pipeline/p-4/workflow", workflows("p-4", 4, "failed")),
+            mount("/v2/pipeline/p-2/workflow", workflows("p-2", 2, "success")),
+        ] {
+            mock.mount(server.server()).await;
+        }
+
+        let since = "2024-05-02T00:00:00Z".parse().unwrap();
+        let query = NightlyQuery {
+            workflow: Some("nightly-regression".to_string()),
+            branch: None,
+            since,
+        };
+        let runs = server
+            .client()
+            .scheduled_runs("org", "repo", &query)
+            .await
+            .unwrap();
+        let numbers: Vec<u32> = runs.iter().map(|run| run.pipeline_number).collect();
+        assert_eq!(numbers, vec![4, 2]);
+
+        let summary = NightlySummary::new(runs, since);
+        assert_eq!(summary.failing_streak, 1);
+        assert_eq!(summary.pass_rate(), Some(0.5));
+        let weeks: Vec<(String, usize, usize)> = summary
+            .weeks
+            .iter()
+            .map(|week| (week.week_of.to_string(), week.passed, week.failed))
+            .collect();
+        assert_eq!(
+            weeks,
+            vec![
+                ("2024-05-06".to_string(), 1, 0),
+                ("2024-05-13".to_string(), 0, 1)
+            ]
+        );
+    }
+}
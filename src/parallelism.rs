@@ -0,0 +1,527 @@
+//! Test-splitting advice from per-test timings.
+//!
+//! A job's `parallelism` is usually a guess. Given how long each test took,
+//! as CircleCI's test metadata or the build's JUnit reports record it,
+//! [`advise`] estimates the job's wall time and credits at each
+//! parallelism, recommends the level past which more containers mostly
+//! add cost, and says whether splitting by timings beats splitting by
+//! name.
+//!
+//! Tests are split by file, as `circleci tests run` does, so one slow file
+//! sets a floor no parallelism gets under. Every container also repeats
+//! the job's other steps (checkout, restoring caches, installing
+//! dependencies), which [`setup_millis`] takes from the analyzed build.
+
+use crate::artifacts::Artifact;
+use crate::cost;
+use crate::workflow::Page;
+use crate::{BuildInfo, CircleClient, CircleDebugError};
+use anyhow::{Context, Result};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// How much slower than the fastest estimate the recommendation may be.
+/// Containers past that point save little time for their credits.
+const GOOD_ENOUGH: f64 = 1.1;
+
+/// How much slower than splitting by timings splitting by name may be and
+/// still be recommended, as it needs no stored test results.
+const NAME_SPLIT_SLACK: f64 = 1.05;
+
+/// One test and how long it took.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestTiming {
+    /// Test name.
+    pub name: String,
+    /// Class or suite of the test.
+    pub classname: Option<String>,
+    /// File the test is in, when the reporter records it.
+    pub file: Option<String>,
+    /// Run time in milliseconds.
+    pub millis: u64,
+}
+
+impl TestTiming {
+    /// Returns what the test is split by: its file, or its class when the
+    /// file isn't recorded.
+    fn unit(&self) -> &str {
+        self.file
+            .as_deref()
+            .or(self.classname.as_deref())
+            .unwrap_or(&self.name)
+    }
+}
+
+/// A test result of CircleCI's test metadata.
+#[derive(Debug, Deserialize)]
+struct TestMetadata {
+    name: String,
+    classname: Option<String>,
+    file: Option<String>,
+    /// Run time in seconds.
+    #[serde(default)]
+    run_time: f64,
+}
+
+/// How `circleci tests run` divides test files among containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitBy {
+    /// `--split-by=timings`: balanced by the stored run times.
+    Timings,
+    /// `--split-by=name`: dealt out in name order.
+    Name,
+}
+
+impl std::fmt::Display for SplitBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitBy::Timings => write!(f, "timings"),
+            SplitBy::Name => write!(f, "name"),
+        }
+    }
+}
+
+/// A test file and its tests' total run time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileTiming {
+    /// File, or class when the file isn't recorded.
+    pub file: String,
+    /// Total run time in milliseconds.
+    pub millis: u64,
+}
+
+/// Estimated wall time and cost of the job at one parallelism.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParallelismEstimate {
+    /// Number of containers.
+    pub parallelism: u32,
+    /// Wall time in milliseconds: setup plus the slowest container's
+    /// tests, split by timings.
+    pub wall_millis: u64,
+    /// Credits across all containers; `None` for a resource class without
+    /// a known rate.
+    pub credits: Option<f64>,
+}
+
+/// Recommended parallelism and splitting for a test job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParallelismAdvice {
+    /// Number of tests with timings.
+    pub tests: usize,
+    /// Number of test files.
+    pub files: usize,
+    /// Run time of all tests, in milliseconds.
+    pub test_millis: u64,
+    /// Time every container spends outside the tests, in milliseconds.
+    pub setup_millis: u64,
+    /// Resource class the estimates are priced at.
+    pub resource_class: String,
+    /// Parallelism the analyzed build ran with.
+    pub current: u32,
+    /// Recommended parallelism.
+    pub recommended: u32,
+    /// Recommended splitting.
+    pub split_by: SplitBy,
+    /// The slowest test file, which no parallelism splits.
+    pub slowest_file: Option<FileTiming>,
+    /// Estimates from 1 container up, one per parallelism.
+    pub estimates: Vec<ParallelismEstimate>,
+}
+
+impl ParallelismAdvice {
+    /// Returns the estimate for `parallelism`, if it was estimated.
+    pub fn estimate(&self, parallelism: u32) -> Option<&ParallelismEstimate> {
+        self.estimates
+            .iter()
+            .find(|estimate| estimate.parallelism == parallelism)
+    }
+}
+
+/// Returns the run time of each test file, slowest first.
+fn file_timings(tests: &[TestTiming]) -> Vec<FileTiming> {
+    let mut files: BTreeMap<&str, u64> = BTreeMap::new();
+    for test in tests {
+        *files.entry(test.unit()).or_default() += test.millis;
+    }
+    let mut files: Vec<FileTiming> = files
+        .into_iter()
+        .map(|(file, millis)| FileTiming {
+            file: file.to_string(),
+            millis,
+        })
+        .collect();
+    files.sort_by_key(|file| std::cmp::Reverse(file.millis));
+    files
+}
+
+/// Returns the slowest container's run time when `files` are split over
+/// `containers` by timings: each file, slowest first, goes to the
+/// container with the least so far.
+fn split_by_timings(files: &[FileTiming], containers: u32) -> u64 {
+    let mut loads = vec![0u64; containers.max(1) as usize];
+    for file in files {
+        if let Some(least) = loads.iter_mut().min() {
+            *least += file.millis;
+        }
+    }
+    loads.into_iter().max().unwrap_or(0)
+}
+
+/// Returns the slowest container's run time when `files` are dealt out
+/// over `containers` in name order.
+fn split_by_name(files: &[FileTiming], containers: u32) -> u64 {
+    let mut sorted: Vec<&FileTiming> = files.iter().collect();
+    sorted.sort_by(|a, b| a.file.cmp(&b.file));
+    let containers = containers.max(1) as usize;
+    let mut loads = vec![0u64; containers];
+    for (i, file) in sorted.into_iter().enumerate() {
+        loads[i % containers] += file.millis;
+    }
+    loads.into_iter().max().unwrap_or(0)
+}
+
+/// Returns how long one container of `build` spent outside its test steps:
+/// the run time of container 0's actions in steps not named like tests.
+pub fn setup_millis(build: &BuildInfo) -> u64 {
+    build
+        .steps
+        .iter()
+        .filter(|step| !step.name.to_ascii_lowercase().contains("test"))
+        .filter_map(|step| {
+            step.actions
+                .iter()
+                .find(|action| action.index.unwrap_or(0) == 0)
+        })
+        .filter_map(|action| action.run_time_millis)
+        .sum()
+}
+
+/// Recommends a parallelism and splitting for `build`'s tests.
+///
+/// Wall time is estimated at each parallelism from 1 to
+/// `max_parallelism` (or the build's own, if higher) as the build's setup
+/// time plus the slowest container's tests, split by timings. The
+/// recommendation is the lowest parallelism within 10% of the fastest
+/// estimate. Credits are priced at the build's resource class.
+///
+/// # Returns
+///
+/// `None` when there are no test timings.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::parallelism::{advise, TestTiming};
+///
+/// let build = serde_json::from_value(serde_json::json!({
+///     "build_num": 1, "status": "failed", "branch": null, "subject": null, "steps": []
+/// }))
+/// .unwrap();
+/// let tests: Vec<TestTiming> = (0..8)
+///     .map(|i| TestTiming {
+///         name: format!("test {}", i),
+///         classname: None,
+///         file: Some(format!("spec/{}_spec.rb", i)),
+///         millis: 60_000,
+///     })
+///     .collect();
+/// let advice = advise(&build, &tests, 10).unwrap();
+/// assert_eq!(advice.recommended, 8);
+/// assert_eq!(advice.estimate(4).unwrap().wall_millis, 120_000);
+/// ```
+pub fn advise(
+    build: &BuildInfo,
+    tests: &[TestTiming],
+    max_parallelism: u32,
+) -> Option<ParallelismAdvice> {
+    if tests.is_empty() {
+        return None;
+    }
+    let files = file_timings(tests);
+    let setup_millis = setup_millis(build);
+    let resource_class = build
+        .picard
+        .as_ref()
+        .and_then(|picard| picard.resource_class.as_ref())
+        .and_then(|resource_class| resource_class.class.clone())
+        .unwrap_or_else(|| cost::DEFAULT_RESOURCE_CLASS.to_string());
+    let rate = cost::credits_per_minute(&resource_class);
+    let current = build.parallel.unwrap_or(1).max(1);
+
+    let estimates: Vec<ParallelismEstimate> = (1..=max_parallelism.max(current))
+        .map(|parallelism| {
+            let wall_millis = setup_millis + split_by_timings(&files, parallelism);
+            // Billed per started minute, as in cost::estimate
+            let minutes = wall_millis.div_ceil(60_000).max(1) as f64;
+            ParallelismEstimate {
+                parallelism,
+                wall_millis,
+                credits: rate.map(|rate| rate * minutes * parallelism as f64),
+            }
+        })
+        .collect();
+    let fastest = estimates.iter().map(|e| e.wall_millis).min().unwrap_or(0);
+    let recommended = estimates
+        .iter()
+        .find(|e| e.wall_millis as f64 <= fastest as f64 * GOOD_ENOUGH)
+        .map_or(1, |e| e.parallelism);
+    let by_name = split_by_name(&files, recommended) as f64;
+    let by_timings = split_by_timings(&files, recommended) as f64;
+    let split_by = if by_name <= by_timings * NAME_SPLIT_SLACK {
+        SplitBy::Name
+    } else {
+        SplitBy::Timings
+    };
+
+    Some(ParallelismAdvice {
+        tests: tests.len(),
+        files: files.len(),
+        test_millis: files.iter().map(|file| file.millis).sum(),
+        setup_millis,
+        resource_class,
+        current,
+        recommended,
+        split_by,
+        slowest_file: files.first().cloned(),
+        estimates,
+    })
+}
+
+fn testcase_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"<testcase\b([^>]*)>").unwrap())
+}
+
+fn attribute_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"\b(name|classname|file|time)="([^"]*)""#).unwrap())
+}
+
+/// Replaces the XML entities JUnit reporters escape attributes with.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Returns the tests of a JUnit XML report with their run times, from
+/// each `<testcase>`'s `name`, `classname`, `file`, and `time` (seconds).
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::parallelism::parse_junit;
+///
+/// let xml = r#"<testsuite name="api"><testcase classname="api.UserTest" name="creates a user" time="1.25"/></testsuite>"#;
+/// let tests = parse_junit(xml);
+/// assert_eq!(tests[0].classname.as_deref(), Some("api.UserTest"));
+/// assert_eq!(tests[0].millis, 1250);
+/// ```
+pub fn parse_junit(xml: &str) -> Vec<TestTiming> {
+    testcase_regex()
+        .captures_iter(xml)
+        .filter_map(|caps| {
+            let mut test = TestTiming {
+                name: String::new(),
+                classname: None,
+                file: None,
+                millis: 0,
+            };
+            let mut named = false;
+            for attr in attribute_regex().captures_iter(&caps[1]) {
+                let value = unescape(&attr[2]);
+                match &attr[1] {
+                    "name" => {
+                        test.name = value;
+                        named = true;
+                    }
+                    "classname" => test.classname = Some(value),
+                    "file" => test.file = Some(value),
+                    _ => {
+                        let seconds: f64 = value.trim().parse().unwrap_or(0.0);
+                        test.millis = (seconds * 1000.0).round() as u64;
+                    }
+                }
+            }
+            named.then_some(test)
+        })
+        .collect()
+}
+
+/// Whether an artifact looks like a JUnit report.
+fn is_junit_report(artifact: &Artifact) -> bool {
+    let path = artifact.path.to_ascii_lowercase();
+    path.ends_with(".xml") && (path.contains("junit") || path.contains("test"))
+}
+
+impl CircleClient {
+    /// Fetches the run time of each test of a build.
+    ///
+    /// Uses the test metadata CircleCI keeps from `store_test_results`,
+    /// and, for builds without any, the JUnit XML reports among the
+    /// build's artifacts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the test metadata or the artifacts cannot be
+    /// fetched. Reports that fail to download are skipped.
+    pub async fn test_timings(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+    ) -> Result<Vec<TestTiming>> {
+        let base = format!(
+            "{}/v2/project/{}/{}/tests",
+            self.base_url,
+            crate::project_slug(org, project),
+            build_num
+        );
+        let mut tests = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<TestMetadata> = match self.get_json(url.as_str()).await {
+                Ok(page) => page,
+                Err(err)
+                    if err
+                        .downcast_ref::<CircleDebugError>()
+                        .is_some_and(CircleDebugError::is_not_found) =>
+                {
+                    break;
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("Failed to fetch test results of build #{}", build_num)
+                    })
+                }
+            };
+            tests.extend(page.items.into_iter().map(|test| TestTiming {
+                name: test.name,
+                classname: test.classname,
+                file: test.file.filter(|file| !file.is_empty()),
+                millis: (test.run_time * 1000.0).round() as u64,
+            }));
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+        if !tests.is_empty() {
+            return Ok(tests);
+        }
+
+        let artifacts = self.get_artifacts(org, project, build_num).await?;
+        let reports: Vec<&Artifact> = artifacts.iter().filter(|a| is_junit_report(a)).collect();
+        let bodies =
+            futures::future::join_all(reports.iter().map(|artifact| self.get_artifact(artifact)))
+                .await;
+        Ok(bodies
+            .into_iter()
+            .flatten()
+            .flat_map(|body| parse_junit(&String::from_utf8_lossy(&body)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, FakeCircleCi, StepBuilder};
+
+    fn test(file: &str, seconds: u64) -> TestTiming {
+        TestTiming {
+            name: file.to_string(),
+            classname: None,
+            file: Some(file.to_string()),
+            millis: seconds * 1000,
+        }
+    }
+
+    #[test]
+    fn test_advise_stops_at_the_slowest_file() {
+        let build = BuildBuilder::new(1)
+            .step(
+                StepBuilder::new("Checkout code")
+                    .action(ActionBuilder::new("Checkout code").run_time_millis(30_000)),
+            )
+            .step(
+                StepBuilder::new("Run tests")
+                    .action(ActionBuilder::new("Run tests").run_time_millis(600_000)),
+            )
+            .build();
+        // One 5-minute file and 20 files of 30s
+        let mut tests = vec![test("spec/slow_spec.rb", 300)];
+        tests.extend((0..20).map(|i| test(&format!("spec/{:02}_spec.rb", i), 30)));
+
+        let advice = advise(&build, &tests, 20).unwrap();
+        assert_eq!(advice.setup_millis, 30_000);
+        assert_eq!(advice.test_millis, 900_000);
+        assert_eq!(
+            advice.slowest_file.as_ref().unwrap().file,
+            "spec/slow_spec.rb"
+        );
+        assert_eq!(advice.estimate(1).unwrap().wall_millis, 930_000);
+        // From 3 containers, the slow file alone is the longest one
+        assert_eq!(advice.estimate(3).unwrap().wall_millis, 330_000);
+        assert_eq!(advice.estimate(10).unwrap().wall_millis, 330_000);
+        assert_eq!(advice.recommended, 3);
+        // Name order puts the slow file after 20 others, so it shares a container
+        assert_eq!(advice.split_by, SplitBy::Timings);
+        assert_eq!(advice.estimate(3).unwrap().credits, Some(180.0));
+
+        assert!(advise(&build, &[], 20).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_test_timings_fall_back_to_junit_artifacts() {
+        let server = FakeCircleCi::start().await;
+        server
+            .mount_artifacts(
+                "org",
+                "repo",
+                7,
+                &[
+                    (
+                        "test-results/junit.xml",
+                        r#"<testsuites><testsuite><testcase name="a &amp; b" classname="A" file="a_test.go" time="2.5"></testcase><testcase name="c" classname="C" time="0.1"/></testsuite></testsuites>"#,
+                    ),
+                    ("coverage/cobertura.xml", "<coverage/>"),
+                ],
+            )
+            .await;
+
+        let tests = server
+            .client()
+            .test_timings("org", "repo", 7)
+            .await
+            .unwrap();
+        assert_eq!(
+            tests,
+            vec![
+                TestTiming {
+                    name: "a & b".to_string(),
+                    classname: Some("A".to_string()),
+                    file: Some("a_test.go".to_string()),
+                    millis: 2500,
+                },
+                TestTiming {
+                    name: "c".to_string(),
+                    classname: Some("C".to_string()),
+                    file: None,
+                    millis: 100,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,528 @@
+//! Pipeline parameters: what a project's config declares, and triggering
+//! pipelines with them.
+//!
+//! CircleCI rejects a trigger whose parameters the config doesn't declare,
+//! but only after the pipeline is created, as an errored pipeline; a
+//! mistyped name on a parameter with a default doesn't even do that, it
+//! just runs with the default. [`declared_parameters`] reads the
+//! `parameters:` section of a config, and [`validate`] checks `name=value`
+//! pairs against it before anything is sent:
+//!
+//! ```yaml
+//! parameters:
+//!   run-e2e:
+//!     type: boolean
+//!     default: false
+//!   deploy-env:
+//!     type: enum
+//!     enum: [staging, production]
+//!     default: staging
+//! ```
+
+use crate::resolve::Pipeline;
+use crate::workflow::Page;
+use crate::{project_slug, CircleClient};
+use anyhow::{Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Type of a pipeline parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterType {
+    String,
+    Boolean,
+    Integer,
+    Enum,
+}
+
+impl ParameterType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(ParameterType::String),
+            "boolean" => Some(ParameterType::Boolean),
+            "integer" => Some(ParameterType::Integer),
+            "enum" => Some(ParameterType::Enum),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterType::String => write!(f, "string"),
+            ParameterType::Boolean => write!(f, "boolean"),
+            ParameterType::Integer => write!(f, "integer"),
+            ParameterType::Enum => write!(f, "enum"),
+        }
+    }
+}
+
+/// A pipeline parameter the config declares.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineParameter {
+    /// Parameter name.
+    pub name: String,
+    /// Declared type; `None` if the config names one CircleCI doesn't
+    /// allow for pipeline parameters.
+    #[serde(rename = "type")]
+    pub param_type: Option<ParameterType>,
+    /// Default value as written; `None` makes the parameter required.
+    pub default: Option<String>,
+    /// Description, if the config gives one.
+    pub description: Option<String>,
+    /// Values an `enum` parameter allows.
+    #[serde(default)]
+    pub allowed: Vec<String>,
+}
+
+impl PipelineParameter {
+    /// Converts `value` to the JSON value CircleCI expects for this
+    /// parameter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` isn't a valid value of the parameter's
+    /// type.
+    pub fn to_json(&self, value: &str) -> Result<Value> {
+        match self.param_type {
+            Some(ParameterType::Boolean) => match value {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => anyhow::bail!("'{}' expects true or false, got '{}'", self.name, value),
+            },
+            Some(ParameterType::Integer) => value.parse::<i64>().map(Value::from).map_err(|_| {
+                anyhow::anyhow!("'{}' expects an integer, got '{}'", self.name, value)
+            }),
+            Some(ParameterType::Enum) if !self.allowed.iter().any(|allowed| allowed == value) => {
+                anyhow::bail!(
+                    "'{}' expects one of {}, got '{}'",
+                    self.name,
+                    self.allowed.join(", "),
+                    value
+                )
+            }
+            _ => Ok(Value::String(value.to_string())),
+        }
+    }
+}
+
+/// Strips a trailing `# comment` and surrounding quotes from a YAML
+/// scalar.
+fn scalar(value: &str) -> String {
+    let value = match value.find(" #") {
+        Some(comment) if !value.starts_with(['"', '\'']) => &value[..comment],
+        _ => value,
+    };
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+/// Splits a flow collection's inside at top-level commas.
+fn flow_items(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(inner[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Returns the leading spaces of `line`.
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Sets one property of `param` from its YAML key and value.
+fn set_property(param: &mut PipelineParameter, key: &str, value: &str) {
+    match key {
+        "type" => param.param_type = ParameterType::parse(&scalar(value)),
+        "default" => param.default = Some(scalar(value)),
+        "description" => param.description = Some(scalar(value)),
+        "enum" => {
+            if let Some(inner) = value
+                .trim()
+                .strip_prefix('[')
+                .and_then(|v| v.strip_suffix(']'))
+            {
+                param.allowed = flow_items(inner).into_iter().map(scalar).collect();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the pipeline parameters a config declares in its top-level
+/// `parameters:` section, in order.
+///
+/// Reads the block and flow styles configs use for it (`enum: [a, b]` or a
+/// `- a` list, `name: {type: boolean, default: false}`); anchors and
+/// multi-line strings aren't followed.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::parameters::{declared_parameters, ParameterType};
+///
+/// let config = "version: 2.1\n\
+///               parameters:\n  \
+///                 run-e2e:\n    \
+///                   type: boolean\n    \
+///                   default: false\n\
+///               workflows: {}\n";
+/// let params = declared_parameters(config);
+/// assert_eq!(params[0].name, "run-e2e");
+/// assert_eq!(params[0].param_type, Some(ParameterType::Boolean));
+/// assert_eq!(params[0].default.as_deref(), Some("false"));
+/// ```
+pub fn declared_parameters(config: &str) -> Vec<PipelineParameter> {
+    let mut params: Vec<PipelineParameter> = Vec::new();
+    let mut lines = config
+        .lines()
+        .skip_while(|line| indent(line) > 0 || scalar(line) != "parameters:")
+        .skip(1)
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .take_while(|line| indent(line) > 0)
+        .peekable();
+    let Some(name_indent) = lines.peek().map(|line| indent(line)) else {
+        return params;
+    };
+    // Whether block list items belong to the current parameter's `enum:`
+    let mut in_enum = false;
+
+    for line in lines {
+        let text = line.trim();
+        if indent(line) == name_indent {
+            let Some((name, rest)) = text.split_once(':') else {
+                continue;
+            };
+            let mut param = PipelineParameter {
+                name: scalar(name),
+                param_type: None,
+                default: None,
+                description: None,
+                allowed: Vec::new(),
+            };
+            if let Some(inner) = rest
+                .trim()
+                .strip_prefix('{')
+                .and_then(|r| r.strip_suffix('}'))
+            {
+                for item in flow_items(inner) {
+                    if let Some((key, value)) = item.split_once(':') {
+                        set_property(&mut param, key.trim(), value);
+                    }
+                }
+            }
+            params.push(param);
+            in_enum = false;
+        } else if let Some(param) = params.last_mut() {
+            if let Some(item) = text.strip_prefix("- ").filter(|_| in_enum) {
+                param.allowed.push(scalar(item));
+            } else if let Some((key, value)) = text.split_once(':') {
+                in_enum = key == "enum" && value.trim().is_empty();
+                set_property(param, key, value);
+            }
+        }
+    }
+    params
+}
+
+/// Parses a `name=value` argument.
+///
+/// # Errors
+///
+/// Returns an error if there's no `=` or the name is empty.
+pub fn parse_assignment(arg: &str) -> Result<(String, String)> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => anyhow::bail!("Invalid parameter '{}', expected NAME=VALUE", arg),
+    }
+}
+
+/// Checks `supplied` parameters against the `declared` ones and converts
+/// them to the JSON a trigger sends.
+///
+/// # Errors
+///
+/// Returns an error listing every undeclared name, with the declared
+/// names, every value of the wrong type, and every required parameter
+/// left out.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::parameters::{declared_parameters, validate};
+///
+/// let declared = declared_parameters(
+///     "parameters:\n  run-e2e:\n    type: boolean\n    default: false\n",
+/// );
+/// let supplied = vec![("run-e2e".to_string(), "true".to_string())];
+/// assert_eq!(validate(&declared, &supplied).unwrap()["run-e2e"], true);
+///
+/// let typo = vec![("run_e2e".to_string(), "true".to_string())];
+/// assert!(validate(&declared, &typo).is_err());
+/// ```
+pub fn validate(
+    declared: &[PipelineParameter],
+    supplied: &[(String, String)],
+) -> Result<Map<String, Value>> {
+    let mut values = Map::new();
+    let mut problems = Vec::new();
+    for (name, value) in supplied {
+        match declared.iter().find(|param| param.name == *name) {
+            Some(param) => match param.to_json(value) {
+                Ok(json) => {
+                    values.insert(name.clone(), json);
+                }
+                Err(e) => problems.push(e.to_string()),
+            },
+            None => problems.push(format!("'{}' isn't a declared parameter", name)),
+        }
+    }
+    for param in declared {
+        if param.default.is_none() && !values.contains_key(&param.name) {
+            problems.push(format!("'{}' is required (it has no default)", param.name));
+        }
+    }
+    if !problems.is_empty() {
+        let names: Vec<&str> = declared.iter().map(|param| param.name.as_str()).collect();
+        anyhow::bail!(
+            "{}\n  declared parameters: {}",
+            problems.join("\n"),
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        );
+    }
+    Ok(values)
+}
+
+impl CircleClient {
+    /// Fetches the pipeline parameters the project's config declares, as of
+    /// the latest pipeline on `branch` (any branch if `None`).
+    ///
+    /// Reads the compiled config, falling back to the config as committed
+    /// when the compiled one has no `parameters:` section.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project has no pipeline on the branch yet,
+    /// or a request fails.
+    pub async fn pipeline_parameters(
+        &self,
+        org: &str,
+        project: &str,
+        branch: Option<&str>,
+    ) -> Result<Vec<PipelineParameter>> {
+        let mut url = Url::parse(&format!(
+            "{}/v2/project/{}/pipeline",
+            self.base_url,
+            project_slug(org, project)
+        ))?;
+        if let Some(branch) = branch {
+            url.query_pairs_mut().append_pair("branch", branch);
+        }
+        let page: Page<Pipeline> = self
+            .get_json(url.as_str())
+            .await
+            .with_context(|| format!("Failed to list the pipelines of {}/{}", org, project))?;
+        let Some(latest) = page.items.first() else {
+            match branch {
+                Some(branch) => anyhow::bail!(
+                    "{}/{} has no pipelines on {} to read the config from",
+                    org,
+                    project,
+                    branch
+                ),
+                None => anyhow::bail!(
+                    "{}/{} has no pipelines to read the config from",
+                    org,
+                    project
+                ),
+            }
+        };
+        let config = self.get_pipeline_config(&latest.id).await?;
+        let compiled = declared_parameters(&config.compiled);
+        Ok(if compiled.is_empty() {
+            declared_parameters(&config.source)
+        } else {
+            compiled
+        })
+    }
+
+    /// Triggers a pipeline on `branch` (the project's default branch if
+    /// `None`) with `parameters`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if CircleCI refuses the trigger, e.g. for a token
+    /// without write access.
+    pub async fn trigger_pipeline(
+        &self,
+        org: &str,
+        project: &str,
+        branch: Option<&str>,
+        parameters: Map<String, Value>,
+    ) -> Result<Pipeline> {
+        let url = format!(
+            "{}/v2/project/{}/pipeline",
+            self.base_url,
+            project_slug(org, project)
+        );
+        let mut body = Map::new();
+        if let Some(branch) = branch {
+            body.insert("branch".to_string(), Value::from(branch));
+        }
+        if !parameters.is_empty() {
+            body.insert("parameters".to_string(), Value::Object(parameters));
+        }
+        self.post_json(&url, Some(&Value::Object(body)))
+            .await
+            .with_context(|| format!("Failed to trigger a pipeline of {}/{}", org, project))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[test]
+    fn test_declared_parameters_and_validate() {
+        let config = r#"version: 2.1
+
+commands:
+  setup:
+    parameters:
+      node-version:
+        type: string
+
+parameters:
+  # Only on demand
+  run-e2e:
+    type: boolean
+    default: false
+    description: "Run the end-to-end workflow"
+  deploy-env:
+    type: enum
+    enum:
+      - staging
+      - production
+    default: staging
+  shards: {type: integer, default: 4}
+  image-tag:
+    type: string
+
+jobs:
+  test:
+    parameters:
+      node:
+        type: string
+"#;
+        let params = declared_parameters(config);
+        let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["run-e2e", "deploy-env", "shards", "image-tag"]);
+        assert_eq!(
+            params[0].description.as_deref(),
+            Some("Run the end-to-end workflow")
+        );
+        assert_eq!(params[1].allowed, ["staging", "production"]);
+        assert_eq!(params[2].param_type, Some(ParameterType::Integer));
+        assert_eq!(params[2].default.as_deref(), Some("4"));
+        assert_eq!(params[3].default, None);
+
+        let supplied: Vec<(String, String)> = ["shards=8", "image-tag=abc123"]
+            .into_iter()
+            .map(|arg| parse_assignment(arg).unwrap())
+            .collect();
+        let values = validate(&params, &supplied).unwrap();
+        assert_eq!(values["shards"], 8);
+        assert_eq!(values["image-tag"], "abc123");
+
+        let wrong: Vec<(String, String)> = ["deploy-env=prod", "shards=many", "e2e=true"]
+            .into_iter()
+            .map(|arg| parse_assignment(arg).unwrap())
+            .collect();
+        let message = validate(&params, &wrong).unwrap_err().to_string();
+        assert!(message.contains("'deploy-env' expects one of staging, production, got 'prod'"));
+        assert!(message.contains("'shards' expects an integer"));
+        assert!(message.contains("'e2e' isn't a declared parameter"));
+        assert!(message.contains("'image-tag' is required"));
+        assert!(parse_assignment("novalue").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_parameters_and_trigger() {
+        let server = FakeCircleCi::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/project/gh/org/repo/pipeline"))
+            .and(query_param("branch", "fix/a&b#1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "items": [{"id": "p-9", "number": 9, "state": "created"}],
+                "next_page_token": null
+            })))
+            .mount(server.server())
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/pipeline/p-9/config"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "source": "parameters:\n  run-e2e:\n    type: boolean\n    default: false\n",
+                "compiled": "version: 2\njobs: {}\n"
+            })))
+            .mount(server.server())
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/project/gh/org/repo/pipeline"))
+            .and(body_json(serde_json::json!({
+                "branch": "fix/a&b#1",
+                "parameters": {"run-e2e": true}
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "id": "p-10", "number": 10, "state": "setup-pending",
+                "created_at": "2024-05-01T12:00:00Z"
+            })))
+            .mount(server.server())
+            .await;
+        let client = server.client();
+
+        // The compiled config has none, so the source's are used
+        let declared = client
+            .pipeline_parameters("org", "repo", Some("fix/a&b#1"))
+            .await
+            .unwrap();
+        assert_eq!(declared[0].name, "run-e2e");
+        let values = validate(&declared, &[("run-e2e".to_string(), "true".to_string())]).unwrap();
+        let pipeline = client
+            .trigger_pipeline("org", "repo", Some("fix/a&b#1"), values)
+            .await
+            .unwrap();
+        assert_eq!(pipeline.number, 10);
+    }
+}
@@ -0,0 +1,109 @@
+//! Platform-specific file locations.
+//!
+//! Everything `cdb` writes on its own lives under the platform's cache
+//! directory, its configuration under the config directory, and the build
+//! history database, which shouldn't vanish with the cache, under the data
+//! directory:
+//!
+//! | | Linux | macOS | Windows |
+//! |---|---|---|---|
+//! | Config, credentials | `~/.config/cdb` | `~/Library/Application Support/cdb` | `%APPDATA%\cdb` |
+//! | Cache | `~/.cache/cdb` | `~/Library/Caches/cdb` | `%LOCALAPPDATA%\cdb` |
+//! | History | `~/.local/share/cdb` | `~/Library/Application Support/cdb` | `%APPDATA%\cdb` |
+//!
+//! When the platform has no cache or data directory, the system temp
+//! directory is used instead.
+
+use std::path::PathBuf;
+
+/// Name of the directory created under the platform locations.
+const APP_DIR: &str = "cdb";
+
+/// Returns the default configuration file path, if the platform has one.
+pub fn config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR).join("config.toml"))
+}
+
+/// Returns where `cdb init` stores the API token, if the platform has a
+/// config directory.
+pub fn credentials_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR).join("credentials.toml"))
+}
+
+/// Returns the directory for cached data, falling back to the temp
+/// directory.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR)
+}
+
+/// Returns the directory for data worth keeping, falling back to the temp
+/// directory.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR)
+}
+
+/// Returns where the local build history database lives.
+pub fn history_db() -> PathBuf {
+    data_dir().join("history.sqlite3")
+}
+
+/// Returns the directory HTTP responses are cached in.
+pub fn http_cache_dir() -> PathBuf {
+    cache_dir().join("http")
+}
+
+/// Returns the directory the full-text index of cached logs lives in.
+pub fn log_index_dir() -> PathBuf {
+    cache_dir().join("index")
+}
+
+/// Returns the directory cleaned logs are saved to.
+pub fn logs_dir() -> PathBuf {
+    cache_dir().join("logs")
+}
+
+/// Returns where the cleaned log of a build's step is saved.
+///
+/// `step` is the step's 0-based position in the build. Parallel builds get
+/// one file per container.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::paths;
+///
+/// let path = paths::saved_log(12345, 4, Some(2));
+/// assert!(path.ends_with("cdb-12345-4-2.log"));
+/// ```
+pub fn saved_log(build_num: u32, step: usize, container: Option<u32>) -> PathBuf {
+    let name = match container {
+        Some(container) => format!("cdb-{}-{}-{}.log", build_num, step, container),
+        None => format!("cdb-{}-{}.log", build_num, step),
+    };
+    logs_dir().join(name)
+}
+
+/// Returns where the Markdown analysis of a build is saved, next to its
+/// logs.
+pub fn saved_report(build_num: u32) -> PathBuf {
+    logs_dir().join(format!("cdb-{}-report.md", build_num))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locations_share_the_cache_dir() {
+        let cache = cache_dir();
+        assert!(cache.ends_with(APP_DIR));
+        assert!(http_cache_dir().starts_with(&cache));
+        assert_eq!(saved_log(7, 3, None), logs_dir().join("cdb-7-3.log"));
+        assert_eq!(saved_report(7), logs_dir().join("cdb-7-report.md"));
+        assert!(config_file().is_none_or(|path| path.ends_with("cdb/config.toml")));
+    }
+}
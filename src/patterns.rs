@@ -0,0 +1,804 @@
+//! Smart error detection.
+//!
+//! A set of regex patterns for common CI failures, and [`detect_errors`]
+//! which scans a log and groups every match by pattern so repeated errors
+//! are reported as counts instead of flooding the summary.
+//!
+//! Each pattern carries a [`Severity`] and a confidence score, so a
+//! specific "cannot find module X" ranks above a generic "exited with
+//! code 1" that merely reports the consequence.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// How serious a detected pattern is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Context that helps locate the failure but isn't the cause.
+    Hint,
+    /// Suspicious output that may or may not be the failure.
+    Warning,
+    /// A likely cause of the failure.
+    #[default]
+    Error,
+}
+
+impl Severity {
+    /// Ranking weight used when ordering detected errors.
+    pub fn weight(self) -> f32 {
+        match self {
+            Severity::Error => 3.0,
+            Severity::Warning => 2.0,
+            Severity::Hint => 1.0,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+/// What kind of problem a pattern points to, used for gating with `--fail-on`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// A problem in the code under test: compile, lint, or runtime errors.
+    #[default]
+    Code,
+    /// Failing tests or assertions.
+    Tests,
+    /// The CI environment itself: resources, network, or the executor.
+    Infra,
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureKind::Code => write!(f, "code"),
+            FailureKind::Tests => write!(f, "tests"),
+            FailureKind::Infra => write!(f, "infra"),
+        }
+    }
+}
+
+/// Confidence given to patterns that don't specify one.
+pub const DEFAULT_CONFIDENCE: f32 = 0.8;
+
+/// A known failure signature to look for in logs.
+#[derive(Debug, Clone)]
+pub struct ErrorPattern {
+    /// Stable identifier, e.g. `file-not-found`.
+    pub id: String,
+    /// Human-readable category shown in reports, e.g. "File Not Found".
+    pub category: String,
+    /// Matcher applied to each log line.
+    pub regex: Regex,
+    /// How serious a match is.
+    pub severity: Severity,
+    /// How likely a match is to be the real failure, from 0.0 to 1.0.
+    pub confidence: f32,
+    /// What kind of problem a match points to.
+    pub kind: FailureKind,
+    /// Whether a match is likely to go away on a rerun, like a dropped
+    /// connection or a registry outage.
+    pub transient: bool,
+}
+
+impl ErrorPattern {
+    /// Creates a non-transient, error-severity [`FailureKind::Code`]
+    /// pattern with [`DEFAULT_CONFIDENCE`], compiling `regex`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::patterns::ErrorPattern;
+    ///
+    /// let pattern = ErrorPattern::new("flaky-db", "Database Flake", r"(?i)connection refused").unwrap();
+    /// assert!(pattern.regex.is_match("Error: Connection refused (port 5432)"));
+    /// ```
+    pub fn new(id: &str, category: &str, regex: &str) -> Result<Self> {
+        Ok(ErrorPattern {
+            id: id.to_string(),
+            category: category.to_string(),
+            regex: Regex::new(regex)
+                .with_context(|| format!("invalid regex for pattern '{}'", id))?,
+            severity: Severity::Error,
+            confidence: DEFAULT_CONFIDENCE,
+            kind: FailureKind::Code,
+            transient: false,
+        })
+    }
+
+    /// Ranking score: severity weight × confidence.
+    pub fn score(&self) -> f32 {
+        self.severity.weight() * self.confidence
+    }
+}
+
+struct BuiltinPattern {
+    id: &'static str,
+    category: &'static str,
+    regex: &'static str,
+    severity: Severity,
+    confidence: f32,
+    kind: FailureKind,
+    transient: bool,
+}
+
+/// Built-in patterns in priority order.
+const BUILTIN_PATTERNS: &[BuiltinPattern] = &[
+    // High confidence - specific errors
+    BuiltinPattern {
+        id: "module-resolution",
+        category: "Module Resolution",
+        regex: r"(?i)\[commonjs--resolver\].*failed to resolve",
+        severity: Severity::Error,
+        confidence: 0.95,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "missing-module",
+        category: "Missing Module",
+        regex: r"(?i)cannot find module",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "file-not-found",
+        category: "File Not Found",
+        regex: r"(?i)ENOENT:.*no such file or directory",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "syntax-error",
+        category: "Syntax Error",
+        regex: r"(?i)syntaxerror:",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "type-error",
+        category: "Type Error",
+        regex: r"(?i)typeerror:",
+        severity: Severity::Error,
+        confidence: 0.8,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "reference-error",
+        category: "Reference Error",
+        regex: r"(?i)referenceerror:",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "segfault",
+        category: "Segfault",
+        regex: r"(?i)segmentation fault",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "out-of-memory",
+        category: "Out of Memory",
+        regex: r"(?i)(oom|out of memory|memory limit)",
+        severity: Severity::Error,
+        confidence: 0.6,
+        kind: FailureKind::Infra,
+        transient: false,
+    },
+    // Build & compilation
+    BuiltinPattern {
+        id: "build-failed",
+        category: "Build Failure",
+        regex: r"(?i)build failed",
+        severity: Severity::Error,
+        confidence: 0.6,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "compilation-failed",
+        category: "Compilation Error",
+        regex: r"(?i)compilation failed",
+        severity: Severity::Error,
+        confidence: 0.7,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "typescript-error",
+        category: "TypeScript Error",
+        regex: r"(?i)error TS\d+:",
+        severity: Severity::Error,
+        confidence: 0.95,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "lint-error",
+        category: "Lint Error",
+        regex: r"(?i)eslint.*error",
+        severity: Severity::Warning,
+        confidence: 0.6,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    // Test failures
+    BuiltinPattern {
+        id: "test-failure",
+        category: "Test Failure",
+        regex: r"(?i)test.*failed",
+        severity: Severity::Error,
+        confidence: 0.6,
+        kind: FailureKind::Tests,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "assertion-failure",
+        category: "Assertion Failure",
+        regex: r"(?i)assertion.*failed",
+        severity: Severity::Error,
+        confidence: 0.8,
+        kind: FailureKind::Tests,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "test-suite-failure",
+        category: "Test Suite Failure",
+        regex: r"(?i)\d+ (test|tests|spec|specs) failed",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Tests,
+        transient: false,
+    },
+    // JVM builds: Gradle and Maven
+    BuiltinPattern {
+        id: "javac-error",
+        category: "Java Compilation Error",
+        regex: r"\.java:\d+: error: ",
+        severity: Severity::Error,
+        confidence: 0.95,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "kotlin-error",
+        category: "Kotlin Compilation Error",
+        regex: r"^\s*e: (?:file://)?\S+\.kts?[:\s]",
+        severity: Severity::Error,
+        confidence: 0.95,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "maven-compilation-error",
+        category: "Maven Compilation Error",
+        regex: r"\[ERROR\] \S+\.(?:java|kt|scala):\[\d+,\d+\]",
+        severity: Severity::Error,
+        confidence: 0.95,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "jvm-test-failure",
+        category: "JVM Test Failure",
+        regex: r"There were failing tests|^\S+ > .+ FAILED\s*$|Tests run: \d+, Failures: (?:[1-9]\d*, Errors: \d+|\d+, Errors: [1-9])",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Tests,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "jvm-dependency-resolution",
+        category: "JVM Dependency Resolution",
+        regex: r"Could not resolve dependencies for project|Could not resolve all (?:files|dependencies|artifacts) for configuration",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "gradle-daemon-crash",
+        category: "Gradle Daemon Crash",
+        regex: r"Gradle build daemon disappeared unexpectedly|JVM heap space is exhausted",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Infra,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "gradle-task-failed",
+        category: "Gradle Task Failure",
+        regex: r"Execution failed for task '(?P<task>:?[^']+)'",
+        severity: Severity::Error,
+        confidence: 0.75,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "maven-goal-failed",
+        category: "Maven Goal Failure",
+        regex: r"\[ERROR\] Failed to execute goal (?P<goal>\S+)",
+        severity: Severity::Error,
+        confidence: 0.75,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "gradle-build-failed",
+        category: "Gradle Build Failure",
+        regex: r"FAILURE: Build (?:failed with an exception|completed with \d+ failures)",
+        severity: Severity::Hint,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    // Docker builds
+    BuiltinPattern {
+        id: "docker-context-file-missing",
+        category: "Missing Docker Context File",
+        regex: r#"failed to (?:compute cache key|calculate checksum of ref).*"(?P<path>[^"]+)": not found|COPY failed: (?:file not found in build context|stat .*: file does not exist)"#,
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "apt-signing-key",
+        category: "APT Signing Key",
+        regex: r"NO_PUBKEY [0-9A-F]+|EXPKEYSIG [0-9A-F]+|The repository '[^']+' is not signed|apt-key: not found",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "apt-package-missing",
+        category: "APT Package Not Found",
+        regex: r"E: (?:Unable to locate package|Package '?[^ ']+'? has no installation candidate)",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "docker-image-not-found",
+        category: "Docker Image Not Found",
+        regex: r"pull access denied for|failed to resolve source metadata for|manifest (?:for \S+ )?unknown",
+        severity: Severity::Error,
+        confidence: 0.85,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "docker-step-failed",
+        category: "Docker Step Failure",
+        regex: r#"process "(?P<command>.*)" did not complete successfully: exit code: \d+|The command '.*' returned a non-zero code: \d+"#,
+        severity: Severity::Hint,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    // Package & dependency
+    BuiltinPattern {
+        id: "npm-error",
+        category: "NPM Error",
+        regex: r"(?i)npm err!",
+        severity: Severity::Warning,
+        confidence: 0.5,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "yarn-error",
+        category: "Yarn Error",
+        regex: r"(?i)yarn error",
+        severity: Severity::Warning,
+        confidence: 0.5,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "missing-dependency",
+        category: "Missing Dependency",
+        regex: r"(?i)dependency.*not found",
+        severity: Severity::Error,
+        confidence: 0.7,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    // CI environment
+    BuiltinPattern {
+        id: "docker-rate-limit",
+        category: "Docker Hub Rate Limit",
+        regex: r"toomanyrequests: You have reached your pull rate limit",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Infra,
+        transient: true,
+    },
+    BuiltinPattern {
+        id: "maven-transfer-failed",
+        category: "Artifact Download Failure",
+        regex: r"Could not transfer artifact \S+ from/to|Could not GET '[^']+'.*(?:Received status code 5\d\d|Read timed out)",
+        severity: Severity::Warning,
+        confidence: 0.6,
+        kind: FailureKind::Infra,
+        transient: true,
+    },
+    BuiltinPattern {
+        id: "no-output-timeout",
+        category: "No Output Timeout",
+        regex: r"(?i)too long with no output",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Infra,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "registry-unavailable",
+        category: "Registry Unavailable",
+        regex: r"(?i)(npm err!.*\b50[234]\b|registry\.(npmjs\.org|yarnpkg\.com).*\b50[234]\b|\b50[234] (service unavailable|bad gateway|gateway time-?out).*registry)",
+        severity: Severity::Warning,
+        confidence: 0.85,
+        kind: FailureKind::Infra,
+        transient: true,
+    },
+    BuiltinPattern {
+        id: "git-auth-failed",
+        category: "Git Permission Denied",
+        regex: r"(?i)(permission denied \(publickey[^)]*\)|ERROR: Repository not found\.)",
+        severity: Severity::Error,
+        confidence: 0.9,
+        kind: FailureKind::Infra,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "git-clone-failed",
+        category: "Git Clone Failed",
+        regex: r"(?i)(fatal: unable to access '[^']+': (could not resolve host|failed to connect|connection timed out|operation timed out|empty reply|recv failure)|the remote end hung up unexpectedly|error: RPC failed|fatal: early EOF|ssh: connect to host \S+ port \d+: (connection timed out|operation timed out|network is unreachable))",
+        severity: Severity::Warning,
+        confidence: 0.8,
+        kind: FailureKind::Infra,
+        transient: true,
+    },
+    BuiltinPattern {
+        id: "network-error",
+        category: "Network Error",
+        regex: r"(ETIMEDOUT|ECONNRESET|EAI_AGAIN|getaddrinfo ENOTFOUND|socket hang up)",
+        severity: Severity::Warning,
+        confidence: 0.6,
+        kind: FailureKind::Infra,
+        transient: true,
+    },
+    // Exit indicators - consequences of a failure rather than its cause
+    BuiltinPattern {
+        id: "non-zero-exit",
+        category: "Non-zero Exit",
+        regex: r"(?i)exited with (code|status) [1-9]",
+        severity: Severity::Hint,
+        confidence: 0.9,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+    BuiltinPattern {
+        id: "command-failure",
+        category: "Command Failure",
+        regex: r"(?i)command failed",
+        severity: Severity::Hint,
+        confidence: 0.6,
+        kind: FailureKind::Code,
+        transient: false,
+    },
+];
+
+/// Returns the built-in error patterns in priority order.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::patterns::builtin_patterns;
+///
+/// let patterns = builtin_patterns();
+/// assert!(patterns.iter().any(|p| p.id == "typescript-error"));
+/// ```
+pub fn builtin_patterns() -> Vec<ErrorPattern> {
+    BUILTIN_PATTERNS
+        .iter()
+        .map(|builtin| ErrorPattern {
+            severity: builtin.severity,
+            confidence: builtin.confidence,
+            kind: builtin.kind,
+            transient: builtin.transient,
+            ..ErrorPattern::new(builtin.id, builtin.category, builtin.regex)
+                .expect("built-in patterns are valid")
+        })
+        .collect()
+}
+
+/// A single log line matched by a pattern.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorMatch {
+    /// 1-based line number within the analyzed log.
+    pub line_number: usize,
+    /// The matched line, untrimmed.
+    pub line: String,
+}
+
+/// All matches of one pattern within a log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorGroup {
+    /// Id of the pattern that matched.
+    pub pattern_id: String,
+    /// Category of the pattern that matched.
+    pub category: String,
+    /// Severity of the pattern that matched.
+    pub severity: Severity,
+    /// Confidence of the pattern that matched.
+    pub confidence: f32,
+    /// Kind of the pattern that matched.
+    pub kind: FailureKind,
+    /// Whether the pattern that matched is transient.
+    #[serde(default)]
+    pub transient: bool,
+    /// First matching line.
+    pub first: ErrorMatch,
+    /// Last matching line; equal to `first` when there is a single match.
+    pub last: ErrorMatch,
+    /// Line numbers of every match, in order.
+    pub line_numbers: Vec<usize>,
+}
+
+impl ErrorGroup {
+    /// Number of lines this pattern matched.
+    pub fn count(&self) -> usize {
+        self.line_numbers.len()
+    }
+
+    /// Maps line numbers of the analyzed text back to another log's, where
+    /// line `n` of the analyzed text was line `origins[n - 1]` of that log,
+    /// e.g. the [`line_numbers`](crate::logs::NoiseReduction::line_numbers)
+    /// of a collapsed log.
+    pub fn renumber(&mut self, origins: &[usize]) {
+        let map = |n: usize| origins.get(n - 1).copied().unwrap_or(n);
+        self.first.line_number = map(self.first.line_number);
+        self.last.line_number = map(self.last.line_number);
+        for n in &mut self.line_numbers {
+            *n = map(*n);
+        }
+    }
+
+    /// Ranking score: severity weight × confidence.
+    pub fn score(&self) -> f32 {
+        self.severity.weight() * self.confidence
+    }
+}
+
+/// Scans a log with every pattern and groups the matches per pattern.
+///
+/// Groups are ordered by [`score`](ErrorGroup::score), highest first, with
+/// ties kept in pattern order. A line matching several patterns is counted
+/// in each of their groups.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::patterns::{builtin_patterns, detect_errors};
+///
+/// let log = "src/a.ts(1,1): error TS2322: bad\nsrc/b.ts(9,3): error TS2345: worse\ndone";
+/// let groups = detect_errors(log, &builtin_patterns());
+///
+/// assert_eq!(groups.len(), 1);
+/// assert_eq!(groups[0].category, "TypeScript Error");
+/// assert_eq!(groups[0].count(), 2);
+/// assert_eq!(groups[0].first.line_number, 1);
+/// assert_eq!(groups[0].last.line_number, 2);
+/// ```
+///
+/// # Performance
+///
+/// O(patterns × lines); each line is tested against every pattern.
+#[tracing::instrument(level = "debug", skip_all, fields(patterns = patterns.len(), lines))]
+pub fn detect_errors(text: &str, patterns: &[ErrorPattern]) -> Vec<ErrorGroup> {
+    let lines: Vec<&str> = text.lines().collect();
+    tracing::Span::current().record("lines", lines.len());
+
+    let mut groups: Vec<ErrorGroup> = patterns
+        .iter()
+        .filter_map(|pattern| {
+            let line_numbers: Vec<usize> = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| pattern.regex.is_match(line))
+                .map(|(i, _)| i + 1)
+                .collect();
+
+            let make_match = |line_number: usize| ErrorMatch {
+                line_number,
+                line: lines[line_number - 1].to_string(),
+            };
+
+            Some(ErrorGroup {
+                pattern_id: pattern.id.clone(),
+                category: pattern.category.clone(),
+                severity: pattern.severity,
+                confidence: pattern.confidence,
+                kind: pattern.kind,
+                transient: pattern.transient,
+                first: make_match(*line_numbers.first()?),
+                last: make_match(*line_numbers.last()?),
+                line_numbers,
+            })
+        })
+        .collect();
+
+    // Stable sort keeps pattern order for equal scores
+    groups.sort_by(|a, b| b.score().total_cmp(&a.score()));
+    tracing::debug!(groups = groups.len(), "error detection finished");
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_pattern_ids_are_unique() {
+        let patterns = builtin_patterns();
+        let mut ids: Vec<_> = patterns.iter().map(|p| p.id.as_str()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), patterns.len());
+    }
+
+    #[test]
+    fn test_detect_errors_counts_every_match() {
+        let log = "npm ERR! code 1\n".repeat(40) + "exited with code 1";
+        let groups = detect_errors(&log, &builtin_patterns());
+
+        let npm = groups.iter().find(|g| g.pattern_id == "npm-error").unwrap();
+        assert_eq!(npm.count(), 40);
+        assert_eq!(npm.last.line_number, 40);
+
+        let exit = groups
+            .iter()
+            .find(|g| g.pattern_id == "non-zero-exit")
+            .unwrap();
+        assert_eq!(exit.first.line_number, 41);
+    }
+
+    #[test]
+    fn test_detect_errors_ranks_causes_above_consequences() {
+        let log = "exited with code 1\nError: Cannot find module 'left-pad'";
+        let groups = detect_errors(log, &builtin_patterns());
+
+        assert_eq!(groups[0].pattern_id, "missing-module");
+        assert_eq!(groups.last().unwrap().pattern_id, "non-zero-exit");
+        assert_eq!(groups.last().unwrap().severity, Severity::Hint);
+    }
+
+    #[test]
+    fn test_builtin_confidence_in_range() {
+        for pattern in builtin_patterns() {
+            assert!(
+                (0.0..=1.0).contains(&pattern.confidence),
+                "{} has confidence {}",
+                pattern.id,
+                pattern.confidence
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_errors_clean_log() {
+        assert!(detect_errors("all good\nDone in 3s", &builtin_patterns()).is_empty());
+    }
+
+    #[test]
+    fn test_network_flakes_are_transient() {
+        let log = [
+            "npm ERR! 503 Service Unavailable - GET https://registry.npmjs.org/left-pad",
+            "fatal: unable to access 'https://github.com/org/repo.git/': Could not resolve host: github.com",
+            "Error: read ECONNRESET",
+            "fatal: could not read Username for 'https://github.com': terminal prompts disabled",
+        ]
+        .join("\n");
+        let groups = detect_errors(&log, &builtin_patterns());
+        let transient: Vec<_> = groups
+            .iter()
+            .filter(|g| g.transient)
+            .map(|g| (g.pattern_id.as_str(), g.first.line_number))
+            .collect();
+
+        assert_eq!(
+            transient,
+            vec![
+                ("registry-unavailable", 1),
+                ("git-clone-failed", 2),
+                ("network-error", 3)
+            ]
+        );
+        assert!(groups
+            .iter()
+            .filter(|g| g.transient)
+            .all(|g| g.kind == FailureKind::Infra));
+    }
+
+    #[test]
+    fn test_jvm_causes_rank_above_build_failed() {
+        let log = [
+            "> Task :app:compileJava",
+            "/repo/app/src/main/java/com/acme/App.java:12: error: cannot find symbol",
+            "FAILURE: Build failed with an exception.",
+            "* What went wrong:",
+            "Execution failed for task ':app:compileJava'.",
+            "[ERROR] Tests run: 4, Failures: 1, Errors: 0, Skipped: 0, Time elapsed: 0.2 s <<< FAILURE!",
+        ]
+        .join("\n");
+        let groups = detect_errors(&log, &builtin_patterns());
+        let ids: Vec<_> = groups.iter().map(|g| g.pattern_id.as_str()).collect();
+
+        assert_eq!(ids[0], "javac-error");
+        assert!(ids.contains(&"gradle-task-failed"));
+        assert!(ids.contains(&"jvm-test-failure"));
+        assert_eq!(
+            groups.last().unwrap().pattern_id,
+            "gradle-build-failed",
+            "{:?}",
+            ids
+        );
+        assert!(detect_errors(
+            "[INFO] Tests run: 4, Failures: 0, Errors: 0, Skipped: 0",
+            &builtin_patterns()
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_docker_causes_rank_above_step_failure() {
+        let log = [
+            "#6 [3/5] COPY yarn.lock ./",
+            "#6 ERROR: failed to calculate checksum of ref 4f1c::k2x: \"/yarn.lock\": not found",
+            "#8 1.204 W: GPG error: https://dl.yarnpkg.com/debian stable InRelease: NO_PUBKEY 23E7166788B63E1E",
+            "ERROR: failed to solve: process \"/bin/sh -c make\" did not complete successfully: exit code: 2",
+        ]
+        .join("\n");
+        let groups = detect_errors(&log, &builtin_patterns());
+        let ids: Vec<_> = groups.iter().map(|g| g.pattern_id.as_str()).collect();
+
+        assert_eq!(ids[0], "docker-context-file-missing");
+        assert!(ids.contains(&"apt-signing-key"));
+        assert_eq!(ids.last(), Some(&"docker-step-failed"));
+    }
+
+    #[test]
+    fn test_invalid_pattern_regex() {
+        assert!(ErrorPattern::new("bad", "Bad", "(unclosed").is_err());
+    }
+}
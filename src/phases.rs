@@ -0,0 +1,221 @@
+//! Phases of a long action, from the timestamps in its output.
+//!
+//! A single "Run tests" step often installs dependencies, compiles, runs
+//! the tests, and uploads results, and CircleCI only times the step as a
+//! whole. When the log's lines carry timestamps, [`phase_timings`] splits
+//! it at the lines that start each phase, such as `npm ci`, `tsc`,
+//! `jest`, or `Uploading artifacts`, and adds up how long each phase
+//! lasted:
+//!
+//! ```text
+//! 2024-05-01T12:00:00Z + npm ci
+//! 2024-05-01T12:06:00Z + npm run build
+//! 2024-05-01T12:06:40Z + npm test
+//! 2024-05-01T12:09:00Z Uploading test results
+//! 2024-05-01T12:09:05Z done
+//! ```
+//!
+//! Logs without timestamps have no phases.
+
+use crate::logs::find_timestamp;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// A part of an action's work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Installing dependencies: `npm ci`, `pip install`, `bundle install`.
+    Install,
+    /// Compiling or bundling: `tsc`, `webpack`, `cargo build`, `go build`.
+    Compile,
+    /// Running tests: `jest`, `pytest`, `go test`, Gradle's `:test`.
+    Test,
+    /// Uploading results, saving caches, and stopping services.
+    Teardown,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Install => write!(f, "install"),
+            Phase::Compile => write!(f, "compile"),
+            Phase::Test => write!(f, "test"),
+            Phase::Teardown => write!(f, "teardown"),
+        }
+    }
+}
+
+/// How long an action spent in one phase.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PhaseTiming {
+    /// The phase.
+    pub phase: Phase,
+    /// Milliseconds spent in it, across every stretch of the log it
+    /// covered.
+    pub millis: u64,
+    /// 1-based line number where the phase first started.
+    pub line_number: usize,
+}
+
+/// Lines that start each phase, matched without their timestamp.
+///
+/// Tools are only recognized where a command starts, after an optional
+/// `+ `, `$ `, or `> ` echo prefix, so a path or package name mentioning
+/// `jest` in an install log doesn't switch phases.
+fn phase_markers() -> &'static [(Phase, Regex)] {
+    static MARKERS: OnceLock<Vec<(Phase, Regex)>> = OnceLock::new();
+    MARKERS.get_or_init(|| {
+        const COMMAND: &str = r"^\s*(?:[+$>]\s*)?(?:sudo\s+|npx\s+|bundle exec\s+|python3? -m\s+)?";
+        [
+            (
+                Phase::Install,
+                format!(
+                    r"{}(?:npm (?:ci|install)|yarn(?: install)?$|pnpm install|pip3? install|poetry install|bundle install|go mod download|apt-get(?: -y)? install|composer install)\b|(?i)^\s*(?:installing dependencies|resolving packages)",
+                    COMMAND
+                ),
+            ),
+            (
+                Phase::Compile,
+                format!(
+                    r"{}(?:(?:npm|yarn|pnpm) (?:run )?build|tsc|webpack|cargo build|go build|javac|mvn(?: -\S+)* (?:compile|package))\b|^> Task :\S*compile|^\s*Compiling \S+ v\d",
+                    COMMAND
+                ),
+            ),
+            (
+                Phase::Test,
+                format!(
+                    r"{}(?:(?:npm|yarn|pnpm) (?:run )?test|jest|pytest|go test|cargo test|rspec|mvn(?: -\S+)* (?:verify|test)|\./gradlew(?: \S+)* test)\b|^> Task :\S*test\b|T E S T S|(?i)^\s*running \d+ tests?\b",
+                    COMMAND
+                ),
+            ),
+            (
+                Phase::Teardown,
+                format!(
+                    r"(?i)^\s*(?:uploading (?:artifacts|test results)|saving cache|cleaning up|tearing down)|{}docker[- ]compose down\b",
+                    COMMAND
+                ),
+            ),
+        ]
+        .into_iter()
+        .map(|(phase, regex)| (phase, Regex::new(&regex).expect("phase markers are valid")))
+        .collect()
+    })
+}
+
+/// Returns the phase `line` starts, if it starts one.
+fn phase_of(line: &str) -> Option<Phase> {
+    phase_markers()
+        .iter()
+        .find(|(_, regex)| regex.is_match(line))
+        .map(|(phase, _)| *phase)
+}
+
+/// Adds up how long the action whose output `log` is spent in each phase.
+///
+/// A phase runs from the first line that starts it to the first line that
+/// starts a different one, or the last timestamp in the log; a phase the
+/// log returns to keeps adding up. Time before the first phase marker
+/// isn't counted. Lines without a timestamp take the one above them.
+///
+/// # Returns
+///
+/// Phases in the order they first started. Empty when the log has no
+/// timestamps or no phase markers.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::phases::{phase_timings, Phase};
+///
+/// let log = "2024-05-01T12:00:00Z + npm ci\n\
+///            2024-05-01T12:06:00Z added 1200 packages\n\
+///            2024-05-01T12:06:00Z + npm test\n\
+///            2024-05-01T12:09:00Z Tests: 2 failed, 40 passed\n";
+/// let phases = phase_timings(log);
+/// assert_eq!(phases[0].phase, Phase::Install);
+/// assert_eq!(phases[0].millis, 6 * 60 * 1000);
+/// assert_eq!(phases[1].phase, Phase::Test);
+/// assert_eq!(phases[1].millis, 3 * 60 * 1000);
+/// ```
+pub fn phase_timings(log: &str) -> Vec<PhaseTiming> {
+    let mut timings: Vec<PhaseTiming> = Vec::new();
+    // The phase being timed and when it started
+    let mut current: Option<(Phase, DateTime<Utc>)> = None;
+    let mut last_time: Option<DateTime<Utc>> = None;
+
+    for (i, line) in log.lines().enumerate() {
+        let text = match find_timestamp(line) {
+            Some((time, range)) => {
+                last_time = Some(time);
+                format!("{}{}", &line[..range.start], line[range.end..].trim_start())
+            }
+            None => line.to_string(),
+        };
+        let (Some(phase), Some(time)) = (phase_of(&text), last_time) else {
+            continue;
+        };
+        if current.is_some_and(|(running, _)| running == phase) {
+            continue;
+        }
+        if let Some((running, started)) = current {
+            add_time(&mut timings, running, started, time);
+        }
+        if !timings.iter().any(|timing| timing.phase == phase) {
+            timings.push(PhaseTiming {
+                phase,
+                millis: 0,
+                line_number: i + 1,
+            });
+        }
+        current = Some((phase, time));
+    }
+    if let (Some((running, started)), Some(end)) = (current, last_time) {
+        add_time(&mut timings, running, started, end);
+    }
+    timings
+}
+
+/// Adds the time from `start` to `end` to `phase`, which is in `timings`.
+fn add_time(timings: &mut [PhaseTiming], phase: Phase, start: DateTime<Utc>, end: DateTime<Utc>) {
+    if let Some(timing) = timings.iter_mut().find(|timing| timing.phase == phase) {
+        // Zero if the clock went backwards
+        timing.millis += (end - start).num_milliseconds().max(0) as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings_revisited_phase_and_untimed_log() {
+        let log = "2024-05-01T12:00:00Z Spinning up\n\
+                   2024-05-01T12:00:10Z + npm ci\n\
+                   2024-05-01T12:02:10Z + npx tsc -p .\n\
+                   src/index.ts(3,1): error TS2304\n\
+                   2024-05-01T12:03:10Z + npm test\n\
+                   2024-05-01T12:03:20Z PASS node_modules/jest-junit/index.test.js\n\
+                   2024-05-01T12:04:10Z + npm install --no-save jest-junit\n\
+                   2024-05-01T12:04:40Z + npm test -- --reporters=jest-junit\n\
+                   2024-05-01T12:05:40Z Uploading test results\n\
+                   2024-05-01T12:05:50Z exit 1\n";
+        let phases: Vec<(Phase, u64, usize)> = phase_timings(log)
+            .into_iter()
+            .map(|timing| (timing.phase, timing.millis / 1000, timing.line_number))
+            .collect();
+        assert_eq!(
+            phases,
+            vec![
+                (Phase::Install, 150, 2),
+                (Phase::Compile, 60, 3),
+                (Phase::Test, 120, 5),
+                (Phase::Teardown, 10, 9),
+            ]
+        );
+
+        assert!(phase_timings("+ npm ci\n+ npm test\n").is_empty());
+    }
+}
@@ -0,0 +1,623 @@
+//! Post-mortem documents, for `cdb report`.
+//!
+//! A [`PostMortem`] puts everything an incident writeup needs about a
+//! failed build in one document: what ran and when, the rest of the
+//! workflow, each detected error with the log lines around it, failed
+//! tests and how to rerun them, where the time went, and the commits and
+//! owners to look at. It renders as Markdown, for issues and wikis, or as
+//! a standalone HTML page.
+
+use crate::cancel::CanceledBy;
+use crate::format_duration;
+use crate::logs;
+use crate::patterns::ErrorGroup;
+use crate::report::{BuildReport, FailedAction};
+use std::fmt::Write;
+
+/// Log lines shown before and after each detected error.
+pub const CONTEXT_LINES: usize = 3;
+
+/// Document format of a post-mortem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PostMortemFormat {
+    /// GitHub-flavored Markdown.
+    #[default]
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+/// Log lines around a detected error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// 1-based number of the first line in `lines`.
+    pub first_line: usize,
+    /// The lines, the error's among them.
+    pub lines: Vec<String>,
+    /// Index of the error's line in `lines`.
+    pub error_index: usize,
+}
+
+/// Finds `line` in `log` and returns up to `radius` lines on either side
+/// of its first occurrence.
+///
+/// Matched lines are searched for by their text rather than their line
+/// number, since errors are detected after repeated lines are collapsed.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::postmortem::error_context;
+///
+/// let log = "a\nb\nError: boom\nc\nd\ne";
+/// let context = error_context(log, "Error: boom", 1).unwrap();
+/// assert_eq!(context.lines, vec!["b", "Error: boom", "c"]);
+/// assert_eq!((context.first_line, context.error_index), (2, 1));
+/// ```
+pub fn error_context(log: &str, line: &str, radius: usize) -> Option<ErrorContext> {
+    let lines: Vec<&str> = log.lines().collect();
+    let at = lines.iter().position(|l| *l == line)?;
+    let start = at.saturating_sub(radius);
+    let end = (at + radius + 1).min(lines.len());
+    Some(ErrorContext {
+        first_line: start + 1,
+        lines: lines[start..end].iter().map(|l| l.to_string()).collect(),
+        error_index: at - start,
+    })
+}
+
+/// A build's post-mortem.
+#[derive(Debug, Clone)]
+pub struct PostMortem<'a> {
+    report: &'a BuildReport,
+    /// Context per failed action, per error group, in report order.
+    contexts: Vec<Vec<Option<ErrorContext>>>,
+}
+
+impl<'a> PostMortem<'a> {
+    /// Creates a post-mortem of `report`, with the log lines around each
+    /// error read from the logs saved during the analysis.
+    ///
+    /// Errors whose log can't be read are shown with their line alone.
+    pub fn new(report: &'a BuildReport) -> Self {
+        let contexts = report
+            .failed_actions
+            .iter()
+            .map(|action| {
+                let log = action
+                    .log_path
+                    .as_ref()
+                    .and_then(|path| std::fs::read(path).ok())
+                    .map(|log| {
+                        // Capped like the analyzed text, so error lines match
+                        let log = String::from_utf8_lossy(&log);
+                        logs::cap_lines(&log, logs::MAX_LINE_BYTES).text
+                    });
+                action
+                    .errors
+                    .iter()
+                    .map(|group| error_context(log.as_deref()?, &group.first.line, CONTEXT_LINES))
+                    .collect()
+            })
+            .collect();
+        PostMortem { report, contexts }
+    }
+
+    /// Renders the document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::postmortem::{PostMortem, PostMortemFormat};
+    /// use circle_debug::report::BuildReport;
+    /// use circle_debug::BuildInfo;
+    ///
+    /// let build: BuildInfo = serde_json::from_str(
+    ///     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null}"#,
+    /// )
+    /// .unwrap();
+    /// let report = BuildReport::new("org", "repo", &build);
+    /// let html = PostMortem::new(&report).render(PostMortemFormat::Html);
+    /// assert!(html.contains("<h1>Post-Mortem: Build #7 of org/repo</h1>"));
+    /// ```
+    pub fn render(&self, format: PostMortemFormat) -> String {
+        match format {
+            PostMortemFormat::Markdown => {
+                let mut doc = Markdown::default();
+                self.write(&mut doc);
+                doc.out
+            }
+            PostMortemFormat::Html => {
+                let mut doc = Html::default();
+                self.write(&mut doc);
+                doc.finish(&format!(
+                    "Build #{} of {}/{}",
+                    self.report.build_num, self.report.org, self.report.project
+                ))
+            }
+        }
+    }
+
+    fn write(&self, doc: &mut dyn Document) {
+        let report = self.report;
+        doc.heading(
+            1,
+            &format!(
+                "Post-Mortem: Build #{} of {}/{}",
+                report.build_num, report.org, report.project
+            ),
+        );
+        self.write_summary(doc);
+
+        if let Some(upstream) = &report.upstream {
+            doc.paragraph(&format!(
+                "The failure comes from the upstream job {} ({}).",
+                upstream.job, upstream.status
+            ));
+        }
+        if let Some(cancellation) = &report.cancellation {
+            doc.paragraph(&match &cancellation.by {
+                CanceledBy::User { login, .. } => format!("Canceled by {}.", login),
+                CanceledBy::Superseded { build_num, .. } => {
+                    format!("Auto-canceled by the newer build #{}.", build_num)
+                }
+                CanceledBy::Unknown => "Canceled.".to_string(),
+            });
+        }
+        if !report.workflow_jobs.is_empty() {
+            doc.heading(2, "Workflow");
+            let rows = report
+                .workflow_jobs
+                .iter()
+                .map(|job| {
+                    let name = if job.job_number == Some(report.build_num) {
+                        format!("{} (this build)", job.name)
+                    } else {
+                        job.name.clone()
+                    };
+                    vec![
+                        name,
+                        job.status.replace('_', " "),
+                        job.job_number
+                            .map(|n| format!("#{}", n))
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            doc.table(&["Job", "Status", "Build"], rows);
+        }
+
+        self.write_errors(doc);
+
+        let tests: Vec<String> = report
+            .failed_tests()
+            .map(|test| match (&test.file, &test.name) {
+                (Some(file), Some(name)) => format!("{}: {}", file, name),
+                (Some(file), None) => file.clone(),
+                (None, Some(name)) => name.clone(),
+                (None, None) => "(unnamed test)".to_string(),
+            })
+            .collect();
+        if !tests.is_empty() {
+            doc.heading(2, "Failed Tests");
+            doc.list(&tests);
+        }
+        if !report.reproduce.is_empty() {
+            doc.heading(2, "Reproduce Locally");
+            doc.code("sh", &report.reproduce, None, 1);
+        }
+
+        self.write_timing(doc);
+
+        if let Some(suspects) = &report.suspects {
+            doc.heading(2, "Suspect Commits");
+            doc.paragraph(&format!(
+                "Commits since build #{}, the previous passing build; those touching files named in the errors are flagged.",
+                suspects.base_build
+            ));
+            let commits: Vec<String> = suspects
+                .commits
+                .iter()
+                .map(|suspect| {
+                    let flag = if suspect.matched_files.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" - FLAGGED: {}", suspect.matched_files.join(", "))
+                    };
+                    format!(
+                        "{} {} ({}){}",
+                        suspect.commit.short_sha(),
+                        suspect.commit.subject,
+                        suspect.commit.author.as_deref().unwrap_or("unknown"),
+                        flag
+                    )
+                })
+                .collect();
+            doc.list(&commits);
+        }
+        if !report.test_history.is_empty() {
+            doc.heading(2, "Last Touched");
+            for file in &report.test_history {
+                doc.paragraph(&file.file);
+                let commits: Vec<String> = file
+                    .commits
+                    .iter()
+                    .map(|commit| {
+                        let date = commit
+                            .date
+                            .map(|date| format!(", {}", date.format("%Y-%m-%d")))
+                            .unwrap_or_default();
+                        format!(
+                            "{} {} ({}{})",
+                            commit.short_sha(),
+                            commit.subject,
+                            commit.author.as_deref().unwrap_or("unknown"),
+                            date
+                        )
+                    })
+                    .collect();
+                doc.list(&commits);
+            }
+        }
+        if !report.owners.is_empty() {
+            doc.heading(2, "Code Owners");
+            let owners: Vec<String> = report
+                .owners
+                .iter()
+                .map(|file| format!("{}: {}", file.path, file.owners.join(", ")))
+                .collect();
+            doc.list(&owners);
+        }
+    }
+
+    fn write_summary(&self, doc: &mut dyn Document) {
+        let report = self.report;
+        let mut rows = vec![vec!["Status".to_string(), report.status.clone()]];
+        let mut row = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                rows.push(vec![name.to_string(), value]);
+            }
+        };
+        row("Branch", report.branch.clone());
+        row(
+            "Commit",
+            report.vcs_revision.as_ref().map(|sha| {
+                let short = sha.get(..7).unwrap_or(sha);
+                match &report.subject {
+                    Some(subject) => format!("{} {}", short, subject),
+                    None => short.to_string(),
+                }
+            }),
+        );
+        row("Author", report.author_name.clone());
+        row(
+            "Trigger",
+            report.trigger.as_ref().map(|trigger| match &trigger.user {
+                Some(user) => format!("{} by {}", trigger.kind, user),
+                None => trigger.kind.to_string(),
+            }),
+        );
+        row(
+            "Pull request",
+            report
+                .trigger
+                .as_ref()
+                .and_then(|trigger| trigger.pull_request.as_ref())
+                .map(|pr| pr.url.clone()),
+        );
+        row(
+            "Workflow",
+            report.workflow.as_ref().and_then(|workflow| {
+                Some(format!(
+                    "{} / {}",
+                    workflow.workflow_name.as_deref()?,
+                    workflow.job_name.as_deref()?
+                ))
+            }),
+        );
+        row(
+            "Started",
+            report
+                .start_time
+                .map(|at| at.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+        );
+        row("Duration", report.build_time_millis.map(format_duration));
+        row("Queued", report.queued_millis.map(format_duration));
+        let environment = report.environment.clone().unwrap_or_default();
+        row("Executor", environment.executor_label());
+        row(
+            "Images",
+            Some(environment.images.join(", ")).filter(|images| !images.is_empty()),
+        );
+        row("OS", environment.os_label());
+        row(
+            "Cost",
+            report
+                .cost
+                .as_ref()
+                .map(|cost| format!("{:.0} credits (${:.2})", cost.credits, cost.usd)),
+        );
+        row("Link", report.build_url.clone());
+        doc.heading(2, "Summary");
+        doc.table(&["", ""], rows);
+    }
+
+    fn write_errors(&self, doc: &mut dyn Document) {
+        if self.report.failed_actions.is_empty() {
+            return;
+        }
+        doc.heading(2, "Detected Errors");
+        for (action, contexts) in self.report.failed_actions.iter().zip(&self.contexts) {
+            doc.heading(3, &action_title(action));
+            let command: Vec<String> = action
+                .command_lines()
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            if !command.is_empty() {
+                doc.code("sh", &command, None, 1);
+            }
+            if let Some(error) = &action.fetch_error {
+                doc.paragraph(&format!("Logs couldn't be fetched: {}", error));
+                continue;
+            }
+            if action.errors.is_empty() {
+                doc.paragraph("No known error pattern detected.");
+            }
+            for (group, context) in action.errors.iter().zip(contexts) {
+                doc.paragraph(&group_title(group));
+                match context {
+                    Some(context) => doc.code(
+                        "text",
+                        &context.lines,
+                        Some(context.error_index),
+                        context.first_line,
+                    ),
+                    None => doc.code(
+                        "text",
+                        std::slice::from_ref(&group.first.line),
+                        Some(0),
+                        group.first.line_number,
+                    ),
+                }
+            }
+        }
+    }
+
+    fn write_timing(&self, doc: &mut dyn Document) {
+        let timings = &self.report.step_timings;
+        if timings.is_empty() {
+            return;
+        }
+        let total: u64 = timings.iter().map(|t| t.millis).sum();
+        doc.heading(2, "Timing");
+        doc.paragraph(&format!(
+            "Steps ran for {} in total.",
+            format_duration(total)
+        ));
+        let rows = timings
+            .iter()
+            .map(|step| {
+                vec![
+                    step.name.clone(),
+                    format_duration(step.millis),
+                    format!("{}%", step.millis * 100 / total.max(1)),
+                    step.status.clone(),
+                ]
+            })
+            .collect();
+        doc.table(&["Step", "Time", "Share", "Status"], rows);
+    }
+}
+
+fn action_title(action: &FailedAction) -> String {
+    let container = action
+        .container_label()
+        .map(|label| format!(" ({})", label))
+        .unwrap_or_default();
+    if action.step == action.name {
+        format!("{}{}", action.step, container)
+    } else {
+        format!("{} › {}{}", action.step, action.name, container)
+    }
+}
+
+fn group_title(group: &ErrorGroup) -> String {
+    let count = if group.count() > 1 {
+        format!(", {} matching lines", group.count())
+    } else {
+        String::new()
+    };
+    format!(
+        "{} ({}, {:.0}% confidence{})",
+        group.category,
+        group.severity,
+        group.confidence * 100.0,
+        count
+    )
+}
+
+/// The building blocks a post-mortem is written with; text is plain and
+/// escaped by each format.
+trait Document {
+    fn heading(&mut self, level: usize, text: &str);
+    fn paragraph(&mut self, text: &str);
+    fn list(&mut self, items: &[String]);
+    fn table(&mut self, header: &[&str], rows: Vec<Vec<String>>);
+    /// A code block whose first line is numbered `first_line`, with the
+    /// line at `mark` emphasized.
+    fn code(&mut self, lang: &str, lines: &[String], mark: Option<usize>, first_line: usize);
+}
+
+#[derive(Default)]
+struct Markdown {
+    out: String,
+}
+
+impl Document for Markdown {
+    fn heading(&mut self, level: usize, text: &str) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        let _ = writeln!(self.out, "{} {}", "#".repeat(level), text);
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        let _ = writeln!(self.out, "\n{}", text);
+    }
+
+    fn list(&mut self, items: &[String]) {
+        self.out.push('\n');
+        for item in items {
+            let _ = writeln!(self.out, "- {}", item);
+        }
+    }
+
+    fn table(&mut self, header: &[&str], rows: Vec<Vec<String>>) {
+        let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        let _ = writeln!(self.out, "\n| {} |", header.join(" | "));
+        let _ = writeln!(self.out, "|{}", "---|".repeat(header.len()));
+        for row in rows {
+            let cells: Vec<String> = row.iter().map(|c| cell(c)).collect();
+            let _ = writeln!(self.out, "| {} |", cells.join(" | "));
+        }
+    }
+
+    fn code(&mut self, lang: &str, lines: &[String], mark: Option<usize>, first_line: usize) {
+        let fence = if lines.iter().any(|l| l.contains("```")) {
+            "~~~"
+        } else {
+            "```"
+        };
+        let width = (first_line + lines.len()).to_string().len();
+        let _ = writeln!(self.out, "\n{}{}", fence, lang);
+        for (i, line) in lines.iter().enumerate() {
+            if mark.is_some() {
+                let marker = if mark == Some(i) { ">" } else { " " };
+                let _ = writeln!(self.out, "{} {:>width$} | {}", marker, first_line + i, line);
+            } else {
+                let _ = writeln!(self.out, "{}", line);
+            }
+        }
+        let _ = writeln!(self.out, "{}", fence);
+    }
+}
+
+#[derive(Default)]
+struct Html {
+    body: String,
+}
+
+impl Html {
+    fn finish(self, title: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(title),
+            HTML_STYLE,
+            self.body
+        )
+    }
+}
+
+const HTML_STYLE: &str = "body { font-family: -apple-system, sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+pre { background: #f6f8fa; padding: 0.8em; overflow-x: auto; }
+pre mark { background: #ffd7d5; display: block; }
+";
+
+impl Document for Html {
+    fn heading(&mut self, level: usize, text: &str) {
+        let _ = writeln!(self.body, "<h{0}>{1}</h{0}>", level, escape(text));
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        let _ = writeln!(self.body, "<p>{}</p>", escape(text));
+    }
+
+    fn list(&mut self, items: &[String]) {
+        self.body.push_str("<ul>\n");
+        for item in items {
+            let _ = writeln!(self.body, "<li>{}</li>", escape(item));
+        }
+        self.body.push_str("</ul>\n");
+    }
+
+    fn table(&mut self, header: &[&str], rows: Vec<Vec<String>>) {
+        self.body.push_str("<table>\n");
+        if header.iter().any(|h| !h.is_empty()) {
+            let cells: Vec<String> = header
+                .iter()
+                .map(|h| format!("<th>{}</th>", escape(h)))
+                .collect();
+            let _ = writeln!(self.body, "<tr>{}</tr>", cells.concat());
+        }
+        for row in rows {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|c| format!("<td>{}</td>", escape(c)))
+                .collect();
+            let _ = writeln!(self.body, "<tr>{}</tr>", cells.concat());
+        }
+        self.body.push_str("</table>\n");
+    }
+
+    fn code(&mut self, lang: &str, lines: &[String], mark: Option<usize>, first_line: usize) {
+        let _ = write!(self.body, "<pre><code class=\"language-{}\">", lang);
+        for (i, line) in lines.iter().enumerate() {
+            let text = if mark.is_some() {
+                format!("{:>5} | {}", first_line + i, escape(line))
+            } else {
+                escape(line)
+            };
+            if mark == Some(i) {
+                let _ = write!(self.body, "<mark>{}</mark>", text);
+            } else {
+                let _ = writeln!(self.body, "{}", text);
+            }
+        }
+        self.body.push_str("</code></pre>\n");
+    }
+}
+
+/// Escapes text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{BuildBuilder, FailedActionBuilder};
+
+    #[test]
+    fn test_post_mortem_shows_error_context() {
+        let log = "> jest\nPASS a.test.js\nnpm ERR! code ELIFECYCLE\nnpm ERR! errno 1\n";
+        let log_path =
+            std::env::temp_dir().join(format!("cdb-postmortem-{}.log", std::process::id()));
+        std::fs::write(&log_path, log).unwrap();
+
+        let mut report = BuildReport::new("org", "repo", &BuildBuilder::new(12).build());
+        report.failed_actions.push(
+            FailedActionBuilder::new("Run tests")
+                .log_path(log_path.display().to_string())
+                .log(log)
+                .build(),
+        );
+        let post_mortem = PostMortem::new(&report);
+        std::fs::remove_file(&log_path).unwrap();
+
+        let markdown = post_mortem.render(PostMortemFormat::Markdown);
+        assert!(markdown.starts_with("# Post-Mortem: Build #12 of org/repo\n"));
+        assert!(markdown.contains(
+            "```text\n  1 | > jest\n  2 | PASS a.test.js\n> 3 | npm ERR! code ELIFECYCLE\n"
+        ));
+
+        let html = post_mortem.render(PostMortemFormat::Html);
+        assert!(html.contains("<mark>    3 | npm ERR! code ELIFECYCLE</mark>"));
+        assert!(html.contains("&gt; jest"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Followed projects, for `cdb projects`, `cdb follow`, and `cdb top`.
+//!
+//! CircleCI only answers for projects the token's user follows: builds of
+//! an unfollowed project come back as 404s. The v1.1 project list carries
+//! each branch's most recent builds, which is enough for a one-line status
+//! per project. The recent builds of every followed project, newest first,
+//! show what is running and queued right now.
+
+use crate::{v1_vcs, CircleClient, WorkflowInfo};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A project the token's user follows.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Project {
+    /// Organization (or user) that owns the project.
+    pub username: String,
+    /// Repository name.
+    pub reponame: String,
+    /// `github`, `bitbucket`, or `circleci` for standalone organizations.
+    #[serde(default)]
+    pub vcs_type: Option<String>,
+    /// The repository's default branch.
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Recent builds per branch.
+    #[serde(default)]
+    pub branches: BTreeMap<String, ProjectBranch>,
+}
+
+/// A branch of a [`Project`] and its most recent builds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProjectBranch {
+    /// Finished builds, newest first.
+    #[serde(default)]
+    pub recent_builds: Vec<BranchBuild>,
+    /// Builds running now.
+    #[serde(default)]
+    pub running_builds: Vec<BranchBuild>,
+}
+
+/// A build in a [`ProjectBranch`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BranchBuild {
+    /// Build number.
+    pub build_num: u32,
+    /// Build status (e.g., "success", "failed", "running").
+    pub status: String,
+    /// Result once finished (e.g., "success", "failed").
+    #[serde(default)]
+    pub outcome: Option<String>,
+    /// When the build was queued.
+    #[serde(default)]
+    pub added_at: Option<DateTime<Utc>>,
+    /// Commit SHA that was built.
+    #[serde(default)]
+    pub vcs_revision: Option<String>,
+}
+
+impl ProjectBranch {
+    /// Returns the branch's newest build, running or finished.
+    pub fn newest_build(&self) -> Option<&BranchBuild> {
+        self.running_builds
+            .iter()
+            .chain(&self.recent_builds)
+            .max_by_key(|build| build.build_num)
+    }
+}
+
+impl Project {
+    /// Returns the newest build, running or finished, of the default
+    /// branch, or of any branch when the default branch has none.
+    ///
+    /// # Returns
+    ///
+    /// The branch and its build.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::projects::Project;
+    ///
+    /// let project: Project = serde_json::from_str(r#"{
+    ///     "username": "org", "reponame": "repo", "default_branch": "main",
+    ///     "branches": {
+    ///         "main": {"recent_builds": [{"build_num": 7, "status": "failed"}]},
+    ///         "feature": {"running_builds": [{"build_num": 9, "status": "running"}]}
+    ///     }
+    /// }"#).unwrap();
+    /// let (branch, build) = project.latest_build().unwrap();
+    /// assert_eq!((branch, build.build_num), ("main", 7));
+    /// ```
+    pub fn latest_build(&self) -> Option<(&str, &BranchBuild)> {
+        let default = self.default_branch.as_deref().and_then(|name| {
+            self.branches
+                .get(name)
+                .and_then(ProjectBranch::newest_build)
+                .map(|build| (name, build))
+        });
+        default.or_else(|| {
+            self.branches
+                .iter()
+                .filter_map(|(name, branch)| {
+                    branch.newest_build().map(|build| (name.as_str(), build))
+                })
+                .max_by_key(|(_, build)| build.build_num)
+        })
+    }
+}
+
+/// How many recent builds are listed per request, the API's maximum.
+const RECENT_BUILDS_PAGE: usize = 100;
+
+/// How many pages of recent builds are looked through for active ones.
+pub const MAX_RECENT_PAGES: usize = 5;
+
+/// A build that is running or waiting to run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActiveBuild {
+    /// Organization (or user) that owns the project.
+    pub username: String,
+    /// Repository name.
+    pub reponame: String,
+    /// Build number.
+    pub build_num: u32,
+    /// Branch being built.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Build status (e.g., "running", "queued", "not_running").
+    pub status: String,
+    /// Where the build is in its life (e.g., "running", "queued").
+    #[serde(default)]
+    pub lifecycle: Option<String>,
+    /// When the build was queued.
+    #[serde(default)]
+    pub queued_at: Option<DateTime<Utc>>,
+    /// When the build started running.
+    #[serde(default)]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Workflow and job the build belongs to.
+    #[serde(default)]
+    pub workflows: Option<WorkflowInfo>,
+    /// Link to the build in the CircleCI UI.
+    #[serde(default)]
+    pub build_url: Option<String>,
+}
+
+impl ActiveBuild {
+    /// Returns `true` if the build hasn't finished.
+    pub fn is_active(&self) -> bool {
+        let state = self.lifecycle.as_deref().unwrap_or(&self.status);
+        matches!(state, "running" | "queued" | "not_running" | "scheduled")
+    }
+
+    /// Returns `true` if the build is running rather than waiting.
+    pub fn is_running(&self) -> bool {
+        self.lifecycle.as_deref().unwrap_or(&self.status) == "running"
+    }
+
+    /// How long the build has been running, or waiting when it hasn't
+    /// started.
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let since = if self.is_running() {
+            self.start_time.or(self.queued_at)
+        } else {
+            self.queued_at
+        };
+        since.map(|since| (now - since).max(Duration::zero()))
+    }
+}
+
+/// Result of following a project.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FollowResult {
+    /// Whether the project is now followed.
+    pub following: bool,
+}
+
+impl CircleClient {
+    /// Lists the projects the token's user follows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// for project in client.followed_projects().await? {
+    ///     println!("{}/{}", project.username, project.reponame);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn followed_projects(&self) -> Result<Vec<Project>> {
+        self.get_json(&format!("{}/v1.1/projects", self.base_url))
+            .await
+            .context("Failed to list followed projects")
+    }
+
+    /// Lists the running and queued builds of every followed project, or
+    /// only `org`'s, newest first.
+    ///
+    /// Pages through recent builds until one has no active builds, at most
+    /// [`MAX_RECENT_PAGES`] of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recent builds can't be listed.
+    pub async fn active_builds(&self, org: Option<&str>) -> Result<Vec<ActiveBuild>> {
+        let mut active = Vec::new();
+        for page in 0..MAX_RECENT_PAGES {
+            let url = format!(
+                "{}/v1.1/recent-builds?shallow=true&limit={}&offset={}",
+                self.base_url,
+                RECENT_BUILDS_PAGE,
+                page * RECENT_BUILDS_PAGE
+            );
+            let builds: Vec<ActiveBuild> = self
+                .get_json(&url)
+                .await
+                .context("Failed to list recent builds")?;
+            let count = builds.len();
+            let page_active: Vec<ActiveBuild> =
+                builds.into_iter().filter(ActiveBuild::is_active).collect();
+            // Active builds are the newest; a page without any is past them
+            let done = count < RECENT_BUILDS_PAGE || page_active.is_empty();
+            active.extend(
+                page_active
+                    .into_iter()
+                    .filter(|build| org.is_none_or(|org| build.username.eq_ignore_ascii_case(org))),
+            );
+            if done {
+                break;
+            }
+        }
+        Ok(active)
+    }
+
+    /// Follows a project, so its builds can be fetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, e.g. because the token's
+    /// user can't see the repository.
+    pub async fn follow_project(&self, org: &str, project: &str) -> Result<FollowResult> {
+        let url = format!(
+            "{}/v1.1/project/{}/{}/{}/follow",
+            self.base_url,
+            v1_vcs(org),
+            org,
+            project
+        );
+        self.post_json(&url, None)
+            .await
+            .with_context(|| format!("Failed to follow {}/{}", org, project))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_active_builds_of_one_org() {
+        let server = FakeCircleCi::start().await;
+        let build = |org: &str, number: u32, lifecycle: &str| {
+            serde_json::json!({
+                "username": org, "reponame": "repo", "build_num": number,
+                "branch": "main", "status": lifecycle, "lifecycle": lifecycle,
+                "queued_at": "2024-05-01T12:00:00Z", "start_time": "2024-05-01T12:01:00Z"
+            })
+        };
+        Mock::given(method("GET"))
+            .and(path("/api/v1.1/recent-builds"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                build("org", 12, "queued"),
+                build("org", 11, "running"),
+                build("other", 40, "running"),
+                build("org", 10, "finished")
+            ])))
+            .mount(server.server())
+            .await;
+
+        let builds = server.client().active_builds(Some("Org")).await.unwrap();
+        let numbers: Vec<u32> = builds.iter().map(|build| build.build_num).collect();
+        assert_eq!(numbers, vec![12, 11]);
+
+        let now = "2024-05-01T12:05:00Z".parse().unwrap();
+        assert_eq!(builds[0].elapsed(now), Some(Duration::minutes(5)));
+        assert_eq!(builds[1].elapsed(now), Some(Duration::minutes(4)));
+    }
+
+    #[tokio::test]
+    async fn test_followed_projects_and_follow() {
+        let server = FakeCircleCi::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1.1/projects"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                    "username": "org",
+                    "reponame": "repo",
+                    "vcs_type": "github",
+                    "vcs_url": "https://github.com/org/repo",
+                    "default_branch": "main",
+                    "branches": {
+                        "main": {
+                            "recent_builds": [
+                                {"build_num": 12, "status": "success", "outcome": "success",
+                                 "added_at": "2024-05-01T12:00:00Z", "vcs_revision": "abc"},
+                                {"build_num": 10, "status": "failed", "outcome": "failed"}
+                            ],
+                            "running_builds": [],
+                            "last_success": {"build_num": 12}
+                        }
+                    }
+                }])),
+            )
+            .mount(server.server())
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1.1/project/github/org/other/follow"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"following": true})),
+            )
+            .mount(server.server())
+            .await;
+        let client = server.client();
+
+        let projects = client.followed_projects().await.unwrap();
+        let (branch, build) = projects[0].latest_build().unwrap();
+        assert_eq!(
+            (branch, build.build_num, build.status.as_str()),
+            ("main", 12, "success")
+        );
+        assert!(
+            client
+                .follow_project("org", "other")
+                .await
+                .unwrap()
+                .following
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Paths into JSON output, for `--query`.
+//!
+//! A small subset of JMESPath, enough to pull a field out of a report in a
+//! script without jq:
+//!
+//! - `failed_actions` - a field; a leading `.`, as in jq, is allowed
+//! - `failed_actions[0]` - an array element; negative indexes count from
+//!   the end
+//! - `failed_actions[*].name` - the rest of the path applied to every
+//!   element, leaving out those where it finds nothing
+//! - `failed_actions[].errors[]` - like `[*]`, but flattens the arrays
+//!   found so far into one
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// A parsed `--query` path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(i64),
+    Project,
+    Flatten,
+}
+
+impl Query {
+    /// Parses a path such as `failed_actions[0].errors[*].category`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error saying where the path stops making sense.
+    pub fn parse(expr: &str) -> Result<Self> {
+        let trimmed = expr.trim();
+        let mut rest = trimmed.strip_prefix('.').unwrap_or(trimmed);
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(inner) = rest.strip_prefix('[') {
+                let Some(end) = inner.find(']') else {
+                    bail!("Invalid query '{}': missing ']'", expr);
+                };
+                segments.push(match inner[..end].trim() {
+                    "" => Segment::Flatten,
+                    "*" => Segment::Project,
+                    index => match index.parse() {
+                        Ok(index) => Segment::Index(index),
+                        Err(_) => bail!(
+                            "Invalid query '{}': expected an index, '*', or nothing in [{}]",
+                            expr,
+                            index
+                        ),
+                    },
+                });
+                rest = &inner[end + 1..];
+                continue;
+            }
+            if !segments.is_empty() {
+                let Some(after) = rest.strip_prefix('.') else {
+                    bail!(
+                        "Invalid query '{}': expected '.' or '[' before '{}'",
+                        expr,
+                        rest
+                    );
+                };
+                rest = after;
+            }
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                bail!(
+                    "Invalid query '{}': expected a field name at '{}'",
+                    expr,
+                    rest
+                );
+            }
+            segments.push(Segment::Field(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        Ok(Query { segments })
+    }
+
+    /// Returns what the path finds in `value`, or `null` if it finds
+    /// nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::query::Query;
+    /// use serde_json::json;
+    ///
+    /// let report = json!({
+    ///     "failed_actions": [
+    ///         {"name": "Build", "errors": []},
+    ///         {"name": "Test", "errors": [{"category": "Jest"}, {"category": "OOM"}]}
+    ///     ]
+    /// });
+    /// let query = Query::parse("failed_actions[].errors[].category").unwrap();
+    /// assert_eq!(query.apply(&report), json!(["Jest", "OOM"]));
+    /// let query = Query::parse("failed_actions[-1].name").unwrap();
+    /// assert_eq!(query.apply(&report), json!("Test"));
+    /// ```
+    pub fn apply(&self, value: &Value) -> Value {
+        apply(value, &self.segments)
+    }
+}
+
+/// Applies `segments`: everything up to the last `[]` first, then the
+/// rest to each element of the flattened result.
+fn apply(value: &Value, segments: &[Segment]) -> Value {
+    let Some(last) = segments.iter().rposition(|s| *s == Segment::Flatten) else {
+        return apply_simple(value, segments);
+    };
+    let Value::Array(items) = apply(value, &segments[..last]) else {
+        return Value::Null;
+    };
+    let flattened = items.into_iter().flat_map(|item| match item {
+        Value::Array(inner) => inner,
+        other => vec![other],
+    });
+    Value::Array(
+        flattened
+            .map(|item| apply_simple(&item, &segments[last + 1..]))
+            .filter(|found| !found.is_null())
+            .collect(),
+    )
+}
+
+/// Applies `segments`, which contain no `[]`.
+fn apply_simple(value: &Value, segments: &[Segment]) -> Value {
+    let Some((segment, rest)) = segments.split_first() else {
+        return value.clone();
+    };
+    match (segment, value) {
+        (Segment::Field(name), Value::Object(map)) => match map.get(name) {
+            Some(field) => apply_simple(field, rest),
+            None => Value::Null,
+        },
+        (Segment::Index(index), Value::Array(items)) => {
+            let position = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(*index as usize)
+            };
+            match position.and_then(|i| items.get(i)) {
+                Some(item) => apply_simple(item, rest),
+                None => Value::Null,
+            }
+        }
+        (Segment::Project, Value::Array(items)) => Value::Array(
+            items
+                .iter()
+                .map(|item| apply_simple(item, rest))
+                .filter(|found| !found.is_null())
+                .collect(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// Formats a query result for printing: strings as they are, so scripts
+/// don't have to strip quotes, anything else as pretty-printed JSON.
+pub fn format_result(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_apply() {
+        let report = json!({
+            "build_num": 7,
+            "failed_actions": [
+                {"errors": [{"first": {"line": "boom"}}]},
+                {"errors": []}
+            ]
+        });
+        let run = |expr: &str| Query::parse(expr).unwrap().apply(&report);
+        assert_eq!(run(".build_num"), json!(7));
+        assert_eq!(run("failed_actions[0].errors[0].first.line"), json!("boom"));
+        assert_eq!(
+            run("failed_actions[*].errors[0].first.line"),
+            json!(["boom"])
+        );
+        assert_eq!(run("failed_actions[5]"), Value::Null);
+        assert_eq!(run("build_num.missing"), Value::Null);
+        assert_eq!(
+            format_result(&run("failed_actions[0].errors[0].first.line")),
+            "boom"
+        );
+
+        assert!(Query::parse("failed_actions[x]").is_err());
+        assert!(Query::parse("failed_actions[0").is_err());
+        assert!(Query::parse("a..b").is_err());
+    }
+}
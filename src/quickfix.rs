@@ -0,0 +1,124 @@
+//! Compiler-style `file:line:col` output for editors.
+//!
+//! [`render`] prints one line per detected error and failed test that
+//! names a file,
+//!
+//! ```text
+//! src/api.ts:14:3: error: TypeScript Error (Build): src/api.ts(14,3): error TS2322: ...
+//! src/Button.test.tsx:1: error: Failed test (Run tests): Button › renders label
+//! ```
+//!
+//! which Vim loads into its quickfix list (`:cexpr system('cdb build ...
+//! --format quickfix')`) and a VS Code task matches with a problem matcher.
+
+use crate::codeowners::repo_relative;
+use crate::patterns::Severity;
+use crate::report::BuildReport;
+use crate::source::locate;
+
+/// Renders a report's detected errors and failed tests as
+/// `file:line[:col]: severity: message` lines.
+///
+/// Errors whose line names no file are left out, since an editor can't
+/// jump to them. The line defaults to 1 when the log doesn't give one, as
+/// it never does for failed tests; the column is omitted when unknown.
+/// Hints have the severity `info`.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::quickfix::render;
+/// use circle_debug::report::BuildReport;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(render(&BuildReport::new("org", "repo", &build)), "");
+/// ```
+pub fn render(report: &BuildReport) -> String {
+    let mut out = String::new();
+    for action in &report.failed_actions {
+        for group in &action.errors {
+            let line = group.first.line.trim();
+            let Some(location) = locate(line) else {
+                continue;
+            };
+            let severity = match group.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Hint => "info",
+            };
+            let position = match location.col {
+                Some(col) => format!("{}:{}", location.line.unwrap_or(1), col),
+                None => location.line.unwrap_or(1).to_string(),
+            };
+            out.push_str(&format!(
+                "{}:{}: {}: {} ({}): {}\n",
+                location.file,
+                position,
+                severity,
+                group.category,
+                action.step,
+                one_line(line)
+            ));
+        }
+        for test in &action.failed_tests {
+            let Some(file) = &test.file else {
+                continue;
+            };
+            out.push_str(&format!(
+                "{}:1: error: Failed test ({}): {}\n",
+                repo_relative(file),
+                action.step,
+                one_line(test.name.as_deref().unwrap_or("test file failed"))
+            ));
+        }
+    }
+    out
+}
+
+/// Keeps a message on one line, which the formats require.
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repro::{FailedTest, TestRunner};
+    use crate::test_utils::{BuildBuilder, FailedActionBuilder};
+
+    #[test]
+    fn test_render_quickfix() {
+        let log = "\
+/home/circleci/project/src/api/client.ts(14,3): error TS2322: Type 'string' is not assignable to type 'number'.
+npm ERR! code ELIFECYCLE
+";
+        let mut report = BuildReport::new("org", "repo", &BuildBuilder::new(9).build());
+        report.failed_actions.push(
+            FailedActionBuilder::new("Test")
+                .log(log)
+                .failed_tests(vec![FailedTest {
+                    runner: TestRunner::Jest,
+                    file: Some("src/Button.test.tsx".to_string()),
+                    name: Some("Button › renders label".to_string()),
+                }])
+                .build(),
+        );
+
+        let rendered = render(&report);
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", rendered);
+        assert!(
+            lines[0].starts_with("src/api/client.ts:14:3: error: TypeScript Error (Test): "),
+            "{}",
+            lines[0]
+        );
+        assert_eq!(
+            lines[1],
+            "src/Button.test.tsx:1: error: Failed test (Test): Button › renders label"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Recorded HTTP fixtures.
+//!
+//! [`RecordingClient`] wraps a [`CircleClient`] so every API request and log
+//! download it makes is either saved to a fixture directory or served back
+//! from one without touching the network. Recordings of real builds make
+//! deterministic tests and offline demos.
+//!
+//! Each response is stored as `<name>.json` (URL and status) next to
+//! `<name>.body`. Tokens are sent as headers and never written, and
+//! presigned log URLs are stored without their signature, so fixtures are
+//! safe to commit.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use anyhow::Result;
+//! # async fn example() -> Result<()> {
+//! use circle_debug::record::RecordingClient;
+//! use circle_debug::CircleClient;
+//!
+//! // Capture a real build once...
+//! let recorder = RecordingClient::record(CircleClient::new()?, "fixtures/build-123");
+//! recorder.get_build("org", "repo", 123).await?;
+//!
+//! // ...then replay it anywhere, no token needed
+//! let replay = RecordingClient::replay("fixtures/build-123")?;
+//! let build = replay.get_build("org", "repo", 123).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cache::{cache_key, fnv1a};
+use crate::{CircleClient, CircleDebugError};
+use anyhow::{bail, Context, Result};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use std::path::PathBuf;
+
+/// A [`CircleClient`] that records responses to, or replays them from, a
+/// fixture directory.
+///
+/// Derefs to the wrapped client, so every client method is available.
+pub struct RecordingClient {
+    inner: CircleClient,
+}
+
+impl RecordingClient {
+    /// Wraps `client` so its responses are saved to `dir`.
+    ///
+    /// The directory is created on the first response. Any response cache
+    /// on `client` is bypassed so every request reaches the network.
+    pub fn record(mut client: CircleClient, dir: impl Into<PathBuf>) -> Self {
+        client.cache = None;
+        client.recorder = Some(Recorder {
+            dir: dir.into(),
+            mode: Mode::Record,
+        });
+        RecordingClient { inner: client }
+    }
+
+    /// Creates a client that serves responses from `dir` without network
+    /// access.
+    ///
+    /// Requests without a recorded response fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` doesn't exist.
+    pub fn replay(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            bail!("Fixture directory {} does not exist", dir.display());
+        }
+
+        let mut client = CircleClient::with_token("replay")?;
+        client.recorder = Some(Recorder {
+            dir,
+            mode: Mode::Replay,
+        });
+        Ok(RecordingClient { inner: client })
+    }
+
+    /// Returns the wrapped client, still recording or replaying.
+    pub fn into_inner(self) -> CircleClient {
+        self.inner
+    }
+}
+
+impl Deref for RecordingClient {
+    type Target = CircleClient;
+
+    fn deref(&self) -> &CircleClient {
+        &self.inner
+    }
+}
+
+/// Where fixtures live and whether they are written or read.
+#[derive(Debug, Clone)]
+pub(crate) struct Recorder {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// A response read back from a fixture.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Recorded {
+    pub(crate) status: StatusCode,
+    /// The body is a strict suffix of the resource, from a tail download.
+    pub(crate) partial: bool,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Recorded {
+    /// Returns the body as text, for error messages.
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FixtureMeta {
+    url: String,
+    status: u16,
+    #[serde(default)]
+    partial: bool,
+}
+
+impl Recorder {
+    /// Returns the recorded response for `url` when replaying, `None` when
+    /// recording.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if replaying and no response was recorded for `url`.
+    pub(crate) fn replayed(&self, url: &str) -> Result<Option<Recorded>> {
+        if self.mode != Mode::Replay {
+            return Ok(None);
+        }
+
+        let key = cache_key(url);
+        let (meta_path, body_path) = self.paths(key);
+        let meta: FixtureMeta = match std::fs::read(&meta_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid fixture {}", meta_path.display()))?,
+            Err(_) => {
+                return Err(CircleDebugError::NetworkError(format!(
+                    "No recorded response for {} in {}",
+                    key,
+                    self.dir.display()
+                ))
+                .into())
+            }
+        };
+        let body = std::fs::read(&body_path)
+            .with_context(|| format!("Failed to read fixture {}", body_path.display()))?;
+
+        tracing::debug!(url = key, status = meta.status, "replayed response");
+        Ok(Some(Recorded {
+            status: StatusCode::from_u16(meta.status)
+                .with_context(|| format!("Invalid status in {}", meta_path.display()))?,
+            partial: meta.partial,
+            body,
+        }))
+    }
+
+    /// Saves a response for `url` when recording; does nothing when
+    /// replaying.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fixture can't be written.
+    pub(crate) fn save(
+        &self,
+        url: &str,
+        status: StatusCode,
+        body: &[u8],
+        partial: bool,
+    ) -> Result<()> {
+        if self.mode != Mode::Record {
+            return Ok(());
+        }
+
+        let key = cache_key(url);
+        let meta = FixtureMeta {
+            url: key.to_string(),
+            status: status.as_u16(),
+            partial,
+        };
+        std::fs::create_dir_all(&self.dir).with_context(|| {
+            format!("Failed to create fixture directory {}", self.dir.display())
+        })?;
+        let (meta_path, body_path) = self.paths(key);
+        std::fs::write(&body_path, body)
+            .with_context(|| format!("Failed to write fixture {}", body_path.display()))?;
+        std::fs::write(&meta_path, serde_json::to_vec_pretty(&meta)?)
+            .with_context(|| format!("Failed to write fixture {}", meta_path.display()))?;
+
+        tracing::debug!(url = key, status = meta.status, "recorded response");
+        Ok(())
+    }
+
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        let name = fixture_name(key);
+        (
+            self.dir.join(format!("{}.json", name)),
+            self.dir.join(format!("{}.body", name)),
+        )
+    }
+}
+
+/// Names a fixture after the tail of its URL path, for readable
+/// directories, plus a hash of the whole URL to keep names unique.
+fn fixture_name(key: &str) -> String {
+    let path = key
+        .split_once("://")
+        .map_or(key, |(_, rest)| rest)
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    let slug: Vec<&str> = path.rsplit('/').filter(|s| !s.is_empty()).take(3).collect();
+    let slug: String = slug
+        .into_iter()
+        .rev()
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(60)
+        .collect();
+    format!("{}-{:016x}", slug, fnv1a(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cdb-record-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_fixture_name() {
+        let name = fixture_name("https://circleci.com/api/v1.1/project/github/org/repo/123");
+        assert!(name.starts_with("org-repo-123-"), "{}", name);
+        assert_ne!(
+            name,
+            fixture_name("https://circleci.com/api/v1.1/project/github/other/org/repo/123")
+        );
+    }
+
+    #[test]
+    fn test_record_then_replay() {
+        let dir = temp_dir("round-trip");
+        let url = "https://circleci.com/api/v1.1/project/github/org/repo/123";
+        let log = "https://bucket.s3.amazonaws.com/output?X-Amz-Signature=one";
+
+        let recorder = Recorder {
+            dir: dir.clone(),
+            mode: Mode::Record,
+        };
+        assert_eq!(recorder.replayed(url).unwrap(), None);
+        recorder.save(url, StatusCode::OK, b"{}", false).unwrap();
+        recorder
+            .save(log, StatusCode::NOT_FOUND, b"gone", false)
+            .unwrap();
+
+        let replayer = Recorder {
+            dir: dir.clone(),
+            mode: Mode::Replay,
+        };
+        let recorded = replayer.replayed(url).unwrap().unwrap();
+        assert_eq!(recorded.status, StatusCode::OK);
+        assert_eq!(recorded.body, b"{}");
+
+        // Signatures differ between fetches of the same log
+        let recorded = replayer
+            .replayed("https://bucket.s3.amazonaws.com/output?X-Amz-Signature=two")
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded.status, StatusCode::NOT_FOUND);
+        assert_eq!(recorded.text(), "gone");
+
+        let err = replayer
+            .replayed(&format!("{}/artifacts", url))
+            .unwrap_err();
+        assert!(err.to_string().contains("No recorded response"), "{}", err);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
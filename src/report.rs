@@ -0,0 +1,573 @@
+//! Serializable analysis results.
+//!
+//! [`BuildReport`] collects everything `cdb build` learns about a build:
+//! metadata, failed actions with their detected errors, step timings, and
+//! cost. The terminal output and `--format json` are both rendered from it.
+
+use crate::bench::PerfRegression;
+use crate::cancel::Cancellation;
+use crate::codeowners::FileOwners;
+use crate::cost::{self, CostEstimate};
+use crate::coverage::CoverageCheck;
+use crate::docker::DockerFailure;
+use crate::environment::Environment;
+use crate::fingerprint::fingerprint;
+use crate::history::SimilarErrors;
+use crate::jvm::JvmSummary;
+use crate::keys::KeyCheck;
+use crate::monorepo::PackageSummary;
+use crate::patterns::ErrorGroup;
+use crate::phases::PhaseTiming;
+use crate::repro::FailedTest;
+use crate::resources::ResourceCheck;
+use crate::retries::{self, RetriedStep};
+use crate::suspects::{FileHistory, SuspectCommits};
+use crate::trigger::Trigger;
+use crate::upstream::UpstreamFailure;
+use crate::workflow::{JobSummary, WorkflowJob};
+use crate::{Action, BuildInfo, Step, WorkflowInfo};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Analysis of a single build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildReport {
+    /// Organization (or user) that owns the project; a UUID for standalone
+    /// organizations.
+    pub org: String,
+    /// Project (repository) name; a UUID for standalone organizations.
+    pub project: String,
+    /// The project's v2 API slug, e.g. `gh/org/repo` or
+    /// `circleci/<org-id>/<project-id>`.
+    #[serde(default)]
+    pub project_slug: String,
+    /// Build number.
+    pub build_num: u32,
+    /// Build status (e.g., "success", "failed").
+    pub status: String,
+    /// Git branch name.
+    pub branch: Option<String>,
+    /// Commit subject line.
+    pub subject: Option<String>,
+    /// Full commit SHA.
+    pub vcs_revision: Option<String>,
+    /// Commit author.
+    pub author_name: Option<String>,
+    /// Why the build was triggered.
+    pub why: Option<String>,
+    /// Who and what triggered the build, and its pull request.
+    #[serde(default)]
+    pub trigger: Option<Trigger>,
+    /// Workflow and job the build belongs to.
+    pub workflow: Option<WorkflowInfo>,
+    /// Link to the build in the CircleCI UI.
+    pub build_url: Option<String>,
+    /// When the build started running.
+    pub start_time: Option<DateTime<Utc>>,
+    /// Total build time in milliseconds.
+    pub build_time_millis: Option<u64>,
+    /// Milliseconds the build waited for capacity.
+    pub queued_millis: Option<u64>,
+    /// Estimated credit cost.
+    pub cost: Option<CostEstimate>,
+    /// Executor, resource class, images, and OS the build ran on.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    /// Failed actions, in display order.
+    pub failed_actions: Vec<FailedAction>,
+    /// Run time per step, longest first.
+    pub step_timings: Vec<StepTiming>,
+    /// Steps that ran again after failing.
+    #[serde(default)]
+    pub retries: Vec<RetriedStep>,
+    /// Other jobs of the workflow, when requested.
+    pub jobs: Option<Vec<JobSummary>>,
+    /// Every job of the build's workflow and its status, this one included.
+    #[serde(default)]
+    pub workflow_jobs: Vec<WorkflowJob>,
+    /// How often the transient errors found here hit recent failed builds.
+    #[serde(default)]
+    pub transient_history: Vec<SimilarErrors>,
+    /// Commits since the previous passing build of the job.
+    #[serde(default)]
+    pub suspects: Option<SuspectCommits>,
+    /// Owners of the files named in detected errors, from CODEOWNERS.
+    #[serde(default)]
+    pub owners: Vec<FileOwners>,
+    /// The latest commits touching each failing test's file.
+    #[serde(default)]
+    pub test_history: Vec<FileHistory>,
+    /// Who or what canceled the build, for canceled builds.
+    #[serde(default)]
+    pub cancellation: Option<Cancellation>,
+    /// The earlier job of the workflow the failure came from, if any.
+    #[serde(default)]
+    pub upstream: Option<UpstreamFailure>,
+    /// Commands that rerun the failed tests locally.
+    #[serde(default)]
+    pub reproduce: Vec<String>,
+    /// Earlier builds with the same failures, from the local history. One
+    /// entry per distinct fingerprint; empty when history is off.
+    #[serde(default)]
+    pub seen_before: Vec<SeenBefore>,
+    /// Line coverage and its change, with `--coverage`.
+    #[serde(default)]
+    pub coverage: Option<CoverageCheck>,
+    /// The project's checkout keys, when a step failed with "Permission
+    /// denied (publickey)".
+    #[serde(default)]
+    pub checkout_keys: Option<KeyCheck>,
+    /// Peak CPU and memory against the resource class, with `--resources`.
+    #[serde(default)]
+    pub resources: Option<ResourceCheck>,
+}
+
+/// A failed action and what was found in its logs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FailedAction {
+    /// Name of the step the action belongs to.
+    pub step: String,
+    /// Action name.
+    pub name: String,
+    /// Parallel container index.
+    pub container: Option<u32>,
+    /// Every parallel container the action failed on the same way, when
+    /// identical failures were collapsed into this entry; empty otherwise.
+    #[serde(default)]
+    pub containers: Vec<u32>,
+    /// The shell command of a `run` step.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// URL of the action's log output.
+    pub output_url: Option<String>,
+    /// Where the cleaned log was saved.
+    pub log_path: Option<String>,
+    /// Detected errors, highest ranked first.
+    pub errors: Vec<ErrorGroup>,
+    /// Packages found in a monorepo log, and which of them failed.
+    #[serde(default)]
+    pub packages: Option<PackageSummary>,
+    /// What Gradle or Maven reported about the failure.
+    #[serde(default)]
+    pub jvm: Option<JvmSummary>,
+    /// The Dockerfile instruction a `docker build` failed on.
+    #[serde(default)]
+    pub docker: Option<DockerFailure>,
+    /// Failed tests found in the log.
+    #[serde(default)]
+    pub failed_tests: Vec<FailedTest>,
+    /// Benchmarks the log reports as slower, most regressed first.
+    #[serde(default)]
+    pub regressions: Vec<PerfRegression>,
+    /// How long the action spent installing, compiling, testing, and
+    /// tearing down, from its log's timestamps.
+    #[serde(default)]
+    pub phases: Vec<PhaseTiming>,
+    /// Why the log couldn't be fetched, if it couldn't.
+    pub fetch_error: Option<String>,
+}
+
+impl FailedAction {
+    /// Creates the entry for a failed action of `step`, with nothing found
+    /// in its log yet.
+    pub fn new(step: &Step, action: &Action) -> Self {
+        FailedAction {
+            step: step.name.clone(),
+            name: action.name.clone(),
+            container: action.index,
+            containers: Vec::new(),
+            command: action.bash_command.clone(),
+            output_url: action.output_url.clone(),
+            log_path: None,
+            errors: Vec::new(),
+            packages: None,
+            jvm: None,
+            docker: None,
+            failed_tests: Vec::new(),
+            regressions: Vec::new(),
+            phases: Vec::new(),
+            fetch_error: None,
+        }
+    }
+
+    /// Names the container the action ran on, or all of them for
+    /// collapsed identical failures, e.g. `containers 0, 2, 3`.
+    ///
+    /// `None` for actions without a container index.
+    pub fn container_label(&self) -> Option<String> {
+        if self.containers.is_empty() {
+            return self.container.map(|c| format!("container {}", c));
+        }
+        let containers: Vec<String> = self.containers.iter().map(u32::to_string).collect();
+        Some(format!("containers {}", containers.join(", ")))
+    }
+
+    /// Lines of the action's command worth showing: without the shebang
+    /// CircleCI puts in front, blank lines around it, or the indentation
+    /// all its lines share.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::report::FailedAction;
+    ///
+    /// let action: FailedAction = serde_json::from_value(serde_json::json!({
+    ///     "step": "Run tests", "name": "Run tests", "container": null,
+    ///     "output_url": null, "log_path": null, "errors": [], "fetch_error": null,
+    ///     "command": "#!/bin/bash -eo pipefail\n  npm ci\n  npm test -- --ci\n",
+    /// }))
+    /// .unwrap();
+    /// assert_eq!(action.command_lines(), vec!["npm ci", "npm test -- --ci"]);
+    /// ```
+    pub fn command_lines(&self) -> Vec<&str> {
+        let Some(command) = &self.command else {
+            return Vec::new();
+        };
+        let mut lines: Vec<&str> = command.lines().collect();
+        if lines.first().is_some_and(|line| line.starts_with("#!")) {
+            lines.remove(0);
+        }
+        while lines.first().is_some_and(|line| line.trim().is_empty()) {
+            lines.remove(0);
+        }
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        let indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        lines
+            .into_iter()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end())
+            .collect()
+    }
+
+    /// Returns what tells this failure apart from another container's: the
+    /// fingerprints of its errors and its failed tests.
+    ///
+    /// `None` when there's nothing to compare, because the log wasn't
+    /// analyzed or nothing was found in it.
+    fn signature(&self) -> Option<(Vec<String>, &[FailedTest])> {
+        if self.log_path.is_none() || (self.errors.is_empty() && self.failed_tests.is_empty()) {
+            return None;
+        }
+        Some((
+            self.errors.iter().map(fingerprint).collect(),
+            &self.failed_tests,
+        ))
+    }
+}
+
+/// Groups the failed actions that failed identically on different
+/// parallel containers: the same action of the same step, with the same
+/// errors (by [`fingerprint`]) and failed tests.
+///
+/// # Returns
+///
+/// Groups of indexes into `actions`, ordered by their first member, each
+/// in order. Actions without a match are groups of one.
+pub fn identical_failures(actions: &[FailedAction]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, action) in actions.iter().enumerate() {
+        let signature = action.signature();
+        let same = groups.iter_mut().find(|group| {
+            let first = &actions[group[0]];
+            signature.is_some()
+                && first.step == action.step
+                && first.name == action.name
+                && group
+                    .iter()
+                    .all(|&j| actions[j].container != action.container)
+                && first.signature() == signature
+        });
+        match same {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups
+}
+
+/// A failure found in one or more jobs, by [`fingerprint`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SharedFailure {
+    /// The fingerprint the jobs share.
+    pub fingerprint: String,
+    /// Id of the pattern that matched.
+    pub pattern_id: String,
+    /// Category of the pattern that matched.
+    pub category: String,
+    /// First matching line, from the first job it was found in.
+    pub line: String,
+    /// Jobs that failed this way, by [`BuildReport::job_label`], in the
+    /// order given.
+    pub jobs: Vec<String>,
+}
+
+/// Deduplicates the errors of several jobs' reports, e.g. every failed
+/// job of a workflow, by [`fingerprint`].
+///
+/// # Returns
+///
+/// One entry per distinct fingerprint, those hitting the most jobs first,
+/// then in the order they were found.
+pub fn shared_failures<'a>(
+    reports: impl IntoIterator<Item = &'a BuildReport>,
+) -> Vec<SharedFailure> {
+    let mut shared: Vec<SharedFailure> = Vec::new();
+    for report in reports {
+        let job = report.job_label();
+        for group in report.errors() {
+            let fingerprint = fingerprint(group);
+            match shared.iter_mut().find(|s| s.fingerprint == fingerprint) {
+                Some(failure) if !failure.jobs.contains(&job) => failure.jobs.push(job.clone()),
+                Some(_) => {}
+                None => shared.push(SharedFailure {
+                    fingerprint,
+                    pattern_id: group.pattern_id.clone(),
+                    category: group.category.clone(),
+                    line: group.first.line.clone(),
+                    jobs: vec![job.clone()],
+                }),
+            }
+        }
+    }
+    // Stable, so ties keep the order they were found in
+    shared.sort_by_key(|failure| std::cmp::Reverse(failure.jobs.len()));
+    shared
+}
+
+/// Earlier builds that failed with the same fingerprint, from the local
+/// history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SeenBefore {
+    /// The shared fingerprint.
+    pub fingerprint: String,
+    /// Category of the pattern that matched.
+    pub category: String,
+    /// Build numbers of the project with the same failure, newest first.
+    /// Empty for a failure not seen before.
+    pub builds: Vec<u32>,
+    /// When the oldest of those builds started.
+    pub first_seen: Option<DateTime<Utc>>,
+}
+
+/// Total run time of one step across its actions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepTiming {
+    /// Step name.
+    pub name: String,
+    /// Milliseconds spent in the step.
+    pub millis: u64,
+    /// `failed` if any action failed, otherwise the actions' status.
+    #[serde(default)]
+    pub status: String,
+}
+
+impl BuildReport {
+    /// Creates a report with the build's metadata, timings, retried steps,
+    /// and cost.
+    ///
+    /// Failed actions, workflow jobs and their timings, error history, suspects, owners, test history,
+    /// coverage, checkout keys, resource usage, and the cancellation start empty; the caller adds them as their logs and
+    /// details are fetched.
+    pub fn new(org: &str, project: &str, build: &BuildInfo) -> Self {
+        BuildReport {
+            org: org.to_string(),
+            project: project.to_string(),
+            project_slug: crate::project_slug(org, project),
+            build_num: build.build_num,
+            status: build.status.clone(),
+            branch: build.branch.clone(),
+            subject: build.subject.clone(),
+            vcs_revision: build.vcs_revision.clone(),
+            author_name: build.author_name.clone(),
+            why: build.why.clone(),
+            trigger: build.trigger(),
+            workflow: build.workflows.clone(),
+            build_url: build.build_url.clone(),
+            start_time: build.start_time,
+            build_time_millis: build.build_time_millis,
+            queued_millis: build.queue_duration().map(|d| d.as_millis() as u64),
+            cost: cost::estimate(build),
+            environment: Some(Environment::from_build(build)).filter(|e| !e.is_empty()),
+            failed_actions: Vec::new(),
+            step_timings: step_timings(build),
+            retries: retries::retried_steps(build),
+            jobs: None,
+            workflow_jobs: Vec::new(),
+            transient_history: Vec::new(),
+            suspects: None,
+            owners: Vec::new(),
+            test_history: Vec::new(),
+            cancellation: None,
+            upstream: None,
+            reproduce: Vec::new(),
+            seen_before: Vec::new(),
+            coverage: None,
+            checkout_keys: None,
+            resources: None,
+        }
+    }
+
+    /// Names the build's job, e.g. "test", or "#123" outside a workflow.
+    pub fn job_label(&self) -> String {
+        self.workflow
+            .as_ref()
+            .and_then(|workflow| workflow.job_name.clone())
+            .unwrap_or_else(|| format!("#{}", self.build_num))
+    }
+
+    /// Returns every detected error group across all failed actions.
+    pub fn errors(&self) -> impl Iterator<Item = &ErrorGroup> {
+        self.failed_actions
+            .iter()
+            .flat_map(|action| action.errors.iter())
+    }
+
+    /// Returns every failed test across all failed actions.
+    pub fn failed_tests(&self) -> impl Iterator<Item = &FailedTest> {
+        self.failed_actions
+            .iter()
+            .flat_map(|action| action.failed_tests.iter())
+    }
+
+    /// Returns every performance regression, with the action it was found
+    /// in.
+    pub fn regressions(&self) -> impl Iterator<Item = (&FailedAction, &PerfRegression)> {
+        self.failed_actions.iter().flat_map(|action| {
+            action
+                .regressions
+                .iter()
+                .map(move |regression| (action, regression))
+        })
+    }
+}
+
+/// Sums action run times per step, dropping steps without timing data.
+pub(crate) fn step_timings(build: &BuildInfo) -> Vec<StepTiming> {
+    let mut timings: Vec<StepTiming> = build
+        .steps
+        .iter()
+        .map(|step| StepTiming {
+            name: step.name.clone(),
+            millis: step.actions.iter().filter_map(|a| a.run_time_millis).sum(),
+            status: if step.has_failures() {
+                "failed".to_string()
+            } else {
+                step.actions
+                    .iter()
+                    .map(|a| a.status.as_str())
+                    .find(|status| *status != "success")
+                    .unwrap_or("success")
+                    .to_string()
+            },
+        })
+        .filter(|timing| timing.millis > 0)
+        .collect();
+
+    // Longest first
+    timings.sort_by_key(|timing| std::cmp::Reverse(timing.millis));
+    timings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FailedActionBuilder;
+
+    #[test]
+    fn test_report_step_timings() {
+        let build: BuildInfo = serde_json::from_str(
+            r#"{"build_num": 9, "status": "failed", "branch": "main", "subject": null,
+                "build_time_millis": 90000,
+                "steps": [
+                    {"name": "Checkout", "actions": [
+                        {"name": "Checkout", "status": "success", "failed": null, "output_url": null,
+                         "type": "test", "run_time_millis": 1000}]},
+                    {"name": "Setup", "actions": [
+                        {"name": "Setup", "status": "success", "failed": null, "output_url": null,
+                         "type": "test", "run_time_millis": null}]},
+                    {"name": "Test", "actions": [
+                        {"name": "Test", "status": "failed", "failed": true, "output_url": null,
+                         "type": "test", "run_time_millis": 60000, "index": 0},
+                        {"name": "Test", "status": "success", "failed": null, "output_url": null,
+                         "type": "test", "run_time_millis": 20000, "index": 1}]}
+                ]}"#,
+        )
+        .unwrap();
+
+        let report = BuildReport::new("org", "repo", &build);
+        let names: Vec<_> = report
+            .step_timings
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Test", "Checkout"]);
+        assert_eq!(report.step_timings[0].millis, 80000);
+        assert_eq!(report.step_timings[0].status, "failed");
+        assert_eq!(report.step_timings[1].status, "success");
+        assert_eq!(report.cost.as_ref().unwrap().credits, 20.0);
+        assert_eq!(report.errors().count(), 0);
+    }
+
+    #[test]
+    fn test_identical_failures_across_containers() {
+        let action = |container: u32, log: &str| {
+            FailedActionBuilder::new("Run tests")
+                .container(container)
+                .log_path(format!("cdb-9-{}.log", container))
+                .log(log)
+                .build()
+        };
+        let mut unanalyzed = action(4, "");
+        unanalyzed.log_path = None;
+        let actions = vec![
+            action(0, "Segmentation fault at 0x7ffd3a"),
+            action(1, "npm ERR! code ELIFECYCLE"),
+            action(2, "Segmentation fault at 0x1b2c9e"),
+            action(3, "Segmentation fault at 0x55aa01"),
+            unanalyzed.clone(),
+            unanalyzed,
+        ];
+        assert_eq!(
+            identical_failures(&actions),
+            vec![vec![0, 2, 3], vec![1], vec![4], vec![5]]
+        );
+    }
+
+    #[test]
+    fn test_shared_failures_across_jobs() {
+        let build: BuildInfo = serde_json::from_str(
+            r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null, "steps": []}"#,
+        )
+        .unwrap();
+        let job = |name: Option<&str>, log: &str| {
+            let mut report = BuildReport::new("org", "repo", &build);
+            report.workflow = name.map(|name| WorkflowInfo {
+                job_name: Some(name.to_string()),
+                job_id: None,
+                workflow_name: Some("ci".to_string()),
+                workflow_id: None,
+            });
+            report
+                .failed_actions
+                .push(FailedActionBuilder::new("Run tests").log(log).build());
+            report
+        };
+        let reports = vec![
+            job(Some("lint"), "npm ERR! code ELIFECYCLE"),
+            job(Some("test"), "Segmentation fault at 0x7ffd3a"),
+            job(None, "Segmentation fault at 0x1b2c9e"),
+        ];
+
+        let shared = shared_failures(&reports);
+        let jobs: Vec<&[String]> = shared.iter().map(|s| s.jobs.as_slice()).collect();
+        assert_eq!(jobs[0], ["test".to_string(), "#7".to_string()]);
+        assert_eq!(shared[0].line, "Segmentation fault at 0x7ffd3a");
+        assert!(jobs[1..].iter().all(|jobs| *jobs == ["lint".to_string()]));
+    }
+}
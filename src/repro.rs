@@ -0,0 +1,312 @@
+//! Local commands that rerun just the failed tests.
+//!
+//! [`parse_failed_tests`] picks failed tests out of Jest, `cargo test`, and
+//! pytest output, and [`reproduce_commands`] turns them into commands to
+//! paste into a terminal:
+//!
+//! | Runner | Failure in the log | Command |
+//! |---|---|---|
+//! | Jest | `FAIL src/a.test.ts` then `● Suite › name` | `npx jest src/a.test.ts -t 'Suite name'` |
+//! | `cargo test` | `test mod::name ... FAILED` | `cargo test -- --exact mod::name` |
+//! | pytest | `FAILED tests/x.py::test_y - ...` | `pytest tests/x.py::test_y` |
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// The test runner that reported a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestRunner {
+    Jest,
+    Cargo,
+    Pytest,
+}
+
+/// A failed test, as its runner named it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailedTest {
+    /// Which runner ran it.
+    pub runner: TestRunner,
+    /// Test file, for runners that select tests by file.
+    pub file: Option<String>,
+    /// Test name, e.g. `Button › renders label` for Jest or
+    /// `parser::tests::empty` for `cargo test`. `None` when the whole file
+    /// failed, like a Jest suite that failed to run.
+    pub name: Option<String>,
+}
+
+fn jest_file_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*(?P<result>FAIL|PASS)\s+(?P<file>\S+)").unwrap())
+}
+
+fn jest_test_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*● (?P<name>.+?)\s*$").unwrap())
+}
+
+fn cargo_test_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^test (?P<name>\S+) \.\.\. FAILED\s*$").unwrap())
+}
+
+fn pytest_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // The short test summary, or verbose mode's per-test lines
+        Regex::new(
+            r"^(?:FAILED (?P<summary>\S+\.py::\S+)|(?P<verbose>\S+\.py::\S+) FAILED)(?:\s|$)",
+        )
+        .unwrap()
+    })
+}
+
+/// Finds the failed tests in a log.
+///
+/// # Returns
+///
+/// Each failed test once, in the order they first appear.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::repro::{parse_failed_tests, TestRunner};
+///
+/// let log = "test parser::tests::empty ... FAILED\n\
+///            FAILED tests/test_api.py::test_login - AssertionError\n";
+/// let tests = parse_failed_tests(log);
+/// assert_eq!(tests.len(), 2);
+/// assert_eq!(tests[0].runner, TestRunner::Cargo);
+/// assert_eq!(tests[1].file.as_deref(), Some("tests/test_api.py"));
+/// ```
+pub fn parse_failed_tests(log: &str) -> Vec<FailedTest> {
+    let mut tests: Vec<FailedTest> = Vec::new();
+    let mut push = |test: FailedTest| {
+        if !tests.contains(&test) {
+            tests.push(test);
+        }
+    };
+    // The Jest test file whose failures are being listed
+    let mut jest_file: Option<&str> = None;
+    let mut jest_file_tests = 0;
+
+    for line in log.lines() {
+        if let Some(caps) = jest_file_regex().captures(line) {
+            if let (Some(file), 0) = (jest_file, jest_file_tests) {
+                push(jest_suite(file));
+            }
+            jest_file = (&caps["result"] == "FAIL").then(|| caps.name("file").unwrap().as_str());
+            jest_file_tests = 0;
+            continue;
+        }
+        if let Some(file) = jest_file {
+            if let Some(caps) = jest_test_regex().captures(line) {
+                let name = &caps["name"];
+                // Console output blocks and suites that didn't load aren't tests
+                if name != "Console" && name != "Test suite failed to run" {
+                    push(FailedTest {
+                        runner: TestRunner::Jest,
+                        file: Some(file.to_string()),
+                        name: Some(name.to_string()),
+                    });
+                    jest_file_tests += 1;
+                }
+                continue;
+            }
+        }
+
+        if let Some(caps) = cargo_test_regex().captures(line) {
+            push(FailedTest {
+                runner: TestRunner::Cargo,
+                file: None,
+                name: Some(caps["name"].to_string()),
+            });
+        } else if let Some(caps) = pytest_regex().captures(line.trim_start()) {
+            let id = caps
+                .name("summary")
+                .or_else(|| caps.name("verbose"))
+                .unwrap()
+                .as_str();
+            let (file, name) = id.split_once("::").unwrap();
+            push(FailedTest {
+                runner: TestRunner::Pytest,
+                file: Some(file.to_string()),
+                name: Some(name.to_string()),
+            });
+        }
+    }
+    if let (Some(file), 0) = (jest_file, jest_file_tests) {
+        push(jest_suite(file));
+    }
+    tests
+}
+
+fn jest_suite(file: &str) -> FailedTest {
+    FailedTest {
+        runner: TestRunner::Jest,
+        file: Some(file.to_string()),
+        name: None,
+    }
+}
+
+/// Builds one command per runner (per file, for Jest) that reruns exactly
+/// `tests`.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::repro::{reproduce_commands, FailedTest, TestRunner};
+///
+/// let tests = [FailedTest {
+///     runner: TestRunner::Jest,
+///     file: Some("src/Button.test.tsx".to_string()),
+///     name: Some("Button › renders label".to_string()),
+/// }];
+/// assert_eq!(
+///     reproduce_commands(&tests),
+///     vec!["npx jest src/Button.test.tsx -t 'Button renders label'"]
+/// );
+/// ```
+pub fn reproduce_commands(tests: &[FailedTest]) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    let mut jest_files: Vec<&str> = Vec::new();
+    for file in tests
+        .iter()
+        .filter(|test| test.runner == TestRunner::Jest)
+        .filter_map(|test| test.file.as_deref())
+    {
+        if !jest_files.contains(&file) {
+            jest_files.push(file);
+        }
+    }
+    for file in jest_files {
+        let in_file = tests
+            .iter()
+            .filter(|test| test.runner == TestRunner::Jest && test.file.as_deref() == Some(file));
+        let mut names = Vec::new();
+        let mut whole_file = false;
+        for test in in_file {
+            match &test.name {
+                // `-t` matches the describe blocks and test name joined by spaces
+                Some(name) => names.push(regex::escape(&name.replace(" › ", " "))),
+                None => whole_file = true,
+            }
+        }
+        let mut command = format!("npx jest {}", shell_quote(file));
+        if !whole_file && !names.is_empty() {
+            command.push_str(&format!(" -t {}", shell_quote(&names.join("|"))));
+        }
+        commands.push(command);
+    }
+
+    let cargo: Vec<String> = tests
+        .iter()
+        .filter(|test| test.runner == TestRunner::Cargo)
+        .filter_map(|test| test.name.as_deref())
+        .map(shell_quote)
+        .collect();
+    if !cargo.is_empty() {
+        commands.push(format!("cargo test -- --exact {}", cargo.join(" ")));
+    }
+
+    let pytest: Vec<String> = tests
+        .iter()
+        .filter(|test| test.runner == TestRunner::Pytest)
+        .filter_map(|test| match (&test.file, &test.name) {
+            (Some(file), Some(name)) => Some(shell_quote(&format!("{}::{}", file, name))),
+            (Some(file), None) => Some(shell_quote(file)),
+            _ => None,
+        })
+        .collect();
+    if !pytest.is_empty() {
+        commands.push(format!("pytest {}", pytest.join(" ")));
+    }
+
+    commands
+}
+
+/// Quotes `arg` for a POSIX shell, leaving plain words as they are.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:@%+=,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jest_failures_by_file() {
+        let log = "\
+PASS src/utils.test.ts
+FAIL src/components/Button.test.tsx (5.2 s)
+  ● Console
+
+    console.log
+      rendering
+
+  ● Button › renders label
+
+    expect(received).toBe(expected)
+
+  ● Button › handles (click)
+
+FAIL src/api.test.ts
+  ● Test suite failed to run
+
+    Cannot find module './client'
+
+Summary of all failing tests
+FAIL src/components/Button.test.tsx (5.2 s)
+  ● Button › renders label
+";
+        let tests = parse_failed_tests(log);
+        let names: Vec<_> = tests.iter().map(|t| t.name.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("Button › renders label"),
+                Some("Button › handles (click)"),
+                None
+            ]
+        );
+        assert_eq!(
+            reproduce_commands(&tests),
+            vec![
+                r"npx jest src/components/Button.test.tsx -t 'Button renders label|Button handles \(click\)'",
+                "npx jest src/api.test.ts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cargo_and_pytest_commands() {
+        let log = "\
+test parser::tests::empty ... FAILED
+test parser::tests::nested ... ok
+test result: FAILED. 1 passed; 1 failed; 0 ignored
+tests/test_user.py::TestUser::test_name[it's] FAILED                 [ 50%]
+FAILED tests/test_api.py::test_login - AssertionError: 401 != 200
+FAILED tests/test_user.py::TestUser::test_name[it's] - KeyError
+";
+        let tests = parse_failed_tests(log);
+        assert_eq!(tests.len(), 3);
+        assert_eq!(
+            reproduce_commands(&tests),
+            vec![
+                "cargo test -- --exact parser::tests::empty",
+                r"pytest 'tests/test_user.py::TestUser::test_name[it'\''s]' tests/test_api.py::test_login",
+            ]
+        );
+        assert!(parse_failed_tests("test result: ok. 3 passed\n").is_empty());
+    }
+}
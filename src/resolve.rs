@@ -0,0 +1,338 @@
+//! Mapping between build, job, pipeline, and workflow identifiers.
+//!
+//! The v1.1 API names a job run by its build number; the v2 API calls the
+//! same number the job number, and groups jobs into workflows (by UUID)
+//! and workflows into pipelines (by UUID, and by a per-project number shown
+//! in the UI). These helpers go from whichever identifier a caller has to
+//! the others.
+
+use crate::workflow::Page;
+use crate::{project_slug, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// A pipeline, as returned by the v2 API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pipeline {
+    /// UUID of the pipeline.
+    pub id: String,
+    /// Per-project number shown in the UI.
+    pub number: u32,
+    /// Pipeline state (e.g., "created", "errored").
+    pub state: String,
+    /// When the pipeline was triggered.
+    pub created_at: Option<DateTime<Utc>>,
+    /// Branch and commit the pipeline ran on.
+    #[serde(default)]
+    pub vcs: Option<PipelineVcs>,
+    /// What started the pipeline.
+    #[serde(default)]
+    pub trigger: Option<PipelineTrigger>,
+    /// Values the trigger passed in, such as the schedule of a scheduled
+    /// pipeline, where CircleCI reports them.
+    #[serde(default)]
+    pub trigger_parameters: Option<serde_json::Value>,
+}
+
+impl Pipeline {
+    /// Returns `true` for pipelines started by a schedule, either a
+    /// scheduled pipeline or a scheduled workflow trigger in the config.
+    pub fn is_scheduled(&self) -> bool {
+        self.trigger.as_ref().is_some_and(|trigger| {
+            matches!(trigger.kind.as_str(), "schedule" | "scheduled_pipeline")
+        })
+    }
+}
+
+/// What started a [`Pipeline`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineTrigger {
+    /// Trigger type (e.g., "webhook", "api", "schedule",
+    /// "scheduled_pipeline").
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Version control details of a [`Pipeline`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineVcs {
+    /// Branch, absent for tag pipelines.
+    pub branch: Option<String>,
+    /// Full commit SHA.
+    pub revision: Option<String>,
+}
+
+/// A workflow of a pipeline, as returned by the v2 API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineWorkflow {
+    /// UUID of the workflow.
+    pub id: String,
+    /// Name of the workflow in the config.
+    pub name: String,
+    /// Workflow status (e.g., "success", "failed", "running").
+    pub status: String,
+    /// UUID of the pipeline.
+    pub pipeline_id: String,
+    /// Number of the pipeline.
+    pub pipeline_number: u32,
+}
+
+/// Every identifier of one job run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobRef {
+    /// v2 job number, which is also the v1.1 build number.
+    pub job_number: u32,
+    /// Name of the job in the workflow config.
+    pub name: String,
+    /// Job status (e.g., "success", "failed").
+    pub status: String,
+    /// UUID of the pipeline.
+    pub pipeline_id: Option<String>,
+    /// Number of the pipeline.
+    pub pipeline_number: Option<u32>,
+    /// UUID of the workflow the job last ran in.
+    pub workflow_id: Option<String>,
+    /// Name of that workflow.
+    pub workflow_name: Option<String>,
+}
+
+/// Job details from `/v2/project/{slug}/job/{number}`.
+#[derive(Debug, Deserialize)]
+struct JobDetails {
+    number: u32,
+    name: String,
+    status: String,
+    pipeline: Option<IdRef>,
+    latest_workflow: Option<WorkflowRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdRef {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowRef {
+    id: String,
+    name: Option<String>,
+}
+
+impl CircleClient {
+    /// Looks up a pipeline by its number within a project.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipeline doesn't exist or the request fails.
+    pub async fn get_pipeline(&self, org: &str, project: &str, number: u32) -> Result<Pipeline> {
+        let url = format!(
+            "{}/v2/project/{}/pipeline/{}",
+            self.base_url,
+            project_slug(org, project),
+            number
+        );
+        self.get_json(&url).await.with_context(|| {
+            format!(
+                "Failed to look up pipeline {} of {}/{}",
+                number, org, project
+            )
+        })
+    }
+
+    /// Looks up a pipeline by its UUID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipeline doesn't exist or the request fails.
+    pub async fn get_pipeline_by_id(&self, pipeline_id: &str) -> Result<Pipeline> {
+        let url = format!("{}/v2/pipeline/{}", self.base_url, pipeline_id);
+        self.get_json(&url)
+            .await
+            .with_context(|| format!("Failed to look up pipeline {}", pipeline_id))
+    }
+
+    /// Lists the workflows of a pipeline, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails.
+    pub async fn get_pipeline_workflows(&self, pipeline_id: &str) -> Result<Vec<PipelineWorkflow>> {
+        let base = format!("{}/v2/pipeline/{}/workflow", self.base_url, pipeline_id);
+        let mut workflows = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<PipelineWorkflow> = self.get_json(url.as_str()).await?;
+            workflows.extend(page.items);
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(workflows)
+    }
+
+    /// Resolves a build (or job) number to its pipeline and workflow.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the job doesn't exist or a request fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let job = client.resolve_job("myorg", "myrepo", 12345).await?;
+    /// println!(
+    ///     "{} ran in pipeline {:?}, workflow {:?}",
+    ///     job.name, job.pipeline_number, job.workflow_name
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn resolve_job(&self, org: &str, project: &str, number: u32) -> Result<JobRef> {
+        let url = format!(
+            "{}/v2/project/{}/job/{}",
+            self.base_url,
+            project_slug(org, project),
+            number
+        );
+        let job: JobDetails = self
+            .get_json(&url)
+            .await
+            .with_context(|| format!("Failed to look up job {} of {}/{}", number, org, project))?;
+        let pipeline = match &job.pipeline {
+            Some(pipeline) => Some(self.get_pipeline_by_id(&pipeline.id).await?),
+            None => None,
+        };
+
+        Ok(JobRef {
+            job_number: job.number,
+            name: job.name,
+            status: job.status,
+            pipeline_id: pipeline.as_ref().map(|p| p.id.clone()),
+            pipeline_number: pipeline.as_ref().map(|p| p.number),
+            workflow_id: job.latest_workflow.as_ref().map(|w| w.id.clone()),
+            workflow_name: job.latest_workflow.and_then(|w| w.name),
+        })
+    }
+
+    /// Lists every job of a pipeline, given its number, across all its
+    /// workflows.
+    ///
+    /// Approval jobs, which have no job number, are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipeline doesn't exist or a request fails.
+    pub async fn pipeline_jobs(
+        &self,
+        org: &str,
+        project: &str,
+        pipeline_number: u32,
+    ) -> Result<Vec<JobRef>> {
+        let pipeline = self.get_pipeline(org, project, pipeline_number).await?;
+        let workflows = self.get_pipeline_workflows(&pipeline.id).await?;
+        let jobs = futures::future::join_all(
+            workflows
+                .iter()
+                .map(|workflow| self.get_workflow_jobs(&workflow.id)),
+        )
+        .await;
+
+        let mut refs = Vec::new();
+        for (workflow, jobs) in workflows.iter().zip(jobs) {
+            for job in jobs? {
+                let Some(job_number) = job.job_number else {
+                    continue;
+                };
+                refs.push(JobRef {
+                    job_number,
+                    name: job.name,
+                    status: job.status,
+                    pipeline_id: Some(pipeline.id.clone()),
+                    pipeline_number: Some(pipeline.number),
+                    workflow_id: Some(workflow.id.clone()),
+                    workflow_name: Some(workflow.name.clone()),
+                });
+            }
+        }
+        Ok(refs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_resolve_job_and_pipeline_jobs() {
+        let server = FakeCircleCi::start().await;
+        let mount = |route: &str, body: serde_json::Value| {
+            Mock::given(method("GET"))
+                .and(path(format!("/api{}", route)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        };
+        let pipeline = serde_json::json!({
+            "id": "p-1", "number": 42, "state": "created",
+            "created_at": "2024-05-01T12:00:00Z",
+            "vcs": {"branch": "main", "revision": "abc123"}
+        });
+        for mock in [
+            mount(
+                "/v2/project/gh/org/repo/job/101",
+                serde_json::json!({
+                    "number": 101, "name": "test", "status": "failed",
+                    "pipeline": {"id": "p-1"},
+                    "latest_workflow": {"id": "w-1", "name": "ci"}
+                }),
+            ),
+            mount("/v2/pipeline/p-1", pipeline.clone()),
+            mount("/v2/project/gh/org/repo/pipeline/42", pipeline),
+            mount(
+                "/v2/pipeline/p-1/workflow",
+                serde_json::json!({
+                    "items": [{"id": "w-1", "name": "ci", "status": "failed",
+                               "pipeline_id": "p-1", "pipeline_number": 42}],
+                    "next_page_token": null
+                }),
+            ),
+            mount(
+                "/v2/workflow/w-1/job",
+                serde_json::json!({
+                    "items": [
+                        {"id": "a", "name": "build", "job_number": 100, "status": "success", "type": "build"},
+                        {"id": "b", "name": "test", "job_number": 101, "status": "failed", "type": "build"},
+                        {"id": "c", "name": "hold", "status": "on_hold", "type": "approval"}
+                    ],
+                    "next_page_token": null
+                }),
+            ),
+        ] {
+            mock.mount(server.server()).await;
+        }
+        let client = server.client();
+
+        let job = client.resolve_job("org", "repo", 101).await.unwrap();
+        assert_eq!(job.pipeline_number, Some(42));
+        assert_eq!(job.workflow_id.as_deref(), Some("w-1"));
+
+        let jobs = client.pipeline_jobs("org", "repo", 42).await.unwrap();
+        let numbers: Vec<u32> = jobs.iter().map(|job| job.job_number).collect();
+        assert_eq!(numbers, vec![100, 101]);
+        assert_eq!(jobs[1], job);
+    }
+}
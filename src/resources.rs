@@ -0,0 +1,340 @@
+//! CPU and memory use of a job, against its resource class.
+//!
+//! CircleCI samples how much CPU and memory a job uses while it runs and
+//! shows it in the web app's Resources tab. [`CircleClient::resource_usage`]
+//! fetches those samples where the API serves them; the endpoint isn't in
+//! CircleCI's published API reference, so it may be missing for some jobs,
+//! executors, or plans. [`check`] compares the peaks with the job's
+//! resource class and says whether a smaller class would do ("peaked at
+//! 28% CPU on large, medium would fit") or the job ran out of room, which
+//! is often what's behind an OOM kill.
+
+use crate::cost;
+use crate::{BuildInfo, CircleClient, CircleDebugError};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Share of a resource a class should keep free at the job's peak, so a
+/// suggested smaller class isn't sized to the byte.
+const HEADROOM: f64 = 1.25;
+
+/// Peak memory, as a percentage of the class's, from which a job counts
+/// as out of memory.
+const MEMORY_SATURATED_PERCENT: f64 = 90.0;
+
+/// Average CPU, as a percentage of the class's, from which a job counts as
+/// CPU-bound. Peaks at 100% are normal while compiling, so the average is
+/// what tells.
+const CPU_SATURATED_PERCENT: f64 = 90.0;
+
+/// vCPUs and memory in MB of the Docker and Arm resource classes, smallest
+/// first within each family.
+const CLASS_SIZES: &[(&str, f64, u64)] = &[
+    ("small", 1.0, 2048),
+    ("medium", 2.0, 4096),
+    ("medium+", 3.0, 6144),
+    ("large", 4.0, 8192),
+    ("xlarge", 8.0, 16384),
+    ("2xlarge", 16.0, 32768),
+    ("2xlarge+", 20.0, 40960),
+    ("arm.medium", 2.0, 8192),
+    ("arm.large", 4.0, 16384),
+    ("arm.xlarge", 8.0, 32768),
+    ("arm.2xlarge", 16.0, 65536),
+];
+
+/// One reading of a job's resource use.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageSample {
+    /// When the reading was taken.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// CPU use, as a percentage of all the class's vCPUs.
+    pub cpu_percent: Option<f64>,
+    /// Memory in use, in MB.
+    pub memory_mb: Option<f64>,
+}
+
+/// Response of the resource usage endpoint.
+#[derive(Debug, Deserialize)]
+struct UsageResponse {
+    #[serde(default)]
+    items: Vec<UsageSample>,
+}
+
+/// Which resource a job ran out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    Cpu,
+    Memory,
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resource::Cpu => write!(f, "CPU"),
+            Resource::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// What a job's peaks say about its resource class.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum Verdict {
+    /// The class is about right.
+    Fits,
+    /// A smaller class would have had room for the peaks.
+    Underutilized {
+        /// The smallest class of the same family that would fit.
+        suggested_class: String,
+    },
+    /// The job used (nearly) all of a resource.
+    Saturated {
+        /// The resource it ran out of.
+        resource: Resource,
+        /// The next class up in the same family, if there is one.
+        suggested_class: Option<String>,
+    },
+}
+
+/// A job's CPU and memory use against its resource class.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceCheck {
+    /// Resource class the job ran on.
+    pub resource_class: String,
+    /// vCPUs of the class.
+    pub cpu: f64,
+    /// Memory of the class in MB.
+    pub ram_mb: u64,
+    /// Highest CPU use, as a percentage of the class's.
+    pub peak_cpu_percent: f64,
+    /// Average CPU use, as a percentage of the class's.
+    pub average_cpu_percent: f64,
+    /// Highest memory use in MB.
+    pub peak_memory_mb: f64,
+    /// Highest memory use, as a percentage of the class's.
+    pub peak_memory_percent: f64,
+    /// What the peaks suggest.
+    pub verdict: Verdict,
+}
+
+/// Returns a class's vCPUs and memory in MB, if it's a known Docker or Arm
+/// class.
+fn class_size(class: &str) -> Option<(f64, u64)> {
+    CLASS_SIZES
+        .iter()
+        .find(|(name, _, _)| *name == class)
+        .map(|(_, cpu, ram)| (*cpu, *ram))
+}
+
+/// Returns the classes of the same family as `class`, smallest first.
+fn family(class: &str) -> impl Iterator<Item = &'static (&'static str, f64, u64)> {
+    let arm = class.starts_with("arm.");
+    CLASS_SIZES
+        .iter()
+        .filter(move |(name, _, _)| name.starts_with("arm.") == arm)
+}
+
+/// Compares a job's resource use with its resource class.
+///
+/// The class and its size come from the build payload, falling back to
+/// the published sizes of Docker and Arm classes.
+///
+/// # Returns
+///
+/// `None` when there are no CPU or memory readings, or the class's size
+/// isn't known.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::resources::{check, UsageSample, Verdict};
+///
+/// let build = serde_json::from_value(serde_json::json!({
+///     "build_num": 1, "status": "success", "branch": null, "subject": null, "steps": [],
+///     "picard": {"executor": "docker", "resource_class": {"class": "large"}}
+/// }))
+/// .unwrap();
+/// let samples: Vec<UsageSample> = [(20.0, 1200.0), (28.0, 1900.0)]
+///     .into_iter()
+///     .map(|(cpu, memory)| UsageSample {
+///         timestamp: None,
+///         cpu_percent: Some(cpu),
+///         memory_mb: Some(memory),
+///     })
+///     .collect();
+/// let check = check(&build, &samples).unwrap();
+/// assert_eq!(check.peak_cpu_percent, 28.0);
+/// assert_eq!(
+///     check.verdict,
+///     Verdict::Underutilized { suggested_class: "medium".to_string() }
+/// );
+/// ```
+pub fn check(build: &BuildInfo, samples: &[UsageSample]) -> Option<ResourceCheck> {
+    let reported = build
+        .picard
+        .as_ref()
+        .and_then(|picard| picard.resource_class.clone())
+        .unwrap_or_default();
+    let class = reported
+        .class
+        .unwrap_or_else(|| cost::DEFAULT_RESOURCE_CLASS.to_string());
+    let known = class_size(&class);
+    let cpu = reported.cpu.or(known.map(|(cpu, _)| cpu))?;
+    let ram_mb = reported.ram.or(known.map(|(_, ram)| ram))?;
+
+    let cpu_readings: Vec<f64> = samples.iter().filter_map(|s| s.cpu_percent).collect();
+    let memory_readings: Vec<f64> = samples.iter().filter_map(|s| s.memory_mb).collect();
+    if cpu_readings.is_empty() && memory_readings.is_empty() {
+        return None;
+    }
+    let peak_cpu_percent = cpu_readings.iter().copied().fold(0.0, f64::max);
+    let average_cpu_percent = if cpu_readings.is_empty() {
+        0.0
+    } else {
+        cpu_readings.iter().sum::<f64>() / cpu_readings.len() as f64
+    };
+    let peak_memory_mb = memory_readings.iter().copied().fold(0.0, f64::max);
+    let peak_memory_percent = peak_memory_mb / ram_mb as f64 * 100.0;
+
+    let saturated = if peak_memory_percent >= MEMORY_SATURATED_PERCENT {
+        Some(Resource::Memory)
+    } else if average_cpu_percent >= CPU_SATURATED_PERCENT {
+        Some(Resource::Cpu)
+    } else {
+        None
+    };
+    let verdict = match saturated {
+        Some(resource) => Verdict::Saturated {
+            resource,
+            suggested_class: known.and_then(|(cpu, ram)| {
+                family(&class)
+                    .find(|(_, c, r)| *c >= cpu && *r >= ram && (*c > cpu || *r > ram))
+                    .map(|(name, _, _)| name.to_string())
+            }),
+        },
+        None => {
+            let needed_cpu = peak_cpu_percent / 100.0 * cpu * HEADROOM;
+            let needed_ram = peak_memory_mb * HEADROOM;
+            let smallest = family(&class)
+                .find(|(_, c, r)| *c >= needed_cpu && *r as f64 >= needed_ram)
+                .filter(|(_, c, r)| *c < cpu && *r <= ram_mb);
+            match (known, smallest) {
+                (Some(_), Some((name, _, _))) => Verdict::Underutilized {
+                    suggested_class: name.to_string(),
+                },
+                _ => Verdict::Fits,
+            }
+        }
+    };
+
+    Some(ResourceCheck {
+        resource_class: class,
+        cpu,
+        ram_mb,
+        peak_cpu_percent,
+        average_cpu_percent,
+        peak_memory_mb,
+        peak_memory_percent,
+        verdict,
+    })
+}
+
+impl CircleClient {
+    /// Fetches the CPU and memory samples CircleCI took while a job ran.
+    ///
+    /// # Returns
+    ///
+    /// `None` when CircleCI has no resource usage for the job, which is
+    /// the case for some executors and plans.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails for another reason or the
+    /// response cannot be parsed.
+    pub async fn resource_usage(
+        &self,
+        org: &str,
+        project: &str,
+        job_number: u32,
+    ) -> Result<Option<Vec<UsageSample>>> {
+        let url = format!(
+            "{}/v2/project/{}/job/{}/resource-usage",
+            self.base_url,
+            crate::project_slug(org, project),
+            job_number
+        );
+        match self.get_json::<UsageResponse>(&url).await {
+            Ok(response) => Ok(Some(response.items)),
+            Err(err)
+                if err
+                    .downcast_ref::<CircleDebugError>()
+                    .is_some_and(CircleDebugError::is_not_found) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err)
+                .with_context(|| format!("Failed to fetch resource usage of #{}", job_number)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(class: serde_json::Value) -> BuildInfo {
+        serde_json::from_value(serde_json::json!({
+            "build_num": 1, "status": "failed", "branch": null, "subject": null, "steps": [],
+            "picard": {"executor": "docker", "resource_class": class}
+        }))
+        .unwrap()
+    }
+
+    fn samples(readings: &[(f64, f64)]) -> Vec<UsageSample> {
+        readings
+            .iter()
+            .map(|(cpu, memory)| UsageSample {
+                timestamp: None,
+                cpu_percent: Some(*cpu),
+                memory_mb: Some(*memory),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_saturation_and_fit() {
+        let medium = build(serde_json::json!({"class": "medium", "cpu": 2.0, "ram": 4096}));
+        let oom = check(&medium, &samples(&[(60.0, 2000.0), (70.0, 3900.0)])).unwrap();
+        assert_eq!(
+            oom.verdict,
+            Verdict::Saturated {
+                resource: Resource::Memory,
+                suggested_class: Some("medium+".to_string()),
+            }
+        );
+
+        let busy = check(&medium, &samples(&[(95.0, 1000.0), (98.0, 1200.0)])).unwrap();
+        assert!(matches!(
+            busy.verdict,
+            Verdict::Saturated {
+                resource: Resource::Cpu,
+                ..
+            }
+        ));
+
+        let fits = check(&medium, &samples(&[(40.0, 1000.0), (75.0, 2500.0)])).unwrap();
+        assert_eq!(fits.verdict, Verdict::Fits);
+
+        // No suggestions for classes without published sizes
+        let mac =
+            build(serde_json::json!({"class": "macos.m1.medium.gen1", "cpu": 4.0, "ram": 6144}));
+        assert_eq!(
+            check(&mac, &samples(&[(5.0, 500.0)])).unwrap().verdict,
+            Verdict::Fits
+        );
+        assert!(check(&medium, &[]).is_none());
+    }
+}
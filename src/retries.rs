@@ -0,0 +1,156 @@
+//! Steps a build ran more than once.
+//!
+//! A step retried with `when: on_fail`, or by an orb's retry command, shows
+//! up in the build as the same step again: another action with the same
+//! name and command on the same container. [`retried_steps`] finds those
+//! repeats and counts the attempts each step needed. A step that failed
+//! before passing on a later attempt is a latent flake: the build may be
+//! green, but the step is one bad attempt from failing it.
+
+use crate::{Action, BuildInfo};
+use serde::{Deserialize, Serialize};
+
+/// A step that ran more than once after failing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetriedStep {
+    /// Step name.
+    pub name: String,
+    /// Parallel container, for steps that run on several.
+    pub container: Option<u32>,
+    /// How many times the step ran.
+    pub attempts: u32,
+    /// How many of the attempts failed.
+    pub failed_attempts: u32,
+    /// Whether the last attempt passed.
+    pub passed: bool,
+    /// Milliseconds spent on the failed attempts.
+    pub wasted_millis: u64,
+}
+
+impl RetriedStep {
+    /// Whether the step failed before passing on a later attempt.
+    pub fn is_flaky(&self) -> bool {
+        self.passed && self.failed_attempts > 0
+    }
+}
+
+/// Finds the steps of `build` that ran again after failing, in the order
+/// they first ran.
+///
+/// Actions are attempts of the same step when they share a name, a
+/// command, and a container. Steps that merely repeat, such as several
+/// "Restoring cache" steps, aren't retries unless one of them failed
+/// before the last.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::retries::retried_steps;
+///
+/// let action = |status: &str| serde_json::json!({
+///     "name": "Run integration tests", "status": status, "failed": status == "failed",
+///     "output_url": null, "type": "test", "bash_command": "npm run test:integration",
+///     "run_time_millis": 60000
+/// });
+/// let build = serde_json::from_value(serde_json::json!({
+///     "build_num": 1, "status": "success", "branch": null, "subject": null,
+///     "steps": [
+///         {"name": "Run integration tests", "actions": [action("failed")]},
+///         {"name": "Run integration tests", "actions": [action("success")]}
+///     ]
+/// }))
+/// .unwrap();
+/// let retried = retried_steps(&build);
+/// assert_eq!(retried[0].attempts, 2);
+/// assert!(retried[0].is_flaky());
+/// ```
+pub fn retried_steps(build: &BuildInfo) -> Vec<RetriedStep> {
+    let parallel = build.steps.iter().any(|step| step.is_parallel());
+    let mut groups: Vec<Vec<&Action>> = Vec::new();
+    for action in build.steps.iter().flat_map(|step| step.actions.iter()) {
+        match groups.iter_mut().find(|group| same_step(group[0], action)) {
+            Some(group) => group.push(action),
+            None => groups.push(vec![action]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|attempts| {
+            attempts.len() > 1
+                && attempts[..attempts.len() - 1]
+                    .iter()
+                    .any(|action| action.is_failed())
+        })
+        .map(|attempts| {
+            let failed: Vec<&&Action> = attempts.iter().filter(|a| a.is_failed()).collect();
+            RetriedStep {
+                name: attempts[0].name.clone(),
+                container: parallel.then(|| attempts[0].container()),
+                attempts: attempts.len() as u32,
+                failed_attempts: failed.len() as u32,
+                passed: attempts.last().is_some_and(|action| !action.is_failed()),
+                wasted_millis: failed.iter().filter_map(|a| a.run_time_millis).sum(),
+            }
+        })
+        .collect()
+}
+
+/// Whether two actions are attempts of the same step.
+fn same_step(a: &Action, b: &Action) -> bool {
+    a.name == b.name && a.bash_command == b.bash_command && a.container() == b.container()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, StepBuilder};
+
+    fn step(name: &str, command: &str, failed: bool) -> StepBuilder {
+        let action = ActionBuilder::new(name)
+            .bash_command(command)
+            .run_time_millis(30_000);
+        StepBuilder::new(name).action(if failed { action.failed() } else { action })
+    }
+
+    #[test]
+    fn test_retried_steps() {
+        let build = BuildBuilder::new(1)
+            .step(step("Restoring cache", "", false))
+            .step(step("Install", "npm ci", true))
+            .step(step("Install", "npm ci", true))
+            .step(step("Install", "npm ci", false))
+            .step(step("Restoring cache", "", false))
+            .step(step("Deploy", "./deploy.sh", true))
+            .step(step("Deploy", "./deploy.sh", true))
+            // Same name, different command: not a retry
+            .step(step("Run tests", "npm test", true))
+            .step(step("Run tests", "npm run e2e", false))
+            .build();
+
+        let retried = retried_steps(&build);
+        assert_eq!(
+            retried,
+            vec![
+                RetriedStep {
+                    name: "Install".to_string(),
+                    container: None,
+                    attempts: 3,
+                    failed_attempts: 2,
+                    passed: true,
+                    wasted_millis: 60_000,
+                },
+                RetriedStep {
+                    name: "Deploy".to_string(),
+                    container: None,
+                    attempts: 2,
+                    failed_attempts: 2,
+                    passed: false,
+                    wasted_millis: 60_000,
+                },
+            ]
+        );
+        assert!(retried[0].is_flaky());
+        assert!(!retried[1].is_flaky());
+    }
+}
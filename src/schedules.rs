@@ -0,0 +1,414 @@
+//! A project's scheduled pipelines.
+//!
+//! [`CircleClient::schedules`] lists the schedules set up under Project
+//! Settings → Triggers, with when they run, on which branch, and with
+//! which pipeline parameters; [`CircleClient::last_scheduled_runs`] finds
+//! the latest pipeline each one started. CircleCI has no "run now" for a
+//! schedule, so [`Schedule::trigger_parameters`] gives what a manual
+//! trigger needs to run the same pipeline, e.g. to rerun a nightly after a
+//! fix.
+
+use crate::resolve::{Pipeline, PipelineWorkflow};
+use crate::workflow::Page;
+use crate::{project_slug, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Most recent pipelines looked through for the schedules' last runs.
+pub const MAX_PIPELINES: usize = 200;
+
+/// When a schedule runs, as the v2 API describes it. Times are UTC.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Timetable {
+    /// Runs per hour, spread over the hour.
+    #[serde(default)]
+    pub per_hour: Option<u32>,
+    /// Hours of the day it runs in.
+    #[serde(default)]
+    pub hours_of_day: Vec<u32>,
+    /// Days of the week, e.g. "MON".
+    #[serde(default)]
+    pub days_of_week: Vec<String>,
+    /// Days of the month, as an alternative to days of the week.
+    #[serde(default)]
+    pub days_of_month: Vec<u32>,
+    /// Months, e.g. "JAN"; all when empty.
+    #[serde(default)]
+    pub months: Vec<String>,
+}
+
+impl Timetable {
+    /// Describes the timetable, e.g. "02:00 UTC, MON TUE WED THU FRI".
+    ///
+    /// CircleCI picks the minute within each hour, so hours show as `:00`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::schedules::Timetable;
+    ///
+    /// let nightly = Timetable {
+    ///     per_hour: Some(1),
+    ///     hours_of_day: vec![2],
+    ///     days_of_week: ["MON", "TUE", "WED", "THU", "FRI"].map(String::from).to_vec(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(nightly.describe(), "02:00 UTC, MON TUE WED THU FRI");
+    /// ```
+    pub fn describe(&self) -> String {
+        let mut hours: Vec<u32> = self.hours_of_day.clone();
+        hours.sort_unstable();
+        let hours = if hours.len() == 24 {
+            "every hour".to_string()
+        } else {
+            let hours: Vec<String> = hours.iter().map(|h| format!("{:02}:00", h)).collect();
+            format!("{} UTC", hours.join(", "))
+        };
+        let per_hour = match self.per_hour {
+            Some(n) if n > 1 => format!("{}× an hour, ", n),
+            _ => String::new(),
+        };
+        let days = if !self.days_of_month.is_empty() {
+            let days: Vec<String> = self.days_of_month.iter().map(u32::to_string).collect();
+            format!("day {} of the month", days.join(", "))
+        } else if self.days_of_week.is_empty() || self.days_of_week.len() == 7 {
+            "every day".to_string()
+        } else {
+            self.days_of_week.join(" ")
+        };
+        let months = if self.months.is_empty() || self.months.len() == 12 {
+            String::new()
+        } else {
+            format!(" in {}", self.months.join(" "))
+        };
+        format!("{}{}, {}{}", per_hour, hours, days, months)
+    }
+}
+
+/// Who a schedule's pipelines run as.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleActor {
+    /// Login of the user, or of the "Scheduling System" actor.
+    pub login: String,
+}
+
+/// A scheduled pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Schedule {
+    /// UUID of the schedule.
+    pub id: String,
+    /// Schedule name.
+    pub name: String,
+    /// Description, if one was given.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// When it runs.
+    #[serde(default)]
+    pub timetable: Timetable,
+    /// Pipeline parameters it triggers with, including `branch` or `tag`.
+    #[serde(default)]
+    pub parameters: Map<String, Value>,
+    /// Who its pipelines run as.
+    #[serde(default)]
+    pub actor: Option<ScheduleActor>,
+    /// When the schedule was created.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the schedule was last changed.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Schedule {
+    /// Returns the branch the schedule runs on, if it names one.
+    pub fn branch(&self) -> Option<&str> {
+        self.parameters.get("branch").and_then(Value::as_str)
+    }
+
+    /// Returns the pipeline parameters of the schedule, without `branch`
+    /// and `tag`, which a trigger sets separately.
+    pub fn trigger_parameters(&self) -> Map<String, Value> {
+        self.parameters
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "branch" | "tag"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// The latest pipeline a schedule started.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LastRun {
+    /// Pipeline number.
+    pub pipeline_number: u32,
+    /// When the pipeline was created.
+    pub created_at: Option<DateTime<Utc>>,
+    /// "failed" if a workflow failed, "running" while one runs, "success"
+    /// when all passed, otherwise the first workflow's status.
+    pub status: Option<String>,
+}
+
+/// Sums up the status of a pipeline from its workflows.
+fn pipeline_status(workflows: &[PipelineWorkflow]) -> Option<String> {
+    let any = |statuses: &[&str]| {
+        workflows
+            .iter()
+            .any(|workflow| statuses.contains(&workflow.status.as_str()))
+    };
+    if any(&["failed", "error", "failing", "unauthorized"]) {
+        Some("failed".to_string())
+    } else if any(&["running", "on_hold", "not_run"]) {
+        Some("running".to_string())
+    } else if !workflows.is_empty() && workflows.iter().all(|w| w.status == "success") {
+        Some("success".to_string())
+    } else {
+        workflows.first().map(|workflow| workflow.status.clone())
+    }
+}
+
+/// Whether `pipeline` was started by `schedule`, out of `schedules`.
+///
+/// A scheduled pipeline whose trigger parameters mention a schedule's id
+/// or name belongs to it. Otherwise it's matched by branch, when only one
+/// schedule runs on that branch.
+fn started_by(pipeline: &Pipeline, schedule: &Schedule, schedules: &[Schedule]) -> bool {
+    if !pipeline.is_scheduled() {
+        return false;
+    }
+    let mentioned = |s: &Schedule| {
+        pipeline.trigger_parameters.as_ref().is_some_and(|params| {
+            let params = params.to_string();
+            params.contains(&format!("\"{}\"", s.id)) || params.contains(&format!("\"{}\"", s.name))
+        })
+    };
+    if schedules.iter().any(mentioned) {
+        return mentioned(schedule);
+    }
+    let branch = pipeline.vcs.as_ref().and_then(|vcs| vcs.branch.as_deref());
+    branch.is_some()
+        && schedule.branch() == branch
+        && schedules.iter().filter(|s| s.branch() == branch).count() == 1
+}
+
+impl CircleClient {
+    /// Lists a project's scheduled pipelines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails, e.g. for a project without
+    /// access to scheduled pipelines.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// for schedule in client.schedules("myorg", "myrepo").await? {
+    ///     println!("{}: {}", schedule.name, schedule.timetable.describe());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn schedules(&self, org: &str, project: &str) -> Result<Vec<Schedule>> {
+        let base = format!(
+            "{}/v2/project/{}/schedule",
+            self.base_url,
+            project_slug(org, project)
+        );
+        let mut schedules = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<Schedule> = self
+                .get_json(url.as_str())
+                .await
+                .with_context(|| format!("Failed to list the schedules of {}/{}", org, project))?;
+            schedules.extend(page.items);
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(schedules)
+    }
+
+    /// Finds the latest pipeline each of `schedules` started, among the
+    /// project's [`MAX_PIPELINES`] most recent pipelines, in the same
+    /// order as `schedules`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipelines can't be listed. A last run whose
+    /// workflows can't be listed has no status.
+    pub async fn last_scheduled_runs(
+        &self,
+        org: &str,
+        project: &str,
+        schedules: &[Schedule],
+    ) -> Result<Vec<Option<LastRun>>> {
+        let base = format!(
+            "{}/v2/project/{}/pipeline",
+            self.base_url,
+            project_slug(org, project)
+        );
+        let mut latest: Vec<Option<Pipeline>> = vec![None; schedules.len()];
+        let mut seen = 0;
+        let mut page_token: Option<String> = None;
+
+        'pages: loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<Pipeline> = self
+                .get_json(url.as_str())
+                .await
+                .with_context(|| format!("Failed to list the pipelines of {}/{}", org, project))?;
+            for pipeline in page.items {
+                seen += 1;
+                if seen > MAX_PIPELINES {
+                    break 'pages;
+                }
+                if let Some(i) = (0..schedules.len()).find(|&i| {
+                    latest[i].is_none() && started_by(&pipeline, &schedules[i], schedules)
+                }) {
+                    latest[i] = Some(pipeline);
+                }
+                if latest.iter().all(Option::is_some) {
+                    break 'pages;
+                }
+            }
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        let workflows = futures::future::join_all(latest.iter().map(|pipeline| async move {
+            match pipeline {
+                Some(pipeline) => self.get_pipeline_workflows(&pipeline.id).await.ok(),
+                None => None,
+            }
+        }))
+        .await;
+        Ok(latest
+            .into_iter()
+            .zip(workflows)
+            .map(|(pipeline, workflows)| {
+                pipeline.map(|pipeline| LastRun {
+                    pipeline_number: pipeline.number,
+                    created_at: pipeline.created_at,
+                    status: workflows.as_deref().and_then(pipeline_status),
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeCircleCi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_schedules_and_last_runs() {
+        let server = FakeCircleCi::start().await;
+        let mount = |route: &str, body: Value| {
+            Mock::given(method("GET"))
+                .and(path(format!("/api{}", route)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        };
+        let schedule = |id: &str, name: &str, branch: &str| {
+            serde_json::json!({
+                "id": id, "name": name, "description": null,
+                "timetable": {"per-hour": 1, "hours-of-day": [2], "days-of-week": ["MON", "TUE"]},
+                "parameters": {"branch": branch, "run-e2e": true},
+                "actor": {"login": "Scheduling System"},
+                "project-slug": "gh/org/repo"
+            })
+        };
+        let pipeline = |id: &str, number: u32, branch: &str, trigger: &str| {
+            serde_json::json!({
+                "id": id, "number": number, "state": "created",
+                "created_at": "2024-05-14T02:00:00Z",
+                "vcs": {"branch": branch, "revision": "abc123"},
+                "trigger": {"type": trigger}
+            })
+        };
+        for mock in [
+            mount(
+                "/v2/project/gh/org/repo/schedule",
+                serde_json::json!({
+                    "items": [
+                        schedule("s-1", "nightly", "main"),
+                        schedule("s-2", "weekly-release", "release")
+                    ],
+                    "next_page_token": null
+                }),
+            ),
+            mount(
+                "/v2/project/gh/org/repo/pipeline",
+                serde_json::json!({
+                    "items": [
+                        pipeline("p-9", 9, "main", "webhook"),
+                        pipeline("p-8", 8, "main", "scheduled_pipeline"),
+                        pipeline("p-7", 7, "main", "scheduled_pipeline")
+                    ],
+                    "next_page_token": null
+                }),
+            ),
+            mount(
+                "/v2/pipeline/p-8/workflow",
+                serde_json::json!({
+                    "items": [
+                        {"id": "w-1", "name": "nightly", "status": "failed",
+                         "pipeline_id": "p-8", "pipeline_number": 8},
+                        {"id": "w-2", "name": "lint", "status": "success",
+                         "pipeline_id": "p-8", "pipeline_number": 8}
+                    ],
+                    "next_page_token": null
+                }),
+            ),
+        ] {
+            mock.mount(server.server()).await;
+        }
+        let client = server.client();
+
+        let schedules = client.schedules("org", "repo").await.unwrap();
+        assert_eq!(schedules.len(), 2);
+        assert_eq!(schedules[0].branch(), Some("main"));
+        assert_eq!(schedules[0].trigger_parameters()["run-e2e"], true);
+        assert!(!schedules[0].trigger_parameters().contains_key("branch"));
+        assert_eq!(schedules[0].timetable.describe(), "02:00 UTC, MON TUE");
+
+        let runs = client
+            .last_scheduled_runs("org", "repo", &schedules)
+            .await
+            .unwrap();
+        assert_eq!(
+            runs[0],
+            Some(LastRun {
+                pipeline_number: 8,
+                created_at: Some("2024-05-14T02:00:00Z".parse().unwrap()),
+                status: Some("failed".to_string()),
+            })
+        );
+        assert_eq!(runs[1], None);
+    }
+}
@@ -0,0 +1,666 @@
+//! Searching logs across builds.
+//!
+//! [`CircleClient::search_builds`] runs a regex over the step logs of a
+//! project's recent builds to answer questions like "when did this warning
+//! first appear?". Logs already in the response [`cache`](crate::cache)
+//! are searched without downloading them; others are fetched only when
+//! asked to, since a project's logs can add up to gigabytes.
+//!
+//! [`search_cache`] instead searches every cached log of every project,
+//! entirely offline.
+
+use crate::cache::ResponseCache;
+use crate::download::read_cached_log;
+use crate::history::BuildQuery;
+use crate::{Action, BuildInfo, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// Options for [`CircleClient::search_builds`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Number of most recent builds to search.
+    pub builds: u32,
+    /// Only search builds of this branch.
+    pub branch: Option<String>,
+    /// Download logs that aren't cached instead of skipping them.
+    pub fetch: bool,
+    /// Lines of context to keep before and after each match.
+    pub context: usize,
+    /// Stop collecting matches in a log after this many.
+    pub max_matches_per_log: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            builds: 20,
+            branch: None,
+            fetch: false,
+            context: 2,
+            max_matches_per_log: 10,
+        }
+    }
+}
+
+/// A matching log line and the lines around it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogMatch {
+    /// 1-based line number within the log.
+    pub line_number: usize,
+    /// The matching line.
+    pub line: String,
+    /// Lines just before the match, in order.
+    pub before: Vec<String>,
+    /// Lines just after the match, in order.
+    pub after: Vec<String>,
+}
+
+/// Matches within one action's log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchHit {
+    /// Project the build belongs to, as `org/project`.
+    #[serde(default)]
+    pub project: String,
+    /// Build the log belongs to.
+    pub build_num: u32,
+    /// Status of the build.
+    pub status: String,
+    /// Branch of the build.
+    pub branch: Option<String>,
+    /// When the build started.
+    pub start_time: Option<DateTime<Utc>>,
+    /// Step the action belongs to.
+    pub step: String,
+    /// Parallel container index.
+    pub container: Option<u32>,
+    /// Number of matching lines, including ones beyond the kept matches.
+    pub total_matches: usize,
+    /// The first matches, with context.
+    pub matches: Vec<LogMatch>,
+}
+
+/// Result of a search across builds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchResults {
+    /// Logs with matches, newest build first.
+    pub hits: Vec<SearchHit>,
+    /// Number of builds whose logs were looked at.
+    pub builds_searched: usize,
+    /// Number of logs searched.
+    pub logs_searched: usize,
+    /// Number of logs skipped because they weren't cached.
+    pub logs_not_cached: usize,
+    /// Number of logs that failed to load.
+    pub logs_failed: usize,
+}
+
+impl SearchResults {
+    /// Returns the oldest build with a match, i.e. where the text first
+    /// appeared among the searched builds.
+    pub fn first_seen(&self) -> Option<&SearchHit> {
+        self.hits.iter().min_by_key(|hit| hit.build_num)
+    }
+
+    /// Returns the number of distinct builds with a match.
+    pub fn matching_builds(&self) -> usize {
+        let mut builds: Vec<(&str, u32)> = self
+            .hits
+            .iter()
+            .map(|hit| (hit.project.as_str(), hit.build_num))
+            .collect();
+        builds.dedup();
+        builds.len()
+    }
+}
+
+fn ansi_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap())
+}
+
+/// Removes ANSI escape codes, which would otherwise split matches.
+pub(crate) fn strip_ansi(text: &str) -> Cow<'_, str> {
+    ansi_regex().replace_all(text, "")
+}
+
+/// Finds the lines of `log` matching `regex`, with `context` lines around
+/// each.
+///
+/// ANSI escape codes are removed before matching. Returns the total
+/// number of matching lines and the first `max_matches` of them.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::search::search_log;
+/// use regex::Regex;
+///
+/// let log = "compiling\nwarning: unused variable `x`\n  --> src/lib.rs:3:9\ndone";
+/// let (total, matches) = search_log(log, &Regex::new("unused variable").unwrap(), 1, 10);
+///
+/// assert_eq!(total, 1);
+/// assert_eq!(matches[0].line_number, 2);
+/// assert_eq!(matches[0].before, vec!["compiling"]);
+/// assert_eq!(matches[0].after, vec!["  --> src/lib.rs:3:9"]);
+/// ```
+pub fn search_log(
+    log: &str,
+    regex: &Regex,
+    context: usize,
+    max_matches: usize,
+) -> (usize, Vec<LogMatch>) {
+    let clean = strip_ansi(log);
+    let lines: Vec<&str> = clean.lines().collect();
+    let mut total = 0;
+    let mut matches = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if !regex.is_match(line) {
+            continue;
+        }
+        total += 1;
+        if matches.len() < max_matches {
+            let to_owned = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect();
+            matches.push(LogMatch {
+                line_number: i + 1,
+                line: line.to_string(),
+                before: to_owned(&lines[i.saturating_sub(context)..i]),
+                after: to_owned(&lines[i + 1..(i + 1 + context).min(lines.len())]),
+            });
+        }
+    }
+    (total, matches)
+}
+
+impl CircleClient {
+    /// Searches the step logs of a project's most recent builds.
+    ///
+    /// Every action with output is searched, not just failed ones, so
+    /// warnings in passing builds are found too. Logs that aren't in the
+    /// response cache are skipped unless [`SearchOptions::fetch`] is set;
+    /// builds or logs that fail to load are counted and skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recent builds can't be listed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::search::SearchOptions;
+    /// use circle_debug::CircleClient;
+    /// use regex::Regex;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let regex = Regex::new("DeprecationWarning")?;
+    /// let results = client
+    ///     .search_builds("myorg", "myrepo", &regex, &SearchOptions::default())
+    ///     .await?;
+    /// if let Some(first) = results.first_seen() {
+    ///     println!("first seen in #{}", first.build_num);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn search_builds(
+        &self,
+        org: &str,
+        project: &str,
+        regex: &Regex,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
+        let query = BuildQuery {
+            branch: options.branch.clone(),
+            limit: options.builds,
+            ..Default::default()
+        };
+        let listed = self
+            .recent_builds(org, project, &query)
+            .await
+            .context("Failed to list recent builds")?;
+        let builds = futures::future::join_all(
+            listed
+                .iter()
+                .map(|build| self.get_build(org, project, build.build_num)),
+        )
+        .await;
+
+        let mut results = SearchResults {
+            hits: Vec::new(),
+            builds_searched: 0,
+            logs_searched: 0,
+            logs_not_cached: 0,
+            logs_failed: 0,
+        };
+        for build in builds {
+            let Ok(build) = build else {
+                continue;
+            };
+            self.search_build_logs(org, project, &build, regex, options, &mut results)
+                .await;
+        }
+
+        Ok(results)
+    }
+
+    /// Searches the logs of every step of one build, fetching the ones
+    /// that aren't cached and caching them.
+    ///
+    /// Of `options`, `builds`, `branch`, and `fetch` don't apply. Hits are
+    /// in step order, one per action with a match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the build can't be fetched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::search::SearchOptions;
+    /// use circle_debug::CircleClient;
+    /// use regex::Regex;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let regex = Regex::new(r"ECONNRESET|ETIMEDOUT")?;
+    /// let results = client
+    ///     .grep_build("myorg", "myrepo", 12345, &regex, &SearchOptions::default())
+    ///     .await?;
+    /// for hit in &results.hits {
+    ///     println!("{}: {} match(es)", hit.step, hit.total_matches);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn grep_build(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+        regex: &Regex,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
+        let build = self.get_build(org, project, build_num).await?;
+        let options = SearchOptions {
+            fetch: true,
+            ..options.clone()
+        };
+        let mut results = SearchResults {
+            hits: Vec::new(),
+            builds_searched: 0,
+            logs_searched: 0,
+            logs_not_cached: 0,
+            logs_failed: 0,
+        };
+        self.search_build_logs(org, project, &build, regex, &options, &mut results)
+            .await;
+        Ok(results)
+    }
+
+    /// Searches the logs of every action of `build`, adding to `results`.
+    ///
+    /// Logs are loaded concurrently: from the cache, or downloaded when
+    /// `options.fetch` is set.
+    async fn search_build_logs(
+        &self,
+        org: &str,
+        project: &str,
+        build: &BuildInfo,
+        regex: &Regex,
+        options: &SearchOptions,
+        results: &mut SearchResults,
+    ) {
+        results.builds_searched += 1;
+        let actions: Vec<(&str, &Action, &str)> = build
+            .steps
+            .iter()
+            .flat_map(|step| step.actions.iter().map(move |action| (step, action)))
+            .filter_map(|(step, action)| {
+                Some((step.name.as_str(), action, action.output_url.as_deref()?))
+            })
+            .collect();
+        let logs = futures::future::join_all(actions.iter().map(|(_, _, url)| async move {
+            match self.cached_logs(url) {
+                Ok(Some(log)) => Some(Ok(log)),
+                Ok(None) if options.fetch => Some(self.get_logs(url).await),
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }))
+        .await;
+
+        for ((step, action, _), log) in actions.iter().zip(logs) {
+            let log = match log {
+                Some(Ok(log)) => log,
+                Some(Err(_)) => {
+                    results.logs_failed += 1;
+                    continue;
+                }
+                None => {
+                    results.logs_not_cached += 1;
+                    continue;
+                }
+            };
+
+            results.logs_searched += 1;
+            let (total, matches) =
+                search_log(&log, regex, options.context, options.max_matches_per_log);
+            if total > 0 {
+                results.hits.push(SearchHit {
+                    project: format!("{}/{}", org, project),
+                    build_num: build.build_num,
+                    status: build.status.clone(),
+                    branch: build.branch.clone(),
+                    start_time: build.start_time,
+                    step: step.to_string(),
+                    container: action.index,
+                    total_matches: total,
+                    matches,
+                });
+            }
+        }
+    }
+}
+
+fn build_url_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"/v1\.1/project/(?:github|circleci)/([^/]+)/([^/]+)/(\d+)$").unwrap()
+    })
+}
+
+/// Returns the finished builds in `cache` with their org and project.
+pub(crate) fn cached_builds(cache: &ResponseCache) -> Vec<(String, String, BuildInfo)> {
+    let mut builds: Vec<_> = cache
+        .entries()
+        .into_iter()
+        .filter(|meta| meta.immutable)
+        .filter_map(|meta| {
+            let caps = build_url_regex().captures(&meta.url)?;
+            let body = cache.get(&meta.url)?.body;
+            let build: BuildInfo = serde_json::from_slice(&body).ok()?;
+            Some((caps[1].to_string(), caps[2].to_string(), build))
+        })
+        .collect();
+    builds.sort_by(|a, b| (&a.0, &a.1, a.2.build_num).cmp(&(&b.0, &b.1, b.2.build_num)));
+    builds
+}
+
+/// Searches the step logs of every finished build in `cache`, without a
+/// request.
+///
+/// `repo` narrows the search to one `(org, project)`. `options.builds`
+/// caps how many of the most recent cached builds are searched, and
+/// `options.fetch` is ignored: a log that isn't cached is counted in
+/// [`SearchResults::logs_not_cached`]. Hits are ordered newest build first.
+///
+/// # Examples
+///
+/// ```no_run
+/// use circle_debug::cache::ResponseCache;
+/// use circle_debug::search::{search_cache, SearchOptions};
+/// use regex::Regex;
+///
+/// let cache = ResponseCache::new(ResponseCache::default_dir());
+/// let regex = Regex::new("ECONNRESET").unwrap();
+/// let results = search_cache(&cache, &regex, &SearchOptions::default(), None);
+/// for hit in &results.hits {
+///     println!("{} #{}: {}", hit.project, hit.build_num, hit.step);
+/// }
+/// ```
+pub fn search_cache(
+    cache: &ResponseCache,
+    regex: &Regex,
+    options: &SearchOptions,
+    repo: Option<(&str, &str)>,
+) -> SearchResults {
+    let mut builds: Vec<_> = cached_builds(cache)
+        .into_iter()
+        .filter(|(org, project, build)| {
+            repo.is_none_or(|(o, p)| o.eq_ignore_ascii_case(org) && p.eq_ignore_ascii_case(project))
+                && options
+                    .branch
+                    .as_ref()
+                    .is_none_or(|branch| build.branch.as_ref() == Some(branch))
+        })
+        .collect();
+    builds.sort_by_key(|(_, _, build)| std::cmp::Reverse((build.start_time, build.build_num)));
+    builds.truncate(options.builds as usize);
+
+    let mut results = SearchResults {
+        hits: Vec::new(),
+        builds_searched: builds.len(),
+        logs_searched: 0,
+        logs_not_cached: 0,
+        logs_failed: 0,
+    };
+    for (org, project, build) in &builds {
+        for step in &build.steps {
+            for action in &step.actions {
+                let Some(url) = action.output_url.as_deref() else {
+                    continue;
+                };
+                let log = match read_cached_log(cache, url) {
+                    Ok(Some(log)) => log,
+                    Ok(None) => {
+                        results.logs_not_cached += 1;
+                        continue;
+                    }
+                    Err(_) => {
+                        results.logs_failed += 1;
+                        continue;
+                    }
+                };
+
+                results.logs_searched += 1;
+                let (total, matches) =
+                    search_log(&log, regex, options.context, options.max_matches_per_log);
+                if total > 0 {
+                    results.hits.push(SearchHit {
+                        project: format!("{}/{}", org, project),
+                        build_num: build.build_num,
+                        status: build.status.clone(),
+                        branch: build.branch.clone(),
+                        start_time: build.start_time,
+                        step: step.name.clone(),
+                        container: action.index,
+                        total_matches: total,
+                        matches,
+                    });
+                }
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, FakeCircleCi, StepBuilder};
+    use reqwest::header::HeaderMap;
+
+    #[test]
+    fn test_search_log_context_and_limit() {
+        let log = "\x1b[33mwarn\x1b[0m a\nok\nwarn b\nwarn c";
+        let regex = Regex::new("^warn").unwrap();
+        let (total, matches) = search_log(log, &regex, 1, 2);
+
+        assert_eq!(total, 3);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line, "warn a");
+        assert!(matches[0].before.is_empty());
+        assert_eq!(matches[1].before, vec!["ok"]);
+        assert_eq!(matches[1].after, vec!["warn c"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_builds_fetches_when_asked() {
+        let server = FakeCircleCi::start().await;
+        let old_log = server.mount_log("old", "all good\n").await;
+        let new_log = server
+            .mount_log("new", "DeprecationWarning: Buffer()\n")
+            .await;
+        let builds = [
+            BuildBuilder::new(2)
+                .step(
+                    StepBuilder::new("test").action(ActionBuilder::new("test").output_url(new_log)),
+                )
+                .build(),
+            BuildBuilder::new(1)
+                .step(
+                    StepBuilder::new("test").action(ActionBuilder::new("test").output_url(old_log)),
+                )
+                .build(),
+        ];
+        for build in &builds {
+            server.mount_build("org", "repo", build).await;
+        }
+        server.mount_builds("org", "repo", &builds).await;
+
+        let client = server.client();
+        let regex = Regex::new("DeprecationWarning").unwrap();
+        let cached_only = client
+            .search_builds("org", "repo", &regex, &SearchOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(cached_only.builds_searched, 2);
+        assert_eq!(cached_only.logs_not_cached, 2);
+        assert!(cached_only.hits.is_empty());
+
+        let options = SearchOptions {
+            fetch: true,
+            ..Default::default()
+        };
+        let results = client
+            .search_builds("org", "repo", &regex, &options)
+            .await
+            .unwrap();
+        assert_eq!(results.logs_searched, 2);
+        assert_eq!(results.matching_builds(), 1);
+        assert_eq!(results.first_seen().unwrap().build_num, 2);
+        assert_eq!(results.hits[0].step, "test");
+    }
+
+    #[tokio::test]
+    async fn test_grep_build_attributes_matches_to_steps() {
+        let server = FakeCircleCi::start().await;
+        let install = server.mount_log("install", "added 812 packages\n").await;
+        let test = server
+            .mount_log(
+                "test",
+                "ok 1\nError: read ECONNRESET\nok 2\nerror: read econnreset\n",
+            )
+            .await;
+        let build = BuildBuilder::new(42)
+            .step(
+                StepBuilder::new("npm ci").action(ActionBuilder::new("npm ci").output_url(install)),
+            )
+            .step(
+                StepBuilder::new("npm test")
+                    .action(ActionBuilder::new("npm test").output_url(test)),
+            )
+            .build();
+        server.mount_build("org", "repo", &build).await;
+
+        let regex = regex::RegexBuilder::new("econnreset")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let options = SearchOptions {
+            context: 1,
+            max_matches_per_log: 1,
+            ..Default::default()
+        };
+        let results = server
+            .client()
+            .grep_build("org", "repo", 42, &regex, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(results.logs_searched, 2);
+        assert_eq!(results.hits.len(), 1);
+        let hit = &results.hits[0];
+        assert_eq!((hit.build_num, hit.step.as_str()), (42, "npm test"));
+        assert_eq!(hit.total_matches, 2);
+        assert_eq!(hit.matches.len(), 1);
+        assert_eq!(hit.matches[0].line_number, 2);
+        assert_eq!(hit.matches[0].before, vec!["ok 1"]);
+    }
+
+    #[test]
+    fn test_search_cache_offline_across_projects() {
+        let dir = std::env::temp_dir().join(format!("cdb-search-cache-{}", std::process::id()));
+        let cache = ResponseCache::new(dir);
+        cache.clear().unwrap();
+        let put_build = |org: &str, project: &str, build: &BuildInfo| {
+            let url = format!(
+                "https://circleci.com/api/v1.1/project/github/{}/{}/{}",
+                org, project, build.build_num
+            );
+            let body = serde_json::to_vec(build).unwrap();
+            cache.put(&url, &HeaderMap::new(), &body, true);
+        };
+        let with_log = |num: u32, step: &str, url: &str| {
+            BuildBuilder::new(num)
+                .step(StepBuilder::new(step).action(ActionBuilder::new(step).output_url(url)))
+                .build()
+        };
+
+        cache.put(
+            "https://logs.example.com/api",
+            &HeaderMap::new(),
+            br#"[{"message": "Error: read ECONNRESET\n"}]"#,
+            true,
+        );
+        cache.put(
+            "https://logs.example.com/web",
+            &HeaderMap::new(),
+            b"all good\n",
+            true,
+        );
+        put_build(
+            "org",
+            "api",
+            &with_log(7, "integration", "https://logs.example.com/api"),
+        );
+        put_build(
+            "org",
+            "web",
+            &with_log(3, "test", "https://logs.example.com/web"),
+        );
+        put_build(
+            "org",
+            "web",
+            &with_log(4, "test", "https://logs.example.com/missing"),
+        );
+
+        let regex = Regex::new("ECONNRESET").unwrap();
+        let results = search_cache(&cache, &regex, &SearchOptions::default(), None);
+        assert_eq!(results.builds_searched, 3);
+        assert_eq!(results.logs_searched, 2);
+        assert_eq!(results.logs_not_cached, 1);
+        assert_eq!(results.hits.len(), 1);
+        assert_eq!(results.hits[0].project, "org/api");
+        assert_eq!(results.hits[0].build_num, 7);
+        assert_eq!(results.hits[0].step, "integration");
+
+        let web_only = search_cache(
+            &cache,
+            &regex,
+            &SearchOptions::default(),
+            Some(("org", "web")),
+        );
+        assert_eq!(web_only.builds_searched, 2);
+        assert!(web_only.hits.is_empty());
+        cache.clear().unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+//! Artifact sizes across recent builds, for `cdb size`.
+//!
+//! Tracks how big a bundle or binary stored with `store_artifacts` was in
+//! each of a project's recent builds, and which builds made it jump.
+
+use crate::history::{BuildFilter, BuildQuery};
+use crate::CircleClient;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Which builds to look at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeQuery {
+    /// Gitignore-style glob of the artifact, e.g. `dist/app.js`; the
+    /// sizes of several matching files are summed.
+    pub artifact: String,
+    /// Number of most recent finished builds to look through.
+    pub builds: u32,
+    /// Only builds of this branch.
+    pub branch: Option<String>,
+    /// Only builds of this workflow job.
+    pub job: Option<String>,
+}
+
+/// The artifact's size in one build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SizePoint {
+    /// Build number.
+    pub build_num: u32,
+    /// Branch the build ran on.
+    pub branch: Option<String>,
+    /// Abbreviated commit SHA.
+    pub revision: Option<String>,
+    /// When the build started.
+    pub start_time: Option<DateTime<Utc>>,
+    /// Total size in bytes of the matching artifacts.
+    pub bytes: u64,
+    /// Number of matching artifacts.
+    pub files: usize,
+}
+
+/// A build in which the artifact grew by more than the threshold.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SizeJump {
+    /// The build where it grew.
+    pub build_num: u32,
+    /// The build before it that had the artifact.
+    pub previous_build: u32,
+    /// Size in the previous build.
+    pub from: u64,
+    /// Size in this build.
+    pub to: u64,
+    /// Growth in percent.
+    pub percent: f64,
+}
+
+/// Returns the builds in which the size grew by more than
+/// `threshold_percent` over the build before.
+///
+/// `points` must be oldest first, as [`CircleClient::artifact_sizes`]
+/// returns them.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::size::{size_jumps, SizePoint};
+///
+/// let point = |build_num, bytes| SizePoint {
+///     build_num,
+///     branch: None,
+///     revision: None,
+///     start_time: None,
+///     bytes,
+///     files: 1,
+/// };
+/// let jumps = size_jumps(&[point(1, 100), point(2, 104), point(3, 130)], 10.0);
+/// assert_eq!(jumps.len(), 1);
+/// assert_eq!((jumps[0].build_num, jumps[0].percent), (3, 25.0));
+/// ```
+pub fn size_jumps(points: &[SizePoint], threshold_percent: f64) -> Vec<SizeJump> {
+    points
+        .windows(2)
+        .filter(|pair| pair[0].bytes > 0)
+        .map(|pair| SizeJump {
+            build_num: pair[1].build_num,
+            previous_build: pair[0].build_num,
+            from: pair[0].bytes,
+            to: pair[1].bytes,
+            percent: (pair[1].bytes as f64 - pair[0].bytes as f64) * 100.0 / pair[0].bytes as f64,
+        })
+        .filter(|jump| jump.percent > threshold_percent)
+        .collect()
+}
+
+impl CircleClient {
+    /// Looks up an artifact's size in each of a project's recent finished
+    /// builds.
+    ///
+    /// Builds that didn't store a matching artifact, such as other jobs of
+    /// the workflow, are left out.
+    ///
+    /// # Returns
+    ///
+    /// One [`SizePoint`] per build that has the artifact, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the glob is invalid or the builds cannot be
+    /// listed. Builds whose artifacts can't be listed are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::size::SizeQuery;
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let query = SizeQuery {
+    ///     artifact: "dist/app.js".to_string(),
+    ///     builds: 20,
+    ///     branch: Some("main".to_string()),
+    ///     job: None,
+    /// };
+    /// for point in client.artifact_sizes("myorg", "myrepo", &query).await? {
+    ///     println!("#{} {} bytes", point.build_num, point.bytes);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn artifact_sizes(
+        &self,
+        org: &str,
+        project: &str,
+        query: &SizeQuery,
+    ) -> Result<Vec<SizePoint>> {
+        let builds = self
+            .recent_builds(
+                org,
+                project,
+                &BuildQuery {
+                    branch: query.branch.clone(),
+                    filter: BuildFilter::Completed,
+                    limit: query.builds,
+                    offset: 0,
+                },
+            )
+            .await
+            .context("Failed to list recent builds")?;
+        let builds: Vec<_> = builds
+            .into_iter()
+            .filter(|build| {
+                query.job.as_deref().is_none_or(|job| {
+                    build.workflows.as_ref().and_then(|w| w.job_name.as_deref()) == Some(job)
+                })
+            })
+            .collect();
+
+        let infos = futures::future::join_all(builds.iter().map(|build| {
+            self.artifact_infos(org, project, build.build_num, Some(&query.artifact))
+        }))
+        .await;
+
+        let mut points = Vec::new();
+        for (build, infos) in builds.iter().zip(infos) {
+            let infos = match infos {
+                Ok(infos) => infos,
+                Err(e) => {
+                    tracing::debug!(build = build.build_num, "skipping build: {:#}", e);
+                    continue;
+                }
+            };
+            if infos.is_empty() {
+                continue;
+            }
+            points.push(SizePoint {
+                build_num: build.build_num,
+                branch: build.branch.clone(),
+                revision: build.short_revision().map(str::to_string),
+                start_time: build.start_time,
+                bytes: infos.iter().filter_map(|info| info.size).sum(),
+                files: infos.len(),
+            });
+        }
+        points.reverse();
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{BuildBuilder, FakeCircleCi};
+
+    #[tokio::test]
+    async fn test_artifact_sizes_oldest_first() {
+        let server = FakeCircleCi::start().await;
+        let builds = [
+            BuildBuilder::new(3).build(),
+            BuildBuilder::new(2).build(),
+            BuildBuilder::new(1).build(),
+        ];
+        server.mount_builds("org", "repo", &builds).await;
+        server
+            .mount_artifacts("org", "repo", 3, &[("dist/app.js", "0123456789")])
+            .await;
+        server
+            .mount_artifacts("org", "repo", 2, &[("coverage/lcov.info", "LF:1")])
+            .await;
+        server
+            .mount_artifacts("org", "repo", 1, &[("dist/app.js", "01234567")])
+            .await;
+
+        let query = SizeQuery {
+            artifact: "dist/*.js".to_string(),
+            builds: 3,
+            branch: None,
+            job: None,
+        };
+        let points = server
+            .client()
+            .artifact_sizes("org", "repo", &query)
+            .await
+            .unwrap();
+        let sizes: Vec<_> = points.iter().map(|p| (p.build_num, p.bytes)).collect();
+        assert_eq!(sizes, vec![(1, 8), (3, 10)]);
+        assert_eq!(size_jumps(&points, 20.0)[0].previous_build, 1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Source locations in log lines, and excerpts of them from a local checkout.
+//!
+//! [`locate`] finds the `file:line:col` an error line points at. A
+//! [`Checkout`] maps that path, as the CI machine saw it, to a file in the
+//! local clone and reads the lines around it, at the build's commit when
+//! the clone has it, so the error can be shown next to the code it's about.
+
+use crate::codeowners::repo_relative;
+use crate::git;
+use crate::suspects::file_paths;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// A line and column right after a file path: `:14:3`, `(14,3)`, or
+/// javac's `:[14,3]`.
+fn position_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?::(?P<line>\d+)(?::(?P<col>\d+))?|\((?P<pline>\d+),(?P<pcol>\d+)\)|:\[(?P<jline>\d+),(?P<jcol>\d+)\])").unwrap()
+    })
+}
+
+/// Where in the source tree a log line points.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// Path with the CI workspace prefix removed.
+    pub file: String,
+    /// 1-based line, when the log has one.
+    pub line: Option<u32>,
+    /// 1-based column, when the log has one.
+    pub col: Option<u32>,
+}
+
+/// Finds the first file path in a log line, with the line and column
+/// right after it.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::source::locate;
+///
+/// let location = locate("/home/circleci/project/src/api.ts(14,3): error TS2322").unwrap();
+/// assert_eq!(location.file, "src/api.ts");
+/// assert_eq!((location.line, location.col), (Some(14), Some(3)));
+/// assert!(locate("npm ERR! code ELIFECYCLE").is_none());
+/// ```
+pub fn locate(line: &str) -> Option<SourceLocation> {
+    let path = file_paths(line).into_iter().next()?;
+    let mut location = SourceLocation {
+        file: repo_relative(&path).to_string(),
+        line: None,
+        col: None,
+    };
+    let after = line
+        .find(path.as_str())
+        .map(|start| &line[start + path.len()..])
+        .unwrap_or_default();
+    if let Some(caps) = position_regex().captures(after) {
+        let get = |names: [&str; 3]| {
+            names
+                .iter()
+                .find_map(|name| caps.name(name))
+                .and_then(|m| m.as_str().parse().ok())
+        };
+        location.line = get(["line", "pline", "jline"]);
+        location.col = get(["col", "pcol", "jcol"]);
+    }
+    Some(location)
+}
+
+/// Lines of a source file around an error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Excerpt {
+    /// Path relative to the checkout root.
+    pub file: String,
+    /// The line the error points at.
+    pub line: u32,
+    /// The column the error points at, if known.
+    pub col: Option<u32>,
+    /// Line numbers and text, the error line included.
+    pub lines: Vec<(u32, String)>,
+    /// `false` when the file was read from a working tree at another
+    /// commit than the build's, so the lines may have moved.
+    pub exact: bool,
+}
+
+/// A local clone of a build's repository.
+#[derive(Debug, Clone)]
+pub struct Checkout {
+    root: PathBuf,
+    /// The build's commit, when the clone has it but isn't at it.
+    build_revision: Option<String>,
+    /// Whether the working tree is at the build's commit.
+    at_build: bool,
+}
+
+impl Checkout {
+    /// Uses the working tree under `root` as is.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Checkout {
+            root: root.into(),
+            build_revision: None,
+            at_build: true,
+        }
+    }
+
+    /// Returns the current directory's checkout if its `origin` is
+    /// `org/project`.
+    ///
+    /// When `HEAD` isn't `revision` but the clone has that commit, files
+    /// are read at it with `git show`; otherwise from the working tree,
+    /// with excerpts marked inexact.
+    pub fn for_build(org: &str, project: &str, revision: Option<&str>) -> Option<Self> {
+        let (o, p) = git::origin_repo().ok()?;
+        if !o.eq_ignore_ascii_case(org) || !p.eq_ignore_ascii_case(project) {
+            return None;
+        }
+        let mut checkout = Checkout::new(git::toplevel().ok()?);
+        if let Some(revision) = revision {
+            let head = git::head_revision().unwrap_or_default();
+            if head != revision {
+                checkout.at_build = false;
+                if git::has_commit(revision) {
+                    checkout.build_revision = Some(revision.to_string());
+                }
+            }
+        }
+        Some(checkout)
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Maps a path from a CI log to a file in the checkout, relative to
+    /// its root.
+    ///
+    /// Workspace prefixes other than `.../project/` (`/home/circleci/repo/`,
+    /// `/go/src/github.com/org/repo/`) are dropped a directory at a time
+    /// until the rest names an existing file.
+    pub fn resolve(&self, file: &str) -> Option<String> {
+        let mut rest = repo_relative(file);
+        loop {
+            if !rest.is_empty() && self.root.join(rest).is_file() {
+                return Some(rest.to_string());
+            }
+            rest = rest.split_once('/')?.1;
+        }
+    }
+
+    /// Reads the `context` lines before and after a location.
+    ///
+    /// Returns `None` if the location has no line, its file isn't in the
+    /// checkout, or the file is shorter than the line.
+    pub fn excerpt(&self, location: &SourceLocation, context: u32) -> Option<Excerpt> {
+        let line = location.line.filter(|line| *line > 0)?;
+        let file = self.resolve(&location.file)?;
+        let at_commit = self
+            .build_revision
+            .as_deref()
+            .and_then(|revision| git::file_at(revision, &file).ok());
+        let exact = at_commit.is_some() || self.at_build;
+        let text = match at_commit {
+            Some(text) => text,
+            None => std::fs::read_to_string(self.root.join(&file)).ok()?,
+        };
+
+        let first = line.saturating_sub(context).max(1);
+        let lines: Vec<(u32, String)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, text)| (i as u32 + 1, text.to_string()))
+            .skip(first as usize - 1)
+            .take((line - first + context + 1) as usize)
+            .collect();
+        if lines.last().is_none_or(|(number, _)| *number < line) {
+            return None;
+        }
+        Some(Excerpt {
+            file,
+            line,
+            col: location.col,
+            lines,
+            exact,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_positions() {
+        let javac = locate("src/main/java/App.java:[12,5] cannot find symbol").unwrap();
+        assert_eq!((javac.line, javac.col), (Some(12), Some(5)));
+        let rust = locate("  --> src/lib.rs:3:9").unwrap();
+        assert_eq!((rust.file.as_str(), rust.line), ("src/lib.rs", Some(3)));
+        assert_eq!(locate("see ./config/app.yml").unwrap().line, None);
+    }
+
+    #[test]
+    fn test_excerpt_from_working_tree() {
+        let root = std::env::temp_dir().join(format!("cdb-source-test-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a.ts"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        let checkout = Checkout::new(&root);
+
+        assert_eq!(
+            checkout.resolve("/home/circleci/repo/src/a.ts").as_deref(),
+            Some("src/a.ts")
+        );
+        assert_eq!(checkout.resolve("src/b.ts"), None);
+
+        let location = |line| SourceLocation {
+            file: "/go/src/github.com/org/repo/src/a.ts".to_string(),
+            line: Some(line),
+            col: None,
+        };
+        let excerpt = checkout.excerpt(&location(2), 2).unwrap();
+        assert_eq!(excerpt.file, "src/a.ts");
+        assert_eq!(
+            excerpt.lines.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(excerpt.exact);
+        assert_eq!(checkout.excerpt(&location(5), 1).unwrap().lines.len(), 2);
+        assert!(checkout.excerpt(&location(6), 1).is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
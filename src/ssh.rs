@@ -0,0 +1,256 @@
+//! Rerunning a job with SSH and finding out how to connect to it.
+//!
+//! "Rerun job with SSH" starts a new build of the job that keeps its
+//! container up after the steps finish. Once the container is up, the
+//! build's `node` list has its address and port, and the "Enable SSH"
+//! step prints the command:
+//!
+//! ```text
+//! You can now SSH into this box if your SSH public key is added:
+//!     $ ssh -p 64535 54.224.97.243
+//! ```
+//!
+//! The container stays up for [`IDLE_TIMEOUT`] after the steps finish if
+//! no one connects, and for at most [`MAX_SESSION`] while someone is.
+
+use crate::{v1_vcs, BuildInfo, CircleClient};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How long an SSH rerun waits for a connection after its steps finish.
+pub const IDLE_TIMEOUT: Duration = Duration::minutes(10);
+
+/// How long an SSH rerun's container stays up at most.
+pub const MAX_SESSION: Duration = Duration::hours(2);
+
+/// Where to SSH into a build's container.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SshAccess {
+    /// Public IP address or host name.
+    pub host: String,
+    /// SSH port.
+    pub port: u16,
+    /// Parallel container, when the build has several.
+    pub container: Option<u32>,
+}
+
+impl SshAccess {
+    /// Returns the command that connects, e.g. `ssh -p 64535 54.224.97.243`.
+    pub fn command(&self) -> String {
+        format!("ssh -p {} {}", self.port, self.host)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    public_ip_addr: Option<String>,
+    port: Option<u16>,
+}
+
+fn ssh_command_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\bssh -p (?P<port>\d+) (?:[\w.-]+@)?(?P<host>[\w.:-]+)").unwrap()
+    })
+}
+
+/// Finds the `ssh -p PORT HOST` command in an "Enable SSH" step's output.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::ssh::parse_ssh_command;
+///
+/// let log = "You can now SSH into this box if your SSH public key is added:\n    $ ssh -p 64535 54.224.97.243\n";
+/// assert_eq!(parse_ssh_command(log).unwrap().command(), "ssh -p 64535 54.224.97.243");
+/// ```
+pub fn parse_ssh_command(log: &str) -> Option<SshAccess> {
+    let caps = ssh_command_regex().captures(log)?;
+    Some(SshAccess {
+        host: caps["host"].to_string(),
+        port: caps["port"].parse().ok()?,
+        container: None,
+    })
+}
+
+impl BuildInfo {
+    /// Returns the SSH address of each of the build's containers, from its
+    /// `node` list. Empty until the containers are up, and for builds
+    /// without SSH.
+    pub fn ssh_access(&self) -> Vec<SshAccess> {
+        let nodes: Vec<Node> = self
+            .extra
+            .get("node")
+            .and_then(|nodes| serde_json::from_value(nodes.clone()).ok())
+            .unwrap_or_default();
+        let several = nodes.len() > 1;
+        nodes
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, node)| {
+                Some(SshAccess {
+                    host: node.public_ip_addr.filter(|host| !host.is_empty())?,
+                    port: node.port?,
+                    container: several.then_some(i as u32),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns when the build started waiting for SSH connections, after
+    /// its steps finished.
+    pub fn ssh_wait_started(&self) -> Option<DateTime<Utc>> {
+        self.steps
+            .iter()
+            .filter(|step| step.name.to_ascii_lowercase().contains("wait for ssh"))
+            .flat_map(|step| step.actions.iter())
+            .filter_map(|action| action.start_time)
+            .min()
+    }
+
+    /// Returns when the build's SSH access ends: [`IDLE_TIMEOUT`] after
+    /// the steps finished if no one has connected by then, and
+    /// [`MAX_SESSION`] after the build started in any case.
+    ///
+    /// # Returns
+    ///
+    /// `(idle_deadline, session_deadline)`; the first is `None` while the
+    /// steps are still running.
+    pub fn ssh_deadlines(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        (
+            self.ssh_wait_started().map(|at| at + IDLE_TIMEOUT),
+            self.start_time.map(|at| at + MAX_SESSION),
+        )
+    }
+}
+
+impl CircleClient {
+    /// Reruns a build with SSH enabled.
+    ///
+    /// # Returns
+    ///
+    /// The new build, usually still queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, e.g. because the token's
+    /// user can't trigger builds of the project.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// let rerun = client.rerun_with_ssh("myorg", "myrepo", 12345).await?;
+    /// println!("SSH rerun is build #{}", rerun.build_num);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rerun_with_ssh(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+    ) -> Result<BuildInfo> {
+        let url = format!(
+            "{}/v1.1/project/{}/{}/{}/{}/ssh",
+            self.base_url,
+            v1_vcs(org),
+            org,
+            project,
+            build_num
+        );
+        self.post_json(&url, None)
+            .await
+            .with_context(|| format!("Failed to rerun build #{} with SSH", build_num))
+    }
+
+    /// Finds where to SSH into a build, from its `node` list or, failing
+    /// that, the output of its "Enable SSH" step.
+    ///
+    /// `build` must have its steps, as from
+    /// [`get_build`](Self::get_build). Returns an empty list until SSH is
+    /// ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the step's output can't be fetched.
+    pub async fn ssh_access(&self, build: &BuildInfo) -> Result<Vec<SshAccess>> {
+        let access = build.ssh_access();
+        if !access.is_empty() {
+            return Ok(access);
+        }
+        let Some(url) = build
+            .steps
+            .iter()
+            .filter(|step| step.name.to_ascii_lowercase().contains("enable ssh"))
+            .flat_map(|step| step.actions.iter())
+            .find_map(|action| action.output_url.as_deref())
+        else {
+            return Ok(Vec::new());
+        };
+        let log = self.get_logs(url).await?;
+        Ok(parse_ssh_command(&log).into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{ActionBuilder, BuildBuilder, FakeCircleCi, StepBuilder};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_rerun_and_find_ssh_access() {
+        let server = FakeCircleCi::start().await;
+        let rerun = BuildBuilder::new(8).running().build();
+        Mock::given(method("POST"))
+            .and(path("/api/v1.1/project/github/org/repo/7/ssh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&rerun))
+            .mount(server.server())
+            .await;
+        let client = server.client();
+        assert_eq!(
+            client
+                .rerun_with_ssh("org", "repo", 7)
+                .await
+                .unwrap()
+                .build_num,
+            8
+        );
+
+        let log_url = server
+            .mount_log(
+                "ssh",
+                "You can now SSH into this box if your SSH public key is added:\n    $ ssh -p 64535 54.224.97.243\n",
+            )
+            .await;
+        let build = BuildBuilder::new(8)
+            .running()
+            .step(
+                StepBuilder::new("Enable SSH")
+                    .action(ActionBuilder::new("Enable SSH").output_url(log_url)),
+            )
+            .build();
+        let access = client.ssh_access(&build).await.unwrap();
+        assert_eq!(access[0].command(), "ssh -p 64535 54.224.97.243");
+
+        let mut build = BuildBuilder::new(8).build();
+        build.extra.insert(
+            "node".to_string(),
+            serde_json::json!([
+                {"public_ip_addr": "10.0.0.1", "port": 2222, "username": "circleci"},
+                {"public_ip_addr": "10.0.0.2", "port": 2223, "username": "circleci"}
+            ]),
+        );
+        let access = build.ssh_access();
+        assert_eq!(access.len(), 2);
+        assert_eq!((access[1].port, access[1].container), (2223, Some(1)));
+    }
+}
@@ -0,0 +1,346 @@
+//! Trends over the local build history.
+//!
+//! [`HistoryStore::stats`] summarizes the builds recorded in a time window:
+//! how often each job fails, which error categories come up most, how long
+//! a red job takes to go green again, and which steps are getting slower.
+
+use crate::store::{build_record, BuildRecord, HistoryStore, BUILD_COLUMNS};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Which recorded builds to summarize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsQuery {
+    /// Only builds of this organization.
+    pub org: Option<String>,
+    /// Only builds of this project.
+    pub project: Option<String>,
+    /// Only builds that started at or after this time.
+    pub since: DateTime<Utc>,
+}
+
+/// Summary of the recorded builds in a time window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    /// Start of the window.
+    pub since: DateTime<Utc>,
+    /// Number of builds in the window.
+    pub builds: usize,
+    /// Number of those that failed.
+    pub failed: usize,
+    /// Failure rate per job, highest first.
+    pub jobs: Vec<JobStats>,
+    /// Error categories by the number of builds they were detected in,
+    /// most frequent first.
+    pub categories: Vec<CategoryCount>,
+    /// Average milliseconds from a job's first failing build to the end of
+    /// the build that fixed it.
+    pub time_to_green_millis: Option<u64>,
+    /// Number of red streaks that went green, behind the average.
+    pub recoveries: usize,
+    /// Steps whose duration grew the most, fastest-growing first.
+    pub growing_steps: Vec<StepTrend>,
+}
+
+/// How often one job failed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobStats {
+    /// Workflow job name, or `-` for builds without one.
+    pub job: String,
+    /// Number of builds of the job.
+    pub builds: usize,
+    /// Number of those that failed.
+    pub failed: usize,
+}
+
+impl JobStats {
+    /// Fraction of builds that failed, `0.0..=1.0`.
+    pub fn failure_rate(&self) -> f64 {
+        self.failed as f64 / self.builds as f64
+    }
+}
+
+/// How many builds an error category was detected in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CategoryCount {
+    /// Category of the pattern.
+    pub category: String,
+    /// Number of builds it was detected in.
+    pub builds: usize,
+}
+
+/// How a step's duration changed over the window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepTrend {
+    /// Workflow job the step belongs to, or `-` for builds without one.
+    pub job: String,
+    /// Step name.
+    pub name: String,
+    /// Average milliseconds in the older half of its builds.
+    pub before_millis: u64,
+    /// Average milliseconds in the newer half of its builds.
+    pub after_millis: u64,
+    /// Number of builds the step ran in.
+    pub builds: usize,
+}
+
+impl StepTrend {
+    /// Milliseconds the step got slower by; negative if it got faster.
+    pub fn growth_millis(&self) -> i64 {
+        self.after_millis as i64 - self.before_millis as i64
+    }
+}
+
+impl HistoryStore {
+    /// Summarizes the recorded builds matching `query`.
+    ///
+    /// Builds without a start time are left out, since they can't be
+    /// placed in the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use circle_debug::stats::StatsQuery;
+    /// use circle_debug::store::HistoryStore;
+    ///
+    /// let store = HistoryStore::in_memory()?;
+    /// let stats = store.stats(&StatsQuery {
+    ///     org: None,
+    ///     project: None,
+    ///     since: chrono::Utc::now() - chrono::Duration::days(30),
+    /// })?;
+    /// assert_eq!(stats.builds, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stats(&self, query: &StatsQuery) -> Result<Stats> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM builds
+             WHERE (?1 IS NULL OR org = ?1) AND (?2 IS NULL OR project = ?2)
+                 AND start_time >= ?3
+             ORDER BY start_time, build_num",
+            BUILD_COLUMNS
+        ))?;
+        let builds: Vec<BuildRecord> = stmt
+            .query_map(params![query.org, query.project, query.since], build_record)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT f.category, COUNT(DISTINCT f.org || '/' || f.project || '#' || f.build_num)
+             FROM failures f JOIN builds b
+                 ON b.org = f.org AND b.project = f.project AND b.build_num = f.build_num
+             WHERE (?1 IS NULL OR b.org = ?1) AND (?2 IS NULL OR b.project = ?2)
+                 AND b.start_time >= ?3
+             GROUP BY f.category
+             ORDER BY 2 DESC, f.category",
+        )?;
+        let categories = stmt
+            .query_map(params![query.org, query.project, query.since], |row| {
+                Ok(CategoryCount {
+                    category: row.get(0)?,
+                    builds: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT b.job, s.name, s.millis
+             FROM steps s JOIN builds b
+                 ON b.org = s.org AND b.project = s.project AND b.build_num = s.build_num
+             WHERE (?1 IS NULL OR b.org = ?1) AND (?2 IS NULL OR b.project = ?2)
+                 AND b.start_time >= ?3
+             ORDER BY b.start_time, b.build_num",
+        )?;
+        let steps: Vec<(Option<String>, String, u64)> = stmt
+            .query_map(params![query.org, query.project, query.since], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let (time_to_green_millis, recoveries) = time_to_green(&builds);
+        Ok(Stats {
+            since: query.since,
+            failed: builds.iter().filter(|b| b.is_failed()).count(),
+            builds: builds.len(),
+            jobs: job_stats(&builds),
+            categories,
+            time_to_green_millis,
+            recoveries,
+            growing_steps: step_trends(&steps),
+        })
+    }
+}
+
+/// Counts builds and failures per job, highest failure rate first.
+fn job_stats(builds: &[BuildRecord]) -> Vec<JobStats> {
+    let mut jobs: Vec<JobStats> = Vec::new();
+    for build in builds {
+        let name = build.job.as_deref().unwrap_or("-");
+        let index = match jobs.iter().position(|job| job.job == name) {
+            Some(index) => index,
+            None => {
+                jobs.push(JobStats {
+                    job: name.to_string(),
+                    builds: 0,
+                    failed: 0,
+                });
+                jobs.len() - 1
+            }
+        };
+        jobs[index].builds += 1;
+        if build.is_failed() {
+            jobs[index].failed += 1;
+        }
+    }
+    jobs.sort_by(|a, b| {
+        b.failure_rate()
+            .total_cmp(&a.failure_rate())
+            .then(b.builds.cmp(&a.builds))
+    });
+    jobs
+}
+
+/// Averages how long red streaks of each project, branch, and job took to
+/// go green, over builds in start order.
+///
+/// # Returns
+///
+/// The average in milliseconds, and how many streaks it covers.
+fn time_to_green(builds: &[BuildRecord]) -> (Option<u64>, usize) {
+    // Start of the current red streak, per project/branch/job
+    type Key<'a> = (&'a str, &'a str, Option<&'a str>, Option<&'a str>);
+    let mut red_since: HashMap<Key, DateTime<Utc>> = HashMap::new();
+    let mut total_millis = 0;
+    let mut recoveries = 0;
+
+    for build in builds {
+        let key = (
+            build.org.as_str(),
+            build.project.as_str(),
+            build.branch.as_deref(),
+            build.job.as_deref(),
+        );
+        if build.is_failed() {
+            if let Some(start) = build.start_time {
+                red_since.entry(key).or_insert(start);
+            }
+        } else if matches!(build.status.as_str(), "success" | "fixed" | "no_tests") {
+            if let (Some(red), Some(green)) = (red_since.remove(&key), build.stop_time()) {
+                total_millis += (green - red).num_milliseconds().max(0) as u64;
+                recoveries += 1;
+            }
+        }
+    }
+
+    let average = (recoveries > 0).then(|| total_millis / recoveries as u64);
+    (average, recoveries)
+}
+
+/// Compares the average duration of each job's steps in the older and
+/// newer half of their runs, fastest-growing first. Steps that ran fewer
+/// than twice or didn't get slower are left out.
+fn step_trends(steps: &[(Option<String>, String, u64)]) -> Vec<StepTrend> {
+    let mut runs: Vec<(&str, &str, Vec<u64>)> = Vec::new();
+    for (job, name, millis) in steps {
+        let job = job.as_deref().unwrap_or("-");
+        match runs.iter_mut().find(|(j, n, _)| *j == job && n == name) {
+            Some((_, _, durations)) => durations.push(*millis),
+            None => runs.push((job, name, vec![*millis])),
+        }
+    }
+
+    let average = |durations: &[u64]| durations.iter().sum::<u64>() / durations.len() as u64;
+    let mut trends: Vec<StepTrend> = runs
+        .into_iter()
+        .filter(|(_, _, durations)| durations.len() >= 2)
+        .map(|(job, name, durations)| {
+            let (before, after) = durations.split_at(durations.len() / 2);
+            StepTrend {
+                job: job.to_string(),
+                name: name.to_string(),
+                before_millis: average(before),
+                after_millis: average(after),
+                builds: durations.len(),
+            }
+        })
+        .filter(|trend| trend.growth_millis() > 0)
+        .collect();
+    trends.sort_by_key(|trend| std::cmp::Reverse(trend.growth_millis()));
+    trends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{BuildReport, StepTiming};
+    use crate::BuildInfo;
+
+    fn report(
+        build_num: u32,
+        status: &str,
+        job: &str,
+        minute: u32,
+        test_millis: u64,
+    ) -> BuildReport {
+        let build: BuildInfo = serde_json::from_value(serde_json::json!({
+            "build_num": build_num, "status": status, "branch": "main", "subject": null,
+            "start_time": format!("2026-10-01T12:{:02}:00Z", minute),
+            "build_time_millis": 60000,
+            "workflows": {"job_name": job}
+        }))
+        .unwrap();
+        let mut report = BuildReport::new("org", "repo", &build);
+        report.step_timings = vec![StepTiming {
+            name: "Test".to_string(),
+            millis: test_millis,
+            status: status.to_string(),
+        }];
+        report
+    }
+
+    #[test]
+    fn test_stats() {
+        let store = HistoryStore::in_memory().unwrap();
+        for report in [
+            report(1, "success", "test", 0, 10000),
+            report(2, "failed", "test", 10, 10000),
+            report(3, "failed", "test", 20, 20000),
+            report(4, "success", "lint", 25, 1000),
+            report(5, "success", "test", 30, 30000),
+        ] {
+            store.record(&report).unwrap();
+        }
+
+        let stats = store
+            .stats(&StatsQuery {
+                org: None,
+                project: Some("repo".to_string()),
+                since: "2026-10-01T00:00:00Z".parse().unwrap(),
+            })
+            .unwrap();
+
+        assert_eq!((stats.builds, stats.failed), (5, 2));
+        assert_eq!(stats.jobs[0].job, "test");
+        assert_eq!(stats.jobs[0].failure_rate(), 0.5);
+        assert_eq!(stats.jobs[1].failed, 0);
+
+        // Red from 12:10 until build 5 finished at 12:31
+        assert_eq!(stats.recoveries, 1);
+        assert_eq!(stats.time_to_green_millis, Some(21 * 60 * 1000));
+
+        // test's Test step: 10s, 10s | 20s, 30s; lint ran only once
+        assert_eq!(stats.growing_steps.len(), 1);
+        assert_eq!(stats.growing_steps[0].job, "test");
+        assert_eq!(stats.growing_steps[0].before_millis, 10000);
+        assert_eq!(stats.growing_steps[0].after_millis, 25000);
+    }
+}
@@ -0,0 +1,563 @@
+//! Local build history database.
+//!
+//! An opt-in SQLite store of every build `cdb` analyzed: its metadata and
+//! timing, step durations, and the errors detected in it. Unlike
+//! [`history`](crate::history), which asks the API about recent builds,
+//! this works offline and remembers builds long after their logs expire,
+//! so it can answer "have we seen this error before?".
+//!
+//! Recording is enabled by creating the database with `cdb history enable`
+//! (or [`HistoryStore::open`]); while it doesn't exist nothing is written.
+//!
+//! # Examples
+//!
+//! ```
+//! # use anyhow::Result;
+//! # fn main() -> Result<()> {
+//! use circle_debug::report::BuildReport;
+//! use circle_debug::store::HistoryStore;
+//! use circle_debug::BuildInfo;
+//!
+//! let build: BuildInfo = serde_json::from_str(
+//!     r#"{"build_num": 7, "status": "failed", "branch": "main", "subject": null}"#,
+//! )?;
+//! let store = HistoryStore::in_memory()?;
+//! store.record(&BuildReport::new("org", "repo", &build))?;
+//!
+//! let builds = store.recent(&Default::default())?;
+//! assert_eq!(builds[0].build_num, 7);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::fingerprint::fingerprint;
+use crate::patterns::{ErrorGroup, Severity};
+use crate::report::BuildReport;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub use crate::report::SeenBefore;
+
+/// Schema of the database, created when it is opened.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS builds (
+    org TEXT NOT NULL,
+    project TEXT NOT NULL,
+    build_num INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    branch TEXT,
+    workflow TEXT,
+    job TEXT,
+    vcs_revision TEXT,
+    subject TEXT,
+    start_time TEXT,
+    build_time_millis INTEGER,
+    queued_millis INTEGER,
+    recorded_at TEXT NOT NULL,
+    PRIMARY KEY (org, project, build_num)
+);
+CREATE TABLE IF NOT EXISTS steps (
+    org TEXT NOT NULL,
+    project TEXT NOT NULL,
+    build_num INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    millis INTEGER NOT NULL,
+    status TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS failures (
+    org TEXT NOT NULL,
+    project TEXT NOT NULL,
+    build_num INTEGER NOT NULL,
+    step TEXT NOT NULL,
+    pattern_id TEXT NOT NULL,
+    category TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    line TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS steps_build ON steps (org, project, build_num);
+CREATE INDEX IF NOT EXISTS failures_build ON failures (org, project, build_num);
+CREATE INDEX IF NOT EXISTS failures_fingerprint ON failures (fingerprint);
+";
+
+/// Columns read by [`build_record`], in order.
+pub(crate) const BUILD_COLUMNS: &str = "org, project, build_num, status, branch, workflow, job, \
+    vcs_revision, subject, start_time, build_time_millis, queued_millis, recorded_at";
+
+/// Build statuses that count as failures.
+pub(crate) const FAILED_STATUSES: [&str; 3] = ["failed", "infrastructure_fail", "timedout"];
+
+/// A build as recorded in the history database.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildRecord {
+    /// Organization (or user) that owns the project.
+    pub org: String,
+    /// Project (repository) name.
+    pub project: String,
+    /// Build number.
+    pub build_num: u32,
+    /// Build status when it was analyzed.
+    pub status: String,
+    /// Git branch name.
+    pub branch: Option<String>,
+    /// Workflow the build belongs to.
+    pub workflow: Option<String>,
+    /// Workflow job the build ran.
+    pub job: Option<String>,
+    /// Full commit SHA.
+    pub vcs_revision: Option<String>,
+    /// Commit subject line.
+    pub subject: Option<String>,
+    /// When the build started running.
+    pub start_time: Option<DateTime<Utc>>,
+    /// Total build time in milliseconds.
+    pub build_time_millis: Option<u64>,
+    /// Milliseconds the build waited for capacity.
+    pub queued_millis: Option<u64>,
+    /// When `cdb` last analyzed the build.
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl BuildRecord {
+    /// Returns `true` if the build failed, including infrastructure
+    /// failures and timeouts.
+    pub fn is_failed(&self) -> bool {
+        FAILED_STATUSES.contains(&self.status.as_str())
+    }
+
+    /// Returns when the build finished, if it started and its run time is
+    /// known.
+    pub fn stop_time(&self) -> Option<DateTime<Utc>> {
+        let millis = self.build_time_millis?;
+        Some(self.start_time? + chrono::Duration::milliseconds(millis as i64))
+    }
+}
+
+/// An error detected in a recorded build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailureRecord {
+    /// Build the error was detected in.
+    pub build_num: u32,
+    /// Step whose log it was found in.
+    pub step: String,
+    /// Id of the pattern that matched.
+    pub pattern_id: String,
+    /// Category of the pattern that matched.
+    pub category: String,
+    /// Kind of the pattern that matched (`code`, `tests`, or `infra`).
+    pub kind: String,
+    /// What identifies this error across builds, see
+    /// [`fingerprint`](crate::fingerprint).
+    pub fingerprint: String,
+    /// First matched line.
+    pub line: String,
+}
+
+/// Which recorded builds to list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordQuery {
+    /// Only builds of this organization.
+    pub org: Option<String>,
+    /// Only builds of this project.
+    pub project: Option<String>,
+    /// Only failed builds.
+    pub failed: bool,
+    /// Number of builds to return.
+    pub limit: u32,
+}
+
+impl Default for RecordQuery {
+    fn default() -> Self {
+        RecordQuery {
+            org: None,
+            project: None,
+            failed: false,
+            limit: 20,
+        }
+    }
+}
+
+/// The history database.
+#[derive(Debug)]
+pub struct HistoryStore {
+    pub(crate) conn: Connection,
+}
+
+impl HistoryStore {
+    /// Returns the default database path, see [`paths`](crate::paths).
+    pub fn default_path() -> PathBuf {
+        crate::paths::history_db()
+    }
+
+    /// Opens the database at `path`, creating it and its parent directory
+    /// if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created or isn't a history
+    /// database.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        Self::with_connection(conn)
+            .with_context(|| format!("Invalid history database {}", path.display()))
+    }
+
+    /// Opens the database at `path` if history is enabled, i.e. the file
+    /// exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be opened.
+    pub fn open_existing(path: &Path) -> Result<Option<Self>> {
+        if path.exists() {
+            Self::open(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Opens a database that lives only in memory, for tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if SQLite can't allocate it.
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(HistoryStore { conn })
+    }
+
+    /// Records an analyzed build, replacing an earlier record of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be written.
+    pub fn record(&self, report: &BuildReport) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let key = params![report.org, report.project, report.build_num];
+        for table in ["builds", "steps", "failures"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE org = ?1 AND project = ?2 AND build_num = ?3",
+                    table
+                ),
+                key,
+            )?;
+        }
+
+        let workflow = report.workflow.as_ref();
+        tx.execute(
+            "INSERT INTO builds (org, project, build_num, status, branch, workflow, job,
+                 vcs_revision, subject, start_time, build_time_millis, queued_millis, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                report.org,
+                report.project,
+                report.build_num,
+                report.status,
+                report.branch,
+                workflow.and_then(|w| w.workflow_name.as_deref()),
+                workflow.and_then(|w| w.job_name.as_deref()),
+                report.vcs_revision,
+                report.subject,
+                report.start_time,
+                report.build_time_millis,
+                report.queued_millis,
+                Utc::now(),
+            ],
+        )?;
+
+        for timing in &report.step_timings {
+            tx.execute(
+                "INSERT INTO steps (org, project, build_num, name, millis, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    report.org,
+                    report.project,
+                    report.build_num,
+                    timing.name,
+                    timing.millis,
+                    timing.status
+                ],
+            )?;
+        }
+
+        for action in &report.failed_actions {
+            for group in &action.errors {
+                tx.execute(
+                    "INSERT INTO failures (org, project, build_num, step, pattern_id, category,
+                         kind, fingerprint, line)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        report.org,
+                        report.project,
+                        report.build_num,
+                        action.step,
+                        group.pattern_id,
+                        group.category,
+                        serde_json::to_value(group.kind)?.as_str(),
+                        fingerprint(group),
+                        group.first.line.trim(),
+                    ],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Lists recorded builds, most recently started first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn recent(&self, query: &RecordQuery) -> Result<Vec<BuildRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM builds
+             WHERE (?1 IS NULL OR org = ?1) AND (?2 IS NULL OR project = ?2)
+                 AND (NOT ?3 OR status IN ('failed', 'infrastructure_fail', 'timedout'))
+             ORDER BY COALESCE(start_time, recorded_at) DESC, build_num DESC
+             LIMIT ?4",
+            BUILD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![query.org, query.project, query.failed, query.limit],
+            build_record,
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Returns a recorded build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn build(&self, org: &str, project: &str, build_num: u32) -> Result<Option<BuildRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM builds WHERE org = ?1 AND project = ?2 AND build_num = ?3",
+                    BUILD_COLUMNS
+                ),
+                params![org, project, build_num],
+                build_record,
+            )
+            .optional()?)
+    }
+
+    /// Returns the errors detected in a recorded build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn failures(&self, org: &str, project: &str, build_num: u32) -> Result<Vec<FailureRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT build_num, step, pattern_id, category, kind, fingerprint, line
+             FROM failures WHERE org = ?1 AND project = ?2 AND build_num = ?3",
+        )?;
+        let rows = stmt.query_map(params![org, project, build_num], failure_record)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Looks up other builds of a project where an error with the same
+    /// fingerprint was detected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn seen_before(
+        &self,
+        org: &str,
+        project: &str,
+        exclude: u32,
+        group: &ErrorGroup,
+    ) -> Result<SeenBefore> {
+        let fingerprint = fingerprint(group);
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT f.build_num, COALESCE(b.start_time, b.recorded_at)
+             FROM failures f JOIN builds b
+                 ON b.org = f.org AND b.project = f.project AND b.build_num = f.build_num
+             WHERE f.org = ?1 AND f.project = ?2 AND f.build_num != ?3 AND f.fingerprint = ?4
+             ORDER BY f.build_num DESC",
+        )?;
+        let rows: Vec<(u32, DateTime<Utc>)> = stmt
+            .query_map(params![org, project, exclude, fingerprint], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(SeenBefore {
+            fingerprint,
+            category: group.category.clone(),
+            first_seen: rows.iter().map(|(_, at)| *at).min(),
+            builds: rows.into_iter().map(|(number, _)| number).collect(),
+        })
+    }
+
+    /// Looks up every distinct failure of a report with
+    /// [`seen_before`](Self::seen_before), highest ranked first.
+    ///
+    /// Hints such as a non-zero exit match nearly every failed build, so
+    /// they're skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn recall(&self, report: &BuildReport) -> Result<Vec<SeenBefore>> {
+        let mut seen: Vec<SeenBefore> = Vec::new();
+        for group in report.errors().filter(|g| g.severity != Severity::Hint) {
+            let entry = self.seen_before(&report.org, &report.project, report.build_num, group)?;
+            if !seen.iter().any(|s| s.fingerprint == entry.fingerprint) {
+                seen.push(entry);
+            }
+        }
+        Ok(seen)
+    }
+
+    /// Returns the number of recorded builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn len(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM builds", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Returns `true` if no builds are recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database can't be read.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// Reads a [`BuildRecord`] from a row selected in column order.
+pub(crate) fn build_record(row: &rusqlite::Row) -> rusqlite::Result<BuildRecord> {
+    Ok(BuildRecord {
+        org: row.get(0)?,
+        project: row.get(1)?,
+        build_num: row.get(2)?,
+        status: row.get(3)?,
+        branch: row.get(4)?,
+        workflow: row.get(5)?,
+        job: row.get(6)?,
+        vcs_revision: row.get(7)?,
+        subject: row.get(8)?,
+        start_time: row.get(9)?,
+        build_time_millis: row.get(10)?,
+        queued_millis: row.get(11)?,
+        recorded_at: row.get(12)?,
+    })
+}
+
+/// Reads a [`FailureRecord`] from a row selected in column order.
+fn failure_record(row: &rusqlite::Row) -> rusqlite::Result<FailureRecord> {
+    Ok(FailureRecord {
+        build_num: row.get(0)?,
+        step: row.get(1)?,
+        pattern_id: row.get(2)?,
+        category: row.get(3)?,
+        kind: row.get(4)?,
+        fingerprint: row.get(5)?,
+        line: row.get(6)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FailedActionBuilder;
+    use crate::BuildInfo;
+
+    fn report(build_num: u32, status: &str, log: &str) -> BuildReport {
+        let build: BuildInfo = serde_json::from_value(serde_json::json!({
+            "build_num": build_num, "status": status, "branch": "main", "subject": null,
+            "start_time": format!("2026-10-{:02}T12:00:00Z", build_num),
+            "workflows": {"job_name": "test", "workflow_name": "ci"},
+            "steps": [{"name": "Test", "actions": [
+                {"name": "Test", "status": status, "failed": status == "failed",
+                 "output_url": null, "type": "test", "run_time_millis": 60000}]}]
+        }))
+        .unwrap();
+        let mut report = BuildReport::new("org", "repo", &build);
+        report
+            .failed_actions
+            .push(FailedActionBuilder::new("Test").log(log).build());
+        report
+    }
+
+    #[test]
+    fn test_record_and_list() {
+        let store = HistoryStore::in_memory().unwrap();
+        store.record(&report(1, "success", "")).unwrap();
+        store
+            .record(&report(2, "failed", "Error: Cannot find module 'left-pad'"))
+            .unwrap();
+        // Recording again replaces the earlier record
+        store
+            .record(&report(2, "failed", "Error: Cannot find module 'left-pad'"))
+            .unwrap();
+
+        assert_eq!(store.len().unwrap(), 2);
+        let builds = store.recent(&RecordQuery::default()).unwrap();
+        assert_eq!(builds[0].build_num, 2);
+        assert_eq!(builds[0].job.as_deref(), Some("test"));
+        assert_eq!(builds[0].build_time_millis, None);
+
+        let failed = store
+            .recent(&RecordQuery {
+                failed: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+
+        let failures = store.failures("org", "repo", 2).unwrap();
+        assert!(!failures.is_empty());
+        assert_eq!(failures[0].step, "Test");
+        assert!(store.build("org", "other", 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_seen_before() {
+        let store = HistoryStore::in_memory().unwrap();
+        let log = "Error: Cannot find module 'left-pad'";
+        store.record(&report(3, "failed", log)).unwrap();
+        store
+            .record(&report(4, "failed", "Segmentation fault"))
+            .unwrap();
+        let current = report(5, "failed", log);
+        store.record(&current).unwrap();
+
+        let group = current.errors().next().unwrap();
+        let seen = store.seen_before("org", "repo", 5, group).unwrap();
+        assert_eq!(seen.builds, vec![3]);
+        assert_eq!(
+            seen.first_seen,
+            Some("2026-10-03T12:00:00Z".parse().unwrap())
+        );
+        assert!(store
+            .seen_before("org", "fork", 5, group)
+            .unwrap()
+            .builds
+            .is_empty());
+    }
+}
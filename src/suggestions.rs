@@ -0,0 +1,395 @@
+//! Fix suggestions for detected errors.
+//!
+//! Suggestions are templates keyed by pattern id. A suggestion can be
+//! restricted to lines matching an extra `when` regex, and its text can
+//! reference capture groups from either regex as `{name}` or `{1}`;
+//! `{line}` expands to the whole matched line. The first suggestion whose
+//! conditions hold wins, and suggestions from the config file are checked
+//! before the built-in ones.
+
+use crate::patterns::ErrorPattern;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// A remediation tip for one error pattern.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    /// Id of the [`ErrorPattern`] this applies to.
+    pub pattern_id: String,
+    /// Only applies when the line also matches this regex.
+    pub when: Option<Regex>,
+    /// Text shown to the user, with `{group}` placeholders.
+    pub template: String,
+}
+
+impl Suggestion {
+    /// Creates a suggestion, compiling `when` if given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `when` is not a valid regex.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::suggestions::Suggestion;
+    ///
+    /// let suggestion = Suggestion::new(
+    ///     "missing-module",
+    ///     Some(r"module '(?P<module>[^']+)'"),
+    ///     "Add '{module}' to package.json",
+    /// ).unwrap();
+    /// assert_eq!(suggestion.pattern_id, "missing-module");
+    /// ```
+    pub fn new(pattern_id: &str, when: Option<&str>, template: &str) -> Result<Self> {
+        let when = when
+            .map(Regex::new)
+            .transpose()
+            .with_context(|| format!("invalid 'when' regex for suggestion on '{}'", pattern_id))?;
+
+        Ok(Suggestion {
+            pattern_id: pattern_id.to_string(),
+            when,
+            template: template.to_string(),
+        })
+    }
+
+    /// Renders the suggestion for `line`, or `None` if its `when` regex
+    /// doesn't match.
+    fn render(&self, pattern: &ErrorPattern, line: &str) -> Option<String> {
+        let when_captures = match &self.when {
+            Some(when) => Some(when.captures(line)?),
+            None => None,
+        };
+        let pattern_captures = pattern.regex.captures(line);
+
+        let text = placeholder_regex().replace_all(&self.template, |caps: &Captures| {
+            let name = &caps[1];
+            if name == "line" {
+                return line.trim().to_string();
+            }
+            [when_captures.as_ref(), pattern_captures.as_ref()]
+                .into_iter()
+                .flatten()
+                .find_map(|captures| capture(captures, name))
+                .unwrap_or_else(|| caps[0].to_string())
+        });
+
+        Some(text.into_owned())
+    }
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{(\w+)\}").unwrap())
+}
+
+/// Looks up a capture group by index or name.
+fn capture(captures: &Captures, name: &str) -> Option<String> {
+    let group = match name.parse::<usize>() {
+        Ok(index) => captures.get(index),
+        Err(_) => captures.name(name),
+    };
+    group.map(|m| m.as_str().to_string())
+}
+
+/// Built-in suggestions as (pattern id, when, template), most specific first.
+const BUILTIN_SUGGESTIONS: &[(&str, Option<&str>, &str)] = &[
+    (
+        "file-not-found",
+        Some(r"(?i)readme"),
+        "Check file case sensitivity (README.md vs readme.md)",
+    ),
+    (
+        "file-not-found",
+        Some(r"package\.json"),
+        "Run 'npm install' to ensure dependencies are installed",
+    ),
+    (
+        "file-not-found",
+        Some(r"'(?P<path>[^']+)'"),
+        "Verify '{path}' exists and the path is correct",
+    ),
+    (
+        "file-not-found",
+        None,
+        "Verify file exists and path is correct",
+    ),
+    (
+        "missing-module",
+        Some(r#"(?i)cannot find module ['"](?P<module>[^'"]+)['"]"#),
+        "Run 'npm install' or check that '{module}' is in package.json dependencies",
+    ),
+    (
+        "missing-module",
+        None,
+        "Run 'npm install' or check package.json dependencies",
+    ),
+    (
+        "missing-dependency",
+        None,
+        "Run 'npm install' or check package.json dependencies",
+    ),
+    (
+        "typescript-error",
+        None,
+        "Run 'npm run typecheck' locally to see full type errors",
+    ),
+    (
+        "lint-error",
+        None,
+        "Run 'npm run lint -- --fix' to auto-fix some issues",
+    ),
+    (
+        "test-failure",
+        None,
+        "Run tests locally with '--verbose' for more details",
+    ),
+    (
+        "test-suite-failure",
+        None,
+        "Run tests locally with '--verbose' for more details",
+    ),
+    (
+        "docker-context-file-missing",
+        Some(r#""/?(?P<path>[^"]+)": not found"#),
+        "'{path}' isn't in the Docker build context - check .dockerignore and the context directory passed to 'docker build'",
+    ),
+    (
+        "docker-context-file-missing",
+        None,
+        "A COPY source isn't in the Docker build context - check .dockerignore and the context directory passed to 'docker build'",
+    ),
+    (
+        "apt-signing-key",
+        Some(r"(?:NO_PUBKEY|EXPKEYSIG) (?P<key>[0-9A-F]+)"),
+        "An apt repository's signing key {key} is missing or expired - download the current key to /etc/apt/keyrings and reference it with [signed-by=...] in the sources list",
+    ),
+    (
+        "apt-signing-key",
+        None,
+        "An apt repository's signing key is missing - download it to /etc/apt/keyrings and reference it with [signed-by=...]; 'apt-key' is gone from recent Debian and Ubuntu images",
+    ),
+    (
+        "apt-package-missing",
+        Some(r"Unable to locate package (?P<package>\S+)"),
+        "Run 'apt-get update' in the same RUN as 'apt-get install', and check that '{package}' exists in the base image's release",
+    ),
+    (
+        "apt-package-missing",
+        None,
+        "Run 'apt-get update' in the same RUN as 'apt-get install', and check the package exists in the base image's release",
+    ),
+    (
+        "docker-image-not-found",
+        None,
+        "Check the FROM image name and tag exist, and that the job is logged in to private registries",
+    ),
+    (
+        "docker-rate-limit",
+        None,
+        "Docker Hub is rate limiting anonymous pulls - log in with 'docker login' before building, or pull through a mirror",
+    ),
+    (
+        "gradle-task-failed",
+        Some(r"task '(?P<task>:?[^']+)'"),
+        "Run './gradlew {task} --stacktrace' locally to reproduce",
+    ),
+    (
+        "maven-goal-failed",
+        Some(r"on project (?P<project>[\w.-]+)"),
+        "Run 'mvn -e -pl {project} -am' with the same goals locally to see the full error",
+    ),
+    (
+        "jvm-test-failure",
+        None,
+        "Test reports are in build/reports/tests (Gradle) or target/surefire-reports (Maven); upload them with store_test_results",
+    ),
+    (
+        "jvm-dependency-resolution",
+        None,
+        "Check the dependency's version and repositories; './gradlew dependencies' or 'mvn dependency:tree' shows what was requested",
+    ),
+    (
+        "gradle-daemon-crash",
+        None,
+        "The Gradle daemon ran out of memory - set org.gradle.jvmargs=-Xmx... in gradle.properties below the executor's RAM, or use a larger resource_class",
+    ),
+    (
+        "maven-transfer-failed",
+        None,
+        "The artifact repository didn't answer - rerun the job; cache ~/.m2 or ~/.gradle to depend on it less",
+    ),
+    (
+        "out-of-memory",
+        None,
+        "Increase Node memory: NODE_OPTIONS='--max-old-space-size=4096'",
+    ),
+    (
+        "no-output-timeout",
+        None,
+        "Raise 'no_output_timeout' on the step, or make the command print progress",
+    ),
+    (
+        "registry-unavailable",
+        None,
+        "The package registry is having an outage - rerun the job; 'npm ci --fetch-retries=5' or 'yarn --network-timeout 600000' rides out short ones",
+    ),
+    (
+        "git-auth-failed",
+        None,
+        "No checkout key could read the repository - run 'cdb keys' to list the project's keys; submodules and other private repositories need a user key",
+    ),
+    (
+        "git-clone-failed",
+        None,
+        "Couldn't reach the Git remote - rerun the job; check your Git host's status page if it keeps failing",
+    ),
+    (
+        "network-error",
+        Some(r"getaddrinfo (ENOTFOUND|EAI_AGAIN) (?P<host>[\w.-]+)"),
+        "DNS lookup for {host} failed - likely transient, rerun the job; if it recurs, check {host} is reachable from CircleCI",
+    ),
+    (
+        "network-error",
+        None,
+        "Likely a transient network issue - rerun the job; add retries if it recurs",
+    ),
+    (
+        "npm-error",
+        None,
+        "Clear cache (npm cache clean --force) and reinstall",
+    ),
+    (
+        "yarn-error",
+        None,
+        "Clear cache (npm cache clean --force) and reinstall",
+    ),
+];
+
+/// An ordered collection of [`Suggestion`]s.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestionRegistry {
+    suggestions: Vec<Suggestion>,
+}
+
+impl SuggestionRegistry {
+    /// Returns a registry holding the built-in suggestions.
+    pub fn builtin() -> Self {
+        let suggestions = BUILTIN_SUGGESTIONS
+            .iter()
+            .map(|(id, when, template)| {
+                Suggestion::new(id, *when, template).expect("built-in suggestions are valid")
+            })
+            .collect();
+
+        SuggestionRegistry { suggestions }
+    }
+
+    /// Adds suggestions that take precedence over the existing ones.
+    pub fn prepend(&mut self, suggestions: impl IntoIterator<Item = Suggestion>) {
+        let mut combined: Vec<Suggestion> = suggestions.into_iter().collect();
+        combined.append(&mut self.suggestions);
+        self.suggestions = combined;
+    }
+
+    /// Returns the first applicable suggestion for a line matched by `pattern`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::patterns::builtin_patterns;
+    /// use circle_debug::suggestions::SuggestionRegistry;
+    ///
+    /// let patterns = builtin_patterns();
+    /// let missing = patterns.iter().find(|p| p.id == "missing-module").unwrap();
+    /// let registry = SuggestionRegistry::builtin();
+    ///
+    /// assert_eq!(
+    ///     registry.suggest(missing, "Error: Cannot find module 'left-pad'").as_deref(),
+    ///     Some("Run 'npm install' or check that 'left-pad' is in package.json dependencies"),
+    /// );
+    /// ```
+    pub fn suggest(&self, pattern: &ErrorPattern, line: &str) -> Option<String> {
+        self.suggestions
+            .iter()
+            .filter(|s| s.pattern_id == pattern.id)
+            .find_map(|s| s.render(pattern, line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::builtin_patterns;
+
+    fn pattern(id: &str) -> ErrorPattern {
+        builtin_patterns().into_iter().find(|p| p.id == id).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_suggestions_use_conditions() {
+        let registry = SuggestionRegistry::builtin();
+        let enoent = pattern("file-not-found");
+
+        assert_eq!(
+            registry
+                .suggest(&enoent, "ENOENT: no such file or directory 'README.md'")
+                .as_deref(),
+            Some("Check file case sensitivity (README.md vs readme.md)")
+        );
+        assert_eq!(
+            registry
+                .suggest(
+                    &enoent,
+                    "ENOENT: no such file or directory, open '/app/x.ts'"
+                )
+                .as_deref(),
+            Some("Verify '/app/x.ts' exists and the path is correct")
+        );
+        assert!(registry
+            .suggest(&pattern("segfault"), "Segmentation fault")
+            .is_none());
+
+        let network = pattern("network-error");
+        assert_eq!(
+            registry
+                .suggest(&network, "Error: getaddrinfo EAI_AGAIN registry.npmjs.org")
+                .as_deref(),
+            Some("DNS lookup for registry.npmjs.org failed - likely transient, rerun the job; if it recurs, check registry.npmjs.org is reachable from CircleCI")
+        );
+    }
+
+    #[test]
+    fn test_prepended_suggestions_win() {
+        let mut registry = SuggestionRegistry::builtin();
+        registry.prepend([Suggestion::new(
+            "npm-error",
+            Some(r"code (?P<code>E\w+)"),
+            "npm failed with {code}; ping #platform-ci ({unknown})",
+        )
+        .unwrap()]);
+
+        let npm = pattern("npm-error");
+        assert_eq!(
+            registry.suggest(&npm, "npm ERR! code ERESOLVE").as_deref(),
+            Some("npm failed with ERESOLVE; ping #platform-ci ({unknown})")
+        );
+        assert_eq!(
+            registry.suggest(&npm, "npm ERR! network").as_deref(),
+            Some("Clear cache (npm cache clean --force) and reinstall")
+        );
+    }
+
+    #[test]
+    fn test_line_placeholder() {
+        let suggestion = Suggestion::new("segfault", None, "Crashed: {line}").unwrap();
+        assert_eq!(
+            suggestion
+                .render(&pattern("segfault"), "  Segmentation fault (core dumped)  ")
+                .as_deref(),
+            Some("Crashed: Segmentation fault (core dumped)")
+        );
+    }
+}
@@ -0,0 +1,519 @@
+//! One-call analysis of a failed build.
+//!
+//! `cdb build` fetches a build, downloads the logs of its failed actions,
+//! cleans them up, and runs smart detection and the test, monorepo,
+//! Gradle/Maven, Docker, benchmark, and phase parsers over them before
+//! rendering anything. [`summarize`] is that analysis on its own: given the build
+//! and its logs, it returns a serializable [`FailureSummary`], so programs
+//! built on the library, such as chat bots, don't have to repeat it.
+//!
+//! Fetching stays with the caller, which keeps this free of network and
+//! disk access:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use circle_debug::CircleClient;
+//! use std::collections::HashMap;
+//!
+//! let client = CircleClient::new()?;
+//! let build = client.get_build("myorg", "myrepo", 12345).await?;
+//! let mut logs = HashMap::new();
+//! for url in build.failed_actions().filter_map(|a| a.output_url.clone()) {
+//!     let log = client.get_logs(&url).await?;
+//!     logs.insert(url, log);
+//! }
+//!
+//! let summary = circle_debug::summarize(&build, &logs);
+//! for step in &summary.failed_steps {
+//!     if let Some(error) = step.errors.first() {
+//!         println!("{}: {} ({})", step.step, error.category, error.first.line.trim());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bench::{self, PerfRegression};
+use crate::docker::{self, DockerFailure};
+use crate::jvm::{self, JvmSummary};
+use crate::logs::{self, TimeBound};
+use crate::monorepo::{self, PackageSummary};
+use crate::patterns::{self, ErrorGroup, ErrorPattern, FailureKind};
+use crate::phases::{self, PhaseTiming};
+use crate::report::{self, FailedAction, StepTiming};
+use crate::repro::{self, FailedTest};
+use crate::{Action, BuildInfo, Step};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// What analyzing a build's failed logs found.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailureSummary {
+    /// Build number.
+    pub build_num: u32,
+    /// Build status (e.g., "failed", "infrastructure_fail").
+    pub status: String,
+    /// Failed actions with their detected errors, failed tests, and
+    /// parser findings, in the order they ran.
+    pub failed_steps: Vec<FailedAction>,
+    /// Kinds of the detected errors, highest ranked first, with
+    /// [`FailureKind::Infra`] added when CircleCI reports an
+    /// infrastructure failure.
+    pub kinds: Vec<FailureKind>,
+    /// Whether every detected error is transient, so a rerun will likely
+    /// pass. `false` when nothing was detected.
+    pub transient: bool,
+    /// Run time per step, longest first.
+    pub step_timings: Vec<StepTiming>,
+    /// Total build time in milliseconds.
+    pub build_time_millis: Option<u64>,
+    /// Commands that rerun the failed tests locally.
+    pub reproduce: Vec<String>,
+}
+
+impl FailureSummary {
+    /// Summarizes `build` from its already analyzed failed actions.
+    pub fn new(build: &BuildInfo, failed_steps: Vec<FailedAction>) -> Self {
+        let mut errors: Vec<&ErrorGroup> = failed_steps
+            .iter()
+            .flat_map(|step| step.errors.iter())
+            .collect();
+        errors.sort_by(|a, b| b.score().total_cmp(&a.score()));
+        let failed_tests: Vec<FailedTest> = failed_steps
+            .iter()
+            .flat_map(|step| step.failed_tests.iter().cloned())
+            .collect();
+
+        FailureSummary {
+            build_num: build.build_num,
+            status: build.status.clone(),
+            kinds: failure_kinds(build, errors.iter().copied()),
+            transient: !errors.is_empty() && errors.iter().all(|group| group.transient),
+            step_timings: report::step_timings(build),
+            build_time_millis: build.build_time_millis,
+            reproduce: repro::reproduce_commands(&failed_tests),
+            failed_steps,
+        }
+    }
+
+    /// Returns every detected error group across all failed steps.
+    pub fn errors(&self) -> impl Iterator<Item = &ErrorGroup> {
+        self.failed_steps.iter().flat_map(|step| step.errors.iter())
+    }
+
+    /// Returns every failed test across all failed steps.
+    pub fn failed_tests(&self) -> impl Iterator<Item = &FailedTest> {
+        self.failed_steps
+            .iter()
+            .flat_map(|step| step.failed_tests.iter())
+    }
+}
+
+/// What the test, monorepo, Gradle/Maven, Docker, benchmark, and phase
+/// parsers find in one cleaned-up log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFindings {
+    /// Packages of a monorepo log, and which of them failed.
+    pub packages: Option<PackageSummary>,
+    /// What Gradle or Maven reported about the failure.
+    pub jvm: Option<JvmSummary>,
+    /// The Dockerfile instruction a `docker build` failed on.
+    pub docker: Option<DockerFailure>,
+    /// Failed tests.
+    pub failed_tests: Vec<FailedTest>,
+    /// Benchmarks reported as slower, most regressed first.
+    pub regressions: Vec<PerfRegression>,
+    /// Time spent per phase, from the log's timestamps.
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl LogFindings {
+    /// Runs every parser over `log`, which should be stripped of ANSI
+    /// codes already.
+    pub fn parse(log: &str) -> Self {
+        LogFindings {
+            packages: monorepo::summarize(log),
+            jvm: jvm::summarize(log),
+            docker: docker::parse_build(log),
+            failed_tests: repro::parse_failed_tests(log),
+            regressions: bench::parse_regressions(log),
+            phases: phases::phase_timings(log),
+        }
+    }
+}
+
+/// How [`analyze_log`] narrows a log down, as `cdb build`'s flags do. The
+/// default collapses noise and keeps every line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogOptions {
+    /// Keep repeated lines and progress output instead of collapsing them.
+    pub no_collapse: bool,
+    /// Only keep the lines of this monorepo package, unless it has none.
+    pub package: Option<String>,
+    /// Drop lines timestamped before this.
+    pub since: Option<TimeBound>,
+    /// Drop lines timestamped after this.
+    pub until: Option<TimeBound>,
+    /// Only keep lines containing this text, unless none do.
+    pub filter: Option<String>,
+    /// Rewrite timestamps as offsets from `start`.
+    pub relative_time: bool,
+    /// When the log started, which `+5m` offsets and relative times count
+    /// from; the log's first timestamp when unset.
+    pub start: Option<DateTime<Utc>>,
+}
+
+/// One log after cleanup and analysis.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogAnalysis {
+    /// The cleaned-up log, narrowed down by the [`LogOptions`].
+    pub text: String,
+    /// Line number in the log as fetched of each line of `text`.
+    pub line_numbers: Vec<usize>,
+    /// Lines removed by noise reduction.
+    pub lines_collapsed: usize,
+    /// Lines cut to [`logs::MAX_LINE_BYTES`].
+    pub lines_truncated: usize,
+    /// Lines replaced because they held binary data.
+    pub binary_lines: usize,
+    /// Lines of the package, out of the total, when
+    /// [`LogOptions::package`] is set. Zero matches means it was ignored.
+    pub package_counts: Option<(usize, usize)>,
+    /// Lines kept by the time window, out of the total, when there is one.
+    pub time_window: Option<(usize, usize)>,
+    /// Lines matching [`LogOptions::filter`], out of the total, when it is
+    /// set. Zero matches means it was ignored.
+    pub filter_counts: Option<(usize, usize)>,
+    /// What the parsers found, in the whole log.
+    pub findings: LogFindings,
+    /// Detected error groups, highest ranked first, numbered by lines of
+    /// the log as fetched.
+    pub errors: Vec<ErrorGroup>,
+}
+
+impl LogAnalysis {
+    /// Returns the entry for a failed action of `step` with what was found.
+    pub fn failed_action(&self, step: &Step, action: &Action) -> FailedAction {
+        FailedAction {
+            errors: self.errors.clone(),
+            packages: self.findings.packages.clone(),
+            jvm: self.findings.jvm.clone(),
+            docker: self.findings.docker.clone(),
+            failed_tests: self.findings.failed_tests.clone(),
+            regressions: self.findings.regressions.clone(),
+            phases: self.findings.phases.clone(),
+            ..FailedAction::new(step, action)
+        }
+    }
+}
+
+/// Analyzes a build's failed actions with the built-in patterns.
+///
+/// `logs` maps an action's `output_url` to its log as fetched, ANSI codes
+/// and all. Failed actions whose log isn't in it are listed with nothing
+/// found.
+pub fn summarize(build: &BuildInfo, logs: &HashMap<String, String>) -> FailureSummary {
+    summarize_with(build, logs, &patterns::builtin_patterns())
+}
+
+/// Like [`summarize`], detecting errors with `patterns`, e.g. the
+/// built-in ones combined with a config file's.
+pub fn summarize_with(
+    build: &BuildInfo,
+    logs: &HashMap<String, String>,
+    patterns: &[ErrorPattern],
+) -> FailureSummary {
+    let failed_steps: Vec<FailedAction> = build
+        .steps
+        .iter()
+        .flat_map(|step| step.actions.iter().map(move |action| (step, action)))
+        .filter(|(_, action)| action.failed.unwrap_or(false))
+        .map(|(step, action)| {
+            let log = action.output_url.as_ref().and_then(|url| logs.get(url));
+            match log {
+                Some(log) => analyze_action(step, action, log, &LogOptions::default(), patterns),
+                None => FailedAction::new(step, action),
+            }
+        })
+        .collect();
+    FailureSummary::new(build, failed_steps)
+}
+
+/// Analyzes one failed action's log the way `cdb build` does; see
+/// [`analyze_log`].
+///
+/// `options.start` defaults to when the action started.
+pub fn analyze_action(
+    step: &Step,
+    action: &Action,
+    log: &str,
+    options: &LogOptions,
+    patterns: &[ErrorPattern],
+) -> FailedAction {
+    let options = LogOptions {
+        start: options.start.or(action.start_time),
+        ..options.clone()
+    };
+    analyze_log(log, &options, patterns).failed_action(step, action)
+}
+
+/// Cleans up and analyzes a log as fetched: strips ANSI codes, collapses
+/// progress output and repeated lines, caps overlong lines, runs the
+/// parsers, narrows the log down by `options`, then runs smart detection
+/// with `patterns`.
+///
+/// The parsers see the whole log, so a package's header still counts when
+/// the time window drops it. Line numbers of the detected errors count
+/// lines of the log as fetched, not of the collapsed or filtered one.
+pub fn analyze_log(log: &str, options: &LogOptions, patterns: &[ErrorPattern]) -> LogAnalysis {
+    let log = logs::strip_ansi(log);
+
+    // Collapse progress animations and repeated lines before analysis,
+    // remembering which line of the log each remaining line was
+    let mut lines_collapsed = 0;
+    let (text, mut line_numbers): (Cow<str>, Vec<usize>) = if options.no_collapse {
+        let line_numbers = (1..=log.lines().count()).collect();
+        (log, line_numbers)
+    } else {
+        let reduced = logs::reduce_noise(&log);
+        lines_collapsed = reduced.lines_removed;
+        (reduced.text.into(), reduced.line_numbers)
+    };
+
+    // Cut megabyte-long lines and binary junk
+    let capped = logs::cap_lines(&text, logs::MAX_LINE_BYTES);
+    let (lines_truncated, binary_lines) = (capped.lines_truncated, capped.binary_lines);
+    let text: Cow<str> = if lines_truncated + binary_lines > 0 {
+        capped.text.into()
+    } else {
+        text
+    };
+
+    // Attribute lines to packages before the time window can drop the
+    // headers some task runners print ahead of a package's output
+    let findings = LogFindings::parse(&text);
+    let mut package_counts = None;
+    let text: Cow<str> = match &options.package {
+        Some(name) => {
+            let mask = monorepo::package_lines(&text, name);
+            let kept = keep_lines(&text, &mask);
+            package_counts = Some((kept.lines().count(), text.lines().count()));
+            if kept.is_empty() {
+                text
+            } else {
+                keep_line_numbers(&mut line_numbers, &mask);
+                kept.into()
+            }
+        }
+        None => text,
+    };
+
+    // Narrow to the time window before text filtering, so lines without
+    // their own timestamp still inherit the one above them
+    let mut time_window = None;
+    let text: Cow<str> = if options.since.is_some() || options.until.is_some() {
+        let mask = logs::lines_in_window(
+            &text,
+            options.start,
+            options.since.as_ref(),
+            options.until.as_ref(),
+        );
+        let windowed = keep_lines(&text, &mask);
+        time_window = Some((windowed.lines().count(), text.lines().count()));
+        keep_line_numbers(&mut line_numbers, &mask);
+        windowed.into()
+    } else {
+        text
+    };
+
+    let mut filter_counts = None;
+    let text: Cow<str> = match &options.filter {
+        Some(filter) => {
+            let mask: Vec<bool> = text.lines().map(|line| line.contains(filter)).collect();
+            let filtered = keep_lines(&text, &mask);
+            filter_counts = Some((filtered.lines().count(), text.lines().count()));
+            if filtered.is_empty() {
+                text
+            } else {
+                keep_line_numbers(&mut line_numbers, &mask);
+                filtered.into()
+            }
+        }
+        None => text,
+    };
+
+    let text: Cow<str> = if options.relative_time {
+        logs::relative_timestamps(&text, options.start).into()
+    } else {
+        text
+    };
+
+    let mut errors = patterns::detect_errors(&text, patterns);
+    for group in &mut errors {
+        group.renumber(&line_numbers);
+    }
+
+    LogAnalysis {
+        text: text.into_owned(),
+        line_numbers,
+        lines_collapsed,
+        lines_truncated,
+        binary_lines,
+        package_counts,
+        time_window,
+        filter_counts,
+        findings,
+        errors,
+    }
+}
+
+/// Returns the lines of `text` whose entry in `mask` is `true`.
+fn keep_lines(text: &str, mask: &[bool]) -> String {
+    text.lines()
+        .zip(mask)
+        .filter(|(_, kept)| **kept)
+        .map(|(line, _)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Drops the line numbers whose entry in `mask` is `false`, keeping them in
+/// step with [`keep_lines`].
+fn keep_line_numbers(line_numbers: &mut Vec<usize>, mask: &[bool]) {
+    let mut mask = mask.iter();
+    line_numbers.retain(|_| mask.next().copied().unwrap_or(true));
+}
+
+/// Returns the distinct kinds of `errors`, in order, with
+/// [`FailureKind::Infra`] added when CircleCI reports `build` as an
+/// infrastructure failure.
+pub fn failure_kinds<'a>(
+    build: &BuildInfo,
+    errors: impl IntoIterator<Item = &'a ErrorGroup>,
+) -> Vec<FailureKind> {
+    let mut kinds: Vec<FailureKind> = Vec::new();
+    for kind in errors.into_iter().map(|group| group.kind) {
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    if build.is_infrastructure_failure() && !kinds.contains(&FailureKind::Infra) {
+        kinds.push(FailureKind::Infra);
+    }
+    kinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_failed_build() {
+        let build: BuildInfo = serde_json::from_value(serde_json::json!({
+            "build_num": 77,
+            "status": "failed",
+            "branch": "main",
+            "subject": null,
+            "build_time_millis": 90000,
+            "steps": [
+                {
+                    "name": "Checkout code",
+                    "actions": [{
+                        "name": "Checkout code", "status": "success", "failed": null,
+                        "output_url": "https://example.com/checkout", "type": "test",
+                        "run_time_millis": 2000
+                    }]
+                },
+                {
+                    "name": "npm test",
+                    "actions": [
+                        {
+                            "name": "npm test", "status": "failed", "failed": true, "index": 0,
+                            "output_url": "https://example.com/test-0", "type": "test",
+                            "run_time_millis": 60000
+                        },
+                        {
+                            "name": "npm test", "status": "failed", "failed": true, "index": 1,
+                            "output_url": "https://example.com/test-1", "type": "test"
+                        }
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+        let logs = HashMap::from([(
+            "https://example.com/test-0".to_string(),
+            "\x1b[31mError: Cannot find module 'lodash'\x1b[0m\nwaiting\nwaiting\nwaiting\n"
+                .to_string(),
+        )]);
+
+        let summary = summarize(&build, &logs);
+        assert_eq!(summary.failed_steps.len(), 2);
+        let first = &summary.failed_steps[0];
+        assert_eq!(first.container, Some(0));
+        assert_eq!(
+            first.errors[0].first.line,
+            "Error: Cannot find module 'lodash'"
+        );
+        // Container 1's log wasn't given
+        assert!(summary.failed_steps[1].errors.is_empty());
+        assert_eq!(summary.kinds, vec![first.errors[0].kind]);
+        assert!(!summary.transient);
+        assert_eq!(summary.step_timings[0].name, "npm test");
+        assert_eq!(summary.step_timings[0].status, "failed");
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["failed_steps"][0]["step"], "npm test");
+    }
+
+    #[test]
+    fn test_analyze_action_counts_lines_before_collapsing() {
+        let step: Step = serde_json::from_value(serde_json::json!({
+            "name": "npm test",
+            "actions": [{
+                "name": "npm test", "status": "failed", "failed": true,
+                "output_url": null, "type": "test"
+            }]
+        }))
+        .unwrap();
+        let log = "Downloading 1/4\nDownloading 2/4\nDownloading 3/4\nDownloading 4/4\n\
+                   Error: Cannot find module 'lodash'\n";
+        let action = analyze_action(
+            &step,
+            &step.actions[0],
+            log,
+            &LogOptions::default(),
+            &patterns::builtin_patterns(),
+        );
+        assert_eq!(action.errors[0].first.line_number, 5);
+        assert_eq!(action.errors[0].line_numbers, vec![5]);
+    }
+
+    #[test]
+    fn test_analyze_log_with_filter() {
+        let log = "start\nwaiting\nwaiting\nwaiting\nnpm ERR! code E404\ndone\n\
+                   npm ERR! 404 Not Found\n";
+        let options = LogOptions {
+            filter: Some("npm".to_string()),
+            ..LogOptions::default()
+        };
+        let analysis = analyze_log(log, &options, &patterns::builtin_patterns());
+        assert_eq!(analysis.text, "npm ERR! code E404\nnpm ERR! 404 Not Found");
+        assert_eq!(analysis.line_numbers, vec![5, 7]);
+        assert_eq!(analysis.lines_collapsed, 2);
+        assert_eq!(analysis.filter_counts, Some((2, 5)));
+        assert_eq!(analysis.errors[0].first.line_number, 5);
+
+        // A filter nothing matches is ignored
+        let options = LogOptions {
+            filter: Some("nothing".to_string()),
+            no_collapse: true,
+            ..LogOptions::default()
+        };
+        let analysis = analyze_log(log, &options, &patterns::builtin_patterns());
+        assert_eq!(analysis.filter_counts, Some((0, 7)));
+        assert_eq!(analysis.line_numbers, (1..=7).collect::<Vec<_>>());
+    }
+}
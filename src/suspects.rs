@@ -0,0 +1,345 @@
+//! Suspect-commit correlation.
+//!
+//! When a build fails, the commits since the previous passing build of the
+//! same job are the likely culprits. Commits touching a file that a
+//! detected error mentions are flagged, since they point straight at the
+//! change that broke it.
+//!
+//! Failing tests get the latest commits touching their files, regardless
+//! of the range, to answer who touched them last.
+
+use crate::codeowners::repo_relative;
+use crate::git::{self, Commit};
+use crate::github::GitHubClient;
+use crate::patterns::ErrorGroup;
+use crate::repro::FailedTest;
+use crate::{BuildInfo, CircleClient};
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Where the commit list came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitSource {
+    /// The local git checkout.
+    Git,
+    /// The GitHub compare API.
+    GitHub,
+}
+
+/// Commits between the previous passing build and the failing one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SuspectCommits {
+    /// Number of the previous passing build.
+    pub base_build: u32,
+    /// Commit built by the previous passing build.
+    pub base_revision: String,
+    /// Commit built by the failing build.
+    pub head_revision: String,
+    /// Where the commits were listed from.
+    pub source: CommitSource,
+    /// Commits in the range, newest first.
+    pub commits: Vec<Suspect>,
+}
+
+impl SuspectCommits {
+    /// Returns the commits touching files named in the errors.
+    pub fn flagged(&self) -> impl Iterator<Item = &Suspect> {
+        self.commits.iter().filter(|s| !s.matched_files.is_empty())
+    }
+}
+
+/// A commit in the suspect range.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Suspect {
+    /// The commit.
+    #[serde(flatten)]
+    pub commit: Commit,
+    /// Files changed by the commit that detected errors mention.
+    pub matched_files: Vec<String>,
+}
+
+/// The latest commits touching a failing test's file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileHistory {
+    /// The test file, as the test runner named it.
+    pub file: String,
+    /// Where the commits were listed from.
+    pub source: CommitSource,
+    /// Up to [`FILE_HISTORY_COMMITS`] commits, newest first.
+    pub commits: Vec<Commit>,
+}
+
+/// How many failing test files get their history looked up.
+pub const MAX_HISTORY_FILES: usize = 10;
+
+/// How many commits are listed per failing test file.
+pub const FILE_HISTORY_COMMITS: usize = 3;
+
+/// How many finished builds of the branch to look back through for the
+/// previous passing build.
+pub const LOOKBACK_BUILDS: u32 = 100;
+
+fn path_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?:[\w.@~-]+/)*[\w@~-][\w.@~-]*\.(?:tsx?|jsx?|mjs|cjs|rs|py|rb|go|java|kt|scala|swift|cc|cpp|c|hpp|h|cs|php|json|ya?ml|toml|s?css|vue|svelte|exs?|sh|sql|gradle)\b",
+        )
+        .unwrap()
+    })
+}
+
+/// Extracts file paths mentioned in a log line.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::suspects::file_paths;
+///
+/// assert_eq!(
+///     file_paths("src/api/client.ts(14,3): error TS2322: Type 'x' is not assignable"),
+///     vec!["src/api/client.ts"]
+/// );
+/// assert!(file_paths("npm ERR! code ELIFECYCLE").is_empty());
+/// ```
+pub fn file_paths(line: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for m in path_regex().find_iter(line) {
+        let path = m.as_str().trim_start_matches("./");
+        if !paths.iter().any(|p| p == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
+/// Returns the file paths mentioned by the first and last line of every
+/// error group, without duplicates.
+pub fn error_files<'a>(groups: impl IntoIterator<Item = &'a ErrorGroup>) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for group in groups {
+        for line in [&group.first.line, &group.last.line] {
+            for path in file_paths(line) {
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Returns `true` if a repository-relative path and a path from a log refer
+/// to the same file.
+///
+/// Logs often show absolute paths from the build container
+/// (`/home/circleci/project/src/a.ts`) or paths relative to a package, so
+/// either may be a suffix of the other at a `/` boundary.
+fn same_file(repo_path: &str, log_path: &str) -> bool {
+    let ends_with = |long: &str, short: &str| {
+        long == short
+            || long
+                .strip_suffix(short)
+                .is_some_and(|prefix| prefix.ends_with('/'))
+    };
+    ends_with(log_path, repo_path) || ends_with(repo_path, log_path)
+}
+
+/// Pairs each commit with the error files it touched.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::git::Commit;
+/// use circle_debug::suspects::correlate;
+///
+/// let commit = Commit {
+///     sha: "abc1234".to_string(),
+///     author: None,
+///     date: None,
+///     subject: "Refactor client".to_string(),
+///     files: vec!["src/api/client.ts".to_string()],
+/// };
+/// let suspects = correlate(vec![commit], &["/home/circleci/project/src/api/client.ts".to_string()]);
+///
+/// assert_eq!(suspects[0].matched_files, vec!["src/api/client.ts"]);
+/// ```
+pub fn correlate(commits: Vec<Commit>, error_files: &[String]) -> Vec<Suspect> {
+    commits
+        .into_iter()
+        .map(|commit| {
+            let matched_files = commit
+                .files
+                .iter()
+                .filter(|file| error_files.iter().any(|e| same_file(file, e)))
+                .cloned()
+                .collect();
+            Suspect {
+                commit,
+                matched_files,
+            }
+        })
+        .collect()
+}
+
+/// Lists the commits between the previous passing build of the same job
+/// and `build`, flagging those that touch files named in `errors`.
+///
+/// Commits come from the local checkout when it has both revisions, and
+/// from the GitHub API otherwise.
+///
+/// # Returns
+///
+/// `None` when the build has no revision or branch, or no passing build
+/// was found within [`LOOKBACK_BUILDS`].
+///
+/// # Errors
+///
+/// Returns an error if the build history or the commits cannot be listed.
+pub async fn find_suspects<'a>(
+    client: &CircleClient,
+    org: &str,
+    project: &str,
+    build: &BuildInfo,
+    errors: impl IntoIterator<Item = &'a ErrorGroup>,
+) -> Result<Option<SuspectCommits>> {
+    let files = error_files(errors);
+    let Some(head) = build.vcs_revision.clone() else {
+        return Ok(None);
+    };
+    let Some(base_build) = client
+        .previous_green(org, project, build, LOOKBACK_BUILDS)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let Some(base) = base_build.vcs_revision.clone() else {
+        return Ok(None);
+    };
+
+    let (source, commits) = if base == head {
+        (CommitSource::Git, Vec::new())
+    } else if git::has_commit(&base) && git::has_commit(&head) {
+        (CommitSource::Git, git::commits_between(&base, &head)?)
+    } else {
+        let commits = GitHubClient::new()
+            .compare(org, project, &base, &head)
+            .await?;
+        (CommitSource::GitHub, commits)
+    };
+
+    Ok(Some(SuspectCommits {
+        base_build: base_build.build_num,
+        base_revision: base,
+        head_revision: head,
+        source,
+        commits: correlate(commits, &files),
+    }))
+}
+
+/// Returns the files of `tests`, repository-relative where the runner
+/// printed a container path, each once and at most
+/// [`MAX_HISTORY_FILES`].
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::repro::parse_failed_tests;
+/// use circle_debug::suspects::test_files;
+///
+/// let tests = parse_failed_tests(
+///     "FAILED tests/test_api.py::test_login - assert 1\n\
+///      FAILED tests/test_api.py::test_logout - assert 2\n",
+/// );
+/// assert_eq!(test_files(&tests), vec!["tests/test_api.py"]);
+/// ```
+pub fn test_files(tests: &[FailedTest]) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for file in tests.iter().filter_map(|test| test.file.as_deref()) {
+        let file = repo_relative(file);
+        if !files.iter().any(|f| f == file) {
+            files.push(file.to_string());
+        }
+    }
+    files.truncate(MAX_HISTORY_FILES);
+    files
+}
+
+/// Lists the latest commits up to `revision` touching each failing test's
+/// file, leaving out files no commit touched.
+///
+/// Commits come from the local checkout when it has `revision`, and from
+/// the GitHub API otherwise.
+///
+/// # Errors
+///
+/// Returns an error if git or GitHub fails for any of the files.
+pub async fn test_file_history(
+    org: &str,
+    project: &str,
+    revision: &str,
+    tests: &[FailedTest],
+) -> Result<Vec<FileHistory>> {
+    let files = test_files(tests);
+    let histories: Vec<(CommitSource, Vec<Commit>)> =
+        if git::has_commit(revision) {
+            files
+                .iter()
+                .map(|file| {
+                    git::file_history(revision, file, FILE_HISTORY_COMMITS)
+                        .map(|commits| (CommitSource::Git, commits))
+                })
+                .collect::<Result<_>>()?
+        } else {
+            let github = GitHubClient::new();
+            futures::future::join_all(files.iter().map(|file| {
+                github.file_commits(org, project, file, revision, FILE_HISTORY_COMMITS)
+            }))
+            .await
+            .into_iter()
+            .map(|commits| commits.map(|commits| (CommitSource::GitHub, commits)))
+            .collect::<Result<_>>()?
+        };
+
+    Ok(files
+        .into_iter()
+        .zip(histories)
+        .filter(|(_, (_, commits))| !commits.is_empty())
+        .map(|(file, (source, commits))| FileHistory {
+            file,
+            source,
+            commits,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_paths() {
+        assert_eq!(
+            file_paths("    at Object.<anonymous> (src/utils/date.test.js:12:5)"),
+            vec!["src/utils/date.test.js"]
+        );
+        assert_eq!(
+            file_paths("ENOENT: no such file or directory, open './config/app.yml'"),
+            vec!["config/app.yml"]
+        );
+        assert_eq!(
+            file_paths("  --> src/main.rs:10:5 and src/main.rs:12:1"),
+            vec!["src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_same_file() {
+        assert!(same_file("src/a.ts", "/home/circleci/project/src/a.ts"));
+        assert!(same_file("packages/web/src/a.ts", "src/a.ts"));
+        assert!(!same_file("src/data.ts", "src/a.ts"));
+    }
+}
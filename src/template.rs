@@ -0,0 +1,108 @@
+//! Custom report templates.
+//!
+//! Renders a [`BuildReport`] through a user-provided
+//! [Handlebars](https://handlebarsjs.com/) template, for teams that need
+//! their own report format (wiki markup, email bodies) without changing
+//! the built-in renderers. The template sees the report exactly as
+//! `--format json` prints it.
+//!
+//! Output is not HTML-escaped. Besides the standard helpers, templates can
+//! use:
+//!
+//! - `{{duration build_time_millis}}` - formats milliseconds as "2m 30s"
+//! - `{{short_sha vcs_revision}}` - the first 7 characters of a SHA
+
+use crate::format_duration;
+use crate::report::BuildReport;
+use anyhow::{Context, Result};
+use handlebars::{handlebars_helper, Handlebars};
+
+const TEMPLATE_NAME: &str = "report";
+
+handlebars_helper!(duration: |millis: u64| format_duration(millis));
+handlebars_helper!(short_sha: |sha: str| sha.get(..7).unwrap_or(sha).to_string());
+
+/// A compiled report template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    registry: Handlebars<'static>,
+}
+
+impl Template {
+    /// Compiles a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template has invalid syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::template::Template;
+    ///
+    /// assert!(Template::new("Build {{build_num}}: {{status}}").is_ok());
+    /// assert!(Template::new("{{#if status}}unclosed").is_err());
+    /// ```
+    pub fn new(source: &str) -> Result<Self> {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_helper("duration", Box::new(duration));
+        registry.register_helper("short_sha", Box::new(short_sha));
+        registry
+            .register_template_string(TEMPLATE_NAME, source)
+            .context("Invalid template")?;
+        Ok(Template { registry })
+    }
+
+    /// Loads and compiles a template file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or has invalid syntax.
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))?;
+        Self::new(&source).with_context(|| format!("Failed to load template {}", path.display()))
+    }
+
+    /// Renders a report.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a helper is called with the wrong arguments.
+    pub fn render(&self, report: &BuildReport) -> Result<String> {
+        self.registry
+            .render(TEMPLATE_NAME, report)
+            .context("Failed to render template")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FailedActionBuilder;
+    use crate::BuildInfo;
+
+    #[test]
+    fn test_render_report() {
+        let build: BuildInfo = serde_json::from_str(
+            r#"{"build_num": 42, "status": "failed", "branch": "main", "subject": "Fix <b>",
+                "steps": [], "build_time_millis": 150000, "vcs_revision": "abcdef1234567"}"#,
+        )
+        .unwrap();
+        let mut report = BuildReport::new("org", "repo", &build);
+        report
+            .failed_actions
+            .push(FailedActionBuilder::new("Run tests").build());
+
+        let template = Template::new(
+            "h2. #{{build_num}} {{subject}} ({{short_sha vcs_revision}}, {{duration build_time_millis}})\n\
+             {{#each failed_actions}}* {{step}}\n{{/each}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&report).unwrap(),
+            "h2. #42 Fix <b> (abcdef1, 2m 30s)\n* Run tests\n"
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Fixtures for testing code built on the library.
+//!
+//! Enabled with the `test-utils` feature. [`BuildBuilder`], [`StepBuilder`],
+//! and [`ActionBuilder`] make API models without spelling out every field,
+//! [`FailedActionBuilder`] does the same for report entries, and
+//! [`FakeCircleCi`] serves them from a local HTTP server that a
+//! [`CircleClient`] can be pointed at.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use anyhow::Result;
+//! # async fn example() -> Result<()> {
+//! use circle_debug::test_utils::{ActionBuilder, BuildBuilder, FakeCircleCi, StepBuilder};
+//!
+//! let server = FakeCircleCi::start().await;
+//! let log_url = server.mount_log("test", "npm ERR! code E404\n").await;
+//! let build = BuildBuilder::new(42)
+//!     .status("failed")
+//!     .step(StepBuilder::new("npm test").action(ActionBuilder::new("npm test").failed().output_url(log_url)))
+//!     .build();
+//! server.mount_build("org", "repo", &build).await;
+//!
+//! let client = server.client();
+//! assert_eq!(client.get_build("org", "repo", 42).await?.status, "failed");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::patterns::{self, ErrorGroup};
+use crate::report::FailedAction;
+use crate::repro::FailedTest;
+use crate::{Action, BuildInfo, CircleClient, Step, WorkflowInfo};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Builds a [`BuildInfo`], starting from a finished, successful build.
+#[derive(Debug, Clone)]
+pub struct BuildBuilder {
+    build: BuildInfo,
+}
+
+impl BuildBuilder {
+    /// Starts a successful build numbered `build_num` on `main`.
+    pub fn new(build_num: u32) -> Self {
+        let mut extra = serde_json::Map::new();
+        extra.insert("lifecycle".to_string(), "finished".into());
+        BuildBuilder {
+            build: BuildInfo {
+                build_num,
+                status: "success".to_string(),
+                branch: Some("main".to_string()),
+                subject: None,
+                steps: Vec::new(),
+                queued_at: None,
+                usage_queued_at: None,
+                start_time: None,
+                stop_time: None,
+                build_time_millis: None,
+                vcs_revision: None,
+                author_name: None,
+                why: None,
+                workflows: None,
+                build_url: None,
+                parallel: None,
+                picard: None,
+                extra,
+            },
+        }
+    }
+
+    /// Sets the build status, e.g. `"failed"` or `"infrastructure_fail"`.
+    pub fn status(mut self, status: &str) -> Self {
+        self.build.status = status.to_string();
+        self
+    }
+
+    /// Sets the branch, or clears it with `None`.
+    pub fn branch(mut self, branch: Option<&str>) -> Self {
+        self.build.branch = branch.map(str::to_string);
+        self
+    }
+
+    /// Sets the commit subject line.
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.build.subject = Some(subject.to_string());
+        self
+    }
+
+    /// Sets the built commit SHA.
+    pub fn revision(mut self, sha: &str) -> Self {
+        self.build.vcs_revision = Some(sha.to_string());
+        self
+    }
+
+    /// Sets the commit author.
+    pub fn author(mut self, name: &str) -> Self {
+        self.build.author_name = Some(name.to_string());
+        self
+    }
+
+    /// Puts the build in a workflow as job `job_name`.
+    pub fn workflow(mut self, workflow_name: &str, job_name: &str) -> Self {
+        self.build.workflows = Some(WorkflowInfo {
+            job_name: Some(job_name.to_string()),
+            workflow_name: Some(workflow_name.to_string()),
+            ..WorkflowInfo::default()
+        });
+        self
+    }
+
+    /// Sets the total build time.
+    pub fn build_time_millis(mut self, millis: u64) -> Self {
+        self.build.build_time_millis = Some(millis);
+        self
+    }
+
+    /// Marks the build as still running.
+    pub fn running(mut self) -> Self {
+        self.build.status = "running".to_string();
+        self.build
+            .extra
+            .insert("lifecycle".to_string(), "running".into());
+        self
+    }
+
+    /// Appends a step.
+    pub fn step(mut self, step: StepBuilder) -> Self {
+        self.build.steps.push(step.build());
+        self
+    }
+
+    pub fn build(self) -> BuildInfo {
+        self.build
+    }
+}
+
+/// Builds a [`Step`].
+#[derive(Debug, Clone)]
+pub struct StepBuilder {
+    step: Step,
+}
+
+impl StepBuilder {
+    /// Starts a step with no actions.
+    pub fn new(name: &str) -> Self {
+        StepBuilder {
+            step: Step {
+                name: name.to_string(),
+                actions: Vec::new(),
+                extra: serde_json::Map::new(),
+            },
+        }
+    }
+
+    /// Appends an action; add one per container for parallel steps.
+    pub fn action(mut self, action: ActionBuilder) -> Self {
+        self.step.actions.push(action.build());
+        self
+    }
+
+    pub fn build(self) -> Step {
+        self.step
+    }
+}
+
+/// Builds an [`Action`], starting from a successful one.
+#[derive(Debug, Clone)]
+pub struct ActionBuilder {
+    action: Action,
+}
+
+impl ActionBuilder {
+    /// Starts a successful `test` action.
+    pub fn new(name: &str) -> Self {
+        ActionBuilder {
+            action: Action {
+                name: name.to_string(),
+                status: "success".to_string(),
+                failed: None,
+                output_url: None,
+                action_type: "test".to_string(),
+                run_time_millis: None,
+                start_time: None,
+                end_time: None,
+                index: None,
+                parallel: None,
+                bash_command: None,
+                extra: serde_json::Map::new(),
+            },
+        }
+    }
+
+    /// Marks the action as failed.
+    pub fn failed(mut self) -> Self {
+        self.action.status = "failed".to_string();
+        self.action.failed = Some(true);
+        self
+    }
+
+    /// Sets the action status without changing `failed`.
+    pub fn status(mut self, status: &str) -> Self {
+        self.action.status = status.to_string();
+        self
+    }
+
+    /// Sets where the action's log is fetched from.
+    pub fn output_url(mut self, url: impl Into<String>) -> Self {
+        self.action.output_url = Some(url.into());
+        self
+    }
+
+    /// Sets the action's run time.
+    pub fn run_time_millis(mut self, millis: u64) -> Self {
+        self.action.run_time_millis = Some(millis);
+        self
+    }
+
+    /// Puts the action on parallel container `index`.
+    pub fn container(mut self, index: u32) -> Self {
+        self.action.index = Some(index);
+        self.action.parallel = Some(true);
+        self
+    }
+
+    /// Sets the shell command the action ran.
+    pub fn bash_command(mut self, command: &str) -> Self {
+        self.action.bash_command = Some(command.to_string());
+        self
+    }
+
+    pub fn build(self) -> Action {
+        self.action
+    }
+}
+
+/// Builds a report's [`FailedAction`], starting from one with nothing found
+/// in its log.
+#[derive(Debug, Clone)]
+pub struct FailedActionBuilder {
+    action: FailedAction,
+}
+
+impl FailedActionBuilder {
+    /// Starts a failed action named like its step, `step`.
+    pub fn new(step: &str) -> Self {
+        FailedActionBuilder {
+            action: FailedAction::new(
+                &StepBuilder::new(step).build(),
+                &ActionBuilder::new(step).failed().build(),
+            ),
+        }
+    }
+
+    /// Puts the action on parallel container `index`.
+    pub fn container(mut self, index: u32) -> Self {
+        self.action.container = Some(index);
+        self
+    }
+
+    /// Sets where the action's log was saved.
+    pub fn log_path(mut self, path: impl Into<String>) -> Self {
+        self.action.log_path = Some(path.into());
+        self
+    }
+
+    /// Sets the detected errors.
+    pub fn errors(mut self, errors: Vec<ErrorGroup>) -> Self {
+        self.action.errors = errors;
+        self
+    }
+
+    /// Sets the detected errors to what the built-in patterns find in
+    /// `log`.
+    pub fn log(self, log: &str) -> Self {
+        self.errors(patterns::detect_errors(log, &patterns::builtin_patterns()))
+    }
+
+    /// Sets the failed tests.
+    pub fn failed_tests(mut self, tests: Vec<FailedTest>) -> Self {
+        self.action.failed_tests = tests;
+        self
+    }
+
+    pub fn build(self) -> FailedAction {
+        self.action
+    }
+}
+
+/// A local HTTP server that answers like the CircleCI API.
+///
+/// Only what's mounted is served; other requests get a `404`. Use
+/// [`server`](Self::server) to mount custom [`wiremock`] mocks.
+pub struct FakeCircleCi {
+    server: MockServer,
+}
+
+impl FakeCircleCi {
+    /// Starts a server on a random local port.
+    pub async fn start() -> Self {
+        FakeCircleCi {
+            server: MockServer::start().await,
+        }
+    }
+
+    /// Returns a client that sends its requests to this server.
+    pub fn client(&self) -> CircleClient {
+        CircleClient::with_token("test-token")
+            .expect("token is not empty")
+            .with_base_url(format!("{}/api", self.server.uri()))
+    }
+
+    /// Returns the underlying mock server.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Serves `build` as build `build.build_num` of `org/project`.
+    pub async fn mount_build(&self, org: &str, project: &str, build: &BuildInfo) {
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/api/v1.1/project/{}/{}/{}/{}",
+                crate::v1_vcs(org),
+                org,
+                project,
+                build.build_num
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(build))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves `builds` as the project's build list, newest first, for
+    /// every branch and query.
+    pub async fn mount_builds(&self, org: &str, project: &str, builds: &[BuildInfo]) {
+        let listed: Vec<BuildInfo> = builds
+            .iter()
+            .cloned()
+            .map(|mut build| {
+                // Build lists omit steps
+                build.steps.clear();
+                build
+            })
+            .collect();
+        let prefix = format!(
+            "/api/v1.1/project/{}/{}/{}",
+            crate::v1_vcs(org),
+            org,
+            project
+        );
+        Mock::given(method("GET"))
+            .and(wiremock::matchers::path_regex(format!(
+                "^{}(/tree/.+)?$",
+                regex::escape(&prefix)
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(listed))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves `files`, as `(path, contents)`, as the artifacts of build
+    /// `build_num`, all from container 0. `HEAD` requests for them are
+    /// answered too.
+    pub async fn mount_artifacts(
+        &self,
+        org: &str,
+        project: &str,
+        build_num: u32,
+        files: &[(&str, &str)],
+    ) {
+        let mut listed = Vec::new();
+        for (artifact_path, contents) in files {
+            let route = format!("/artifacts/{}/0/{}", build_num, artifact_path);
+            for verb in ["GET", "HEAD"] {
+                Mock::given(method(verb))
+                    .and(path(route.as_str()))
+                    .respond_with(ResponseTemplate::new(200).set_body_string(*contents))
+                    .mount(&self.server)
+                    .await;
+            }
+            listed.push(serde_json::json!({
+                "path": artifact_path,
+                "node_index": 0,
+                "url": format!("{}{}", self.server.uri(), route),
+            }));
+        }
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/api/v1.1/project/{}/{}/{}/{}/artifacts",
+                crate::v1_vcs(org),
+                org,
+                project,
+                build_num
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(listed))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves `output` as an action log and returns its URL.
+    pub async fn mount_log(&self, name: &str, output: &str) -> String {
+        let route = format!("/output/{}", name);
+        let body = serde_json::json!([{ "type": "out", "message": output }]);
+        Mock::given(method("GET"))
+            .and(path(route.as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+        format!("{}{}", self.server.uri(), route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_server_serves_builds_and_logs() {
+        let server = FakeCircleCi::start().await;
+        let log_url = server
+            .mount_log("test-0", "npm ERR! code E404\nnpm ERR! 404 Not Found\n")
+            .await;
+        let build = BuildBuilder::new(42)
+            .status("failed")
+            .subject("Bump left-pad")
+            .step(StepBuilder::new("Checkout").action(ActionBuilder::new("Checkout")))
+            .step(
+                StepBuilder::new("npm test")
+                    .action(ActionBuilder::new("npm test").failed().output_url(&log_url)),
+            )
+            .build();
+        server.mount_build("org", "repo", &build).await;
+        server
+            .mount_builds(
+                "org",
+                "repo",
+                &[build.clone(), BuildBuilder::new(41).build()],
+            )
+            .await;
+
+        let client = server.client();
+        let fetched = client.get_build("org", "repo", 42).await.unwrap();
+        assert_eq!(fetched, build);
+        assert!(fetched.is_finished());
+        assert_eq!(fetched.failed_actions().count(), 1);
+
+        let logs = client.get_logs(&log_url).await.unwrap();
+        assert!(logs.contains("npm ERR! code E404"));
+
+        let query = crate::history::BuildQuery {
+            branch: Some("main".to_string()),
+            ..crate::history::BuildQuery::default()
+        };
+        let builds = client.recent_builds("org", "repo", &query).await.unwrap();
+        assert_eq!(builds.len(), 2);
+        assert!(builds[0].steps.is_empty());
+
+        let err = client.get_build("org", "repo", 7).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("404") || format!("{:#}", err).contains("not found"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Colors of the output's semantic roles.
+//!
+//! The `[theme]` table of the config file restyles what `cdb` prints for
+//! errors, warnings, successes, secondary text, and the highlighted first
+//! line of each detected error, e.g. for a light terminal background where
+//! the default white-on-red highlight is hard to read:
+//!
+//! ```toml
+//! [theme]
+//! error = { color = "bright_red" }
+//! highlight = { color = "black", background = "bright_yellow", style = ["bold"] }
+//! ```
+//!
+//! A role that's set replaces its default entirely; roles left out keep
+//! theirs. Colors and styles are those of [`crate::highlight`].
+
+use crate::highlight::{Color, Style};
+use serde::Deserialize;
+
+/// The `[theme]` table: how each role is styled, `None` for the default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    /// Failures and error lines. Defaults to red.
+    pub error: Option<RoleStyle>,
+    /// Warnings, pending states, and section markers. Defaults to yellow.
+    pub warning: Option<RoleStyle>,
+    /// Passing checks and finished work. Defaults to green.
+    pub success: Option<RoleStyle>,
+    /// Secondary details such as line numbers and hints. Defaults to
+    /// dimmed.
+    pub dimmed: Option<RoleStyle>,
+    /// The first line of each detected error. Defaults to bold white on
+    /// red.
+    pub highlight: Option<RoleStyle>,
+}
+
+/// Colors and styles of one role.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoleStyle {
+    /// Text color.
+    pub color: Option<Color>,
+    /// Background color.
+    pub background: Option<Color>,
+    /// Styles, e.g. `["bold", "underline"]`.
+    #[serde(default)]
+    pub style: Vec<Style>,
+}
+
+impl RoleStyle {
+    /// A role style with just a text color.
+    pub fn color(color: Color) -> Self {
+        RoleStyle {
+            color: Some(color),
+            ..Default::default()
+        }
+    }
+}
+
+impl Theme {
+    /// Returns this theme with the roles `other` sets replacing its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::highlight::Color;
+    /// use circle_debug::theme::{RoleStyle, Theme};
+    ///
+    /// let user = Theme {
+    ///     error: Some(RoleStyle::color(Color::BrightRed)),
+    ///     warning: Some(RoleStyle::color(Color::Magenta)),
+    ///     ..Default::default()
+    /// };
+    /// let project = Theme {
+    ///     warning: Some(RoleStyle::color(Color::Blue)),
+    ///     ..Default::default()
+    /// };
+    /// let theme = user.overridden_by(project);
+    /// assert_eq!(theme.error, Some(RoleStyle::color(Color::BrightRed)));
+    /// assert_eq!(theme.warning, Some(RoleStyle::color(Color::Blue)));
+    /// ```
+    pub fn overridden_by(self, other: Theme) -> Theme {
+        Theme {
+            error: other.error.or(self.error),
+            warning: other.warning.or(self.warning),
+            success: other.success.or(self.success),
+            dimmed: other.dimmed.or(self.dimmed),
+            highlight: other.highlight.or(self.highlight),
+        }
+    }
+}
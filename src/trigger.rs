@@ -0,0 +1,185 @@
+//! Why a build ran.
+//!
+//! Collects the v1.1 build's `why`, `user`, `committer_name`,
+//! `pull_requests`, and `retry_of` fields into a [`Trigger`], so a report
+//! can say "push by octocat, PR #123" rather than just "github".
+
+use crate::BuildInfo;
+use serde::{Deserialize, Serialize};
+
+/// What started a build, and who.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trigger {
+    /// What kind of event started the build.
+    pub kind: TriggerKind,
+    /// Login of the user who pushed, rebuilt, or called the API.
+    pub user: Option<String>,
+    /// Name of the commit's committer.
+    pub committer: Option<String>,
+    /// The pull request the commit belongs to.
+    pub pull_request: Option<PullRequest>,
+    /// Build this one reran.
+    pub retry_of: Option<u32>,
+}
+
+/// What kind of event started a build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    /// A push to the repository.
+    Push,
+    /// A scheduled pipeline or scheduled workflow.
+    Scheduled,
+    /// A call to the CircleCI API.
+    Api,
+    /// A rerun from the UI.
+    Retry,
+    /// A rerun with SSH enabled.
+    SshRerun,
+    /// A `why` this library doesn't know, as sent.
+    Other(String),
+}
+
+/// A pull request linked to a build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PullRequest {
+    /// Pull request number, when the URL has one.
+    pub number: Option<u32>,
+    /// Link to the pull request.
+    pub url: String,
+}
+
+impl std::fmt::Display for TriggerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TriggerKind::Push => write!(f, "push"),
+            TriggerKind::Scheduled => write!(f, "scheduled pipeline"),
+            TriggerKind::Api => write!(f, "API"),
+            TriggerKind::Retry => write!(f, "rerun"),
+            TriggerKind::SshRerun => write!(f, "rerun with SSH"),
+            TriggerKind::Other(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+impl BuildInfo {
+    /// Returns why the build ran, or `None` if CircleCI didn't say.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circle_debug::trigger::TriggerKind;
+    /// use circle_debug::BuildInfo;
+    ///
+    /// let build: BuildInfo = serde_json::from_str(
+    ///     r#"{"build_num": 7, "status": "failed", "branch": "fix", "subject": null, "why": "github",
+    ///         "user": {"login": "octocat", "is_user": true},
+    ///         "pull_requests": [{"url": "https://github.com/org/repo/pull/123"}]}"#,
+    /// )
+    /// .unwrap();
+    /// let trigger = build.trigger().unwrap();
+    /// assert_eq!(trigger.kind, TriggerKind::Push);
+    /// assert_eq!(trigger.user.as_deref(), Some("octocat"));
+    /// assert_eq!(trigger.pull_request.unwrap().number, Some(123));
+    /// ```
+    pub fn trigger(&self) -> Option<Trigger> {
+        let why = self.why.as_deref()?;
+        let kind = match why {
+            "github" | "bitbucket" | "gitlab" | "push" => TriggerKind::Push,
+            "scheduled_pipeline" | "schedule" | "scheduled" => TriggerKind::Scheduled,
+            "api" | "trigger" => TriggerKind::Api,
+            "retry" => TriggerKind::Retry,
+            "ssh" => TriggerKind::SshRerun,
+            other => TriggerKind::Other(other.to_string()),
+        };
+        let string = |key: &str| {
+            self.extra
+                .get(key)
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+
+        Some(Trigger {
+            kind,
+            user: self
+                .extra
+                .get("user")
+                .and_then(|user| user.get("login"))
+                .and_then(|login| login.as_str())
+                .map(str::to_string),
+            committer: string("committer_name"),
+            pull_request: self
+                .extra
+                .get("pull_requests")
+                .and_then(|prs| prs.as_array())
+                .and_then(|prs| prs.first())
+                .and_then(|pr| pr.get("url"))
+                .and_then(|url| url.as_str())
+                .map(|url| PullRequest {
+                    number: pull_request_number(url),
+                    url: url.to_string(),
+                }),
+            retry_of: self
+                .extra
+                .get("retry_of")
+                .and_then(|v| v.as_u64())
+                .and_then(|n| u32::try_from(n).ok()),
+        })
+    }
+}
+
+/// Parses the number out of a GitHub `/pull/123`, Bitbucket
+/// `/pull-requests/123`, or GitLab `/merge_requests/123` URL.
+fn pull_request_number(url: &str) -> Option<u32> {
+    let mut segments = url.trim_end_matches('/').rsplit('/');
+    let number = segments.next()?.parse().ok()?;
+    matches!(
+        segments.next()?,
+        "pull" | "pull-requests" | "merge_requests"
+    )
+    .then_some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::BuildBuilder;
+
+    #[test]
+    fn test_trigger_kinds() {
+        let mut build = BuildBuilder::new(8).build();
+        assert_eq!(build.trigger(), None);
+
+        build.why = Some("retry".to_string());
+        build.extra.insert("retry_of".to_string(), 7.into());
+        build
+            .extra
+            .insert("committer_name".to_string(), "GitHub".into());
+        let trigger = build.trigger().unwrap();
+        assert_eq!(trigger.kind, TriggerKind::Retry);
+        assert_eq!(trigger.retry_of, Some(7));
+        assert_eq!(trigger.committer.as_deref(), Some("GitHub"));
+        assert_eq!(trigger.pull_request, None);
+
+        build.why = Some("edit".to_string());
+        assert_eq!(
+            build.trigger().unwrap().kind,
+            TriggerKind::Other("edit".to_string())
+        );
+        assert_eq!(TriggerKind::SshRerun.to_string(), "rerun with SSH");
+    }
+
+    #[test]
+    fn test_pull_request_number() {
+        assert_eq!(
+            pull_request_number("https://github.com/org/repo/pull/123"),
+            Some(123)
+        );
+        assert_eq!(
+            pull_request_number("https://bitbucket.org/org/repo/pull-requests/9/"),
+            Some(9)
+        );
+        assert_eq!(pull_request_number("https://github.com/org/repo/123"), None);
+    }
+}
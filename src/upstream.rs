@@ -0,0 +1,194 @@
+//! Failures that started in an earlier job of the workflow.
+//!
+//! A job that attaches a workspace fails in "Attaching Workspace" when the
+//! job that should have persisted it didn't, and a job that lists `failed`
+//! among the terminal statuses it `requires` runs after its dependency
+//! failed, and usually fails for the same reason. Either way the logs worth
+//! reading are in the upstream job. [`find_upstream`] walks the workflow's
+//! dependency graph back to the first job that failed.
+
+use crate::workflow::WorkflowJob;
+use crate::BuildInfo;
+use serde::{Deserialize, Serialize};
+
+/// Workflow job statuses that mean the job itself failed.
+const FAILED_STATUSES: &[&str] = &["failed", "infrastructure_fail", "timedout"];
+
+/// The earlier job a failure comes from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamFailure {
+    /// Name of the upstream job.
+    pub job: String,
+    /// Its build number, for analyzing it.
+    pub job_number: Option<u32>,
+    /// Its status, e.g. `failed`, or `success` for a job that didn't
+    /// persist the workspace.
+    pub status: String,
+    /// How the analyzed job depends on it.
+    pub reason: UpstreamReason,
+}
+
+/// How a failure traces back to an upstream job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamReason {
+    /// The job's "Attaching Workspace" step failed.
+    WorkspaceAttach,
+    /// A job this one requires failed.
+    DependencyFailed,
+}
+
+/// Finds the upstream job `build` failed because of.
+///
+/// `jobs` are the jobs of the build's workflow, from
+/// [`CircleClient::get_workflow_jobs`](crate::CircleClient::get_workflow_jobs).
+///
+/// # Returns
+///
+/// The first failed job among the build's transitive dependencies, or for a
+/// failed workspace attach with no failed dependency, the job it directly
+/// requires. `None` if the build didn't fail because of another job.
+///
+/// # Examples
+///
+/// ```
+/// use circle_debug::upstream::{find_upstream, UpstreamReason};
+/// use circle_debug::workflow::WorkflowJob;
+/// use circle_debug::BuildInfo;
+///
+/// let build: BuildInfo = serde_json::from_str(
+///     r#"{"build_num": 12, "status": "failed", "branch": "main", "subject": null,
+///         "steps": [{"name": "Attaching Workspace", "actions": [
+///             {"name": "Attaching Workspace", "status": "failed", "failed": true, "type": "test"}
+///         ]}]}"#,
+/// )
+/// .unwrap();
+/// let jobs: Vec<WorkflowJob> = serde_json::from_str(
+///     r#"[{"id": "a", "name": "build", "job_number": 11, "status": "failed", "type": "build",
+///          "started_at": null, "stopped_at": null},
+///         {"id": "b", "name": "test", "job_number": 12, "status": "failed", "type": "build",
+///          "started_at": null, "stopped_at": null, "dependencies": ["a"]}]"#,
+/// )
+/// .unwrap();
+/// let upstream = find_upstream(&build, &jobs).unwrap();
+/// assert_eq!(upstream.job, "build");
+/// assert_eq!(upstream.job_number, Some(11));
+/// assert_eq!(upstream.reason, UpstreamReason::WorkspaceAttach);
+/// ```
+pub fn find_upstream(build: &BuildInfo, jobs: &[WorkflowJob]) -> Option<UpstreamFailure> {
+    if !FAILED_STATUSES.contains(&build.status.as_str()) {
+        return None;
+    }
+    let this = jobs
+        .iter()
+        .find(|job| job.job_number == Some(build.build_num))?;
+    let attach_failed = build.steps.iter().any(|step| {
+        step.name.to_lowercase().starts_with("attaching workspace") && step.has_failures()
+    });
+    let reason = if attach_failed {
+        UpstreamReason::WorkspaceAttach
+    } else {
+        UpstreamReason::DependencyFailed
+    };
+
+    let failed = root_failure(this, jobs, &mut Vec::new());
+    let job = match failed {
+        Some(job) => job,
+        None if attach_failed => this
+            .dependencies
+            .iter()
+            .find_map(|id| jobs.iter().find(|job| &job.id == id))?,
+        None => return None,
+    };
+    Some(UpstreamFailure {
+        job: job.name.clone(),
+        job_number: job.job_number,
+        status: job.status.clone(),
+        reason,
+    })
+}
+
+/// Finds the earliest failed job among `job`'s dependencies: a failed job
+/// none of whose own dependencies failed.
+fn root_failure<'a>(
+    job: &'a WorkflowJob,
+    jobs: &'a [WorkflowJob],
+    seen: &mut Vec<&'a str>,
+) -> Option<&'a WorkflowJob> {
+    for id in &job.dependencies {
+        if seen.contains(&id.as_str()) {
+            continue;
+        }
+        seen.push(id);
+        let Some(dependency) = jobs.iter().find(|candidate| &candidate.id == id) else {
+            continue;
+        };
+        if let Some(root) = root_failure(dependency, jobs, seen) {
+            return Some(root);
+        }
+        if FAILED_STATUSES.contains(&dependency.status.as_str()) {
+            return Some(dependency);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ActionBuilder, BuildBuilder, StepBuilder};
+
+    fn job(id: &str, number: u32, status: &str, dependencies: &[&str]) -> WorkflowJob {
+        WorkflowJob {
+            id: id.to_string(),
+            name: id.to_string(),
+            job_number: Some(number),
+            status: status.to_string(),
+            job_type: "build".to_string(),
+            started_at: None,
+            stopped_at: None,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            project_slug: None,
+        }
+    }
+
+    #[test]
+    fn test_find_upstream_walks_to_first_failure() {
+        let jobs = vec![
+            job("checkout", 1, "success", &[]),
+            job("build", 2, "failed", &["checkout"]),
+            job("package", 3, "failed", &["build"]),
+            job("deploy", 4, "failed", &["package"]),
+        ];
+        let build = BuildBuilder::new(4)
+            .status("failed")
+            .step(StepBuilder::new("Deploy").action(ActionBuilder::new("Deploy").failed()))
+            .build();
+        let upstream = find_upstream(&build, &jobs).unwrap();
+        assert_eq!(upstream.job, "build");
+        assert_eq!(upstream.reason, UpstreamReason::DependencyFailed);
+
+        // Its own failure, not an upstream one
+        let build = BuildBuilder::new(2).status("failed").build();
+        assert_eq!(find_upstream(&build, &jobs), None);
+    }
+
+    #[test]
+    fn test_find_upstream_workspace_not_persisted() {
+        let jobs = vec![
+            job("build", 1, "success", &[]),
+            job("test", 2, "failed", &["build"]),
+        ];
+        let build = BuildBuilder::new(2)
+            .status("failed")
+            .step(
+                StepBuilder::new("Attaching Workspace")
+                    .action(ActionBuilder::new("Attaching Workspace").failed()),
+            )
+            .build();
+        let upstream = find_upstream(&build, &jobs).unwrap();
+        assert_eq!(upstream.job, "build");
+        assert_eq!(upstream.status, "success");
+        assert_eq!(upstream.reason, UpstreamReason::WorkspaceAttach);
+    }
+}
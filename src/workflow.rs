@@ -0,0 +1,200 @@
+//! Workflow jobs.
+//!
+//! A v1.1 build is a single job. Builds run by a workflow only link to it
+//! by id, so the v2 API is used to list the sibling jobs, for example to
+//! show how long each of them sat in the queue and what it cost.
+
+use crate::cost::{self, CostEstimate};
+use crate::{BuildInfo, CircleClient};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// A job within a workflow, as returned by the v2 API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorkflowJob {
+    /// UUID of the job.
+    pub id: String,
+    /// Name of the job in the workflow config.
+    pub name: String,
+    /// Build number, usable with [`CircleClient::get_build`]. Approval
+    /// jobs don't have one.
+    pub job_number: Option<u32>,
+    /// Job status (e.g., "success", "failed", "blocked").
+    pub status: String,
+    /// Job type, `"build"` or `"approval"`.
+    #[serde(rename = "type")]
+    pub job_type: String,
+    /// When the job started running.
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the job finished.
+    pub stopped_at: Option<DateTime<Utc>>,
+    /// Ids of the jobs this one `requires`.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Slug of the job's project, e.g. `gh/org/repo` or
+    /// `circleci/<org-id>/<project-id>`.
+    #[serde(default)]
+    pub project_slug: Option<String>,
+}
+
+/// One page of a v2 list endpoint.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Page<T> {
+    pub(crate) items: Vec<T>,
+    pub(crate) next_page_token: Option<String>,
+}
+
+/// Queue time, run time, and cost of one job in a workflow.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobSummary {
+    /// Job name.
+    pub name: String,
+    /// Build number of the job.
+    pub job_number: u32,
+    /// Milliseconds between being queued and starting.
+    pub queued_millis: Option<u64>,
+    /// Milliseconds spent running.
+    pub ran_millis: Option<u64>,
+    /// Estimated credit cost.
+    pub cost: Option<CostEstimate>,
+}
+
+impl JobSummary {
+    /// Summarizes a job from its v1.1 build details.
+    pub fn from_build(name: &str, build: &BuildInfo) -> Self {
+        JobSummary {
+            name: name.to_string(),
+            job_number: build.build_num,
+            queued_millis: build.queue_duration().map(|d| d.as_millis() as u64),
+            ran_millis: build.build_time_millis,
+            cost: cost::estimate(build),
+        }
+    }
+
+    /// A job whose build details couldn't be loaded.
+    pub fn unknown(name: &str, job_number: u32) -> Self {
+        JobSummary {
+            name: name.to_string(),
+            job_number,
+            queued_millis: None,
+            ran_millis: None,
+            cost: None,
+        }
+    }
+}
+
+impl CircleClient {
+    /// Lists every job of a workflow, following pagination.
+    ///
+    /// # Arguments
+    ///
+    /// * `workflow_id` - The workflow UUID, e.g. from [`BuildInfo::workflows`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails or a response cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # async fn example() -> Result<()> {
+    /// use circle_debug::CircleClient;
+    ///
+    /// let client = CircleClient::new()?;
+    /// for job in client.get_workflow_jobs("5034460f-c7c4-4c43-9457-de07e2029e7b").await? {
+    ///     println!("{}: {}", job.name, job.status);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_workflow_jobs(&self, workflow_id: &str) -> Result<Vec<WorkflowJob>> {
+        let base = format!("{}/v2/workflow/{}/job", self.base_url, workflow_id);
+        let mut jobs = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut url = Url::parse(&base)?;
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("page-token", token);
+            }
+            let page: Page<WorkflowJob> = self.get_json(url.as_str()).await?;
+            jobs.extend(page.items);
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    /// Summarizes queue time, run time, and cost for every job of a workflow.
+    ///
+    /// Lists the jobs with the v2 API, then fetches each job's build
+    /// details concurrently. Approval jobs are skipped; jobs whose details
+    /// fail to load are returned with [`JobSummary::unknown`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow's jobs cannot be listed.
+    pub async fn get_job_summaries(
+        &self,
+        org: &str,
+        project: &str,
+        workflow_id: &str,
+    ) -> Result<Vec<JobSummary>> {
+        let jobs: Vec<(String, u32)> = self
+            .get_workflow_jobs(workflow_id)
+            .await?
+            .into_iter()
+            .filter_map(|job| job.job_number.map(|number| (job.name, number)))
+            .collect();
+
+        let builds = futures::future::join_all(
+            jobs.iter()
+                .map(|(_, number)| self.get_build(org, project, *number)),
+        )
+        .await;
+
+        Ok(jobs
+            .iter()
+            .zip(builds)
+            .map(|((name, number), build)| match build {
+                Ok(build) => JobSummary::from_build(name, &build),
+                Err(_) => JobSummary::unknown(name, *number),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workflow_jobs_page() {
+        let page: Page<WorkflowJob> = serde_json::from_str(
+            r#"{
+                "items": [
+                    {"id": "a", "name": "build", "job_number": 101, "status": "success",
+                     "type": "build", "started_at": "2024-05-01T12:00:00Z",
+                     "stopped_at": "2024-05-01T12:05:00Z", "dependencies": []},
+                    {"id": "b", "name": "hold", "status": "on_hold", "type": "approval",
+                     "started_at": null, "stopped_at": null}
+                ],
+                "next_page_token": null
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].job_number, Some(101));
+        assert_eq!(page.items[1].job_number, None);
+        assert!(page.items[1].dependencies.is_empty());
+        assert!(page.next_page_token.is_none());
+    }
+}